@@ -8,6 +8,7 @@ criterion_group!(benches, once_event);
 criterion_main!(benches);
 
 const CANARY: usize = 0x356789111aaaa;
+const BATCH_SIZE: usize = 1000;
 
 fn once_event(c: &mut Criterion) {
     let mut group = c.benchmark_group("once_event");
@@ -123,5 +124,23 @@ fn once_event(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("ref_create_1000_one_by_one", |b| {
+        b.iter(|| {
+            let events = (0..BATCH_SIZE)
+                .map(|_| OnceEvent::new_in_ref(&ref_storage))
+                .collect::<Vec<_>>();
+
+            assert_eq!(events.len(), BATCH_SIZE);
+        });
+    });
+
+    group.bench_function("ref_create_1000_many", |b| {
+        b.iter(|| {
+            let events = OnceEvent::new_many_in_ref(&ref_storage, BATCH_SIZE);
+
+            assert_eq!(events.len(), BATCH_SIZE);
+        });
+    });
+
     group.finish();
 }
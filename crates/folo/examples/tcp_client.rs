@@ -0,0 +1,43 @@
+use folo::{
+    io::{self, OperationResultExt},
+    net::{ConnectOptions, TcpConnection},
+};
+use std::{error::Error, time::Duration};
+use tracing::{event, Level};
+
+/// Connects to the `tcp_echo` example server, sends a message and prints the echoed response.
+/// Start the echo server first.
+#[folo::main(print_metrics)]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    tracing_subscriber::fmt::init();
+
+    let mut connection = TcpConnection::connect_with(
+        "127.0.0.1:1234".parse()?,
+        ConnectOptions::new()
+            .timeout(Duration::from_secs(5))
+            .nodelay(true),
+    )
+    .await?;
+
+    event!(Level::INFO, "connected to echo server");
+
+    let message = b"Hello, Folo!";
+
+    connection
+        .send(io::PinnedBuffer::from_boxed_slice(Box::new(*message)))
+        .await
+        .into_inner()?;
+
+    let response = connection
+        .receive(io::PinnedBuffer::from_pool())
+        .await
+        .into_inner()?;
+
+    event!(
+        Level::INFO,
+        message = "received echo",
+        response = String::from_utf8_lossy(response.as_slice()).to_string()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,387 @@
+//! A `tracing` layer that writes events to the Windows Event Log or to an ETW provider, for
+//! services that must integrate with Windows operational tooling. Enabled by the `event-log`
+//! feature.
+//!
+//! Writing to either destination is a synchronous syscall, so the layer only formats each event
+//! and queues it, with a dedicated background thread writing the queued events in batches. This
+//! keeps the cost of logging on async worker threads low and predictable.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use folo::event_log::{EventLogSinkBuilder, EventLogTarget};
+//! use tracing_subscriber::prelude::*;
+//!
+//! let (layer, _guard) = EventLogSinkBuilder::new(EventLogTarget::EventLog {
+//!     source: "Contoso Service".to_string(),
+//! })
+//! .max_level(tracing::Level::WARN)
+//! .build()
+//! .unwrap();
+//!
+//! tracing_subscriber::registry().with(layer).init();
+//!
+//! // Keep the guard alive until shutdown - dropping it flushes the queued events.
+//! ```
+
+use crate::io;
+use crossbeam::channel;
+use std::{
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+use windows::{
+    core::{GUID, PCWSTR},
+    Win32::{
+        Foundation::{HANDLE, WIN32_ERROR},
+        System::{
+            Diagnostics::Etw::{EventRegister, EventUnregister, EventWriteString, REGHANDLE},
+            EventLog::{
+                DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+                EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+            },
+        },
+    },
+};
+
+/// Where an event log sink writes its events.
+#[derive(Clone, Debug)]
+pub enum EventLogTarget {
+    /// The Application log of the Windows Event Log, under the specified event source name.
+    ///
+    /// The events are written with event ID 0 and the formatted event as the only insertion
+    /// string. Unless the source is registered with a message file that maps this to "%1", Event
+    /// Viewer will prefix the text with a note that the event description cannot be found.
+    EventLog { source: String },
+
+    /// The ETW provider with the specified GUID, as string events (`EventWriteString`). These are
+    /// only recorded while a trace session has enabled the provider.
+    Etw { provider: GUID },
+}
+
+/// Builds an event log sink, consisting of a `tracing` layer and a guard that owns the background
+/// thread writing the events.
+#[derive(Debug)]
+pub struct EventLogSinkBuilder {
+    target: EventLogTarget,
+    max_level: Level,
+    queue_capacity: usize,
+}
+
+impl EventLogSinkBuilder {
+    pub fn new(target: EventLogTarget) -> Self {
+        Self {
+            target,
+            max_level: Level::INFO,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+        }
+    }
+
+    /// The most verbose level of events to write. Defaults to `INFO`.
+    pub fn max_level(mut self, value: Level) -> Self {
+        self.max_level = value;
+        self
+    }
+
+    /// How many events may be queued for writing. If the background thread falls behind and the
+    /// queue is full, new events are dropped (and the number of dropped events is written once the
+    /// thread catches up), so logging never blocks the thread emitting the event.
+    pub fn queue_capacity(mut self, value: usize) -> Self {
+        self.queue_capacity = value;
+        self
+    }
+
+    /// Opens the target and starts the background thread. The returned guard must be kept alive
+    /// for as long as events should be written - dropping it writes any queued events and stops
+    /// the background thread.
+    pub fn build(self) -> io::Result<(EventLogLayer, EventLogGuard)> {
+        if self.queue_capacity == 0 {
+            return Err(io::Error::InvalidOptions(
+                "event log queue capacity must be at least 1".to_string(),
+            ));
+        }
+
+        let writer = Writer::open(&self.target)?;
+
+        let (tx, rx) = channel::bounded(self.queue_capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let join_handle = thread::Builder::new()
+            .name("folo-event-log".to_string())
+            .spawn({
+                let dropped = Arc::clone(&dropped);
+                move || writer.run(&rx, &dropped)
+            })?;
+
+        Ok((
+            EventLogLayer {
+                tx: tx.clone(),
+                max_level: self.max_level,
+                dropped,
+            },
+            EventLogGuard {
+                tx,
+                join_handle: Some(join_handle),
+            },
+        ))
+    }
+}
+
+/// Maximum number of events we write in one go before checking whether any events were dropped.
+const BATCH_SIZE: usize = 128;
+
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// A `tracing` layer that queues events for the background thread of an event log sink.
+#[derive(Debug)]
+pub struct EventLogLayer {
+    tx: channel::Sender<Message>,
+    max_level: Level,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S> Layer<S> for EventLogLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        // More verbose levels compare as greater.
+        if *metadata.level() > self.max_level {
+            return;
+        }
+
+        let mut formatter = EventFormatter::default();
+        event.record(&mut formatter);
+
+        let record = Record {
+            level: *metadata.level(),
+            text: formatter.finish(metadata.target()),
+        };
+
+        if self.tx.try_send(Message::Record(record)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Keeps the background thread of an event log sink running. Dropping this writes any queued
+/// events and stops the thread, after which events given to the layer are dropped.
+#[derive(Debug)]
+pub struct EventLogGuard {
+    tx: channel::Sender<Message>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for EventLogGuard {
+    fn drop(&mut self) {
+        // This is queued after any events already in the queue, so those get written first. We
+        // wait for room in the queue because we must not lose the shutdown signal.
+        _ = self.tx.send(Message::Shutdown);
+
+        if let Some(join_handle) = self.join_handle.take() {
+            _ = join_handle.join();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Message {
+    Record(Record),
+    Shutdown,
+}
+
+#[derive(Debug)]
+struct Record {
+    level: Level,
+    text: String,
+}
+
+/// Formats an event as "target: message field1=value1 field2=value2".
+#[derive(Default)]
+struct EventFormatter {
+    message: String,
+    fields: String,
+}
+
+impl EventFormatter {
+    fn finish(self, target: &str) -> String {
+        format!("{target}: {}{}", self.message, self.fields)
+    }
+}
+
+impl tracing::field::Visit for EventFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            _ = write!(self.message, "{value:?}");
+        } else {
+            _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// Owns the open event log target. Lives on the background thread.
+enum Writer {
+    EventLog(HANDLE),
+    Etw(REGHANDLE),
+}
+
+// SAFETY: Both handle types may be used from any thread. The writer is only ever used by one
+// thread at a time (created on the builder thread, then moved to the background thread).
+unsafe impl Send for Writer {}
+
+impl Writer {
+    fn open(target: &EventLogTarget) -> io::Result<Self> {
+        match target {
+            EventLogTarget::EventLog { source } => {
+                let source = to_wide(source);
+
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                let handle = unsafe {
+                    RegisterEventSourceW(PCWSTR::null(), PCWSTR::from_raw(source.as_ptr()))?
+                };
+
+                Ok(Self::EventLog(handle))
+            }
+            EventLogTarget::Etw { provider } => {
+                let mut handle = 0;
+
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                let result = unsafe { EventRegister(provider, None, None, &mut handle) };
+
+                WIN32_ERROR(result).ok()?;
+
+                Ok(Self::Etw(REGHANDLE(handle as i64)))
+            }
+        }
+    }
+
+    fn run(self, rx: &channel::Receiver<Message>, dropped: &AtomicU64) {
+        // We block until something arrives, then write everything that has piled up meanwhile.
+        while let Ok(first) = rx.recv() {
+            let mut message = Some(first);
+            let mut written = 0;
+
+            while let Some(current) = message.take() {
+                match current {
+                    Message::Record(record) => self.write(&record),
+                    Message::Shutdown => {
+                        self.report_dropped(dropped);
+                        return;
+                    }
+                }
+
+                written += 1;
+
+                if written < BATCH_SIZE {
+                    message = rx.try_recv().ok();
+                }
+            }
+
+            self.report_dropped(dropped);
+        }
+    }
+
+    fn report_dropped(&self, dropped: &AtomicU64) {
+        let count = dropped.swap(0, Ordering::Relaxed);
+
+        if count == 0 {
+            return;
+        }
+
+        self.write(&Record {
+            level: Level::WARN,
+            text: format!(
+                "folo::event_log: {count} events were dropped because the event log queue was full"
+            ),
+        });
+    }
+
+    fn write(&self, record: &Record) {
+        let text = to_wide(&record.text);
+
+        // Failures are ignored because there is nowhere else to report them. Logging them via
+        // `tracing` would feed them right back into this sink.
+        match self {
+            Self::EventLog(handle) => {
+                let event_type = match record.level {
+                    Level::ERROR => EVENTLOG_ERROR_TYPE,
+                    Level::WARN => EVENTLOG_WARNING_TYPE,
+                    _ => EVENTLOG_INFORMATION_TYPE,
+                };
+
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                _ = unsafe { report_event(*handle, event_type, &text) };
+            }
+            Self::Etw(handle) => {
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                _ = unsafe {
+                    EventWriteString(
+                        *handle,
+                        etw_level(record.level),
+                        0,
+                        PCWSTR::from_raw(text.as_ptr()),
+                    )
+                };
+            }
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // SAFETY: The handles are valid because we opened them and only close them here.
+        unsafe {
+            match self {
+                Self::EventLog(handle) => _ = DeregisterEventSource(*handle),
+                Self::Etw(handle) => _ = EventUnregister(*handle),
+            }
+        }
+    }
+}
+
+unsafe fn report_event(
+    handle: HANDLE,
+    event_type: REPORT_EVENT_TYPE,
+    text: &[u16],
+) -> windows::core::Result<()> {
+    ReportEventW(
+        handle,
+        event_type,
+        0,
+        0,
+        None,
+        0,
+        Some(&[PCWSTR::from_raw(text.as_ptr())]),
+        None,
+    )
+}
+
+/// Maps a `tracing` level to the corresponding ETW level (TRACE_LEVEL_* constants).
+fn etw_level(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 2,
+        Level::WARN => 3,
+        Level::INFO => 4,
+        _ => 5,
+    }
+}
+
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
@@ -1,3 +1,13 @@
+mod file;
 mod functions;
+mod read_dir;
+mod volume;
+mod walk;
+mod writer;
 
+pub use file::*;
 pub use functions::*;
+pub use read_dir::*;
+pub use volume::*;
+pub use walk::*;
+pub use writer::*;
@@ -0,0 +1,245 @@
+use crate::{
+    fs::path_to_cstring,
+    io::{self, transfer_in_chunks, OperationKind, OperationResult, PinnedBuffer},
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    util::{OwnedHandle, ThreadSafe},
+};
+use futures::stream::{FuturesOrdered, StreamExt};
+use negative_impl::negative_impl;
+use std::{future::Future, num::NonZeroUsize, path::Path, rc::Rc};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{ERROR_HANDLE_EOF, HANDLE, STATUS_END_OF_FILE},
+        Storage::FileSystem::{
+            CreateFileA, GetFileSizeEx, ReadFile, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ,
+            FILE_SHARE_READ, OPEN_EXISTING,
+        },
+    },
+};
+
+/// A file opened for asynchronous positional reads, bound to the current async worker thread.
+pub struct File {
+    // Shared with any read-ahead pipelines, which may outlive the File itself.
+    handle: Rc<OwnedHandle<HANDLE>>,
+}
+
+impl File {
+    /// Opens an existing file for reading.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path_cstr = path_to_cstring(path.as_ref())?;
+
+        // Opening a file is a blocking operation, so we kick it off to a synchronous worker thread
+        // to avoid blocking the async workers with this potentially slow call.
+        let handle = spawn_sync(SynchronousTaskType::FileSystem, move || -> io::Result<_> {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    FILE_GENERIC_READ.0,
+                    FILE_SHARE_READ,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self {
+            handle: Rc::new(handle),
+        })
+    }
+
+    /// The current size of the file, in bytes.
+    pub async fn len(&self) -> io::Result<u64> {
+        // SAFETY: File handles can be used from any thread and we keep the handle open until the
+        // synchronous task has completed because we wait for it while borrowing self.
+        let handle = unsafe { ThreadSafe::new(**self.handle) };
+
+        spawn_sync(
+            SynchronousTaskType::FileSystem,
+            move || -> io::Result<u64> {
+                let mut size: i64 = 0;
+
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                unsafe { GetFileSizeEx(*handle, &mut size as *mut _)? };
+
+                Ok(size as u64)
+            },
+        )
+        .await
+    }
+
+    /// Reads from the file at the specified offset into the active region of the buffer.
+    ///
+    /// The buffer will be returned in the result with the active region set to the bytes read, with
+    /// a length of 0 if the offset is at or beyond the end of the file. Buffers longer than
+    /// `io::MAX_OPERATION_LEN` are read via multiple sequential operations.
+    pub async fn read_at(&self, offset: u64, buffer: PinnedBuffer) -> OperationResult {
+        transfer_in_chunks(buffer, |buffer, chunk_offset| {
+            read_at(
+                Rc::clone(&self.handle),
+                offset + chunk_offset as u64,
+                buffer,
+            )
+        })
+        .await
+    }
+
+    /// Starts a read-ahead pipeline that reads the file sequentially from the beginning, keeping up
+    /// to `window` reads in flight ahead of the consumer. This saturates the bandwidth of the
+    /// storage device for sequential scans without the caller having to manage concurrency.
+    ///
+    /// Each read uses a buffer from the buffer pool.
+    pub fn read_ahead(&self, window: NonZeroUsize) -> ReadAhead {
+        ReadAhead::new(Rc::clone(&self.handle), window)
+    }
+
+    pub(crate) fn handle(&self) -> HANDLE {
+        **self.handle
+    }
+}
+
+#[negative_impl]
+impl !Send for File {}
+#[negative_impl]
+impl !Sync for File {}
+
+/// Reads a file sequentially with multiple reads in flight. Created via `File::read_ahead()`.
+pub struct ReadAhead {
+    handle: Rc<OwnedHandle<HANDLE>>,
+    window: usize,
+
+    // Offset of the next chunk to be returned to the consumer.
+    position: u64,
+
+    // Offset of the next read to be issued.
+    next_read_offset: u64,
+
+    in_flight: FuturesOrdered<ChunkRead>,
+
+    end_of_file: bool,
+}
+
+type ChunkRead = std::pin::Pin<Box<dyn Future<Output = (u64, usize, OperationResult)>>>;
+
+impl ReadAhead {
+    fn new(handle: Rc<OwnedHandle<HANDLE>>, window: NonZeroUsize) -> Self {
+        Self {
+            handle,
+            window: window.get(),
+            position: 0,
+            next_read_offset: 0,
+            in_flight: FuturesOrdered::new(),
+            end_of_file: false,
+        }
+    }
+
+    /// Returns the next chunk of the file, in order, or `None` once the end of the file has been
+    /// reached.
+    pub async fn next(&mut self) -> io::Result<Option<PinnedBuffer>> {
+        loop {
+            if self.end_of_file {
+                return Ok(None);
+            }
+
+            while self.in_flight.len() < self.window {
+                self.issue_read();
+            }
+
+            // Polling the ordered set polls every read in it, so all of them get started even
+            // though we only wait for the first.
+            let (offset, requested_len, result) = self
+                .in_flight
+                .next()
+                .await
+                .expect("we always have reads in flight at this point");
+
+            let buffer = result.map_err(|e| e.into_inner())?;
+
+            if offset != self.position {
+                // A stale speculative read, issued before we learned about a short read.
+                continue;
+            }
+
+            if buffer.len() == 0 {
+                self.end_of_file = true;
+                self.in_flight = FuturesOrdered::new();
+                return Ok(None);
+            }
+
+            self.position += buffer.len() as u64;
+
+            if buffer.len() < requested_len {
+                // The reads after this one were issued at offsets that assumed a full read, so
+                // they are useless. We start again from where this one ended.
+                self.in_flight = FuturesOrdered::new();
+                self.next_read_offset = self.position;
+            }
+
+            return Ok(Some(buffer));
+        }
+    }
+
+    fn issue_read(&mut self) {
+        let buffer = PinnedBuffer::from_pool();
+        let requested_len = buffer.len();
+        let offset = self.next_read_offset;
+        let handle = Rc::clone(&self.handle);
+
+        self.next_read_offset += requested_len as u64;
+
+        self.in_flight.push_back(Box::pin(async move {
+            (offset, requested_len, read_at(handle, offset, buffer).await)
+        }));
+    }
+}
+
+#[negative_impl]
+impl !Send for ReadAhead {}
+#[negative_impl]
+impl !Sync for ReadAhead {}
+
+async fn read_at(
+    handle: Rc<OwnedHandle<HANDLE>>,
+    offset: u64,
+    buffer: PinnedBuffer,
+) -> OperationResult {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.set_offset(offset as usize);
+    operation.cancel_on_drop(**handle);
+    operation.set_kind(OperationKind::Read);
+
+    // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+    // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
+    let result = unsafe {
+        operation
+            .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                Ok(ReadFile(
+                    **handle,
+                    Some(buffer),
+                    Some(bytes_transferred_immediately as *mut _),
+                    Some(overlapped),
+                )?)
+            })
+            .await
+    };
+
+    // Reading at or past the end of the file is reported as an error, either immediately or via
+    // the completion status. For us, it is just an empty read.
+    match result {
+        Err(io::OperationError {
+            inner: io::Error::Windows(e),
+            mut buffer,
+        }) if e.code() == STATUS_END_OF_FILE.into() || e.code() == ERROR_HANDLE_EOF.into() => {
+            buffer.set_len(0);
+            Ok(buffer)
+        }
+        other => other,
+    }
+}
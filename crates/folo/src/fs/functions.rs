@@ -1,5 +1,5 @@
 use crate::{
-    io::{self, PinnedBuffer},
+    io::{self, OperationKind, PinnedBuffer},
     rt::{current_async_agent, spawn_sync, SynchronousTaskType},
     util::OwnedHandle,
 };
@@ -7,9 +7,15 @@ use std::{ffi::CString, path::Path};
 use windows::{
     core::PCSTR,
     Win32::{
-        Foundation::{HANDLE, STATUS_END_OF_FILE},
+        Foundation::{
+            ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND,
+            ERROR_SHARING_VIOLATION, HANDLE, STATUS_END_OF_FILE,
+        },
         Storage::FileSystem::{
-            CreateFileA, GetFileSizeEx, ReadFile, FILE_FLAG_OVERLAPPED, FILE_FLAG_SEQUENTIAL_SCAN, FILE_GENERIC_READ, FILE_SHARE_READ, OPEN_EXISTING
+            CreateFileA, GetFileAttributesA, GetFileSizeEx, ReadFile, FILE_ACCESS_RIGHTS,
+            FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OVERLAPPED, FILE_FLAG_SEQUENTIAL_SCAN,
+            FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_DELETE, FILE_SHARE_READ,
+            FILE_SHARE_WRITE, INVALID_FILE_ATTRIBUTES, OPEN_EXISTING,
         },
     },
 };
@@ -35,7 +41,7 @@ pub async fn read_large_buffer(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
         // a synchronous worker thread to avoid blocking the async workers with these slow calls.
 
         let (file_handle, file_size) =
-            spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            spawn_sync(SynchronousTaskType::FileSystem, move || -> io::Result<_> {
                 let file_handle = OwnedHandle::new(CreateFileA(
                     PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
                     FILE_GENERIC_READ.0,
@@ -99,6 +105,110 @@ pub async fn read_large_buffer(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
     }
 }
 
+/// Checks whether a file or directory exists at the path. Returns `Ok(false)` only if the path is
+/// known not to exist - if the check itself fails (e.g. due to lack of permissions or an unreachable
+/// network share), the error is returned instead.
+///
+/// The check is performed on a synchronous worker thread because probing a path can block for a
+/// long time, especially on network shares.
+pub async fn try_exists(path: impl AsRef<Path>) -> io::Result<bool> {
+    let path_cstr = path_to_cstring(path.as_ref())?;
+
+    spawn_sync(
+        SynchronousTaskType::FileSystem,
+        move || -> io::Result<bool> {
+            // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+            let attributes =
+                unsafe { GetFileAttributesA(PCSTR::from_raw(path_cstr.as_ptr() as *const u8)) };
+
+            if attributes != INVALID_FILE_ATTRIBUTES {
+                return Ok(true);
+            }
+
+            let error = windows_result::Error::from_win32();
+
+            if error.code() == ERROR_FILE_NOT_FOUND.into()
+                || error.code() == ERROR_PATH_NOT_FOUND.into()
+            {
+                Ok(false)
+            } else {
+                Err(error.into())
+            }
+        },
+    )
+    .await
+}
+
+/// Checks whether the current process can open the existing file or directory at the path for
+/// reading. Returns `Ok(false)` if access is denied; other failures (including the path not
+/// existing) are returned as errors.
+///
+/// The check is performed on a synchronous worker thread because probing a path can block for a
+/// long time, especially on network shares. Note that the answer may be outdated by the time the
+/// caller acts on it.
+pub async fn can_read(path: impl AsRef<Path>) -> io::Result<bool> {
+    can_open(path.as_ref(), FILE_GENERIC_READ).await
+}
+
+/// Checks whether the current process can open the existing file or directory at the path for
+/// writing. Returns `Ok(false)` if access is denied; other failures (including the path not
+/// existing) are returned as errors.
+///
+/// The check is performed on a synchronous worker thread because probing a path can block for a
+/// long time, especially on network shares. Note that the answer may be outdated by the time the
+/// caller acts on it.
+pub async fn can_write(path: impl AsRef<Path>) -> io::Result<bool> {
+    can_open(path.as_ref(), FILE_GENERIC_WRITE).await
+}
+
+async fn can_open(path: &Path, access: FILE_ACCESS_RIGHTS) -> io::Result<bool> {
+    let path_cstr = path_to_cstring(path)?;
+
+    spawn_sync(
+        SynchronousTaskType::FileSystem,
+        move || -> io::Result<bool> {
+            // We share everything to avoid our probe being denied (or denying others) just because
+            // someone else happens to have the file open. Backup semantics allow opening directories.
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            let result = unsafe {
+                CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    access.0,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAG_BACKUP_SEMANTICS,
+                    None,
+                )
+            };
+
+            match result {
+                Ok(handle) => {
+                    drop(unsafe { OwnedHandle::new(handle) });
+                    Ok(true)
+                }
+                Err(e)
+                    if e.code() == ERROR_ACCESS_DENIED.into()
+                        || e.code() == ERROR_SHARING_VIOLATION.into() =>
+                {
+                    Ok(false)
+                }
+                Err(e) => Err(e.into()),
+            }
+        },
+    )
+    .await
+}
+
+pub(super) fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(
+        path.to_str()
+            .ok_or_else(|| io::Error::InvalidOptions("path must be valid Unicode".to_string()))?,
+    )
+    .map_err(|_| io::Error::InvalidOptions("path must not contain NUL".to_string()))
+}
+
 /// Reads a chunk of bytes from a file at a given offset and fills the provided buffer with them,
 /// appending the bytes to the beginning of the buffer's active region (without changing the
 /// region).
@@ -116,6 +226,8 @@ async fn read_buffer_from_file(
 
     let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
     operation.set_offset(offset);
+    operation.cancel_on_drop(*file);
+    operation.set_kind(OperationKind::Read);
 
     // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
     // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
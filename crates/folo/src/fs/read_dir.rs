@@ -0,0 +1,122 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::{
+    ffi::OsString,
+    fs::FileType,
+    path::{Path, PathBuf},
+};
+
+/// An entry in a directory, as returned by `read_dir()` and `walk()`.
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    path: PathBuf,
+
+    // The type of the entry itself - for symbolic links (and junctions), this is the link.
+    file_type: FileType,
+
+    // Only resolved if we were asked to follow symbolic links - the type of the link target and
+    // the canonical path of the target if it is a directory.
+    target: Option<LinkTarget>,
+}
+
+#[derive(Clone, Debug)]
+struct LinkTarget {
+    is_dir: bool,
+    canonical_path: Option<PathBuf>,
+}
+
+impl DirEntry {
+    /// The full path of the entry (the path of the directory joined with the file name).
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> OsString {
+        self.path.file_name().map(Into::into).unwrap_or_default()
+    }
+
+    /// The type of the entry. For symbolic links and junctions, this is the type of the link
+    /// itself, not of its target.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Whether the entry is a directory that a recursive traversal should descend into. If the
+    /// entry was read with symbolic links being followed, this includes links to directories.
+    pub fn is_dir(&self) -> bool {
+        match &self.target {
+            Some(target) => target.is_dir,
+            None => self.file_type.is_dir(),
+        }
+    }
+
+    /// The canonical path of the directory, if known. Only available for directories read while
+    /// following symbolic links, used to detect cycles.
+    pub(super) fn canonical_path(&self) -> Option<&Path> {
+        self.target
+            .as_ref()
+            .and_then(|target| target.canonical_path.as_deref())
+    }
+}
+
+/// Returns the entries of a directory, in no particular order.
+///
+/// The directory is read on a synchronous worker thread because directory enumeration can block
+/// for a long time, especially on network shares.
+pub async fn read_dir(path: impl AsRef<Path>) -> io::Result<Vec<DirEntry>> {
+    read_dir_core(path.as_ref().to_path_buf(), false).await
+}
+
+/// Reads a directory, optionally resolving the targets of symbolic links (and the canonical paths
+/// of all directories, for cycle detection) while we are on the synchronous worker anyway.
+pub(super) async fn read_dir_core(
+    path: PathBuf,
+    follow_symlinks: bool,
+) -> io::Result<Vec<DirEntry>> {
+    spawn_sync(
+        SynchronousTaskType::FileSystem,
+        move || -> io::Result<Vec<DirEntry>> {
+            let mut entries = Vec::new();
+
+            for entry in std::fs::read_dir(&path)? {
+                let entry = entry?;
+                let path = entry.path();
+                let file_type = entry.file_type()?;
+
+                let target = if follow_symlinks {
+                    // A link whose target does not exist (or is inaccessible) is not a directory
+                    // for our purposes - we still return the link itself.
+                    let is_dir = if file_type.is_symlink() {
+                        std::fs::metadata(&path).is_ok_and(|m| m.is_dir())
+                    } else {
+                        file_type.is_dir()
+                    };
+
+                    let canonical_path = if is_dir {
+                        std::fs::canonicalize(&path).ok()
+                    } else {
+                        None
+                    };
+
+                    Some(LinkTarget {
+                        is_dir,
+                        canonical_path,
+                    })
+                } else {
+                    None
+                };
+
+                entries.push(DirEntry {
+                    path,
+                    file_type,
+                    target,
+                });
+            }
+
+            Ok(entries)
+        },
+    )
+    .await
+}
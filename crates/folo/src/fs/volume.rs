@@ -0,0 +1,138 @@
+use crate::{
+    fs::path_to_cstring,
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::{ffi::CStr, path::Path};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::MAX_PATH,
+        Storage::FileSystem::{GetDiskFreeSpaceExA, GetVolumeInformationA, GetVolumePathNameA},
+    },
+};
+
+/// Space usage of the volume that contains a path. Returned by `disk_usage()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiskUsage {
+    /// Total size of the volume, in bytes.
+    pub total_bytes: u64,
+
+    /// Free space on the volume, in bytes.
+    pub free_bytes: u64,
+
+    /// Free space on the volume that is available to the current user, in bytes. This may be less
+    /// than `free_bytes` if disk quotas are in use.
+    pub available_bytes: u64,
+}
+
+/// Information about the volume that contains a path. Returned by `volume_info()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VolumeInfo {
+    /// The root path of the volume (e.g. `C:\` or a mount point directory), with trailing slash.
+    pub root_path: String,
+
+    /// The label of the volume, which may be empty.
+    pub label: String,
+
+    /// The name of the file system (e.g. `NTFS` or `ReFS`).
+    pub file_system: String,
+
+    pub serial_number: u32,
+
+    /// The maximum length of a single path component supported by the file system.
+    pub max_component_length: u32,
+
+    /// The `FILE_*` flags describing the capabilities of the file system (e.g. whether it supports
+    /// sparse files or compression).
+    pub file_system_flags: u32,
+}
+
+/// Returns the space usage of the volume that contains the path, which may be any existing file or
+/// directory on the volume.
+///
+/// The query is performed on a synchronous worker thread because it can block for a long time,
+/// especially on network shares.
+pub async fn disk_usage(path: impl AsRef<Path>) -> io::Result<DiskUsage> {
+    let path_cstr = path_to_cstring(path.as_ref())?;
+
+    spawn_sync(SynchronousTaskType::FileSystem, move || -> io::Result<_> {
+        let mut available_bytes: u64 = 0;
+        let mut total_bytes: u64 = 0;
+        let mut free_bytes: u64 = 0;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        unsafe {
+            GetDiskFreeSpaceExA(
+                PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                Some(&mut available_bytes as *mut _),
+                Some(&mut total_bytes as *mut _),
+                Some(&mut free_bytes as *mut _),
+            )?;
+        }
+
+        Ok(DiskUsage {
+            total_bytes,
+            free_bytes,
+            available_bytes,
+        })
+    })
+    .await
+}
+
+/// Returns information about the volume that contains the path, which may be any existing file or
+/// directory on the volume.
+///
+/// The query is performed on a synchronous worker thread because it can block for a long time,
+/// especially on network shares.
+pub async fn volume_info(path: impl AsRef<Path>) -> io::Result<VolumeInfo> {
+    let path_cstr = path_to_cstring(path.as_ref())?;
+
+    spawn_sync(SynchronousTaskType::FileSystem, move || -> io::Result<_> {
+        // The API wants a buffer "large enough", which the docs suggest is MAX_PATH + 1.
+        let mut root_path = [0_u8; MAX_PATH as usize + 1];
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        unsafe {
+            GetVolumePathNameA(
+                PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                &mut root_path,
+            )?;
+        }
+
+        let mut label = [0_u8; MAX_PATH as usize + 1];
+        let mut file_system = [0_u8; MAX_PATH as usize + 1];
+        let mut serial_number: u32 = 0;
+        let mut max_component_length: u32 = 0;
+        let mut file_system_flags: u32 = 0;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The root path is
+        // NUL-terminated by the previous call.
+        unsafe {
+            GetVolumeInformationA(
+                PCSTR::from_raw(root_path.as_ptr()),
+                Some(&mut label),
+                Some(&mut serial_number as *mut _),
+                Some(&mut max_component_length as *mut _),
+                Some(&mut file_system_flags as *mut _),
+                Some(&mut file_system),
+            )?;
+        }
+
+        Ok(VolumeInfo {
+            root_path: nul_terminated_to_string(&root_path),
+            label: nul_terminated_to_string(&label),
+            file_system: nul_terminated_to_string(&file_system),
+            serial_number,
+            max_component_length,
+            file_system_flags,
+        })
+    })
+    .await
+}
+
+fn nul_terminated_to_string(buffer: &[u8]) -> String {
+    CStr::from_bytes_until_nul(buffer)
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,214 @@
+use crate::{
+    fs::{read_dir::read_dir_core, DirEntry},
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use negative_impl::negative_impl;
+use std::{
+    collections::{HashSet, VecDeque},
+    future::Future,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task,
+};
+
+/// How many directories `walk()` reads concurrently unless configured otherwise.
+pub const DEFAULT_WALK_CONCURRENCY: NonZeroUsize = NonZeroUsize::new(4).unwrap();
+
+/// Recursively traverses the directory tree under `path`, returning all entries (files,
+/// directories and links) as a stream. The root directory itself is not returned.
+///
+/// Only entries for which `filter` returns true are returned. Directories for which `filter`
+/// returns false are also not descended into, so the filter can be used to prune the traversal.
+///
+/// Multiple directories are read concurrently on synchronous worker threads (see
+/// `Walk::max_concurrency()`), so entries are returned in no particular order. Errors reading a
+/// directory are returned as stream items and the traversal continues with other directories.
+///
+/// By default, symbolic links and junctions are returned but not followed. See
+/// `Walk::follow_symlinks()`.
+pub fn walk<F>(path: impl AsRef<Path>, filter: F) -> Walk<F>
+where
+    F: FnMut(&DirEntry) -> bool,
+{
+    Walk::new(path.as_ref().to_path_buf(), filter)
+}
+
+type ReadDir = Pin<Box<dyn Future<Output = io::Result<DirContents>>>>;
+
+struct DirContents {
+    entries: Vec<DirEntry>,
+
+    // If set, the directory must be marked as visited (used for the root directory, whose
+    // canonical path we only learn when reading it).
+    visited: Option<PathBuf>,
+}
+
+/// A stream of directory entries. Created via `walk()`.
+pub struct Walk<F>
+where
+    F: FnMut(&DirEntry) -> bool,
+{
+    filter: F,
+    max_concurrency: usize,
+    follow_symlinks: bool,
+
+    // The root is only read once we are first polled, so the options can still be changed.
+    root: Option<PathBuf>,
+
+    // Directories we have found but not yet started reading.
+    pending_dirs: VecDeque<PathBuf>,
+    in_flight: FuturesUnordered<ReadDir>,
+
+    // Items read from directories but not yet returned to the caller.
+    ready: VecDeque<io::Result<DirEntry>>,
+
+    // Canonical paths of all the directories we have descended into when following symbolic
+    // links. If a link leads to one of these, we do not descend again, avoiding infinite loops.
+    visited: HashSet<PathBuf>,
+}
+
+impl<F> Walk<F>
+where
+    F: FnMut(&DirEntry) -> bool,
+{
+    fn new(root: PathBuf, filter: F) -> Self {
+        Self {
+            filter,
+            max_concurrency: DEFAULT_WALK_CONCURRENCY.get(),
+            follow_symlinks: false,
+            root: Some(root),
+            pending_dirs: VecDeque::new(),
+            in_flight: FuturesUnordered::new(),
+            ready: VecDeque::new(),
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Sets how many directories may be read at the same time.
+    pub fn max_concurrency(mut self, value: NonZeroUsize) -> Self {
+        self.max_concurrency = value.get();
+        self
+    }
+
+    /// Sets whether to descend into directories that are the targets of symbolic links and
+    /// junctions. Each directory is visited at most once, so links that form a cycle do not cause
+    /// an infinite traversal.
+    pub fn follow_symlinks(mut self, value: bool) -> Self {
+        self.follow_symlinks = value;
+        self
+    }
+
+    fn start_root(&mut self, root: PathBuf) {
+        let follow_symlinks = self.follow_symlinks;
+
+        self.in_flight.push(Box::pin(async move {
+            // We need the canonical path of the root to detect links that lead back to it.
+            let visited = if follow_symlinks {
+                let root = root.clone();
+                Some(
+                    spawn_sync(SynchronousTaskType::FileSystem, move || {
+                        std::fs::canonicalize(root)
+                    })
+                    .await?,
+                )
+            } else {
+                None
+            };
+
+            Ok(DirContents {
+                entries: read_dir_core(root, follow_symlinks).await?,
+                visited,
+            })
+        }));
+    }
+
+    fn process_contents(&mut self, contents: DirContents) {
+        if let Some(visited) = contents.visited {
+            self.visited.insert(visited);
+        }
+
+        for entry in contents.entries {
+            if !(self.filter)(&entry) {
+                continue;
+            }
+
+            if entry.is_dir() {
+                self.descend_into(&entry);
+            }
+
+            self.ready.push_back(Ok(entry));
+        }
+    }
+
+    fn descend_into(&mut self, entry: &DirEntry) {
+        if self.follow_symlinks {
+            match entry.canonical_path() {
+                Some(canonical_path) => {
+                    if !self.visited.insert(canonical_path.to_path_buf()) {
+                        return;
+                    }
+                }
+                // If we cannot determine where it leads, we cannot tell whether it is a loop,
+                // so we play it safe and do not descend.
+                None => return,
+            }
+        }
+
+        self.pending_dirs.push_back(entry.path().to_path_buf());
+    }
+}
+
+impl<F> Stream for Walk<F>
+where
+    F: FnMut(&DirEntry) -> bool + Unpin,
+{
+    type Item = io::Result<DirEntry>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        if let Some(root) = self.root.take() {
+            self.start_root(root);
+        }
+
+        loop {
+            if let Some(item) = self.ready.pop_front() {
+                return task::Poll::Ready(Some(item));
+            }
+
+            while self.in_flight.len() < self.max_concurrency {
+                let Some(dir) = self.pending_dirs.pop_front() else {
+                    break;
+                };
+
+                let follow_symlinks = self.follow_symlinks;
+                self.in_flight.push(Box::pin(async move {
+                    Ok(DirContents {
+                        entries: read_dir_core(dir, follow_symlinks).await?,
+                        visited: None,
+                    })
+                }));
+            }
+
+            if self.in_flight.is_empty() {
+                return task::Poll::Ready(None);
+            }
+
+            match self.in_flight.poll_next_unpin(cx) {
+                task::Poll::Ready(Some(Ok(contents))) => self.process_contents(contents),
+                task::Poll::Ready(Some(Err(e))) => self.ready.push_back(Err(e)),
+                task::Poll::Ready(None) => unreachable!("we checked that something is in flight"),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        }
+    }
+}
+
+#[negative_impl]
+impl<F> !Send for Walk<F> where F: FnMut(&DirEntry) -> bool {}
+#[negative_impl]
+impl<F> !Sync for Walk<F> where F: FnMut(&DirEntry) -> bool {}
@@ -0,0 +1,263 @@
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    fs::path_to_cstring,
+    io::{self, OperationKind, OperationResult, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    rt::{current_async_agent, spawn, spawn_sync, LocalJoinHandle, SynchronousTaskType},
+    util::{LowPrecisionInstant, OwnedHandle, ThreadSafe},
+};
+use futures::stream::{FuturesUnordered, StreamExt};
+use negative_impl::negative_impl;
+use std::{num::NonZeroUsize, path::Path, rc::Rc};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::HANDLE,
+        Storage::FileSystem::{
+            CreateFileA, FlushFileBuffers, WriteFile, CREATE_ALWAYS, FILE_FLAG_OVERLAPPED,
+            FILE_GENERIC_WRITE, FILE_SHARE_READ,
+        },
+    },
+};
+
+/// A write-behind file writer for append-only workloads such as logs and log-structured storage.
+///
+/// Data given to `write()` is collected into large chunks (the size of a buffer from the buffer
+/// pool), which are written to the file at chunk-aligned offsets with up to `max_in_flight` writes
+/// in progress at the same time. Writes only wait when that limit is reached.
+///
+/// Nothing is guaranteed to be on disk until `barrier()` completes. A barrier writes out any
+/// partially filled chunk, waits for all writes to complete and flushes the file to the storage
+/// device. Any data not yet handed to a chunk write is lost when the writer is dropped, so make
+/// sure to end with a barrier.
+///
+/// If a write fails, the error is reported by the next call to `write()` or `barrier()` and the
+/// writer must not be used further.
+pub struct Writer {
+    handle: Rc<OwnedHandle<HANDLE>>,
+    max_in_flight: usize,
+
+    // The chunk being filled by `write()`, located at `chunk_offset` in the file.
+    chunk: PinnedBuffer,
+    chunk_len: usize,
+    chunk_offset: u64,
+
+    // Each chunk write is a separate task, so the writes make progress in the background even
+    // when nobody is awaiting them.
+    in_flight: FuturesUnordered<LocalJoinHandle<io::Result<()>>>,
+
+    // The first error reported by a write that completed in the background.
+    error: Option<io::Error>,
+}
+
+impl Writer {
+    /// Creates a new file (or truncates an existing one) for writing, allowing up to
+    /// `max_in_flight` chunk writes to be in progress at the same time.
+    pub async fn create(path: impl AsRef<Path>, max_in_flight: NonZeroUsize) -> io::Result<Self> {
+        let path_cstr = path_to_cstring(path.as_ref())?;
+
+        // Opening a file is a blocking operation, so we kick it off to a synchronous worker thread
+        // to avoid blocking the async workers with this potentially slow call.
+        let handle = spawn_sync(SynchronousTaskType::FileSystem, move || -> io::Result<_> {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    FILE_GENERIC_WRITE.0,
+                    FILE_SHARE_READ,
+                    None,
+                    CREATE_ALWAYS,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self {
+            handle: Rc::new(handle),
+            max_in_flight: max_in_flight.get(),
+            chunk: PinnedBuffer::from_pool(),
+            chunk_len: 0,
+            chunk_offset: 0,
+            in_flight: FuturesUnordered::new(),
+            error: None,
+        })
+    }
+
+    /// Appends data to the file. The data is copied, so the caller may reuse its memory as soon as
+    /// this returns. Only waits if the limit on writes in flight has been reached.
+    pub async fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        self.check_error()?;
+
+        while !data.is_empty() {
+            let capacity = self.chunk.len();
+            let count = data.len().min(capacity - self.chunk_len);
+
+            self.chunk.as_mut_slice()[self.chunk_len..self.chunk_len + count]
+                .copy_from_slice(&data[..count]);
+            self.chunk_len += count;
+            data = &data[count..];
+
+            if self.chunk_len == capacity {
+                self.submit_full_chunk().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits until all data written so far is durably stored. This writes out any partially filled
+    /// chunk, waits for all writes in flight to complete and flushes the file buffers of the
+    /// operating system to the storage device.
+    pub async fn barrier(&mut self) -> io::Result<()> {
+        let started = LowPrecisionInstant::now();
+
+        self.check_error()?;
+
+        if self.chunk_len != 0 {
+            // We write a copy of the partial chunk and keep filling the original. Once it is full,
+            // it overwrites the same region again, keeping all writes chunk-aligned. There is no
+            // risk of the two writes racing because we wait for this one to complete below.
+            let mut partial = PinnedBuffer::from_pool();
+            partial.set_len(self.chunk_len);
+            partial
+                .as_mut_slice()
+                .copy_from_slice(&self.chunk.as_slice()[..self.chunk_len]);
+
+            self.in_flight.push(write_chunk(
+                Rc::clone(&self.handle),
+                self.chunk_offset,
+                partial,
+            ));
+        }
+
+        while let Some(result) = self.in_flight.next().await {
+            self.record_result(result);
+        }
+
+        self.check_error()?;
+
+        // SAFETY: File handles can be used from any thread and we keep the handle open until the
+        // synchronous task has completed because we wait for it while borrowing self.
+        let handle = unsafe { ThreadSafe::new(**self.handle) };
+
+        spawn_sync(
+            SynchronousTaskType::FileSystem,
+            move || -> io::Result<()> {
+                // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+                unsafe { FlushFileBuffers(*handle)? };
+                Ok(())
+            },
+        )
+        .await?;
+
+        BARRIER_DURATION
+            .with(|x| x.observe_millis(LowPrecisionInstant::now().duration_since(started)));
+
+        Ok(())
+    }
+
+    async fn submit_full_chunk(&mut self) -> io::Result<()> {
+        while self.in_flight.len() >= self.max_in_flight {
+            let result = self
+                .in_flight
+                .next()
+                .await
+                .expect("we only wait if there is something in flight");
+
+            self.record_result(result);
+            self.check_error()?;
+        }
+
+        let chunk = std::mem::replace(&mut self.chunk, PinnedBuffer::from_pool());
+        let offset = self.chunk_offset;
+
+        self.chunk_offset += self.chunk_len as u64;
+        self.chunk_len = 0;
+
+        self.in_flight
+            .push(write_chunk(Rc::clone(&self.handle), offset, chunk));
+
+        Ok(())
+    }
+
+    fn record_result(&mut self, result: io::Result<()>) {
+        if let Err(e) = result {
+            self.error.get_or_insert(e);
+        }
+    }
+
+    fn check_error(&mut self) -> io::Result<()> {
+        // We report the error only once - afterwards, the writer is in an undefined state.
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for Writer {}
+#[negative_impl]
+impl !Sync for Writer {}
+
+fn write_chunk(
+    handle: Rc<OwnedHandle<HANDLE>>,
+    offset: u64,
+    buffer: PinnedBuffer,
+) -> LocalJoinHandle<io::Result<()>> {
+    spawn(async move {
+        let requested_len = buffer.len();
+
+        let buffer = write_at(handle, offset, buffer)
+            .await
+            .map_err(|e| e.into_inner())?;
+
+        if buffer.len() != requested_len {
+            return Err(io::Error::Internal(format!(
+                "file write at offset {offset} wrote {} of {requested_len} bytes",
+                buffer.len()
+            )));
+        }
+
+        Ok(())
+    })
+}
+
+async fn write_at(
+    handle: Rc<OwnedHandle<HANDLE>>,
+    offset: u64,
+    buffer: PinnedBuffer,
+) -> OperationResult {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.set_offset(offset as usize);
+    operation.cancel_on_drop(**handle);
+    operation.set_kind(OperationKind::Write);
+
+    // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+    // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
+    unsafe {
+        operation
+            .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                Ok(WriteFile(
+                    **handle,
+                    Some(buffer),
+                    Some(bytes_transferred_immediately as *mut _),
+                    Some(overlapped),
+                )?)
+            })
+            .await
+    }
+}
+
+thread_local! {
+    static BARRIER_DURATION: Event = EventBuilder::new()
+        .name("fs_writer_barrier_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+}
@@ -1,18 +1,34 @@
 mod buffer;
+pub(crate) mod buffer_pool;
+mod chunked;
 mod completion_port;
 mod driver;
 mod error;
+mod ingest_ring;
+mod latency_slo;
 mod operation;
 mod operation_result;
 mod primitive;
+mod rio;
 mod waker;
 
 pub use buffer::*;
+pub use buffer_pool::{BufferPool, BufferPoolStats, DEFAULT_BUFFER_POOL_MAX_BYTES};
+pub use chunked::MAX_OPERATION_LEN;
+pub(crate) use chunked::{
+    transfer_all_in_chunks, transfer_all_vectored_in_chunks, transfer_in_chunks,
+};
 pub(crate) use completion_port::*;
 pub(crate) use driver::*;
+pub use driver::{IO_DEQUEUE_BATCH_SIZE, IO_DEQUEUE_MAX_BATCHES};
 pub use error::*;
+pub use ingest_ring::*;
+pub use latency_slo::*;
+pub use operation::{OperationHandle, MAX_VECTORED_BUFFERS};
 #[allow(unused_imports)] // Just WIP, shut up compiler.
 pub(crate) use operation::*;
 pub use operation_result::*;
 pub(crate) use primitive::*;
+pub(crate) use rio::*;
+pub use rio::RIO_SLOT_SIZE;
 pub(crate) use waker::*;
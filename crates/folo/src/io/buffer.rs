@@ -1,16 +1,23 @@
+mod aligned;
+#[cfg(feature = "bytes")]
+mod bytes;
+mod shared;
+
 use crate::{
+    io::buffer_pool,
     metrics::{Event, EventBuilder},
-    util::PinnedSlabChain,
 };
-use core::slice;
+use aligned::AlignedStorage;
 use negative_impl::negative_impl;
+use shared::SharedStorage;
 use std::{
-    cell::{RefCell, UnsafeCell},
     fmt,
     mem::{self},
     ops::Range,
     pin::Pin,
     ptr,
+    rc::Rc,
+    slice,
 };
 
 /// A buffer of bytes for reading from or writing to as part of low level I/O operations. This is
@@ -50,77 +57,73 @@ pub struct PinnedBuffer {
 
 enum Mode {
     Pooled {
-        // This is the real storage of the bytes and determines the capacity.
-        //
-        // We use 'static as the lifetime because in practice this is backed by storage that will
-        // life as long as the buffer lives, despite being a reference.
-        inner: Pin<&'static mut [u8]>,
+        // This is the real storage of the bytes and determines the capacity. It is returned to the
+        // pool when the buffer is dropped.
+        inner: Pin<Box<[u8]>>,
 
-        index_in_pool: usize,
+        size: BufferSize,
     },
     BoxedSlice {
         // We allow the caller to retrieve the inner value from the buffer via
         // `.into_inner_boxed_slice()` if they wish to reuse the storage later.
         inner: Pin<Box<[u8]>>,
     },
+    Vec {
+        // We never resize the vector, so its storage stays where it is. We allow the caller to
+        // retrieve the inner value from the buffer via `.into_inner_vec()`.
+        inner: Vec<u8>,
+    },
+    Aligned {
+        inner: AlignedStorage,
+    },
+    Shared {
+        // A buffer split into pieces via `.split_to()` or `.split_off()`. Each piece has its own
+        // range of the storage and the storage is released when the last piece is dropped.
+        inner: Rc<SharedStorage>,
+        range: Range<usize>,
+    },
 }
 
 impl fmt::Debug for Mode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Pooled { index_in_pool, .. } => f
-                .debug_struct("Pooled")
-                .field("index_in_pool", index_in_pool)
-                .finish(),
+            Self::Pooled { size, .. } => f.debug_struct("Pooled").field("size", size).finish(),
             Self::BoxedSlice { .. } => f.debug_struct("BoxedSlice").finish(),
+            Self::Vec { .. } => f.debug_struct("Vec").finish(),
+            Self::Aligned { inner } => f
+                .debug_struct("Aligned")
+                .field("align", &inner.align())
+                .finish(),
+            Self::Shared { range, .. } => f.debug_struct("Shared").field("range", range).finish(),
         }
     }
 }
 
 impl PinnedBuffer {
-    /// Obtains a new buffer from the current thread's buffer pool.
+    /// Obtains a new buffer from the current thread's buffer pool, of the largest size class.
     pub fn from_pool() -> Self {
-        POOL.with(|pool| {
-            let mut pool = pool.borrow_mut();
-            let inserter = pool.begin_insert();
-            let index = inserter.index();
-
-            // We do not initialize the buffer when we take it from the pool. It has whatever data
-            // it had at the start (maybe zeroes, maybe old I/O operation data).
-            let storage = inserter.insert_uninit();
-
-            // SAFETY: UnsafeCell<T> is layout-compatible with T in most cases (and definitely in
-            // this case), so we can convert that freely. Likewise, MaybeUninit<T> is layout-
-            // compatible with T. Finally, we do not care what bit patterns our buffers are
-            // initialized with because they will be overwritten by new data anyway as part of some
-            // I/O operation. We assume we do not need to worry about dirty contents being somehow
-            // dangerous/sensitive here (perhaps might want to consider zeroing per-usecase).
-            let storage = unsafe { (*storage).assume_init_mut() };
-
-            POOL_ALLOCATED.with(Event::observe_unit);
-
-            // SAFETY: The chain guarantees pinning, we just re-wrap Pin around the inner bytes.
-            // We only ever hand out references derived from UnsafeCell, which are always valid
-            // to hand out as long as we do not create multiple `&mut` references (which we do not
-            // as Buffer holds the only reference and protects it via standard borrow mechanics).
-            let inner = unsafe {
-                Pin::new_unchecked(slice::from_raw_parts_mut(
-                    storage.get() as *mut u8,
-                    POOL_BUFFER_CAPACITY_BYTES,
-                ))
-            };
-
-            let len = inner.len();
-
-            PinnedBuffer {
-                mode: Mode::Pooled {
-                    inner,
-                    index_in_pool: index,
-                },
-                len,
-                start: 0,
-            }
-        })
+        Self::from_pool_with_size(BufferSize::Large)
+    }
+
+    /// Obtains a new buffer of the specified size class from the current thread's buffer pool.
+    /// Smaller buffers are useful for keeping down the memory used by many mostly idle
+    /// connections.
+    ///
+    /// The memory the pool keeps around is limited by a budget per thread (see
+    /// `RuntimeConfig::buffer_pool_max_bytes()`) but buffers are allocated even if the budget
+    /// is exceeded by the buffers in use.
+    pub fn from_pool_with_size(size: BufferSize) -> Self {
+        let inner = Pin::new(buffer_pool::take(size));
+
+        POOL_ALLOCATED.with(Event::observe_unit);
+
+        let len = inner.len();
+
+        PinnedBuffer {
+            mode: Mode::Pooled { inner, size },
+            len,
+            start: 0,
+        }
     }
 
     /// Creates a new buffer from a slice of bytes provided by the caller. Once the buffer has been
@@ -139,13 +142,49 @@ impl PinnedBuffer {
         }
     }
 
-    pub fn capacity(&self) -> usize {
-        match &self.mode {
-            Mode::Pooled { inner, .. } => inner.len(),
-            Mode::BoxedSlice { inner } => inner.len(),
+    /// Creates a new buffer from a vector provided by the caller, without copying the data. The
+    /// capacity of the buffer is the length of the vector - any spare capacity of the vector is
+    /// not used. Once the buffer has been used up, the caller may get the vector back via
+    /// `.into_inner_vec()`.
+    pub fn from_vec(vec: Vec<u8>) -> Self {
+        CALLER_BUFFERS_REFERENCED.with(Event::observe_unit);
+
+        let len = vec.len();
+
+        PinnedBuffer {
+            mode: Mode::Vec { inner: vec },
+            len,
+            start: 0,
         }
     }
 
+    /// Allocates a new zero-initialized buffer of `len` bytes whose start is aligned to `align`
+    /// bytes, for use with devices that require aligned buffers. For example, files opened with
+    /// `FILE_FLAG_NO_BUFFERING` require buffers aligned to the sector size of the volume.
+    ///
+    /// Only the start of the buffer is aligned, so keep the start offset of the active region at
+    /// a multiple of the alignment when such a device requires it. The buffer is not pooled - its
+    /// memory is released when it is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero or if `align` is not a power of two.
+    pub fn new_aligned(len: usize, align: usize) -> Self {
+        let inner = AlignedStorage::new(len, align);
+
+        ALIGNED_ALLOCATED.with(Event::observe_unit);
+
+        PinnedBuffer {
+            mode: Mode::Aligned { inner },
+            len,
+            start: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.storage().len()
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
@@ -192,29 +231,20 @@ impl PinnedBuffer {
 
     /// Obtains a mutable view over the contents of the buffer.
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
-        match &mut self.mode {
-            Mode::Pooled { inner, .. } => &mut inner[self.start..(self.start + self.len)],
-            Mode::BoxedSlice { inner } => &mut inner[self.start..(self.start + self.len)],
-        }
+        let region = self.active_region();
+        &mut self.storage_mut()[region]
     }
 
     /// Sets the length and obtains a mutable view over the contents of the buffer.
     /// Shorthand to easily fill the buffer and set the length in one go for write operations.
     pub fn as_mut_slice_with_len(&mut self, length: usize) -> &mut [u8] {
         self.set_len(length);
-
-        match &mut self.mode {
-            Mode::Pooled { inner, .. } => &mut inner[self.start..(self.start + self.len)],
-            Mode::BoxedSlice { inner } => &mut inner[self.start..(self.start + self.len)],
-        }
+        self.as_mut_slice()
     }
 
     /// Obtains an immutable view over the contents of the buffer.
     pub fn as_slice(&self) -> &[u8] {
-        match &self.mode {
-            Mode::Pooled { inner, .. } => &inner[self.start..(self.start + self.len)],
-            Mode::BoxedSlice { inner } => &inner[self.start..(self.start + self.len)],
-        }
+        &self.storage()[self.active_region()]
     }
 
     pub fn active_region(&self) -> Range<usize> {
@@ -224,6 +254,81 @@ impl PinnedBuffer {
         }
     }
 
+    /// Splits the buffer in two at the specified offset into the active region, without copying.
+    /// The returned buffer has the part of the active region before the offset, while this buffer
+    /// keeps the rest. Both buffers can be used independently, e.g. handed to different consumers
+    /// or used for different I/O operations.
+    ///
+    /// The returned buffer also gets the part of the storage before the active region, while this
+    /// buffer also keeps the part after it. The storage is released (or returned to the pool) once
+    /// all the pieces have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the active region.
+    #[must_use = "the split-off part of the buffer is dropped if unused"]
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len);
+
+        let (inner, range) = self.share();
+        let boundary = range.start + self.start + at;
+
+        let head = PinnedBuffer {
+            mode: Mode::Shared {
+                inner: Rc::clone(&inner),
+                range: range.start..boundary,
+            },
+            len: at,
+            start: self.start,
+        };
+
+        self.mode = Mode::Shared {
+            inner,
+            range: boundary..range.end,
+        };
+        self.start = 0;
+        self.len -= at;
+
+        head
+    }
+
+    /// Splits the buffer in two at the specified offset into the active region, without copying.
+    /// The returned buffer has the part of the active region from the offset onward, while this
+    /// buffer keeps the part before it. Both buffers can be used independently, e.g. handed to
+    /// different consumers or used for different I/O operations.
+    ///
+    /// The returned buffer also gets the part of the storage after the active region, while this
+    /// buffer also keeps the part before it. The storage is released (or returned to the pool) once
+    /// all the pieces have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the active region.
+    #[must_use = "the split-off part of the buffer is dropped if unused"]
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len);
+
+        let (inner, range) = self.share();
+        let boundary = range.start + self.start + at;
+
+        let tail = PinnedBuffer {
+            mode: Mode::Shared {
+                inner: Rc::clone(&inner),
+                range: boundary..range.end,
+            },
+            len: self.len - at,
+            start: 0,
+        };
+
+        self.mode = Mode::Shared {
+            inner,
+            range: range.start..boundary,
+        };
+        self.len = at;
+
+        tail
+    }
+
     /// Consumes the buffer and returns the inner boxed slice that was used to create the object.
     /// Note that the inner boxed slice will be returned in its full extent, ignoring active region.
     ///
@@ -233,26 +338,109 @@ impl PinnedBuffer {
     pub fn into_inner_boxed_slice(self) -> Box<[u8]> {
         assert!(matches!(self.mode, Mode::BoxedSlice { .. }));
 
-        // We are destroying the buffer without going through the usual drop logic.
+        match self.into_mode() {
+            Mode::BoxedSlice { inner } => Pin::into_inner(inner),
+            _ => unreachable!("we already asserted that this is a boxed slice"),
+        }
+    }
+
+    /// Consumes the buffer and returns the caller-provided storage that was used to create the
+    /// object, as a vector. Note that the vector will be returned in its full extent, ignoring
+    /// active region.
+    ///
+    /// This does not copy the data, whether the buffer was created via `from_vec()` or
+    /// `from_boxed_slice()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer was not created from caller-provided storage.
+    pub fn into_inner_vec(self) -> Vec<u8> {
+        assert!(self.is_caller_storage());
+
+        match self.into_mode() {
+            Mode::BoxedSlice { inner } => Pin::into_inner(inner).into_vec(),
+            Mode::Vec { inner } => inner,
+            _ => unreachable!("we already asserted that this is caller-provided storage"),
+        }
+    }
+
+    /// Whether the storage was provided by the caller, who can get it back via
+    /// `.into_inner_vec()`.
+    fn is_caller_storage(&self) -> bool {
+        matches!(self.mode, Mode::BoxedSlice { .. } | Mode::Vec { .. })
+    }
+
+    fn storage(&self) -> &[u8] {
+        match &self.mode {
+            Mode::Pooled { inner, .. } => inner,
+            Mode::BoxedSlice { inner } => inner,
+            Mode::Vec { inner } => inner,
+            Mode::Aligned { inner } => inner.as_slice(),
+            // SAFETY: The ranges of the pieces of shared storage do not overlap and we have
+            // borrowed the only piece that covers this range.
+            Mode::Shared { inner, range } => unsafe {
+                slice::from_raw_parts(inner.as_ptr().add(range.start), range.len())
+            },
+        }
+    }
+
+    fn storage_mut(&mut self) -> &mut [u8] {
+        match &mut self.mode {
+            Mode::Pooled { inner, .. } => inner,
+            Mode::BoxedSlice { inner } => inner,
+            Mode::Vec { inner } => inner,
+            Mode::Aligned { inner } => inner.as_mut_slice(),
+            // SAFETY: The ranges of the pieces of shared storage do not overlap and we have
+            // exclusively borrowed the only piece that covers this range.
+            Mode::Shared { inner, range } => unsafe {
+                slice::from_raw_parts_mut(inner.as_ptr().add(range.start), range.len())
+            },
+        }
+    }
+
+    /// Converts the storage into shared storage (unless it already is), returning it together
+    /// with the range of it that this buffer covers.
+    fn share(&mut self) -> (Rc<SharedStorage>, Range<usize>) {
+        if let Mode::Shared { inner, range } = &self.mode {
+            return (Rc::clone(inner), range.clone());
+        }
+
+        // An empty boxed slice does not allocate, so this is a cheap placeholder.
+        let placeholder = Mode::BoxedSlice {
+            inner: Pin::new(Box::default()),
+        };
+
+        let owner = PinnedBuffer {
+            mode: mem::replace(&mut self.mode, placeholder),
+            len: 0,
+            start: 0,
+        };
+
+        let inner = SharedStorage::new(owner);
+        let range = 0..inner.len();
+
+        (inner, range)
+    }
+
+    /// Destroys the buffer without going through the usual drop logic, returning its storage.
+    fn into_mode(self) -> Mode {
         // SAFETY: We are forgetting self, so nobody should mind that we stole its contents.
         let mode = unsafe { ptr::read(&self.mode) };
         mem::forget(self);
-
-        match mode {
-            Mode::Pooled { .. } => unreachable!("we already asserted that this is a boxed slice"),
-            Mode::BoxedSlice { inner } => Pin::into_inner(inner),
-        }
+        mode
     }
 }
 
 impl Drop for PinnedBuffer {
     fn drop(&mut self) {
-        if let Mode::Pooled { index_in_pool, .. } = self.mode {
-            POOL.with(|pool| {
-                let mut pool = pool.borrow_mut();
-                pool.remove(index_in_pool);
-                POOL_DROPPED.with(Event::observe_unit);
-            });
+        if let Mode::Pooled { inner, size } = &mut self.mode {
+            // An empty boxed slice does not allocate, so this is a cheap placeholder.
+            let storage = Pin::into_inner(mem::replace(inner, Pin::new(Box::default())));
+            let size = *size;
+
+            buffer_pool::give_back(storage, size);
+
+            POOL_DROPPED.with(Event::observe_unit);
         }
     }
 }
@@ -262,16 +450,63 @@ impl !Send for PinnedBuffer {}
 #[negative_impl]
 impl !Sync for PinnedBuffer {}
 
+/// The size classes of the buffers in the buffer pool.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum BufferSize {
+    /// 4 KB.
+    Small,
+
+    /// 16 KB.
+    Medium,
+
+    /// 64 KB, the size of buffers obtained via `PinnedBuffer::from_pool()`.
+    Large,
+}
+
+impl BufferSize {
+    /// The capacity of buffers of this size class, in bytes.
+    pub const fn capacity(self) -> usize {
+        match self {
+            BufferSize::Small => SMALL_POOL_BUFFER_CAPACITY_BYTES,
+            BufferSize::Medium => MEDIUM_POOL_BUFFER_CAPACITY_BYTES,
+            BufferSize::Large => POOL_BUFFER_CAPACITY_BYTES,
+        }
+    }
+
+    /// The next smaller size class, if any.
+    pub const fn smaller(self) -> Option<Self> {
+        match self {
+            BufferSize::Small => None,
+            BufferSize::Medium => Some(BufferSize::Small),
+            BufferSize::Large => Some(BufferSize::Medium),
+        }
+    }
+
+    /// The next larger size class, if any.
+    pub const fn larger(self) -> Option<Self> {
+        match self {
+            BufferSize::Small => Some(BufferSize::Medium),
+            BufferSize::Medium => Some(BufferSize::Large),
+            BufferSize::Large => None,
+        }
+    }
+}
+
+const SMALL_POOL_BUFFER_CAPACITY_BYTES: usize = 4 * 1024;
+const MEDIUM_POOL_BUFFER_CAPACITY_BYTES: usize = 16 * 1024;
 const POOL_BUFFER_CAPACITY_BYTES: usize = 64 * 1024;
 
 thread_local! {
-    static POOL: RefCell<PinnedSlabChain<UnsafeCell<[u8; POOL_BUFFER_CAPACITY_BYTES]>>> = RefCell::new(PinnedSlabChain::new());
-
     static CALLER_BUFFERS_REFERENCED: Event = EventBuilder::new()
         .name("caller_buffers_referenced")
         .build()
         .unwrap();
 
+    static ALIGNED_ALLOCATED: Event = EventBuilder::new()
+        .name("aligned_buffers_allocated")
+        .build()
+        .unwrap();
+
     static POOL_ALLOCATED: Event = EventBuilder::new()
         .name("pool_buffers_allocated")
         .build()
@@ -0,0 +1,93 @@
+use crate::mem::{enter_subsystem, Subsystem};
+use std::{
+    alloc::{self, Layout},
+    ptr::NonNull,
+    slice,
+};
+
+/// Zero-initialized storage whose start is aligned to a caller-specified boundary, for I/O with
+/// devices that require aligned buffers (e.g. files opened with `FILE_FLAG_NO_BUFFERING`).
+pub(super) struct AlignedStorage {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl AlignedStorage {
+    /// # Panics
+    ///
+    /// Panics if `len` is zero or if `align` is not a power of two.
+    pub(super) fn new(len: usize, align: usize) -> Self {
+        assert!(len > 0, "aligned buffer must not be empty");
+
+        let layout = Layout::from_size_align(len, align)
+            .expect("alignment must be a power of two and the length must fit in isize");
+
+        // Allocating the storage is charged to the buffers subsystem, same as for pooled buffers.
+        let _subsystem = enter_subsystem(Subsystem::Buffers);
+
+        // SAFETY: The layout has a nonzero size, as we asserted above.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+
+        let Some(ptr) = NonNull::new(ptr) else {
+            alloc::handle_alloc_error(layout);
+        };
+
+        Self { ptr, layout }
+    }
+
+    pub(super) fn align(&self) -> usize {
+        self.layout.align()
+    }
+
+    pub(super) fn as_slice(&self) -> &[u8] {
+        // SAFETY: We own the allocation of `layout.size()` initialized bytes until we are dropped.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: We own the allocation of `layout.size()` initialized bytes until we are dropped
+        // and the exclusive borrow of self guarantees there are no other references into it.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedStorage {
+    fn drop(&mut self) {
+        let _subsystem = enter_subsystem(Subsystem::Buffers);
+
+        // SAFETY: We allocated the memory with this layout and nothing references it anymore.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_is_aligned_and_zeroed() {
+        for align in [1, 512, 4096, 64 * 1024] {
+            let mut storage = AlignedStorage::new(4096, align);
+
+            assert_eq!(storage.as_slice().as_ptr() as usize % align, 0);
+            assert_eq!(storage.align(), align);
+            assert_eq!(storage.as_slice().len(), 4096);
+            assert!(storage.as_slice().iter().all(|b| *b == 0));
+
+            storage.as_mut_slice()[4095] = 42;
+            assert_eq!(storage.as_slice()[4095], 42);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn align_must_be_power_of_two() {
+        AlignedStorage::new(4096, 3000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn len_must_not_be_zero() {
+        AlignedStorage::new(0, 4096);
+    }
+}
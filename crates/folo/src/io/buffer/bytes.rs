@@ -0,0 +1,56 @@
+//! Conversions between `PinnedBuffer` and the `Bytes`/`BytesMut` types of the `bytes` crate,
+//! enabled by the `bytes` feature.
+//!
+//! Conversions reuse the existing allocation where its ownership allows: caller-provided storage
+//! (`PinnedBuffer::from_vec()` and `from_boxed_slice()`) and uniquely owned `bytes` allocations
+//! change hands without copying. Buffers from the buffer pool are copied because their storage
+//! goes back to the pool, as are `Bytes` that share their allocation with other references.
+
+use super::PinnedBuffer;
+use bytes::{Bytes, BytesMut};
+
+impl From<BytesMut> for PinnedBuffer {
+    /// Takes over the allocation of the `BytesMut` without copying, unless it shares the
+    /// allocation with other references. The buffer covers the contents of the `BytesMut`.
+    fn from(value: BytesMut) -> Self {
+        PinnedBuffer::from_vec(Vec::from(value))
+    }
+}
+
+impl From<Bytes> for PinnedBuffer {
+    /// Takes over the allocation of the `Bytes` without copying if it is the only reference to
+    /// the allocation and copies the contents otherwise. The buffer covers the contents of the
+    /// `Bytes`.
+    fn from(value: Bytes) -> Self {
+        PinnedBuffer::from(BytesMut::from(value))
+    }
+}
+
+impl From<PinnedBuffer> for BytesMut {
+    /// Converts the active region of the buffer, without copying if the buffer was created from
+    /// caller-provided storage.
+    fn from(value: PinnedBuffer) -> Self {
+        if !value.is_caller_storage() {
+            return BytesMut::from(value.as_slice());
+        }
+
+        let region = value.active_region();
+
+        let mut bytes = BytesMut::from(Bytes::from(value.into_inner_vec()));
+        bytes.truncate(region.end);
+        bytes.split_off(region.start)
+    }
+}
+
+impl From<PinnedBuffer> for Bytes {
+    /// Converts the active region of the buffer, without copying if the buffer was created from
+    /// caller-provided storage.
+    fn from(value: PinnedBuffer) -> Self {
+        if !value.is_caller_storage() {
+            return Bytes::copy_from_slice(value.as_slice());
+        }
+
+        let region = value.active_region();
+        Bytes::from(value.into_inner_vec()).slice(region)
+    }
+}
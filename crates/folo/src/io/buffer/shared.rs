@@ -0,0 +1,44 @@
+use super::PinnedBuffer;
+use std::{ptr::NonNull, rc::Rc};
+
+/// The storage of a buffer that has been split into pieces, which stays alive (and, for pooled
+/// buffers, out of the pool) as long as any of the pieces does.
+///
+/// Each piece covers a range of the storage that does not overlap with the range of any other
+/// piece, so each piece can access its range as if it owned it.
+pub(super) struct SharedStorage {
+    // Points to the start of the storage of `owner`, which does not move while we hold it.
+    ptr: NonNull<u8>,
+    len: usize,
+
+    owner: PinnedBuffer,
+}
+
+impl SharedStorage {
+    pub(super) fn new(owner: PinnedBuffer) -> Rc<Self> {
+        let mut shared = Rc::new(Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            owner,
+        });
+
+        // We take the pointer only once the owner has reached its final location, after which we
+        // never touch the owner again until it is dropped.
+        let shared_mut = Rc::get_mut(&mut shared).expect("we just created the Rc");
+        let storage = shared_mut.owner.storage_mut();
+        shared_mut.len = storage.len();
+        shared_mut.ptr = NonNull::new(storage.as_mut_ptr()).expect("slice pointers are not null");
+
+        shared
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The start of the storage. Each piece may only access its own range of the storage through
+    /// this pointer.
+    pub(super) fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+}
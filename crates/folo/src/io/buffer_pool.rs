@@ -0,0 +1,393 @@
+use crate::{
+    io::{BufferSize, PinnedBuffer},
+    mem::{enter_subsystem, Subsystem},
+    metrics::{Event, EventBuilder, Magnitude},
+};
+use std::{cell::RefCell, collections::VecDeque};
+
+/// The default for the maximum number of bytes each thread's buffer pool holds on to, including
+/// buffers in use.
+pub const DEFAULT_BUFFER_POOL_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// The buffer pool of the current thread, which hands out `PinnedBuffer`s by size class and
+/// reclaims them when they are dropped, to be reused by later buffers of the same size class.
+///
+/// Every thread has its own pool, so there is nothing to create or pass around - all the functions
+/// operate on the pool of the current thread. `PinnedBuffer::from_pool()` and
+/// `PinnedBuffer::from_pool_with_size()` take buffers from the same pool.
+///
+/// The memory the pool keeps around is limited by a budget per thread (see
+/// `RuntimeConfig::buffer_pool_max_bytes()`) but buffers are handed out even if the budget is
+/// exceeded by the buffers in use.
+#[derive(Debug)]
+pub enum BufferPool {}
+
+impl BufferPool {
+    /// Takes a buffer of the specified size class, with the entire buffer as the active region.
+    pub fn take(size: BufferSize) -> PinnedBuffer {
+        PinnedBuffer::from_pool_with_size(size)
+    }
+
+    /// Takes a buffer of the smallest size class that holds `len` bytes, with the active region
+    /// set to the first `len` bytes.
+    ///
+    /// Lengths beyond the capacity of the largest size class are allocated directly, without
+    /// involving the pool.
+    pub fn take_for_len(len: usize) -> PinnedBuffer {
+        let size = [BufferSize::Small, BufferSize::Medium, BufferSize::Large]
+            .into_iter()
+            .find(|size| size.capacity() >= len);
+
+        let mut buffer = match size {
+            Some(size) => Self::take(size),
+            None => PinnedBuffer::from_boxed_slice(vec![0; len].into_boxed_slice()),
+        };
+
+        buffer.set_len(len);
+        buffer
+    }
+
+    /// The statistics of the buffer pool of the current thread.
+    pub fn stats() -> BufferPoolStats {
+        CURRENT
+            .try_with(|pool| pool.borrow().stats())
+            .unwrap_or_default()
+    }
+}
+
+/// Statistics of the buffer pool of one thread, obtained via `BufferPool::stats()`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BufferPoolStats {
+    /// Number of buffers taken from the pool that reused an idle buffer.
+    pub hits: u64,
+
+    /// Number of buffers taken from the pool that had to be allocated because there was no idle
+    /// buffer of the size class.
+    pub misses: u64,
+
+    /// Number of buffers taken from the pool that have not yet been dropped.
+    pub outstanding_buffers: usize,
+    pub outstanding_bytes: usize,
+
+    /// Number of buffers the pool holds on to for reuse.
+    pub idle_buffers: usize,
+    pub idle_bytes: usize,
+}
+
+impl BufferPoolStats {
+    /// The fraction of buffers taken from the pool that reused an idle buffer, or 0 if no buffers
+    /// have been taken.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+/// The buffers of one thread. Buffers are allocated on demand and returned to the pool when
+/// dropped, to be reused by later allocations of the same size class.
+///
+/// The pool keeps the memory it holds (in use + idle) under a byte budget by releasing idle
+/// buffers, least recently used first, regardless of size class. Buffers in use cannot be
+/// released, so allocations always succeed - the budget only bounds how much memory stays around
+/// once the buffers are no longer needed.
+#[derive(Debug)]
+pub(crate) struct ThreadBuffers {
+    // Indexed by size class. Most recently returned buffers are at the back.
+    idle: [VecDeque<IdleBuffer>; SIZE_CLASS_COUNT],
+
+    in_use_count: usize,
+    in_use_bytes: usize,
+    idle_bytes: usize,
+    max_bytes: usize,
+
+    hits: u64,
+    misses: u64,
+
+    // Increments each time a buffer is returned, so we can tell which idle buffer is the oldest.
+    next_sequence: u64,
+}
+
+#[derive(Debug)]
+struct IdleBuffer {
+    storage: Box<[u8]>,
+    returned_sequence: u64,
+}
+
+impl ThreadBuffers {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Self {
+            idle: Default::default(),
+            in_use_count: 0,
+            in_use_bytes: 0,
+            idle_bytes: 0,
+            max_bytes,
+            hits: 0,
+            misses: 0,
+            next_sequence: 0,
+        }
+    }
+
+    /// Takes a buffer of the specified size class, reusing the most recently returned idle buffer
+    /// of that size class if there is one.
+    pub(crate) fn take(&mut self, size: BufferSize) -> Box<[u8]> {
+        let capacity = size.capacity();
+        self.in_use_count += 1;
+        self.in_use_bytes += capacity;
+
+        OUTSTANDING_BUFFERS.with(|x| x.observe(self.in_use_count as Magnitude));
+
+        if let Some(idle) = self.idle[size_class_index(size)].pop_back() {
+            self.idle_bytes -= capacity;
+            self.hits += 1;
+            HITS.with(Event::observe_unit);
+
+            return idle.storage;
+        }
+
+        self.misses += 1;
+        MISSES.with(Event::observe_unit);
+
+        // Buffers are overwritten by I/O operations before being read, so the contents do not
+        // matter. Zeroed allocations are cheap, as the operating system hands out zeroed pages.
+        let storage = vec![0; capacity].into_boxed_slice();
+
+        // Growing the pool may push it over budget, in which case we release idle buffers of
+        // other size classes to make room.
+        self.evict_over_budget();
+
+        storage
+    }
+
+    /// Returns a buffer taken from the pool, to be reused later (unless that puts the pool over
+    /// budget).
+    pub(crate) fn give_back(&mut self, storage: Box<[u8]>, size: BufferSize) {
+        debug_assert_eq!(storage.len(), size.capacity());
+
+        self.in_use_count -= 1;
+        self.in_use_bytes -= storage.len();
+        self.idle_bytes += storage.len();
+
+        self.idle[size_class_index(size)].push_back(IdleBuffer {
+            storage,
+            returned_sequence: self.next_sequence,
+        });
+        self.next_sequence += 1;
+
+        self.evict_over_budget();
+    }
+
+    /// Sets the budget, releasing idle buffers right away if the pool is over the new budget.
+    pub(crate) fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.evict_over_budget();
+    }
+
+    /// Total memory held by the pool, in use and idle.
+    pub(crate) fn held_bytes(&self) -> usize {
+        self.in_use_bytes + self.idle_bytes
+    }
+
+    pub(crate) fn idle_bytes(&self) -> usize {
+        self.idle_bytes
+    }
+
+    pub(crate) fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits,
+            misses: self.misses,
+            outstanding_buffers: self.in_use_count,
+            outstanding_bytes: self.in_use_bytes,
+            idle_buffers: self.idle.iter().map(VecDeque::len).sum(),
+            idle_bytes: self.idle_bytes,
+        }
+    }
+
+    fn evict_over_budget(&mut self) {
+        while self.held_bytes() > self.max_bytes {
+            // The least recently used idle buffer is at the front of one of the size classes.
+            let Some(oldest) = self
+                .idle
+                .iter_mut()
+                .filter(|idle| !idle.is_empty())
+                .min_by_key(|idle| idle[0].returned_sequence)
+            else {
+                // Everything is in use, so there is nothing we can release.
+                return;
+            };
+
+            let evicted = oldest
+                .pop_front()
+                .expect("we only consider size classes with idle buffers");
+
+            self.idle_bytes -= evicted.storage.len();
+            drop(evicted);
+
+            BUFFERS_EVICTED.with(Event::observe_unit);
+        }
+    }
+}
+
+const SIZE_CLASS_COUNT: usize = 3;
+
+const OUTSTANDING_BUFFERS_BUCKETS: &[Magnitude] = &[0, 16, 64, 256, 1024, 4096];
+
+fn size_class_index(size: BufferSize) -> usize {
+    match size {
+        BufferSize::Small => 0,
+        BufferSize::Medium => 1,
+        BufferSize::Large => 2,
+    }
+}
+
+/// Takes a buffer of the specified size class from the buffer pool of the current thread.
+pub(crate) fn take(size: BufferSize) -> Box<[u8]> {
+    // Allocating the storage is charged to the buffers subsystem.
+    let _subsystem = enter_subsystem(Subsystem::Buffers);
+
+    CURRENT.with_borrow_mut(|pool| pool.take(size))
+}
+
+/// Returns a buffer to the buffer pool of the current thread.
+pub(crate) fn give_back(storage: Box<[u8]>, size: BufferSize) {
+    let _subsystem = enter_subsystem(Subsystem::Buffers);
+
+    // Buffers dropped while the thread is shutting down may outlive the pool, in which case the
+    // storage is simply released.
+    _ = CURRENT.try_with(|pool| pool.borrow_mut().give_back(storage, size));
+}
+
+/// Sets the budget of the buffer pool of the current thread. Called by the runtime when starting
+/// its worker threads.
+pub(crate) fn set_max_bytes(max_bytes: usize) {
+    CURRENT.with_borrow_mut(|pool| pool.set_max_bytes(max_bytes));
+}
+
+thread_local! {
+    static CURRENT: RefCell<ThreadBuffers> = RefCell::new(ThreadBuffers::new(DEFAULT_BUFFER_POOL_MAX_BYTES));
+
+    static BUFFERS_EVICTED: Event = EventBuilder::new()
+        .name("pool_buffers_evicted")
+        .build()
+        .unwrap();
+
+    static HITS: Event = EventBuilder::new()
+        .name("pool_buffer_hits")
+        .build()
+        .unwrap();
+
+    static MISSES: Event = EventBuilder::new()
+        .name("pool_buffer_misses")
+        .build()
+        .unwrap();
+
+    // Observed whenever a buffer is taken, with the number of buffers in use including it.
+    static OUTSTANDING_BUFFERS: Event = EventBuilder::new()
+        .name("pool_buffers_outstanding")
+        .buckets(OUTSTANDING_BUFFERS_BUCKETS)
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: usize = BufferSize::Small.capacity();
+    const LARGE: usize = BufferSize::Large.capacity();
+
+    #[test]
+    fn reuses_returned_buffers() {
+        let mut pool = ThreadBuffers::new(LARGE * 4);
+
+        let buffer = pool.take(BufferSize::Large);
+        let address = buffer.as_ptr();
+        pool.give_back(buffer, BufferSize::Large);
+
+        assert_eq!(pool.idle_bytes(), LARGE);
+
+        let buffer = pool.take(BufferSize::Large);
+        assert_eq!(buffer.as_ptr(), address);
+        assert_eq!(pool.idle_bytes(), 0);
+        assert_eq!(pool.held_bytes(), LARGE);
+
+        pool.give_back(buffer, BufferSize::Large);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_across_size_classes() {
+        let mut pool = ThreadBuffers::new(LARGE + SMALL * 2);
+
+        let small_a = pool.take(BufferSize::Small);
+        let small_b = pool.take(BufferSize::Small);
+        let large = pool.take(BufferSize::Large);
+
+        // Returned in this order, so small_a is the least recently used.
+        let small_b_address = small_b.as_ptr();
+        pool.give_back(small_a, BufferSize::Small);
+        pool.give_back(large, BufferSize::Large);
+        pool.give_back(small_b, BufferSize::Small);
+        assert_eq!(pool.held_bytes(), LARGE + SMALL * 2);
+
+        pool.set_max_bytes(LARGE + SMALL);
+
+        // Only small_b is left of the small buffers.
+        assert_eq!(pool.held_bytes(), LARGE + SMALL);
+        let small = pool.take(BufferSize::Small);
+        assert_eq!(small.as_ptr(), small_b_address);
+
+        // Taking another small buffer grows the pool over budget, evicting the idle large one.
+        let another_small = pool.take(BufferSize::Small);
+        assert_eq!(pool.idle_bytes(), 0);
+        assert_eq!(pool.held_bytes(), SMALL * 2);
+
+        pool.give_back(small, BufferSize::Small);
+        pool.give_back(another_small, BufferSize::Small);
+    }
+
+    #[test]
+    fn counts_hits_and_outstanding_buffers() {
+        let mut pool = ThreadBuffers::new(LARGE * 4);
+
+        let small = pool.take(BufferSize::Small);
+        let large = pool.take(BufferSize::Large);
+
+        let stats = pool.stats();
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.hits, 0);
+        assert_eq!(stats.outstanding_buffers, 2);
+        assert_eq!(stats.outstanding_bytes, SMALL + LARGE);
+        assert_eq!(stats.hit_rate(), 0.0);
+
+        pool.give_back(small, BufferSize::Small);
+        let small = pool.take(BufferSize::Small);
+        pool.give_back(large, BufferSize::Large);
+
+        let stats = pool.stats();
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.outstanding_buffers, 1);
+        assert_eq!(stats.outstanding_bytes, SMALL);
+        assert_eq!(stats.idle_buffers, 1);
+        assert_eq!(stats.idle_bytes, LARGE);
+        assert!((stats.hit_rate() - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        pool.give_back(small, BufferSize::Small);
+    }
+
+    #[test]
+    fn buffers_in_use_exceed_budget() {
+        let mut pool = ThreadBuffers::new(SMALL);
+
+        let a = pool.take(BufferSize::Small);
+        let b = pool.take(BufferSize::Small);
+        assert_eq!(pool.held_bytes(), SMALL * 2);
+
+        pool.give_back(a, BufferSize::Small);
+        assert_eq!(pool.idle_bytes(), 0);
+
+        pool.give_back(b, BufferSize::Small);
+        assert_eq!(pool.idle_bytes(), SMALL);
+    }
+}
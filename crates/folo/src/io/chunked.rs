@@ -0,0 +1,444 @@
+use crate::io::{
+    OperationError, OperationResult, PinnedBuffer, VectoredOperationError, VectoredOperationResult,
+};
+use std::{future::Future, ops::Range};
+
+/// The maximum number of bytes a single native I/O operation can transfer. Operations on larger
+/// buffers are limited to this many bytes, unless the API documents that it transfers larger
+/// buffers in multiple operations.
+pub const MAX_OPERATION_LEN: usize = u32::MAX as usize;
+
+/// Transfers the active region of the buffer via as many sequential operations as needed to stay
+/// within `MAX_OPERATION_LEN` bytes per operation. The callback starts the operation for one chunk
+/// and receives the buffer (with the active region set to the chunk) and the offset of the chunk
+/// from the start of the original active region.
+///
+/// Stops early if an operation transfers fewer bytes than its chunk had (e.g. at the end of a
+/// file). The result has the active region set to all the bytes transferred. On error, the active
+/// region of the returned buffer is set to the bytes transferred before the failed operation.
+pub(crate) async fn transfer_in_chunks<F, Fut>(buffer: PinnedBuffer, transfer: F) -> OperationResult
+where
+    F: FnMut(PinnedBuffer, usize) -> Fut,
+    Fut: Future<Output = OperationResult>,
+{
+    transfer_in_chunks_of(MAX_OPERATION_LEN, ShortTransfer::Stop, buffer, transfer).await
+}
+
+/// Like `transfer_in_chunks()` but for outgoing data, where a short transfer does not mean there is
+/// no more to transfer. After a short transfer, the rest of the chunk is transferred via another
+/// operation. Only stops early if an operation transfers no bytes at all.
+pub(crate) async fn transfer_all_in_chunks<F, Fut>(
+    buffer: PinnedBuffer,
+    transfer: F,
+) -> OperationResult
+where
+    F: FnMut(PinnedBuffer, usize) -> Fut,
+    Fut: Future<Output = OperationResult>,
+{
+    transfer_in_chunks_of(MAX_OPERATION_LEN, ShortTransfer::Resume, buffer, transfer).await
+}
+
+/// Like `transfer_all_in_chunks()` but for vectored operations, transferring the active regions of
+/// the buffers in order. Each operation covers as many of the remaining buffers as fit within
+/// `MAX_OPERATION_LEN` bytes in total, with a buffer longer than that split over multiple
+/// operations. The callback receives the buffers of one operation, with their active regions set
+/// to the parts to transfer.
+///
+/// The results have the active regions set to the bytes transferred from each buffer, with the
+/// buffers in their original order. On error, the same applies to the bytes transferred before the
+/// failed operation.
+pub(crate) async fn transfer_all_vectored_in_chunks<F, Fut>(
+    buffers: Vec<PinnedBuffer>,
+    transfer: F,
+) -> VectoredOperationResult
+where
+    F: FnMut(Vec<PinnedBuffer>) -> Fut,
+    Fut: Future<Output = VectoredOperationResult>,
+{
+    transfer_all_vectored_in_chunks_of(MAX_OPERATION_LEN, buffers, transfer).await
+}
+
+/// What to do when an operation transfers fewer bytes than its chunk had.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ShortTransfer {
+    Stop,
+    Resume,
+}
+
+async fn transfer_in_chunks_of<F, Fut>(
+    max_chunk_len: usize,
+    short_transfer: ShortTransfer,
+    mut buffer: PinnedBuffer,
+    mut transfer: F,
+) -> OperationResult
+where
+    F: FnMut(PinnedBuffer, usize) -> Fut,
+    Fut: Future<Output = OperationResult>,
+{
+    // The common case needs no adjustments to the buffer.
+    if short_transfer == ShortTransfer::Stop && buffer.len() <= max_chunk_len {
+        return transfer(buffer, 0).await;
+    }
+
+    let region = buffer.active_region();
+    let mut transferred = 0;
+
+    while transferred < region.len() {
+        let chunk_len = (region.len() - transferred).min(max_chunk_len);
+        set_region(&mut buffer, region.start + transferred, chunk_len);
+
+        match transfer(buffer, transferred).await {
+            Ok(chunk) => {
+                buffer = chunk;
+                transferred += buffer.len();
+
+                let stop = match short_transfer {
+                    ShortTransfer::Stop => buffer.len() < chunk_len,
+                    ShortTransfer::Resume => buffer.len() == 0,
+                };
+
+                if stop {
+                    break;
+                }
+            }
+            Err(OperationError {
+                inner,
+                buffer: mut chunk,
+            }) => {
+                set_region(&mut chunk, region.start, transferred);
+                return Err(OperationError::new(inner, chunk));
+            }
+        }
+    }
+
+    set_region(&mut buffer, region.start, transferred);
+    Ok(buffer)
+}
+
+async fn transfer_all_vectored_in_chunks_of<F, Fut>(
+    max_chunk_len: usize,
+    mut buffers: Vec<PinnedBuffer>,
+    mut transfer: F,
+) -> VectoredOperationResult
+where
+    F: FnMut(Vec<PinnedBuffer>) -> Fut,
+    Fut: Future<Output = VectoredOperationResult>,
+{
+    let regions: Vec<Range<usize>> = buffers.iter().map(PinnedBuffer::active_region).collect();
+    let mut transferred = vec![0; buffers.len()];
+
+    // The first buffer that has data left to transfer.
+    let mut first = 0;
+
+    loop {
+        while first < buffers.len() && transferred[first] == regions[first].len() {
+            first += 1;
+        }
+
+        if first == buffers.len() {
+            break;
+        }
+
+        let mut chunk_len = 0;
+        let mut end = first;
+
+        while end < buffers.len() && chunk_len < max_chunk_len {
+            let len = (regions[end].len() - transferred[end]).min(max_chunk_len - chunk_len);
+            set_region(
+                &mut buffers[end],
+                regions[end].start + transferred[end],
+                len,
+            );
+
+            chunk_len += len;
+            end += 1;
+        }
+
+        let chunk = buffers.drain(first..end).collect();
+
+        match transfer(chunk).await {
+            Ok(chunk) => {
+                let mut chunk_transferred = 0;
+
+                for (index, buffer) in chunk.iter().enumerate() {
+                    transferred[first + index] += buffer.len();
+                    chunk_transferred += buffer.len();
+                }
+
+                buffers.splice(first..first, chunk);
+
+                if chunk_transferred == 0 {
+                    break;
+                }
+            }
+            Err(VectoredOperationError {
+                inner,
+                buffers: chunk,
+            }) => {
+                buffers.splice(first..first, chunk);
+                set_regions(&mut buffers, &regions, &transferred);
+                return Err(VectoredOperationError::new(inner, buffers));
+            }
+        }
+    }
+
+    set_regions(&mut buffers, &regions, &transferred);
+    Ok(buffers)
+}
+
+fn set_regions(buffers: &mut [PinnedBuffer], regions: &[Range<usize>], lens: &[usize]) {
+    for ((buffer, region), len) in buffers.iter_mut().zip(regions).zip(lens) {
+        set_region(buffer, region.start, *len);
+    }
+}
+
+fn set_region(buffer: &mut PinnedBuffer, start: usize, len: usize) {
+    // The new region may not fit together with the old length, so we clear that first.
+    buffer.set_len(0);
+    buffer.set_start(start);
+    buffer.set_len(len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io;
+    use futures::executor::block_on;
+    use std::cell::{Cell, RefCell};
+
+    #[test]
+    fn small_buffer_is_one_operation() {
+        let calls = RefCell::new(Vec::new());
+
+        let buffer = PinnedBuffer::from_vec(vec![0; 10]);
+        let result = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Stop,
+            buffer,
+            |buffer, offset| {
+                calls.borrow_mut().push((buffer.active_region(), offset));
+                async { Ok(buffer) }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(result.active_region(), 0..10);
+        assert_eq!(*calls.borrow(), [(0..10, 0)]);
+    }
+
+    #[test]
+    fn large_buffer_is_split_into_chunks() {
+        let calls = RefCell::new(Vec::new());
+
+        let mut buffer = PinnedBuffer::from_vec(vec![0; 50]);
+        buffer.set_start(5);
+        buffer.set_len(40);
+
+        let result = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Stop,
+            buffer,
+            |buffer, offset| {
+                calls.borrow_mut().push((buffer.active_region(), offset));
+                async { Ok(buffer) }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(result.active_region(), 5..45);
+        assert_eq!(*calls.borrow(), [(5..21, 0), (21..37, 16), (37..45, 32)]);
+    }
+
+    #[test]
+    fn short_transfer_stops_early() {
+        let buffer = PinnedBuffer::from_vec(vec![0; 40]);
+
+        let result = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Stop,
+            buffer,
+            |mut buffer, offset| {
+                // The second chunk hits the end of the data after 4 bytes.
+                if offset == 16 {
+                    buffer.set_len(4);
+                }
+
+                async { Ok(buffer) }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(result.active_region(), 0..20);
+    }
+
+    #[test]
+    fn short_transfer_is_resumed() {
+        let calls = RefCell::new(Vec::new());
+
+        let buffer = PinnedBuffer::from_vec(vec![0; 20]);
+        let result = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Resume,
+            buffer,
+            |mut buffer, offset| {
+                calls.borrow_mut().push((buffer.active_region(), offset));
+
+                // The first operation only transfers 4 bytes of its chunk.
+                if offset == 0 {
+                    buffer.set_len(4);
+                }
+
+                async { Ok(buffer) }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(result.active_region(), 0..20);
+        assert_eq!(*calls.borrow(), [(0..16, 0), (4..20, 4)]);
+    }
+
+    #[test]
+    fn empty_transfer_stops_resuming() {
+        let buffer = PinnedBuffer::from_vec(vec![0; 20]);
+        let result = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Resume,
+            buffer,
+            |mut buffer, offset| {
+                if offset == 16 {
+                    buffer.set_len(0);
+                }
+
+                async { Ok(buffer) }
+            },
+        ))
+        .unwrap();
+
+        assert_eq!(result.active_region(), 0..16);
+    }
+
+    #[test]
+    fn vectored_buffers_are_grouped_into_chunks() {
+        let calls = RefCell::new(Vec::new());
+
+        let buffers = vec![
+            PinnedBuffer::from_vec(vec![0; 10]),
+            PinnedBuffer::from_vec(vec![0; 20]),
+            PinnedBuffer::from_vec(vec![0; 4]),
+        ];
+
+        let result = block_on(transfer_all_vectored_in_chunks_of(16, buffers, |buffers| {
+            calls.borrow_mut().push(
+                buffers
+                    .iter()
+                    .map(PinnedBuffer::active_region)
+                    .collect::<Vec<_>>(),
+            );
+
+            async { Ok(buffers) }
+        }))
+        .unwrap();
+
+        let regions: Vec<_> = result.iter().map(PinnedBuffer::active_region).collect();
+        assert_eq!(regions, [0..10, 0..20, 0..4]);
+        assert_eq!(
+            *calls.borrow(),
+            [vec![0..10, 0..6], vec![6..20, 0..2], vec![2..4]]
+        );
+    }
+
+    #[test]
+    fn short_vectored_transfer_is_resumed() {
+        let calls = RefCell::new(Vec::new());
+
+        let buffers = vec![
+            PinnedBuffer::from_vec(vec![0; 6]),
+            PinnedBuffer::from_vec(vec![0; 6]),
+        ];
+
+        let result = block_on(transfer_all_vectored_in_chunks_of(
+            16,
+            buffers,
+            |mut buffers| {
+                calls.borrow_mut().push(
+                    buffers
+                        .iter()
+                        .map(PinnedBuffer::active_region)
+                        .collect::<Vec<_>>(),
+                );
+
+                // The first operation only transfers 8 bytes, ending in the second buffer.
+                if buffers.len() == 2 && buffers[0].len() == 6 {
+                    buffers[1].set_len(2);
+                }
+
+                async { Ok(buffers) }
+            },
+        ))
+        .unwrap();
+
+        let regions: Vec<_> = result.iter().map(PinnedBuffer::active_region).collect();
+        assert_eq!(regions, [0..6, 0..6]);
+        assert_eq!(*calls.borrow(), [vec![0..6, 0..6], vec![2..6]]);
+    }
+
+    #[test]
+    fn vectored_error_reports_bytes_transferred_before_it() {
+        let calls = Cell::new(0);
+
+        let buffers = vec![
+            PinnedBuffer::from_vec(vec![0; 16]),
+            PinnedBuffer::from_vec(vec![0; 16]),
+        ];
+
+        let error = block_on(transfer_all_vectored_in_chunks_of(16, buffers, |buffers| {
+            calls.set(calls.get() + 1);
+
+            // The first operation transfers the first buffer, the second one fails.
+            let fail = calls.get() == 2;
+
+            async move {
+                if fail {
+                    Err(VectoredOperationError::new(
+                        io::Error::ConnectionReset,
+                        buffers,
+                    ))
+                } else {
+                    Ok(buffers)
+                }
+            }
+        }))
+        .unwrap_err();
+
+        assert!(matches!(error.inner, io::Error::ConnectionReset));
+
+        let regions: Vec<_> = error
+            .buffers
+            .iter()
+            .map(PinnedBuffer::active_region)
+            .collect();
+        assert_eq!(regions, [0..16, 0..0]);
+    }
+
+    #[test]
+    fn error_reports_bytes_transferred_before_it() {
+        let buffer = PinnedBuffer::from_vec(vec![0; 40]);
+
+        let error = block_on(transfer_in_chunks_of(
+            16,
+            ShortTransfer::Stop,
+            buffer,
+            |buffer, offset| async move {
+                if offset == 32 {
+                    Err(OperationError::new(io::Error::ConnectionReset, buffer))
+                } else {
+                    Ok(buffer)
+                }
+            },
+        ))
+        .unwrap_err();
+
+        assert!(matches!(error.inner, io::Error::ConnectionReset));
+        assert_eq!(error.buffer.active_region(), 0..32);
+    }
+}
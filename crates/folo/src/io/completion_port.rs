@@ -4,13 +4,20 @@ use crate::{
     util::{OwnedHandle, ThreadSafe},
 };
 use negative_impl::negative_impl;
-use std::sync::Arc;
-use windows::Win32::{
-    Foundation::{HANDLE, INVALID_HANDLE_VALUE},
-    Storage::FileSystem::SetFileCompletionNotificationModes,
-    System::{
-        WindowsProgramming::{FILE_SKIP_COMPLETION_PORT_ON_SUCCESS, FILE_SKIP_SET_EVENT_ON_HANDLE},
-        IO::CreateIoCompletionPort,
+use std::{ffi::c_void, mem, ptr, sync::Arc};
+use windows::{
+    Wdk::Storage::FileSystem::{
+        FileReplaceCompletionInformation, NtSetInformationFile, FILE_COMPLETION_INFORMATION,
+    },
+    Win32::{
+        Foundation::{HANDLE, INVALID_HANDLE_VALUE, STATUS_SUCCESS},
+        Storage::FileSystem::SetFileCompletionNotificationModes,
+        System::{
+            WindowsProgramming::{
+                FILE_SKIP_COMPLETION_PORT_ON_SUCCESS, FILE_SKIP_SET_EVENT_ON_HANDLE,
+            },
+            IO::{CreateIoCompletionPort, IO_STATUS_BLOCK},
+        },
     },
 };
 
@@ -89,6 +96,44 @@ impl CompletionPort {
         Ok(())
     }
 
+    /// Removes the association between an I/O primitive and the completion port it is bound to, so
+    /// it can be bound to the completion port of another thread.
+    ///
+    /// The primitive must not have any I/O operations in progress, as the completion notifications
+    /// of any such operations would be lost, leaking the operations.
+    pub(crate) fn unbind(handle: &(impl Into<IoPrimitive> + Copy)) -> io::Result<()> {
+        let handle = HANDLE::from((*handle).into());
+
+        // A null port removes the association instead of replacing it.
+        let info = FILE_COMPLETION_INFORMATION {
+            Port: HANDLE::default(),
+            Key: ptr::null_mut(),
+        };
+
+        let mut status_block = IO_STATUS_BLOCK::default();
+
+        // SAFETY: The information structure and status block are valid for the duration of the
+        // call. We have to assume the caller provided a valid handle (but if not, it will just be
+        // an error result).
+        let status = unsafe {
+            NtSetInformationFile(
+                handle,
+                &mut status_block,
+                &info as *const _ as *const c_void,
+                mem::size_of::<FILE_COMPLETION_INFORMATION>() as u32,
+                FileReplaceCompletionInformation,
+            )
+        };
+
+        if status != STATUS_SUCCESS {
+            return Err(io::Error::Windows(status.into()));
+        }
+
+        PRIMITIVES_UNBOUND.with(Event::observe_unit);
+
+        Ok(())
+    }
+
     /// Obtains a thread-safe handle to the completion port. The primary use case is to give this
     /// to an IoWaker so that it can be used to wake up the thread that owns this completion port.
     pub(crate) fn handle(&self) -> CompletionPortHandle {
@@ -106,4 +151,9 @@ thread_local! {
         .name("io_primitives_bound")
         .build()
         .unwrap();
+
+    static PRIMITIVES_UNBOUND: Event = EventBuilder::new()
+        .name("io_primitives_unbound")
+        .build()
+        .unwrap();
 }
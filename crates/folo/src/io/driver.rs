@@ -1,22 +1,36 @@
 use crate::constants::GENERAL_MILLISECONDS_BUCKETS;
 use crate::io::operation::{Operation, OperationStore};
-use crate::io::{self, CompletionPort, IoPrimitive, IoWaker, PinnedBuffer, WAKE_UP_COMPLETION_KEY};
+use crate::io::{
+    self, CompletionPort, IoPrimitive, IoWaker, LatencySlos, PinnedBuffer, RioCompletionQueue,
+    RioSocket, RIO_NOTIFY_COMPLETION_KEY, WAKE_UP_COMPLETION_KEY,
+};
 use crate::metrics::{Event, EventBuilder, Magnitude};
-use std::mem::{self, MaybeUninit};
+use crate::rt::clock;
+use std::{
+    mem::{self, MaybeUninit},
+    num::NonZeroUsize,
+    rc::Rc,
+    sync::Arc,
+};
 use windows::Win32::{
     Foundation::WAIT_TIMEOUT,
+    Networking::WinSock::SOCKET,
     System::IO::{GetQueuedCompletionStatusEx, OVERLAPPED_ENTRY},
 };
 use windows_result::HRESULT;
 
-/// Max number of I/O operations to dequeue in one go. Presumably getting more data from the OS with
-/// a single call is desirable but the exact impact of different values on performance is not known.
+/// Default max number of I/O operations to dequeue in one go. Presumably getting more data from the
+/// OS with a single call is desirable but the exact impact of different values on performance is
+/// not known.
 ///
 /// Known aspects of performance impact:
 /// * GetQueuedCompletionStatusEx duration seems linearly affected under non-concurrent synthetic
 ///   message load (e.g. 40 us for 1024 items).
 pub const IO_DEQUEUE_BATCH_SIZE: usize = 1024;
 
+/// Default max number of batches of I/O completions to dequeue per call to `process_completions()`.
+pub const IO_DEQUEUE_MAX_BATCHES: usize = 1;
+
 /// Processes I/O completion operations for a given thread as part of the async worker loop.
 ///
 /// # Safety
@@ -35,23 +49,57 @@ pub(crate) struct Driver {
     //
     // This does not store the read/write buffers, only the operation metadata.
     operation_store: OperationStore,
+
+    // The Registered I/O completion queue, created when the first socket using Registered I/O is
+    // registered. Polled for completions whenever we process completions from the completion port.
+    rio: Option<Rc<RioCompletionQueue>>,
+
+    // Receives the I/O completions dequeued from the completion port. Its length is the batch
+    // size - the max number of completions we dequeue with one call to the OS.
+    completed: Box<[MaybeUninit<OVERLAPPED_ENTRY>]>,
+
+    // If a batch comes back full, there is probably more waiting, so we dequeue up to this many
+    // batches before returning to the caller.
+    max_batches: NonZeroUsize,
 }
 
 impl Driver {
     /// # Safety
     ///
     /// See safety requirements on the type.
-    pub(crate) unsafe fn new() -> Self {
+    pub(crate) unsafe fn new(
+        latency_slos: Option<Arc<LatencySlos>>,
+        batch_size: NonZeroUsize,
+        max_batches: NonZeroUsize,
+    ) -> Self {
+        // The OS takes the batch size as a u32, so anything beyond that would never be filled.
+        let batch_size = batch_size.get().min(u32::MAX as usize);
+
         Self {
             completion_port: CompletionPort::new(),
-            operation_store: OperationStore::new(),
+            operation_store: OperationStore::new(latency_slos),
+            rio: None,
+            completed: vec![MaybeUninit::uninit(); batch_size].into_boxed_slice(),
+            max_batches,
         }
     }
 
+    /// The max number of completions dequeued from the completion port with one call to the OS.
+    #[cfg(test)]
+    pub(crate) fn batch_size(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// The max number of batches dequeued per call to `process_completions()`.
+    #[cfg(test)]
+    pub(crate) fn max_batches(&self) -> NonZeroUsize {
+        self.max_batches
+    }
+
     /// Whether the driver has entered a state where it is safe to drop it. This requires that all
     /// ongoing I/O operations be completed and the completion notification received.
     pub fn is_inert(&self) -> bool {
-        self.operation_store.is_empty()
+        self.operation_store.is_empty() && self.rio.as_ref().is_none_or(|rio| rio.is_inert())
     }
 
     /// Binds an I/O primitive to the completion port of this driver, provided a handle to the I/O
@@ -70,7 +118,9 @@ impl Driver {
     /// The typical workflow is:
     ///
     /// 1. Call `new_operation()` and pass it a buffer to start the preparations to operate on the
-    ///    buffer. You will get an `Operation` that you can configure (e.g. to set the offset).
+    ///    buffer. You will get an `Operation` that you can configure (e.g. to set the offset or to
+    ///    request cancellation if the caller stops waiting via `cancel_on_drop()` or to set the
+    ///    operation kind for latency monitoring via `set_kind()`).
     ///    Often, you will not need to do any preparation and can just proceed to the next step.
     /// 2. Call `Operation::begin()` to start the operation once all preparation is complete.
     ///    You will need to provide a callback through which you provider the buffer + OVERLAPPED
@@ -83,6 +133,32 @@ impl Driver {
         self.operation_store.new_operation(buffer)
     }
 
+    /// Same as `new_operation()` but for vectored I/O that uses multiple buffers at once (up to
+    /// `MAX_VECTORED_BUFFERS`). Start it with `begin_vectored()` instead of `begin()`.
+    pub(crate) fn new_vectored_operation(&mut self, buffers: Vec<PinnedBuffer>) -> Operation {
+        self.operation_store.new_vectored_operation(buffers)
+    }
+
+    /// Sets up a socket of this driver to send and receive data via Registered I/O (RIO). The socket
+    /// must have been created with `WSA_FLAG_REGISTERED_IO`. Stream sockets may split received data
+    /// over multiple receives, whereas datagram sockets receive one whole datagram at a time.
+    pub(crate) fn register_rio_socket(
+        &mut self,
+        socket: SOCKET,
+        is_stream: bool,
+    ) -> io::Result<RioSocket> {
+        let queue = match &self.rio {
+            Some(queue) => Rc::clone(queue),
+            None => {
+                let queue = Rc::new(RioCompletionQueue::new(socket, &self.completion_port)?);
+                self.rio = Some(Rc::clone(&queue));
+                queue
+            }
+        };
+
+        RioSocket::new(queue, socket, is_stream)
+    }
+
     /// Obtains a waker that can be used to wake up the I/O driver from another thread when it
     /// is waiting for I/O.
     pub(crate) fn waker(&self) -> IoWaker {
@@ -92,15 +168,53 @@ impl Driver {
     /// Process any I/O completion notifications and return their results to the callers. If there
     /// is no queued I/O, we wait up to `max_wait_time_ms` milliseconds for new I/O activity, after
     /// which we simply return.
-    pub(crate) fn process_completions(&mut self, max_wait_time_ms: u32) {
-        let mut completed: [MaybeUninit<OVERLAPPED_ENTRY>; IO_DEQUEUE_BATCH_SIZE] =
-            [MaybeUninit::uninit(); IO_DEQUEUE_BATCH_SIZE];
-        let mut completed_items: u32 = 0;
+    ///
+    /// Operations whose deadline has expired are canceled first and we never wait past the next
+    /// deadline, so operations time out promptly even if nothing else wakes us up.
+    pub(crate) fn process_completions(&mut self, mut max_wait_time_ms: u32) {
+        self.operation_store.cancel_expired(clock::now());
 
-        // We intentionally do not loop here because we want to give the caller the opportunity to
-        // process received I/O as soon as possible. Otherwise we might start taking too small
-        // chunks out of the I/O completion stream. Tuning the batch size above is valuable to make
-        // sure we make best use of each iteration and do not leave too much queued in the OS.
+        if let Some(deadline) = self.operation_store.next_deadline() {
+            max_wait_time_ms = max_wait_time_ms.min(clock::milliseconds_until(deadline));
+        }
+
+        if let Some(rio) = &self.rio {
+            // RIO completions are picked up by polling. If there were any, we must not sleep
+            // because the caller has work to do. Otherwise, we ask for the completion port to be
+            // notified once they arrive, so we wake up for them.
+            if rio.process_completions() != 0 {
+                max_wait_time_ms = 0;
+            } else if max_wait_time_ms != 0 {
+                rio.arm_notification();
+            }
+        }
+
+        // We only dequeue a limited number of batches because we want to give the caller the
+        // opportunity to process received I/O as soon as possible. Otherwise we might start taking
+        // too small chunks out of the I/O completion stream. Tuning the batch size and count is
+        // valuable to make sure we make best use of each iteration and do not leave too much
+        // queued in the OS.
+        for _ in 0..self.max_batches.get() {
+            let completed_items = self.dequeue_batch(max_wait_time_ms);
+
+            if completed_items < self.completed.len() {
+                break;
+            }
+
+            // The batch was full, so there is probably more waiting. We only pick up what is
+            // already there - the caller has work to do, so there is no reason to wait.
+            max_wait_time_ms = 0;
+        }
+
+        if let Some(rio) = &self.rio {
+            rio.process_completions();
+        }
+    }
+
+    /// Dequeues one batch of I/O completions and processes them, waiting up to `max_wait_time_ms`
+    /// milliseconds if there are none. Returns the number of completions dequeued.
+    fn dequeue_batch(&mut self, max_wait_time_ms: u32) -> usize {
+        let mut completed_items: u32 = 0;
 
         // SAFETY: TODO
         unsafe {
@@ -110,7 +224,10 @@ impl Driver {
                         ***self.completion_port.handle(),
                         // MaybeUninit is a ZST and binary-compatible. We use it to avoid
                         // initializing the array, which is only used for collecting output.
-                        mem::transmute(completed.as_mut_slice()),
+                        mem::transmute::<
+                            &mut [MaybeUninit<OVERLAPPED_ENTRY>],
+                            &mut [OVERLAPPED_ENTRY],
+                        >(&mut self.completed),
                         &mut completed_items as *mut _,
                         max_wait_time_ms,
                         false,
@@ -128,7 +245,7 @@ impl Driver {
                         WAIT_TIMEOUTS.with(Event::observe_unit);
                     }
 
-                    return;
+                    return 0;
                 }
                 Err(e) => panic!("unexpected error from GetQueuedCompletionStatusEx: {:?}", e),
             }
@@ -136,7 +253,7 @@ impl Driver {
             ASYNC_COMPLETIONS_DEQUEUED.with(|x| x.observe(completed_items as Magnitude));
 
             for index in 0..completed_items {
-                let overlapped_entry = completed[index as usize].assume_init();
+                let overlapped_entry = self.completed[index as usize].assume_init();
 
                 // If the completion key matches our magic value, this is a wakeup packet and needs
                 // special processing.
@@ -146,9 +263,21 @@ impl Driver {
                     continue;
                 }
 
+                // This says that RIO completions have arrived, which we pick up below. The
+                // OVERLAPPED pointer is owned by the RIO completion queue and has no meaning.
+                if overlapped_entry.lpCompletionKey == RIO_NOTIFY_COMPLETION_KEY {
+                    if let Some(rio) = &self.rio {
+                        rio.notification_received();
+                    }
+
+                    continue;
+                }
+
                 self.operation_store.complete_operation(overlapped_entry);
             }
         }
+
+        completed_items as usize
     }
 }
 
@@ -1,5 +1,12 @@
+use std::io::ErrorKind;
 use thiserror::Error;
-use windows::Win32::Networking::WinSock::WSA_ERROR;
+use windows::Win32::{
+    Foundation::{
+        ERROR_OPERATION_ABORTED, STATUS_CANCELLED, STATUS_CONNECTION_ABORTED,
+        STATUS_CONNECTION_RESET, WIN32_ERROR,
+    },
+    Networking::WinSock::{WSAECONNABORTED, WSAECONNRESET, WSA_ERROR},
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -9,6 +16,17 @@ pub enum Error {
     #[error("Winsock error {} ({})", .code, .detail.0)]
     Winsock { code: i32, detail: WSA_ERROR },
 
+    #[error("operation timed out")]
+    TimedOut,
+
+    #[error("operation was canceled")]
+    Canceled,
+
+    /// The connection was forcibly closed (e.g. the peer sent a TCP RST) or aborted locally.
+    /// Distinct from a graceful close by the peer, which is signaled via an empty receive.
+    #[error("connection was reset")]
+    ConnectionReset,
+
     #[error(transparent)]
     Windows(#[from] windows_result::Error),
 
@@ -21,4 +39,50 @@ pub enum Error {
     Internal(String),
 }
 
+impl Error {
+    /// Whether this is an error that the OS uses to report a reset or aborted connection. The
+    /// same condition can surface in different forms depending on whether the operation failed
+    /// immediately (Winsock error) or via an I/O completion (NTSTATUS).
+    pub(crate) fn is_connection_reset(&self) -> bool {
+        match self {
+            Error::ConnectionReset => true,
+            Error::Winsock { detail, .. } => *detail == WSAECONNRESET || *detail == WSAECONNABORTED,
+            Error::Windows(e) => [
+                STATUS_CONNECTION_RESET.to_hresult(),
+                STATUS_CONNECTION_ABORTED.to_hresult(),
+                WIN32_ERROR(WSAECONNRESET.0 as u32).to_hresult(),
+                WIN32_ERROR(WSAECONNABORTED.0 as u32).to_hresult(),
+            ]
+            .contains(&e.code()),
+            _ => false,
+        }
+    }
+
+    /// Whether this is an error that the OS uses to report that an operation was canceled (e.g.
+    /// via `CancelIoEx`) before it could complete.
+    pub(crate) fn is_canceled(&self) -> bool {
+        match self {
+            Error::Canceled => true,
+            Error::Windows(e) => [
+                STATUS_CANCELLED.to_hresult(),
+                ERROR_OPERATION_ABORTED.to_hresult(),
+            ]
+            .contains(&e.code()),
+            _ => false,
+        }
+    }
+
+    /// Converts the error for APIs that use `std::io::Error`, preserving the error kind where the
+    /// standard library has a matching one.
+    pub(crate) fn into_std(self) -> std::io::Error {
+        match self {
+            Error::StdIo(e) => e,
+            Error::ConnectionReset => std::io::Error::new(ErrorKind::ConnectionReset, self),
+            Error::TimedOut => std::io::Error::new(ErrorKind::TimedOut, self),
+            Error::InvalidOptions(_) => std::io::Error::new(ErrorKind::InvalidInput, self),
+            _ => std::io::Error::other(self),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
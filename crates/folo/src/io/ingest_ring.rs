@@ -0,0 +1,307 @@
+use crate::io::{self, BufferSize, OperationResult, PinnedBuffer};
+use futures::{future::LocalBoxFuture, FutureExt};
+use negative_impl::negative_impl;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::{poll_fn, Future},
+    num::NonZeroUsize,
+    task,
+};
+
+/// Keeps a set of receives continuously outstanding on a data source (such as a TCP connection),
+/// each into its own buffer, and exposes the received data to the consumer in order as a sequence
+/// of regions. The data stays in place until the consumer commits it, so parsers can hold stable
+/// views of the incoming bytes (including across region boundaries) without copying them.
+///
+/// Once all the bytes of a region have been committed, its buffer is immediately reused for the
+/// next receive, so the operating system always has somewhere to put incoming data without
+/// waiting for the consumer to ask for it.
+///
+/// Receives may complete in any order - the ring takes care of exposing the regions in the order
+/// in which the receives were started, which is the order in which the data arrived.
+///
+/// Obtain one for a TCP connection via `TcpConnection::ingest_ring()` or provide your own source
+/// of receives via `IngestRing::new()`.
+pub struct IngestRing {
+    receive: ReceiveFn,
+
+    // Oldest first. Fully committed regions are removed from the front and their buffers reused
+    // for new receives at the back.
+    slots: VecDeque<Slot>,
+
+    // Set once the data source has ended (or failed), after which we start no more receives.
+    ended: bool,
+}
+
+type ReceiveFn = Box<dyn FnMut(PinnedBuffer) -> LocalBoxFuture<'static, OperationResult>>;
+
+enum Slot {
+    // The receive is only started when first polled, which we always do in slot order.
+    Pending(LocalBoxFuture<'static, OperationResult>),
+
+    // The active region of the buffer is the received data that has not yet been committed.
+    Completed(OperationResult),
+}
+
+impl IngestRing {
+    /// Creates a ring that keeps `depth` receives outstanding, each into a buffer of the
+    /// specified size class. The `receive` function starts a receive into the provided buffer,
+    /// returning it with the active region set to the received data (empty at the end of the
+    /// data).
+    pub fn new<F, R>(depth: NonZeroUsize, size: BufferSize, mut receive: F) -> Self
+    where
+        F: FnMut(PinnedBuffer) -> R + 'static,
+        R: Future<Output = OperationResult> + 'static,
+    {
+        let mut receive: ReceiveFn = Box::new(move |buffer| receive(buffer).boxed_local());
+
+        let slots = (0..depth.get())
+            .map(|_| Slot::Pending(receive(PinnedBuffer::from_pool_with_size(size))))
+            .collect();
+
+        Self {
+            receive,
+            slots,
+            ended: false,
+        }
+    }
+
+    /// Waits until at least one region of received data is available.
+    ///
+    /// Returns `false` once the data source has ended and all the data received before that has
+    /// been committed. If a receive fails, the error is returned once all the data received
+    /// before it has been committed, after which the ring is ended.
+    pub async fn ready(&mut self) -> io::Result<bool> {
+        poll_fn(|cx| self.poll_ready(cx)).await
+    }
+
+    /// The regions of received data that have not yet been committed, in order. Only regions
+    /// that are available without waiting are included - call `ready()` to wait for more.
+    pub fn regions(&self) -> impl Iterator<Item = &[u8]> {
+        self.slots.iter().map_while(|slot| match slot {
+            Slot::Completed(Ok(buffer)) if buffer.len() != 0 => Some(buffer.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// The number of bytes in the regions available without waiting.
+    pub fn available(&self) -> usize {
+        self.regions().map(<[u8]>::len).sum()
+    }
+
+    /// Marks the first `len` bytes of the available regions as consumed. Regions that are
+    /// fully consumed are released and their buffers reused for new receives.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is greater than the number of available bytes.
+    pub fn commit(&mut self, mut len: usize) {
+        while len > 0 {
+            let Some(Slot::Completed(Ok(buffer))) = self.slots.front_mut() else {
+                panic!("cannot commit more bytes than are available");
+            };
+
+            let region_len = buffer.len();
+            assert!(
+                region_len != 0,
+                "cannot commit more bytes than are available"
+            );
+
+            if len < region_len {
+                buffer.set_len(region_len - len);
+                buffer.set_start(buffer.start() + len);
+                return;
+            }
+
+            len -= region_len;
+
+            let Some(Slot::Completed(Ok(buffer))) = self.slots.pop_front() else {
+                unreachable!("we just checked the front slot");
+            };
+
+            if !self.ended {
+                self.slots
+                    .push_back(Slot::Pending((self.receive)(buffer.use_all())));
+            }
+        }
+    }
+
+    fn poll_ready(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<bool>> {
+        // We poll the receives in order, so the later ones are always started after the earlier
+        // ones, which is the order in which the data source fills them.
+        for slot in &mut self.slots {
+            if let Slot::Pending(receive) = slot {
+                if let task::Poll::Ready(result) = receive.poll_unpin(cx) {
+                    let end_of_data = !matches!(&result, Ok(buffer) if buffer.len() != 0);
+                    self.ended |= end_of_data;
+
+                    *slot = Slot::Completed(result);
+                }
+            }
+        }
+
+        match self.slots.front_mut() {
+            Some(Slot::Pending(_)) => task::Poll::Pending,
+            Some(Slot::Completed(Ok(buffer))) if buffer.len() != 0 => task::Poll::Ready(Ok(true)),
+            Some(Slot::Completed(_)) => {
+                // The end of the data (or a failure) is next in line. Anything after it is just
+                // leftovers from receives that were already started, which we cancel.
+                let Some(Slot::Completed(result)) = self.slots.pop_front() else {
+                    unreachable!("we just checked the front slot");
+                };
+
+                self.ended = true;
+                self.slots.clear();
+
+                match result {
+                    Ok(_) => task::Poll::Ready(Ok(false)),
+                    Err(e) => task::Poll::Ready(Err(e.into_inner())),
+                }
+            }
+            None => task::Poll::Ready(Ok(false)),
+        }
+    }
+}
+
+impl fmt::Debug for IngestRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IngestRing")
+            .field("slots", &self.slots.len())
+            .field("available", &self.available())
+            .field("ended", &self.ended)
+            .finish()
+    }
+}
+
+#[negative_impl]
+impl !Send for IngestRing {}
+#[negative_impl]
+impl !Sync for IngestRing {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+    use std::{cell::RefCell, pin::pin, rc::Rc};
+
+    type Started = Rc<RefCell<Vec<(PinnedBuffer, oneshot::Sender<PinnedBuffer>)>>>;
+
+    /// A data source whose receives complete when the test says so, in whatever order it wants.
+    fn ring(depth: usize) -> (IngestRing, Started) {
+        let started = Started::default();
+
+        let ring = IngestRing::new(NonZeroUsize::new(depth).unwrap(), BufferSize::Small, {
+            let started = Rc::clone(&started);
+
+            move |buffer| {
+                let started = Rc::clone(&started);
+
+                async move {
+                    let (tx, rx) = oneshot::channel();
+                    started.borrow_mut().push((buffer, tx));
+                    Ok(rx.await.unwrap())
+                }
+            }
+        });
+
+        (ring, started)
+    }
+
+    fn complete(started: &Started, index: usize, data: &[u8]) {
+        let (mut buffer, tx) = started.borrow_mut().remove(index);
+        buffer
+            .as_mut_slice_with_len(data.len())
+            .copy_from_slice(data);
+        tx.send(buffer).unwrap();
+    }
+
+    fn poll_ready(ring: &mut IngestRing) -> task::Poll<io::Result<bool>> {
+        let cx = &mut task::Context::from_waker(noop_waker_ref());
+        pin!(ring.ready()).poll(cx)
+    }
+
+    #[test]
+    fn regions_exposed_in_order() {
+        let (mut ring, started) = ring(3);
+
+        assert!(poll_ready(&mut ring).is_pending());
+        assert_eq!(started.borrow().len(), 3);
+
+        // The last one completes first but is not exposed before the others.
+        complete(&started, 2, b"ccc");
+        assert!(poll_ready(&mut ring).is_pending());
+        assert_eq!(ring.available(), 0);
+
+        complete(&started, 0, b"a");
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+        assert_eq!(ring.regions().collect::<Vec<_>>(), vec![&b"a"[..]]);
+
+        complete(&started, 0, b"bb");
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+        assert_eq!(
+            ring.regions().collect::<Vec<_>>(),
+            vec![&b"a"[..], &b"bb"[..], &b"ccc"[..]]
+        );
+    }
+
+    #[test]
+    fn commit_reuses_buffers() {
+        let (mut ring, started) = ring(2);
+
+        assert!(poll_ready(&mut ring).is_pending());
+        complete(&started, 0, b"abc");
+        complete(&started, 0, b"def");
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+
+        // Partial commit keeps the rest of the region in place.
+        ring.commit(2);
+        assert_eq!(
+            ring.regions().collect::<Vec<_>>(),
+            vec![&b"c"[..], &b"def"[..]]
+        );
+
+        // Committing across regions releases the first one, starting a new receive.
+        ring.commit(2);
+        assert_eq!(ring.regions().collect::<Vec<_>>(), vec![&b"ef"[..]]);
+
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+        assert_eq!(started.borrow().len(), 1);
+        assert_eq!(started.borrow()[0].0.len(), BufferSize::Small.capacity());
+    }
+
+    #[test]
+    fn ends_after_data_committed() {
+        let (mut ring, started) = ring(2);
+
+        assert!(poll_ready(&mut ring).is_pending());
+        complete(&started, 0, b"abc");
+        complete(&started, 0, b"");
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+
+        ring.commit(3);
+
+        // No new receive after the end of the data.
+        assert!(matches!(
+            poll_ready(&mut ring),
+            task::Poll::Ready(Ok(false))
+        ));
+        assert!(started.borrow().is_empty());
+        assert!(matches!(
+            poll_ready(&mut ring),
+            task::Poll::Ready(Ok(false))
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn commit_more_than_available_panics() {
+        let (mut ring, started) = ring(1);
+
+        assert!(poll_ready(&mut ring).is_pending());
+        complete(&started, 0, b"abc");
+        assert!(matches!(poll_ready(&mut ring), task::Poll::Ready(Ok(true))));
+
+        ring.commit(4);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::metrics::{Event, EventBuilder};
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+/// The kind of an I/O operation, used to apply latency thresholds to operations of that kind.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OperationKind {
+    /// Establishing an outbound connection.
+    Connect,
+
+    /// Accepting an inbound connection (including waiting for one to arrive).
+    Accept,
+
+    /// Receiving data from a socket or pipe (including waiting for the data to arrive).
+    Receive,
+
+    /// Sending data to a socket or pipe.
+    Send,
+
+    /// Reading from a file.
+    Read,
+
+    /// Writing to a file.
+    Write,
+
+    /// Anything else, such as waiting for a connection to be closed.
+    Other,
+}
+
+impl OperationKind {
+    const COUNT: usize = 7;
+
+    const ALL: [OperationKind; Self::COUNT] = [
+        OperationKind::Connect,
+        OperationKind::Accept,
+        OperationKind::Receive,
+        OperationKind::Send,
+        OperationKind::Read,
+        OperationKind::Write,
+        OperationKind::Other,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OperationKind::Connect => "connect",
+            OperationKind::Accept => "accept",
+            OperationKind::Receive => "receive",
+            OperationKind::Send => "send",
+            OperationKind::Read => "read",
+            OperationKind::Write => "write",
+            OperationKind::Other => "other",
+        }
+    }
+}
+
+/// An I/O operation that took longer than the latency threshold for its kind.
+#[derive(Clone, Copy, Debug)]
+pub struct LatencyBreach {
+    pub kind: OperationKind,
+    pub threshold: Duration,
+    pub duration: Duration,
+}
+
+/// Latency thresholds for I/O operations, given to `RuntimeBuilder::latency_slos()`. Whenever an
+/// operation that completes asynchronously takes longer than the threshold for its kind, the
+/// breach is counted in the `io_latency_breaches_<kind>` metric of the worker that owns the
+/// operation and the breach callback (if any) is called.
+///
+/// This is a cheap way to monitor latency objectives in-process without exporting full latency
+/// histograms. Durations are measured with a low-precision clock (with a resolution of 10-16
+/// milliseconds), so thresholds below a few tens of milliseconds are not meaningful.
+///
+/// Operations that complete immediately are never considered, as they do not wait for anything.
+#[derive(Clone, Default)]
+pub struct LatencySlos {
+    thresholds: [Option<Duration>; OperationKind::COUNT],
+    on_breach: Option<BreachCallback>,
+}
+
+type BreachCallback = Arc<dyn Fn(&LatencyBreach) + Send + Sync + 'static>;
+
+impl LatencySlos {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the latency threshold for operations of the specified kind, replacing any previously
+    /// set for that kind. Operations of kinds without a threshold are not monitored.
+    pub fn threshold(mut self, kind: OperationKind, value: Duration) -> Self {
+        self.thresholds[kind.index()] = Some(value);
+        self
+    }
+
+    /// Registers a function to call on every breach, on the async worker thread that owns the
+    /// operation, right before the result of the operation is delivered. The function must be
+    /// quick, as it delays the processing of other I/O completions on the worker, and must not
+    /// start I/O operations of its own.
+    pub fn on_breach<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&LatencyBreach) + Send + Sync + 'static,
+    {
+        self.on_breach = Some(Arc::new(f));
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.thresholds.iter().all(Option::is_none)
+    }
+
+    /// Checks the duration of a completed operation against the threshold for its kind.
+    pub(crate) fn check(&self, kind: OperationKind, duration: Duration) {
+        let Some(threshold) = self.thresholds[kind.index()] else {
+            return;
+        };
+
+        if duration <= threshold {
+            return;
+        }
+
+        BREACHES.with(|events| events[kind.index()].observe_unit());
+
+        if let Some(on_breach) = &self.on_breach {
+            on_breach(&LatencyBreach {
+                kind,
+                threshold,
+                duration,
+            });
+        }
+    }
+}
+
+impl Debug for LatencySlos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let thresholds = OperationKind::ALL
+            .iter()
+            .filter_map(|kind| self.thresholds[kind.index()].map(|value| (kind, value)))
+            .collect::<Vec<_>>();
+
+        f.debug_struct("LatencySlos")
+            .field("thresholds", &thresholds)
+            .field("on_breach", &self.on_breach.is_some())
+            .finish()
+    }
+}
+
+thread_local! {
+    static BREACHES: [Event; OperationKind::COUNT] = OperationKind::ALL.map(|kind| {
+        EventBuilder::new()
+            .name(format!("io_latency_breaches_{}", kind.name()))
+            .build()
+            .unwrap()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn only_breaches_of_monitored_kinds_are_reported() {
+        let breaches = Arc::new(Mutex::new(Vec::new()));
+
+        let slos = LatencySlos::new()
+            .threshold(OperationKind::Receive, Duration::from_millis(50))
+            .on_breach({
+                let breaches = Arc::clone(&breaches);
+                move |breach| breaches.lock().unwrap().push(*breach)
+            });
+
+        slos.check(OperationKind::Receive, Duration::from_millis(50));
+        slos.check(OperationKind::Send, Duration::from_secs(10));
+        slos.check(OperationKind::Receive, Duration::from_millis(51));
+
+        let breaches = breaches.lock().unwrap();
+        assert_eq!(breaches.len(), 1);
+        assert_eq!(breaches[0].kind, OperationKind::Receive);
+        assert_eq!(breaches[0].threshold, Duration::from_millis(50));
+        assert_eq!(breaches[0].duration, Duration::from_millis(51));
+    }
+
+    #[test]
+    fn empty_without_thresholds() {
+        assert!(LatencySlos::new().is_empty());
+        assert!(!LatencySlos::new()
+            .threshold(OperationKind::Accept, Duration::from_secs(1))
+            .is_empty());
+    }
+}
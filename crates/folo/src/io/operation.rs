@@ -1,22 +1,36 @@
-use super::PinnedBuffer;
+use super::{IoPrimitive, LatencySlos, OperationKind, PinnedBuffer};
 use crate::{
     constants::{GENERAL_BYTES_BUCKETS, GENERAL_MILLISECONDS_BUCKETS},
     io,
+    mem::{enter_subsystem, Subsystem},
     metrics::{Event, EventBuilder, Magnitude},
-    util::{LowPrecisionInstant, PinnedSlabChain},
+    rt::clock,
+    sync::CancellationToken,
+    util::{
+        once_event::{self, OnceEvent, OnceEventEmbeddedStorage},
+        LowPrecisionInstant, PinnedSlabChain,
+    },
 };
+use futures::future::{self, Either};
 use negative_impl::negative_impl;
 use std::{
-    cell::{RefCell, UnsafeCell},
+    any::Any,
+    cell::{Cell, RefCell, UnsafeCell},
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
     fmt,
     mem::{self, ManuallyDrop},
+    pin::{pin, Pin},
     ptr,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use tracing::{event, Level};
+use tracing::{event, field, span, Instrument, Level, Span};
 use windows::Win32::{
-    Foundation::{ERROR_IO_PENDING, NTSTATUS, STATUS_SUCCESS},
+    Foundation::{ERROR_IO_PENDING, HANDLE, NTSTATUS, STATUS_SUCCESS},
     Networking::WinSock::{SOCKET_ERROR, WSA_IO_PENDING},
-    System::IO::{OVERLAPPED, OVERLAPPED_ENTRY},
+    System::IO::{CancelIoEx, OVERLAPPED, OVERLAPPED_ENTRY},
 };
 
 /// Maintains the backing storage for the metadata structures of I/O operations submitted to the
@@ -42,12 +56,28 @@ pub(super) struct OperationStore {
     // reference from the slab chain and giving it to the operating system to mutate, which would
     // be invalid Rust without Unsafecell.
     items: RefCell<PinnedSlabChain<UnsafeCell<OperationCore>>>,
+
+    // If set, the durations of asynchronously completed operations are checked against these.
+    latency_slos: Option<Arc<LatencySlos>>,
+
+    // The deadlines of operations in progress, earliest first. Entries are not removed right away
+    // when the operation completes, as that would require a search. Instead, we count the stale
+    // entries and compact the heap once they make up half of it.
+    deadlines: RefCell<BinaryHeap<Reverse<DeadlineEntry>>>,
+    stale_deadlines: Cell<usize>,
+
+    // Tie-breaker to keep the order of deadlines that are equal stable.
+    next_deadline_sequence: Cell<u64>,
 }
 
 impl OperationStore {
-    pub fn new() -> Self {
+    pub fn new(latency_slos: Option<Arc<LatencySlos>>) -> Self {
         Self {
             items: RefCell::new(PinnedSlabChain::new()),
+            latency_slos,
+            deadlines: RefCell::new(BinaryHeap::new()),
+            stale_deadlines: Cell::new(0),
+            next_deadline_sequence: Cell::new(0),
         }
     }
 
@@ -61,14 +91,124 @@ impl OperationStore {
     /// data, which the operation takes ownership of. Once the operation has completed, the buffer
     /// is returned to the caller for reading, reuse or disposal.
     pub fn new_operation(&self, buffer: PinnedBuffer) -> Operation {
+        self.insert_operation(buffer, Vec::new())
+    }
+
+    /// Creates a new operation for performing vectored I/O (scatter/gather) on multiple buffers at
+    /// once. The operation takes ownership of the buffers and returns them all to the caller once
+    /// the operation has completed, with the transferred bytes spread over the buffers in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffers` is empty or has more than `MAX_VECTORED_BUFFERS` items.
+    pub fn new_vectored_operation(&self, buffers: Vec<PinnedBuffer>) -> Operation {
+        assert!(
+            !buffers.is_empty() && buffers.len() <= MAX_VECTORED_BUFFERS,
+            "vectored operations require between 1 and {MAX_VECTORED_BUFFERS} buffers"
+        );
+
+        let mut buffers = buffers.into_iter();
+        let buffer = buffers
+            .next()
+            .expect("we verified above that there is at least one");
+
+        self.insert_operation(buffer, buffers.collect())
+    }
+
+    /// The earliest deadline of any operation in progress, if any have deadlines.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let mut deadlines = self.deadlines.borrow_mut();
+
+        // Completed operations have no need to wake up the I/O driver.
+        while deadlines
+            .peek()
+            .is_some_and(|entry| entry.0.state.completed.get())
+        {
+            deadlines.pop();
+            self.stale_deadlines.set(self.stale_deadlines.get() - 1);
+        }
+
+        deadlines.peek().map(|entry| entry.0.deadline)
+    }
+
+    /// Cancels all operations in progress with a deadline at or before `now`. The operating system
+    /// then completes them with a cancellation status, which we report as `io::Error::TimedOut`.
+    pub fn cancel_expired(&self, now: Instant) {
+        let mut deadlines = self.deadlines.borrow_mut();
+
+        while let Some(entry) = deadlines.peek() {
+            if entry.0.deadline > now {
+                break;
+            }
+
+            let entry = deadlines.pop().expect("we just peeked at it").0;
+
+            if entry.state.completed.get() {
+                self.stale_deadlines.set(self.stale_deadlines.get() - 1);
+            } else {
+                entry.state.expire();
+            }
+        }
+    }
+
+    /// Records that an operation whose deadline is still in the heap has completed, compacting the
+    /// heap if it consists mostly of such stale entries.
+    fn deadline_completed(&self) {
+        let stale_deadlines = self.stale_deadlines.get() + 1;
+
+        let mut deadlines = self.deadlines.borrow_mut();
+
+        if stale_deadlines * 2 >= deadlines.len() {
+            deadlines.retain(|entry| !entry.0.state.completed.get());
+            self.stale_deadlines.set(0);
+        } else {
+            self.stale_deadlines.set(stale_deadlines);
+        }
+    }
+
+    fn register_deadline(&self, deadline: Instant, state: Rc<DeadlineState>) {
+        let sequence = self.next_deadline_sequence.get();
+        self.next_deadline_sequence.set(sequence + 1);
+
+        self.deadlines.borrow_mut().push(Reverse(DeadlineEntry {
+            deadline,
+            sequence,
+            state,
+        }));
+    }
+
+    fn insert_operation(
+        &self,
+        buffer: PinnedBuffer,
+        additional_buffers: Vec<PinnedBuffer>,
+    ) -> Operation {
         OPERATIONS_ALLOCATED.with(Event::observe_unit);
 
+        let _subsystem = enter_subsystem(Subsystem::Operations);
+
         let mut items = self.items.borrow_mut();
 
         let inserter = items.begin_insert();
         let key = inserter.index();
 
-        let core = inserter.insert(UnsafeCell::new(OperationCore::new(key, buffer)));
+        let core = inserter.insert(UnsafeCell::new(OperationCore::new(
+            key,
+            buffer,
+            additional_buffers,
+        )));
+
+        // SAFETY: The core is pinned in the slab chain and not yet referenced by anything else.
+        // The event is embedded in the core and the core is only released once both the sender
+        // and the receiver are gone, so the event does not outlive its storage.
+        unsafe {
+            let core = &mut *core.get();
+
+            let (result_tx, result_rx) =
+                OnceEvent::new_embedded(Pin::new_unchecked(&core.result_event));
+
+            core.result_tx = Some(result_tx);
+            core.result_rx = Some(result_rx);
+        }
 
         Operation {
             // SAFETY: The core is only referenced by either Operation or the operating system at any
@@ -79,9 +219,9 @@ impl OperationStore {
     }
 
     /// Delivers the result of an operation that has completed asynchronously to its originator and
-    /// releases any resources held by the operation store. We consume here the OVERLAPPED_ENTRY
-    /// structure that represents not only the operation core but also the status and the number of
-    /// bytes transferred.
+    /// releases any resources held by the operation store once the originator is done with them.
+    /// We consume here the OVERLAPPED_ENTRY structure that represents not only the operation core
+    /// but also the status and the number of bytes transferred.
     ///
     /// If the operation was executed on a caller-provided buffer, the caller can now get the buffer
     /// back from the returned value and reuse it for another operation.
@@ -103,14 +243,19 @@ impl OperationStore {
         // given time, so there is no possibility of multiple exclusive references being created.
         let core = &mut *(overlapped_entry.lpOverlapped as *mut OperationCore);
 
+        let span = mem::replace(&mut core.span, Span::none());
+        let _span = span.enter();
+        span.record("bytes_transferred", bytes_transferred);
+
         // The buffer is returned to the originator, carrying any data affected by the operation.
         // This also enables them to reuse the buffer if they wish to do so.
         let mut buffer = core
             .buffer
             .take()
             .expect("buffer must exist because we only remove it after completion");
+        let mut additional_buffers = mem::take(&mut core.additional_buffers);
 
-        buffer.set_len(bytes_transferred);
+        distribute_bytes_transferred(&mut buffer, &mut additional_buffers, bytes_transferred);
 
         let duration = LowPrecisionInstant::now().duration_since(
             core.started
@@ -120,29 +265,54 @@ impl OperationStore {
 
         OPERATION_COMPLETED_ASYNC_OK_DURATION.with(|x| x.observe_millis(duration));
 
+        if let Some(latency_slos) = &self.latency_slos {
+            latency_slos.check(core.kind, duration);
+        }
+
+        // Once we mark the operation as completed, its deadline no longer touches it. If the
+        // deadline has not expired, it is still in the heap and is now stale.
+        let deadline_expired = core.deadline_state.take().is_some_and(|deadline_state| {
+            deadline_state.completed.set(true);
+
+            if !deadline_state.expired.get() {
+                self.deadline_completed();
+            }
+
+            deadline_state.expired.get()
+        });
+
         let result_tx = core
             .result_tx
             .take()
             .expect("result tx must exist because we have not yet sent the result");
 
         // The operation may not have been successful, so we need to investigate the status.
-        // We ignore the tx return value because the receiver may have dropped already.
+        // If the receiver has been dropped already, the result is simply dropped.
         if status != STATUS_SUCCESS {
-            _ = result_tx.send(Err(io::OperationError::new(
-                io::Error::Windows(status.into()),
-                buffer,
-            )));
+            let mut error = io::Error::Windows(status.into());
+
+            // If we canceled the operation because its deadline expired, the cancellation is
+            // reported as a timeout. If the operation failed for some other reason before our
+            // cancellation took effect, we report its real outcome.
+            if deadline_expired && error.is_canceled() {
+                OPERATIONS_TIMED_OUT.with(Event::observe_unit);
+                error = io::Error::TimedOut;
+            }
+
+            result_tx.set((
+                Err(io::OperationError::new(error, buffer)),
+                additional_buffers,
+            ));
         } else {
-            _ = result_tx.send(Ok(buffer));
+            result_tx.set((Ok(buffer), additional_buffers));
         }
 
-        // All done!
-        self.release(core.key);
+        self.release_if_abandoned(core);
     }
 
     /// Delivers the result of an operation that has completed synchronously to its originator and
-    /// releases any resources held by the operation store. We consume here the OVERLAPPED
-    /// structure that represents the operation core.
+    /// releases any resources held by the operation store once the originator is done with them.
+    /// We consume here the OVERLAPPED structure that represents the operation core.
     ///
     /// If the operation was executed on a caller-provided buffer, the caller can now get the buffer
     /// back from the returned value and reuse it for another operation.
@@ -166,23 +336,47 @@ impl OperationStore {
             .buffer
             .take()
             .expect("buffer must exist because we only remove it after completion");
+        let mut additional_buffers = mem::take(&mut core.additional_buffers);
 
         let bytes_transferred = core.immediate_bytes_transferred as usize;
-        assert!(bytes_transferred <= buffer.len());
+        assert!(
+            bytes_transferred
+                <= buffer.len() + additional_buffers.iter().map(|b| b.len()).sum::<usize>()
+        );
+
+        core.span.record("bytes_transferred", bytes_transferred);
 
         OPERATIONS_COMPLETED_SYNC.with(Event::observe_unit);
         OPERATION_COMPLETED_BYTES.with(|x| x.observe(bytes_transferred as Magnitude));
 
-        buffer.set_len(bytes_transferred);
+        distribute_bytes_transferred(&mut buffer, &mut additional_buffers, bytes_transferred);
 
-        _ = core
-            .result_tx
+        // The deadline was never registered but we mark it completed regardless, so nothing can
+        // ever use the OVERLAPPED pointer it holds.
+        if let Some(deadline_state) = core.deadline_state.take() {
+            deadline_state.completed.set(true);
+        }
+
+        core.result_tx
             .take()
             .expect("result tx must exist because we have not yet sent the result")
-            .send(Ok(buffer));
+            .set((Ok(buffer), additional_buffers));
+
+        self.release_if_abandoned(core);
+    }
 
-        // All done!
-        self.release(core.key);
+    /// Releases the core of a completed operation if the originator has stopped waiting for the
+    /// result. Otherwise, the core is released once the originator drops the receiver, as the
+    /// receiver references the result event embedded in the core.
+    ///
+    /// # Safety
+    ///
+    /// The core must not have been released yet.
+    unsafe fn release_if_abandoned(&self, core: *const OperationCore) {
+        // Both the sender and the receiver are gone once the event is inert.
+        if (*core).result_event.is_inert() {
+            self.release((*core).key);
+        }
     }
 
     fn release(&self, key: OperationKey) {
@@ -203,6 +397,27 @@ impl OperationStore {
 
 type OperationKey = usize;
 
+/// The maximum number of buffers that a single vectored I/O operation can use.
+pub const MAX_VECTORED_BUFFERS: usize = 8;
+
+/// What the operation core delivers to the originator of the operation: the result of the
+/// operation on the primary buffer, plus any additional buffers of a vectored operation.
+type CoreResult = (io::OperationResult, Vec<PinnedBuffer>);
+
+/// Sets the length of each buffer to the number of bytes transferred into/out of it, assuming the
+/// bytes were transferred into the buffers in order, filling each before moving on to the next.
+fn distribute_bytes_transferred(
+    buffer: &mut PinnedBuffer,
+    additional_buffers: &mut [PinnedBuffer],
+    mut bytes_transferred: usize,
+) {
+    for buffer in std::iter::once(buffer).chain(additional_buffers.iter_mut()) {
+        let len = buffer.len().min(bytes_transferred);
+        buffer.set_len(len);
+        bytes_transferred -= len;
+    }
+}
+
 /// Constrained API surface that allows an operation to command the store that owns it. This creates
 /// a circular reference between an operation and the OperationStore, so we always use
 /// OperationStore via interior mutability to prevent accidents here.
@@ -220,6 +435,73 @@ impl ControlNode {
     unsafe fn complete_immediately(&mut self, overlapped: *mut OVERLAPPED) {
         self.store.complete_immediately(overlapped)
     }
+
+    unsafe fn release_if_abandoned(&mut self, core: *const OperationCore) {
+        self.store.release_if_abandoned(core)
+    }
+
+    fn register_deadline(&mut self, deadline: Instant, state: Rc<DeadlineState>) {
+        self.store.register_deadline(deadline, state);
+    }
+}
+
+#[derive(Debug)]
+struct DeadlineEntry {
+    deadline: Instant,
+    sequence: u64,
+    state: Rc<DeadlineState>,
+}
+
+impl PartialEq for DeadlineEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DeadlineEntry {}
+
+impl PartialOrd for DeadlineEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DeadlineEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline
+            .cmp(&other.deadline)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+/// Shared between an operation with a deadline and the entry of the deadline in the operation
+/// store, which outlives the operation if the operation completes before the deadline.
+#[derive(Debug)]
+struct DeadlineState {
+    // The primitive the operation is performed on, which we cancel the operation on.
+    cancel_target: HANDLE,
+
+    // Only valid as long as `completed` is false, as the operation core may be released as soon as
+    // the operation completes.
+    overlapped: *mut OVERLAPPED,
+
+    completed: Cell<bool>,
+    expired: Cell<bool>,
+}
+
+impl DeadlineState {
+    fn expire(&self) {
+        if self.completed.get() {
+            return;
+        }
+
+        self.expired.set(true);
+
+        // SAFETY: The operation has not completed, so the OVERLAPPED pointer is still valid. We
+        // ignore the result because the only expected failure is ERROR_NOT_FOUND, which means that
+        // the operation has completed and the completion is already on its way to the I/O driver.
+        _ = unsafe { CancelIoEx(self.cancel_target, Some(self.overlapped)) };
+    }
 }
 
 // Just being careful here because we have a 'static reference in there which is very "loose".
@@ -253,6 +535,10 @@ struct OperationCore {
     /// the buffer to the caller and set this to None.
     buffer: Option<PinnedBuffer>,
 
+    /// Any further buffers of a vectored operation, used after `buffer` in the listed order. Empty
+    /// for regular operations. Returned to the caller together with `buffer`.
+    additional_buffers: Vec<PinnedBuffer>,
+
     /// Used to operate the control node, which requires us to know our own key.
     key: OperationKey,
 
@@ -263,33 +549,88 @@ struct OperationCore {
 
     /// This is where the I/O completion handler will deliver the result of the operation.
     /// Value is cleared when consumed, to make it obvious if any accidental reuse occurs.
-    result_tx: Option<oneshot::Sender<io::OperationResult>>,
-    result_rx: Option<oneshot::Receiver<io::OperationResult>>,
+    ///
+    /// These must be declared before `result_event`, so they are dropped before it.
+    result_tx: Option<once_event::EmbeddedSender<CoreResult>>,
+    result_rx: Option<once_event::EmbeddedReceiver<CoreResult>>,
 
     /// Timestamp of when the operation is started. Used to report I/O operation durations.
     started: Option<LowPrecisionInstant>,
 
+    /// The I/O primitive the operation is performed on, if the originator has asked for the
+    /// operation to be canceled when they stop waiting for the result.
+    cancel_target: Option<HANDLE>,
+
+    /// If set, the operation is canceled once this much time has passed since it was started and
+    /// the result is reported as `io::Error::TimedOut`. Requires `cancel_target`.
+    timeout: Option<Duration>,
+
+    /// If set, the operation is canceled once this point in time has been reached and the result
+    /// is reported as `io::Error::TimedOut`. Requires `cancel_target`.
+    deadline: Option<Instant>,
+
+    /// Tracks the deadline (whichever is earlier of `timeout` and `deadline`) once the operation
+    /// has been started, if it has one.
+    deadline_state: Option<Rc<DeadlineState>>,
+
+    /// If set, the operation is canceled once cancellation is requested via an `OperationHandle`
+    /// and the result is reported as `io::Error::Canceled`. Requires `cancel_target`.
+    cancel_token: Option<CancellationToken>,
+
+    /// What the operation does, for the purpose of latency monitoring.
+    kind: OperationKind,
+
+    /// Covers the operation from submission until the originator stops waiting for the result, as
+    /// a child of whatever span was current when the operation was submitted. Entered while the
+    /// result is delivered, so events logged during completion are attributed to the originator.
+    span: Span,
+
+    /// Anything the originator wants to keep alive for as long as the operating system may still be
+    /// working on the operation, which may be longer than the originator waits for the result.
+    keep_alive: Option<Box<dyn Any>>,
+
+    /// Backing storage for the event that delivers the result of the operation. Embedding it here
+    /// saves a separate allocation per operation. As the receiver references it, the core is only
+    /// released once the result has been delivered and the receiver has been dropped.
+    result_event: OnceEventEmbeddedStorage<CoreResult>,
+
     // Once pinned, this type cannot be unpinned.
     _phantom_pin: std::marker::PhantomPinned,
 }
 
 impl OperationCore {
-    pub fn new(key: OperationKey, mut buffer: PinnedBuffer) -> Self {
-        let (result_tx, result_rx) = oneshot::channel();
-
+    pub fn new(
+        key: OperationKey,
+        mut buffer: PinnedBuffer,
+        mut additional_buffers: Vec<PinnedBuffer>,
+    ) -> Self {
         // IOCP cannot deal with bigger slices of data than u32::MAX, so limit the active range.
-        if buffer.len() > u32::MAX as usize {
-            buffer.set_len(u32::MAX as usize);
+        // APIs that promise to transfer whole buffers split them via `transfer_in_chunks()` or
+        // `transfer_all_in_chunks()` first.
+        for buffer in std::iter::once(&mut buffer).chain(additional_buffers.iter_mut()) {
+            if buffer.len() > io::MAX_OPERATION_LEN {
+                buffer.set_len(io::MAX_OPERATION_LEN);
+            }
         }
 
         Self {
             overlapped: OVERLAPPED::default(),
             buffer: Some(buffer),
+            additional_buffers,
             key,
             immediate_bytes_transferred: 0,
-            result_tx: Some(result_tx),
-            result_rx: Some(result_rx),
+            result_tx: None,
+            result_rx: None,
             started: None,
+            cancel_target: None,
+            timeout: None,
+            deadline: None,
+            deadline_state: None,
+            cancel_token: None,
+            kind: OperationKind::Other,
+            span: Span::none(),
+            keep_alive: None,
+            result_event: OnceEvent::new_embedded_storage(),
             _phantom_pin: std::marker::PhantomPinned,
         }
     }
@@ -299,6 +640,7 @@ impl fmt::Debug for OperationCore {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("OperationCore")
             .field("buffer", &self.buffer)
+            .field("additional_buffers", &self.additional_buffers)
             .field("key", &self.key)
             .field(
                 "immediate_bytes_transferred",
@@ -307,6 +649,14 @@ impl fmt::Debug for OperationCore {
             .field("result_tx", &self.result_tx)
             .field("result_rx", &self.result_rx)
             .field("started", &self.started)
+            .field("cancel_target", &self.cancel_target)
+            .field("timeout", &self.timeout)
+            .field("deadline", &self.deadline)
+            .field("deadline_state", &self.deadline_state)
+            .field("cancel_token", &self.cancel_token)
+            .field("kind", &self.kind)
+            .field("span", &self.span)
+            .field("keep_alive", &self.keep_alive.is_some())
             .finish()
     }
 }
@@ -339,6 +689,100 @@ impl Operation {
         self.core.overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
     }
 
+    /// Sets what the operation does, so the latency thresholds for that kind of operation are
+    /// applied to it. Operations are of kind `OperationKind::Other` by default.
+    pub fn set_kind(&mut self, kind: OperationKind) {
+        self.core.kind = kind;
+    }
+
+    /// Cancels the native operation via `CancelIoEx` if the future returned by `begin()` is
+    /// dropped before the operation completes. The operating system then completes the operation
+    /// promptly with a cancellation status, after which the I/O driver releases the operation and
+    /// its buffers. Without this, an abandoned operation lingers until it completes on its own.
+    ///
+    /// The primitive must be the one the native I/O operation is performed on and must remain open
+    /// at least until the future returned by `begin()` is dropped.
+    pub fn cancel_on_drop(&mut self, primitive: impl Into<IoPrimitive>) {
+        self.core.cancel_target = Some(primitive.into().into());
+    }
+
+    /// Keeps a value alive until the operation is released, which happens only once the operating
+    /// system has completed the operation, even if the future returned by `begin()` is dropped
+    /// before that. Holding a reference to the I/O primitive this way lets its owner know whether
+    /// any operations on it may still be in progress (including abandoned ones whose cancellation
+    /// the operating system has not yet processed).
+    pub fn keep_alive(&mut self, value: impl Any) {
+        self.core.keep_alive = Some(Box::new(value));
+    }
+
+    /// Cancels the native operation if it has not completed within `timeout` of being started,
+    /// reporting `io::Error::TimedOut` with the buffers restored to the active regions they had
+    /// when the operation was started, so they can be reused as-is (e.g. to retry the operation).
+    ///
+    /// If the operation completes in the same instant as the timeout elapses, the result of the
+    /// operation is returned instead of a timeout error, so no transferred data is lost.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cancel_on_drop()` has not been called first, as that specifies the primitive to
+    /// cancel the operation on.
+    pub fn cancel_after(&mut self, timeout: Duration) {
+        assert!(
+            self.core.cancel_target.is_some(),
+            "cancel_after() requires cancel_on_drop() to specify the primitive to cancel"
+        );
+
+        self.core.timeout = Some(timeout);
+    }
+
+    /// Cancels the native operation if it has not completed by `deadline`, reporting
+    /// `io::Error::TimedOut` the same way as `cancel_after()`. If both are set, whichever expires
+    /// first applies.
+    ///
+    /// The deadline is enforced by the I/O driver, so the operation needs no timer of its own.
+    /// Like other timers of the runtime, it has the precision of the async worker loop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cancel_on_drop()` has not been called first, as that specifies the primitive to
+    /// cancel the operation on.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        assert!(
+            self.core.cancel_target.is_some(),
+            "set_deadline() requires cancel_on_drop() to specify the primitive to cancel"
+        );
+
+        self.core.deadline = Some(deadline);
+    }
+
+    /// Obtains a handle that can cancel the native operation while it is in progress, reporting
+    /// `io::Error::Canceled` with the buffers restored to the active regions they had when the
+    /// operation was started. Cancellation requested before the operation is started takes effect
+    /// as soon as it starts.
+    ///
+    /// If the operation completes before the cancellation takes effect, the result of the
+    /// operation is returned instead of a cancellation error, so no transferred data is lost.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cancel_on_drop()` has not been called first, as that specifies the primitive to
+    /// cancel the operation on.
+    pub fn handle(&mut self) -> OperationHandle {
+        assert!(
+            self.core.cancel_target.is_some(),
+            "handle() requires cancel_on_drop() to specify the primitive to cancel"
+        );
+
+        let token = self
+            .core
+            .cancel_token
+            .get_or_insert_with(CancellationToken::new);
+
+        OperationHandle {
+            token: token.clone(),
+        }
+    }
+
     /// Executes an I/O operation, using the specified callback to pass the operation buffer and
     /// OVERLAPPED metadata structure to native OS functions.
     ///
@@ -364,9 +808,111 @@ impl Operation {
     ///
     /// TODO: Replace 'static lifetimes with something that makes it clear that the values
     /// have some temporary lifetime only valid for the duration of the callback.
-    pub async unsafe fn begin<F>(self, f: F) -> io::OperationResult
+    ///
+    /// # Tracing
+    ///
+    /// The operation is covered by an `io_operation` span (at TRACE level) that is a child of the
+    /// span that is current when the operation is submitted (i.e. when the returned future is
+    /// first polled). The span is entered whenever the awaiting task polls the operation and while
+    /// the result is delivered, so the time spent waiting for I/O shows up in the trace of the
+    /// request that started it, even though the completion arrives via the completion port.
+    pub async unsafe fn begin<F>(mut self, f: F) -> io::OperationResult
     where
         F: FnOnce(&'static mut [u8], *mut OVERLAPPED, &mut u32) -> io::Result<()>,
+    {
+        let span = self.begin_span();
+
+        let (result, _) = self
+            .begin_core(|buffer, _, overlapped, immediate_bytes_transferred| {
+                f(buffer, overlapped, immediate_bytes_transferred)
+            })
+            .instrument(span)
+            .await;
+
+        result
+    }
+
+    /// Executes a vectored I/O operation created via `new_vectored_operation()`, using the
+    /// specified callback to pass the operation buffers and OVERLAPPED metadata structure to native
+    /// OS functions. Other than operating on multiple buffers, this is equivalent to `begin()`.
+    ///
+    /// # Callback arguments
+    ///
+    /// 1. The buffers to be used for the operation, in order. These are typically converted into an
+    ///    array of `WSABUF` for the native API.
+    /// 2. The OVERLAPPED structure to be used for the operation. Pass it along to the native API
+    ///    without modification.
+    /// 3. An exclusive reference to a variable that is to receive the number of bytes transferred
+    ///    if the I/O operation completes synchronously. See `begin()`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `begin()`.
+    pub async unsafe fn begin_vectored<F>(mut self, f: F) -> io::VectoredOperationResult
+    where
+        F: FnOnce(&mut [&'static mut [u8]], *mut OVERLAPPED, &mut u32) -> io::Result<()>,
+    {
+        let span = self.begin_span();
+
+        let (result, additional_buffers) = self
+            .begin_core(
+                |buffer, additional_buffers, overlapped, immediate_bytes_transferred| {
+                    let mut buffers = Vec::with_capacity(1 + additional_buffers.len());
+                    buffers.push(buffer);
+
+                    for additional_buffer in additional_buffers.iter_mut() {
+                        // SAFETY: Same lifetime lie as with the primary buffer - the slices are
+                        // only valid for the duration of the callback.
+                        buffers.push(mem::transmute::<&mut [u8], &'static mut [u8]>(
+                            additional_buffer.as_mut_slice(),
+                        ));
+                    }
+
+                    f(&mut buffers, overlapped, immediate_bytes_transferred)
+                },
+            )
+            .instrument(span)
+            .await;
+
+        match result {
+            Ok(buffer) => {
+                let mut buffers = Vec::with_capacity(1 + additional_buffers.len());
+                buffers.push(buffer);
+                buffers.extend(additional_buffers);
+                Ok(buffers)
+            }
+            Err(e) => {
+                let (inner, buffer) = e.into_inner_and_buffer();
+
+                let mut buffers = Vec::with_capacity(1 + additional_buffers.len());
+                buffers.push(buffer);
+                buffers.extend(additional_buffers);
+                Err(io::VectoredOperationError::new(inner, buffers))
+            }
+        }
+    }
+
+    /// Creates the span that covers the operation, as a child of the current span.
+    fn begin_span(&mut self) -> Span {
+        let span = span!(
+            Level::TRACE,
+            "io_operation",
+            kind = ?self.core.kind,
+            bytes_transferred = field::Empty
+        );
+
+        self.core.span = span.clone();
+        span
+    }
+
+    async unsafe fn begin_core<F>(self, f: F) -> CoreResult
+    where
+        F: FnOnce(
+            &'static mut [u8],
+            &'static mut [PinnedBuffer],
+            *mut OVERLAPPED,
+            &mut u32,
+        ) -> io::Result<()>,
     {
         let result_rx = self
             .core
@@ -378,13 +924,55 @@ impl Operation {
         // callback fails or even resurrect it immediately if the callback completes synchronously.
         let mut control_node = self.control.clone();
 
-        let (buffer, overlapped, immediate_bytes_transferred) = self.into_callback_arguments();
-
-        match f(buffer, overlapped, immediate_bytes_transferred) {
+        let cancel_target = self.core.cancel_target;
+        let cancel_token = self.core.cancel_token.take();
+
+        // A timeout is just a deadline relative to the start of the operation.
+        let deadline = match (self.core.deadline, self.core.timeout) {
+            (Some(deadline), Some(timeout)) => Some(deadline.min(clock::now() + timeout)),
+            (deadline, timeout) => deadline.or_else(|| timeout.map(|t| clock::now() + t)),
+        };
+
+        let deadline_state = deadline.map(|_| {
+            let state = Rc::new(DeadlineState {
+                cancel_target: cancel_target
+                    .expect("a deadline can only be set together with a cancel target"),
+                overlapped: &mut self.core.overlapped as *mut _,
+                completed: Cell::new(false),
+                expired: Cell::new(false),
+            });
+
+            self.core.deadline_state = Some(Rc::clone(&state));
+            state
+        });
+
+        let may_stop_early = deadline.is_some() || cancel_token.is_some();
+
+        let (buffer, additional_buffers, overlapped, immediate_bytes_transferred) =
+            self.into_callback_arguments();
+
+        // If the operation times out or is canceled, we restore the active regions of the buffers,
+        // which will have been shrunk to the (likely zero) number of bytes transferred before
+        // cancellation.
+        let original_lens = may_stop_early.then(|| {
+            std::iter::once(buffer.len())
+                .chain(additional_buffers.iter().map(|b| b.len()))
+                .collect::<Vec<_>>()
+        });
+
+        let started_async = match f(
+            buffer,
+            additional_buffers,
+            overlapped,
+            immediate_bytes_transferred,
+        ) {
             // The operation was started asynchronously. This is what we want to see.
-            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => {}
+            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => true,
             Err(io::Error::Winsock { code, detail })
-                if code == SOCKET_ERROR && detail == WSA_IO_PENDING => {}
+                if code == SOCKET_ERROR && detail == WSA_IO_PENDING =>
+            {
+                true
+            }
 
             // The operation completed synchronously. This means we will not get a completion
             // notification and must handle the result inline (because we set a flag saying this
@@ -397,6 +985,7 @@ impl Operation {
                 );
 
                 control_node.complete_immediately(overlapped);
+                false
             }
 
             // Something went wrong. In this case, the operation core was not consumed by the OS.
@@ -411,18 +1000,97 @@ impl Operation {
                     "buffer must exist because we only remove it after completion or failure and right now we are doing the latter",
                 );
 
+                let additional_buffers = mem::take(&mut (&mut *core).additional_buffers);
+
+                // The receiver references the event embedded in the core, so it must go first.
+                drop(result_rx);
                 control_node.release((&*core).key);
 
-                return Err(io::OperationError::new(e, buffer));
+                return (Err(io::OperationError::new(e, buffer)), additional_buffers);
+            }
+        };
+
+        // The operation is in progress, so the I/O driver now takes care of its deadline. There is
+        // nothing to cancel if the operation already completed - the core may even have been
+        // released and reused for another operation by the time the deadline arrives.
+        if started_async {
+            if let (Some(deadline), Some(deadline_state)) = (deadline, deadline_state) {
+                control_node.register_deadline(deadline, deadline_state);
             }
         }
 
-        result_rx.await.expect(
-            "no expected code path drops the I/O operation without signaling completion result",
-        )
+        let mut pending = PendingOperation {
+            result_rx: ManuallyDrop::new(result_rx),
+            overlapped,
+            cancel_target,
+            control: control_node,
+        };
+
+        let Some(original_lens) = original_lens else {
+            return (&mut *pending.result_rx).await;
+        };
+
+        let mut cancel_requested = false;
+
+        let (result, mut additional_buffers) = match &cancel_token {
+            Some(token) => {
+                // We prefer the result over canceling if both are ready at the same time.
+                match future::select(&mut *pending.result_rx, pin!(token.cancelled())).await {
+                    Either::Left((result, _)) => result,
+                    Either::Right(_) => {
+                        cancel_requested = true;
+                        pending.cancel();
+
+                        // The operation is not released before the OS reports its completion, so
+                        // we wait for the cancellation to be processed, which also gives us the
+                        // buffers back.
+                        (&mut *pending.result_rx).await
+                    }
+                }
+            }
+            None => (&mut *pending.result_rx).await,
+        };
+
+        match result {
+            // The I/O driver already reports cancellation due to the deadline as a timeout.
+            Err(e)
+                if matches!(e.inner, io::Error::TimedOut)
+                    || (cancel_requested && e.inner.is_canceled()) =>
+            {
+                let (inner, mut buffer) = e.into_inner_and_buffer();
+
+                let inner = match inner {
+                    io::Error::TimedOut => io::Error::TimedOut,
+                    _ => io::Error::Canceled,
+                };
+
+                for (buffer, len) in std::iter::once(&mut buffer)
+                    .chain(additional_buffers.iter_mut())
+                    .zip(original_lens)
+                {
+                    buffer.set_len(len);
+                }
+
+                (
+                    Err(io::OperationError::new(inner, buffer)),
+                    additional_buffers,
+                )
+            }
+            // The operation completed (or failed for some other reason) before our cancellation
+            // took effect, so we report its real outcome.
+            result => (result, additional_buffers),
+        }
     }
 
-    fn into_callback_arguments(self) -> (&'static mut [u8], *mut OVERLAPPED, &'static mut u32) {
+    #[allow(clippy::type_complexity)] // It is just a temporary tuple, no need for ceremony.
+    fn into_callback_arguments(
+        self,
+    ) -> (
+        &'static mut [u8],
+        &'static mut [PinnedBuffer],
+        *mut OVERLAPPED,
+        &'static mut u32,
+    ) {
         // We do not want to run Drop - this is an intentional cleanupless shattering of the type.
         // This is the reason for the "you must pass OVERLAPPED to the native API" warnings above.
         // If the values we extract are not used, we forever leak the object we got them from.
@@ -445,6 +1113,12 @@ impl Operation {
                         .as_mut_slice(),
                 )
             },
+            // SAFETY: Same as above.
+            unsafe {
+                mem::transmute::<&mut [PinnedBuffer], &'static mut [PinnedBuffer]>(
+                    operation.additional_buffers.as_mut_slice(),
+                )
+            },
             &mut operation.overlapped as *mut _,
             // SAFETY: Sets the lifetime to 'static because I cannot figure out a straightforward way to declare lifetimes here.
             // As long as the value is only used during the callback, this is fine (caller is responsible for not using it afterwards).
@@ -459,6 +1133,109 @@ impl Drop for Operation {
     }
 }
 
+/// Cancels an I/O operation while it is in progress, returned by APIs that start cancelable
+/// operations (e.g. `TcpConnection::receive_cancelable()`). Clones of the handle cancel the same
+/// operation.
+///
+/// The handle may be used from any thread - the native cancellation is always performed by the
+/// async worker that is waiting for the result of the operation.
+#[derive(Clone, Debug)]
+pub struct OperationHandle {
+    token: CancellationToken,
+}
+
+impl OperationHandle {
+    /// Creates a handle for an operation that watches for cancellation itself instead of relying
+    /// on the I/O driver (e.g. a receive via Registered I/O).
+    pub(crate) fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+        }
+    }
+
+    pub(crate) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Requests the operation to be canceled. The result of the operation resolves to
+    /// `io::Error::Canceled` once the operating system has processed the cancellation, unless the
+    /// operation completes first. Repeated calls and calls after the operation has completed have
+    /// no effect.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Whether cancellation of the operation has been requested.
+    pub fn is_canceled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+/// An operation that has been started asynchronously and whose result we are waiting for. If we
+/// stop waiting before the result arrives, the native operation is canceled (if the originator
+/// asked for that via `Operation::cancel_on_drop()`).
+///
+/// The operation core is kept alive at least until this is dropped, as the receiver references the
+/// result event embedded in the core.
+struct PendingOperation {
+    // Dropped manually before we release the operation core.
+    result_rx: ManuallyDrop<once_event::EmbeddedReceiver<CoreResult>>,
+    overlapped: *mut OVERLAPPED,
+    cancel_target: Option<HANDLE>,
+    control: ControlNode,
+}
+
+impl PendingOperation {
+    /// Requests the OS to cancel the operation if it is still in progress. The result of the
+    /// operation is still delivered via `result_rx` once the OS has processed the cancellation.
+    fn cancel(&self) {
+        let Some(handle) = self.cancel_target else {
+            return;
+        };
+
+        // Once the result has been delivered, there is nothing left to cancel. Completions are
+        // processed on the current thread, so this cannot change under our feet.
+        if self.is_completed() {
+            return;
+        }
+
+        OPERATIONS_CANCELED.with(Event::observe_unit);
+
+        // SAFETY: The operation is still in progress, so the OVERLAPPED pointer is still valid. We
+        // ignore the result because the only expected failure is ERROR_NOT_FOUND, which means that
+        // the operation has completed and the completion is already on its way to the I/O driver.
+        _ = unsafe { CancelIoEx(handle, Some(self.overlapped)) };
+    }
+
+    fn is_completed(&self) -> bool {
+        // SAFETY: The core is not released before we are dropped. We only access a field that the
+        // operating system does not touch.
+        unsafe {
+            (*(self.overlapped as *const OperationCore))
+                .result_tx
+                .is_none()
+        }
+    }
+}
+
+impl Drop for PendingOperation {
+    fn drop(&mut self) {
+        self.cancel();
+
+        // SAFETY: We never touch the receiver again after dropping it.
+        unsafe { ManuallyDrop::drop(&mut self.result_rx) };
+
+        // If the operation is still in progress, the I/O driver releases the core once it
+        // completes, seeing that we are no longer waiting for the result.
+        //
+        // SAFETY: The core is not released before both we and the sender are gone.
+        unsafe {
+            self.control
+                .release_if_abandoned(self.overlapped as *const OperationCore);
+        }
+    }
+}
+
 thread_local! {
     static OPERATIONS_ALLOCATED: Event = EventBuilder::new()
         .name("io_ops_allocated")
@@ -470,6 +1247,16 @@ thread_local! {
         .build()
         .unwrap();
 
+    static OPERATIONS_CANCELED: Event = EventBuilder::new()
+        .name("io_ops_canceled")
+        .build()
+        .unwrap();
+
+    static OPERATIONS_TIMED_OUT: Event = EventBuilder::new()
+        .name("io_ops_timed_out")
+        .build()
+        .unwrap();
+
     static OPERATIONS_COMPLETED_SYNC: Event = EventBuilder::new()
         .name("io_ops_completed_sync")
         .build()
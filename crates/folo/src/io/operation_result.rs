@@ -26,8 +26,34 @@ impl OperationError {
     }
 }
 
+/// An error for a vectored I/O operation that was attempted on multiple data buffers. Contains not
+/// only the error information but also the data buffers that were used, enabling them to be
+/// inspected or reused.
+#[derive(Debug, Error)]
+#[error("vectored I/O operation failed: {inner}")]
+pub struct VectoredOperationError {
+    pub inner: crate::io::Error,
+    pub buffers: Vec<PinnedBuffer>,
+}
+
+impl VectoredOperationError {
+    pub fn new(inner: crate::io::Error, buffers: Vec<PinnedBuffer>) -> Self {
+        Self { inner, buffers }
+    }
+
+    pub fn into_inner(self) -> crate::io::Error {
+        self.inner
+    }
+
+    pub fn into_inner_and_buffers(self) -> (crate::io::Error, Vec<PinnedBuffer>) {
+        (self.inner, self.buffers)
+    }
+}
+
 pub type OperationResult = std::result::Result<PinnedBuffer, OperationError>;
 
+pub type VectoredOperationResult = std::result::Result<Vec<PinnedBuffer>, VectoredOperationError>;
+
 pub trait OperationResultExt {
     fn into_inner(self) -> crate::io::Result<PinnedBuffer>;
 }
@@ -40,3 +66,16 @@ impl OperationResultExt for OperationResult {
         }
     }
 }
+
+pub trait VectoredOperationResultExt {
+    fn into_inner(self) -> crate::io::Result<Vec<PinnedBuffer>>;
+}
+
+impl VectoredOperationResultExt for VectoredOperationResult {
+    fn into_inner(self) -> crate::io::Result<Vec<PinnedBuffer>> {
+        match self {
+            Ok(buffers) => Ok(buffers),
+            Err(VectoredOperationError { inner, .. }) => Err(inner),
+        }
+    }
+}
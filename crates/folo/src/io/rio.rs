@@ -0,0 +1,967 @@
+use crate::{
+    io::{self, CompletionPort, OperationError, OperationResult, PinnedBuffer},
+    metrics::{Event, EventBuilder, Magnitude},
+    net::winsock,
+    rt::{select2, sleep_until},
+    sync::CancellationToken,
+};
+use futures::future::{self, Either};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt,
+    future::{poll_fn, Future},
+    mem::{self, MaybeUninit},
+    ptr,
+    rc::Rc,
+    sync::OnceLock,
+    task::{self, Waker},
+    time::Instant,
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Networking::WinSock::{
+            WSAGetLastError, RIORESULT, RIO_BUF, RIO_BUFFERID, RIO_CORRUPT_CQ, RIO_CQ,
+            RIO_EXTENSION_FUNCTION_TABLE, RIO_IOCP_COMPLETION, RIO_MAX_CQ_SIZE,
+            RIO_NOTIFICATION_COMPLETION, RIO_NOTIFICATION_COMPLETION_0,
+            RIO_NOTIFICATION_COMPLETION_0_1, RIO_RQ, SOCKET, SOCKET_ERROR, WSAEALREADY, WSA_ERROR,
+        },
+        System::IO::OVERLAPPED,
+    },
+};
+
+// Value is meaningless, just has to be unique.
+pub(crate) const RIO_NOTIFY_COMPLETION_KEY: usize = 0x23546789898;
+
+/// The size of the registered slots that sockets using Registered I/O send data from and receive
+/// data into. A datagram sent or received via Registered I/O must fit into one slot, whereas sends
+/// on a stream socket are split over as many slots as needed.
+pub const RIO_SLOT_SIZE: usize = 16 * 1024;
+
+/// How many receives and how many sends each socket using Registered I/O can have in progress at
+/// the same time. Sends beyond this wait for earlier ones to complete.
+const RIO_SLOTS_PER_DIRECTION: u32 = 16;
+
+/// The completion queue of each worker starts with room for this many completions and grows as
+/// sockets are registered with it.
+const RIO_INITIAL_COMPLETION_QUEUE_SIZE: u32 = 1024;
+
+/// Max number of RIO completions to dequeue in one go.
+const RIO_DEQUEUE_BATCH_SIZE: usize = 256;
+
+// These are macros in the Windows SDK headers, so the `windows` crate does not provide them.
+const RIO_INVALID_BUFFERID: RIO_BUFFERID = RIO_BUFFERID(0xFFFF_FFFF);
+const RIO_INVALID_CQ: RIO_CQ = RIO_CQ(0);
+const RIO_INVALID_RQ: RIO_RQ = RIO_RQ(0);
+
+// The function table is the same for every socket, so we only load it once per process.
+static FUNCTIONS: OnceLock<RIO_EXTENSION_FUNCTION_TABLE> = OnceLock::new();
+
+fn functions(socket: SOCKET) -> io::Result<&'static RIO_EXTENSION_FUNCTION_TABLE> {
+    if let Some(functions) = FUNCTIONS.get() {
+        return Ok(functions);
+    }
+
+    let functions = winsock::rio_functions(socket)?;
+    Ok(FUNCTIONS.get_or_init(|| functions))
+}
+
+/// Obtains a function from the RIO function table. We verify when loading the table that all the
+/// functions we use are present.
+fn rio_fn<F>(function: Option<F>) -> F {
+    function.expect("we verified when loading the table that the RIO functions are present")
+}
+
+fn last_winsock_error() -> io::Error {
+    io::Error::Winsock {
+        code: SOCKET_ERROR,
+        // SAFETY: Nothing unsafe here, just an FFI call.
+        detail: unsafe { WSAGetLastError() },
+    }
+}
+
+/// The Registered I/O (RIO) completion queue of an async worker, shared by all the sockets of the
+/// worker that use Registered I/O. Created by the I/O driver when the first such socket is
+/// registered.
+///
+/// The I/O driver polls the queue for completions as part of processing I/O completions. Before
+/// the driver goes to sleep, it asks for a notification packet to be posted to the completion
+/// port of the worker once completions arrive, so RIO completions wake it up like any other I/O.
+///
+/// The queue remains open for as long as any socket refers to it, as the request queues of the
+/// sockets post their completions into it.
+pub(crate) struct RioCompletionQueue {
+    functions: &'static RIO_EXTENSION_FUNCTION_TABLE,
+    completion_queue: RIO_CQ,
+
+    // How many completions the queue can hold. This must cover the requests that the request
+    // queues attached to it can have in progress, which is what we have reserved so far.
+    capacity: Cell<u32>,
+    reserved: Cell<u32>,
+
+    // Requests posted to the operating system for which we have not yet dequeued the completion.
+    pending: Cell<usize>,
+
+    // Whether we have asked for a notification packet that has not yet arrived.
+    notification_armed: Cell<bool>,
+
+    // Given to the operating system as part of the notification packets. We never look at it but
+    // it must remain valid for as long as notifications may be posted.
+    _notification_overlapped: Box<OVERLAPPED>,
+}
+
+impl RioCompletionQueue {
+    pub(crate) fn new(socket: SOCKET, completion_port: &CompletionPort) -> io::Result<Self> {
+        let functions = functions(socket)?;
+
+        let notification_overlapped = Box::new(OVERLAPPED::default());
+
+        let notification = RIO_NOTIFICATION_COMPLETION {
+            Type: RIO_IOCP_COMPLETION,
+            Anonymous: RIO_NOTIFICATION_COMPLETION_0 {
+                Iocp: RIO_NOTIFICATION_COMPLETION_0_1 {
+                    IocpHandle: ***completion_port.handle(),
+                    CompletionKey: RIO_NOTIFY_COMPLETION_KEY as *mut _,
+                    Overlapped: &*notification_overlapped as *const _ as *mut _,
+                },
+            },
+        };
+
+        // SAFETY: We pass a valid notification structure. The completion port outlives the queue
+        // for all practical purposes (both are owned by the same worker) and the OVERLAPPED lives
+        // as long as the queue.
+        let completion_queue = unsafe {
+            rio_fn(functions.RIOCreateCompletionQueue)(
+                RIO_INITIAL_COMPLETION_QUEUE_SIZE,
+                &notification as *const _,
+            )
+        };
+
+        if completion_queue == RIO_INVALID_CQ {
+            return Err(last_winsock_error());
+        }
+
+        Ok(Self {
+            functions,
+            completion_queue,
+            capacity: Cell::new(RIO_INITIAL_COMPLETION_QUEUE_SIZE),
+            reserved: Cell::new(0),
+            pending: Cell::new(0),
+            notification_armed: Cell::new(false),
+            _notification_overlapped: notification_overlapped,
+        })
+    }
+
+    /// Whether no requests are in progress, so no more completions will arrive.
+    pub(crate) fn is_inert(&self) -> bool {
+        self.pending.get() == 0
+    }
+
+    /// Delivers the results of any completed requests to their originators. Returns the number of
+    /// completions processed.
+    pub(crate) fn process_completions(&self) -> usize {
+        if self.pending.get() == 0 {
+            return 0;
+        }
+
+        let mut results: [MaybeUninit<RIORESULT>; RIO_DEQUEUE_BATCH_SIZE] =
+            [MaybeUninit::uninit(); RIO_DEQUEUE_BATCH_SIZE];
+
+        // SAFETY: We pass a buffer of the declared size. MaybeUninit is binary-compatible with the
+        // structure, so the operating system can fill it in.
+        let count = unsafe {
+            rio_fn(self.functions.RIODequeueCompletion)(
+                self.completion_queue,
+                results.as_mut_ptr() as *mut RIORESULT,
+                RIO_DEQUEUE_BATCH_SIZE as u32,
+            )
+        };
+
+        if count == RIO_CORRUPT_CQ {
+            panic!("RIO completion queue is corrupted");
+        }
+
+        if count == 0 {
+            return 0;
+        }
+
+        RIO_COMPLETIONS_DEQUEUED.with(|x| x.observe(count as Magnitude));
+
+        for result in &results[..count as usize] {
+            // SAFETY: The operating system has filled in the first `count` results.
+            let result = unsafe { result.assume_init() };
+
+            // SAFETY: The request context is the request we leaked when posting the request, which
+            // we now take back ownership of.
+            let request = unsafe { Rc::from_raw(result.RequestContext as *const RioRequest) };
+
+            self.pending.set(self.pending.get() - 1);
+
+            request.result.set(Some(RioCompletion {
+                status: result.Status,
+                bytes_transferred: result.BytesTransferred,
+            }));
+
+            for waker in request.wakers.take() {
+                waker.wake();
+            }
+        }
+
+        count as usize
+    }
+
+    /// Asks for a notification packet to be posted to the completion port once completions are
+    /// available, unless we have already asked. Called before the I/O driver waits for I/O.
+    pub(crate) fn arm_notification(&self) {
+        if self.notification_armed.get() || self.pending.get() == 0 {
+            return;
+        }
+
+        // SAFETY: Nothing unsafe here, just an FFI call with our own valid queue.
+        let result = unsafe { rio_fn(self.functions.RIONotify)(self.completion_queue) };
+
+        if result != 0 && result != WSAEALREADY.0 {
+            panic!("unexpected error from RIONotify: {result}");
+        }
+
+        self.notification_armed.set(true);
+    }
+
+    /// Records that the notification packet has arrived, so we need to ask again for the next one.
+    pub(crate) fn notification_received(&self) {
+        self.notification_armed.set(false);
+    }
+
+    /// Reserves room in the queue for the completions of a new request queue, growing the queue
+    /// if needed.
+    fn reserve(&self, completions: u32) -> io::Result<()> {
+        let required = self.reserved.get() + completions;
+
+        if required > self.capacity.get() {
+            let new_capacity = (self.capacity.get() * 2).max(required);
+
+            if new_capacity > RIO_MAX_CQ_SIZE {
+                return Err(io::Error::StdIo(std::io::Error::new(
+                    std::io::ErrorKind::OutOfMemory,
+                    "too many sockets are using Registered I/O on this worker",
+                )));
+            }
+
+            // SAFETY: Nothing unsafe here, just an FFI call with our own valid queue.
+            let resized = unsafe {
+                rio_fn(self.functions.RIOResizeCompletionQueue)(self.completion_queue, new_capacity)
+            };
+
+            if !resized.as_bool() {
+                return Err(last_winsock_error());
+            }
+
+            self.capacity.set(new_capacity);
+        }
+
+        self.reserved.set(required);
+        Ok(())
+    }
+
+    fn release(&self, completions: u32) {
+        self.reserved.set(self.reserved.get() - completions);
+    }
+}
+
+impl Drop for RioCompletionQueue {
+    fn drop(&mut self) {
+        // SAFETY: Every request holds a reference to the queue via its socket, so nothing can be
+        // in progress anymore once we are dropped.
+        unsafe {
+            rio_fn(self.functions.RIOCloseCompletionQueue)(self.completion_queue);
+        }
+    }
+}
+
+impl fmt::Debug for RioCompletionQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RioCompletionQueue")
+            .field("completion_queue", &self.completion_queue)
+            .field("capacity", &self.capacity)
+            .field("reserved", &self.reserved)
+            .field("pending", &self.pending)
+            .field("notification_armed", &self.notification_armed)
+            .finish()
+    }
+}
+
+/// A socket that sends and receives data via Registered I/O (RIO) instead of overlapped I/O, owned
+/// by the type that exposes the socket (e.g. `TcpConnection`). The socket must have been created
+/// with `WSA_FLAG_REGISTERED_IO`.
+///
+/// Each socket has its own request queue and a registered memory region, divided into slots for
+/// receiving and for sending. Data is copied between the slots and the buffers of the caller, as
+/// the buffers themselves are not registered with the operating system. For small messages, the
+/// copy costs far less than the per-operation overhead of overlapped I/O that RIO avoids.
+///
+/// RIO requests cannot be canceled. If the caller stops waiting for a receive (e.g. because its
+/// deadline has expired), the request remains in progress and the data it receives is returned by
+/// the next receive, so no data is lost. Any requests in progress complete once the socket is
+/// closed.
+pub(crate) struct RioSocket {
+    state: Rc<RioSocketState>,
+}
+
+impl RioSocket {
+    /// Attaches a request queue to the socket, with the completions delivered to the queue.
+    /// A stream socket may split received data over multiple receives, whereas each receive of a
+    /// datagram socket returns a whole datagram.
+    pub(crate) fn new(
+        queue: Rc<RioCompletionQueue>,
+        socket: SOCKET,
+        is_stream: bool,
+    ) -> io::Result<Self> {
+        let functions = queue.functions;
+
+        queue.reserve(RIO_SLOTS_PER_DIRECTION * 2)?;
+
+        let region_len = RIO_SLOT_SIZE * RIO_SLOTS_PER_DIRECTION as usize * 2;
+        let region = Box::into_raw(vec![0u8; region_len].into_boxed_slice()) as *mut u8;
+
+        // From here on, dropping the state releases everything we have reserved or created.
+        let mut state = RioSocketState {
+            queue,
+            functions,
+            request_queue: RIO_INVALID_RQ,
+            buffer_id: RIO_INVALID_BUFFERID,
+            region,
+            region_len,
+            is_stream,
+            free_receive_slots: RefCell::new((0..RIO_SLOTS_PER_DIRECTION).rev().collect()),
+            free_send_slots: RefCell::new(
+                (RIO_SLOTS_PER_DIRECTION..RIO_SLOTS_PER_DIRECTION * 2)
+                    .rev()
+                    .collect(),
+            ),
+            slot_waiters: RefCell::new(Vec::new()),
+            orphaned_receives: RefCell::new(VecDeque::new()),
+            closed: Cell::new(false),
+        };
+
+        // SAFETY: The region remains allocated until the buffer is deregistered.
+        state.buffer_id = unsafe {
+            rio_fn(functions.RIORegisterBuffer)(PCSTR::from_raw(region), region_len as u32)
+        };
+
+        if state.buffer_id == RIO_INVALID_BUFFERID {
+            return Err(last_winsock_error());
+        }
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. We use one data
+        // buffer per request.
+        state.request_queue = unsafe {
+            rio_fn(functions.RIOCreateRequestQueue)(
+                socket,
+                RIO_SLOTS_PER_DIRECTION,
+                1,
+                RIO_SLOTS_PER_DIRECTION,
+                1,
+                state.queue.completion_queue,
+                state.queue.completion_queue,
+                ptr::null(),
+            )
+        };
+
+        if state.request_queue == RIO_INVALID_RQ {
+            return Err(last_winsock_error());
+        }
+
+        RIO_SOCKETS_REGISTERED.with(Event::observe_unit);
+
+        Ok(Self {
+            state: Rc::new(state),
+        })
+    }
+
+    /// Receives the next block of data into the buffer, returning it with the active region set to
+    /// the data received. See `TcpConnection::receive()`.
+    ///
+    /// The returned future does not borrow the socket. If the socket is dropped before the future
+    /// starts a receive, it fails.
+    pub(crate) fn receive(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        receive(Rc::clone(&self.state), buffer, None, None)
+    }
+
+    /// Like `receive()` but gives up once the deadline expires or once cancellation is requested,
+    /// failing with `io::Error::TimedOut` or `io::Error::Canceled` and returning the buffer
+    /// unchanged. The data that the receive would have returned is returned by the next receive.
+    pub(crate) fn receive_until(
+        &self,
+        buffer: PinnedBuffer,
+        deadline: Option<Instant>,
+        cancellation: Option<CancellationToken>,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        receive(Rc::clone(&self.state), buffer, deadline, cancellation)
+    }
+
+    /// Obtains a receiver that can start receives without borrowing the socket.
+    pub(crate) fn receiver(&self) -> RioReceiver {
+        RioReceiver {
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// Sends the active region of the buffer, returning the buffer once all of it has been sent.
+    ///
+    /// The returned future does not borrow the socket. If the socket is dropped before the future
+    /// has started sending all the data, it fails.
+    pub(crate) fn send(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        send(Rc::clone(&self.state), buffer, None)
+    }
+
+    /// Like `send()` but gives up once the deadline expires, failing with `io::Error::TimedOut`.
+    /// Some of the data may have been sent by then.
+    pub(crate) fn send_until(
+        &self,
+        buffer: PinnedBuffer,
+        deadline: Option<Instant>,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        send(Rc::clone(&self.state), buffer, deadline)
+    }
+}
+
+/// Starts receives on a `RioSocket` without borrowing it, for types that keep their own receives
+/// in progress (e.g. `IngestRing`). Receives started after the socket has been dropped fail.
+#[derive(Clone)]
+pub(crate) struct RioReceiver {
+    state: Rc<RioSocketState>,
+}
+
+impl RioReceiver {
+    /// See `RioSocket::receive()`.
+    pub(crate) fn receive(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        receive(Rc::clone(&self.state), buffer, None, None)
+    }
+
+    /// Waits until data or the end of the stream can be received, without consuming anything.
+    /// Returns how many bytes the next receive can return, which is 0 if the peer has closed the
+    /// stream.
+    pub(crate) fn peek(&self) -> impl Future<Output = io::Result<usize>> + 'static {
+        peek(Rc::clone(&self.state))
+    }
+}
+
+impl fmt::Debug for RioReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RioReceiver")
+            .field("request_queue", &self.state.request_queue)
+            .finish()
+    }
+}
+
+impl Drop for RioSocket {
+    fn drop(&mut self) {
+        // The socket is about to be closed, after which the request queue must not be used.
+        self.state.closed.set(true);
+
+        // The orphaned receives reference the state, so we must let go of them to release it.
+        self.state.orphaned_receives.borrow_mut().clear();
+    }
+}
+
+impl fmt::Debug for RioSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RioSocket")
+            .field("request_queue", &self.state.request_queue)
+            .field("is_stream", &self.state.is_stream)
+            .finish()
+    }
+}
+
+struct RioSocketState {
+    queue: Rc<RioCompletionQueue>,
+    functions: &'static RIO_EXTENSION_FUNCTION_TABLE,
+
+    request_queue: RIO_RQ,
+
+    // The registered memory region, with the receive slots followed by the send slots. The
+    // operating system writes into the region while we hold it, so we only access it via the
+    // pointer and only touch slots that have no request in progress.
+    buffer_id: RIO_BUFFERID,
+    region: *mut u8,
+    region_len: usize,
+
+    is_stream: bool,
+
+    free_receive_slots: RefCell<Vec<u32>>,
+    free_send_slots: RefCell<Vec<u32>>,
+
+    // Tasks waiting for a slot to become free.
+    slot_waiters: RefCell<Vec<Waker>>,
+
+    // Receives that the caller stopped waiting for, oldest first. Their data is returned by the
+    // next receives, to preserve the order of the received data.
+    orphaned_receives: RefCell<VecDeque<Rc<RioRequest>>>,
+
+    // Set once the owner has dropped the socket, after which we must not start new requests.
+    closed: Cell<bool>,
+}
+
+impl RioSocketState {
+    fn slot_ptr(&self, slot: u32) -> *mut u8 {
+        // SAFETY: Slot indexes are always within the region.
+        unsafe { self.region.add(slot as usize * RIO_SLOT_SIZE) }
+    }
+
+    async fn acquire_slot(&self, is_receive: bool) -> io::Result<u32> {
+        let free_slots = if is_receive {
+            &self.free_receive_slots
+        } else {
+            &self.free_send_slots
+        };
+
+        poll_fn(|cx| {
+            if self.closed.get() {
+                return task::Poll::Ready(Err(socket_closed()));
+            }
+
+            match free_slots.borrow_mut().pop() {
+                Some(slot) => task::Poll::Ready(Ok(slot)),
+                None => {
+                    self.slot_waiters.borrow_mut().push(cx.waker().clone());
+                    task::Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    fn release_slot(&self, slot: u32) {
+        if slot < RIO_SLOTS_PER_DIRECTION {
+            self.free_receive_slots.borrow_mut().push(slot);
+        } else {
+            self.free_send_slots.borrow_mut().push(slot);
+        }
+
+        let waiters = mem::take(&mut *self.slot_waiters.borrow_mut());
+
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Posts a receive of up to `len` bytes into a free receive slot.
+    async fn begin_receive(self: &Rc<Self>, len: usize) -> io::Result<Rc<RioRequest>> {
+        let slot = self.acquire_slot(true).await?;
+
+        self.post(slot, len.min(RIO_SLOT_SIZE), true)
+    }
+
+    /// Copies the data into a free send slot and posts a send of it.
+    async fn begin_send(self: &Rc<Self>, data: &[u8]) -> io::Result<Rc<RioRequest>> {
+        assert!(data.len() <= RIO_SLOT_SIZE);
+
+        let slot = self.acquire_slot(false).await?;
+
+        // SAFETY: The slot is free, so the operating system is not accessing it, and the data fits.
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.slot_ptr(slot), data.len());
+        }
+
+        self.post(slot, data.len(), false)
+    }
+
+    fn post(
+        self: &Rc<Self>,
+        slot: u32,
+        len: usize,
+        is_receive: bool,
+    ) -> io::Result<Rc<RioRequest>> {
+        // From here on, the slot is released when the request is dropped.
+        let request = Rc::new(RioRequest {
+            socket: Rc::clone(self),
+            slot,
+            result: Cell::new(None),
+            wakers: RefCell::new(Vec::new()),
+            consumed: Cell::new(0),
+        });
+
+        let rio_buf = RIO_BUF {
+            BufferId: self.buffer_id,
+            Offset: slot * RIO_SLOT_SIZE as u32,
+            Length: len as u32,
+        };
+
+        // The operating system holds a reference to the request until we dequeue its completion.
+        let context = Rc::into_raw(Rc::clone(&request));
+
+        // SAFETY: The slot is within our registered region and is reserved for this request. The
+        // request queue is valid because the socket has not been closed (we checked when we got
+        // the slot and nothing could have closed it since then).
+        let posted = unsafe {
+            if is_receive {
+                rio_fn(self.functions.RIOReceive)(
+                    self.request_queue,
+                    &rio_buf as *const _,
+                    1,
+                    0,
+                    context as *const _,
+                )
+            } else {
+                rio_fn(self.functions.RIOSend)(
+                    self.request_queue,
+                    &rio_buf as *const _,
+                    1,
+                    0,
+                    context as *const _,
+                )
+            }
+        };
+
+        if !posted.as_bool() {
+            let error = last_winsock_error();
+
+            // SAFETY: The operating system did not take the reference, so we take it back.
+            drop(unsafe { Rc::from_raw(context) });
+
+            return Err(error);
+        }
+
+        self.queue.pending.set(self.queue.pending.get() + 1);
+
+        if is_receive {
+            RIO_RECEIVES_POSTED.with(Event::observe_unit);
+        } else {
+            RIO_SENDS_POSTED.with(Event::observe_unit);
+        }
+
+        Ok(request)
+    }
+}
+
+impl Drop for RioSocketState {
+    fn drop(&mut self) {
+        // Every request holds a reference to us, so the region is no longer in use by the
+        // operating system. The request queue itself is released when the socket is closed.
+        if self.buffer_id != RIO_INVALID_BUFFERID {
+            // SAFETY: Nothing unsafe here, just an FFI call with our own valid buffer ID.
+            unsafe {
+                rio_fn(self.functions.RIODeregisterBuffer)(self.buffer_id);
+            }
+        }
+
+        // SAFETY: We allocated the region as a boxed slice of this length in `RioSocket::new()`.
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(self.region, self.region_len)) });
+
+        self.queue.release(RIO_SLOTS_PER_DIRECTION * 2);
+    }
+}
+
+/// A receive or send posted to a request queue, shared between the operating system (until the
+/// completion is dequeued) and the task waiting for the result.
+struct RioRequest {
+    socket: Rc<RioSocketState>,
+    slot: u32,
+
+    result: Cell<Option<RioCompletion>>,
+
+    // The tasks waiting for the result. There can be more than one if a receive and a peek are
+    // waiting for the same request.
+    wakers: RefCell<Vec<Waker>>,
+
+    // How many of the received bytes have already been returned to the caller, if the data did not
+    // fit into a single buffer of the caller.
+    consumed: Cell<u32>,
+}
+
+impl RioRequest {
+    async fn wait(&self) -> RioCompletion {
+        poll_fn(|cx| match self.result.get() {
+            Some(completion) => task::Poll::Ready(completion),
+            None => {
+                let mut wakers = self.wakers.borrow_mut();
+
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+
+                task::Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl Drop for RioRequest {
+    fn drop(&mut self) {
+        self.socket.release_slot(self.slot);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RioCompletion {
+    // A Winsock error code or 0 on success.
+    status: i32,
+    bytes_transferred: u32,
+}
+
+impl RioCompletion {
+    fn into_result(self) -> io::Result<u32> {
+        if self.status == 0 {
+            Ok(self.bytes_transferred)
+        } else {
+            Err(io::Error::Winsock {
+                code: SOCKET_ERROR,
+                detail: WSA_ERROR(self.status),
+            })
+        }
+    }
+}
+
+/// Holds a receive while we wait for it, handing it over to the next receive if we stop waiting.
+struct ReceiveInProgress {
+    request: Option<Rc<RioRequest>>,
+
+    // Whether the request was taken from the orphaned receives, in which case it goes back to the
+    // front of the queue, as it is older than any other orphaned receive.
+    was_orphaned: bool,
+}
+
+impl Drop for ReceiveInProgress {
+    fn drop(&mut self) {
+        let Some(request) = self.request.take() else {
+            return;
+        };
+
+        let socket = Rc::clone(&request.socket);
+
+        if socket.closed.get() {
+            return;
+        }
+
+        let mut orphaned_receives = socket.orphaned_receives.borrow_mut();
+
+        if self.was_orphaned {
+            orphaned_receives.push_front(request);
+        } else {
+            orphaned_receives.push_back(request);
+        }
+    }
+}
+
+async fn receive(
+    socket: Rc<RioSocketState>,
+    mut buffer: PinnedBuffer,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+) -> OperationResult {
+    // If we give up, the receive in progress is orphaned and the buffer is left unchanged.
+    match give_up_at(receive_into(&socket, &mut buffer), deadline, cancellation).await {
+        Ok(()) => Ok(buffer),
+        Err(e) => Err(OperationError::new(e, buffer)),
+    }
+}
+
+async fn receive_into(socket: &Rc<RioSocketState>, buffer: &mut PinnedBuffer) -> io::Result<()> {
+    let orphaned = socket.orphaned_receives.borrow_mut().pop_front();
+    let was_orphaned = orphaned.is_some();
+
+    let request = match orphaned {
+        Some(request) => request,
+        None => socket.begin_receive(buffer.len()).await?,
+    };
+
+    let mut in_progress = ReceiveInProgress {
+        request: Some(request),
+        was_orphaned,
+    };
+
+    let completion = in_progress
+        .request
+        .as_ref()
+        .expect("we just set it")
+        .wait()
+        .await;
+
+    // We are done waiting, so the request is ours to consume from here on.
+    let request = in_progress.request.take().expect("we just set it");
+
+    let bytes_transferred = completion.into_result()? as usize;
+
+    let consumed = request.consumed.get() as usize;
+    let len = (bytes_transferred - consumed).min(buffer.len());
+
+    // SAFETY: The request has completed, so the operating system no longer touches the slot, and
+    // we stay within the bytes it received.
+    unsafe {
+        ptr::copy_nonoverlapping(
+            socket.slot_ptr(request.slot).add(consumed),
+            buffer.as_mut_slice().as_mut_ptr(),
+            len,
+        );
+    }
+
+    buffer.set_len(len);
+
+    if consumed + len < bytes_transferred {
+        if !socket.is_stream {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "datagram was larger than the buffer and has been truncated",
+            )));
+        }
+
+        // The rest of the data is returned by the next receive.
+        request.consumed.set((consumed + len) as u32);
+        socket.orphaned_receives.borrow_mut().push_front(request);
+    }
+
+    Ok(())
+}
+
+async fn peek(socket: Rc<RioSocketState>) -> io::Result<usize> {
+    // The next receive takes the oldest orphaned receive, so that is the one to wait for. If there
+    // is none, we start one and leave it for the next receive.
+    let front = socket.orphaned_receives.borrow().front().cloned();
+
+    let request = match front {
+        Some(request) => request,
+        None => {
+            let request = socket.begin_receive(RIO_SLOT_SIZE).await?;
+            socket
+                .orphaned_receives
+                .borrow_mut()
+                .push_back(Rc::clone(&request));
+            request
+        }
+    };
+
+    let bytes_transferred = request.wait().await.into_result()?;
+
+    Ok((bytes_transferred - request.consumed.get()) as usize)
+}
+
+async fn send(
+    socket: Rc<RioSocketState>,
+    buffer: PinnedBuffer,
+    deadline: Option<Instant>,
+) -> OperationResult {
+    match give_up_at(send_from(&socket, &buffer), deadline, None).await {
+        Ok(()) => Ok(buffer),
+        Err(e) => Err(OperationError::new(e, buffer)),
+    }
+}
+
+async fn send_from(socket: &Rc<RioSocketState>, buffer: &PinnedBuffer) -> io::Result<()> {
+    if !socket.is_stream && buffer.len() > RIO_SLOT_SIZE {
+        return Err(io::Error::InvalidOptions(format!(
+            "datagrams sent via Registered I/O must not be larger than {RIO_SLOT_SIZE} bytes"
+        )));
+    }
+
+    let mut requests = Vec::new();
+    let mut offset = 0;
+
+    // An empty buffer still results in one (empty) send, which is meaningful for datagrams.
+    loop {
+        let len = (buffer.len() - offset).min(RIO_SLOT_SIZE);
+
+        requests.push(
+            socket
+                .begin_send(&buffer.as_slice()[offset..offset + len])
+                .await?,
+        );
+
+        offset += len;
+
+        if offset == buffer.len() {
+            break;
+        }
+    }
+
+    for request in requests {
+        request.wait().await.into_result()?;
+    }
+
+    Ok(())
+}
+
+/// Runs the future unless the deadline expires or cancellation is requested first, in which case
+/// the future is dropped and the corresponding error is returned. Dropping the future does not
+/// cancel any requests it has posted (see `RioSocket`).
+async fn give_up_at<F, T>(
+    future: F,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    if deadline.is_none() && cancellation.is_none() {
+        return future.await;
+    }
+
+    let timed_out = async {
+        match deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => future::pending().await,
+        }
+    };
+
+    let canceled = async {
+        match &cancellation {
+            Some(token) => token.cancelled().await,
+            None => future::pending().await,
+        }
+    };
+
+    let give_up = async {
+        match select2(timed_out, canceled).await {
+            Either::Left(()) => io::Error::TimedOut,
+            Either::Right(()) => io::Error::Canceled,
+        }
+    };
+
+    // The future goes first, so a result that is ready wins over giving up.
+    match select2(future, give_up).await {
+        Either::Left(result) => result,
+        Either::Right(error) => Err(error),
+    }
+}
+
+fn socket_closed() -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        "socket has been closed",
+    ))
+}
+
+const RIO_COMPLETIONS_DEQUEUED_BUCKETS: &[Magnitude] = &[0, 1, 16, 64, 128, 256];
+
+thread_local! {
+    static RIO_COMPLETIONS_DEQUEUED: Event = EventBuilder::new()
+        .name("io_rio_completions_dequeued")
+        .buckets(RIO_COMPLETIONS_DEQUEUED_BUCKETS)
+        .build()
+        .unwrap();
+
+    static RIO_SOCKETS_REGISTERED: Event = EventBuilder::new()
+        .name("io_rio_sockets_registered")
+        .build()
+        .unwrap();
+
+    static RIO_RECEIVES_POSTED: Event = EventBuilder::new()
+        .name("io_rio_receives_posted")
+        .build()
+        .unwrap();
+
+    static RIO_SENDS_POSTED: Event = EventBuilder::new()
+        .name("io_rio_sends_posted")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,13 @@
+mod mailslot;
+mod named_pipe;
+mod ring_memory;
+mod shared_ring;
+#[cfg(feature = "typed-channel")]
+mod typed_channel;
+
+pub use mailslot::*;
+pub(crate) use named_pipe::*;
+pub(crate) use ring_memory::*;
+pub use shared_ring::*;
+#[cfg(feature = "typed-channel")]
+pub use typed_channel::*;
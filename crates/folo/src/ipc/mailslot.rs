@@ -0,0 +1,183 @@
+use crate::{
+    io::{self, OperationKind, OperationResult, PinnedBuffer},
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    util::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{GENERIC_WRITE, HANDLE},
+        Storage::FileSystem::{
+            CreateFileW, ReadFile, WriteFile, FILE_FLAG_OVERLAPPED, FILE_SHARE_READ, OPEN_EXISTING,
+        },
+        System::Mailslots::{CreateMailslotW, GetMailslotInfo},
+    },
+};
+
+// Not exposed by the windows crate.
+const MAILSLOT_WAIT_FOREVER: u32 = u32::MAX;
+const MAILSLOT_NO_MESSAGE: u32 = u32::MAX;
+
+/// The server (reading) end of a mailslot, bound to the I/O driver of the current async worker.
+///
+/// Mailslots are a legacy one-way datagram IPC mechanism - any number of clients can write
+/// messages into the mailslot, which only the process that created it can read. Each message is
+/// received whole, in the order the messages were written.
+///
+/// Use this only for integrating with existing components that communicate via mailslots. For new
+/// IPC, prefer `TypedChannel` or `SharedRing`.
+#[derive(Debug)]
+pub struct MailslotServer {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl MailslotServer {
+    /// Creates a mailslot that accepts messages of any length. The name must be of the form
+    /// `\\.\mailslot\<name>`.
+    ///
+    /// Fails if a mailslot with the same name already exists on this computer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn create(name: &str) -> io::Result<Self> {
+        let name = HSTRING::from(name);
+
+        let handle =
+            spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+                // SAFETY: We are required to close the handle once we are done with it,
+                // which we do via OwnedHandle that closes the handle on drop.
+                Ok(unsafe {
+                    OwnedHandle::new(CreateMailslotW(&name, 0, MAILSLOT_WAIT_FOREVER, None)?)
+                })
+            })
+            .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self { handle })
+    }
+
+    /// Waits for the next message and receives it into the buffer, with the active region of the
+    /// returned buffer set to the message.
+    ///
+    /// The message must fit into the buffer. If it does not, the operation fails with
+    /// `ERROR_INSUFFICIENT_BUFFER` and the message remains in the mailslot - use
+    /// `next_message_len()` to size the buffer before trying again.
+    pub async fn receive(&self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.handle);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do.
+        unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(ReadFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        }
+    }
+
+    /// The length of the next message in the mailslot, or `None` if there are no messages.
+    pub fn next_message_len(&self) -> io::Result<Option<usize>> {
+        let mut next_size = 0;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        unsafe {
+            GetMailslotInfo(*self.handle, None, Some(&mut next_size), None, None)?;
+        }
+
+        Ok((next_size != MAILSLOT_NO_MESSAGE).then_some(next_size as usize))
+    }
+}
+
+#[negative_impl]
+impl !Send for MailslotServer {}
+#[negative_impl]
+impl !Sync for MailslotServer {}
+
+/// The client (writing) end of a mailslot, bound to the I/O driver of the current async worker.
+#[derive(Debug)]
+pub struct MailslotClient {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl MailslotClient {
+    /// Opens a mailslot for writing. The name must be one of:
+    ///
+    /// * `\\.\mailslot\<name>` - the mailslot on this computer.
+    /// * `\\<computer>\mailslot\<name>` - the mailslot on a remote computer.
+    /// * `\\<domain>\mailslot\<name>` - every mailslot with that name in the domain.
+    /// * `\\*\mailslot\<name>` - every mailslot with that name in the primary domain.
+    ///
+    /// Fails with `ERROR_FILE_NOT_FOUND` if the name refers to a mailslot on this computer that
+    /// does not exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn open(name: &str) -> io::Result<Self> {
+        let name = HSTRING::from(name);
+
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileW(
+                    &name,
+                    GENERIC_WRITE.0,
+                    FILE_SHARE_READ,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self { handle })
+    }
+
+    /// Writes the active region of the buffer into the mailslot as a single message.
+    ///
+    /// Messages sent to more than one computer (via a domain name or `*`) are limited to 424 bytes.
+    /// There is no confirmation that anyone received the message.
+    pub async fn send(&self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.handle);
+        operation.set_kind(OperationKind::Send);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do.
+        unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(WriteFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for MailslotClient {}
+#[negative_impl]
+impl !Sync for MailslotClient {}
@@ -0,0 +1,239 @@
+use crate::{
+    io::{self, OperationKind, OperationResult, PinnedBuffer},
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    util::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{
+            ERROR_BROKEN_PIPE, ERROR_NO_DATA, ERROR_PIPE_CONNECTED, ERROR_PIPE_NOT_CONNECTED,
+            GENERIC_READ, GENERIC_WRITE, HANDLE, STATUS_PIPE_BROKEN, STATUS_PIPE_CLOSING,
+            STATUS_PIPE_DISCONNECTED,
+        },
+        Storage::FileSystem::{
+            CreateFileW, ReadFile, WriteFile, FILE_FLAGS_AND_ATTRIBUTES,
+            FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, FILE_SHARE_NONE, OPEN_EXISTING,
+            PIPE_ACCESS_DUPLEX,
+        },
+        System::Pipes::{
+            ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    },
+};
+
+/// Size of the buffers the operating system allocates for each direction of a pipe instance. This
+/// is only a hint - writes larger than this still succeed, they just complete once the reader has
+/// made room.
+const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// A connected named pipe (either end), bound to the I/O driver of the current async worker.
+#[derive(Debug)]
+pub(crate) struct PipeStream {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl PipeStream {
+    /// Connects to a pipe instance that a server has created and is waiting for clients on.
+    ///
+    /// Fails with `ERROR_FILE_NOT_FOUND` if there is no such pipe and with `ERROR_PIPE_BUSY` if
+    /// there is no free instance of the pipe.
+    pub(crate) async fn connect(name: &str) -> io::Result<Self> {
+        let name = HSTRING::from(name);
+
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileW(
+                    &name,
+                    (GENERIC_READ | GENERIC_WRITE).0,
+                    FILE_SHARE_NONE,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self { handle })
+    }
+
+    /// Reads whatever data is available, up to the length of the buffer. An empty buffer signals
+    /// that the other end has closed the pipe.
+    pub(crate) async fn read(&self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.handle);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do.
+        let result = unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(ReadFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        };
+
+        // The other end closing the pipe is reported as an error, either immediately or via the
+        // completion status. For us, it is just the end of the stream.
+        match result {
+            Err(io::OperationError { inner, mut buffer }) if is_pipe_closed(&inner) => {
+                buffer.set_len(0);
+                Ok(buffer)
+            }
+            other => other,
+        }
+    }
+
+    /// Writes the contents of the buffer. Fails with `ConnectionReset` if the other end has closed
+    /// the pipe.
+    pub(crate) async fn write(&self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.handle);
+        operation.set_kind(OperationKind::Send);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do.
+        let result = unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(WriteFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        };
+
+        match result {
+            Err(io::OperationError { inner, buffer }) if is_pipe_closed(&inner) => {
+                Err(io::OperationError::new(io::Error::ConnectionReset, buffer))
+            }
+            other => other,
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for PipeStream {}
+#[negative_impl]
+impl !Sync for PipeStream {}
+
+/// The server end of a named pipe instance that is waiting for a client to connect.
+#[derive(Debug)]
+pub(crate) struct PipeInstance {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl PipeInstance {
+    /// Creates a new instance of the named pipe, accepting only local clients. If `first` is set,
+    /// this fails if the pipe already exists (e.g. because another process is serving it).
+    pub(crate) async fn create(name: &str, first: bool) -> io::Result<Self> {
+        let name = HSTRING::from(name);
+
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            let open_mode = PIPE_ACCESS_DUPLEX
+                | FILE_FLAG_OVERLAPPED
+                | if first {
+                    FILE_FLAG_FIRST_PIPE_INSTANCE
+                } else {
+                    FILE_FLAGS_AND_ATTRIBUTES(0)
+                };
+
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            unsafe {
+                let handle = CreateNamedPipeW(
+                    &name,
+                    open_mode,
+                    PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                    PIPE_UNLIMITED_INSTANCES,
+                    PIPE_BUFFER_SIZE,
+                    PIPE_BUFFER_SIZE,
+                    0,
+                    None,
+                );
+
+                if handle.is_invalid() {
+                    return Err(windows::core::Error::from_win32().into());
+                }
+
+                Ok(OwnedHandle::new(handle))
+            }
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle))?;
+
+        Ok(Self { handle })
+    }
+
+    /// Waits for a client to connect to this instance.
+    pub(crate) async fn wait_for_client(self) -> io::Result<PipeStream> {
+        // We do not transfer any data but the I/O driver still wants a buffer.
+        let mut operation =
+            current_async_agent::with_io(|io| io.new_operation(PinnedBuffer::from_pool()));
+        operation.cancel_on_drop(*self.handle);
+        operation.set_kind(OperationKind::Accept);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do.
+        unsafe {
+            operation
+                .begin(|_, overlapped, _| {
+                    match ConnectNamedPipe(*self.handle, Some(overlapped)) {
+                        // The client connected between creation and our wait, which is just as
+                        // good. This is reported synchronously, without a completion.
+                        Err(e) if e.code() == ERROR_PIPE_CONNECTED.to_hresult() => Ok(()),
+                        result => Ok(result?),
+                    }
+                })
+                .await
+                .map_err(|e| e.into_inner())?;
+        }
+
+        Ok(PipeStream {
+            handle: self.handle,
+        })
+    }
+}
+
+#[negative_impl]
+impl !Send for PipeInstance {}
+#[negative_impl]
+impl !Sync for PipeInstance {}
+
+/// Whether this is an error that the operating system uses to report that the other end of a
+/// pipe has been closed.
+fn is_pipe_closed(error: &io::Error) -> bool {
+    match error {
+        io::Error::Windows(e) => [
+            ERROR_BROKEN_PIPE.to_hresult(),
+            ERROR_NO_DATA.to_hresult(),
+            ERROR_PIPE_NOT_CONNECTED.to_hresult(),
+            STATUS_PIPE_BROKEN.to_hresult(),
+            STATUS_PIPE_CLOSING.to_hresult(),
+            STATUS_PIPE_DISCONNECTED.to_hresult(),
+        ]
+        .contains(&e.code()),
+        _ => false,
+    }
+}
@@ -0,0 +1,400 @@
+use crate::io;
+use std::{
+    mem, ptr,
+    sync::atomic::{self, AtomicU32, AtomicU64, Ordering},
+};
+
+/// How many readers may be attached to one shared ring at the same time.
+pub(crate) const MAX_READERS: usize = 64;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"foloring");
+const VERSION: u32 = 1;
+
+/// Slot stamp that marks a slot as being written to.
+const STAMP_WRITING: u64 = 0;
+
+/// The layout of a shared ring in a region of memory that may be shared between processes. The
+/// region starts with a header, followed by `capacity` slots of equal size, each holding one
+/// message of up to `max_message_len` bytes.
+///
+/// There is exactly one writer, which overwrites the oldest slot on every publish. Readers do not
+/// hold the writer back - a reader that falls more than `capacity` messages behind misses the
+/// overwritten messages. Each slot is guarded by a stamp (seqlock-style) that tells readers which
+/// message the slot holds and whether it was overwritten while they were reading it.
+///
+/// This type does not own the memory, it only knows how to interpret it.
+#[derive(Debug)]
+pub(crate) struct RingMemory {
+    base: *mut u8,
+    capacity: u32,
+    max_message_len: u32,
+}
+
+#[repr(C)]
+struct Header {
+    // Written last during initialization, so a reader that sees this knows the rest is valid.
+    magic: AtomicU64,
+    version: u32,
+    capacity: u32,
+    max_message_len: u32,
+    _reserved: u32,
+
+    /// Sequence number of the next message to be published. This is also the number of messages
+    /// published so far.
+    write_sequence: AtomicU64,
+
+    readers: [ReaderSlot; MAX_READERS],
+}
+
+#[repr(C)]
+struct ReaderSlot {
+    claimed: AtomicU32,
+
+    // Set by a reader that is about to wait for the next message. The writer clears it and
+    // signals the event of the reader after publishing.
+    waiting: AtomicU32,
+}
+
+#[repr(C)]
+struct SlotHeader {
+    /// `STAMP_WRITING` while the slot is being written, sequence number + 1 of the message in the
+    /// slot otherwise (so a never written slot also reads as "being written").
+    stamp: AtomicU64,
+    len: u32,
+    _reserved: u32,
+}
+
+impl RingMemory {
+    /// The number of bytes of memory required for a ring of the specified dimensions, or `None` if
+    /// the dimensions are too large to be addressable.
+    pub(crate) fn required_size(capacity: u32, max_message_len: u32) -> Option<usize> {
+        let slot_size = slot_size(max_message_len)?;
+
+        (capacity as usize)
+            .checked_mul(slot_size)?
+            .checked_add(mem::size_of::<Header>())
+            .filter(|size| *size <= isize::MAX as usize)
+    }
+
+    /// Lays out a new ring in the memory region.
+    ///
+    /// # Safety
+    ///
+    /// The region must be at least `required_size()` bytes long, 8-byte aligned, zero-filled and
+    /// not yet visible to any readers. It must remain valid for the lifetime of the returned value.
+    pub(crate) unsafe fn initialize(base: *mut u8, capacity: u32, max_message_len: u32) -> Self {
+        // SAFETY: The caller guarantees the region is large enough and nobody else is using it.
+        unsafe {
+            let header = base as *mut Header;
+            ptr::addr_of_mut!((*header).version).write(VERSION);
+            ptr::addr_of_mut!((*header).capacity).write(capacity);
+            ptr::addr_of_mut!((*header).max_message_len).write(max_message_len);
+        }
+
+        let ring = Self {
+            base,
+            capacity,
+            max_message_len,
+        };
+
+        ring.header().magic.store(MAGIC, Ordering::Release);
+
+        ring
+    }
+
+    /// Interprets a memory region initialized (possibly by another process) via `initialize()`.
+    ///
+    /// # Safety
+    ///
+    /// The region must be at least `size` bytes long, 8-byte aligned and remain valid for the
+    /// lifetime of the returned value.
+    pub(crate) unsafe fn attach(base: *mut u8, size: usize) -> io::Result<Self> {
+        if size < mem::size_of::<Header>() {
+            return Err(io::Error::InvalidOptions(
+                "memory region is too small to be a shared ring".to_string(),
+            ));
+        }
+
+        // SAFETY: The caller guarantees the region is valid and we checked it fits the header.
+        let header = unsafe { &*(base as *const Header) };
+
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(io::Error::InvalidOptions(
+                "memory region is not an initialized shared ring".to_string(),
+            ));
+        }
+
+        if header.version != VERSION {
+            return Err(io::Error::InvalidOptions(format!(
+                "shared ring has layout version {} but only version {VERSION} is supported",
+                header.version
+            )));
+        }
+
+        let capacity = header.capacity;
+        let max_message_len = header.max_message_len;
+
+        let fits =
+            Self::required_size(capacity, max_message_len).is_some_and(|required| required <= size);
+
+        if !fits {
+            return Err(io::Error::InvalidOptions(
+                "shared ring header does not match the size of the memory region".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            base,
+            capacity,
+            max_message_len,
+        })
+    }
+
+    pub(crate) fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub(crate) fn max_message_len(&self) -> u32 {
+        self.max_message_len
+    }
+
+    /// Sequence number of the next message to be published.
+    pub(crate) fn write_sequence(&self) -> u64 {
+        self.header().write_sequence.load(Ordering::SeqCst)
+    }
+
+    /// The sequence number of the oldest message a reader whose next message is `next_sequence`
+    /// can still read, considering that older messages may have been overwritten.
+    pub(crate) fn oldest_readable(&self, next_sequence: u64, write_sequence: u64) -> u64 {
+        next_sequence.max(write_sequence.saturating_sub(self.capacity as u64))
+    }
+
+    /// Publishes a message into the next slot, overwriting the oldest message.
+    ///
+    /// # Safety
+    ///
+    /// There must be only one writer and the message must not exceed `max_message_len`.
+    pub(crate) unsafe fn publish(&self, message: &[u8]) {
+        debug_assert!(message.len() <= self.max_message_len as usize);
+
+        let header = self.header();
+        let sequence = header.write_sequence.load(Ordering::Relaxed);
+        let slot = self.slot(sequence);
+
+        // SAFETY: The slot is within the region and the stamp is at the start of the slot.
+        let stamp = unsafe { &(*slot).stamp };
+
+        stamp.store(STAMP_WRITING, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        // SAFETY: The slot is within the region, has room for `max_message_len` bytes and we are
+        // the only writer. Readers may be reading the same bytes concurrently but they detect
+        // this via the stamp and discard what they read.
+        unsafe {
+            ptr::addr_of_mut!((*slot).len).write_volatile(message.len() as u32);
+            ptr::copy_nonoverlapping(message.as_ptr(), payload(slot), message.len());
+        }
+
+        stamp.store(sequence + 1, Ordering::Release);
+        header.write_sequence.store(sequence + 1, Ordering::SeqCst);
+    }
+
+    /// Reads the message with the specified sequence number, returning `None` if the slot no
+    /// longer (or does not yet) hold that message.
+    pub(crate) fn read(&self, sequence: u64) -> Option<Vec<u8>> {
+        let slot = self.slot(sequence);
+
+        // SAFETY: The slot is within the region and the stamp is at the start of the slot.
+        let stamp = unsafe { &(*slot).stamp };
+
+        if stamp.load(Ordering::Acquire) != sequence + 1 {
+            return None;
+        }
+
+        // SAFETY: The slot is within the region and has room for `max_message_len` bytes. The
+        // writer may be overwriting the slot concurrently, in which case we read garbage - this is
+        // detected below by the stamp having changed, so we never hand out garbage.
+        let message = unsafe {
+            let len = ptr::addr_of!((*slot).len)
+                .read_volatile()
+                .min(self.max_message_len) as usize;
+
+            let mut message = Vec::with_capacity(len);
+            ptr::copy_nonoverlapping(payload(slot), message.as_mut_ptr(), len);
+            message.set_len(len);
+            message
+        };
+
+        atomic::fence(Ordering::Acquire);
+
+        if stamp.load(Ordering::Relaxed) != sequence + 1 {
+            return None;
+        }
+
+        Some(message)
+    }
+
+    /// Claims a free reader slot, returning its index or `None` if all slots are taken.
+    pub(crate) fn claim_reader(&self) -> Option<usize> {
+        self.header().readers.iter().position(|reader| {
+            reader
+                .claimed
+                .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    pub(crate) fn release_reader(&self, index: usize) {
+        let reader = &self.header().readers[index];
+        reader.waiting.store(0, Ordering::Relaxed);
+        reader.claimed.store(0, Ordering::Release);
+    }
+
+    /// Marks the reader as waiting for the next message. The reader must check for new messages
+    /// again after this, as one may have been published just before.
+    pub(crate) fn set_waiting(&self, index: usize) {
+        self.header().readers[index]
+            .waiting
+            .store(1, Ordering::SeqCst);
+    }
+
+    /// Clears the waiting mark of every waiting reader, calling `f` with the index of each.
+    pub(crate) fn take_waiting(&self, mut f: impl FnMut(usize)) {
+        for (index, reader) in self.header().readers.iter().enumerate() {
+            // Checking first avoids dirtying the cache line of readers that are not waiting.
+            if reader.waiting.load(Ordering::SeqCst) != 0
+                && reader.waiting.swap(0, Ordering::SeqCst) != 0
+            {
+                f(index);
+            }
+        }
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: The region is valid and starts with the header (guaranteed by the constructors).
+        unsafe { &*(self.base as *const Header) }
+    }
+
+    fn slot(&self, sequence: u64) -> *mut SlotHeader {
+        let index = (sequence % self.capacity as u64) as usize;
+
+        // The size cannot overflow because `required_size()` was checked by the constructors.
+        let slot_size = slot_size(self.max_message_len).expect("validated on construction");
+
+        // SAFETY: The index is less than the capacity, so the slot is within the region.
+        unsafe {
+            self.base
+                .add(mem::size_of::<Header>() + index * slot_size)
+                .cast()
+        }
+    }
+}
+
+/// Size of one slot, including its header. Rounded up to keep every slot 8-byte aligned.
+fn slot_size(max_message_len: u32) -> Option<usize> {
+    (max_message_len as usize)
+        .checked_next_multiple_of(8)?
+        .checked_add(mem::size_of::<SlotHeader>())
+}
+
+/// # Safety
+///
+/// The slot pointer must be valid.
+unsafe fn payload(slot: *mut SlotHeader) -> *mut u8 {
+    // SAFETY: The payload directly follows the slot header, within the same slot.
+    unsafe { (slot as *mut u8).add(mem::size_of::<SlotHeader>()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Backed by u64 to get the required alignment.
+    fn new_region(capacity: u32, max_message_len: u32) -> Vec<u64> {
+        let size = RingMemory::required_size(capacity, max_message_len).unwrap();
+        vec![0; size.div_ceil(8)]
+    }
+
+    #[test]
+    fn publish_and_read() {
+        let mut region = new_region(4, 16);
+        let writer = unsafe { RingMemory::initialize(region.as_mut_ptr().cast(), 4, 16) };
+        let reader =
+            unsafe { RingMemory::attach(region.as_mut_ptr().cast(), region.len() * 8) }.unwrap();
+
+        assert_eq!(reader.capacity(), 4);
+        assert_eq!(reader.max_message_len(), 16);
+        assert_eq!(reader.write_sequence(), 0);
+        assert_eq!(reader.read(0), None);
+
+        unsafe {
+            writer.publish(b"hello");
+            writer.publish(b"");
+        }
+
+        assert_eq!(reader.write_sequence(), 2);
+        assert_eq!(reader.read(0), Some(b"hello".to_vec()));
+        assert_eq!(reader.read(1), Some(Vec::new()));
+        assert_eq!(reader.read(2), None);
+    }
+
+    #[test]
+    fn overwritten_messages_are_not_readable() {
+        let mut region = new_region(2, 8);
+        let ring = unsafe { RingMemory::initialize(region.as_mut_ptr().cast(), 2, 8) };
+
+        for message in [b"one", b"two", b"six"] {
+            unsafe { ring.publish(message) };
+        }
+
+        assert_eq!(ring.read(0), None);
+        assert_eq!(ring.read(1), Some(b"two".to_vec()));
+        assert_eq!(ring.read(2), Some(b"six".to_vec()));
+
+        assert_eq!(ring.oldest_readable(0, ring.write_sequence()), 1);
+        assert_eq!(ring.oldest_readable(2, ring.write_sequence()), 2);
+        assert_eq!(ring.oldest_readable(3, ring.write_sequence()), 3);
+    }
+
+    #[test]
+    fn attach_rejects_invalid_regions() {
+        let mut region = new_region(2, 8);
+        let size = region.len() * 8;
+
+        assert!(unsafe { RingMemory::attach(region.as_mut_ptr().cast(), size) }.is_err());
+
+        unsafe { RingMemory::initialize(region.as_mut_ptr().cast(), 2, 8) };
+
+        assert!(unsafe { RingMemory::attach(region.as_mut_ptr().cast(), size - 8) }.is_err());
+        assert!(unsafe { RingMemory::attach(region.as_mut_ptr().cast(), size) }.is_ok());
+    }
+
+    #[test]
+    fn reader_slots() {
+        let mut region = new_region(1, 8);
+        let ring = unsafe { RingMemory::initialize(region.as_mut_ptr().cast(), 1, 8) };
+
+        let first = ring.claim_reader().unwrap();
+        let second = ring.claim_reader().unwrap();
+        assert_ne!(first, second);
+
+        ring.set_waiting(second);
+
+        let mut signaled = Vec::new();
+        ring.take_waiting(|index| signaled.push(index));
+        assert_eq!(signaled, vec![second]);
+
+        signaled.clear();
+        ring.take_waiting(|index| signaled.push(index));
+        assert!(signaled.is_empty());
+
+        ring.release_reader(first);
+        assert_eq!(ring.claim_reader(), Some(first));
+
+        for _ in 2..MAX_READERS {
+            assert!(ring.claim_reader().is_some());
+        }
+
+        assert_eq!(ring.claim_reader(), None);
+    }
+}
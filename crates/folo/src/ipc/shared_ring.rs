@@ -0,0 +1,404 @@
+use crate::{
+    io,
+    ipc::{RingMemory, MAX_READERS},
+    util::{wait_for_handle, OwnedHandle},
+};
+use negative_impl::negative_impl;
+use std::mem;
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{GetLastError, ERROR_ALREADY_EXISTS, HANDLE, INVALID_HANDLE_VALUE},
+        System::{
+            Memory::{
+                CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery,
+                FILE_MAP_READ, FILE_MAP_WRITE, MEMORY_BASIC_INFORMATION,
+                MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+            },
+            Threading::{CreateEventW, OpenEventW, SetEvent, SYNCHRONIZATION_SYNCHRONIZE},
+        },
+    },
+};
+
+const DEFAULT_CAPACITY: u32 = 1024;
+const DEFAULT_MAX_MESSAGE_LEN: u32 = 4096;
+
+/// Builds a `SharedRing`, creating the named shared memory region and events that readers in
+/// other processes open via `SharedRingReader::open()`.
+#[derive(Debug)]
+pub struct SharedRingBuilder {
+    name: String,
+    capacity: u32,
+    max_message_len: u32,
+}
+
+impl SharedRingBuilder {
+    /// The name identifies the ring to readers. It is a kernel object name, so it may be prefixed
+    /// with `Local\` (the default, visible to the current session) or `Global\` (visible to all
+    /// sessions, requires the `SeCreateGlobalPrivilege` privilege).
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            capacity: DEFAULT_CAPACITY,
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+        }
+    }
+
+    /// How many messages the ring holds. Readers that fall further behind than this miss the
+    /// oldest messages. Defaults to 1024.
+    pub fn capacity(mut self, value: u32) -> Self {
+        self.capacity = value;
+        self
+    }
+
+    /// The maximum length of a message in bytes. Every message occupies this much shared memory,
+    /// regardless of its actual length. Defaults to 4096.
+    pub fn max_message_len(mut self, value: u32) -> Self {
+        self.max_message_len = value;
+        self
+    }
+
+    /// Creates the ring. Fails if a kernel object with the same name already exists, which
+    /// includes the case where a previous ring with the same name still has readers attached.
+    pub fn build(self) -> io::Result<SharedRing> {
+        if self.capacity == 0 || self.max_message_len == 0 {
+            return Err(io::Error::InvalidOptions(
+                "shared ring capacity and maximum message length must be at least 1".to_string(),
+            ));
+        }
+
+        let size =
+            RingMemory::required_size(self.capacity, self.max_message_len).ok_or_else(|| {
+                io::Error::InvalidOptions(format!(
+                    "shared ring of {} messages of up to {} bytes is too large",
+                    self.capacity, self.max_message_len
+                ))
+            })?;
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let mapping = unsafe {
+            OwnedHandle::new(CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                None,
+                PAGE_READWRITE,
+                (size as u64 >> 32) as u32,
+                size as u32,
+                &HSTRING::from(&self.name),
+            )?)
+        };
+
+        // If the mapping already exists, we get a handle to the existing one. We never want to
+        // share a ring with another writer, so this is a failure.
+        //
+        // SAFETY: Nothing unsafe here, just an FFI call.
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            return Err(windows::core::Error::from(ERROR_ALREADY_EXISTS.to_hresult()).into());
+        }
+
+        // Readers open these when they attach, so they must exist before the ring is initialized.
+        let reader_events = (0..MAX_READERS)
+            .map(|index| create_reader_event(&self.name, index))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let view = MappedView::new(*mapping)?;
+
+        // SAFETY: A new mapping is zero-filled, page-aligned, at least `size` bytes long and
+        // readers cannot use it before we initialize it. The view outlives the ring memory because
+        // both are owned by the SharedRing.
+        let ring =
+            unsafe { RingMemory::initialize(view.base(), self.capacity, self.max_message_len) };
+
+        Ok(SharedRing {
+            ring,
+            reader_events,
+            _view: view,
+            _mapping: mapping,
+        })
+    }
+}
+
+/// The writing end of a shared memory ring buffer that broadcasts messages to multiple readers
+/// (up to 64 at a time) in the same or other processes on the same host.
+///
+/// Messages are copied into shared memory and readers copy them out, with no syscalls on the
+/// publishing path unless a reader has caught up and is waiting for the next message. This makes
+/// the ring suitable for very high-rate telemetry where pipes would be too slow.
+///
+/// The writer never waits for readers. A reader that falls behind by more than the capacity of the
+/// ring misses the oldest messages, which it can detect via `SharedRingReader::missed()`.
+#[derive(Debug)]
+pub struct SharedRing {
+    ring: RingMemory,
+
+    // Indexed by reader slot. Signaled after publishing if the reader in that slot is waiting.
+    reader_events: Vec<OwnedHandle<HANDLE>>,
+
+    // The ring memory points into the view, so this must be dropped after it.
+    _view: MappedView,
+    _mapping: OwnedHandle<HANDLE>,
+}
+
+impl SharedRing {
+    /// The maximum length of a message in bytes.
+    pub fn max_message_len(&self) -> usize {
+        self.ring.max_message_len() as usize
+    }
+
+    /// Publishes a message to all attached readers, overwriting the oldest message in the ring.
+    pub fn publish(&self, message: &[u8]) -> io::Result<()> {
+        if message.len() > self.max_message_len() {
+            return Err(io::Error::InvalidOptions(format!(
+                "message of {} bytes exceeds the maximum message length of the shared ring ({})",
+                message.len(),
+                self.max_message_len()
+            )));
+        }
+
+        // SAFETY: We are the only writer (the type is !Sync and the name is exclusively ours) and
+        // we checked the length of the message above.
+        unsafe { self.ring.publish(message) };
+
+        self.ring.take_waiting(|index| {
+            // This can only fail if the handle is invalid, which it is not because we own it.
+            //
+            // SAFETY: Nothing unsafe here, just an FFI call with a valid handle.
+            _ = unsafe { SetEvent(*self.reader_events[index]) };
+        });
+
+        Ok(())
+    }
+}
+
+// SAFETY: The ring memory is shared memory that is valid to access from any thread and all the
+// handles are valid to use from any thread.
+unsafe impl Send for SharedRing {}
+
+// Publishing from multiple threads at the same time would violate the single writer requirement.
+#[negative_impl]
+impl !Sync for SharedRing {}
+
+/// The reading end of a `SharedRing`, which may be in the same or another process.
+///
+/// A reader only receives messages published after it was opened, in the order they were
+/// published, without ever blocking the writer.
+#[derive(Debug)]
+pub struct SharedRingReader {
+    ring: RingMemory,
+    index: usize,
+    event: OwnedHandle<HANDLE>,
+
+    next_sequence: u64,
+    missed: u64,
+
+    // The ring memory points into the view, so this must be dropped after it.
+    _view: MappedView,
+    _mapping: OwnedHandle<HANDLE>,
+}
+
+impl SharedRingReader {
+    /// Attaches to the shared ring with the specified name. Fails if there is no such ring or if
+    /// the maximum number of readers (64) is already attached to it.
+    pub fn open(name: &str) -> io::Result<Self> {
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let mapping = unsafe {
+            OwnedHandle::new(OpenFileMappingW(
+                (FILE_MAP_READ | FILE_MAP_WRITE).0,
+                false,
+                &HSTRING::from(name),
+            )?)
+        };
+
+        let view = MappedView::new(*mapping)?;
+
+        // SAFETY: The view is valid for `size()` bytes and page-aligned. The view outlives the
+        // ring memory because both are owned by the reader.
+        let ring = unsafe { RingMemory::attach(view.base(), view.size())? };
+
+        let index = ring.claim_reader().ok_or_else(|| {
+            io::Error::InvalidOptions(format!(
+                "shared ring {name} already has the maximum of {MAX_READERS} readers attached"
+            ))
+        })?;
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let event = match unsafe {
+            OpenEventW(
+                SYNCHRONIZATION_SYNCHRONIZE,
+                false,
+                &HSTRING::from(reader_event_name(name, index)),
+            )
+        } {
+            Ok(event) => unsafe { OwnedHandle::new(event) },
+            Err(e) => {
+                ring.release_reader(index);
+                return Err(e.into());
+            }
+        };
+
+        Ok(Self {
+            next_sequence: ring.write_sequence(),
+            ring,
+            index,
+            event,
+            missed: 0,
+            _view: view,
+            _mapping: mapping,
+        })
+    }
+
+    /// Receives the next message, waiting for one to be published if the reader has caught up.
+    ///
+    /// Waiting does not occupy any thread of the runtime - the writer signals an event that wakes
+    /// up the waiting task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn receive(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(message) = self.try_receive() {
+                return Ok(message);
+            }
+
+            self.ring.set_waiting(self.index);
+
+            // A message may have been published between our check above and marking ourselves as
+            // waiting, in which case the writer did not signal us.
+            if let Some(message) = self.try_receive() {
+                return Ok(message);
+            }
+
+            // The event may also be left signaled from an earlier wait that ended with the check
+            // above, which only costs us an extra iteration.
+            wait_for_handle(*self.event)?.await;
+        }
+    }
+
+    /// Receives the next message if one has been published, without waiting.
+    pub fn try_receive(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let write_sequence = self.ring.write_sequence();
+
+            if self.next_sequence >= write_sequence {
+                return None;
+            }
+
+            let sequence = self
+                .ring
+                .oldest_readable(self.next_sequence, write_sequence);
+            self.missed += sequence - self.next_sequence;
+            self.next_sequence = sequence + 1;
+
+            // If the writer overwrote the message while we were reading it, we count it as missed
+            // and move on to the next one.
+            match self.ring.read(sequence) {
+                Some(message) => return Some(message),
+                None => self.missed += 1,
+            }
+        }
+    }
+
+    /// The number of messages this reader has missed because it fell too far behind the writer.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+impl Drop for SharedRingReader {
+    fn drop(&mut self) {
+        self.ring.release_reader(self.index);
+    }
+}
+
+#[negative_impl]
+impl !Send for SharedRingReader {}
+#[negative_impl]
+impl !Sync for SharedRingReader {}
+
+/// A view of an entire file mapping, unmapped on drop.
+#[derive(Debug)]
+struct MappedView {
+    address: MEMORY_MAPPED_VIEW_ADDRESS,
+    size: usize,
+}
+
+impl MappedView {
+    fn new(mapping: HANDLE) -> io::Result<Self> {
+        // SAFETY: Nothing unsafe here, just an FFI call with a valid handle. A size of 0 maps the
+        // entire mapping.
+        let address = unsafe { MapViewOfFile(mapping, FILE_MAP_READ | FILE_MAP_WRITE, 0, 0, 0) };
+
+        if address.Value.is_null() {
+            return Err(windows::core::Error::from_win32().into());
+        }
+
+        // From here on, the view is unmapped on drop if anything fails.
+        let mut view = Self { address, size: 0 };
+
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+
+        // SAFETY: We pass a valid pointer to a structure of the expected type.
+        let result = unsafe {
+            VirtualQuery(
+                Some(address.Value),
+                &mut info,
+                mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+
+        if result == 0 {
+            return Err(windows::core::Error::from_win32().into());
+        }
+
+        view.size = info.RegionSize;
+        Ok(view)
+    }
+
+    fn base(&self) -> *mut u8 {
+        self.address.Value as *mut u8
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for MappedView {
+    fn drop(&mut self) {
+        // SAFETY: The view is valid because we mapped it and only unmap it here.
+        _ = unsafe { UnmapViewOfFile(self.address) };
+    }
+}
+
+// SAFETY: A view of a file mapping may be accessed and unmapped from any thread.
+unsafe impl Send for MappedView {}
+
+fn create_reader_event(ring_name: &str, index: usize) -> io::Result<OwnedHandle<HANDLE>> {
+    // Auto-reset, so each signal wakes up the reader once.
+    //
+    // SAFETY: We are required to close the handle once we are done with it,
+    // which we do via OwnedHandle that closes the handle on drop.
+    let event = unsafe {
+        OwnedHandle::new(CreateEventW(
+            None,
+            false,
+            false,
+            &HSTRING::from(reader_event_name(ring_name, index)),
+        )?)
+    };
+
+    // SAFETY: Nothing unsafe here, just an FFI call.
+    if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+        return Err(windows::core::Error::from(ERROR_ALREADY_EXISTS.to_hresult()).into());
+    }
+
+    Ok(event)
+}
+
+// The mapping and the events share the kernel object namespace, so they need distinct names.
+fn reader_event_name(ring_name: &str, index: usize) -> String {
+    format!("{ring_name}.reader{index}")
+}
@@ -0,0 +1,375 @@
+use crate::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    ipc::{PipeInstance, PipeStream},
+    metrics::{Event, EventBuilder},
+    rt::{clock, sleep},
+};
+use negative_impl::negative_impl;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{marker::PhantomData, time::Duration};
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_PIPE_BUSY};
+
+/// Every message is preceded by its length as a little-endian u32.
+const LEN_PREFIX: usize = 4;
+
+const DEFAULT_MAX_MESSAGE_LEN: u32 = 16 * 1024 * 1024;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Builds either end of a `TypedChannel`: the server end via `listen()`, which accepts any number
+/// of clients, or the client end via `connect()`.
+#[derive(Clone, Debug)]
+pub struct TypedChannelBuilder {
+    name: String,
+    max_message_len: u32,
+    connect_timeout: Duration,
+}
+
+impl TypedChannelBuilder {
+    /// The name of the pipe must be of the form `\\.\pipe\<name>`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+
+    /// The maximum length of a serialized message in bytes, in either direction. Sending a longer
+    /// message fails and receiving one fails and disconnects the channel, as a defense against a
+    /// misbehaving peer making us allocate unbounded amounts of memory. Defaults to 16 MB.
+    pub fn max_message_len(mut self, value: u32) -> Self {
+        self.max_message_len = value;
+        self
+    }
+
+    /// How long a client keeps trying to connect (or reconnect) while the server is not available,
+    /// before giving up with `io::Error::TimedOut`. Defaults to 10 seconds.
+    pub fn connect_timeout(mut self, value: Duration) -> Self {
+        self.connect_timeout = value;
+        self
+    }
+
+    /// Connects to a server listening on the pipe, waiting for it to become available if it is
+    /// not yet listening or all its pipe instances are busy.
+    ///
+    /// If the connection is lost later (e.g. because the server restarted), the channel
+    /// reconnects on the next `send()` or `receive()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn connect<T>(self) -> io::Result<TypedChannel<T>> {
+        let pipe = connect_with_retry(&self.name, self.connect_timeout).await?;
+        let max_message_len = self.max_message_len;
+
+        Ok(TypedChannel::new(pipe, Some(self), max_message_len))
+    }
+
+    /// Starts listening for clients on the pipe. Fails if another server is already listening on
+    /// a pipe with the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn listen<T>(self) -> io::Result<TypedChannelListener<T>> {
+        let instance = PipeInstance::create(&self.name, true).await?;
+
+        Ok(TypedChannelListener {
+            name: self.name,
+            max_message_len: self.max_message_len,
+            next_instance: Some(instance),
+            _message: PhantomData,
+        })
+    }
+}
+
+/// Accepts clients connecting to a named pipe, returning a `TypedChannel` for each of them.
+#[derive(Debug)]
+pub struct TypedChannelListener<T> {
+    name: String,
+    max_message_len: u32,
+
+    // The pipe instance that the next client will connect to. We always keep one ready, so clients
+    // do not find the pipe missing while we are busy with a previous client.
+    next_instance: Option<PipeInstance>,
+
+    _message: PhantomData<fn(T) -> T>,
+}
+
+impl<T> TypedChannelListener<T> {
+    /// Waits for the next client to connect.
+    ///
+    /// The returned channel does not reconnect - once the client disconnects, it stays
+    /// disconnected and the client is expected to come back via the listener.
+    pub async fn accept(&mut self) -> io::Result<TypedChannel<T>> {
+        let instance = match self.next_instance.take() {
+            Some(instance) => instance,
+            None => PipeInstance::create(&self.name, false).await?,
+        };
+
+        let pipe = instance.wait_for_client().await?;
+
+        // If this fails, we try again on the next accept. The client that just connected is not
+        // affected, so we do not fail the accept.
+        self.next_instance = PipeInstance::create(&self.name, false).await.ok();
+
+        Ok(TypedChannel::new(pipe, None, self.max_message_len))
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for TypedChannelListener<T> {}
+#[negative_impl]
+impl<T> !Sync for TypedChannelListener<T> {}
+
+/// A bidirectional channel for messages of type `T` over a named pipe, for talking to processes
+/// on the same host (e.g. between a control plane and local agents).
+///
+/// Each message is serialized as JSON and sent as a frame prefixed with its length, so the
+/// receiver always gets whole messages regardless of how the bytes were split up in transit. Use
+/// an enum as `T` to exchange different kinds of messages.
+///
+/// A channel established via `TypedChannelBuilder::connect()` reconnects automatically if the
+/// connection is lost:
+///
+/// * `receive()` reports the loss as the end of the stream (`None`) - the next call reconnects.
+/// * `send()` reconnects and sends the message again if the pipe was found to be closed by the
+///   server. Any partial message the server may have received from the first attempt is
+///   discarded by the server along with the closed pipe.
+///
+/// Messages that were in flight when the connection was lost are not recovered - if that matters,
+/// the protocol on top of the channel must take care of it.
+#[derive(Debug)]
+pub struct TypedChannel<T> {
+    // None if the connection has been lost.
+    pipe: Option<PipeStream>,
+
+    // Set for client channels, which reconnect once the connection has been lost.
+    reconnect: Option<TypedChannelBuilder>,
+
+    max_message_len: u32,
+
+    // Bytes received but not yet returned as messages. Always starts at a frame boundary.
+    received: Vec<u8>,
+
+    _message: PhantomData<fn(T) -> T>,
+}
+
+impl<T> TypedChannel<T> {
+    fn new(pipe: PipeStream, reconnect: Option<TypedChannelBuilder>, max_message_len: u32) -> Self {
+        Self {
+            pipe: Some(pipe),
+            reconnect,
+            max_message_len,
+            received: Vec::new(),
+            _message: PhantomData,
+        }
+    }
+
+    /// Whether the channel is currently connected. A client channel that is not connected
+    /// reconnects on the next `send()` or `receive()`.
+    pub fn is_connected(&self) -> bool {
+        self.pipe.is_some()
+    }
+
+    /// Returns the pipe, reconnecting first if the connection has been lost and this is a client
+    /// channel. Returns `None` if the connection has been lost for good.
+    async fn pipe(&mut self) -> io::Result<Option<&PipeStream>> {
+        if self.pipe.is_none() {
+            let Some(reconnect) = &self.reconnect else {
+                return Ok(None);
+            };
+
+            self.pipe = Some(connect_with_retry(&reconnect.name, reconnect.connect_timeout).await?);
+
+            RECONNECTS.with(Event::observe_unit);
+        }
+
+        Ok(self.pipe.as_ref())
+    }
+
+    fn disconnect(&mut self) {
+        self.pipe = None;
+
+        // Whatever we had received from the lost connection is of no use on a new one.
+        self.received.clear();
+    }
+}
+
+impl<T> TypedChannel<T>
+where
+    T: Serialize,
+{
+    /// Sends a message to the peer.
+    ///
+    /// On a server channel, fails with `io::Error::ConnectionReset` if the client has disconnected.
+    pub async fn send(&mut self, message: &T) -> io::Result<()> {
+        let frame = self.encode(message)?;
+
+        match self.send_frame(&frame).await {
+            Err(io::Error::ConnectionReset) if self.reconnect.is_some() => {
+                // The server closed the pipe since we last used it (e.g. because it restarted),
+                // so we send the message again on a new connection.
+                self.send_frame(&frame).await
+            }
+            result => result,
+        }
+    }
+
+    fn encode(&self, message: &T) -> io::Result<Box<[u8]>> {
+        let mut frame = vec![0; LEN_PREFIX];
+        serde_json::to_writer(&mut frame, message).map_err(std::io::Error::from)?;
+
+        let len = frame.len() - LEN_PREFIX;
+
+        if len > self.max_message_len as usize {
+            return Err(io::Error::InvalidOptions(format!(
+                "message of {len} bytes exceeds the maximum message length of the channel ({})",
+                self.max_message_len
+            )));
+        }
+
+        frame[..LEN_PREFIX].copy_from_slice(&(len as u32).to_le_bytes());
+
+        Ok(frame.into_boxed_slice())
+    }
+
+    async fn send_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let Some(pipe) = self.pipe().await? else {
+            return Err(io::Error::ConnectionReset);
+        };
+
+        let buffer = PinnedBuffer::from_boxed_slice(frame.into());
+
+        match pipe.write(buffer).await.into_inner() {
+            Ok(buffer) if buffer.len() == frame.len() => Ok(()),
+            Ok(buffer) => Err(io::Error::Internal(format!(
+                "pipe write of {} bytes wrote only {} bytes",
+                frame.len(),
+                buffer.len()
+            ))),
+            Err(io::Error::ConnectionReset) => {
+                self.disconnect();
+                Err(io::Error::ConnectionReset)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<T> TypedChannel<T>
+where
+    T: DeserializeOwned,
+{
+    /// Receives the next message from the peer. Returns `None` if the peer has closed the pipe.
+    ///
+    /// On a client channel, the next call after `None` reconnects to the server.
+    pub async fn receive(&mut self) -> io::Result<Option<T>> {
+        loop {
+            if let Some(message) = self.decode()? {
+                return Ok(Some(message));
+            }
+
+            let Some(pipe) = self.pipe().await? else {
+                return Ok(None);
+            };
+
+            let buffer = pipe.read(PinnedBuffer::from_pool()).await.into_inner()?;
+
+            if buffer.len() == 0 {
+                let truncated = !self.received.is_empty();
+                self.disconnect();
+
+                if truncated {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "pipe was closed in the middle of a message",
+                    )
+                    .into());
+                }
+
+                return Ok(None);
+            }
+
+            self.received.extend_from_slice(buffer.as_slice());
+        }
+    }
+
+    /// Takes the next message out of the received bytes, if a whole one has been received.
+    fn decode(&mut self) -> io::Result<Option<T>> {
+        let Some(prefix) = self.received.get(..LEN_PREFIX) else {
+            return Ok(None);
+        };
+
+        let len = u32::from_le_bytes(prefix.try_into().expect("prefix has the correct length"));
+
+        if len > self.max_message_len {
+            // We cannot find the next frame boundary without reading the whole message, which
+            // is what we are trying to avoid, so the connection is unusable from here on.
+            self.disconnect();
+
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "received message of {len} bytes exceeds the maximum message length of the channel ({})",
+                    self.max_message_len
+                ),
+            )
+            .into());
+        }
+
+        let frame_len = LEN_PREFIX + len as usize;
+
+        let Some(payload) = self.received.get(LEN_PREFIX..frame_len) else {
+            return Ok(None);
+        };
+
+        let message = serde_json::from_slice(payload);
+
+        // The frame is consumed even if it fails to deserialize, so the next message can still be
+        // received.
+        self.received.drain(..frame_len);
+
+        Ok(Some(message.map_err(std::io::Error::from)?))
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for TypedChannel<T> {}
+#[negative_impl]
+impl<T> !Sync for TypedChannel<T> {}
+
+/// Connects to the pipe, retrying with increasing delays while the pipe does not exist (the server
+/// is not running) or all its instances are busy (the server has not yet created a new instance
+/// after accepting the previous client).
+async fn connect_with_retry(name: &str, timeout: Duration) -> io::Result<PipeStream> {
+    let deadline = clock::now() + timeout;
+    let mut delay = INITIAL_RETRY_DELAY;
+
+    loop {
+        match PipeStream::connect(name).await {
+            Err(io::Error::Windows(e))
+                if e.code() == ERROR_FILE_NOT_FOUND.to_hresult()
+                    || e.code() == ERROR_PIPE_BUSY.to_hresult() =>
+            {
+                if clock::now() + delay > deadline {
+                    return Err(io::Error::TimedOut);
+                }
+
+                sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+thread_local! {
+    static RECONNECTS: Event = EventBuilder::new()
+        .name("ipc_typed_channel_reconnects")
+        .build()
+        .unwrap();
+}
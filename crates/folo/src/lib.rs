@@ -5,13 +5,20 @@ pub mod __private;
 mod constants;
 #[cfg(feature = "criterion")]
 pub mod criterion;
+#[cfg(feature = "event-log")]
+pub mod event_log;
 pub mod fs;
 pub mod io;
+pub mod ipc;
+pub mod log;
+pub mod mem;
 pub mod net;
 pub mod metrics;
 pub mod rt;
+pub mod stream;
 pub mod sync;
 pub mod util;
+pub mod winreg;
 
 /// Marks a `main()` function as the async entry point of an app based on the Folo runtime.
 ///
@@ -0,0 +1,563 @@
+//! Non-blocking log output for async workers.
+//!
+//! Writing log records straight to a file or pipe would make every log statement wait for I/O,
+//! stalling the worker under load. `AsyncWriter` instead queues formatted records in memory and
+//! a background task on the same worker writes them out in batches. Logging never waits - if the
+//! output cannot keep up and the queue is full, records are dropped according to the configured
+//! `OverflowPolicy` and counted.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use folo::log::{AsyncWriterBuilder, OverflowPolicy};
+//!
+//! let log = AsyncWriterBuilder::new()
+//!     .max_queued_bytes(4 * 1024 * 1024)
+//!     .overflow_policy(OverflowPolicy::DropOldest)
+//!     .file("service.log")
+//!     .await?;
+//!
+//! log.write_line(format_args!("listening on port {port}"));
+//!
+//! // Before shutting down, make sure everything has been written.
+//! log.flush().await?;
+//! ```
+
+use crate::{
+    fs::Writer,
+    io::{self, PinnedBuffer},
+    ipc::PipeStream,
+    metrics::{Event, EventBuilder},
+    rt::spawn,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt::{self, Write as _},
+    future::poll_fn,
+    mem,
+    num::NonZeroUsize,
+    path::Path,
+    rc::Rc,
+    task::{Poll, Waker},
+};
+
+/// How many bytes of records may be queued by default before records start getting dropped.
+pub const DEFAULT_MAX_QUEUED_BYTES: usize = 1024 * 1024;
+
+/// How many chunk writes the file destination keeps in progress at the same time.
+const FILE_MAX_IN_FLIGHT: NonZeroUsize = NonZeroUsize::new(4).unwrap();
+
+/// What to do with a record that does not fit into the queue of an `AsyncWriter`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Drop the new record, keeping the records already queued. Preserves the records leading up
+    /// to the overload, which often explain its cause.
+    DropNewest,
+
+    /// Drop the oldest queued records until the new record fits. Preserves the most recent
+    /// records, which describe the current state.
+    DropOldest,
+}
+
+/// Builds an `AsyncWriter` with custom options.
+#[derive(Debug)]
+pub struct AsyncWriterBuilder {
+    max_queued_bytes: usize,
+    overflow_policy: OverflowPolicy,
+}
+
+impl AsyncWriterBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_queued_bytes: DEFAULT_MAX_QUEUED_BYTES,
+            overflow_policy: OverflowPolicy::DropNewest,
+        }
+    }
+
+    /// The maximum number of bytes of records waiting to be written. Records that do not fit are
+    /// dropped according to the overflow policy. Defaults to `DEFAULT_MAX_QUEUED_BYTES`.
+    pub fn max_queued_bytes(mut self, value: usize) -> Self {
+        self.max_queued_bytes = value;
+        self
+    }
+
+    /// What to do with records that do not fit into the queue. Defaults to
+    /// `OverflowPolicy::DropNewest`.
+    pub fn overflow_policy(mut self, value: OverflowPolicy) -> Self {
+        self.overflow_policy = value;
+        self
+    }
+
+    /// Creates a writer that writes records to a new file (or truncates an existing one).
+    ///
+    /// Written records are flushed to the storage device whenever the queue runs empty, so a
+    /// crash loses at most the records of the last batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub async fn file(self, path: impl AsRef<Path>) -> io::Result<AsyncWriter> {
+        let writer = Writer::create(path, FILE_MAX_IN_FLIGHT).await?;
+
+        Ok(self.start(Destination::File(writer)))
+    }
+
+    /// Creates a writer that writes records to a named pipe (e.g. `\\.\pipe\contoso-logs`)
+    /// served by another process, such as a log collector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub async fn pipe(self, name: &str) -> io::Result<AsyncWriter> {
+        let pipe = PipeStream::connect(name).await?;
+
+        Ok(self.start(Destination::Pipe(pipe)))
+    }
+
+    fn start(self, destination: Destination) -> AsyncWriter {
+        let shared = Rc::new(Shared {
+            queue: RefCell::new(RecordQueue::new(
+                self.max_queued_bytes,
+                self.overflow_policy,
+            )),
+            dropped_records: Cell::new(0),
+            queued_sequence: Cell::new(0),
+            written_sequence: Cell::new(0),
+            error: RefCell::new(None),
+            failed: Cell::new(false),
+            closed: Cell::new(false),
+            flusher_waker: Cell::new(None),
+            flush_waiters: RefCell::new(Vec::new()),
+        });
+
+        _ = spawn(run_flusher(Rc::clone(&shared), destination));
+
+        AsyncWriter {
+            handle: Rc::new(WriterHandle { shared }),
+        }
+    }
+}
+
+impl Default for AsyncWriterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Queues log records for writing by a background task on the current async worker, so logging
+/// never waits for I/O. Create one via `AsyncWriterBuilder`.
+///
+/// Clones share the same queue and destination. Once the last clone is dropped, the background
+/// task writes out the records still queued and then closes the destination.
+///
+/// If writing to the destination fails, all further records are dropped and the error is reported
+/// by `flush()`.
+#[derive(Clone)]
+pub struct AsyncWriter {
+    handle: Rc<WriterHandle>,
+}
+
+impl AsyncWriter {
+    /// Queues a record to be written as-is. Returns `false` if the record was dropped instead,
+    /// because the queue was full (with `OverflowPolicy::DropNewest`), the record is larger than
+    /// the whole queue or the writer has failed.
+    pub fn write_record(&self, record: &[u8]) -> bool {
+        self.handle.shared.enqueue(record.to_vec())
+    }
+
+    /// Formats a record and queues it, terminated by a line break. Returns `false` if the record
+    /// was dropped instead (see `write_record()`).
+    pub fn write_line(&self, args: fmt::Arguments<'_>) -> bool {
+        let mut line = String::new();
+
+        // Formatting into a String only fails if a Display implementation fails.
+        if line.write_fmt(args).is_err() {
+            return false;
+        }
+
+        line.push('\n');
+
+        self.handle.shared.enqueue(line.into_bytes())
+    }
+
+    /// The number of records dropped so far, including those dropped from the queue to make room
+    /// for newer records.
+    pub fn dropped_records(&self) -> u64 {
+        self.handle.shared.dropped_records.get()
+    }
+
+    /// Waits until all records queued before the call have been written to the destination (and,
+    /// for files, flushed to the storage device).
+    pub async fn flush(&self) -> io::Result<()> {
+        let shared = &self.handle.shared;
+        let target = shared.queued_sequence.get();
+
+        poll_fn(|cx| {
+            if shared.failed.get() || shared.written_sequence.get() >= target {
+                return Poll::Ready(());
+            }
+
+            shared.flush_waiters.borrow_mut().push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await;
+
+        match shared.error.borrow_mut().take() {
+            Some(e) => Err(e),
+            None if shared.failed.get() => Err(io::Error::Internal(
+                "log writer has failed and its error was already reported".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for AsyncWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncWriter")
+            .field("dropped_records", &self.dropped_records())
+            .finish_non_exhaustive()
+    }
+}
+
+#[negative_impl]
+impl !Send for AsyncWriter {}
+#[negative_impl]
+impl !Sync for AsyncWriter {}
+
+/// Shared by all clones of an `AsyncWriter`, telling the background task when the last one is
+/// dropped.
+struct WriterHandle {
+    shared: Rc<Shared>,
+}
+
+impl Drop for WriterHandle {
+    fn drop(&mut self) {
+        self.shared.closed.set(true);
+        self.shared.wake_flusher();
+    }
+}
+
+struct Shared {
+    queue: RefCell<RecordQueue>,
+    dropped_records: Cell<u64>,
+
+    // Every record that is queued (and not dropped right away) gets the next sequence number.
+    // Once a batch has been written, `written_sequence` catches up with the last record in it.
+    queued_sequence: Cell<u64>,
+    written_sequence: Cell<u64>,
+
+    // The error that made the writer fail, until it is reported by `flush()`.
+    error: RefCell<Option<io::Error>>,
+    failed: Cell<bool>,
+
+    // Set once all the `AsyncWriter` clones are dropped.
+    closed: Cell<bool>,
+
+    flusher_waker: Cell<Option<Waker>>,
+    flush_waiters: RefCell<Vec<Waker>>,
+}
+
+impl Shared {
+    fn enqueue(&self, record: Vec<u8>) -> bool {
+        if self.failed.get() {
+            self.record_dropped(1);
+            return false;
+        }
+
+        let outcome = self.queue.borrow_mut().push(record);
+        self.record_dropped(outcome.dropped);
+
+        if outcome.accepted {
+            self.queued_sequence.set(self.queued_sequence.get() + 1);
+            self.wake_flusher();
+        }
+
+        outcome.accepted
+    }
+
+    fn record_dropped(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        self.dropped_records
+            .set(self.dropped_records.get() + count as u64);
+        DROPPED_RECORDS.with(|x| x.observe(count as i64));
+    }
+
+    fn wake_flusher(&self) {
+        if let Some(waker) = self.flusher_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_flush_waiters(&self) {
+        let waiters = mem::take(&mut *self.flush_waiters.borrow_mut());
+
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+enum Destination {
+    File(Writer),
+    Pipe(PipeStream),
+}
+
+impl Destination {
+    async fn write(&mut self, batch: Vec<u8>) -> io::Result<()> {
+        match self {
+            Destination::File(writer) => writer.write(&batch).await,
+            Destination::Pipe(pipe) => {
+                let requested_len = batch.len();
+
+                let buffer = pipe
+                    .write(PinnedBuffer::from_boxed_slice(batch.into_boxed_slice()))
+                    .await
+                    .map_err(|e| e.into_inner())?;
+
+                if buffer.len() != requested_len {
+                    return Err(io::Error::Internal(format!(
+                        "pipe write wrote {} of {requested_len} bytes",
+                        buffer.len()
+                    )));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Called whenever the queue has run empty, so written records do not linger in buffers.
+    async fn sync(&mut self) -> io::Result<()> {
+        match self {
+            Destination::File(writer) => writer.barrier().await,
+            Destination::Pipe(_) => Ok(()),
+        }
+    }
+}
+
+async fn run_flusher(shared: Rc<Shared>, mut destination: Destination) {
+    // The sequence number of the last record we have taken from the queue, either to write it or
+    // because it was dropped from the queue to make room for newer records.
+    let mut taken_sequence = 0;
+
+    loop {
+        poll_fn(|cx| {
+            if !shared.queue.borrow().is_empty() || shared.closed.get() {
+                return Poll::Ready(());
+            }
+
+            shared.flusher_waker.set(Some(cx.waker().clone()));
+            Poll::Pending
+        })
+        .await;
+
+        let (batch, record_count, dropped_count) = {
+            let mut queue = shared.queue.borrow_mut();
+            let (batch, record_count) = queue.take_all();
+            (batch, record_count, queue.take_dropped_sequences())
+        };
+
+        if record_count == 0 {
+            // The queue is empty, so we were woken up because all the writers are gone.
+            return;
+        }
+
+        taken_sequence += (record_count + dropped_count) as u64;
+
+        let mut result = destination.write(batch).await;
+
+        // Written records may still be sitting in buffers, so we sync them before telling anyone
+        // that they have been written. Under load, we only sync once the queue runs empty, unless
+        // someone is waiting for the records to be written.
+        let should_sync =
+            shared.queue.borrow().is_empty() || !shared.flush_waiters.borrow().is_empty();
+
+        if result.is_ok() && should_sync {
+            result = destination.sync().await;
+
+            if result.is_ok() {
+                shared.written_sequence.set(taken_sequence);
+                shared.wake_flush_waiters();
+            }
+        }
+
+        if let Err(e) = result {
+            // Records still queued are never going to be written.
+            let (_, abandoned) = shared.queue.borrow_mut().take_all();
+            shared.record_dropped(abandoned);
+
+            *shared.error.borrow_mut() = Some(e);
+            shared.failed.set(true);
+            shared.wake_flush_waiters();
+            return;
+        }
+    }
+}
+
+/// The records waiting to be written, with the overflow policy applied as records are added.
+#[derive(Debug)]
+struct RecordQueue {
+    records: VecDeque<Vec<u8>>,
+    queued_bytes: usize,
+    max_queued_bytes: usize,
+    overflow_policy: OverflowPolicy,
+
+    // How many accepted records have been dropped from the queue to make room for newer ones
+    // since the last `take_dropped_sequences()`.
+    dropped_sequences: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct PushOutcome {
+    accepted: bool,
+    dropped: usize,
+}
+
+impl RecordQueue {
+    fn new(max_queued_bytes: usize, overflow_policy: OverflowPolicy) -> Self {
+        Self {
+            records: VecDeque::new(),
+            queued_bytes: 0,
+            max_queued_bytes,
+            overflow_policy,
+            dropped_sequences: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn push(&mut self, record: Vec<u8>) -> PushOutcome {
+        if record.len() > self.max_queued_bytes {
+            return PushOutcome {
+                accepted: false,
+                dropped: 1,
+            };
+        }
+
+        let mut dropped = 0;
+
+        while self.queued_bytes + record.len() > self.max_queued_bytes {
+            match self.overflow_policy {
+                OverflowPolicy::DropNewest => {
+                    return PushOutcome {
+                        accepted: false,
+                        dropped: 1,
+                    };
+                }
+                OverflowPolicy::DropOldest => {
+                    let oldest = self
+                        .records
+                        .pop_front()
+                        .expect("the record fits into an empty queue, so the queue is not empty");
+
+                    self.queued_bytes -= oldest.len();
+                    self.dropped_sequences += 1;
+                    dropped += 1;
+                }
+            }
+        }
+
+        self.queued_bytes += record.len();
+        self.records.push_back(record);
+
+        PushOutcome {
+            accepted: true,
+            dropped,
+        }
+    }
+
+    /// Removes all the queued records, returning them concatenated, together with their count.
+    fn take_all(&mut self) -> (Vec<u8>, usize) {
+        let mut batch = Vec::with_capacity(self.queued_bytes);
+        let count = self.records.len();
+
+        for record in self.records.drain(..) {
+            batch.extend_from_slice(&record);
+        }
+
+        self.queued_bytes = 0;
+
+        (batch, count)
+    }
+
+    fn take_dropped_sequences(&mut self) -> usize {
+        mem::take(&mut self.dropped_sequences)
+    }
+}
+
+thread_local! {
+    static DROPPED_RECORDS: Event = EventBuilder::new()
+        .name("log_async_writer_dropped_records")
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_newest_keeps_queued_records() {
+        let mut queue = RecordQueue::new(10, OverflowPolicy::DropNewest);
+
+        assert!(queue.push(b"12345".to_vec()).accepted);
+        assert!(queue.push(b"6789".to_vec()).accepted);
+        assert_eq!(
+            queue.push(b"ab".to_vec()),
+            PushOutcome {
+                accepted: false,
+                dropped: 1
+            }
+        );
+
+        assert_eq!(queue.take_all(), (b"123456789".to_vec(), 2));
+        assert!(queue.is_empty());
+        assert_eq!(queue.take_dropped_sequences(), 0);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room() {
+        let mut queue = RecordQueue::new(10, OverflowPolicy::DropOldest);
+
+        queue.push(b"1234".to_vec());
+        queue.push(b"5678".to_vec());
+
+        assert_eq!(
+            queue.push(b"abcdef".to_vec()),
+            PushOutcome {
+                accepted: true,
+                dropped: 1
+            }
+        );
+
+        assert_eq!(queue.take_all(), (b"5678abcdef".to_vec(), 2));
+        assert_eq!(queue.take_dropped_sequences(), 1);
+        assert_eq!(queue.take_dropped_sequences(), 0);
+    }
+
+    #[test]
+    fn oversized_record_is_dropped() {
+        let mut queue = RecordQueue::new(4, OverflowPolicy::DropOldest);
+
+        queue.push(b"12".to_vec());
+
+        assert_eq!(
+            queue.push(b"12345".to_vec()),
+            PushOutcome {
+                accepted: false,
+                dropped: 1
+            }
+        );
+
+        // The queued record was not sacrificed for a record that could never fit.
+        assert_eq!(queue.take_all(), (b"12".to_vec(), 1));
+    }
+}
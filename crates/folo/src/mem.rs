@@ -0,0 +1,5 @@
+mod budget;
+mod instrumented_allocator;
+
+pub use budget::*;
+pub use instrumented_allocator::*;
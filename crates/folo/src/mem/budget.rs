@@ -0,0 +1,566 @@
+use crate::rt::{spawn, spawn_on_any, LocalJoinHandle, RemoteJoinHandle};
+use pin_project::{pin_project, pinned_drop};
+use std::{
+    cell::Cell,
+    fmt,
+    future::Future,
+    pin::Pin,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+    task,
+};
+use thiserror::Error;
+
+/// A memory budget shared by a group of tasks (e.g. all the tasks of one tenant), limiting how
+/// much heap memory they may hold at the same time.
+///
+/// Tasks are placed under the budget by spawning them via `MemoryBudget::spawn()` or
+/// `MemoryBudget::spawn_on_any()`. While such a task is being created, polled or dropped, the
+/// memory it allocates is charged to the budget and the memory it releases is credited back. Once
+/// the budget is exceeded, further spawns under it fail with `BudgetExceeded` and the callback set
+/// via `on_exceeded()` is called. Already running tasks are not interrupted - it is up to the
+/// callback to decide what to do about them.
+///
+/// Accounting requires `InstrumentedAllocator` to be installed as the global allocator. Without
+/// it, the budget always reports zero usage and is never exceeded.
+///
+/// # Ownership
+///
+/// Memory is charged to the budget that is active on the thread at the time of allocation and is
+/// credited back to that same budget when released, no matter where or on which thread that
+/// happens. If a task hands memory over to code outside the budget (e.g. returns it as the task
+/// result or sends it to a task that is not under the budget), the memory keeps counting against
+/// the budget until released. Memory handed to a budgeted task from outside never counts against
+/// the budget. Each allocation charged to a budget keeps the budget alive until it is released.
+///
+/// This type is thread-safe and cheap to clone - clones refer to the same budget.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+type ExceededCallback = Box<dyn Fn(&MemoryBudget) + Send + Sync>;
+
+pub(super) struct BudgetInner {
+    limit_bytes: usize,
+
+    used_bytes: AtomicI64,
+
+    on_exceeded: Option<ExceededCallback>,
+
+    // Set once the callback has been called, cleared when usage drops back under the limit, so
+    // the callback is called once each time the budget is exceeded.
+    exceeded_reported: AtomicBool,
+}
+
+impl MemoryBudget {
+    /// Creates a budget that allows the tasks under it to hold up to `limit_bytes` of heap memory.
+    pub fn new(limit_bytes: usize) -> Self {
+        Self::with_callback(limit_bytes, None)
+    }
+
+    /// Creates a budget that calls `callback` whenever the tasks under it are found to have
+    /// exceeded it (checked after each poll of a task and on each spawn). The callback is called
+    /// once each time the budget is exceeded, on whichever thread observed it, and must not block.
+    pub fn with_on_exceeded<F>(limit_bytes: usize, callback: F) -> Self
+    where
+        F: Fn(&MemoryBudget) + Send + Sync + 'static,
+    {
+        Self::with_callback(limit_bytes, Some(Box::new(callback)))
+    }
+
+    fn with_callback(limit_bytes: usize, on_exceeded: Option<ExceededCallback>) -> Self {
+        Self {
+            inner: Arc::new(BudgetInner {
+                limit_bytes,
+                used_bytes: AtomicI64::new(0),
+                on_exceeded,
+                exceeded_reported: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn limit_bytes(&self) -> usize {
+        self.inner.limit_bytes
+    }
+
+    /// The number of bytes currently allocated under the budget and not yet released. See the
+    /// type-level documentation for how memory is attributed to budgets.
+    pub fn used_bytes(&self) -> i64 {
+        self.inner.used_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.used_bytes() > self.inner.limit_bytes as i64
+    }
+
+    /// Spawns a task under the budget on the current async worker thread. Fails without spawning
+    /// if the budget is already exceeded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub fn spawn<F, R>(&self, future: F) -> Result<LocalJoinHandle<R>, BudgetExceeded>
+    where
+        F: Future<Output = R> + 'static,
+        R: 'static,
+    {
+        self.check()?;
+
+        // The task bookkeeping allocated by the spawn is also charged to the budget.
+        let _guard = self.enter();
+        Ok(spawn(Budgeted::new(future, self.clone())))
+    }
+
+    /// Spawns a task under the budget on any async worker thread of the runtime that owns the
+    /// current thread, creating the future via closure. Fails without spawning if the budget is
+    /// already exceeded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not owned by a Folo runtime.
+    pub fn spawn_on_any<FN, F, R>(
+        &self,
+        future_fn: FN,
+    ) -> Result<RemoteJoinHandle<R>, BudgetExceeded>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        self.check()?;
+
+        let budget = self.clone();
+
+        Ok(spawn_on_any(move || {
+            // The future is created on the target thread, so that is where we charge it.
+            let future = {
+                let _guard = budget.enter();
+                future_fn()
+            };
+
+            Budgeted::new(future, budget)
+        }))
+    }
+
+    fn check(&self) -> Result<(), BudgetExceeded> {
+        self.report_if_exceeded();
+
+        if self.is_exceeded() {
+            Err(BudgetExceeded {
+                used_bytes: self.used_bytes(),
+                limit_bytes: self.inner.limit_bytes,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Calls the callback if the budget has become exceeded since the last time it was called.
+    fn report_if_exceeded(&self) {
+        if !self.is_exceeded() {
+            self.inner.exceeded_reported.store(false, Ordering::Relaxed);
+            return;
+        }
+
+        if self.inner.exceeded_reported.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some(callback) = &self.inner.on_exceeded {
+            callback(self);
+        }
+    }
+
+    /// Charges allocations made on the current thread to this budget until the returned guard is
+    /// dropped, after which the previously active budget (if any) is restored.
+    pub(crate) fn enter(&self) -> BudgetGuard {
+        let previous = CURRENT_BUDGET.replace(Arc::as_ptr(&self.inner));
+
+        BudgetGuard {
+            _budget: Arc::clone(&self.inner),
+            previous,
+        }
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit_bytes", &self.inner.limit_bytes)
+            .field("used_bytes", &self.used_bytes())
+            .finish()
+    }
+}
+
+/// A spawn was refused because the memory budget it was made under has been exceeded.
+#[derive(Clone, Copy, Debug, Error, Eq, PartialEq)]
+#[error("memory budget exceeded: {used_bytes} bytes in use, limit is {limit_bytes} bytes")]
+pub struct BudgetExceeded {
+    pub used_bytes: i64,
+    pub limit_bytes: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct BudgetGuard {
+    // Keeps the budget alive for as long as the allocator may be charging it via raw pointer.
+    _budget: Arc<BudgetInner>,
+    previous: *const BudgetInner,
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        CURRENT_BUDGET.set(self.previous);
+    }
+}
+
+impl fmt::Debug for BudgetInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetInner")
+            .field("limit_bytes", &self.limit_bytes)
+            .field("used_bytes", &self.used_bytes)
+            .finish()
+    }
+}
+
+/// Wraps the future of a task under a budget, charging everything it does to the budget.
+#[pin_project(PinnedDrop)]
+struct Budgeted<F> {
+    // Only None while being dropped, so we can drop the future within the budget scope.
+    #[pin]
+    inner: Option<F>,
+    budget: MemoryBudget,
+}
+
+impl<F> Budgeted<F> {
+    fn new(inner: F, budget: MemoryBudget) -> Self {
+        Self {
+            inner: Some(inner),
+            budget,
+        }
+    }
+}
+
+impl<F> Future for Budgeted<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+
+        let result = {
+            let _guard = this.budget.enter();
+
+            this.inner
+                .as_pin_mut()
+                .expect("the future is only removed when dropped")
+                .poll(cx)
+        };
+
+        this.budget.report_if_exceeded();
+
+        result
+    }
+}
+
+#[pinned_drop]
+impl<F> PinnedDrop for Budgeted<F> {
+    fn drop(self: Pin<&mut Self>) {
+        let mut this = self.project();
+
+        let _guard = this.budget.enter();
+        this.inner.set(None);
+    }
+}
+
+/// Charges an allocation to the budget active on the current thread, if any. Returns the budget to
+/// credit once the allocation is released (null if none), which holds a strong reference to the
+/// budget until then. Called from within the global allocator, so this must never allocate.
+pub(super) fn charge_allocation(bytes: usize) -> *const BudgetInner {
+    let budget = CURRENT_BUDGET.try_with(Cell::get).unwrap_or(ptr::null());
+
+    if budget.is_null() {
+        return budget;
+    }
+
+    // SAFETY: The pointer is only set while a guard holding a strong reference exists, so we can
+    // add one of our own.
+    unsafe {
+        Arc::increment_strong_count(budget);
+        (*budget)
+            .used_bytes
+            .fetch_add(bytes as i64, Ordering::Relaxed);
+    }
+
+    budget
+}
+
+/// Adjusts the charge of an allocation whose size has changed to the budget it is charged to.
+///
+/// # Safety
+///
+/// `budget` must have been returned by `charge_allocation()` for an allocation that has not yet
+/// been released.
+pub(super) unsafe fn recharge_allocation(budget: *const BudgetInner, delta_bytes: i64) {
+    if !budget.is_null() {
+        (*budget)
+            .used_bytes
+            .fetch_add(delta_bytes, Ordering::Relaxed);
+    }
+}
+
+/// Credits a released allocation back to the budget it was charged to and releases the reference
+/// to the budget. If that was the last reference, the budget is freed, which calls back into the
+/// global allocator - the caller must be ready for that.
+///
+/// # Safety
+///
+/// `budget` must have been returned by `charge_allocation()` for the allocation being released.
+pub(super) unsafe fn credit_allocation(budget: *const BudgetInner, bytes: usize) {
+    if budget.is_null() {
+        return;
+    }
+
+    (*budget)
+        .used_bytes
+        .fetch_sub(bytes as i64, Ordering::Relaxed);
+    Arc::decrement_strong_count(budget);
+}
+
+// Accessed from within the global allocator, so it must never allocate itself.
+thread_local! {
+    static CURRENT_BUDGET: Cell<*const BudgetInner> = const { Cell::new(ptr::null()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::InstrumentedAllocator;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::AtomicUsize,
+        thread,
+    };
+
+    // As with the allocator tests, we use a non-global allocator instance on a fresh thread, so
+    // the budget only observes what the test itself does.
+
+    #[test]
+    fn credits_the_budget_that_was_charged() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(100, 8).unwrap();
+            let budget = MemoryBudget::new(1000);
+
+            unsafe {
+                let outside = allocator.alloc(layout);
+
+                let inside = {
+                    let _guard = budget.enter();
+                    allocator.alloc(layout)
+                };
+
+                assert_eq!(budget.used_bytes(), 100);
+
+                // Released outside the scope but still credited to the budget it was charged to.
+                allocator.dealloc(inside, layout);
+                assert_eq!(budget.used_bytes(), 0);
+
+                // Never charged to the budget, so not credited to it either.
+                let _guard = budget.enter();
+                allocator.dealloc(outside, layout);
+            }
+
+            assert_eq!(budget.used_bytes(), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn credits_the_budget_that_was_charged_on_another_thread() {
+        let budget = MemoryBudget::new(1000);
+        let layout = Layout::from_size_align(100, 8).unwrap();
+
+        // The allocator is stateless, so an instance on each thread behaves like a shared one.
+        let ptr = thread::spawn({
+            let budget = budget.clone();
+
+            move || {
+                let allocator = InstrumentedAllocator::new(System);
+                let _guard = budget.enter();
+
+                // SAFETY: Valid layout; the pointer is released below with the same layout.
+                unsafe { allocator.alloc(layout) as usize }
+            }
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(budget.used_bytes(), 100);
+
+        thread::spawn(move || {
+            let allocator = InstrumentedAllocator::new(System);
+
+            // SAFETY: Allocated above with the same layout by the same kind of allocator.
+            unsafe { allocator.dealloc(ptr as *mut u8, layout) };
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(budget.used_bytes(), 0);
+    }
+
+    #[test]
+    fn realloc_recharges_the_budget_that_was_charged() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(100, 8).unwrap();
+            let budget = MemoryBudget::new(1000);
+
+            unsafe {
+                let ptr = {
+                    let _guard = budget.enter();
+                    allocator.alloc(layout)
+                };
+
+                let ptr = allocator.realloc(ptr, layout, 300);
+                assert_eq!(budget.used_bytes(), 300);
+
+                allocator.dealloc(ptr, Layout::from_size_align(300, 8).unwrap());
+            }
+
+            assert_eq!(budget.used_bytes(), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn page_aligned_realloc_keeps_the_budget_that_was_charged() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(4096, 4096).unwrap();
+            let budget = MemoryBudget::new(10_000);
+
+            unsafe {
+                let ptr = {
+                    let _guard = budget.enter();
+                    allocator.alloc(layout)
+                };
+
+                assert_eq!(ptr as usize % 4096, 0);
+
+                let ptr = allocator.realloc(ptr, layout, 100);
+                assert_eq!(ptr as usize % 4096, 0);
+                assert_eq!(budget.used_bytes(), 100);
+
+                allocator.dealloc(ptr, Layout::from_size_align(100, 4096).unwrap());
+            }
+
+            assert_eq!(budget.used_bytes(), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn allocation_keeps_budget_alive() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(100, 8).unwrap();
+            let budget = MemoryBudget::new(1000);
+
+            let ptr = unsafe {
+                let _guard = budget.enter();
+                allocator.alloc(layout)
+            };
+
+            assert_eq!(Arc::strong_count(&budget.inner), 2);
+
+            unsafe { allocator.dealloc(ptr, layout) };
+
+            assert_eq!(Arc::strong_count(&budget.inner), 1);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn nested_scopes_restore_previous() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(10, 8).unwrap();
+            let outer_budget = MemoryBudget::new(1000);
+            let inner_budget = MemoryBudget::new(1000);
+
+            unsafe {
+                let _outer = outer_budget.enter();
+
+                let inner_ptr = {
+                    let _inner = inner_budget.enter();
+                    allocator.alloc(layout)
+                };
+
+                let outer_ptr = allocator.alloc(layout);
+
+                assert_eq!(outer_budget.used_bytes(), 10);
+                assert_eq!(inner_budget.used_bytes(), 10);
+
+                allocator.dealloc(inner_ptr, layout);
+                allocator.dealloc(outer_ptr, layout);
+            }
+
+            assert_eq!(outer_budget.used_bytes(), 0);
+            assert_eq!(inner_budget.used_bytes(), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn exceeding_reports_once_and_fails_check() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(100, 8).unwrap();
+
+            let reported = Arc::new(AtomicUsize::new(0));
+            let budget = MemoryBudget::with_on_exceeded(50, {
+                let reported = Arc::clone(&reported);
+                move |_| {
+                    reported.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+
+            let ptr = unsafe {
+                let _guard = budget.enter();
+                allocator.alloc(layout)
+            };
+
+            assert!(budget.is_exceeded());
+            assert_eq!(
+                budget.check(),
+                Err(BudgetExceeded {
+                    used_bytes: 100,
+                    limit_bytes: 50
+                })
+            );
+            assert!(budget.check().is_err());
+            assert_eq!(reported.load(Ordering::Relaxed), 1);
+
+            unsafe {
+                let _guard = budget.enter();
+                allocator.dealloc(ptr, layout);
+            }
+
+            assert!(budget.check().is_ok());
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,447 @@
+use crate::{
+    mem::budget::{self, BudgetInner},
+    metrics::{Event, EventBuilder, Magnitude},
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    ptr,
+};
+
+/// A global allocator wrapper that counts allocations and allocated bytes on each thread, broken
+/// down by the Folo subsystem that was active when the allocation was made. Install it as the
+/// global allocator of your app to find out where the memory of a long-running service goes:
+///
+/// ```ignore
+/// use folo::mem::InstrumentedAllocator;
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: InstrumentedAllocator<System> = InstrumentedAllocator::new(System);
+/// ```
+///
+/// The counters are thread-local, so each worker thread accounts for its own allocations. They are
+/// published as part of the metrics report page of each worker when the worker shuts down and can
+/// also be inspected at any time via `current_thread_stats()`.
+///
+/// If this allocator is not installed, all counters remain zero and nothing is published.
+///
+/// # Attribution
+///
+/// Memory is attributed to the subsystem active at allocation time. Deallocations are attributed to
+/// the subsystem active at deallocation time, which may differ (e.g. a buffer allocated by an I/O
+/// operation may be released by user code), so per-subsystem "live bytes" are approximate. Memory
+/// freed on a different thread than where it was allocated is likewise attributed to the freeing
+/// thread.
+///
+/// The allocator also charges allocations to the `MemoryBudget` active on the thread, if any. To
+/// credit each allocation back to the budget it was charged to, every allocation is followed by a
+/// pointer-sized footer (plus padding to align it), independent of the alignment of the allocation.
+#[derive(Debug)]
+pub struct InstrumentedAllocator<A = System> {
+    inner: A,
+}
+
+impl<A> InstrumentedAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+// SAFETY: We forward all calls to the inner allocator, only enlarging each allocation by a footer
+// that follows the memory we hand out, so the memory keeps the requested alignment. The
+// bookkeeping we do on the side never allocates (the thread-local counters are const-initialized
+// plain cells), except that releasing the last reference to a budget frees the budget.
+unsafe impl<A> GlobalAlloc for InstrumentedAllocator<A>
+where
+    A: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some((outer_layout, offset)) = with_footer(layout) else {
+            return ptr::null_mut();
+        };
+
+        let ptr = self.inner.alloc(outer_layout);
+
+        if ptr.is_null() {
+            return ptr;
+        }
+
+        budget_slot(ptr, offset).write(record_allocation(layout.size()));
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let Some((outer_layout, offset)) = with_footer(layout) else {
+            return ptr::null_mut();
+        };
+
+        let ptr = self.inner.alloc_zeroed(outer_layout);
+
+        if ptr.is_null() {
+            return ptr;
+        }
+
+        budget_slot(ptr, offset).write(record_allocation(layout.size()));
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (outer_layout, offset) =
+            with_footer(layout).expect("the layout fit a footer when the memory was allocated");
+
+        let budget = budget_slot(ptr, offset).read();
+
+        self.inner.dealloc(ptr, outer_layout);
+
+        record_deallocation(layout.size(), budget);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let (outer_layout, offset) =
+            with_footer(layout).expect("the layout fit a footer when the memory was allocated");
+
+        let Some((new_outer_layout, new_offset)) =
+            with_footer(Layout::from_size_align_unchecked(new_size, layout.align()))
+        else {
+            return ptr::null_mut();
+        };
+
+        // The footer moves when the size changes, so we read it before the old memory goes away.
+        let budget = budget_slot(ptr, offset).read();
+
+        let new_ptr = self
+            .inner
+            .realloc(ptr, outer_layout, new_outer_layout.size());
+
+        // On failure, the original allocation remains valid and nothing changed.
+        if new_ptr.is_null() {
+            return new_ptr;
+        }
+
+        // The allocation stays charged to the same budget.
+        budget_slot(new_ptr, new_offset).write(budget);
+        record_reallocation(layout.size(), new_size, budget);
+        new_ptr
+    }
+}
+
+/// Returns the layout of an allocation enlarged by a footer that records the budget it is charged
+/// to, together with the offset of the footer. The footer is placed after the memory we hand out,
+/// so the overhead does not grow with the alignment (e.g. for page-aligned buffers). None if the
+/// enlarged layout is invalid.
+fn with_footer(layout: Layout) -> Option<(Layout, usize)> {
+    layout.extend(Layout::new::<*const BudgetInner>()).ok()
+}
+
+/// The location of the budget in the footer of an allocation.
+///
+/// # Safety
+///
+/// The pointer must have been returned by one of the allocation functions of the allocator and the
+/// offset must be the footer offset of the layout it was allocated with.
+unsafe fn budget_slot(ptr: *mut u8, offset: usize) -> *mut *const BudgetInner {
+    ptr.add(offset).cast()
+}
+
+/// The part of Folo (or the app) that was active when memory was allocated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Subsystem {
+    /// I/O buffers, including the buffer pool.
+    Buffers,
+
+    /// Async task creation and bookkeeping (the task itself and its captured state).
+    Tasks,
+
+    /// I/O operation metadata (operation store, result delivery).
+    Operations,
+
+    /// Anything not attributed to a specific subsystem, typically application logic.
+    Other,
+}
+
+impl Subsystem {
+    const COUNT: usize = 4;
+
+    const ALL: [Subsystem; Self::COUNT] = [
+        Subsystem::Buffers,
+        Subsystem::Tasks,
+        Subsystem::Operations,
+        Subsystem::Other,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Subsystem::Buffers => 0,
+            Subsystem::Tasks => 1,
+            Subsystem::Operations => 2,
+            Subsystem::Other => 3,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Subsystem::Buffers => "buffers",
+            Subsystem::Tasks => "tasks",
+            Subsystem::Operations => "operations",
+            Subsystem::Other => "other",
+        }
+    }
+}
+
+/// Allocation statistics of one subsystem on one thread, as counted by `InstrumentedAllocator`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AllocationStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub allocated_bytes: u64,
+    pub deallocated_bytes: u64,
+}
+
+impl AllocationStats {
+    /// Approximate number of bytes currently allocated. See `InstrumentedAllocator` for the
+    /// caveats of attributing memory to subsystems.
+    pub fn live_bytes(&self) -> i64 {
+        self.allocated_bytes as i64 - self.deallocated_bytes as i64
+    }
+}
+
+/// Returns the allocation statistics of the current thread for the given subsystem.
+pub fn current_thread_stats(subsystem: Subsystem) -> AllocationStats {
+    COUNTERS
+        .try_with(|counters| counters[subsystem.index()].snapshot())
+        .unwrap_or_default()
+}
+
+/// Attributes allocations made on the current thread to the given subsystem until the returned
+/// guard is dropped, after which the previously active subsystem is restored.
+pub(crate) fn enter_subsystem(subsystem: Subsystem) -> SubsystemGuard {
+    let previous = CURRENT_SUBSYSTEM.replace(subsystem);
+    SubsystemGuard { previous }
+}
+
+#[derive(Debug)]
+pub(crate) struct SubsystemGuard {
+    previous: Subsystem,
+}
+
+impl Drop for SubsystemGuard {
+    fn drop(&mut self) {
+        CURRENT_SUBSYSTEM.set(self.previous);
+    }
+}
+
+/// Publishes the allocation counters of the current thread as metrics events, so they become part
+/// of the next report page. Does nothing if no allocations were ever counted on this thread (e.g.
+/// because `InstrumentedAllocator` is not installed).
+///
+/// This is meant to be called once, just before a worker thread emits its final report page.
+pub(crate) fn publish_metrics() {
+    for subsystem in Subsystem::ALL {
+        let stats = current_thread_stats(subsystem);
+
+        if stats.allocations == 0 {
+            continue;
+        }
+
+        let name = subsystem.name();
+
+        event(format!("mem_{name}_allocations")).observe_many(1, stats.allocations as usize);
+        event(format!("mem_{name}_deallocations")).observe_many(1, stats.deallocations as usize);
+        event(format!("mem_{name}_allocated_bytes")).observe(stats.allocated_bytes as Magnitude);
+        event(format!("mem_{name}_live_bytes")).observe(stats.live_bytes());
+    }
+}
+
+fn event(name: String) -> Event {
+    EventBuilder::new()
+        .name(name)
+        .build()
+        .expect("we always provide a name, which is the only required parameter")
+}
+
+/// Returns the budget the allocation is charged to, to be stored in the footer of the allocation.
+fn record_allocation(size: usize) -> *const BudgetInner {
+    count_allocation(size);
+
+    budget::charge_allocation(size)
+}
+
+/// # Safety
+///
+/// `budget` must be the budget stored in the footer of the allocation being released.
+unsafe fn record_deallocation(size: usize, budget: *const BudgetInner) {
+    count_deallocation(size);
+
+    budget::credit_allocation(budget, size);
+}
+
+/// # Safety
+///
+/// `budget` must be the budget stored in the footer of the allocation being resized.
+unsafe fn record_reallocation(old_size: usize, new_size: usize, budget: *const BudgetInner) {
+    count_deallocation(old_size);
+    count_allocation(new_size);
+
+    budget::recharge_allocation(budget, new_size as i64 - old_size as i64);
+}
+
+fn count_allocation(size: usize) {
+    // We use try_with because allocations may still happen while thread-local storage is being
+    // torn down at the end of the thread. Such allocations are simply not counted.
+    _ = CURRENT_SUBSYSTEM.try_with(|subsystem| {
+        _ = COUNTERS.try_with(|counters| counters[subsystem.get().index()].record_allocation(size));
+    });
+}
+
+fn count_deallocation(size: usize) {
+    _ = CURRENT_SUBSYSTEM.try_with(|subsystem| {
+        _ = COUNTERS
+            .try_with(|counters| counters[subsystem.get().index()].record_deallocation(size));
+    });
+}
+
+#[derive(Debug)]
+struct SubsystemCounters {
+    allocations: Cell<u64>,
+    deallocations: Cell<u64>,
+    allocated_bytes: Cell<u64>,
+    deallocated_bytes: Cell<u64>,
+}
+
+impl SubsystemCounters {
+    const fn new() -> Self {
+        Self {
+            allocations: Cell::new(0),
+            deallocations: Cell::new(0),
+            allocated_bytes: Cell::new(0),
+            deallocated_bytes: Cell::new(0),
+        }
+    }
+
+    fn record_allocation(&self, size: usize) {
+        self.allocations.set(self.allocations.get() + 1);
+        self.allocated_bytes
+            .set(self.allocated_bytes.get() + size as u64);
+    }
+
+    fn record_deallocation(&self, size: usize) {
+        self.deallocations.set(self.deallocations.get() + 1);
+        self.deallocated_bytes
+            .set(self.deallocated_bytes.get() + size as u64);
+    }
+
+    fn snapshot(&self) -> AllocationStats {
+        AllocationStats {
+            allocations: self.allocations.get(),
+            deallocations: self.deallocations.get(),
+            allocated_bytes: self.allocated_bytes.get(),
+            deallocated_bytes: self.deallocated_bytes.get(),
+        }
+    }
+}
+
+// These are accessed from within the global allocator, so they must never allocate themselves.
+// Const initialization of plain cells guarantees that.
+thread_local! {
+    static CURRENT_SUBSYSTEM: Cell<Subsystem> = const { Cell::new(Subsystem::Other) };
+
+    static COUNTERS: [SubsystemCounters; Subsystem::COUNT] = const {
+        [
+            SubsystemCounters::new(),
+            SubsystemCounters::new(),
+            SubsystemCounters::new(),
+            SubsystemCounters::new(),
+        ]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // The tests use a non-global instance of the allocator and run on fresh threads, so the
+    // counters only observe what the test itself does.
+
+    #[test]
+    fn counts_allocations_per_subsystem() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(100, 8).unwrap();
+
+            unsafe {
+                let other = allocator.alloc(layout);
+
+                let buffer = {
+                    let _guard = enter_subsystem(Subsystem::Buffers);
+                    allocator.alloc(layout)
+                };
+
+                allocator.dealloc(other, layout);
+
+                let _guard = enter_subsystem(Subsystem::Buffers);
+                allocator.dealloc(buffer, layout);
+            }
+
+            let other = current_thread_stats(Subsystem::Other);
+            assert_eq!(other.allocations, 1);
+            assert_eq!(other.deallocations, 1);
+            assert_eq!(other.allocated_bytes, 100);
+            assert_eq!(other.live_bytes(), 0);
+
+            let buffers = current_thread_stats(Subsystem::Buffers);
+            assert_eq!(buffers.allocations, 1);
+            assert_eq!(buffers.deallocations, 1);
+            assert_eq!(buffers.live_bytes(), 0);
+
+            assert_eq!(
+                current_thread_stats(Subsystem::Tasks),
+                AllocationStats::default()
+            );
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn realloc_moves_bytes() {
+        thread::spawn(|| {
+            let allocator = InstrumentedAllocator::new(System);
+            let layout = Layout::from_size_align(16, 8).unwrap();
+
+            unsafe {
+                let ptr = allocator.alloc(layout);
+                let ptr = allocator.realloc(ptr, layout, 64);
+                allocator.dealloc(ptr, Layout::from_size_align(64, 8).unwrap());
+            }
+
+            let stats = current_thread_stats(Subsystem::Other);
+            assert_eq!(stats.allocations, 2);
+            assert_eq!(stats.deallocations, 2);
+            assert_eq!(stats.allocated_bytes, 80);
+            assert_eq!(stats.live_bytes(), 0);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn subsystem_guard_restores_previous() {
+        thread::spawn(|| {
+            let outer = enter_subsystem(Subsystem::Tasks);
+
+            {
+                let _inner = enter_subsystem(Subsystem::Operations);
+                assert_eq!(CURRENT_SUBSYSTEM.get(), Subsystem::Operations);
+            }
+
+            assert_eq!(CURRENT_SUBSYSTEM.get(), Subsystem::Tasks);
+
+            drop(outer);
+            assert_eq!(CURRENT_SUBSYSTEM.get(), Subsystem::Other);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -11,7 +11,7 @@ use std::{
     time::Duration,
 };
 
-use crate::util::LowPrecisionInstant;
+use crate::{rt::current_runtime, util::LowPrecisionInstant};
 
 pub type Magnitude = i64;
 
@@ -203,12 +203,22 @@ impl ObservationBagSnapshot {
 /// A report page is a single thread's contribution to a report. Collect all the pages from all
 /// the threads and you can assemble a report to show to the operator or to export.
 pub struct ReportPage {
+    runtime_name: Option<String>,
     bags: HashMap<String, ObservationBagSnapshot>,
 }
 
+impl ReportPage {
+    /// The name of the runtime that owns the thread the page was assembled on, if any. Use this to
+    /// tell apart pages from different runtimes sending to the same channel.
+    pub fn runtime_name(&self) -> Option<&str> {
+        self.runtime_name.as_deref()
+    }
+}
+
 /// Assembles a report page representing the latest state of observations on the current thread.
 pub fn report_page() -> ReportPage {
     ReportPage {
+        runtime_name: current_runtime::try_get().map(|runtime| runtime.name().to_string()),
         bags: BAGS.with_borrow(|bags| {
             bags.iter()
                 .map(|(name, bag)| (name.clone(), bag.snapshot()))
@@ -1,6 +1,77 @@
+mod accept_rate_limiter;
+pub mod codec;
+mod connect_options;
+mod connection_limiter;
+pub mod dns;
+mod drainer;
+mod happy_eyeballs;
+pub mod http1;
+mod interfaces;
+pub mod pool;
+mod ping;
+mod proxy_protocol;
+#[cfg(feature = "quinn")]
+mod quinn_runtime;
+mod receive_batches;
+mod receive_buffer_sizer;
+mod resolve;
+#[cfg(feature = "rustls")]
+mod rustls_stream;
+mod shared_tcp_listener;
+pub mod socks;
+mod socket_handoff;
+mod socket_options;
+mod socket_pair;
+pub(crate) mod socket_pool;
 mod tcp_connection;
+mod tcp_listener;
+mod tcp_profile;
 mod tcp_server;
+mod throttled;
+pub mod tls;
+mod tls_listener;
+mod tls_server;
+#[cfg(feature = "udp-stream")]
+mod udp_datagrams;
+mod udp_message;
+mod udp_socket;
+mod unix_listener;
+mod unix_stream;
+pub mod ws;
 pub(crate) mod winsock;
 
+pub(crate) use accept_rate_limiter::*;
+pub use connect_options::*;
+pub(crate) use connection_limiter::*;
+pub use drainer::*;
+pub use happy_eyeballs::*;
+pub use interfaces::*;
+pub use ping::*;
+pub use proxy_protocol::ProxyHeader;
+#[cfg(feature = "quinn")]
+pub use quinn_runtime::*;
+pub use receive_batches::*;
+pub use receive_buffer_sizer::*;
+pub use resolve::*;
+#[cfg(feature = "rustls")]
+pub use rustls_stream::*;
+pub use shared_tcp_listener::*;
+pub use socket_handoff::*;
+pub use socket_options::{KeepaliveSettings, SocketOptions};
+pub use socket_pair::*;
+pub use socket_pool::MAX_POOLED_SOCKETS;
 pub use tcp_connection::*;
+pub use tcp_listener::*;
+pub use tcp_profile::*;
 pub use tcp_server::*;
+pub use throttled::*;
+pub use tls_listener::*;
+pub use tls_server::*;
+#[cfg(feature = "udp-stream")]
+pub use udp_datagrams::*;
+pub use udp_message::{
+    CoalescedDatagrams, EcnCodepoint, ReceivedMessage, RECEIVE_MESSAGE_RESERVE,
+};
+pub use udp_socket::*;
+pub use unix_listener::*;
+pub use unix_stream::UnixStream;
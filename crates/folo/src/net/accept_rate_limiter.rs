@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+/// Limits the rate at which connections are accepted, via a token bucket that holds up to one
+/// second worth of accepts. This allows short bursts up to the per-second rate while keeping the
+/// long-term average at or below it.
+#[derive(Debug)]
+pub(crate) struct AcceptRateLimiter {
+    per_second: u32,
+
+    // Fractional tokens accumulate between accepts.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl AcceptRateLimiter {
+    pub fn new(per_second: u32, now: Instant) -> Self {
+        Self {
+            per_second,
+            tokens: per_second as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Takes a token for one accept if one is available. Otherwise, returns how long until the next
+    /// token becomes available.
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second as f64)
+            .min(self.per_second as f64);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            (1.0 - self.tokens) / self.per_second as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_burst_then_limits() {
+        let start = Instant::now();
+        let mut limiter = AcceptRateLimiter::new(10, start);
+
+        for _ in 0..10 {
+            assert!(limiter.try_take(start).is_ok());
+        }
+
+        let wait = limiter.try_take(start).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let start = Instant::now();
+        let mut limiter = AcceptRateLimiter::new(10, start);
+
+        for _ in 0..10 {
+            limiter.try_take(start).unwrap();
+        }
+
+        let later = start + Duration::from_millis(250);
+        assert!(limiter.try_take(later).is_ok());
+        assert!(limiter.try_take(later).is_ok());
+        assert!(limiter.try_take(later).is_err());
+
+        // The bucket never holds more than one second worth of tokens.
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert!(limiter.try_take(much_later).is_ok());
+        }
+        assert!(limiter.try_take(much_later).is_err());
+    }
+}
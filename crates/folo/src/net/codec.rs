@@ -0,0 +1,32 @@
+//! Framing of the byte stream of a `TcpConnection` into messages, via codecs that decode frames
+//! from received bytes and encode frames into bytes to send.
+//!
+//! Wrap a connection into `Framed` together with a codec (e.g. `LengthDelimitedCodec` for binary
+//! protocols or `LinesCodec` for text protocols) to get a `Stream` of received frames and a `Sink`
+//! for frames to send.
+
+mod framed;
+mod length_delimited;
+mod lines;
+
+pub use framed::*;
+pub use length_delimited::*;
+pub use lines::*;
+
+use crate::io;
+
+/// Decodes frames from the bytes received from a connection.
+pub trait Decoder {
+    type Item;
+
+    /// Decodes the first frame from `src`, which holds the received bytes not yet consumed by
+    /// previous frames. Returns the frame and the number of bytes it consumed, or `None` if `src`
+    /// does not yet hold a complete frame.
+    fn decode(&mut self, src: &[u8]) -> io::Result<Option<(Self::Item, usize)>>;
+}
+
+/// Encodes frames into bytes to send to a connection.
+pub trait Encoder<Item> {
+    /// Appends the encoded frame to `dst`.
+    fn encode(&mut self, item: Item, dst: &mut Vec<u8>) -> io::Result<()>;
+}
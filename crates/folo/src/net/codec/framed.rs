@@ -0,0 +1,232 @@
+use crate::{
+    io::{self, OperationResult, PinnedBuffer},
+    net::{
+        codec::{Decoder, Encoder},
+        TcpConnection,
+    },
+};
+use futures::{future::LocalBoxFuture, FutureExt, Sink, Stream};
+use negative_impl::negative_impl;
+use std::{mem, net::Shutdown, pin::Pin, task};
+
+/// Once this many encoded bytes are waiting to be sent, `Sink::poll_ready()` flushes them before
+/// accepting more frames.
+const BACKPRESSURE_BOUNDARY: usize = 64 * 1024;
+
+/// A `TcpConnection` combined with a codec, exposing the connection as a `Stream` of decoded
+/// frames and a `Sink` of frames to encode and send. Use the `StreamExt` and `SinkExt` extension
+/// traits of the `futures` crate to receive and send frames.
+///
+/// Frames are encoded into a buffer that is handed to the connection as a whole when the sink is
+/// flushed, so many small frames can be sent with one operation. Closing the sink flushes it and
+/// then closes the connection for writing, so the peer sees the end of the stream.
+///
+/// Dropping the `Framed` cancels any operations in progress and closes the connection.
+pub struct Framed<C> {
+    connection: TcpConnection,
+    codec: C,
+
+    // Received bytes not yet consumed by decoded frames, starting at `read_start`.
+    read_buffer: Vec<u8>,
+    read_start: usize,
+    receive: Option<LocalBoxFuture<'static, OperationResult>>,
+    read_closed: bool,
+
+    // Encoded frames waiting to be sent.
+    write_buffer: Vec<u8>,
+    send: Option<LocalBoxFuture<'static, OperationResult>>,
+
+    // How many bytes the send in progress was given.
+    send_len: usize,
+}
+
+impl<C> Framed<C> {
+    pub fn new(mut connection: TcpConnection, codec: C) -> Self {
+        let mut read_buffer = Vec::new();
+
+        // Anything received together with accepting the connection is the start of the stream.
+        if let Some(initial_data) = connection.take_initial_data() {
+            read_buffer.extend_from_slice(initial_data.as_slice());
+        }
+
+        Self {
+            connection,
+            codec,
+            read_buffer,
+            read_start: 0,
+            receive: None,
+            read_closed: false,
+            write_buffer: Vec::new(),
+            send: None,
+            send_len: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &TcpConnection {
+        &self.connection
+    }
+
+    /// The underlying TCP connection. Sending or receiving data directly on it corrupts the stream
+    /// of frames.
+    pub fn get_mut(&mut self) -> &mut TcpConnection {
+        &mut self.connection
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns the connection, dropping any received bytes that have not been decoded and any
+    /// encoded frames that have not been sent.
+    pub fn into_inner(self) -> TcpConnection {
+        self.connection
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.read_start += len;
+
+        if self.read_start == self.read_buffer.len() {
+            self.read_buffer.clear();
+            self.read_start = 0;
+        } else if self.read_start > self.read_buffer.len() / 2 {
+            // Move the remainder to the front once the consumed part dominates, so the buffer does
+            // not grow without bound while keeping the copying to a minimum.
+            self.read_buffer.drain(..self.read_start);
+            self.read_start = 0;
+        }
+    }
+}
+
+impl<C> Stream for Framed<C>
+where
+    C: Decoder + Unpin,
+{
+    type Item = io::Result<C::Item>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            match this.codec.decode(&this.read_buffer[this.read_start..]) {
+                Ok(Some((item, consumed))) => {
+                    this.consume(consumed);
+                    return task::Poll::Ready(Some(Ok(item)));
+                }
+                Ok(None) => {}
+                Err(e) => return task::Poll::Ready(Some(Err(e))),
+            }
+
+            if this.read_closed {
+                if this.read_start == this.read_buffer.len() {
+                    return task::Poll::Ready(None);
+                }
+
+                // Report the truncated frame once, then end the stream.
+                this.read_buffer.clear();
+                this.read_start = 0;
+
+                return task::Poll::Ready(Some(Err(io::Error::StdIo(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "connection closed in the middle of a frame",
+                )))));
+            }
+
+            let connection = &this.connection;
+            let receive = this.receive.get_or_insert_with(|| {
+                connection
+                    .receive_detached(PinnedBuffer::from_pool())
+                    .boxed_local()
+            });
+
+            let result = task::ready!(receive.poll_unpin(cx));
+            this.receive = None;
+
+            match result {
+                Ok(buffer) if buffer.len() == 0 => this.read_closed = true,
+                Ok(buffer) => this.read_buffer.extend_from_slice(buffer.as_slice()),
+                Err(e) => return task::Poll::Ready(Some(Err(e.into_inner()))),
+            }
+        }
+    }
+}
+
+impl<C, Item> Sink<Item> for Framed<C>
+where
+    C: Encoder<Item> + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        if self.write_buffer.len() >= BACKPRESSURE_BOUNDARY {
+            return self.as_mut().poll_flush(cx);
+        }
+
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        this.codec.encode(item, &mut this.write_buffer)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(send) = &mut this.send {
+                let result = task::ready!(send.poll_unpin(cx));
+                this.send = None;
+
+                match result {
+                    // The connection only sends less than all the data if it can send no more.
+                    Ok(buffer) if buffer.len() < this.send_len => {
+                        return task::Poll::Ready(Err(io::Error::StdIo(
+                            std::io::ErrorKind::WriteZero.into(),
+                        )));
+                    }
+                    Ok(_) => {}
+                    Err(e) => return task::Poll::Ready(Err(e.into_inner())),
+                }
+            }
+
+            if this.write_buffer.is_empty() {
+                return task::Poll::Ready(Ok(()));
+            }
+
+            // We hand over the encoded bytes as they are, without copying them.
+            let buffer = PinnedBuffer::from_boxed_slice(
+                mem::take(&mut this.write_buffer).into_boxed_slice(),
+            );
+
+            this.send_len = buffer.len();
+            this.send = Some(this.connection.send_detached(buffer).boxed_local());
+        }
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Result<(), Self::Error>> {
+        task::ready!(self.as_mut().poll_flush(cx))?;
+
+        task::Poll::Ready(self.connection.shutdown(Shutdown::Write))
+    }
+}
+
+#[negative_impl]
+impl<C> !Send for Framed<C> {}
+#[negative_impl]
+impl<C> !Sync for Framed<C> {}
@@ -0,0 +1,323 @@
+use crate::{
+    io,
+    net::codec::{Decoder, Encoder},
+};
+
+/// The default maximum length of a frame payload, to protect against peers that claim to send
+/// huge frames.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// The order of the bytes in the length field of a frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+/// A codec for frames that carry the length of their payload in a fixed-size length field, which
+/// is how most binary RPC protocols delimit their messages.
+///
+/// By default, the length field is 4 bytes long, big-endian and at the start of the frame. If
+/// the length field is preceded by other fields (e.g. a message type), set the length field
+/// offset - the bytes before the length field are then included at the start of the decoded
+/// frames and must be included at the start of the frames to encode. The length field itself is
+/// never included, as the codec takes care of it.
+///
+/// The length field contains the length of the payload that follows it.
+#[derive(Clone, Debug)]
+pub struct LengthDelimitedCodec {
+    length_field_size: usize,
+    length_field_offset: usize,
+    byte_order: ByteOrder,
+    max_frame_len: usize,
+}
+
+impl LengthDelimitedCodec {
+    /// Creates a codec with the default options.
+    pub fn new() -> Self {
+        Self {
+            length_field_size: 4,
+            length_field_offset: 0,
+            byte_order: ByteOrder::BigEndian,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        self.length_field_offset + self.length_field_size
+    }
+
+    fn frame_too_long(&self, len: u64) -> io::Error {
+        io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "frame payload of {len} bytes exceeds the maximum of {} bytes",
+                self.max_frame_len
+            ),
+        ))
+    }
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, src: &[u8]) -> io::Result<Option<(Self::Item, usize)>> {
+        let header_len = self.header_len();
+
+        if src.len() < header_len {
+            return Ok(None);
+        }
+
+        let length_field = &src[self.length_field_offset..header_len];
+
+        let payload_len = match self.byte_order {
+            ByteOrder::BigEndian => length_field
+                .iter()
+                .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte)),
+            ByteOrder::LittleEndian => length_field
+                .iter()
+                .rev()
+                .fold(0_u64, |value, byte| (value << 8) | u64::from(*byte)),
+        };
+
+        if payload_len > self.max_frame_len as u64 {
+            return Err(self.frame_too_long(payload_len));
+        }
+
+        // Cannot overflow because it is no more than the maximum frame length.
+        let payload_len = payload_len as usize;
+        let frame_len = header_len + payload_len;
+
+        if src.len() < frame_len {
+            return Ok(None);
+        }
+
+        let mut frame = Vec::with_capacity(self.length_field_offset + payload_len);
+        frame.extend_from_slice(&src[..self.length_field_offset]);
+        frame.extend_from_slice(&src[header_len..frame_len]);
+
+        Ok(Some((frame, frame_len)))
+    }
+}
+
+impl<T> Encoder<T> for LengthDelimitedCodec
+where
+    T: AsRef<[u8]>,
+{
+    fn encode(&mut self, item: T, dst: &mut Vec<u8>) -> io::Result<()> {
+        let item = item.as_ref();
+
+        if item.len() < self.length_field_offset {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "frame of {} bytes is shorter than the {} bytes that precede the length field",
+                    item.len(),
+                    self.length_field_offset
+                ),
+            )));
+        }
+
+        let (prefix, payload) = item.split_at(self.length_field_offset);
+
+        if payload.len() > self.max_frame_len {
+            return Err(self.frame_too_long(payload.len() as u64));
+        }
+
+        let length_bytes = (payload.len() as u64).to_be_bytes();
+        let length_field = &length_bytes[length_bytes.len() - self.length_field_size..];
+
+        dst.reserve(self.header_len() + payload.len());
+        dst.extend_from_slice(prefix);
+
+        match self.byte_order {
+            ByteOrder::BigEndian => dst.extend_from_slice(length_field),
+            ByteOrder::LittleEndian => dst.extend(length_field.iter().rev()),
+        }
+
+        dst.extend_from_slice(payload);
+
+        Ok(())
+    }
+}
+
+/// Builds a `LengthDelimitedCodec` with custom options.
+#[derive(Debug)]
+pub struct LengthDelimitedCodecBuilder {
+    length_field_size: usize,
+    length_field_offset: usize,
+    byte_order: ByteOrder,
+    max_frame_len: Option<usize>,
+}
+
+impl LengthDelimitedCodecBuilder {
+    pub fn new() -> Self {
+        let defaults = LengthDelimitedCodec::new();
+
+        Self {
+            length_field_size: defaults.length_field_size,
+            length_field_offset: defaults.length_field_offset,
+            byte_order: defaults.byte_order,
+            max_frame_len: None,
+        }
+    }
+
+    /// The size of the length field in bytes, from 1 to 8. Defaults to 4.
+    pub fn length_field_size(mut self, value: usize) -> Self {
+        self.length_field_size = value;
+        self
+    }
+
+    /// The number of bytes that precede the length field in each frame. Defaults to 0.
+    pub fn length_field_offset(mut self, value: usize) -> Self {
+        self.length_field_offset = value;
+        self
+    }
+
+    /// The order of the bytes in the length field. Defaults to big-endian (network byte order).
+    pub fn byte_order(mut self, value: ByteOrder) -> Self {
+        self.byte_order = value;
+        self
+    }
+
+    /// The maximum length of a frame payload. Receiving a longer frame fails the stream of
+    /// frames and encoding a longer frame fails the send. Defaults to `DEFAULT_MAX_FRAME_LEN` or
+    /// the largest length that fits into the length field, whichever is smaller.
+    pub fn max_frame_len(mut self, value: usize) -> Self {
+        self.max_frame_len = Some(value);
+        self
+    }
+
+    pub fn build(self) -> io::Result<LengthDelimitedCodec> {
+        if !(1..=8).contains(&self.length_field_size) {
+            return Err(io::Error::InvalidOptions(
+                "length field size must be from 1 to 8 bytes".to_string(),
+            ));
+        }
+
+        let max_representable = match self.length_field_size {
+            8 => u64::MAX,
+            size => (1_u64 << (size * 8)) - 1,
+        };
+
+        let max_frame_len = match self.max_frame_len {
+            Some(value) if value as u64 > max_representable => {
+                return Err(io::Error::InvalidOptions(format!(
+                    "maximum frame length {value} does not fit into a length field of {} bytes",
+                    self.length_field_size
+                )));
+            }
+            Some(value) => value,
+            None => (DEFAULT_MAX_FRAME_LEN as u64).min(max_representable) as usize,
+        };
+
+        Ok(LengthDelimitedCodec {
+            length_field_size: self.length_field_size,
+            length_field_offset: self.length_field_offset,
+            byte_order: self.byte_order,
+            max_frame_len,
+        })
+    }
+}
+
+impl Default for LengthDelimitedCodecBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(codec: &mut LengthDelimitedCodec, frame: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        codec.encode(frame, &mut encoded).unwrap();
+
+        let (decoded, consumed) = codec.decode(&encoded).unwrap().unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(consumed, encoded.len());
+
+        encoded
+    }
+
+    #[test]
+    fn default_is_four_byte_big_endian() {
+        let mut codec = LengthDelimitedCodec::new();
+
+        let encoded = round_trip(&mut codec, b"hello");
+        assert_eq!(encoded, b"\x00\x00\x00\x05hello");
+    }
+
+    #[test]
+    fn little_endian_with_offset() {
+        let mut codec = LengthDelimitedCodecBuilder::new()
+            .length_field_size(2)
+            .length_field_offset(1)
+            .byte_order(ByteOrder::LittleEndian)
+            .build()
+            .unwrap();
+
+        // The first byte is a message type that precedes the length field.
+        let encoded = round_trip(&mut codec, b"\x07hello");
+        assert_eq!(encoded, b"\x07\x05\x00hello");
+    }
+
+    #[test]
+    fn incomplete_frame_waits_for_more() {
+        let mut codec = LengthDelimitedCodec::new();
+
+        assert!(codec.decode(b"\x00\x00").unwrap().is_none());
+        assert!(codec.decode(b"\x00\x00\x00\x05hel").unwrap().is_none());
+
+        // Only the first frame is consumed.
+        let (frame, consumed) = codec.decode(b"\x00\x00\x00\x01a\x00").unwrap().unwrap();
+        assert_eq!(frame, b"a");
+        assert_eq!(consumed, 5);
+    }
+
+    #[test]
+    fn too_long_frame_is_rejected() {
+        let mut codec = LengthDelimitedCodecBuilder::new()
+            .max_frame_len(4)
+            .build()
+            .unwrap();
+
+        assert!(codec.decode(b"\x00\x00\x00\x05").is_err());
+        assert!(codec.encode(b"hello", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn default_max_fits_into_length_field() {
+        let mut codec = LengthDelimitedCodecBuilder::new()
+            .length_field_size(1)
+            .build()
+            .unwrap();
+
+        round_trip(&mut codec, &[42; 255]);
+        assert!(codec.encode([42; 256], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        assert!(LengthDelimitedCodecBuilder::new()
+            .length_field_size(0)
+            .build()
+            .is_err());
+        assert!(LengthDelimitedCodecBuilder::new()
+            .length_field_size(9)
+            .build()
+            .is_err());
+        assert!(LengthDelimitedCodecBuilder::new()
+            .length_field_size(1)
+            .max_frame_len(256)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    io,
+    net::codec::{Decoder, Encoder},
+};
+
+/// The default maximum length of a line, to protect against peers that never send a delimiter.
+pub const DEFAULT_MAX_LINE_LEN: usize = 64 * 1024;
+
+/// A codec for text protocols that send one message per line, such as SMTP or the Redis protocol.
+///
+/// By default, lines are delimited by `\n`, with a `\r` right before the delimiter also removed
+/// from decoded lines, so both `\n` and `\r\n` line endings are understood. Encoded lines are
+/// terminated by `\n`. Use `LinesCodecBuilder` to delimit lines differently (e.g. with `\r\n`
+/// both ways).
+///
+/// Decoded lines do not include the delimiter and must be valid UTF-8. Lines to encode must not
+/// include the delimiter - the codec appends it.
+#[derive(Clone, Debug)]
+pub struct LinesCodec {
+    delimiter: Vec<u8>,
+    strip_carriage_return: bool,
+    max_line_len: usize,
+
+    // How far into the received bytes we have already searched for the delimiter without finding
+    // it, so we do not search the same bytes again when more arrive.
+    searched_len: usize,
+}
+
+impl LinesCodec {
+    /// Creates a codec with the default options.
+    pub fn new() -> Self {
+        Self {
+            delimiter: b"\n".to_vec(),
+            strip_carriage_return: true,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            searched_len: 0,
+        }
+    }
+
+    fn line_too_long(&self) -> io::Error {
+        io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("line exceeds the maximum of {} bytes", self.max_line_len),
+        ))
+    }
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+
+    fn decode(&mut self, src: &[u8]) -> io::Result<Option<(Self::Item, usize)>> {
+        // A delimiter may have been only partially received when we last searched, so we go back
+        // far enough to find it once the rest of it arrives.
+        let search_start = self
+            .searched_len
+            .saturating_sub(self.delimiter.len() - 1)
+            .min(src.len());
+
+        let found = src[search_start..]
+            .windows(self.delimiter.len())
+            .position(|window| window == self.delimiter);
+
+        let Some(position) = found else {
+            if src.len() > self.max_line_len + self.delimiter.len() {
+                return Err(self.line_too_long());
+            }
+
+            self.searched_len = src.len();
+            return Ok(None);
+        };
+
+        self.searched_len = 0;
+
+        let line_end = search_start + position;
+        let consumed = line_end + self.delimiter.len();
+
+        let mut line = &src[..line_end];
+
+        if self.strip_carriage_return {
+            line = line.strip_suffix(b"\r").unwrap_or(line);
+        }
+
+        if line.len() > self.max_line_len {
+            return Err(self.line_too_long());
+        }
+
+        let line = std::str::from_utf8(line).map_err(|e| {
+            io::Error::StdIo(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })?;
+
+        Ok(Some((line.to_string(), consumed)))
+    }
+}
+
+impl<T> Encoder<T> for LinesCodec
+where
+    T: AsRef<str>,
+{
+    fn encode(&mut self, item: T, dst: &mut Vec<u8>) -> io::Result<()> {
+        let line = item.as_ref().as_bytes();
+
+        if line.len() > self.max_line_len {
+            return Err(self.line_too_long());
+        }
+
+        if line
+            .windows(self.delimiter.len())
+            .any(|window| window == self.delimiter)
+        {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "line contains the line delimiter",
+            )));
+        }
+
+        dst.reserve(line.len() + self.delimiter.len());
+        dst.extend_from_slice(line);
+        dst.extend_from_slice(&self.delimiter);
+
+        Ok(())
+    }
+}
+
+/// Builds a `LinesCodec` with custom options.
+#[derive(Debug)]
+pub struct LinesCodecBuilder {
+    delimiter: Vec<u8>,
+    strip_carriage_return: Option<bool>,
+    max_line_len: usize,
+}
+
+impl LinesCodecBuilder {
+    pub fn new() -> Self {
+        Self {
+            delimiter: b"\n".to_vec(),
+            strip_carriage_return: None,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+        }
+    }
+
+    /// The bytes that terminate each line, such as `\r\n` or `\0`. Defaults to `\n`.
+    pub fn delimiter(mut self, value: impl Into<Vec<u8>>) -> Self {
+        self.delimiter = value.into();
+        self
+    }
+
+    /// Whether to remove a `\r` right before the delimiter from decoded lines. Defaults to
+    /// enabled if the delimiter is `\n` and disabled otherwise.
+    pub fn strip_carriage_return(mut self, value: bool) -> Self {
+        self.strip_carriage_return = Some(value);
+        self
+    }
+
+    /// The maximum length of a line, not including the delimiter. Receiving a longer line fails
+    /// the stream of lines and encoding a longer line fails the send. Defaults to
+    /// `DEFAULT_MAX_LINE_LEN`.
+    pub fn max_line_len(mut self, value: usize) -> Self {
+        self.max_line_len = value;
+        self
+    }
+
+    pub fn build(self) -> io::Result<LinesCodec> {
+        if self.delimiter.is_empty() {
+            return Err(io::Error::InvalidOptions(
+                "line delimiter must not be empty".to_string(),
+            ));
+        }
+
+        let strip_carriage_return = self
+            .strip_carriage_return
+            .unwrap_or(self.delimiter == b"\n");
+
+        Ok(LinesCodec {
+            delimiter: self.delimiter,
+            strip_carriage_return,
+            max_line_len: self.max_line_len,
+            searched_len: 0,
+        })
+    }
+}
+
+impl Default for LinesCodecBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_accepts_both_line_endings() {
+        let mut codec = LinesCodec::new();
+
+        let (line, consumed) = codec.decode(b"hello\r\nworld\n").unwrap().unwrap();
+        assert_eq!(line, "hello");
+        assert_eq!(consumed, 7);
+
+        let (line, consumed) = codec.decode(b"world\n").unwrap().unwrap();
+        assert_eq!(line, "world");
+        assert_eq!(consumed, 6);
+
+        let mut encoded = Vec::new();
+        codec.encode("hello", &mut encoded).unwrap();
+        assert_eq!(encoded, b"hello\n");
+    }
+
+    #[test]
+    fn delimiter_split_across_receives() {
+        let mut codec = LinesCodecBuilder::new().delimiter("\r\n").build().unwrap();
+
+        assert!(codec.decode(b"PING").unwrap().is_none());
+        assert!(codec.decode(b"PING\r").unwrap().is_none());
+
+        let (line, consumed) = codec.decode(b"PING\r\n+OK").unwrap().unwrap();
+        assert_eq!(line, "PING");
+        assert_eq!(consumed, 6);
+
+        let mut encoded = Vec::new();
+        codec.encode("+PONG", &mut encoded).unwrap();
+        assert_eq!(encoded, b"+PONG\r\n");
+    }
+
+    #[test]
+    fn empty_lines() {
+        let mut codec = LinesCodec::new();
+
+        let (line, consumed) = codec.decode(b"\n").unwrap().unwrap();
+        assert_eq!(line, "");
+        assert_eq!(consumed, 1);
+    }
+
+    #[test]
+    fn too_long_line_is_rejected() {
+        let mut codec = LinesCodecBuilder::new().max_line_len(4).build().unwrap();
+
+        assert!(codec.decode(b"abcd").unwrap().is_none());
+        assert!(codec.decode(b"abcdef").is_err());
+        assert!(codec.encode("abcde", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        let mut codec = LinesCodec::new();
+
+        assert!(codec.decode(b"\xff\xfe\n").is_err());
+        assert!(codec.encode("a\nb", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn empty_delimiter_is_rejected() {
+        assert!(LinesCodecBuilder::new().delimiter("").build().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    net::{KeepaliveSettings, SocketOptions},
+    rt::clock,
+    sync::CancellationToken,
+};
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Options for establishing an outbound TCP connection via `TcpConnection::connect_with()`.
+///
+/// Socket options specified here are applied after the connection has been established but before
+/// it is returned to the caller - if applying any of them fails, the connection is closed and the
+/// error returned, so the caller never observes a partially configured connection.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOptions {
+    pub(super) deadline: Option<Instant>,
+    pub(super) cancellation_token: Option<CancellationToken>,
+    pub(super) socket_options: SocketOptions,
+    pub(super) reuse_socket: bool,
+    pub(super) fast_open: bool,
+    pub(super) initial_data: Option<Box<[u8]>>,
+    pub(super) local_addr: Option<SocketAddr>,
+    pub(super) interface_index: Option<u32>,
+    pub(super) registered_io: bool,
+}
+
+impl ConnectOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If the connection has not been established by this time, the attempt is abandoned and
+    /// `io::Error::TimedOut` is returned.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to the specified duration from now.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(clock::now() + timeout)
+    }
+
+    /// If the token is cancelled before the connection has been established, the attempt is
+    /// abandoned and `io::Error::Canceled` is returned.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Sets the socket options to apply to the connection, replacing any previously set.
+    pub fn socket_options(mut self, value: SocketOptions) -> Self {
+        self.socket_options = value;
+        self
+    }
+
+    /// Sets TCP_NODELAY on the connection, disabling (if true) the Nagle algorithm.
+    pub fn nodelay(mut self, value: bool) -> Self {
+        self.socket_options = self.socket_options.nodelay(value);
+        self
+    }
+
+    /// Sets SO_KEEPALIVE on the connection, enabling (if true) TCP keepalive packets with the
+    /// operating system default timing.
+    pub fn keepalive(mut self, value: bool) -> Self {
+        self.socket_options = self.socket_options.keepalive(value);
+        self
+    }
+
+    /// Enables TCP keepalive packets on the connection with custom timing.
+    pub fn keepalive_settings(mut self, value: KeepaliveSettings) -> Self {
+        self.socket_options = self.socket_options.keepalive_settings(value);
+        self
+    }
+
+    /// If enabled, the connection uses an idle socket from the socket pool of the current async
+    /// worker if one is available and its socket is returned to the pool when the connection is
+    /// dropped, saving the cost of creating a new socket for each connection. Disabled by default.
+    ///
+    /// Sockets are returned to the pool via `DisconnectEx`, which may take a while to complete
+    /// (up to the TCP TIME_WAIT period if we close the connection first). Socket options set on a
+    /// previous connection remain in effect on the reused socket.
+    pub fn reuse_socket(mut self, value: bool) -> Self {
+        self.reuse_socket = value;
+        self
+    }
+
+    /// If enabled, TCP Fast Open (TFO) is used for the connection. Once the client has obtained a
+    /// TFO cookie from a server (on a previous connection to it), the data set via
+    /// `initial_data()` is sent together with the SYN of subsequent connections, saving one round
+    /// trip before the server sees the first request. Disabled by default.
+    ///
+    /// Without a cached cookie or if the server does not support TFO, the connection is established
+    /// normally and the initial data is sent right after the handshake.
+    ///
+    /// Data sent with the SYN may be delivered to the server more than once if the SYN is
+    /// retransmitted, so only use this with initial data that is safe to replay (e.g. idempotent
+    /// requests).
+    pub fn fast_open(mut self, value: bool) -> Self {
+        self.fast_open = value;
+        self
+    }
+
+    /// Data to send as part of establishing the connection, before the connection is returned to
+    /// the caller. With `fast_open()` enabled, this data may be sent together with the SYN.
+    pub fn initial_data(mut self, data: impl Into<Box<[u8]>>) -> Self {
+        self.initial_data = Some(data.into());
+        self
+    }
+
+    /// Binds the connection to the specified local address before connecting, instead of letting
+    /// the operating system pick one. Use this on multi-homed hosts to select the source IP (and
+    /// thereby the route) of the connection. A port of 0 lets the operating system pick the port.
+    ///
+    /// The address family must match that of the remote address. Connections bound to a local
+    /// address never use the socket pool (see `reuse_socket()`).
+    pub fn local_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Sends the outgoing traffic of the connection via the network interface with the specified
+    /// index (`IP_UNICAST_IF`/`IPV6_UNICAST_IF`), regardless of the routing table. Connections
+    /// bound to an interface never use the socket pool (see `reuse_socket()`).
+    pub fn interface_index(mut self, index: u32) -> Self {
+        self.interface_index = Some(index);
+        self
+    }
+
+    /// If enabled, the sends and receives of the connection use Registered I/O (RIO) instead of
+    /// overlapped I/O, which greatly reduces the per-operation overhead for connections exchanging
+    /// large numbers of small messages. Disabled by default.
+    ///
+    /// Data is copied between the buffers of the caller and 512 KiB of memory registered with the
+    /// operating system for each connection (see `io::RIO_SLOT_SIZE`). Receives cannot be
+    /// canceled - if the caller stops waiting for a receive (including on timeout or cancellation),
+    /// the data it receives is returned by the next receive. Registered I/O does not support urgent
+    /// data, so `send_urgent()` and `receive_urgent()` fail with `ErrorKind::Unsupported`.
+    /// Connections using Registered I/O never use the socket pool (see `reuse_socket()`).
+    pub fn registered_io(mut self, value: bool) -> Self {
+        self.registered_io = value;
+        self
+    }
+}
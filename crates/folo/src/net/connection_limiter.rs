@@ -0,0 +1,183 @@
+use crate::{
+    constants::POISONED_LOCK,
+    metrics::{Event, EventBuilder},
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc, Mutex,
+    },
+    task::{self, Waker},
+};
+
+/// Limits the number of live connections accepted by a TCP server or listener. A permit is
+/// acquired before accepting each connection (or right after, if excess connections are rejected
+/// instead of left waiting) and the permit travels with the connection to whichever worker handles
+/// it, being released when the connection is dropped.
+///
+/// Any number of tasks may be waiting for a permit (e.g. concurrent `TcpListener::accept()` calls).
+/// All of them are woken up when a permit is released and compete for it.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimiter {
+    max: usize,
+    active: AtomicUsize,
+    waiting: Mutex<Vec<Waker>>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            active: AtomicUsize::new(0),
+            waiting: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Waits until the number of live connections is below the limit and takes a permit for one
+    /// more connection.
+    pub fn acquire(self: &Arc<Self>) -> impl Future<Output = ConnectionPermit> {
+        Acquire {
+            limiter: Arc::clone(self),
+            paused: false,
+        }
+    }
+
+    /// Takes a permit for one more connection if the number of live connections is below the
+    /// limit, without waiting.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        let _waiting = self.waiting.lock().expect(POISONED_LOCK);
+        self.take_permit()
+    }
+
+    // Must be called under the `waiting` lock, which serializes all the permit takers.
+    fn take_permit(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        if self.active.load(atomic::Ordering::Acquire) >= self.max {
+            return None;
+        }
+
+        self.active.fetch_add(1, atomic::Ordering::AcqRel);
+
+        Some(ConnectionPermit {
+            limiter: Arc::clone(self),
+        })
+    }
+
+    fn release(&self) {
+        self.active.fetch_sub(1, atomic::Ordering::AcqRel);
+
+        let waiting = std::mem::take(&mut *self.waiting.lock().expect(POISONED_LOCK));
+
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+}
+
+struct Acquire {
+    limiter: Arc<ConnectionLimiter>,
+
+    // Whether we have already reported the pause in accepting connections.
+    paused: bool,
+}
+
+impl Future for Acquire {
+    type Output = ConnectionPermit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let limiter = Arc::clone(&self.limiter);
+
+        // We register the waker under the same lock that releases take it, so a release either
+        // happens before our check (and we see the free slot) or after it (and wakes us up).
+        let mut waiting = limiter.waiting.lock().expect(POISONED_LOCK);
+
+        if let Some(permit) = limiter.take_permit() {
+            return task::Poll::Ready(permit);
+        }
+
+        if !self.paused {
+            self.paused = true;
+            ACCEPT_PAUSED.with(Event::observe_unit);
+        }
+
+        if !waiting.iter().any(|w| w.will_wake(cx.waker())) {
+            waiting.push(cx.waker().clone());
+        }
+
+        task::Poll::Pending
+    }
+}
+
+/// Counts as one live connection until dropped.
+#[derive(Debug)]
+pub(crate) struct ConnectionPermit {
+    limiter: Arc<ConnectionLimiter>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+thread_local! {
+    static ACCEPT_PAUSED: Event = EventBuilder::new()
+        .name("net_tcp_accept_paused_at_connection_limit")
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{task::noop_waker_ref, FutureExt};
+
+    #[test]
+    fn acquire_waits_for_release() {
+        let limiter = ConnectionLimiter::new(2);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let first = limiter.acquire().now_or_never().unwrap();
+        let _second = limiter.acquire().now_or_never().unwrap();
+
+        let mut third = Box::pin(limiter.acquire());
+        assert!(third.poll_unpin(&mut cx).is_pending());
+
+        drop(first);
+
+        assert!(third.poll_unpin(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn try_acquire_respects_limit() {
+        let limiter = ConnectionLimiter::new(1);
+
+        let first = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+
+        drop(first);
+
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn release_wakes_all_waiting() {
+        let limiter = ConnectionLimiter::new(1);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let permit = limiter.try_acquire().unwrap();
+
+        let mut second = Box::pin(limiter.acquire());
+        let mut third = Box::pin(limiter.acquire());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+        assert!(third.poll_unpin(&mut cx).is_pending());
+
+        drop(permit);
+
+        // Only one of them gets the permit but both must have been woken up to compete for it.
+        assert!(limiter.waiting.lock().unwrap().is_empty());
+        assert!(second.poll_unpin(&mut cx).is_ready());
+        assert!(third.poll_unpin(&mut cx).is_pending());
+    }
+}
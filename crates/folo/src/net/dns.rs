@@ -0,0 +1,9 @@
+//! A stub DNS resolver that queries explicitly configured DNS servers from the async worker itself,
+//! as an alternative to `net::resolve()` (which uses the name resolution services of the operating
+//! system).
+
+mod cache;
+mod message;
+mod resolver;
+
+pub use resolver::*;
@@ -0,0 +1,163 @@
+use super::message::RecordType;
+use std::{collections::HashMap, net::IpAddr, time::Instant};
+
+/// Remembers the addresses of recently resolved names until their TTL expires.
+#[derive(Debug)]
+pub(super) struct Cache {
+    capacity: usize,
+
+    // Keyed by the normalized name (lowercase, without the trailing dot).
+    entries: HashMap<(String, RecordType), Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    addrs: Vec<IpAddr>,
+    expires: Instant,
+}
+
+impl Cache {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub(super) fn get(
+        &mut self,
+        name: &str,
+        record_type: RecordType,
+        now: Instant,
+    ) -> Option<Vec<IpAddr>> {
+        let key = (normalize(name), record_type);
+
+        let entry = self.entries.get(&key)?;
+
+        if entry.expires <= now {
+            self.entries.remove(&key);
+            return None;
+        }
+
+        Some(entry.addrs.clone())
+    }
+
+    pub(super) fn insert(
+        &mut self,
+        name: &str,
+        record_type: RecordType,
+        addrs: Vec<IpAddr>,
+        expires: Instant,
+        now: Instant,
+    ) {
+        if self.capacity == 0 || expires <= now {
+            return;
+        }
+
+        let key = (normalize(name), record_type);
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, entry| entry.expires > now);
+
+            // Still full of live entries, so we make room by dropping the one expiring soonest.
+            if self.entries.len() >= self.capacity {
+                let soonest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires)
+                    .map(|(key, _)| key.clone())
+                    .expect("cache is full, so it has entries");
+
+                self.entries.remove(&soonest);
+            }
+        }
+
+        self.entries.insert(key, Entry { addrs, expires });
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::Ipv4Addr, time::Duration};
+
+    fn addrs(last_octet: u8) -> Vec<IpAddr> {
+        vec![Ipv4Addr::new(192, 0, 2, last_octet).into()]
+    }
+
+    #[test]
+    fn returns_entries_until_they_expire() {
+        let now = Instant::now();
+        let mut cache = Cache::new(10);
+
+        cache.insert(
+            "Example.com.",
+            RecordType::A,
+            addrs(1),
+            now + Duration::from_secs(60),
+            now,
+        );
+
+        assert_eq!(cache.get("example.com", RecordType::A, now), Some(addrs(1)));
+        assert_eq!(cache.get("example.com", RecordType::Aaaa, now), None);
+        assert_eq!(
+            cache.get("example.com", RecordType::A, now + Duration::from_secs(60)),
+            None
+        );
+    }
+
+    #[test]
+    fn evicts_entry_expiring_soonest_when_full() {
+        let now = Instant::now();
+        let mut cache = Cache::new(2);
+
+        cache.insert(
+            "a",
+            RecordType::A,
+            addrs(1),
+            now + Duration::from_secs(30),
+            now,
+        );
+        cache.insert(
+            "b",
+            RecordType::A,
+            addrs(2),
+            now + Duration::from_secs(10),
+            now,
+        );
+        cache.insert(
+            "c",
+            RecordType::A,
+            addrs(3),
+            now + Duration::from_secs(20),
+            now,
+        );
+
+        assert_eq!(cache.get("a", RecordType::A, now), Some(addrs(1)));
+        assert_eq!(cache.get("b", RecordType::A, now), None);
+        assert_eq!(cache.get("c", RecordType::A, now), Some(addrs(3)));
+    }
+
+    #[test]
+    fn zero_capacity_disables_caching() {
+        let now = Instant::now();
+        let mut cache = Cache::new(0);
+
+        cache.insert(
+            "a",
+            RecordType::A,
+            addrs(1),
+            now + Duration::from_secs(30),
+            now,
+        );
+        assert_eq!(cache.get("a", RecordType::A, now), None);
+    }
+}
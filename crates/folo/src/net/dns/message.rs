@@ -0,0 +1,371 @@
+use crate::io;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+/// The types of records the resolver queries for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(super) enum RecordType {
+    A,
+    Aaaa,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Aaaa => 28,
+        }
+    }
+}
+
+pub(super) const RCODE_NO_ERROR: u8 = 0;
+pub(super) const RCODE_NAME_ERROR: u8 = 3;
+
+/// The largest UDP response we advertise via EDNS(0), as recommended by DNS Flag Day 2020 to avoid
+/// IP fragmentation. Larger responses are truncated by the server and retried over TCP.
+pub(super) const MAX_UDP_RESPONSE_LEN: u16 = 1232;
+
+const HEADER_LEN: usize = 12;
+
+const FLAG_RESPONSE: u8 = 0x80;
+const FLAG_TRUNCATED: u8 = 0x02;
+const FLAG_RECURSION_DESIRED: u8 = 0x01;
+
+const CLASS_IN: u16 = 1;
+const TYPE_OPT: u16 = 41;
+
+// Root name, type, class (the UDP payload size), TTL (extended flags) and an empty RDATA.
+const OPT_RECORD_LEN: usize = 1 + 2 + 2 + 4 + 2;
+
+const MAX_NAME_LEN: usize = 255;
+const MAX_LABEL_LEN: usize = 63;
+
+/// The parts of a DNS response that the resolver cares about.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(super) struct Response {
+    /// The response did not fit into a UDP datagram, so it must be retried over TCP.
+    pub(super) truncated: bool,
+
+    pub(super) rcode: u8,
+
+    /// The addresses in the answer section of the type that was queried for.
+    pub(super) addrs: Vec<IpAddr>,
+
+    /// The lowest TTL among the returned addresses, if any.
+    pub(super) ttl: Option<Duration>,
+}
+
+/// Encodes a recursive query for records of the specified type, advertising EDNS(0) support.
+pub(super) fn encode_query(id: u16, name: &str, record_type: RecordType) -> io::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(HEADER_LEN + name.len() + 2 + 4 + OPT_RECORD_LEN);
+
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&[FLAG_RECURSION_DESIRED, 0]);
+
+    // One question, no answers, no authorities, one additional record (the OPT record).
+    for count in [1u16, 0, 0, 1] {
+        query.extend_from_slice(&count.to_be_bytes());
+    }
+
+    encode_name(name, &mut query)?;
+    query.extend_from_slice(&record_type.code().to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    query.push(0);
+    query.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    query.extend_from_slice(&MAX_UDP_RESPONSE_LEN.to_be_bytes());
+    query.extend_from_slice(&[0; 4]);
+    query.extend_from_slice(&[0; 2]);
+
+    Ok(query)
+}
+
+fn encode_name(name: &str, out: &mut Vec<u8>) -> io::Result<()> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+
+    if name.is_empty() {
+        return Err(invalid_name("host name must not be empty"));
+    }
+
+    let start = out.len();
+
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return Err(invalid_name(
+                "each label of the host name must be 1 to 63 bytes long",
+            ));
+        }
+
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+
+    out.push(0);
+
+    if out.len() - start > MAX_NAME_LEN {
+        return Err(invalid_name("host name must be at most 255 bytes long"));
+    }
+
+    Ok(())
+}
+
+/// Whether the data is a response to the query, as opposed to a stale or spoofed datagram. The
+/// question must match, except for the case of the name, which some servers randomize.
+pub(super) fn is_response_to(query: &[u8], data: &[u8]) -> bool {
+    let question = &query[HEADER_LEN..query.len() - OPT_RECORD_LEN];
+
+    data.len() >= HEADER_LEN + question.len()
+        && data[..2] == query[..2]
+        && data[2] & FLAG_RESPONSE != 0
+        && read_u16(data, 4) == Some(1)
+        && data[HEADER_LEN..HEADER_LEN + question.len()].eq_ignore_ascii_case(question)
+}
+
+/// Parses a response that has been verified to belong to the query of the specified type.
+pub(super) fn parse_response(data: &[u8], record_type: RecordType) -> io::Result<Response> {
+    let mut response = Response {
+        truncated: data[2] & FLAG_TRUNCATED != 0,
+        rcode: data[3] & 0x0F,
+        ..Default::default()
+    };
+
+    // The answers of a truncated response may be incomplete, so we do not look at them.
+    if response.truncated {
+        return Ok(response);
+    }
+
+    let question_count = read_u16(data, 4).ok_or_else(malformed)?;
+    let answer_count = read_u16(data, 6).ok_or_else(malformed)?;
+
+    let mut offset = HEADER_LEN;
+
+    for _ in 0..question_count {
+        offset = skip_name(data, offset)? + 4;
+    }
+
+    for _ in 0..answer_count {
+        offset = skip_name(data, offset)?;
+
+        let kind = read_u16(data, offset).ok_or_else(malformed)?;
+        let class = read_u16(data, offset + 2).ok_or_else(malformed)?;
+        let ttl = read_u32(data, offset + 4).ok_or_else(malformed)?;
+        let len = read_u16(data, offset + 8).ok_or_else(malformed)? as usize;
+
+        let rdata = data
+            .get(offset + 10..offset + 10 + len)
+            .ok_or_else(malformed)?;
+        offset += 10 + len;
+
+        // Any CNAME records leading to the addresses are skipped - the server has already
+        // followed them for us.
+        if class != CLASS_IN || kind != record_type.code() {
+            continue;
+        }
+
+        let addr = match record_type {
+            RecordType::A => IpAddr::from(<[u8; 4]>::try_from(rdata).map_err(|_| malformed())?),
+            RecordType::Aaaa => IpAddr::from(<[u8; 16]>::try_from(rdata).map_err(|_| malformed())?),
+        };
+
+        if !response.addrs.contains(&addr) {
+            response.addrs.push(addr);
+        }
+
+        let ttl = Duration::from_secs(ttl.into());
+        response.ttl = Some(response.ttl.map_or(ttl, |existing| existing.min(ttl)));
+    }
+
+    Ok(response)
+}
+
+/// Returns the offset just past the (possibly compressed) name at the specified offset.
+fn skip_name(data: &[u8], mut offset: usize) -> io::Result<usize> {
+    loop {
+        let len = *data.get(offset).ok_or_else(malformed)?;
+
+        match len & 0xC0 {
+            // A compression pointer ends the name.
+            0xC0 => return Ok(offset + 2),
+            0x00 if len == 0 => return Ok(offset + 1),
+            0x00 => offset += 1 + len as usize,
+            _ => return Err(malformed()),
+        }
+    }
+}
+
+/// Parses an IP address literal, which the resolver returns as-is instead of querying for it.
+pub(super) fn parse_literal(host: &str) -> Option<IpAddr> {
+    // IPv6 literals may come in brackets, as in URLs.
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'));
+
+    match unbracketed {
+        Some(host) => host.parse::<Ipv6Addr>().ok().map(IpAddr::from),
+        None => host
+            .parse::<Ipv4Addr>()
+            .map(IpAddr::from)
+            .or_else(|_| host.parse::<Ipv6Addr>().map(IpAddr::from))
+            .ok(),
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn invalid_name(message: &str) -> io::Error {
+    io::Error::InvalidOptions(message.to_string())
+}
+
+fn malformed() -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "malformed DNS response",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_to(query: &[u8], flags: [u8; 2], answers: &[(u16, u32, &[u8])]) -> Vec<u8> {
+        let question = &query[HEADER_LEN..query.len() - OPT_RECORD_LEN];
+
+        let mut data = query[..2].to_vec();
+        data.extend_from_slice(&flags);
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(question);
+
+        for (kind, ttl, rdata) in answers {
+            // A compression pointer to the name in the question.
+            data.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+            data.extend_from_slice(&kind.to_be_bytes());
+            data.extend_from_slice(&CLASS_IN.to_be_bytes());
+            data.extend_from_slice(&ttl.to_be_bytes());
+            data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            data.extend_from_slice(rdata);
+        }
+
+        data
+    }
+
+    #[test]
+    fn encodes_query_with_opt_record() {
+        let query = encode_query(0x1234, "example.com.", RecordType::Aaaa).unwrap();
+
+        assert_eq!(&query[..12], [0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(&query[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&query[25..29], [0, 28, 0, 1]);
+        assert_eq!(&query[29..], [0, 0, 41, 0x04, 0xD0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert!(encode_query(1, "", RecordType::A).is_err());
+        assert!(encode_query(1, ".", RecordType::A).is_err());
+        assert!(encode_query(1, "a..b", RecordType::A).is_err());
+        assert!(encode_query(1, &"a".repeat(64), RecordType::A).is_err());
+        assert!(encode_query(1, &["a"; 128].join("."), RecordType::A).is_err());
+    }
+
+    #[test]
+    fn parses_addresses_and_lowest_ttl() {
+        let query = encode_query(7, "example.com", RecordType::A).unwrap();
+        let data = response_to(
+            &query,
+            [0x81, 0x80],
+            &[
+                (5, 60, b"\x03www\x00"),
+                (1, 300, &[192, 0, 2, 1]),
+                (1, 120, &[192, 0, 2, 2]),
+                (28, 10, &[0; 16]),
+            ],
+        );
+
+        assert!(is_response_to(&query, &data));
+
+        let response = parse_response(&data, RecordType::A).unwrap();
+        assert_eq!(
+            response,
+            Response {
+                truncated: false,
+                rcode: RCODE_NO_ERROR,
+                addrs: vec![
+                    Ipv4Addr::new(192, 0, 2, 1).into(),
+                    Ipv4Addr::new(192, 0, 2, 2).into()
+                ],
+                ttl: Some(Duration::from_secs(120)),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_truncation_and_errors() {
+        let query = encode_query(7, "example.com", RecordType::A).unwrap();
+
+        let truncated = response_to(&query, [0x83, 0x80], &[]);
+        assert!(parse_response(&truncated, RecordType::A).unwrap().truncated);
+
+        let not_found = response_to(&query, [0x81, 0x83], &[]);
+        let response = parse_response(&not_found, RecordType::A).unwrap();
+        assert_eq!(response.rcode, RCODE_NAME_ERROR);
+        assert!(response.addrs.is_empty());
+    }
+
+    #[test]
+    fn recognizes_unrelated_responses() {
+        let query = encode_query(7, "example.com", RecordType::A).unwrap();
+
+        let mut other_id = response_to(&query, [0x81, 0x80], &[]);
+        other_id[1] = 8;
+        assert!(!is_response_to(&query, &other_id));
+
+        let other_type = encode_query(7, "example.com", RecordType::Aaaa).unwrap();
+        assert!(!is_response_to(
+            &query,
+            &response_to(&other_type, [0x81, 0x80], &[])
+        ));
+
+        // A query is not a response.
+        assert!(!is_response_to(&query, &query));
+
+        // Servers may echo the name with a different case.
+        let mut mixed_case = response_to(&query, [0x81, 0x80], &[]);
+        mixed_case[13] = b'E';
+        assert!(is_response_to(&query, &mixed_case));
+    }
+
+    #[test]
+    fn rejects_malformed_answers() {
+        let query = encode_query(7, "example.com", RecordType::A).unwrap();
+
+        let mut data = response_to(&query, [0x81, 0x80], &[(1, 300, &[192, 0, 2, 1])]);
+        data.truncate(data.len() - 2);
+        assert!(parse_response(&data, RecordType::A).is_err());
+
+        let data = response_to(&query, [0x81, 0x80], &[(1, 300, &[192, 0, 2])]);
+        assert!(parse_response(&data, RecordType::A).is_err());
+    }
+
+    #[test]
+    fn parses_literals() {
+        assert_eq!(parse_literal("127.0.0.1"), Some(Ipv4Addr::LOCALHOST.into()));
+        assert_eq!(parse_literal("::1"), Some(Ipv6Addr::LOCALHOST.into()));
+        assert_eq!(parse_literal("[::1]"), Some(Ipv6Addr::LOCALHOST.into()));
+        assert_eq!(parse_literal("example.com"), None);
+        assert_eq!(parse_literal("[127.0.0.1]"), None);
+    }
+}
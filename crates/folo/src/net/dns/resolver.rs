@@ -0,0 +1,406 @@
+use super::{
+    cache::Cache,
+    message::{self, RecordType, Response, RCODE_NAME_ERROR, RCODE_NO_ERROR},
+};
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    io::{self, BufferSize, OperationResultExt, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::{TcpConnection, UdpSocket},
+    rt::{self, sleep_until},
+};
+use futures::future::{self, Either};
+use negative_impl::negative_impl;
+use std::{
+    cell::{Cell, RefCell},
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+/// Options for creating a `Resolver`.
+#[derive(Clone, Debug)]
+pub struct ResolverOptions {
+    servers: Vec<SocketAddr>,
+    timeout: Duration,
+    attempts: u32,
+    cache_capacity: usize,
+    max_cache_ttl: Duration,
+}
+
+impl ResolverOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a DNS server (usually on port 53) to query. Servers are queried in the order they were
+    /// added, moving on to the next one if a server does not respond in time or fails to resolve
+    /// the name. At least one server is required.
+    pub fn server(mut self, addr: SocketAddr) -> Self {
+        self.servers.push(addr);
+        self
+    }
+
+    /// How long to wait for each server to respond to each query before moving on to the next
+    /// server. Defaults to 2 seconds.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = value;
+        self
+    }
+
+    /// How many times to go through the list of servers before giving up. Defaults to 2.
+    pub fn attempts(mut self, value: u32) -> Self {
+        self.attempts = value;
+        self
+    }
+
+    /// How many resolved names (per address family) to remember, or 0 to disable caching. Defaults
+    /// to 1024.
+    pub fn cache_capacity(mut self, value: usize) -> Self {
+        self.cache_capacity = value;
+        self
+    }
+
+    /// The longest time to remember a resolved name for, regardless of the TTL returned by the
+    /// server. Defaults to 1 hour.
+    pub fn max_cache_ttl(mut self, value: Duration) -> Self {
+        self.max_cache_ttl = value;
+        self
+    }
+}
+
+impl Default for ResolverOptions {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            timeout: Duration::from_secs(2),
+            attempts: 2,
+            cache_capacity: 1024,
+            max_cache_ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// A stub DNS resolver that sends queries directly to the configured DNS servers over UDP, falling
+/// back to TCP for responses too large for a datagram, and caches the results for their TTL.
+///
+/// Unlike `net::resolve()`, which goes through the name resolution services of the operating system
+/// (and their threads), all work happens on the current async worker and only the configured
+/// servers are used - the hosts file, the DNS client cache of the operating system and other name
+/// resolution mechanisms are not consulted. Each async worker should have its own resolver, as the
+/// type is bound to the thread that created it.
+///
+/// Each query is sent from a new socket with a port picked by the operating system and a random
+/// query ID, and only responses matching the query are accepted, to defend against spoofed
+/// responses. DNSSEC is not validated.
+pub struct Resolver {
+    options: ResolverOptions,
+    cache: RefCell<Cache>,
+
+    id_hasher: RandomState,
+    queries_started: Cell<u64>,
+}
+
+impl Resolver {
+    pub fn new(options: ResolverOptions) -> io::Result<Self> {
+        if options.servers.is_empty() {
+            return Err(io::Error::InvalidOptions(
+                "at least one DNS server is required".to_string(),
+            ));
+        }
+
+        if options.attempts == 0 {
+            return Err(io::Error::InvalidOptions(
+                "at least one attempt is required".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            cache: RefCell::new(Cache::new(options.cache_capacity)),
+            options,
+            id_hasher: RandomState::new(),
+            queries_started: Cell::new(0),
+        })
+    }
+
+    /// Resolves a host name to the addresses of the host, combined with the specified port. IP
+    /// address literals are returned as-is. IPv6 and IPv4 addresses are queried concurrently and
+    /// returned in that order.
+    ///
+    /// Fails with `std::io::ErrorKind::NotFound` if the name does not exist or has no addresses.
+    pub async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok(self
+            .lookup_ip(host)
+            .await?
+            .into_iter()
+            .map(|addr| SocketAddr::new(addr, port))
+            .collect())
+    }
+
+    /// Resolves a host name to the IP addresses of the host. See `resolve()`.
+    pub async fn lookup_ip(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        if let Some(addr) = message::parse_literal(host) {
+            return Ok(vec![addr]);
+        }
+
+        let (ipv6, ipv4) = future::join(
+            self.lookup(host, RecordType::Aaaa),
+            self.lookup(host, RecordType::A),
+        )
+        .await;
+
+        match (ipv6, ipv4) {
+            (Ok(mut ipv6), Ok(ipv4)) => {
+                ipv6.extend(ipv4);
+
+                if ipv6.is_empty() {
+                    return Err(not_found("host has no addresses"));
+                }
+
+                Ok(ipv6)
+            }
+            // One address family is enough - the other may be blocked or broken.
+            (Ok(addrs), Err(e)) | (Err(e), Ok(addrs)) => {
+                if addrs.is_empty() {
+                    return Err(e);
+                }
+
+                Ok(addrs)
+            }
+            (Err(_), Err(e)) => Err(e),
+        }
+    }
+
+    /// Forgets all the cached names.
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    async fn lookup(&self, name: &str, record_type: RecordType) -> io::Result<Vec<IpAddr>> {
+        if let Some(addrs) = self.cache.borrow_mut().get(name, record_type, rt::now()) {
+            CACHE_HITS.with(Event::observe_unit);
+            return Ok(addrs);
+        }
+
+        let started = rt::now();
+
+        let result = self.query(name, record_type).await;
+
+        match &result {
+            Ok(_) => QUERY_OK_DURATION.with(|x| x.observe_millis(rt::now() - started)),
+            Err(_) => QUERIES_FAILED.with(Event::observe_unit),
+        }
+
+        let response = result?;
+
+        if response.rcode == RCODE_NAME_ERROR {
+            return Err(not_found("host does not exist"));
+        }
+
+        if let Some(ttl) = response.ttl {
+            let now = rt::now();
+            let expires = now + ttl.min(self.options.max_cache_ttl);
+
+            self.cache
+                .borrow_mut()
+                .insert(name, record_type, response.addrs.clone(), expires, now);
+        }
+
+        Ok(response.addrs)
+    }
+
+    /// Queries the servers in order until one gives a definitive answer (the addresses or that the
+    /// name does not exist), returning the last error if none does.
+    async fn query(&self, name: &str, record_type: RecordType) -> io::Result<Response> {
+        let mut last_error = None;
+
+        for _ in 0..self.options.attempts {
+            for server in &self.options.servers {
+                let query = message::encode_query(self.next_query_id(), name, record_type)?;
+                let deadline = rt::now() + self.options.timeout;
+
+                match query_server(*server, &query, record_type, deadline).await {
+                    Ok(response)
+                        if response.rcode == RCODE_NO_ERROR
+                            || response.rcode == RCODE_NAME_ERROR =>
+                    {
+                        return Ok(response);
+                    }
+                    Ok(response) => {
+                        last_error = Some(io::Error::StdIo(std::io::Error::other(format!(
+                            "DNS server {server} failed to resolve the name (response code {})",
+                            response.rcode
+                        ))));
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+        }
+
+        Err(last_error.expect("there is at least one server and one attempt"))
+    }
+
+    fn next_query_id(&self) -> u16 {
+        let count = self.queries_started.get();
+        self.queries_started.set(count.wrapping_add(1));
+
+        // Unpredictable to anyone who cannot see the queries, which is what matters for spoofing.
+        let mut hasher = self.id_hasher.build_hasher();
+        hasher.write_u64(count);
+        hasher.finish() as u16
+    }
+}
+
+#[negative_impl]
+impl !Send for Resolver {}
+#[negative_impl]
+impl !Sync for Resolver {}
+
+impl std::fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resolver")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+async fn query_server(
+    server: SocketAddr,
+    query: &[u8],
+    record_type: RecordType,
+    deadline: Instant,
+) -> io::Result<Response> {
+    let response = before_deadline(query_udp(server, query, record_type), deadline).await?;
+
+    if !response.truncated {
+        return Ok(response);
+    }
+
+    TCP_FALLBACKS.with(Event::observe_unit);
+
+    before_deadline(query_tcp(server, query, record_type), deadline).await
+}
+
+async fn before_deadline<T>(
+    operation: impl Future<Output = io::Result<T>>,
+    deadline: Instant,
+) -> io::Result<T> {
+    match future::select(pin!(operation), pin!(sleep_until(deadline))).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(io::Error::TimedOut),
+    }
+}
+
+async fn query_udp(
+    server: SocketAddr,
+    query: &[u8],
+    record_type: RecordType,
+) -> io::Result<Response> {
+    let local_addr = match server {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+
+    // Connecting makes the operating system discard datagrams from anyone but the server and
+    // report an unreachable server as an error instead of making us wait for the timeout.
+    let mut socket = UdpSocket::bind(local_addr)?;
+    socket.connect(server)?;
+
+    let mut buffer = PinnedBuffer::from_pool_with_size(BufferSize::Small);
+    buffer
+        .as_mut_slice_with_len(query.len())
+        .copy_from_slice(query);
+    socket.send(buffer).await.into_inner()?;
+
+    loop {
+        let buffer = socket
+            .receive(PinnedBuffer::from_pool_with_size(BufferSize::Small))
+            .await
+            .into_inner()?;
+
+        // Anything else is a late response to an earlier query or an attempt at spoofing.
+        if message::is_response_to(query, buffer.as_slice()) {
+            return message::parse_response(buffer.as_slice(), record_type);
+        }
+    }
+}
+
+async fn query_tcp(
+    server: SocketAddr,
+    query: &[u8],
+    record_type: RecordType,
+) -> io::Result<Response> {
+    let mut connection = TcpConnection::connect(server).await?;
+
+    // Over TCP, each message is preceded by its length.
+    let mut buffer = PinnedBuffer::from_pool_with_size(BufferSize::Small);
+    let data = buffer.as_mut_slice_with_len(2 + query.len());
+    data[..2].copy_from_slice(&(query.len() as u16).to_be_bytes());
+    data[2..].copy_from_slice(query);
+    connection.send(buffer).await.into_inner()?;
+
+    let mut received = Vec::new();
+
+    loop {
+        if received.len() >= 2 {
+            let len = u16::from_be_bytes([received[0], received[1]]) as usize;
+
+            if received.len() >= 2 + len {
+                let response = &received[2..2 + len];
+
+                if !message::is_response_to(query, response) {
+                    return Err(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "DNS server responded to a different query",
+                    )));
+                }
+
+                return message::parse_response(response, record_type);
+            }
+        }
+
+        let buffer = connection
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()?;
+
+        if buffer.len() == 0 {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "DNS server closed the connection before responding",
+            )));
+        }
+
+        received.extend_from_slice(buffer.as_slice());
+    }
+}
+
+fn not_found(message: &'static str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(std::io::ErrorKind::NotFound, message))
+}
+
+thread_local! {
+    static QUERY_OK_DURATION: Event = EventBuilder::new()
+        .name("net_dns_query_ok_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static QUERIES_FAILED: Event = EventBuilder::new()
+        .name("net_dns_queries_failed")
+        .build()
+        .unwrap();
+
+    static CACHE_HITS: Event = EventBuilder::new()
+        .name("net_dns_cache_hits")
+        .build()
+        .unwrap();
+
+    static TCP_FALLBACKS: Event = EventBuilder::new()
+        .name("net_dns_tcp_fallbacks")
+        .build()
+        .unwrap();
+}
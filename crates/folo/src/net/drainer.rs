@@ -0,0 +1,246 @@
+use crate::{
+    constants::POISONED_LOCK,
+    metrics::{Event, EventBuilder},
+    net::TcpConnection,
+    rt::sleep,
+    sync::CancellationToken,
+};
+use futures::future::{self, Either};
+use negative_impl::negative_impl;
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::{pin, Pin},
+    sync::{Arc, Mutex},
+    task::{self, Waker},
+    time::Duration,
+};
+use windows::Win32::{
+    Foundation::HANDLE,
+    Networking::WinSock::{shutdown, SD_BOTH, SOCKET},
+    System::IO::CancelIoEx,
+};
+
+/// Tracks the live connections of a server so they can be drained on shutdown (e.g. during a
+/// deploy): first asking them to finish on their own, then forcibly cancelling the I/O of any that
+/// did not finish in time.
+///
+/// Connections are registered via `track()`. Connection handlers watch the shutdown signal (via
+/// `is_draining()` or `shutdown_token()`) and wrap up their work once it is raised, for example by
+/// finishing the current request and then closing the connection.
+///
+/// The drainer is thread-safe and cheap to clone, so the same drainer can track connections
+/// handled by any number of async workers.
+#[derive(Clone, Debug, Default)]
+pub struct Drainer {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    shutdown: CancellationToken,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+
+    // The sockets of the tracked connections, so we can cancel their I/O. A socket stays open for
+    // as long as it is in here because the connection unregisters itself before it is closed.
+    sockets: HashMap<u64, SOCKET>,
+
+    // Tasks awaiting the last tracked connection to finish. Drained when that happens.
+    awaiting_idle: Vec<Waker>,
+}
+
+impl Drainer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking a connection. The connection counts as live until the returned wrapper is
+    /// dropped (or `into_inner()` is called on it).
+    ///
+    /// Connections may still be tracked after draining has started, in which case their handlers
+    /// will observe the shutdown signal immediately.
+    pub fn track(&self, connection: TcpConnection) -> TrackedConnection {
+        TrackedConnection {
+            registration: self.register(connection.raw_socket()),
+            connection,
+        }
+    }
+
+    /// Registers a socket as a live connection until the returned registration is dropped, which
+    /// must happen before the socket is closed.
+    pub(crate) fn register(&self, socket: SOCKET) -> Registration {
+        let mut state = self.inner.state.lock().expect(POISONED_LOCK);
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.sockets.insert(id, socket);
+
+        Registration {
+            inner: Arc::clone(&self.inner),
+            id,
+        }
+    }
+
+    /// Number of tracked connections that are still live.
+    pub fn active_connections(&self) -> usize {
+        self.inner.state.lock().expect(POISONED_LOCK).sockets.len()
+    }
+
+    /// Whether draining has started and connections should wrap up their work.
+    pub fn is_draining(&self) -> bool {
+        self.inner.shutdown.is_cancelled()
+    }
+
+    /// A token that is cancelled once draining starts. Connection handlers can await
+    /// `CancellationToken::cancelled()` on it or pass it to any operation that accepts one.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.inner.shutdown.clone()
+    }
+
+    /// Raises the shutdown signal and waits for all tracked connections to finish. Any connection
+    /// still live after `timeout` has its I/O forcibly cancelled and both directions shut down, so
+    /// its pending and future operations fail promptly, after which we wait for its handler to
+    /// drop it.
+    ///
+    /// Returns the number of connections whose I/O had to be forcibly cancelled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        self.inner.shutdown.cancel();
+
+        if let Either::Left(_) = future::select(pin!(self.idle()), pin!(sleep(timeout))).await {
+            return 0;
+        }
+
+        let forced = self.force_cancel();
+        CONNECTIONS_FORCED.with(|x| x.observe(forced as i64));
+
+        self.idle().await;
+
+        forced
+    }
+
+    /// Returns a future that completes once there are no more live tracked connections.
+    pub fn idle(&self) -> impl Future<Output = ()> + '_ {
+        Idle { drainer: self }
+    }
+
+    fn force_cancel(&self) -> usize {
+        // We hold the lock while operating on the sockets, which keeps the connections from
+        // unregistering themselves and closing their sockets under our feet.
+        let state = self.inner.state.lock().expect(POISONED_LOCK);
+
+        for socket in state.sockets.values() {
+            // Either may fail if the connection is already closed for other reasons, which is
+            // just as good.
+            //
+            // SAFETY: The socket is open as long as it is registered, see above.
+            unsafe {
+                _ = shutdown(*socket, SD_BOTH);
+                _ = CancelIoEx(HANDLE(socket.0 as *mut _), None);
+            }
+        }
+
+        state.sockets.len()
+    }
+}
+
+struct Idle<'a> {
+    drainer: &'a Drainer,
+}
+
+impl Future for Idle<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // We register the waker under the same lock that unregistering connections take it, so the
+        // last connection either finishes before our check (and we see no connections) or after it
+        // (and wakes us up).
+        let mut state = self.drainer.inner.state.lock().expect(POISONED_LOCK);
+
+        if state.sockets.is_empty() {
+            return task::Poll::Ready(());
+        }
+
+        if !state.awaiting_idle.iter().any(|w| w.will_wake(cx.waker())) {
+            state.awaiting_idle.push(cx.waker().clone());
+        }
+
+        task::Poll::Pending
+    }
+}
+
+/// A connection tracked by a `Drainer`, counting as live until dropped. Dereferences to the
+/// connection itself.
+pub struct TrackedConnection {
+    // Dropped before the connection, so the socket is unregistered before it can be closed.
+    registration: Registration,
+    connection: TcpConnection,
+}
+
+impl TrackedConnection {
+    /// Stops tracking the connection and returns it.
+    pub fn into_inner(self) -> TcpConnection {
+        let Self {
+            registration,
+            connection,
+        } = self;
+
+        drop(registration);
+        connection
+    }
+}
+
+impl Deref for TrackedConnection {
+    type Target = TcpConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
+
+impl DerefMut for TrackedConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.connection
+    }
+}
+
+#[negative_impl]
+impl !Send for TrackedConnection {}
+#[negative_impl]
+impl !Sync for TrackedConnection {}
+
+#[derive(Debug)]
+pub(crate) struct Registration {
+    inner: Arc<Inner>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().expect(POISONED_LOCK);
+        state.sockets.remove(&self.id);
+
+        if state.sockets.is_empty() {
+            for waker in state.awaiting_idle.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+thread_local! {
+    static CONNECTIONS_FORCED: Event = EventBuilder::new()
+        .name("net_drain_connections_forced")
+        .buckets(&[0, 1, 10, 100, 1000])
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,201 @@
+use crate::{
+    io,
+    metrics::{Event, EventBuilder},
+    net::{resolve, ConnectOptions, TcpConnection},
+    rt::{sleep, sleep_until},
+};
+use futures::{
+    future::{self, Either},
+    stream::{FuturesUnordered, StreamExt},
+};
+use std::{collections::VecDeque, net::SocketAddr, pin::pin, time::Duration};
+
+/// How long to wait for a connection attempt before starting the next one in parallel
+/// (RFC 8305, section 5).
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Resolves the host name and establishes a TCP connection to it, using default options.
+///
+/// See `connect_with()`.
+pub async fn connect(host: &str, port: u16) -> io::Result<TcpConnection> {
+    connect_with(host, port, ConnectOptions::default()).await
+}
+
+/// Resolves the host name and establishes a TCP connection to it via the "Happy Eyeballs"
+/// algorithm (RFC 8305): the resolved IPv6 and IPv4 addresses are tried alternately, starting a
+/// new attempt whenever the previous one fails or has not succeeded within 250 milliseconds,
+/// without abandoning the attempts already in progress. The first connection to be established is
+/// returned and all other attempts are cancelled. This keeps a broken network path for one address
+/// family from delaying the connection by a full connect timeout.
+///
+/// The options apply to every attempt. The deadline and the cancellation token in the options
+/// cover the connection as a whole (including name resolution), not each attempt individually.
+///
+/// If every attempt fails, the error of the last one to fail is returned.
+///
+/// The connection is bound to the current async worker thread.
+pub async fn connect_with(
+    host: &str,
+    port: u16,
+    options: ConnectOptions,
+) -> io::Result<TcpConnection> {
+    let addrs = {
+        let resolve = pin!(resolve(host, port));
+
+        let deadline = pin!(async {
+            match options.deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => future::pending().await,
+            }
+        });
+
+        let cancelled = pin!(async {
+            match &options.cancellation_token {
+                Some(token) => token.cancelled().await,
+                None => future::pending().await,
+            }
+        });
+
+        match future::select(resolve, future::select(deadline, cancelled)).await {
+            Either::Left((result, _)) => result?,
+            Either::Right((Either::Left(_), _)) => return Err(io::Error::TimedOut),
+            Either::Right((Either::Right(_), _)) => return Err(io::Error::Canceled),
+        }
+    };
+
+    if addrs.is_empty() {
+        return Err(io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("host name {host} did not resolve to any addresses"),
+        )));
+    }
+
+    let mut remaining = interleave_families(addrs).into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut attempts_started = 0;
+    let mut last_error = None;
+
+    let mut start_attempt = |attempts: &mut FuturesUnordered<_>, addr: SocketAddr| {
+        attempts_started += 1;
+        attempts.push(TcpConnection::connect_with(addr, options.clone()));
+    };
+
+    // There is always at least one address, so we always start at least one attempt.
+    start_attempt(&mut attempts, remaining.next().expect("checked above"));
+
+    let result = loop {
+        let more_addrs = remaining.len() != 0;
+
+        let delay = pin!(async move {
+            if more_addrs {
+                sleep(CONNECTION_ATTEMPT_DELAY).await
+            } else {
+                future::pending().await
+            }
+        });
+
+        let step = match future::select(attempts.next(), delay).await {
+            Either::Left((result, _)) => Some(result),
+            Either::Right(_) => None,
+        };
+
+        match step {
+            Some(Some(Ok(connection))) => break Ok(connection),
+            Some(Some(Err(e))) => {
+                last_error = Some(e);
+
+                // A failed attempt lets the next one start right away instead of after the delay.
+                if let Some(addr) = remaining.next() {
+                    start_attempt(&mut attempts, addr);
+                }
+            }
+            Some(None) => {
+                // Every attempt has failed and there are no addresses left to try.
+                break Err(last_error
+                    .take()
+                    .expect("an attempt can only finish with a connection or an error"));
+            }
+            None => {
+                let addr = remaining
+                    .next()
+                    .expect("the delay only elapses if there are addresses left");
+
+                start_attempt(&mut attempts, addr);
+            }
+        }
+    };
+
+    ATTEMPTS.with(|x| x.observe(attempts_started));
+
+    // Any attempts still in progress are cancelled when `attempts` is dropped.
+    result
+}
+
+/// Reorders the addresses so the address families alternate, starting with the family of the first
+/// address. The operating system already sorts the addresses by preference (RFC 6724), which we
+/// otherwise preserve within each family (RFC 8305, section 4).
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+
+    let first_is_ipv6 = first.is_ipv6();
+
+    let (mut preferred, mut other): (VecDeque<_>, VecDeque<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut result = Vec::with_capacity(preferred.len() + other.len());
+
+    loop {
+        match (preferred.pop_front(), other.pop_front()) {
+            (None, None) => return result,
+            (a, b) => result.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+thread_local! {
+    static ATTEMPTS: Event = EventBuilder::new()
+        .name("net_happy_eyeballs_attempts")
+        .buckets(&[1, 2, 3, 4, 8])
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn v4(last: u8) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, last), 80))
+    }
+
+    fn v6(last: u16) -> SocketAddr {
+        SocketAddr::from((Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, last), 80))
+    }
+
+    #[test]
+    fn interleave_starts_with_first_family() {
+        assert_eq!(
+            interleave_families(vec![v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            vec![v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+
+        assert_eq!(
+            interleave_families(vec![v4(1), v4(2), v6(1)]),
+            vec![v4(1), v6(1), v4(2)]
+        );
+    }
+
+    #[test]
+    fn interleave_single_family_is_unchanged() {
+        assert_eq!(
+            interleave_families(vec![v4(3), v4(1), v4(2)]),
+            vec![v4(3), v4(1), v4(2)]
+        );
+
+        assert_eq!(interleave_families(Vec::new()), Vec::new());
+    }
+}
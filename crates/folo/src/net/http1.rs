@@ -0,0 +1,13 @@
+//! A minimal HTTP/1.1 client, for services that only need to call a handful of HTTP endpoints.
+//!
+//! Build a `Request`, send it to a server via `Client::send()` and get back the `Response` with the
+//! complete body. Connections are reused across requests via a `pool::ConnectionPool`.
+
+mod body;
+mod client;
+mod request;
+mod response;
+
+pub use client::*;
+pub use request::{Method, Request};
+pub use response::Response;
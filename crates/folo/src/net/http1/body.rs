@@ -0,0 +1,299 @@
+use crate::io;
+
+/// A chunk size line, including any chunk extensions, is not allowed to be longer than this.
+const MAX_CHUNK_LINE_LEN: usize = 4 * 1024;
+
+/// How the end of a response body is determined.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) enum BodyKind {
+    /// There is no body (e.g. the response to a HEAD request).
+    Empty,
+
+    /// The body has the length from the `Content-Length` header.
+    Length(u64),
+
+    /// The body consists of chunks, each preceded by its length.
+    Chunked,
+
+    /// The body ends when the server closes the connection.
+    UntilClose,
+}
+
+#[derive(Debug)]
+enum State {
+    Length { remaining: u64 },
+    ChunkSize,
+    ChunkData { remaining: u64 },
+    ChunkDataEnd,
+    Trailers,
+    UntilClose,
+    Done,
+}
+
+/// Decodes a response body from the bytes received after the response head.
+#[derive(Debug)]
+pub(super) struct BodyDecoder {
+    state: State,
+    body: Vec<u8>,
+    max_len: usize,
+}
+
+impl BodyDecoder {
+    pub(super) fn new(kind: BodyKind, max_len: usize) -> io::Result<Self> {
+        let state = match kind {
+            BodyKind::Empty | BodyKind::Length(0) => State::Done,
+            BodyKind::Length(len) if len > max_len as u64 => return Err(too_long(max_len)),
+            BodyKind::Length(len) => State::Length { remaining: len },
+            BodyKind::Chunked => State::ChunkSize,
+            BodyKind::UntilClose => State::UntilClose,
+        };
+
+        Ok(Self {
+            state,
+            body: Vec::new(),
+            max_len,
+        })
+    }
+
+    pub(super) fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Decodes as much of the body from `src` as possible, returning how many bytes were consumed.
+    /// Once the body is complete, any remaining bytes are left unconsumed.
+    pub(super) fn decode(&mut self, src: &[u8]) -> io::Result<usize> {
+        let mut consumed = 0;
+
+        loop {
+            let remaining_src = &src[consumed..];
+
+            match &mut self.state {
+                State::Done => return Ok(consumed),
+                State::Length { remaining } | State::ChunkData { remaining } => {
+                    if remaining_src.is_empty() {
+                        return Ok(consumed);
+                    }
+
+                    let len = remaining_src
+                        .len()
+                        .min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+                    *remaining -= len as u64;
+
+                    if *remaining == 0 {
+                        self.state = match self.state {
+                            State::Length { .. } => State::Done,
+                            _ => State::ChunkDataEnd,
+                        };
+                    }
+
+                    self.append(&remaining_src[..len])?;
+                    consumed += len;
+                }
+                State::UntilClose => {
+                    self.append(remaining_src)?;
+                    return Ok(src.len());
+                }
+                State::ChunkSize => {
+                    let Some(line) = take_line(remaining_src)? else {
+                        return Ok(consumed);
+                    };
+
+                    consumed += line.len() + 2;
+                    let size = parse_chunk_size(line)?;
+
+                    if self.body.len() as u64 + size > self.max_len as u64 {
+                        return Err(too_long(self.max_len));
+                    }
+
+                    self.state = match size {
+                        0 => State::Trailers,
+                        size => State::ChunkData { remaining: size },
+                    };
+                }
+                State::ChunkDataEnd => {
+                    if remaining_src.len() < 2 {
+                        return Ok(consumed);
+                    }
+
+                    if &remaining_src[..2] != b"\r\n" {
+                        return Err(invalid_body("chunk data is not followed by CRLF"));
+                    }
+
+                    consumed += 2;
+                    self.state = State::ChunkSize;
+                }
+                State::Trailers => {
+                    // We do not expose trailers, so we skip them up to the empty line that ends
+                    // the body.
+                    let Some(line) = take_line(remaining_src)? else {
+                        return Ok(consumed);
+                    };
+
+                    consumed += line.len() + 2;
+
+                    if line.is_empty() {
+                        self.state = State::Done;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Processes the end of the stream, which completes a body that ends when the connection is
+    /// closed and means that any other incomplete body was truncated.
+    pub(super) fn finish_at_eof(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Done => Ok(()),
+            State::UntilClose => {
+                self.state = State::Done;
+                Ok(())
+            }
+            _ => Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed before the end of the HTTP response body",
+            ))),
+        }
+    }
+
+    pub(super) fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    fn append(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.body.len() + data.len() > self.max_len {
+            return Err(too_long(self.max_len));
+        }
+
+        self.body.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Returns the line at the start of `src` without its CRLF, or `None` if the line is not complete.
+fn take_line(src: &[u8]) -> io::Result<Option<&[u8]>> {
+    match src.windows(2).position(|window| window == b"\r\n") {
+        Some(end) => Ok(Some(&src[..end])),
+        None if src.len() > MAX_CHUNK_LINE_LEN => Err(invalid_body("chunk line is too long")),
+        None => Ok(None),
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> io::Result<u64> {
+    // Chunk extensions follow the size after a semicolon. We do not understand any of them.
+    let size = line
+        .split(|byte| *byte == b';')
+        .next()
+        .unwrap_or_default()
+        .trim_ascii();
+
+    std::str::from_utf8(size)
+        .ok()
+        .filter(|size| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|size| u64::from_str_radix(size, 16).ok())
+        .ok_or_else(|| invalid_body("invalid chunk size"))
+}
+
+fn too_long(max_len: usize) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("HTTP response body exceeds the maximum of {max_len} bytes"),
+    ))
+}
+
+fn invalid_body(message: &str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid HTTP response body: {message}"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(kind: BodyKind, src: &[u8]) -> (Vec<u8>, usize) {
+        let mut decoder = BodyDecoder::new(kind, 1024).unwrap();
+        let consumed = decoder.decode(src).unwrap();
+        assert!(decoder.is_done());
+        (decoder.into_body(), consumed)
+    }
+
+    #[test]
+    fn content_length_body() {
+        let (body, consumed) = decode_all(BodyKind::Length(5), b"helloHTTP/1.1");
+        assert_eq!(body, b"hello");
+        assert_eq!(consumed, 5);
+
+        let (body, consumed) = decode_all(BodyKind::Empty, b"leftover");
+        assert!(body.is_empty());
+        assert_eq!(consumed, 0);
+    }
+
+    #[test]
+    fn chunked_body() {
+        let src = b"5\r\nhello\r\n7;name=value\r\n, world\r\n0\r\nTrailer: x\r\n\r\nnext";
+
+        let (body, consumed) = decode_all(BodyKind::Chunked, src);
+        assert_eq!(body, b"hello, world");
+        assert_eq!(consumed, src.len() - 4);
+    }
+
+    #[test]
+    fn chunked_body_arrives_byte_by_byte() {
+        let src = b"a\r\n0123456789\r\n0\r\n\r\n";
+
+        let mut decoder = BodyDecoder::new(BodyKind::Chunked, 1024).unwrap();
+        let mut received = Vec::new();
+
+        for byte in src {
+            received.push(*byte);
+            let consumed = decoder.decode(&received).unwrap();
+            received.drain(..consumed);
+        }
+
+        assert!(decoder.is_done());
+        assert!(received.is_empty());
+        assert_eq!(decoder.into_body(), b"0123456789");
+    }
+
+    #[test]
+    fn until_close_body() {
+        let mut decoder = BodyDecoder::new(BodyKind::UntilClose, 1024).unwrap();
+
+        assert_eq!(decoder.decode(b"all of ").unwrap(), 7);
+        assert_eq!(decoder.decode(b"it").unwrap(), 2);
+        assert!(!decoder.is_done());
+
+        decoder.finish_at_eof().unwrap();
+        assert!(decoder.is_done());
+        assert_eq!(decoder.into_body(), b"all of it");
+    }
+
+    #[test]
+    fn truncated_body_is_an_error() {
+        let mut decoder = BodyDecoder::new(BodyKind::Length(10), 1024).unwrap();
+        decoder.decode(b"short").unwrap();
+
+        assert!(decoder.finish_at_eof().is_err());
+    }
+
+    #[test]
+    fn too_long_body_is_rejected() {
+        assert!(BodyDecoder::new(BodyKind::Length(2048), 1024).is_err());
+
+        let mut decoder = BodyDecoder::new(BodyKind::Chunked, 1024).unwrap();
+        assert!(decoder.decode(b"401\r\n").is_err());
+
+        let mut decoder = BodyDecoder::new(BodyKind::UntilClose, 4).unwrap();
+        assert!(decoder.decode(b"hello").is_err());
+    }
+
+    #[test]
+    fn invalid_chunks_are_rejected() {
+        let mut decoder = BodyDecoder::new(BodyKind::Chunked, 1024).unwrap();
+        assert!(decoder.decode(b"xyz\r\n").is_err());
+
+        let mut decoder = BodyDecoder::new(BodyKind::Chunked, 1024).unwrap();
+        assert!(decoder.decode(b"2\r\nabXY").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use super::{
+    body::{BodyDecoder, BodyKind},
+    request::Request,
+    response::{Response, ResponseHead},
+};
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    io::{self, OperationResultExt, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::pool::{ConnectionPool, PooledConnection},
+    rt::{self, sleep_until},
+};
+use futures::future::{self, Either};
+use negative_impl::negative_impl;
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+/// The default maximum length of a response body, to protect against servers that send huge
+/// responses.
+pub const DEFAULT_MAX_RESPONSE_BODY_LEN: usize = 16 * 1024 * 1024;
+
+/// Options for creating a `Client`.
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    timeout: Duration,
+    max_response_body_len: usize,
+    connection_pool: Option<ConnectionPool>,
+}
+
+impl ClientOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long a request may take in total, from getting a connection to receiving the end of the
+    /// response body. Defaults to 30 seconds.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = value;
+        self
+    }
+
+    /// The maximum length of a response body. Receiving a longer body fails the request. Defaults
+    /// to `DEFAULT_MAX_RESPONSE_BODY_LEN`.
+    pub fn max_response_body_len(mut self, value: usize) -> Self {
+        self.max_response_body_len = value;
+        self
+    }
+
+    /// The pool to take connections from, e.g. to share connections with other clients or to
+    /// configure connection limits. Defaults to a new pool with the default configuration.
+    pub fn connection_pool(mut self, value: ConnectionPool) -> Self {
+        self.connection_pool = Some(value);
+        self
+    }
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_response_body_len: DEFAULT_MAX_RESPONSE_BODY_LEN,
+            connection_pool: None,
+        }
+    }
+}
+
+/// A minimal HTTP/1.1 client for calling HTTP endpoints, owned by the current async worker.
+///
+/// Connections are kept alive and reused for later requests to the same target via a
+/// `ConnectionPool`. If a reused connection turns out to have been closed by the server before
+/// any response arrives, idempotent requests are retried once on a new connection.
+///
+/// Only plain HTTP is supported - there is no TLS, proxy, redirect or compression support.
+#[derive(Debug)]
+pub struct Client {
+    timeout: Duration,
+    max_response_body_len: usize,
+    pool: ConnectionPool,
+}
+
+impl Client {
+    pub fn new(options: ClientOptions) -> Self {
+        Self {
+            timeout: options.timeout,
+            max_response_body_len: options.max_response_body_len,
+            pool: options.connection_pool.unwrap_or_default(),
+        }
+    }
+
+    /// Sends the request to the server at the specified address and receives the response.
+    ///
+    /// A response with an error status code (e.g. 404) is still a successful exchange and is
+    /// returned as `Ok`. Returns `io::Error::TimedOut` if the exchange does not complete within the
+    /// timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub async fn send(&self, addr: SocketAddr, request: Request) -> io::Result<Response> {
+        let started = rt::now();
+
+        let mut encoded = Vec::new();
+        request.encode(&addr.to_string(), &mut encoded)?;
+
+        let result = before_deadline(
+            self.send_encoded(addr, &request, &encoded),
+            started + self.timeout,
+        )
+        .await;
+
+        match &result {
+            Ok(_) => REQUEST_OK_DURATION.with(|x| x.observe_millis(rt::now() - started)),
+            Err(_) => REQUESTS_FAILED.with(Event::observe_unit),
+        }
+
+        result
+    }
+
+    async fn send_encoded(
+        &self,
+        addr: SocketAddr,
+        request: &Request,
+        encoded: &[u8],
+    ) -> io::Result<Response> {
+        let mut retried = false;
+
+        loop {
+            let connection = ConnectionGuard::new(self.pool.get(addr).await?);
+
+            match connection
+                .exchange(request, encoded, self.max_response_body_len)
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(ExchangeError {
+                    error,
+                    before_response,
+                }) => {
+                    if retried || !before_response || !request.method().is_idempotent() {
+                        return Err(error);
+                    }
+
+                    RETRIES.with(Event::observe_unit);
+                    retried = true;
+                }
+            }
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for Client {}
+#[negative_impl]
+impl !Sync for Client {}
+
+struct ExchangeError {
+    error: io::Error,
+
+    // Nothing was received from the server, so the server may not have processed the request.
+    before_response: bool,
+}
+
+/// Discards the connection unless the exchange on it completes in a way that leaves it ready for
+/// the next request, so a failed or canceled exchange never returns a connection to the pool with
+/// part of a response still in flight.
+struct ConnectionGuard {
+    connection: Option<PooledConnection>,
+    reusable: bool,
+}
+
+impl ConnectionGuard {
+    fn new(connection: PooledConnection) -> Self {
+        Self {
+            connection: Some(connection),
+            reusable: false,
+        }
+    }
+
+    fn connection(&mut self) -> &mut PooledConnection {
+        self.connection
+            .as_mut()
+            .expect("connection is only taken when dropping the guard")
+    }
+
+    async fn exchange(
+        mut self,
+        request: &Request,
+        encoded: &[u8],
+        max_body_len: usize,
+    ) -> Result<Response, ExchangeError> {
+        let before_response = |error| ExchangeError {
+            error,
+            before_response: true,
+        };
+        let after_response = |error| ExchangeError {
+            error,
+            before_response: false,
+        };
+
+        self.connection()
+            .send(PinnedBuffer::from_boxed_slice(encoded.into()))
+            .await
+            .into_inner()
+            .map_err(before_response)?;
+
+        let mut received = Vec::new();
+
+        // Informational responses (e.g. 100 Continue) precede the final response, so we skip them.
+        let head = loop {
+            if let Some((head, consumed)) =
+                ResponseHead::parse(&received).map_err(after_response)?
+            {
+                received.drain(..consumed);
+
+                if head.status() == 101 {
+                    return Err(after_response(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "server switched protocols, which the HTTP client does not support",
+                    ))));
+                }
+
+                if head.is_informational() {
+                    continue;
+                }
+
+                break head;
+            }
+
+            match self.receive_more(&mut received).await {
+                Ok(true) => {}
+                Ok(false) if received.is_empty() => {
+                    return Err(before_response(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "connection closed before an HTTP response was received",
+                    ))));
+                }
+                Ok(false) => {
+                    return Err(after_response(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "connection closed in the middle of the HTTP response head",
+                    ))));
+                }
+                Err(e) if received.is_empty() => return Err(before_response(e)),
+                Err(e) => return Err(after_response(e)),
+            }
+        };
+
+        let body_kind = head.body_kind(request.method()).map_err(after_response)?;
+        let mut body = BodyDecoder::new(body_kind, max_body_len).map_err(after_response)?;
+
+        loop {
+            let consumed = body.decode(&received).map_err(after_response)?;
+            received.drain(..consumed);
+
+            if body.is_done() {
+                break;
+            }
+
+            if !self
+                .receive_more(&mut received)
+                .await
+                .map_err(after_response)?
+            {
+                body.finish_at_eof().map_err(after_response)?;
+                break;
+            }
+        }
+
+        // Anything received after the response would be mistaken for the start of the next
+        // response, so such a connection is not reused.
+        self.reusable = head.keeps_alive()
+            && !request.closes_connection()
+            && body_kind != BodyKind::UntilClose
+            && received.is_empty();
+
+        Ok(head.into_response(body.into_body()))
+    }
+
+    /// Receives more data from the server, returning `false` if the server closed the connection.
+    async fn receive_more(&mut self, received: &mut Vec<u8>) -> io::Result<bool> {
+        let buffer = self
+            .connection()
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()?;
+
+        received.extend_from_slice(buffer.as_slice());
+        Ok(buffer.len() != 0)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if !self.reusable {
+                connection.discard();
+            }
+        }
+    }
+}
+
+async fn before_deadline<T>(
+    operation: impl Future<Output = io::Result<T>>,
+    deadline: Instant,
+) -> io::Result<T> {
+    match future::select(pin!(operation), pin!(sleep_until(deadline))).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(io::Error::TimedOut),
+    }
+}
+
+thread_local! {
+    static REQUEST_OK_DURATION: Event = EventBuilder::new()
+        .name("net_http1_request_ok_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static REQUESTS_FAILED: Event = EventBuilder::new()
+        .name("net_http1_requests_failed")
+        .build()
+        .unwrap();
+
+    static RETRIES: Event = EventBuilder::new()
+        .name("net_http1_retries")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,246 @@
+use crate::io;
+
+/// The method of an HTTP request.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Patch => "PATCH",
+            Self::Options => "OPTIONS",
+        }
+    }
+
+    /// Whether sending the request more than once has the same effect as sending it once, which
+    /// makes it safe to retry if the connection fails before a response arrives.
+    pub fn is_idempotent(self) -> bool {
+        !matches!(self, Self::Post | Self::Patch)
+    }
+}
+
+/// An HTTP request to send via `Client::send()`.
+///
+/// The `Host` header defaults to the address of the target and the `Content-Length` header is
+/// always set by the client from the body, so it must not be set manually. Bodies with chunked
+/// transfer encoding are not supported for requests.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Request {
+    method: Method,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Creates a request with the specified method for the specified target, which is the path
+    /// and query of the resource (e.g. `/api/items?page=2`).
+    pub fn new(method: Method, target: impl Into<String>) -> Self {
+        Self {
+            method,
+            target: target.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn get(target: impl Into<String>) -> Self {
+        Self::new(Method::Get, target)
+    }
+
+    pub fn post(target: impl Into<String>) -> Self {
+        Self::new(Method::Post, target)
+    }
+
+    /// Adds a header. Headers are sent in the order they were added and a header may be added
+    /// more than once.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, value: impl Into<Vec<u8>>) -> Self {
+        self.body = value.into();
+        self
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(existing, _)| existing.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the request asks the server to close the connection after responding.
+    pub(super) fn closes_connection(&self) -> bool {
+        self.headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("connection") && has_token(value, "close")
+        })
+    }
+
+    /// Appends the encoded request to `dst`, adding a `Host` header with the specified value if
+    /// the request does not have one.
+    pub(super) fn encode(&self, default_host: &str, dst: &mut Vec<u8>) -> io::Result<()> {
+        if self.target.is_empty() || self.target.bytes().any(|b| b.is_ascii_whitespace()) {
+            return Err(invalid_request(
+                "target must be non-empty and without whitespace",
+            ));
+        }
+
+        for (name, value) in &self.headers {
+            if name.is_empty() || !name.bytes().all(is_token_byte) {
+                return Err(invalid_request(&format!("invalid header name {name:?}")));
+            }
+
+            if value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0) {
+                return Err(invalid_request(&format!("invalid value for header {name}")));
+            }
+
+            if name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("transfer-encoding")
+            {
+                return Err(invalid_request(&format!(
+                    "header {name} is set by the client and must not be set manually"
+                )));
+            }
+        }
+
+        dst.extend_from_slice(self.method.as_str().as_bytes());
+        dst.push(b' ');
+        dst.extend_from_slice(self.target.as_bytes());
+        dst.extend_from_slice(b" HTTP/1.1\r\n");
+
+        if !self.has_header("host") {
+            write_header(dst, "Host", default_host);
+        }
+
+        for (name, value) in &self.headers {
+            write_header(dst, name, value);
+        }
+
+        // Methods that are expected to have a body get the header even if the body is empty, so
+        // the server does not wait for one.
+        if !self.body.is_empty()
+            || matches!(self.method, Method::Post | Method::Put | Method::Patch)
+        {
+            write_header(dst, "Content-Length", &self.body.len().to_string());
+        }
+
+        dst.extend_from_slice(b"\r\n");
+        dst.extend_from_slice(&self.body);
+
+        Ok(())
+    }
+}
+
+fn write_header(dst: &mut Vec<u8>, name: &str, value: &str) {
+    dst.extend_from_slice(name.as_bytes());
+    dst.extend_from_slice(b": ");
+    dst.extend_from_slice(value.as_bytes());
+    dst.extend_from_slice(b"\r\n");
+}
+
+/// Whether the byte may appear in a token (e.g. a header name), as defined by RFC 9110.
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Whether a comma-separated header value contains the token, ignoring case.
+pub(super) fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+fn invalid_request(message: &str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("invalid HTTP request: {message}"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(request: &Request) -> String {
+        let mut encoded = Vec::new();
+        request.encode("example.com:80", &mut encoded).unwrap();
+        String::from_utf8(encoded).unwrap()
+    }
+
+    #[test]
+    fn get_has_host_and_no_length() {
+        let request = Request::get("/items?page=2").header("Accept", "application/json");
+
+        assert_eq!(
+            encode(&request),
+            "GET /items?page=2 HTTP/1.1\r\nHost: example.com:80\r\nAccept: application/json\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn post_has_length_and_body() {
+        let request = Request::post("/items")
+            .header("host", "api.example.com")
+            .body("{}");
+
+        assert_eq!(
+            encode(&request),
+            "POST /items HTTP/1.1\r\nhost: api.example.com\r\nContent-Length: 2\r\n\r\n{}"
+        );
+
+        assert_eq!(
+            encode(&Request::post("/empty")),
+            "POST /empty HTTP/1.1\r\nHost: example.com:80\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let mut dst = Vec::new();
+
+        assert!(Request::get("/a b").encode("x", &mut dst).is_err());
+        assert!(Request::get("").encode("x", &mut dst).is_err());
+        assert!(Request::get("/")
+            .header("Bad Name", "x")
+            .encode("x", &mut dst)
+            .is_err());
+        assert!(Request::get("/")
+            .header("X-Injected", "a\r\nEvil: yes")
+            .encode("x", &mut dst)
+            .is_err());
+        assert!(Request::get("/")
+            .header("Content-Length", "5")
+            .encode("x", &mut dst)
+            .is_err());
+    }
+
+    #[test]
+    fn connection_close_is_detected() {
+        assert!(!Request::get("/").closes_connection());
+        assert!(Request::get("/")
+            .header("Connection", "Upgrade, Close")
+            .closes_connection());
+    }
+}
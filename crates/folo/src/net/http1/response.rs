@@ -0,0 +1,310 @@
+use super::{
+    body::BodyKind,
+    request::{has_token, Method},
+};
+use crate::io;
+
+/// The response head (status line and headers) is not allowed to be longer than this.
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+/// A response to an HTTP request, with the complete body.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Response {
+    head: ResponseHead,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// The status code (e.g. 200).
+    pub fn status(&self) -> u16 {
+        self.head.status
+    }
+
+    /// The reason phrase that accompanies the status code (e.g. "OK"), which carries no meaning
+    /// and may be empty.
+    pub fn reason(&self) -> &str {
+        &self.head.reason
+    }
+
+    /// Whether the status code indicates success (200-299).
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.head.status)
+    }
+
+    /// The headers of the response, in the order they were received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.head.headers
+    }
+
+    /// The value of the first header with the specified name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.header(name)
+    }
+
+    /// The body of the response, with any transfer encoding removed.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+/// The status line and headers of a response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct ResponseHead {
+    status: u16,
+    reason: String,
+    minor_version: u8,
+    headers: Vec<(String, String)>,
+}
+
+impl ResponseHead {
+    /// Parses the response head at the start of `src`. Returns the head and the number of bytes it
+    /// consumed, or `None` if `src` does not yet hold the complete head.
+    pub(super) fn parse(src: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let Some(end) = src.windows(4).position(|window| window == b"\r\n\r\n") else {
+            if src.len() > MAX_HEAD_LEN {
+                return Err(invalid_response("response head is too long"));
+            }
+
+            return Ok(None);
+        };
+
+        let head = String::from_utf8_lossy(&src[..end]);
+        let mut lines = head.split("\r\n");
+
+        let status_line = lines.next().unwrap_or_default();
+        let (minor_version, status, reason) = parse_status_line(status_line)?;
+
+        let mut headers = Vec::new();
+
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(invalid_response("header line without a colon"));
+            };
+
+            // Also rejects obsolete line folding, which starts the line with whitespace.
+            if name.is_empty() || name.bytes().any(|b| b.is_ascii_whitespace()) {
+                return Err(invalid_response("invalid header name"));
+            }
+
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+
+        Ok(Some((
+            Self {
+                status,
+                reason: reason.to_string(),
+                minor_version,
+                headers,
+            },
+            end + 4,
+        )))
+    }
+
+    pub(super) fn status(&self) -> u16 {
+        self.status
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.headers
+            .iter()
+            .filter(move |(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether this is an informational (1xx) response that precedes the final response.
+    pub(super) fn is_informational(&self) -> bool {
+        (100..200).contains(&self.status)
+    }
+
+    /// Determines how the end of the body is found, as specified by RFC 9112 section 6.3.
+    pub(super) fn body_kind(&self, method: Method) -> io::Result<BodyKind> {
+        if method == Method::Head || matches!(self.status, 100..=199 | 204 | 304) {
+            return Ok(BodyKind::Empty);
+        }
+
+        if let Some(last_encoding) = self
+            .header_values("transfer-encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|encoding| !encoding.is_empty())
+            .last()
+        {
+            return Ok(if last_encoding.eq_ignore_ascii_case("chunked") {
+                BodyKind::Chunked
+            } else {
+                BodyKind::UntilClose
+            });
+        }
+
+        let mut length = None;
+
+        for value in self
+            .header_values("content-length")
+            .flat_map(|value| value.split(','))
+        {
+            let value = value.trim();
+
+            let parsed = value
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| value.parse::<u64>().ok())
+                .flatten()
+                .ok_or_else(|| invalid_response("invalid Content-Length"))?;
+
+            if length.is_some_and(|length| length != parsed) {
+                return Err(invalid_response("conflicting Content-Length headers"));
+            }
+
+            length = Some(parsed);
+        }
+
+        Ok(length.map_or(BodyKind::UntilClose, BodyKind::Length))
+    }
+
+    /// Whether the server keeps the connection open for further requests after this response.
+    pub(super) fn keeps_alive(&self) -> bool {
+        let connection = self.header_values("connection").collect::<Vec<_>>();
+
+        if self.minor_version == 0 {
+            connection
+                .iter()
+                .any(|value| has_token(value, "keep-alive"))
+        } else {
+            !connection.iter().any(|value| has_token(value, "close"))
+        }
+    }
+
+    pub(super) fn into_response(self, body: Vec<u8>) -> Response {
+        Response { head: self, body }
+    }
+}
+
+fn parse_status_line(line: &str) -> io::Result<(u8, u16, &str)> {
+    let (version, rest) = line
+        .split_once(' ')
+        .ok_or_else(|| invalid_response("invalid status line"))?;
+
+    let minor_version = match version {
+        "HTTP/1.1" => 1,
+        "HTTP/1.0" => 0,
+        _ => return Err(invalid_response("unsupported HTTP version")),
+    };
+
+    let (status, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    let status = status
+        .parse::<u16>()
+        .ok()
+        .filter(|status| status.to_string().len() == 3)
+        .ok_or_else(|| invalid_response("invalid status code"))?;
+
+    Ok((minor_version, status, reason))
+}
+
+fn invalid_response(message: &str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("invalid HTTP response: {message}"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> ResponseHead {
+        let (head, consumed) = ResponseHead::parse(src.as_bytes()).unwrap().unwrap();
+        assert_eq!(consumed, src.find("\r\n\r\n").unwrap() + 4);
+        head
+    }
+
+    #[test]
+    fn parses_head() {
+        let head = parse("HTTP/1.1 404 Not Found\r\nContent-Length: 3\r\nX-A:  b \r\n\r\nabc");
+
+        assert_eq!(head.status(), 404);
+        assert_eq!(head.reason, "Not Found");
+        assert_eq!(head.header("x-a"), Some("b"));
+        assert_eq!(head.body_kind(Method::Get).unwrap(), BodyKind::Length(3));
+        assert_eq!(head.body_kind(Method::Head).unwrap(), BodyKind::Empty);
+        assert!(head.keeps_alive());
+
+        let response = head.into_response(b"abc".to_vec());
+        assert!(!response.is_success());
+        assert_eq!(response.header("CONTENT-LENGTH"), Some("3"));
+        assert_eq!(response.body(), b"abc");
+    }
+
+    #[test]
+    fn incomplete_head_waits_for_more() {
+        assert!(ResponseHead::parse(b"HTTP/1.1 200 OK\r\nA: b\r\n")
+            .unwrap()
+            .is_none());
+        assert!(ResponseHead::parse(&vec![b'a'; MAX_HEAD_LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn determines_body_kind() {
+        let chunked = parse("HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n");
+        assert_eq!(chunked.body_kind(Method::Get).unwrap(), BodyKind::Chunked);
+
+        // Transfer-Encoding takes precedence over Content-Length.
+        let chunked =
+            parse("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n");
+        assert_eq!(chunked.body_kind(Method::Get).unwrap(), BodyKind::Chunked);
+
+        let until_close = parse("HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(
+            until_close.body_kind(Method::Get).unwrap(),
+            BodyKind::UntilClose
+        );
+
+        let no_content = parse("HTTP/1.1 204 No Content\r\n\r\n");
+        assert_eq!(no_content.body_kind(Method::Get).unwrap(), BodyKind::Empty);
+
+        let conflicting =
+            parse("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n");
+        assert!(conflicting.body_kind(Method::Get).is_err());
+
+        let invalid = parse("HTTP/1.1 200 OK\r\nContent-Length: +5\r\n\r\n");
+        assert!(invalid.body_kind(Method::Get).is_err());
+    }
+
+    #[test]
+    fn determines_keep_alive() {
+        assert!(!parse("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n").keeps_alive());
+        assert!(!parse("HTTP/1.0 200 OK\r\n\r\n").keeps_alive());
+        assert!(parse("HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\n\r\n").keeps_alive());
+    }
+
+    #[test]
+    fn invalid_heads_are_rejected() {
+        for head in [
+            "HTTP/2 200 OK\r\n\r\n",
+            "HTTP/1.1 20 OK\r\n\r\n",
+            "HTTP/1.1\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nNo colon\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nA: b\r\n folded\r\n\r\n",
+        ] {
+            assert!(ResponseHead::parse(head.as_bytes()).is_err(), "{head}");
+        }
+    }
+
+    #[test]
+    fn status_without_reason() {
+        let head = parse("HTTP/1.1 200\r\n\r\n");
+        assert_eq!(head.status(), 200);
+        assert_eq!(head.reason, "");
+    }
+}
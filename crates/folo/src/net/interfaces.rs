@@ -0,0 +1,325 @@
+use crate::{
+    io::{self, IoWaker},
+    net::winsock,
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+};
+use futures::{channel::mpsc, Stream, StreamExt};
+use negative_impl::negative_impl;
+use std::{ffi::c_void, mem, net::IpAddr, pin::Pin, task};
+use windows::Win32::{
+    Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_NO_DATA, HANDLE, NO_ERROR, WIN32_ERROR},
+    NetworkManagement::{
+        IpHelper::{
+            CancelMibChangeNotify2, GetAdaptersAddresses, NotifyIpInterfaceChange,
+            NotifyUnicastIpAddressChange, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER,
+            GAA_FLAG_SKIP_MULTICAST, IF_TYPE_SOFTWARE_LOOPBACK, IP_ADAPTER_ADDRESSES_LH,
+            IP_ADAPTER_UNICAST_ADDRESS_LH, MIB_IPINTERFACE_ROW, MIB_NOTIFICATION_TYPE,
+            MIB_UNICASTIPADDRESS_ROW,
+        },
+        Ndis::IfOperStatusUp,
+    },
+    Networking::WinSock::AF_UNSPEC,
+};
+
+/// How many times we retry if the interfaces change between sizing the buffer and filling it.
+const MAX_ENUMERATE_ATTEMPTS: usize = 4;
+
+/// The initial buffer size recommended by the `GetAdaptersAddresses()` documentation.
+const INITIAL_BUFFER_SIZE: usize = 15 * 1024;
+
+/// A network interface of the local machine, as returned by `interfaces()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NetworkInterface {
+    name: String,
+    description: String,
+    index: u32,
+    mtu: u32,
+    is_up: bool,
+    is_loopback: bool,
+    addresses: Vec<InterfaceAddress>,
+}
+
+impl NetworkInterface {
+    /// The user-visible name of the interface (e.g. "Ethernet").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The description of the interface, typically the name of the network adapter.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The index of the interface, as used for selecting the interface of multicast traffic and
+    /// as the scope ID of IPv6 link-local addresses.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The largest packet that the interface can transmit, in bytes.
+    pub fn mtu(&self) -> u32 {
+        self.mtu
+    }
+
+    /// Whether the interface is operational and able to pass packets.
+    pub fn is_up(&self) -> bool {
+        self.is_up
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        self.is_loopback
+    }
+
+    /// The unicast addresses assigned to the interface.
+    pub fn addresses(&self) -> &[InterfaceAddress] {
+        &self.addresses
+    }
+}
+
+/// A unicast address assigned to a network interface.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InterfaceAddress {
+    addr: IpAddr,
+    prefix_length: u8,
+}
+
+impl InterfaceAddress {
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The length of the network prefix of the address, in bits.
+    pub fn prefix_length(&self) -> u8 {
+        self.prefix_length
+    }
+}
+
+/// Lists the network interfaces of the local machine with their assigned unicast addresses,
+/// including interfaces that are not up.
+///
+/// # Panics
+///
+/// Panics if the current thread is not owned by the Folo runtime.
+pub async fn interfaces() -> io::Result<Vec<NetworkInterface>> {
+    spawn_sync(SynchronousTaskType::Syscall, enumerate_interfaces).await
+}
+
+fn enumerate_interfaces() -> io::Result<Vec<NetworkInterface>> {
+    // u64 elements to align the adapter structures that the operating system places in the buffer.
+    let mut buffer: Vec<u64> = vec![0; INITIAL_BUFFER_SIZE / mem::size_of::<u64>()];
+
+    for _ in 0..MAX_ENUMERATE_ATTEMPTS {
+        let mut size = (buffer.len() * mem::size_of::<u64>()) as u32;
+
+        // SAFETY: The buffer is valid for the specified size and we only read it after success.
+        let result = unsafe {
+            GetAdaptersAddresses(
+                AF_UNSPEC.0 as u32,
+                GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER,
+                None,
+                Some(buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH),
+                &mut size as *mut _,
+            )
+        };
+
+        match WIN32_ERROR(result) {
+            NO_ERROR => {
+                // SAFETY: The call succeeded, so the buffer holds a valid list of adapters.
+                return Ok(unsafe { parse_adapters(buffer.as_ptr() as *const _) });
+            }
+            ERROR_NO_DATA => return Ok(Vec::new()),
+            ERROR_BUFFER_OVERFLOW => {
+                buffer = vec![0; (size as usize).div_ceil(mem::size_of::<u64>())];
+            }
+            error => error.ok()?,
+        }
+    }
+
+    Err(io::Error::StdIo(std::io::Error::other(
+        "network interfaces kept changing while being enumerated",
+    )))
+}
+
+/// # Safety
+///
+/// The pointer must point to the first element of a valid list of adapters returned by
+/// `GetAdaptersAddresses()`.
+unsafe fn parse_adapters(mut adapter: *const IP_ADAPTER_ADDRESSES_LH) -> Vec<NetworkInterface> {
+    let mut interfaces = Vec::new();
+
+    while let Some(current) = adapter.as_ref() {
+        let mut addresses = Vec::new();
+        let mut address: *const IP_ADAPTER_UNICAST_ADDRESS_LH = current.FirstUnicastAddress;
+
+        while let Some(current_address) = address.as_ref() {
+            if let Some(addr) = winsock::from_native_socket_addr(current_address.Address.lpSockaddr)
+            {
+                addresses.push(InterfaceAddress {
+                    addr: addr.ip(),
+                    prefix_length: current_address.OnLinkPrefixLength,
+                });
+            }
+
+            address = current_address.Next;
+        }
+
+        // The IPv4 index is zero if IPv4 is disabled on the interface, in which case we use the
+        // IPv6 index. They are the same if both are enabled.
+        let ipv4_index = current.Anonymous1.Anonymous.IfIndex;
+
+        interfaces.push(NetworkInterface {
+            name: current.FriendlyName.to_string().unwrap_or_default(),
+            description: current.Description.to_string().unwrap_or_default(),
+            index: if ipv4_index != 0 {
+                ipv4_index
+            } else {
+                current.Ipv6IfIndex
+            },
+            mtu: current.Mtu,
+            is_up: current.OperStatus == IfOperStatusUp,
+            is_loopback: current.IfType == IF_TYPE_SOFTWARE_LOOPBACK,
+            addresses,
+        });
+
+        adapter = current.Next;
+    }
+
+    interfaces
+}
+
+/// Starts listening for changes to the network interfaces of the local machine or to the
+/// addresses assigned to them. See `InterfaceChanges`.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn interface_changes() -> io::Result<InterfaceChanges> {
+    let (tx, rx) = mpsc::unbounded();
+
+    let mut changes = InterfaceChanges {
+        context: Box::into_raw(Box::new(ChangeContext {
+            tx,
+            io_waker: current_async_agent::with_io(|io| io.waker()),
+        })),
+        rx,
+        interface_notification: HANDLE::default(),
+        address_notification: HANDLE::default(),
+    };
+
+    // SAFETY: The context stays alive until both notifications are canceled (see Drop), which
+    // waits for any callbacks in progress to complete.
+    unsafe {
+        NotifyIpInterfaceChange(
+            AF_UNSPEC,
+            Some(on_interface_changed),
+            Some(changes.context as *const c_void),
+            false,
+            &mut changes.interface_notification as *mut _,
+        )
+        .ok()?;
+
+        NotifyUnicastIpAddressChange(
+            AF_UNSPEC,
+            Some(on_address_changed),
+            Some(changes.context as *const c_void),
+            false,
+            &mut changes.address_notification as *mut _,
+        )
+        .ok()?;
+    }
+
+    Ok(changes)
+}
+
+/// A stream that yields an item whenever a network interface of the local machine has been added,
+/// removed or changed (e.g. gone up or down) or an address has been assigned or unassigned, after
+/// which `interfaces()` returns the new state. Notifications that arrive between polls are
+/// coalesced into a single item. The stream never ends.
+///
+/// Returned by `interface_changes()`.
+pub struct InterfaceChanges {
+    // Shared with the notification callbacks until both notifications are canceled.
+    context: *mut ChangeContext,
+    rx: mpsc::UnboundedReceiver<()>,
+
+    interface_notification: HANDLE,
+    address_notification: HANDLE,
+}
+
+impl Stream for InterfaceChanges {
+    type Item = ();
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        match self.rx.poll_next_unpin(cx) {
+            task::Poll::Ready(Some(())) => {
+                while let Ok(Some(())) = self.rx.try_next() {}
+
+                task::Poll::Ready(Some(()))
+            }
+            task::Poll::Ready(None) => task::Poll::Ready(None),
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+impl Drop for InterfaceChanges {
+    fn drop(&mut self) {
+        // Canceling waits for any callbacks in progress to complete, after which nothing refers to
+        // the context anymore.
+        //
+        // SAFETY: The handles are valid until canceled, which only happens here.
+        unsafe {
+            if !self.interface_notification.is_invalid() {
+                _ = CancelMibChangeNotify2(self.interface_notification);
+            }
+
+            if !self.address_notification.is_invalid() {
+                _ = CancelMibChangeNotify2(self.address_notification);
+            }
+
+            drop(Box::from_raw(self.context));
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for InterfaceChanges {}
+#[negative_impl]
+impl !Sync for InterfaceChanges {}
+
+struct ChangeContext {
+    tx: mpsc::UnboundedSender<()>,
+
+    // The async worker may be asleep waiting for I/O, so we need to wake it up in addition to
+    // waking the task that polls the stream.
+    io_waker: IoWaker,
+}
+
+impl ChangeContext {
+    fn notify(&self) {
+        // The receiver may already be gone, which is fine.
+        _ = self.tx.unbounded_send(());
+        self.io_waker.wake();
+    }
+}
+
+unsafe extern "system" fn on_interface_changed(
+    context: *const c_void,
+    _row: *const MIB_IPINTERFACE_ROW,
+    _notification_type: MIB_NOTIFICATION_TYPE,
+) {
+    // SAFETY: This is the context we registered, which outlives the notification.
+    unsafe { &*(context as *const ChangeContext) }.notify();
+}
+
+unsafe extern "system" fn on_address_changed(
+    context: *const c_void,
+    _row: *const MIB_UNICASTIPADDRESS_ROW,
+    _notification_type: MIB_NOTIFICATION_TYPE,
+) {
+    // SAFETY: This is the context we registered, which outlives the notification.
+    unsafe { &*(context as *const ChangeContext) }.notify();
+}
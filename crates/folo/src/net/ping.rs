@@ -0,0 +1,388 @@
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    io,
+    metrics::{Event, EventBuilder},
+    rt::spawn,
+    util::{wait_for_handle, OwnedHandle},
+};
+use std::{
+    ffi::c_void,
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ptr,
+    time::Duration,
+};
+use windows::Win32::{
+    Foundation::{GetLastError, ERROR_IO_PENDING, HANDLE},
+    NetworkManagement::IpHelper::{
+        Icmp6CreateFile, Icmp6ParseReplies, Icmp6SendEcho2, IcmpCloseHandle, IcmpCreateFile,
+        IcmpParseReplies, IcmpSendEcho2, ICMPV6_ECHO_REPLY_LH, ICMP_ECHO_REPLY,
+        IP_DEST_HOST_UNREACHABLE, IP_DEST_NET_UNREACHABLE, IP_DEST_UNREACHABLE,
+        IP_OPTION_INFORMATION, IP_REQ_TIMED_OUT, IP_SUCCESS,
+    },
+    Networking::WinSock::{AF_INET6, SOCKADDR_IN6},
+    System::Threading::{CreateEventW, WaitForSingleObject, INFINITE},
+};
+
+/// The largest payload that fits into a single IPv4 datagram with an ICMP echo header.
+const MAX_PAYLOAD_LENGTH: usize = 65_500;
+
+/// Options for `ping_with()`.
+#[derive(Clone, Debug)]
+pub struct PingOptions {
+    timeout: Duration,
+    ttl: u8,
+    payload: Vec<u8>,
+}
+
+impl PingOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long to wait for the reply. Defaults to 4 seconds.
+    pub fn timeout(mut self, value: Duration) -> Self {
+        self.timeout = value;
+        self
+    }
+
+    /// The TTL (IPv4) or hop limit (IPv6) of the echo request. Defaults to 128.
+    pub fn ttl(mut self, value: u8) -> Self {
+        self.ttl = value;
+        self
+    }
+
+    /// The data to send in the echo request, which the target echoes back. At most 65500 bytes.
+    /// Defaults to the same 32 bytes that the `ping` command sends.
+    pub fn payload(mut self, value: impl Into<Vec<u8>>) -> Self {
+        self.payload = value.into();
+        self
+    }
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(4),
+            ttl: 128,
+            payload: b"abcdefghijklmnopqrstuvwabcdefghi".to_vec(),
+        }
+    }
+}
+
+/// The reply to an ICMP echo request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PingReply {
+    address: IpAddr,
+    round_trip_time: Duration,
+    ttl: Option<u8>,
+}
+
+impl PingReply {
+    /// The address that sent the reply.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The round trip time measured by the operating system, with millisecond precision.
+    pub fn round_trip_time(&self) -> Duration {
+        self.round_trip_time
+    }
+
+    /// The remaining TTL of the reply when it arrived. Only reported for IPv4.
+    pub fn ttl(&self) -> Option<u8> {
+        self.ttl
+    }
+}
+
+/// Sends an ICMP echo request to the specified address and waits for the reply, with default
+/// options. See `ping_with()`.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub async fn ping(addr: IpAddr) -> io::Result<PingReply> {
+    ping_with(addr, PingOptions::default()).await
+}
+
+/// Sends an ICMP echo request to the specified address and waits for the reply. Does not require
+/// administrative privileges. The operating system assigns the identifier and sequence number of
+/// the request and matches the reply to it, so any number of pings can be in flight at once.
+///
+/// Returns `io::Error::TimedOut` if there is no reply within the timeout. If the reply is an error
+/// message (e.g. the destination is unreachable), the error is reported as `io::Error::StdIo` with
+/// the ICMP status code in the message.
+///
+/// Dropping the returned future before it completes does not cancel the request, which remains in
+/// flight in the background until the reply arrives or the timeout elapses.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub async fn ping_with(addr: IpAddr, options: PingOptions) -> io::Result<PingReply> {
+    if options.payload.len() > MAX_PAYLOAD_LENGTH {
+        return Err(io::Error::InvalidOptions(format!(
+            "ping payload must be at most {MAX_PAYLOAD_LENGTH} bytes long"
+        )));
+    }
+
+    let timeout_millis = u32::try_from(options.timeout.as_millis())
+        .ok()
+        .filter(|millis| *millis > 0)
+        .ok_or_else(|| {
+            io::Error::InvalidOptions(
+                "ping timeout must be at least 1 millisecond and fit into u32 milliseconds"
+                    .to_string(),
+            )
+        })?;
+
+    // The request cannot be canceled, so we let it run to completion in its own task, which owns
+    // everything that the operating system refers to while the request is pending.
+    let result = spawn(async move {
+        let mut echo = PendingEcho::new(addr, &options)?;
+        echo.send(timeout_millis)?;
+
+        wait_for_handle(*echo.event)?.await;
+        echo.in_progress = false;
+
+        echo.parse_reply()
+    })
+    .await;
+
+    match &result {
+        Ok(reply) => ROUND_TRIP_TIME.with(|x| x.observe_millis(reply.round_trip_time)),
+        Err(_) => FAILURES.with(Event::observe_unit),
+    }
+
+    result
+}
+
+/// An ICMP handle, which is closed via its own function instead of `CloseHandle()`.
+struct IcmpHandle(HANDLE);
+
+impl Drop for IcmpHandle {
+    fn drop(&mut self) {
+        // SAFETY: We own the handle and nothing uses it after this.
+        _ = unsafe { IcmpCloseHandle(self.0) };
+    }
+}
+
+/// The state of an echo request. The operating system writes into the reply buffer while the
+/// request is pending, so the state must stay alive until the request completes.
+struct PendingEcho {
+    destination: IpAddr,
+    request_options: IP_OPTION_INFORMATION,
+    payload: Vec<u8>,
+
+    // u64 elements to align the reply structures that the operating system places at the start.
+    reply: Vec<u64>,
+
+    icmp: IcmpHandle,
+    event: OwnedHandle<HANDLE>,
+
+    in_progress: bool,
+}
+
+impl PendingEcho {
+    fn new(destination: IpAddr, options: &PingOptions) -> io::Result<Self> {
+        // SAFETY: We are required to close the handle once we are done with it, which IcmpHandle
+        // does on drop.
+        let icmp = IcmpHandle(unsafe {
+            match destination {
+                IpAddr::V4(_) => IcmpCreateFile()?,
+                IpAddr::V6(_) => Icmp6CreateFile()?,
+            }
+        });
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let event = unsafe { OwnedHandle::new(CreateEventW(None, true, false, None)?) };
+
+        // Room for the reply structure, the echoed payload, an ICMP error message and the
+        // IO_STATUS_BLOCK that the operating system uses to track asynchronous requests.
+        let reply_length = mem::size_of::<ICMP_ECHO_REPLY>()
+            .max(mem::size_of::<ICMPV6_ECHO_REPLY_LH>())
+            + options.payload.len()
+            + 8
+            + 16;
+
+        Ok(Self {
+            destination,
+            request_options: IP_OPTION_INFORMATION {
+                Ttl: options.ttl,
+                ..Default::default()
+            },
+            payload: options.payload.clone(),
+            reply: vec![0; reply_length.div_ceil(mem::size_of::<u64>())],
+            icmp,
+            event,
+            in_progress: false,
+        })
+    }
+
+    fn reply_length(&self) -> u32 {
+        (self.reply.len() * mem::size_of::<u64>()) as u32
+    }
+
+    /// Starts the request. The event is signaled once the request completes.
+    fn send(&mut self, timeout_millis: u32) -> io::Result<()> {
+        let reply_length = self.reply_length();
+
+        // SAFETY: Everything we pass in lives in self, which the caller keeps alive (and Drop
+        // waits for the request to complete) until the event is signaled. A zero return value
+        // means either that the request is pending (ERROR_IO_PENDING) or that it failed.
+        let result = unsafe {
+            match self.destination {
+                IpAddr::V4(destination) => IcmpSendEcho2(
+                    self.icmp.0,
+                    *self.event,
+                    None,
+                    None,
+                    u32::from_ne_bytes(destination.octets()),
+                    self.payload.as_ptr() as *const c_void,
+                    self.payload.len() as u16,
+                    Some(&self.request_options as *const _),
+                    self.reply.as_mut_ptr() as *mut c_void,
+                    reply_length,
+                    timeout_millis,
+                ),
+                IpAddr::V6(destination) => {
+                    let source = SOCKADDR_IN6 {
+                        sin6_family: AF_INET6,
+                        ..Default::default()
+                    };
+
+                    let mut native_destination = SOCKADDR_IN6 {
+                        sin6_family: AF_INET6,
+                        ..Default::default()
+                    };
+                    native_destination.sin6_addr.u.Byte = destination.octets();
+
+                    Icmp6SendEcho2(
+                        self.icmp.0,
+                        *self.event,
+                        None,
+                        None,
+                        &source as *const _,
+                        &native_destination as *const _,
+                        self.payload.as_ptr() as *const c_void,
+                        self.payload.len() as u16,
+                        Some(&self.request_options as *const _),
+                        self.reply.as_mut_ptr() as *mut c_void,
+                        reply_length,
+                        timeout_millis,
+                    )
+                }
+            }
+        };
+
+        if result == 0 {
+            let error = windows::core::Error::from_win32();
+
+            if error.code() != ERROR_IO_PENDING.into() {
+                return Err(error.into());
+            }
+        }
+
+        // The event is signaled even if the reply was already available immediately.
+        self.in_progress = true;
+        Ok(())
+    }
+
+    /// Interprets the reply buffer of a completed request.
+    fn parse_reply(&mut self) -> io::Result<PingReply> {
+        let reply_length = self.reply_length();
+        let buffer = self.reply.as_mut_ptr() as *mut c_void;
+
+        // SAFETY: The request is complete, so the buffer holds its result, which the parse
+        // functions check before we read any reply structure from the start of the buffer.
+        unsafe {
+            match self.destination {
+                IpAddr::V4(_) => {
+                    if IcmpParseReplies(buffer, reply_length) == 0 {
+                        return Err(status_to_error(GetLastError().0));
+                    }
+
+                    let reply = ptr::read(buffer as *const ICMP_ECHO_REPLY);
+
+                    if reply.Status != IP_SUCCESS {
+                        return Err(status_to_error(reply.Status));
+                    }
+
+                    Ok(PingReply {
+                        address: Ipv4Addr::from(reply.Address.to_ne_bytes()).into(),
+                        round_trip_time: Duration::from_millis(reply.RoundTripTime as u64),
+                        ttl: Some(reply.Options.Ttl),
+                    })
+                }
+                IpAddr::V6(_) => {
+                    if Icmp6ParseReplies(buffer, reply_length) == 0 {
+                        return Err(status_to_error(GetLastError().0));
+                    }
+
+                    let reply = ptr::read(buffer as *const ICMPV6_ECHO_REPLY_LH);
+
+                    if reply.Status != IP_SUCCESS {
+                        return Err(status_to_error(reply.Status));
+                    }
+
+                    // The address is stored as 16-bit words in network byte order.
+                    let mut octets = [0; 16];
+                    for (target, word) in octets.chunks_exact_mut(2).zip(reply.Address.sin6_addr) {
+                        target.copy_from_slice(&word.to_ne_bytes());
+                    }
+
+                    Ok(PingReply {
+                        address: Ipv6Addr::from(octets).into(),
+                        round_trip_time: Duration::from_millis(reply.RoundTripTime as u64),
+                        ttl: None,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PendingEcho {
+    fn drop(&mut self) {
+        if !self.in_progress {
+            return;
+        }
+
+        // ICMP requests cannot be canceled, so if we are dropped before the request completes
+        // (e.g. because the runtime is shutting down), we wait for it. This is bounded by the
+        // timeout of the request.
+        //
+        // SAFETY: The event is valid until we are dropped, which is only after this returns.
+        unsafe {
+            WaitForSingleObject(*self.event, INFINITE);
+        }
+    }
+}
+
+fn status_to_error(status: u32) -> io::Error {
+    let kind = match status {
+        IP_REQ_TIMED_OUT => return io::Error::TimedOut,
+        IP_DEST_NET_UNREACHABLE => std::io::ErrorKind::NetworkUnreachable,
+        IP_DEST_HOST_UNREACHABLE | IP_DEST_UNREACHABLE => std::io::ErrorKind::HostUnreachable,
+        _ => std::io::ErrorKind::Other,
+    };
+
+    io::Error::StdIo(std::io::Error::new(
+        kind,
+        format!("ICMP echo request failed with status {status}"),
+    ))
+}
+
+thread_local! {
+    static ROUND_TRIP_TIME: Event = EventBuilder::new()
+        .name("net_ping_round_trip_time_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static FAILURES: Event = EventBuilder::new()
+        .name("net_ping_failures")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,502 @@
+//! Client-side pooling of outbound TCP connections, so services that talk to the same targets over
+//! and over (proxies, HTTP clients and the like) can reuse established connections instead of
+//! paying for a new handshake on every request.
+//!
+//! Connections are bound to the async worker that created them, so a pool belongs to one worker
+//! and cannot be shared with other workers. Create one pool per worker, for example in
+//! `RuntimeBuilder::context_init()` via `rt::context`, and get connections from the pool of the
+//! current worker.
+
+use crate::{
+    io,
+    metrics::{Event, EventBuilder},
+    net::{ConnectOptions, TcpConnection},
+    rt::clock,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    future::poll_fn,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+    task::{self, Waker},
+    time::{Duration, Instant},
+};
+
+/// How many connections to each target a pool allows at the same time, unless configured
+/// otherwise.
+pub const DEFAULT_MAX_CONNECTIONS_PER_TARGET: NonZeroUsize = NonZeroUsize::new(16).unwrap();
+
+/// How long a connection may sit idle in a pool before it is closed, unless configured otherwise.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+type HealthCheck = Box<dyn Fn(&TcpConnection) -> bool>;
+
+/// Configures and creates a `ConnectionPool`.
+pub struct ConnectionPoolBuilder {
+    max_connections_per_target: NonZeroUsize,
+    idle_timeout: Duration,
+    connect_options: ConnectOptions,
+    connect_timeout: Option<Duration>,
+    health_check: Option<HealthCheck>,
+}
+
+impl ConnectionPoolBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_connections_per_target: DEFAULT_MAX_CONNECTIONS_PER_TARGET,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            connect_options: ConnectOptions::default(),
+            connect_timeout: None,
+            health_check: None,
+        }
+    }
+
+    /// The maximum number of connections to each target, counting both the connections in use and
+    /// the idle connections in the pool. Once reached, `ConnectionPool::get()` waits for one of
+    /// the connections to be returned or dropped. Defaults to
+    /// `DEFAULT_MAX_CONNECTIONS_PER_TARGET`.
+    pub fn max_connections_per_target(mut self, value: NonZeroUsize) -> Self {
+        self.max_connections_per_target = value;
+        self
+    }
+
+    /// How long a connection may sit idle in the pool before it is closed instead of being reused.
+    /// Keep this below the idle timeout of the targets, so we do not hand out connections that the
+    /// target is about to close. Defaults to `DEFAULT_IDLE_TIMEOUT`.
+    pub fn idle_timeout(mut self, value: Duration) -> Self {
+        self.idle_timeout = value;
+        self
+    }
+
+    /// The options for establishing new connections. Use `connect_timeout()` instead of a deadline
+    /// in the options, as the deadline is a fixed point in time that would apply to every
+    /// connection the pool ever creates.
+    pub fn connect_options(mut self, value: ConnectOptions) -> Self {
+        self.connect_options = value;
+        self
+    }
+
+    /// Abandons establishing a new connection if it takes longer than this, failing with
+    /// `io::Error::TimedOut`. By default, there is no timeout beyond that of the operating system.
+    pub fn connect_timeout(mut self, value: Duration) -> Self {
+        self.connect_timeout = Some(value);
+        self
+    }
+
+    /// Registers a check that idle connections must pass before they are handed out again. If the
+    /// check returns false, the connection is closed and the next idle connection is tried (or a
+    /// new connection established). The check must not block - it is called on the async worker.
+    ///
+    /// Connections that are known to be closed in either direction are never reused, whether or
+    /// not a check is registered.
+    pub fn health_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&TcpConnection) -> bool + 'static,
+    {
+        self.health_check = Some(Box::new(check));
+        self
+    }
+
+    pub fn build(self) -> ConnectionPool {
+        ConnectionPool {
+            inner: Rc::new(Inner {
+                max_connections_per_target: self.max_connections_per_target.get(),
+                idle_timeout: self.idle_timeout,
+                connect_options: self.connect_options,
+                connect_timeout: self.connect_timeout,
+                health_check: self.health_check,
+                targets: RefCell::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl Default for ConnectionPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ConnectionPoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionPoolBuilder")
+            .field(
+                "max_connections_per_target",
+                &self.max_connections_per_target,
+            )
+            .field("idle_timeout", &self.idle_timeout)
+            .field("connect_options", &self.connect_options)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("health_check", &self.health_check.is_some())
+            .finish()
+    }
+}
+
+/// A pool of outbound TCP connections owned by the current async worker, keyed by target address.
+/// Connections obtained via `get()` return to the pool when dropped, to be reused by later calls
+/// for the same target.
+///
+/// The pool is cheap to clone - clones refer to the same pool. Idle connections are closed when
+/// the last clone and all the connections obtained from it are dropped.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    max_connections_per_target: usize,
+    idle_timeout: Duration,
+    connect_options: ConnectOptions,
+    connect_timeout: Option<Duration>,
+    health_check: Option<HealthCheck>,
+
+    targets: RefCell<HashMap<SocketAddr, Target>>,
+}
+
+#[derive(Default)]
+struct Target {
+    // Ordered by the time the connection became idle, most recent last.
+    idle: Vec<IdleConnection>,
+
+    // Connections handed out or being established. Each holds a `Slot`.
+    in_use: usize,
+
+    // Tasks waiting for the number of connections to drop below the limit.
+    waiting: Vec<Waker>,
+}
+
+struct IdleConnection {
+    connection: TcpConnection,
+    idle_since: Instant,
+}
+
+// A slot for a connection to the target, with an idle connection to fill it if there was one.
+struct Take {
+    idle: Option<TcpConnection>,
+    slot: Slot,
+}
+
+impl ConnectionPool {
+    /// Creates a pool with the default configuration. Use `ConnectionPoolBuilder` to configure it.
+    pub fn new() -> Self {
+        ConnectionPoolBuilder::new().build()
+    }
+
+    /// Gets a connection to the target, reusing an idle connection from the pool if there is one
+    /// and establishing a new connection otherwise. If the pool already has the maximum number of
+    /// connections to the target, waits for one of them to be returned or dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn get(&self, addr: SocketAddr) -> io::Result<PooledConnection> {
+        let take = poll_fn(|cx| self.inner.poll_take(addr, cx)).await;
+
+        let Take { idle, slot } = take;
+
+        let connection = match idle {
+            Some(connection) => {
+                CONNECTIONS_REUSED.with(Event::observe_unit);
+                connection
+            }
+            None => {
+                let mut options = self.inner.connect_options.clone();
+
+                if let Some(timeout) = self.inner.connect_timeout {
+                    options = options.timeout(timeout);
+                }
+
+                // If this fails, the slot is released as it is dropped.
+                let connection = TcpConnection::connect_with(addr, options).await?;
+
+                CONNECTIONS_CREATED.with(Event::observe_unit);
+                connection
+            }
+        };
+
+        Ok(PooledConnection {
+            connection: Some(connection),
+            slot,
+        })
+    }
+
+    /// Number of idle connections to the target in the pool, including any that have exceeded the
+    /// idle timeout but have not yet been closed.
+    pub fn idle_connections(&self, addr: SocketAddr) -> usize {
+        self.inner
+            .targets
+            .borrow()
+            .get(&addr)
+            .map_or(0, |target| target.idle.len())
+    }
+
+    /// Number of connections to the target that are in use (or being established).
+    pub fn active_connections(&self, addr: SocketAddr) -> usize {
+        self.inner
+            .targets
+            .borrow()
+            .get(&addr)
+            .map_or(0, |target| target.in_use)
+    }
+
+    /// Closes all idle connections in the pool. Connections in use are not affected.
+    pub fn clear_idle(&self) {
+        // We release the borrow before the connections are dropped.
+        let idle = self
+            .inner
+            .targets
+            .borrow_mut()
+            .values_mut()
+            .flat_map(|target| target.idle.drain(..))
+            .collect::<Vec<_>>();
+
+        drop(idle);
+    }
+}
+
+impl Default for ConnectionPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("targets", &self.inner.targets.borrow().len())
+            .finish()
+    }
+}
+
+#[negative_impl]
+impl !Send for ConnectionPool {}
+#[negative_impl]
+impl !Sync for ConnectionPool {}
+
+impl Inner {
+    fn poll_take(
+        self: &Rc<Self>,
+        addr: SocketAddr,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Take> {
+        loop {
+            let candidate = {
+                let mut targets = self.targets.borrow_mut();
+                let target = targets.entry(addr).or_default();
+
+                match target.idle.pop() {
+                    Some(idle) => {
+                        // The idle connections are in order, so if the most recent one has timed
+                        // out, so have all the others.
+                        if clock::now().saturating_duration_since(idle.idle_since)
+                            >= self.idle_timeout
+                        {
+                            let expired = target.idle.len() + 1;
+                            let older = std::mem::take(&mut target.idle);
+                            drop(targets);
+
+                            CONNECTIONS_EXPIRED.with(|x| x.observe(expired as i64));
+                            drop(older);
+                            drop(idle);
+                            continue;
+                        }
+
+                        // The connection was already counted as part of the target, so it just
+                        // moves from idle to in use.
+                        target.in_use += 1;
+                        Some(idle.connection)
+                    }
+                    None if target.idle.len() + target.in_use < self.max_connections_per_target => {
+                        target.in_use += 1;
+                        None
+                    }
+                    None => {
+                        if !target.waiting.iter().any(|w| w.will_wake(cx.waker())) {
+                            target.waiting.push(cx.waker().clone());
+                        }
+
+                        return task::Poll::Pending;
+                    }
+                }
+            };
+
+            let slot = Slot {
+                pool: Rc::clone(self),
+                addr,
+            };
+
+            let Some(connection) = candidate else {
+                return task::Poll::Ready(Take { idle: None, slot });
+            };
+
+            // The check is called without the pool borrowed, so it may use the pool.
+            if is_reusable(&connection)
+                && self
+                    .health_check
+                    .as_ref()
+                    .is_none_or(|check| check(&connection))
+            {
+                return task::Poll::Ready(Take {
+                    idle: Some(connection),
+                    slot,
+                });
+            }
+
+            // Dropping the slot makes room for another connection.
+            CONNECTIONS_UNHEALTHY.with(Event::observe_unit);
+            drop(connection);
+            drop(slot);
+        }
+    }
+
+    fn give_back(&self, addr: SocketAddr, connection: TcpConnection) {
+        let mut targets = self.targets.borrow_mut();
+        let target = targets.entry(addr).or_default();
+
+        target.idle.push(IdleConnection {
+            connection,
+            idle_since: clock::now(),
+        });
+    }
+
+    fn release_slot(&self, addr: SocketAddr) {
+        let waiting = {
+            let mut targets = self.targets.borrow_mut();
+            let target = targets.entry(addr).or_default();
+
+            target.in_use -= 1;
+
+            let waiting = std::mem::take(&mut target.waiting);
+
+            // We keep the map from growing with targets we no longer talk to.
+            if target.idle.is_empty() && target.in_use == 0 {
+                targets.remove(&addr);
+            }
+
+            waiting
+        };
+
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+}
+
+/// Whether the connection can be used for another request, as far as we know.
+fn is_reusable(connection: &TcpConnection) -> bool {
+    !connection.is_read_closed() && !connection.is_write_closed()
+}
+
+/// Counts a connection against the limit of its target until dropped.
+struct Slot {
+    pool: Rc<Inner>,
+    addr: SocketAddr,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.pool.release_slot(self.addr);
+    }
+}
+
+/// A connection obtained from a `ConnectionPool`, returned to the pool when dropped unless it is
+/// known to be closed or is detached from the pool. Dereferences to the connection itself.
+///
+/// Only drop the connection normally once the current exchange with the target has completed, so
+/// the next user of the connection does not receive leftover data. If the exchange fails midway
+/// or the protocol does not allow reuse, call `discard()` instead.
+pub struct PooledConnection {
+    // Only `None` while being dropped or detached.
+    connection: Option<TcpConnection>,
+
+    // Dropped after the connection is returned to the pool, so waiting tasks find it there.
+    slot: Slot,
+}
+
+impl PooledConnection {
+    /// The address of the target the connection is connected to.
+    pub fn target(&self) -> SocketAddr {
+        self.slot.addr
+    }
+
+    /// Closes the connection instead of returning it to the pool.
+    pub fn discard(mut self) {
+        self.connection.take();
+    }
+
+    /// Removes the connection from the pool, which no longer counts it against the limit of the
+    /// target.
+    pub fn detach(mut self) -> TcpConnection {
+        self.connection
+            .take()
+            .expect("connection is only taken when consuming the wrapper")
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = TcpConnection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection
+            .as_ref()
+            .expect("connection is only taken when consuming the wrapper")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.connection
+            .as_mut()
+            .expect("connection is only taken when consuming the wrapper")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if is_reusable(&connection) {
+                self.slot.pool.give_back(self.slot.addr, connection);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PooledConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledConnection")
+            .field("target", &self.slot.addr)
+            .finish()
+    }
+}
+
+#[negative_impl]
+impl !Send for PooledConnection {}
+#[negative_impl]
+impl !Sync for PooledConnection {}
+
+thread_local! {
+    static CONNECTIONS_CREATED: Event = EventBuilder::new()
+        .name("net_pool_connections_created")
+        .build()
+        .unwrap();
+
+    static CONNECTIONS_REUSED: Event = EventBuilder::new()
+        .name("net_pool_connections_reused")
+        .build()
+        .unwrap();
+
+    static CONNECTIONS_EXPIRED: Event = EventBuilder::new()
+        .name("net_pool_connections_expired")
+        .build()
+        .unwrap();
+
+    static CONNECTIONS_UNHEALTHY: Event = EventBuilder::new()
+        .name("net_pool_connections_unhealthy")
+        .build()
+        .unwrap();
+}
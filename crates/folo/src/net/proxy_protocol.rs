@@ -0,0 +1,323 @@
+use crate::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// The PROXY protocol header sent by a load balancer or reverse proxy (e.g. HAProxy or AWS NLB)
+/// at the start of a connection, describing the connection it received from the original client.
+/// Accepted connections carry it if the listener requires the header (see
+/// `TcpListener::set_proxy_protocol()`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProxyHeader {
+    /// The version of the PROXY protocol the header was sent with (1 or 2).
+    pub version: u8,
+
+    /// The address of the original client, if the proxy relayed a TCP connection over IPv4 or
+    /// IPv6. `None` for connections the proxy made on its own behalf (e.g. health checks) and for
+    /// protocols or address families that the header does not describe with an IP address.
+    pub source: Option<SocketAddr>,
+
+    /// The address the original client connected to, under the same conditions as `source`.
+    pub destination: Option<SocketAddr>,
+}
+
+/// The longest header that version 1 allows, including the terminating CRLF.
+const V1_MAX_LEN: usize = 107;
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_FIXED_LEN: usize = 16;
+
+/// Parses a PROXY protocol header (either version) from the start of the data received on a
+/// connection. Returns `None` if more data is needed to decide, otherwise the header and the
+/// number of bytes it occupies. Any bytes after the header belong to the proxied connection.
+pub(crate) fn parse(data: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    if data.starts_with(V2_SIGNATURE) {
+        return parse_v2(data);
+    }
+
+    if data.starts_with(V1_PREFIX) {
+        return parse_v1(data);
+    }
+
+    // Too little data to tell the versions apart, as long as it matches the start of either.
+    if V2_SIGNATURE.starts_with(data) || V1_PREFIX.starts_with(data) {
+        return Ok(None);
+    }
+
+    Err(invalid(
+        "connection did not start with a PROXY protocol header",
+    ))
+}
+
+fn parse_v1(data: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    let Some(line_end) = data.windows(2).position(|window| window == b"\r\n") else {
+        if data.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY protocol v1 header is too long"));
+        }
+
+        return Ok(None);
+    };
+
+    let consumed = line_end + 2;
+
+    if consumed > V1_MAX_LEN {
+        return Err(invalid("PROXY protocol v1 header is too long"));
+    }
+
+    let line = std::str::from_utf8(&data[..line_end])
+        .map_err(|_| invalid("PROXY protocol v1 header is not valid text"))?;
+
+    let mut fields = line.split(' ').skip(1);
+
+    let header = match fields.next() {
+        // The proxy does not know the protocol of the original connection and the rest of the
+        // line is to be ignored.
+        Some("UNKNOWN") => ProxyHeader {
+            version: 1,
+            source: None,
+            destination: None,
+        },
+        Some(protocol @ ("TCP4" | "TCP6")) => {
+            let mut next = || {
+                fields
+                    .next()
+                    .ok_or_else(|| invalid("PROXY protocol v1 header has too few fields"))
+            };
+
+            let source_ip = parse_v1_ip(next()?, protocol)?;
+            let destination_ip = parse_v1_ip(next()?, protocol)?;
+            let source_port = parse_v1_port(next()?)?;
+            let destination_port = parse_v1_port(next()?)?;
+
+            if fields.next().is_some() {
+                return Err(invalid("PROXY protocol v1 header has too many fields"));
+            }
+
+            ProxyHeader {
+                version: 1,
+                source: Some(SocketAddr::new(source_ip, source_port)),
+                destination: Some(SocketAddr::new(destination_ip, destination_port)),
+            }
+        }
+        _ => return Err(invalid("PROXY protocol v1 header has an unknown protocol")),
+    };
+
+    Ok(Some((header, consumed)))
+}
+
+fn parse_v1_ip(value: &str, protocol: &str) -> io::Result<IpAddr> {
+    let ip = match protocol {
+        "TCP4" => value.parse::<Ipv4Addr>().map(IpAddr::V4),
+        _ => value.parse::<Ipv6Addr>().map(IpAddr::V6),
+    };
+
+    ip.map_err(|_| invalid("PROXY protocol v1 header has an invalid address"))
+}
+
+fn parse_v1_port(value: &str) -> io::Result<u16> {
+    // Leading zeroes are not allowed, so every port has exactly one representation.
+    if value.len() > 1 && value.starts_with('0') {
+        return Err(invalid("PROXY protocol v1 header has an invalid port"));
+    }
+
+    value
+        .parse()
+        .map_err(|_| invalid("PROXY protocol v1 header has an invalid port"))
+}
+
+fn parse_v2(data: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    if data.len() < V2_FIXED_LEN {
+        return Ok(None);
+    }
+
+    let version_command = data[12];
+    let family_protocol = data[13];
+    let address_len = u16::from_be_bytes([data[14], data[15]]) as usize;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("PROXY protocol v2 header has an unknown version"));
+    }
+
+    let is_proxied = match version_command & 0x0F {
+        // The proxy made the connection on its own behalf, so the addresses are to be ignored.
+        0x0 => false,
+        0x1 => true,
+        _ => return Err(invalid("PROXY protocol v2 header has an unknown command")),
+    };
+
+    let consumed = V2_FIXED_LEN + address_len;
+
+    if data.len() < consumed {
+        return Ok(None);
+    }
+
+    let addresses = &data[V2_FIXED_LEN..consumed];
+
+    let (source, destination) = match (is_proxied, family_protocol) {
+        // TCP over IPv4. Any bytes after the addresses are TLVs, which we do not interpret.
+        (true, 0x11) => {
+            if addresses.len() < 12 {
+                return Err(invalid("PROXY protocol v2 header is too short for IPv4"));
+            }
+
+            let ip = |offset: usize| {
+                IpAddr::from(<[u8; 4]>::try_from(&addresses[offset..offset + 4]).unwrap())
+            };
+            let port =
+                |offset: usize| u16::from_be_bytes([addresses[offset], addresses[offset + 1]]);
+
+            (
+                Some(SocketAddr::new(ip(0), port(8))),
+                Some(SocketAddr::new(ip(4), port(10))),
+            )
+        }
+        // TCP over IPv6.
+        (true, 0x21) => {
+            if addresses.len() < 36 {
+                return Err(invalid("PROXY protocol v2 header is too short for IPv6"));
+            }
+
+            let ip = |offset: usize| {
+                IpAddr::from(<[u8; 16]>::try_from(&addresses[offset..offset + 16]).unwrap())
+            };
+            let port =
+                |offset: usize| u16::from_be_bytes([addresses[offset], addresses[offset + 1]]);
+
+            (
+                Some(SocketAddr::new(ip(0), port(32))),
+                Some(SocketAddr::new(ip(16), port(34))),
+            )
+        }
+        // Local connections, unspecified or non-TCP protocols and Unix sockets.
+        _ => (None, None),
+    };
+
+    Ok(Some((
+        ProxyHeader {
+            version: 2,
+            source,
+            destination,
+        },
+        consumed,
+    )))
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(version_command: u8, family_protocol: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(version_command);
+        header.push(family_protocol);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    #[test]
+    fn v1_tcp4() {
+        let data = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET /";
+
+        let (header, consumed) = parse(data).unwrap().unwrap();
+
+        assert_eq!(header.version, 1);
+        assert_eq!(header.source, Some("192.168.0.1:56324".parse().unwrap()));
+        assert_eq!(
+            header.destination,
+            Some("192.168.0.11:443".parse().unwrap())
+        );
+        assert_eq!(&data[consumed..], b"GET /");
+    }
+
+    #[test]
+    fn v1_tcp6_and_unknown() {
+        let (header, _) = parse(b"PROXY TCP6 ::1 2001:db8::1 1000 80\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(header.source, Some("[::1]:1000".parse().unwrap()));
+        assert_eq!(
+            header.destination,
+            Some("[2001:db8::1]:80".parse().unwrap())
+        );
+
+        let (header, consumed) = parse(b"PROXY UNKNOWN ff::1 whatever\r\n").unwrap().unwrap();
+        assert_eq!(header.source, None);
+        assert_eq!(consumed, 30);
+    }
+
+    #[test]
+    fn v1_incomplete_and_invalid() {
+        assert!(parse(b"PRO").unwrap().is_none());
+        assert!(parse(b"PROXY TCP4 1.2.3.4").unwrap().is_none());
+
+        assert!(parse(b"GET / HTTP/1.1\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 1.2.3.4 5.6.7.8 1 2 3\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 ::1 5.6.7.8 1 2\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 1.2.3.4 5.6.7.8 01 2\r\n").is_err());
+        assert!(parse(b"PROXY UDP4 1.2.3.4 5.6.7.8 1 2\r\n").is_err());
+        assert!(parse(&b"PROXY ".repeat(20)).is_err());
+    }
+
+    #[test]
+    fn v2_tcp4_with_tlvs() {
+        let mut addresses = vec![10, 0, 0, 1, 10, 0, 0, 2];
+        addresses.extend_from_slice(&1234_u16.to_be_bytes());
+        addresses.extend_from_slice(&443_u16.to_be_bytes());
+        // A TLV that we skip over.
+        addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0xFF]);
+
+        let mut data = v2_header(0x21, 0x11, &addresses);
+        let header_len = data.len();
+        data.extend_from_slice(b"payload");
+
+        let (header, consumed) = parse(&data).unwrap().unwrap();
+
+        assert_eq!(header.version, 2);
+        assert_eq!(header.source, Some("10.0.0.1:1234".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.2:443".parse().unwrap()));
+        assert_eq!(consumed, header_len);
+
+        // Every truncation of the header asks for more data.
+        for len in 0..header_len {
+            assert!(parse(&data[..len]).unwrap().is_none(), "length {len}");
+        }
+    }
+
+    #[test]
+    fn v2_tcp6() {
+        let source = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
+        let destination = "2001:db8::2".parse::<Ipv6Addr>().unwrap();
+
+        let mut addresses = source.octets().to_vec();
+        addresses.extend_from_slice(&destination.octets());
+        addresses.extend_from_slice(&5000_u16.to_be_bytes());
+        addresses.extend_from_slice(&80_u16.to_be_bytes());
+
+        let (header, _) = parse(&v2_header(0x21, 0x21, &addresses)).unwrap().unwrap();
+
+        assert_eq!(header.source, Some("[2001:db8::1]:5000".parse().unwrap()));
+        assert_eq!(
+            header.destination,
+            Some("[2001:db8::2]:80".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn v2_local_and_invalid() {
+        let (header, consumed) = parse(&v2_header(0x20, 0x00, &[])).unwrap().unwrap();
+        assert_eq!(header.source, None);
+        assert_eq!(consumed, 16);
+
+        // Unknown version, unknown command and truncated addresses.
+        assert!(parse(&v2_header(0x11, 0x11, &[0; 12])).is_err());
+        assert!(parse(&v2_header(0x22, 0x11, &[0; 12])).is_err());
+        assert!(parse(&v2_header(0x21, 0x11, &[0; 4])).is_err());
+    }
+}
@@ -0,0 +1,483 @@
+//! Runs quinn QUIC endpoints on the Folo runtime, enabled by the `quinn` feature.
+
+use crate::{
+    constants,
+    io::{self, PinnedBuffer},
+    net::{EcnCodepoint, ReceivedMessage, UdpSocket},
+    rt::{self, select2},
+};
+use ::quinn::{
+    udp::{self, RecvMeta, Transmit},
+    AsyncTimer, AsyncUdpSocket, Runtime, UdpPoller,
+};
+use futures::future::Either;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::{poll_fn, Future},
+    io::IoSliceMut,
+    mem,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{self, Waker},
+    time::Instant,
+};
+use tracing::{event, Level};
+
+/// How many datagrams may wait to be sent and how many received datagrams may wait for quinn to
+/// pick them up. Received datagrams beyond this are dropped, which QUIC treats as packet loss.
+const QUEUE_CAPACITY: usize = 256;
+
+/// How many datagrams quinn may hand over as one transmit if the operating system supports UDP
+/// segmentation offload. quinn limits this further on its own. This matches quinn-udp on Windows.
+const MAX_TRANSMIT_SEGMENTS: usize = 512;
+
+/// Runs quinn QUIC endpoints on the Folo runtime. Pass it to `quinn::Endpoint::new()` on an async
+/// worker thread. Enabled by the `quinn` feature.
+///
+/// quinn requires its sockets, timers and tasks to be thread-safe, whereas a Folo `UdpSocket` is
+/// bound to the worker thread that created it. The socket of the endpoint is therefore owned by a
+/// task on the worker thread that created the endpoint, which exchanges datagrams with quinn via
+/// queues. The endpoint and its connections may be used from any worker thread - the tasks quinn
+/// spawns to drive them run on the worker thread that spawns them.
+///
+/// Where the operating system supports it, quinn sends multiple datagrams to a peer as one buffer
+/// (see `UdpSocket::set_send_segment_size()`) and learns the ECN codepoint of received datagrams
+/// (see `UdpSocket::set_receive_ecn()`). Datagrams are sent without an ECN codepoint.
+///
+/// # Panics
+///
+/// quinn calls into the runtime when creating, driving and polling endpoints and connections.
+/// These calls panic if made from a thread that is not an async worker thread owned by a Folo
+/// runtime.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuinnRuntime;
+
+impl Runtime for QuinnRuntime {
+    fn new_timer(&self, deadline: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        Box::pin(QuinnTimer {
+            deadline,
+            state: Arc::default(),
+        })
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        // The task keeps running after we drop its join handle.
+        _ = rt::spawn(future);
+    }
+
+    fn wrap_udp_socket(
+        &self,
+        socket: std::net::UdpSocket,
+    ) -> std::io::Result<Arc<dyn AsyncUdpSocket>> {
+        let socket = UdpSocket::from_owned_socket(socket.into()).map_err(io::Error::into_std)?;
+        let local_addr = socket.local_addr().map_err(io::Error::into_std)?;
+
+        // Both are optional - without them, quinn sends one datagram at a time and does not use ECN.
+        let segmentation = socket.send_segment_size().is_ok();
+        _ = socket.set_receive_ecn(true);
+
+        let shared = Arc::new(Mutex::new(SocketState::default()));
+
+        _ = rt::spawn(exchange_datagrams(socket, Arc::clone(&shared)));
+
+        Ok(Arc::new(QuinnSocket {
+            shared,
+            local_addr,
+            segmentation,
+        }))
+    }
+
+    fn now(&self) -> Instant {
+        rt::now()
+    }
+}
+
+/// A timer for quinn. Folo timers are bound to the worker thread that polls them, so the deadline
+/// is awaited by a separate task that wakes up whoever last polled the timer.
+#[derive(Debug)]
+struct QuinnTimer {
+    deadline: Instant,
+    state: Arc<Mutex<TimerState>>,
+}
+
+#[derive(Debug, Default)]
+struct TimerState {
+    waker: Option<Waker>,
+
+    // The deadline of the most recently started task that waits for the timer. A task that wakes
+    // us up before the deadline is harmless (we start another one when polled), so we only start
+    // a new task if the deadline moves earlier than that.
+    awaited_deadline: Option<Instant>,
+}
+
+impl AsyncTimer for QuinnTimer {
+    fn reset(self: Pin<&mut Self>, deadline: Instant) {
+        self.get_mut().deadline = deadline;
+    }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> task::Poll<()> {
+        let deadline = self.deadline;
+
+        if rt::now() >= deadline {
+            return task::Poll::Ready(());
+        }
+
+        let mut state = self.state.lock().expect(constants::POISONED_LOCK);
+        state.waker = Some(cx.waker().clone());
+
+        if state
+            .awaited_deadline
+            .is_none_or(|awaited| awaited > deadline)
+        {
+            state.awaited_deadline = Some(deadline);
+
+            _ = rt::spawn(wake_timer(deadline, Arc::downgrade(&self.state)));
+        }
+
+        task::Poll::Pending
+    }
+}
+
+async fn wake_timer(deadline: Instant, state: Weak<Mutex<TimerState>>) {
+    rt::sleep_until(deadline).await;
+
+    // If the timer is gone, there is nobody to wake up.
+    let Some(state) = state.upgrade() else {
+        return;
+    };
+
+    let waker = {
+        let mut state = state.lock().expect(constants::POISONED_LOCK);
+
+        if state.awaited_deadline == Some(deadline) {
+            state.awaited_deadline = None;
+        }
+
+        state.waker.take()
+    };
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// The socket of a quinn endpoint, as seen by quinn. The `UdpSocket` itself is owned by the task
+/// running `exchange_datagrams()` on the worker thread that created the endpoint.
+struct QuinnSocket {
+    shared: Arc<Mutex<SocketState>>,
+    local_addr: SocketAddr,
+
+    // Whether the operating system supports UDP segmentation offload.
+    segmentation: bool,
+}
+
+#[derive(Default)]
+struct SocketState {
+    received: VecDeque<ReceivedDatagram>,
+    outgoing: VecDeque<OutgoingTransmit>,
+
+    // quinn waits for received datagrams from one task but may wait for room in the outgoing queue
+    // from many (one per connection).
+    receive_waker: Option<Waker>,
+    writable_wakers: Vec<Waker>,
+
+    // The task that owns the socket, waiting for datagrams to send.
+    send_waker: Option<Waker>,
+
+    // The error that ended the task that owns the socket, reported to quinn by every call after.
+    error: Option<std::io::Error>,
+
+    // Set once quinn has dropped the socket, so the task that owns it can end once the outgoing
+    // queue is empty.
+    closed: bool,
+}
+
+struct ReceivedDatagram {
+    contents: Vec<u8>,
+    sender: SocketAddr,
+    ecn: Option<udp::EcnCodepoint>,
+}
+
+struct OutgoingTransmit {
+    contents: Vec<u8>,
+    destination: SocketAddr,
+
+    // If set, the contents are multiple datagrams of this size (the last one may be shorter).
+    segment_size: Option<usize>,
+}
+
+impl SocketState {
+    fn check_error(&self) -> std::io::Result<()> {
+        match &self.error {
+            Some(e) => Err(std::io::Error::new(e.kind(), e.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl AsyncUdpSocket for QuinnSocket {
+    fn create_io_poller(self: Arc<Self>) -> Pin<Box<dyn UdpPoller>> {
+        Box::pin(QuinnPoller { socket: self })
+    }
+
+    fn try_send(&self, transmit: &Transmit) -> std::io::Result<()> {
+        let waker = {
+            let mut state = self.shared.lock().expect(constants::POISONED_LOCK);
+            state.check_error()?;
+
+            if state.outgoing.len() >= QUEUE_CAPACITY {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+
+            state.outgoing.push_back(OutgoingTransmit {
+                contents: transmit.contents.to_vec(),
+                destination: transmit.destination,
+                segment_size: transmit.segment_size,
+            });
+
+            state.send_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut task::Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> task::Poll<std::io::Result<usize>> {
+        let mut state = self.shared.lock().expect(constants::POISONED_LOCK);
+
+        if state.received.is_empty() {
+            state.check_error()?;
+
+            state.receive_waker = Some(cx.waker().clone());
+            return task::Poll::Pending;
+        }
+
+        let mut count = 0;
+
+        for (buf, meta) in bufs.iter_mut().zip(meta) {
+            let Some(datagram) = state.received.pop_front() else {
+                break;
+            };
+
+            // quinn provides buffers for the largest datagram it accepts - anything larger is not
+            // valid QUIC and gets truncated here, to be discarded by quinn.
+            let len = datagram.contents.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram.contents[..len]);
+
+            *meta = RecvMeta {
+                addr: datagram.sender,
+                len,
+                stride: len,
+                ecn: datagram.ecn,
+                dst_ip: None,
+            };
+
+            count += 1;
+        }
+
+        task::Poll::Ready(Ok(count))
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        if self.segmentation {
+            MAX_TRANSMIT_SEGMENTS
+        } else {
+            1
+        }
+    }
+}
+
+impl Drop for QuinnSocket {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.shared.lock().expect(constants::POISONED_LOCK);
+            state.closed = true;
+            state.send_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for QuinnSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuinnSocket")
+            .field("local_addr", &self.local_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Lets a quinn connection wait for room in the outgoing queue of the socket.
+#[derive(Debug)]
+struct QuinnPoller {
+    socket: Arc<QuinnSocket>,
+}
+
+impl UdpPoller for QuinnPoller {
+    fn poll_writable(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context,
+    ) -> task::Poll<std::io::Result<()>> {
+        let mut state = self.socket.shared.lock().expect(constants::POISONED_LOCK);
+        state.check_error()?;
+
+        if state.outgoing.len() < QUEUE_CAPACITY {
+            return task::Poll::Ready(Ok(()));
+        }
+
+        if !state
+            .writable_wakers
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            state.writable_wakers.push(cx.waker().clone());
+        }
+
+        task::Poll::Pending
+    }
+}
+
+/// Owns the socket of a quinn endpoint, receiving datagrams into the received queue and sending
+/// the datagrams from the outgoing queue until quinn drops the socket or the socket fails.
+async fn exchange_datagrams(socket: UdpSocket, shared: Arc<Mutex<SocketState>>) {
+    let Either::Left(error) = select2(
+        receive_datagrams(&socket, &shared),
+        send_datagrams(&socket, &shared),
+    )
+    .await
+    else {
+        return;
+    };
+
+    let wakers = {
+        let mut state = shared.lock().expect(constants::POISONED_LOCK);
+        state.error = Some(error.into_std());
+
+        let mut wakers = mem::take(&mut state.writable_wakers);
+        wakers.extend(state.receive_waker.take());
+        wakers
+    };
+
+    for waker in wakers {
+        waker.wake();
+    }
+}
+
+async fn receive_datagrams(socket: &UdpSocket, shared: &Mutex<SocketState>) -> io::Error {
+    loop {
+        let (buffer, sender, control) =
+            match socket.receive_message_core(PinnedBuffer::from_pool()).await {
+                Ok(received) => received,
+                Err(e) => return e.into_inner(),
+            };
+
+        let message = ReceivedMessage::new(buffer, sender, control);
+
+        let datagram = ReceivedDatagram {
+            contents: message.buffer().as_slice().to_vec(),
+            sender,
+            ecn: message.ecn().and_then(to_quinn_ecn),
+        };
+
+        let waker = {
+            let mut state = shared.lock().expect(constants::POISONED_LOCK);
+
+            // Like a full socket receive buffer, a full queue drops the datagram.
+            if state.received.len() >= QUEUE_CAPACITY {
+                continue;
+            }
+
+            state.received.push_back(datagram);
+            state.receive_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+async fn send_datagrams(socket: &UdpSocket, shared: &Mutex<SocketState>) {
+    // The segment size applies to all sends via the socket, so we only change it when a transmit
+    // needs a different one. One send is in flight at a time, so this never affects another send.
+    let mut current_segment_size = 0;
+
+    while let Some(transmit) = poll_fn(|cx| next_outgoing(shared, cx)).await {
+        let segment_size = match transmit.segment_size {
+            Some(size) if size < transmit.contents.len() => size as u32,
+            _ => 0,
+        };
+
+        // As with sends via a native socket, quinn does not care why a transmit was not sent - it
+        // is the same as the datagrams getting lost on the way.
+        if segment_size != current_segment_size {
+            if let Err(e) = socket.set_send_segment_size(segment_size) {
+                event!(Level::DEBUG, message = "QUIC send segmentation failed", error = ?e);
+                continue;
+            }
+
+            current_segment_size = segment_size;
+        }
+
+        if let Err(e) = socket
+            .send_to_core(
+                PinnedBuffer::from_vec(transmit.contents),
+                transmit.destination,
+            )
+            .await
+        {
+            event!(Level::DEBUG, message = "QUIC datagram send failed", error = ?e.into_inner());
+        }
+    }
+}
+
+/// Takes the next datagram to send, or `None` once quinn has dropped the socket and everything it
+/// sent before that has been taken.
+fn next_outgoing(
+    shared: &Mutex<SocketState>,
+    cx: &mut task::Context,
+) -> task::Poll<Option<OutgoingTransmit>> {
+    let (next, wakers) = {
+        let mut state = shared.lock().expect(constants::POISONED_LOCK);
+
+        match state.outgoing.pop_front() {
+            Some(next) => (next, mem::take(&mut state.writable_wakers)),
+            None if state.closed => return task::Poll::Ready(None),
+            None => {
+                state.send_waker = Some(cx.waker().clone());
+                return task::Poll::Pending;
+            }
+        }
+    };
+
+    for waker in wakers {
+        waker.wake();
+    }
+
+    task::Poll::Ready(Some(next))
+}
+
+fn to_quinn_ecn(ecn: EcnCodepoint) -> Option<udp::EcnCodepoint> {
+    match ecn {
+        EcnCodepoint::NotCapable => None,
+        EcnCodepoint::Ect0 => Some(udp::EcnCodepoint::Ect0),
+        EcnCodepoint::Ect1 => Some(udp::EcnCodepoint::Ect1),
+        EcnCodepoint::CongestionExperienced => Some(udp::EcnCodepoint::Ce),
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    io::{BufferSize, OperationResult, PinnedBuffer},
+    metrics::{Event, EventBuilder, Magnitude},
+    net::UdpSocket,
+};
+use futures::{future::LocalBoxFuture, FutureExt};
+use negative_impl::negative_impl;
+use std::{collections::VecDeque, fmt, future::poll_fn, mem, num::NonZeroUsize, task};
+
+/// Keeps a set of datagram receives continuously outstanding on a `UdpSocket` and yields the
+/// datagrams that have arrived in batches, so a busy receiver handles many datagrams per wakeup
+/// instead of one. Obtain one via `UdpSocket::receive_batches()`.
+///
+/// Each batch contains whatever has arrived by the time it is requested (at least one datagram),
+/// so sparse traffic is not held back waiting for a full batch.
+/// Each completed receive is immediately replaced by a new one into a fresh buffer from the buffer
+/// pool, so the operating system always has somewhere to put incoming datagrams.
+///
+/// `UdpSocket::receive_many()` uses the same machinery with caller-provided buffers, which are not
+/// replaced once used.
+///
+/// Dropping the receiver cancels the outstanding receives. With Registered I/O, receives cannot be
+/// canceled - datagrams arriving for them are delivered to the next receives on the socket.
+pub struct ReceiveBatches<'a> {
+    socket: &'a UdpSocket,
+
+    // The size class of the buffers that replace completed receives. None if completed receives
+    // are not replaced (the receiver only uses the buffers it was created with).
+    size: Option<BufferSize>,
+
+    // Oldest first. Completed slots are taken out of the queue by the next batch.
+    slots: VecDeque<Slot<'a>>,
+}
+
+enum Slot<'a> {
+    Pending(LocalBoxFuture<'a, OperationResult>),
+    Completed(OperationResult),
+}
+
+impl<'a> ReceiveBatches<'a> {
+    pub(super) fn new(socket: &'a UdpSocket, depth: NonZeroUsize, size: BufferSize) -> Self {
+        let buffers = (0..depth.get()).map(|_| PinnedBuffer::from_pool_with_size(size));
+
+        Self::with_buffers(socket, buffers, Some(size))
+    }
+
+    /// Creates a receiver that receives into the provided buffers, in order, and only replaces
+    /// completed receives if a size class is provided.
+    pub(super) fn with_buffers(
+        socket: &'a UdpSocket,
+        buffers: impl IntoIterator<Item = PinnedBuffer>,
+        size: Option<BufferSize>,
+    ) -> Self {
+        let buffers = buffers.into_iter();
+
+        let mut batches = Self {
+            socket,
+            size,
+            slots: VecDeque::with_capacity(buffers.size_hint().0),
+        };
+
+        for buffer in buffers {
+            let receive = batches.start_receive(buffer);
+            batches.slots.push_back(Slot::Pending(receive));
+        }
+
+        batches
+    }
+
+    /// Waits until at least one datagram has arrived and returns all the datagrams that have
+    /// arrived so far, in the order in which the receives were started. Each result holds a buffer
+    /// with the active region set to the datagram, or the error of the receive.
+    pub async fn next_batch(&mut self) -> Vec<OperationResult> {
+        poll_fn(|cx| self.poll_next_batch(cx)).await
+    }
+
+    fn poll_next_batch(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Vec<OperationResult>> {
+        // We poll the receives in order, so the later ones are always started after the earlier
+        // ones, which is the order in which the operating system fills them.
+        for slot in &mut self.slots {
+            if let Slot::Pending(receive) = slot {
+                if let task::Poll::Ready(result) = receive.poll_unpin(cx) {
+                    *slot = Slot::Completed(result);
+                }
+            }
+        }
+
+        let mut batch = Vec::new();
+
+        for slot in mem::take(&mut self.slots) {
+            match slot {
+                Slot::Completed(result) => batch.push(result),
+                pending => self.slots.push_back(pending),
+            }
+        }
+
+        if batch.is_empty() {
+            return task::Poll::Pending;
+        }
+
+        // The replacements are started right away, so there is no gap while the caller processes
+        // the batch. Any that complete immediately are returned by the next batch.
+        if let Some(size) = self.size {
+            for _ in 0..batch.len() {
+                let mut receive = self.start_receive(PinnedBuffer::from_pool_with_size(size));
+
+                let slot = match receive.poll_unpin(cx) {
+                    task::Poll::Ready(result) => Slot::Completed(result),
+                    task::Poll::Pending => Slot::Pending(receive),
+                };
+
+                self.slots.push_back(slot);
+            }
+        }
+
+        BATCH_SIZE.with(|x| x.observe(batch.len() as Magnitude));
+
+        task::Poll::Ready(batch)
+    }
+
+    fn start_receive(&self, buffer: PinnedBuffer) -> LocalBoxFuture<'a, OperationResult> {
+        self.socket.receive_core(buffer).boxed_local()
+    }
+}
+
+impl fmt::Debug for ReceiveBatches<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReceiveBatches")
+            .field("size", &self.size)
+            .field("depth", &self.slots.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[negative_impl]
+impl !Send for ReceiveBatches<'_> {}
+#[negative_impl]
+impl !Sync for ReceiveBatches<'_> {}
+
+thread_local! {
+    static BATCH_SIZE: Event = EventBuilder::new()
+        .name("net_udp_receive_batch_size")
+        .buckets(&[1, 2, 4, 8, 16, 32, 64])
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,168 @@
+use crate::io::{BufferSize, PinnedBuffer};
+
+/// How many consecutive receives must fit into the next smaller size class before we shrink.
+const SHRINK_AFTER_RECEIVES: u32 = 16;
+
+/// Picks the size class of the buffers to receive into on a connection, based on the sizes of the
+/// recent payloads, similar to the receive buffer autotuning of operating systems. Busy connections
+/// get large buffers to keep up their throughput, whereas mostly idle ones hold only small buffers.
+///
+/// A receive that fills the whole buffer suggests there was more data waiting, so we grow to the
+/// next size class right away. We only shrink to the next smaller size class once a run of
+/// receives has fit into it, so a connection with occasional small payloads amid bulk transfers
+/// does not oscillate between size classes.
+///
+/// `TcpConnection::receive_adaptive()` uses one of these for each connection. For other transports,
+/// take buffers via `buffer()` and report back the number of bytes received via `observe()`.
+#[derive(Clone, Debug)]
+pub struct ReceiveBufferSizer {
+    size: BufferSize,
+    min: BufferSize,
+    max: BufferSize,
+
+    // Number of consecutive receives that would have fit into the next smaller size class.
+    fit_smaller: u32,
+}
+
+impl ReceiveBufferSizer {
+    /// Creates a sizer that starts with the smallest size class and may grow up to the largest.
+    pub fn new() -> Self {
+        Self::with_bounds(BufferSize::Small, BufferSize::Large)
+    }
+
+    /// Creates a sizer that starts with the `min` size class and stays between `min` and `max`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is larger than `max`.
+    pub fn with_bounds(min: BufferSize, max: BufferSize) -> Self {
+        assert!(min <= max, "minimum buffer size must not exceed maximum");
+
+        Self {
+            size: min,
+            min,
+            max,
+            fit_smaller: 0,
+        }
+    }
+
+    /// The size class of the buffers to receive into next.
+    pub fn size(&self) -> BufferSize {
+        self.size
+    }
+
+    /// Obtains a buffer of the current size class from the buffer pool.
+    pub fn buffer(&self) -> PinnedBuffer {
+        PinnedBuffer::from_pool_with_size(self.size)
+    }
+
+    /// Updates the size class based on the number of bytes received into a buffer obtained from
+    /// `buffer()`.
+    pub fn observe(&mut self, bytes_received: usize) {
+        if bytes_received >= self.size.capacity() {
+            self.fit_smaller = 0;
+
+            if let Some(larger) = self.size.larger().filter(|larger| *larger <= self.max) {
+                self.size = larger;
+            }
+
+            return;
+        }
+
+        let Some(smaller) = self.size.smaller().filter(|smaller| *smaller >= self.min) else {
+            return;
+        };
+
+        if bytes_received > smaller.capacity() {
+            self.fit_smaller = 0;
+            return;
+        }
+
+        self.fit_smaller += 1;
+
+        if self.fit_smaller >= SHRINK_AFTER_RECEIVES {
+            self.size = smaller;
+            self.fit_smaller = 0;
+        }
+    }
+}
+
+impl Default for ReceiveBufferSizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_when_buffer_filled() {
+        let mut sizer = ReceiveBufferSizer::new();
+        assert_eq!(sizer.size(), BufferSize::Small);
+
+        sizer.observe(BufferSize::Small.capacity());
+        assert_eq!(sizer.size(), BufferSize::Medium);
+
+        sizer.observe(BufferSize::Medium.capacity());
+        assert_eq!(sizer.size(), BufferSize::Large);
+
+        sizer.observe(BufferSize::Large.capacity());
+        assert_eq!(sizer.size(), BufferSize::Large);
+    }
+
+    #[test]
+    fn shrinks_after_run_of_small_payloads() {
+        let mut sizer = ReceiveBufferSizer::with_bounds(BufferSize::Medium, BufferSize::Large);
+        sizer.observe(BufferSize::Medium.capacity());
+        assert_eq!(sizer.size(), BufferSize::Large);
+
+        for _ in 0..SHRINK_AFTER_RECEIVES - 1 {
+            sizer.observe(100);
+        }
+
+        assert_eq!(sizer.size(), BufferSize::Large);
+
+        sizer.observe(100);
+        assert_eq!(sizer.size(), BufferSize::Medium);
+
+        // Never below the minimum.
+        for _ in 0..SHRINK_AFTER_RECEIVES * 2 {
+            sizer.observe(100);
+        }
+
+        assert_eq!(sizer.size(), BufferSize::Medium);
+    }
+
+    #[test]
+    fn larger_payload_interrupts_shrinking() {
+        let mut sizer = ReceiveBufferSizer::new();
+        sizer.observe(BufferSize::Small.capacity());
+        assert_eq!(sizer.size(), BufferSize::Medium);
+
+        for _ in 0..SHRINK_AFTER_RECEIVES - 1 {
+            sizer.observe(100);
+        }
+
+        // Does not fit into a small buffer, so the run starts over.
+        sizer.observe(BufferSize::Small.capacity() + 1);
+
+        for _ in 0..SHRINK_AFTER_RECEIVES - 1 {
+            sizer.observe(100);
+        }
+
+        assert_eq!(sizer.size(), BufferSize::Medium);
+    }
+
+    #[test]
+    fn respects_maximum() {
+        let mut sizer = ReceiveBufferSizer::with_bounds(BufferSize::Small, BufferSize::Medium);
+
+        for _ in 0..3 {
+            sizer.observe(BufferSize::Large.capacity());
+        }
+
+        assert_eq!(sizer.size(), BufferSize::Medium);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    io,
+    metrics::{Event, EventBuilder},
+    net::winsock,
+    util::LowPrecisionInstant,
+};
+use std::{mem, net::SocketAddr, ptr};
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::{
+        Foundation::HANDLE,
+        Networking::WinSock::{
+            FreeAddrInfoExW, GetAddrInfoExCancel, GetAddrInfoExW, ADDRINFOEXW, AF_UNSPEC, NS_ALL,
+            SOCK_STREAM, WSA_ERROR, WSA_IO_PENDING,
+        },
+        System::IO::OVERLAPPED,
+    },
+};
+
+/// Resolves a host name to the addresses of the host, combined with the specified port, via the
+/// name resolution services of the operating system (DNS, the hosts file and so on). IP address
+/// literals are returned as-is.
+///
+/// The lookup is performed asynchronously by the operating system (via `GetAddrInfoExW`), so it
+/// does not block the async worker while waiting for a DNS server to respond, unlike
+/// `std::net::ToSocketAddrs`. Dropping the returned future cancels the lookup.
+///
+/// The addresses are returned in the order of preference determined by the operating system.
+pub async fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    winsock::ensure_initialized();
+
+    let started = LowPrecisionInstant::now();
+
+    let (result_tx, result_rx) = oneshot::channel();
+
+    let lookup = Box::into_raw(Box::new(Lookup {
+        overlapped: OVERLAPPED::default(),
+        name: HSTRING::from(host),
+        port,
+        result: ptr::null_mut(),
+        result_tx: Some(result_tx),
+    }));
+
+    let hints = ADDRINFOEXW {
+        ai_family: AF_UNSPEC.0 as i32,
+        // Without this, we get every address once for each socket type.
+        ai_socktype: SOCK_STREAM.0,
+        ..Default::default()
+    };
+
+    let mut cancel_handle = HANDLE::default();
+
+    // SAFETY: The lookup stays alive until the completion routine takes ownership of it again, or
+    // until we do if the call completes synchronously (in which case the routine is not called).
+    // The hints and the cancel handle are only used during the call itself.
+    let status = unsafe {
+        GetAddrInfoExW(
+            &(*lookup).name,
+            PCWSTR::null(),
+            NS_ALL,
+            None,
+            Some(&hints),
+            &mut (*lookup).result,
+            None,
+            Some(&(*lookup).overlapped),
+            Some(lookup_completed),
+            Some(&mut cancel_handle),
+        )
+    };
+
+    let result = if status == WSA_IO_PENDING.0 {
+        let mut guard = CancelOnDrop {
+            handle: cancel_handle,
+            armed: true,
+        };
+
+        let result = result_rx.await.map_err(|_| {
+            io::Error::Internal("name resolution completed without a result".to_string())
+        });
+
+        guard.armed = false;
+
+        result?
+    } else {
+        // SAFETY: The call completed synchronously, so the completion routine will not be called
+        // and we are responsible for releasing the lookup.
+        let mut lookup = unsafe { Box::from_raw(lookup) };
+        lookup.finish(status as u32)
+    };
+
+    match &result {
+        Ok(_) => RESOLVE_OK_DURATION.with(|x| x.observe_millis(started.elapsed())),
+        Err(_) => RESOLVES_FAILED.with(Event::observe_unit),
+    }
+
+    result
+}
+
+// Facilitates conversion to/from OVERLAPPED in the completion routine.
+#[repr(C)]
+struct Lookup {
+    // NB! This must be the first item in the struct because we treat `*Lookup` and `*OVERLAPPED`
+    // as equivalent!
+    overlapped: OVERLAPPED,
+
+    // Kept alive until the lookup completes, just to be on the safe side.
+    name: HSTRING,
+    port: u16,
+
+    // Filled by the operating system once the lookup completes successfully.
+    result: *mut ADDRINFOEXW,
+
+    result_tx: Option<oneshot::Sender<io::Result<Vec<SocketAddr>>>>,
+}
+
+impl Lookup {
+    fn finish(&mut self, error: u32) -> io::Result<Vec<SocketAddr>> {
+        if error != 0 {
+            return Err(io::Error::Winsock {
+                code: error as i32,
+                detail: WSA_ERROR(error as i32),
+            });
+        }
+
+        let mut addrs = Vec::new();
+        let mut entry = self.result;
+
+        while !entry.is_null() {
+            // SAFETY: The operating system gave us a valid list of entries, which we free below.
+            let info = unsafe { &*entry };
+
+            // SAFETY: The address is valid for the family it declares, as the entry says.
+            if let Some(mut addr) = unsafe { winsock::from_native_socket_addr(info.ai_addr) } {
+                addr.set_port(self.port);
+
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+
+            entry = info.ai_next;
+        }
+
+        if !self.result.is_null() {
+            // SAFETY: We are done with the list and nothing references it anymore.
+            unsafe {
+                FreeAddrInfoExW(Some(
+                    mem::replace(&mut self.result, ptr::null_mut()) as *const _
+                ))
+            };
+        }
+
+        Ok(addrs)
+    }
+}
+
+/// Called by the operating system on one of its own threads once an asynchronous lookup completes,
+/// including when it completes due to being canceled.
+unsafe extern "system" fn lookup_completed(
+    error: u32,
+    _bytes_transferred: u32,
+    overlapped: *const OVERLAPPED,
+) {
+    // SAFETY: This is the lookup we leaked when starting it, which we now take back ownership of.
+    let mut lookup = Box::from_raw(overlapped as *mut Lookup);
+
+    let result = lookup.finish(error);
+
+    // The receiver is gone if the caller stopped waiting, which is fine.
+    _ = lookup
+        .result_tx
+        .take()
+        .expect("a lookup completes only once")
+        .send(result);
+}
+
+/// Cancels the lookup if the caller stops waiting for it. The completion routine still gets called
+/// (with a cancellation error) and releases the lookup.
+struct CancelOnDrop {
+    handle: HANDLE,
+    armed: bool,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if self.armed {
+            // If this fails, the lookup has already completed, which is just as good.
+            // SAFETY: Nothing unsafe here, just an FFI call with a valid handle.
+            unsafe {
+                GetAddrInfoExCancel(&self.handle);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static RESOLVE_OK_DURATION: Event = EventBuilder::new()
+        .name("net_resolve_ok_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static RESOLVES_FAILED: Event = EventBuilder::new()
+        .name("net_resolves_failed")
+        .build()
+        .unwrap();
+}
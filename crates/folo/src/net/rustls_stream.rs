@@ -0,0 +1,256 @@
+use crate::{
+    io::{self, OperationError, OperationResult, OperationResultExt, PinnedBuffer},
+    net::TcpConnection,
+};
+use negative_impl::negative_impl;
+use rustls::{
+    pki_types::ServerName, ClientConfig, ClientConnection, ServerConfig, ServerConnection,
+};
+use std::{
+    future::Future,
+    io::{ErrorKind, Read, Write},
+    sync::Arc,
+};
+
+/// A TLS session over a TCP connection, driven by rustls (a pure-Rust TLS implementation).
+/// Enabled by the `rustls` feature.
+///
+/// The API mirrors that of `TcpConnection` - data is exchanged via `PinnedBuffer` instances, with
+/// the ciphertext moved between rustls and the connection via buffers from the buffer pool.
+///
+/// See also `net::tls` for TLS sessions driven by SChannel, which can use the certificates from the
+/// Windows certificate store.
+pub struct RustlsStream {
+    connection: TcpConnection,
+    tls: rustls::Connection,
+
+    // Ciphertext received from the peer that rustls has not yet accepted, because it first wants
+    // us to read the plaintext it already has.
+    incoming: Option<PinnedBuffer>,
+
+    // The peer closed the connection (whether it first closed the TLS session or not).
+    read_closed: bool,
+}
+
+impl RustlsStream {
+    /// Performs the client side of the TLS handshake on the connection.
+    pub async fn connect(
+        config: Arc<ClientConfig>,
+        server_name: ServerName<'static>,
+        connection: TcpConnection,
+    ) -> io::Result<Self> {
+        let tls = ClientConnection::new(config, server_name).map_err(tls_error)?;
+
+        Self::handshake(tls.into(), connection).await
+    }
+
+    /// Performs the server side of the TLS handshake on the connection.
+    pub async fn accept(config: Arc<ServerConfig>, connection: TcpConnection) -> io::Result<Self> {
+        let tls = ServerConnection::new(config).map_err(tls_error)?;
+
+        Self::handshake(tls.into(), connection).await
+    }
+
+    /// Performs the TLS handshake on the connection, using a rustls session that the caller
+    /// created (e.g. to customize it before the handshake).
+    pub async fn handshake(
+        tls: rustls::Connection,
+        mut connection: TcpConnection,
+    ) -> io::Result<Self> {
+        // Anything the connection received before we took it over belongs to the handshake.
+        let incoming = connection.take_initial_data();
+
+        let mut stream = Self {
+            connection,
+            tls,
+            incoming,
+            read_closed: false,
+        };
+
+        loop {
+            stream.flush().await?;
+
+            if !stream.tls.is_handshaking() {
+                return Ok(stream);
+            }
+
+            if !stream.receive_ciphertext().await? {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+
+    /// Receives the next chunk of decrypted data into the buffer.
+    ///
+    /// The buffer will be returned in the result with the active region set to the bytes read, with
+    /// a length of 0 if the peer closed the TLS session. A peer that closes the connection without
+    /// closing the TLS session first results in an error, as the data may have been truncated.
+    pub async fn receive(&mut self, mut buffer: PinnedBuffer) -> OperationResult {
+        loop {
+            match self.tls.reader().read(buffer.as_mut_slice()) {
+                Ok(len) => {
+                    buffer.set_len(len);
+                    return Ok(buffer);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(OperationError::new(e.into(), buffer)),
+            }
+
+            let result = match self.receive_ciphertext().await {
+                Ok(true) => self.flush().await,
+                Ok(false) => Err(unexpected_eof()),
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                return Err(OperationError::new(e, buffer));
+            }
+        }
+    }
+
+    /// Encrypts and sends a buffer of data to the peer.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        let mut sent = 0;
+
+        while sent < buffer.len() {
+            // rustls takes as much as fits into its send buffer, which we then flush.
+            let result = match self.tls.writer().write(&buffer.as_slice()[sent..]) {
+                Ok(len) => {
+                    sent += len;
+                    self.flush().await
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            if let Err(e) = result {
+                return Err(OperationError::new(e, buffer));
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Closes the TLS session by sending a close_notify alert to the peer, which then sees the end
+    /// of the stream. The TCP connection itself remains open until the stream is dropped.
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.tls.send_close_notify();
+        self.flush().await
+    }
+
+    /// The rustls session, e.g. to inspect the negotiated protocol version or ALPN protocol.
+    pub fn tls(&self) -> &rustls::Connection {
+        &self.tls
+    }
+
+    /// The underlying TCP connection.
+    pub fn get_ref(&self) -> &TcpConnection {
+        &self.connection
+    }
+
+    /// The underlying TCP connection. Sending or receiving data directly on it corrupts the TLS
+    /// session.
+    pub fn get_mut(&mut self) -> &mut TcpConnection {
+        &mut self.connection
+    }
+
+    /// Sends everything rustls wants to send to the peer.
+    async fn flush(&mut self) -> io::Result<()> {
+        while self.tls.wants_write() {
+            let mut buffer = PinnedBuffer::from_pool();
+
+            let mut destination = buffer.as_mut_slice();
+            let len = self.tls.write_tls(&mut destination)?;
+            buffer.set_len(len);
+
+            self.connection.send(buffer).await.into_inner()?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives rustls more ciphertext to process, receiving it from the peer if we have none
+    /// left over. Returns `false` if the peer has closed the connection.
+    async fn receive_ciphertext(&mut self) -> io::Result<bool> {
+        let mut buffer = match self.incoming.take() {
+            Some(buffer) => buffer,
+            None => {
+                if self.read_closed {
+                    return Ok(false);
+                }
+
+                let buffer = self
+                    .connection
+                    .receive(PinnedBuffer::from_pool())
+                    .await
+                    .into_inner()?;
+
+                if buffer.len() == 0 {
+                    self.read_closed = true;
+                    return Ok(false);
+                }
+
+                buffer
+            }
+        };
+
+        while buffer.len() != 0 {
+            let mut source = buffer.as_slice();
+
+            // Reading from a slice cannot fail, so an error means the plaintext buffer of rustls
+            // is full and the caller must read from it before we can give rustls more.
+            let len = self.tls.read_tls(&mut source).unwrap_or_default();
+
+            if len == 0 {
+                self.incoming = Some(buffer);
+                break;
+            }
+
+            buffer.set_len(buffer.len() - len);
+            buffer.set_start(buffer.start() + len);
+
+            self.tls.process_new_packets().map_err(tls_error)?;
+        }
+
+        Ok(true)
+    }
+}
+
+#[negative_impl]
+impl !Send for RustlsStream {}
+#[negative_impl]
+impl !Sync for RustlsStream {}
+
+/// Performs the server side of the TLS handshake via rustls, for use with `TlsServerBuilder` or
+/// `TlsListener`.
+/// Enabled by the `rustls` feature.
+#[derive(Clone, Debug)]
+pub struct RustlsAcceptor {
+    config: Arc<ServerConfig>,
+}
+
+impl RustlsAcceptor {
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        Self { config }
+    }
+}
+
+impl crate::net::AcceptTls for RustlsAcceptor {
+    type Stream = RustlsStream;
+
+    fn accept(&self, connection: TcpConnection) -> impl Future<Output = io::Result<Self::Stream>> {
+        RustlsStream::accept(Arc::clone(&self.config), connection)
+    }
+}
+
+fn tls_error(error: rustls::Error) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(ErrorKind::InvalidData, error))
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        ErrorKind::UnexpectedEof,
+        "connection closed without closing the TLS session",
+    ))
+}
@@ -0,0 +1,264 @@
+use crate::{
+    io,
+    metrics::{Event, EventBuilder},
+    net::{
+        tcp_server::{create_listen_socket, ListenOptions},
+        winsock, SocketOptions, TcpConnection, TcpProfile,
+    },
+    rt::current_async_agent,
+    util::{wait_for_handle, OwnedHandle},
+};
+use std::{mem, net::SocketAddr, num::NonZeroU16, slice, sync::Arc};
+use windows::Win32::{
+    Foundation::HANDLE,
+    Networking::WinSock::{
+        setsockopt, AcceptEx, WSAGetLastError, WSAGetOverlappedResult, WSASocketA, AF_INET,
+        IPPROTO_TCP, SOCKADDR_IN, SOCKET, SOCKET_ERROR, SOCK_STREAM, SOL_SOCKET,
+        SO_UPDATE_ACCEPT_CONTEXT, WSA_FLAG_OVERLAPPED, WSA_IO_PENDING,
+    },
+    System::{
+        Threading::CreateEventW,
+        IO::{CancelIoEx, OVERLAPPED},
+    },
+};
+
+/// A socket listening for TCP connections that is shared by all the async workers of a runtime,
+/// each of which accepts connections on its own thread via `accept()`. Every accepted connection
+/// stays on the worker that accepted it, so a server can follow the thread-per-core model without
+/// forwarding connections between threads. Clone the listener to give each worker its own handle
+/// (e.g. via `spawn_on_all()`) and run an accept loop on every worker.
+///
+/// Every pending `accept()` call is a separate accept operation on the same listen socket. The
+/// operating system hands each incoming connection to one of the pending operations, so connections
+/// are distributed between the workers that are accepting, and a busy worker that is not currently
+/// accepting does not receive new connections.
+///
+/// On Linux, the same effect is achieved by binding one socket per worker with `SO_REUSEPORT`.
+/// Windows has no equivalent - with `SO_REUSEADDR`, all connections go to one of the sockets bound
+/// to the same port and which one is undefined, so the other workers would sit idle. Instead, the
+/// single listen socket is bound with `SO_EXCLUSIVEADDRUSE`, so no other socket (from this or any
+/// other process) can bind the same port and intercept connections.
+///
+/// The listen socket stays open until every clone of the listener is dropped.
+#[derive(Clone, Debug)]
+pub struct SharedTcpListener {
+    socket: Arc<OwnedHandle<SOCKET>>,
+    local_addr: SocketAddr,
+    socket_options: SocketOptions,
+}
+
+impl SharedTcpListener {
+    /// Starts listening for connections on the specified port on all local IPv4 addresses.
+    ///
+    /// Unlike `TcpListener::bind()`, this may be called from any thread, including threads that
+    /// are not owned by a Folo runtime.
+    pub fn bind(port: NonZeroU16) -> io::Result<Self> {
+        // The listen socket is not bound to the I/O driver of any async worker because each worker
+        // needs to accept on it. Accept operations signal an event when they complete instead.
+        let socket = create_listen_socket(ListenOptions {
+            port,
+            fast_open: false,
+            exclusive_address: true,
+        })?;
+
+        let local_addr = winsock::local_addr(*socket)?;
+
+        Ok(Self {
+            socket: Arc::new(socket),
+            local_addr,
+            socket_options: SocketOptions::default(),
+        })
+    }
+
+    /// Sets the socket options to apply to every accepted connection. By default, the operating
+    /// system defaults are used.
+    ///
+    /// Only affects this clone of the listener and clones made from it afterwards.
+    pub fn set_socket_options(&mut self, options: SocketOptions) {
+        self.socket_options = options;
+    }
+
+    /// Applies the socket options of a named profile to every accepted connection, replacing any
+    /// previously set socket options.
+    pub fn set_profile(&mut self, profile: TcpProfile) {
+        self.set_socket_options(profile.socket_options());
+    }
+
+    /// The local address the listener is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Accepts the next connection on the current async worker. Dropping the returned future
+    /// cancels the accept, without losing a connection that has not yet been accepted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn accept(&self) -> io::Result<TcpConnection> {
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let connection_socket = unsafe {
+            OwnedHandle::new(WSASocketA(
+                AF_INET.0 as i32,
+                SOCK_STREAM.0,
+                IPPROTO_TCP.0,
+                None,
+                0,
+                WSA_FLAG_OVERLAPPED,
+            )?)
+        };
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let event = unsafe { OwnedHandle::new(CreateEventW(None, true, false, None)?) };
+
+        // The operating system writes into this while the accept is pending, so it must stay at a
+        // fixed address until the accept completes, even if we are dropped (see PendingAccept). It
+        // is declared after the connection socket, so it is dropped (and the accept finished) first.
+        let mut pending = Box::new(PendingAccept {
+            overlapped: OVERLAPPED {
+                hEvent: *event,
+                ..Default::default()
+            },
+            addresses: [0; ADDRESS_LENGTH * 2],
+            listen_socket: Arc::clone(&self.socket),
+            event,
+            in_progress: false,
+        });
+
+        let mut bytes_received: u32 = 0;
+
+        // SAFETY: The buffer and the OVERLAPPED struct live in the box, which outlives the accept.
+        let accepted = unsafe {
+            AcceptEx(
+                **pending.listen_socket,
+                *connection_socket,
+                pending.addresses.as_mut_ptr() as *mut _,
+                0,
+                ADDRESS_LENGTH as u32,
+                ADDRESS_LENGTH as u32,
+                &mut bytes_received as *mut _,
+                &mut pending.overlapped as *mut _,
+            )
+        };
+
+        if !accepted.as_bool() {
+            // SAFETY: Nothing unsafe here, just an FFI call.
+            let detail = unsafe { WSAGetLastError() };
+
+            if detail != WSA_IO_PENDING {
+                return Err(io::Error::Winsock {
+                    code: SOCKET_ERROR,
+                    detail,
+                });
+            }
+
+            pending.in_progress = true;
+
+            wait_for_handle(*pending.event)?.await;
+
+            let mut flags: u32 = 0;
+
+            // SAFETY: The event is signaled, so the operation is complete and we are only reading
+            // its result from the OVERLAPPED struct that we passed to AcceptEx.
+            let result = unsafe {
+                WSAGetOverlappedResult(
+                    **pending.listen_socket,
+                    &pending.overlapped as *const _,
+                    &mut bytes_received as *mut _,
+                    false,
+                    &mut flags as *mut _,
+                )
+            };
+
+            pending.in_progress = false;
+            result?;
+        }
+
+        // We need to refer to this via pointer, so let's copy it out to an lvalue first.
+        let listen_socket = self.socket.0;
+        // SAFETY: The size is right, so creating the slice is OK. We only use it for the single
+        // call on the next line, so no lifetime concerns - the slice is gone before the storage
+        // goes away in all cases.
+        let listen_socket_as_slice = unsafe {
+            slice::from_raw_parts(
+                &listen_socket as *const _ as *const u8,
+                mem::size_of::<usize>(),
+            )
+        };
+
+        // Inherits the properties of the listen socket, as with any socket accepted via AcceptEx.
+        winsock::to_io_result(unsafe {
+            setsockopt(
+                *connection_socket,
+                SOL_SOCKET,
+                SO_UPDATE_ACCEPT_CONTEXT,
+                Some(listen_socket_as_slice),
+            )
+        })?;
+
+        self.socket_options.apply(*connection_socket)?;
+
+        // Only the accepted connection is bound to the I/O driver of the current worker, which is
+        // what keeps it on this worker for the rest of its life.
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*connection_socket))?;
+
+        CONNECTIONS_ACCEPTED.with(Event::observe_unit);
+
+        Ok(TcpConnection::new(connection_socket, None))
+    }
+}
+
+/// Length of each of the local and remote address in the AcceptEx output buffer.
+const ADDRESS_LENGTH: usize = mem::size_of::<SOCKADDR_IN>() + 16;
+
+/// The state of an accept operation, which the operating system may still be writing into after
+/// the future that started it is dropped. In that case, we cancel the operation and wait for it to
+/// complete before releasing the state.
+struct PendingAccept {
+    overlapped: OVERLAPPED,
+    addresses: [u8; ADDRESS_LENGTH * 2],
+
+    listen_socket: Arc<OwnedHandle<SOCKET>>,
+    event: OwnedHandle<HANDLE>,
+
+    in_progress: bool,
+}
+
+impl Drop for PendingAccept {
+    fn drop(&mut self) {
+        if !self.in_progress {
+            return;
+        }
+
+        let mut bytes_received: u32 = 0;
+        let mut flags: u32 = 0;
+
+        // SAFETY: The operation may still be in progress, so the OVERLAPPED struct is valid. We
+        // ignore the result of the cancellation because the only expected failure is
+        // ERROR_NOT_FOUND, which means that the operation has already completed. Either way, the
+        // wait for the result returns promptly, after which nothing refers to our state anymore.
+        unsafe {
+            _ = CancelIoEx(
+                HANDLE(self.listen_socket.0 as *mut _),
+                Some(&self.overlapped as *const _),
+            );
+
+            _ = WSAGetOverlappedResult(
+                **self.listen_socket,
+                &self.overlapped as *const _,
+                &mut bytes_received as *mut _,
+                true,
+                &mut flags as *mut _,
+            );
+        }
+    }
+}
+
+thread_local! {
+    static CONNECTIONS_ACCEPTED: Event = EventBuilder::new()
+        .name("net_shared_tcp_listener_connections_accepted")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,318 @@
+use crate::{
+    io,
+    net::{
+        drainer, socket_pool::SocketReuse, winsock, ConnectionPermit, ProxyHeader, TrafficCounters,
+    },
+    rt::{spawn_sync, SynchronousTaskType},
+    util::OwnedHandle,
+};
+use std::{
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    mem,
+    os::windows::io::{AsRawHandle, FromRawHandle},
+    ptr, slice,
+};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{ERROR_PIPE_CONNECTED, GENERIC_ALL, HANDLE},
+        Networking::WinSock::{WSADuplicateSocketW, SOCKET, WSAPROTOCOL_INFOW},
+        Security::{
+            AddAccessAllowedAce, GetLengthSid, GetTokenInformation, InitializeAcl,
+            InitializeSecurityDescriptor, SetSecurityDescriptorDacl, TokenUser, ACCESS_ALLOWED_ACE,
+            ACL, ACL_REVISION, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, SECURITY_DESCRIPTOR,
+            TOKEN_QUERY, TOKEN_USER,
+        },
+        Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX},
+        System::{
+            Pipes::{
+                ConnectNamedPipe, CreateNamedPipeW, GetNamedPipeClientProcessId,
+                GetNamedPipeServerProcessId, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_TYPE_BYTE, PIPE_WAIT,
+            },
+            Threading::{GetCurrentProcess, OpenProcessToken},
+        },
+    },
+};
+
+/// A socket duplicated for use by a specific other process (via `WSADuplicateSocketW`), in a form
+/// that can be transferred to that process as bytes and reconstructed there.
+///
+/// This enables zero-downtime binary swaps: the old process hands its live connections to the new
+/// process, which continues serving them without the peers noticing.
+#[derive(Clone, Copy)]
+pub struct SocketHandoff {
+    protocol_info: WSAPROTOCOL_INFOW,
+}
+
+impl SocketHandoff {
+    /// Size of the serialized form, in bytes.
+    pub const SIZE: usize = mem::size_of::<WSAPROTOCOL_INFOW>();
+
+    /// Duplicates the socket for use by the specified process.
+    pub(crate) fn duplicate(socket: SOCKET, process_id: u32) -> io::Result<Self> {
+        let mut protocol_info = WSAPROTOCOL_INFOW::default();
+
+        // SAFETY: We pass a valid pointer to a structure of the expected type.
+        winsock::to_io_result(unsafe {
+            WSADuplicateSocketW(socket, process_id, &mut protocol_info as *mut _)
+        })?;
+
+        Ok(Self { protocol_info })
+    }
+
+    pub(crate) fn protocol_info(&self) -> &WSAPROTOCOL_INFOW {
+        &self.protocol_info
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: The protocol info is plain old data without padding-sensitive invariants.
+        unsafe { slice::from_raw_parts(&self.protocol_info as *const _ as *const u8, Self::SIZE) }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() != Self::SIZE {
+            return Err(io::Error::InvalidOptions(format!(
+                "socket handoff must be exactly {} bytes but was {}",
+                Self::SIZE,
+                bytes.len()
+            )));
+        }
+
+        // SAFETY: Length checked above and any bit pattern is a valid protocol info structure (the
+        // OS validates the contents when it is used).
+        let protocol_info =
+            unsafe { ptr::read_unaligned(bytes.as_ptr() as *const WSAPROTOCOL_INFOW) };
+
+        Ok(Self { protocol_info })
+    }
+}
+
+impl std::fmt::Debug for SocketHandoff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocketHandoff")
+            .field("dwCatalogEntryId", &self.protocol_info.dwCatalogEntryId)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A connection detached from the async worker thread that owned it, in a form that can be sent to
+/// another thread of the same process and turned back into a connection there via
+/// `TcpConnection::from_thread_handoff()`. Created via `TcpConnection::duplicate_for_thread()`.
+///
+/// Dropping the handoff closes the connection.
+#[derive(Debug)]
+pub struct ThreadHandoff {
+    pub(crate) socket: OwnedHandle<SOCKET>,
+    pub(crate) read_closed: bool,
+    pub(crate) write_closed: bool,
+    pub(crate) connection_permit: Option<ConnectionPermit>,
+    pub(crate) drain_registration: Option<drainer::Registration>,
+
+    // Copied out of the pinned buffer, as buffers belong to the buffer pool of their thread.
+    pub(crate) initial_data: Option<Box<[u8]>>,
+
+    pub(crate) proxy_header: Option<ProxyHeader>,
+    pub(crate) reuse: Option<SocketReuse>,
+
+    // Carried over so the statistics of the connection survive the move.
+    pub(crate) traffic: TrafficCounters,
+}
+
+/// Sends a socket to the process serving the named pipe, which must be the expected receiver
+/// process. The socket is duplicated for that process and the function only returns once the
+/// receiver has confirmed that it has taken over the socket, after which the caller may close its
+/// own copy.
+pub(crate) async fn send_via_pipe(
+    socket: SOCKET,
+    pipe_name: &str,
+    receiver_process_id: u32,
+) -> io::Result<()> {
+    let pipe_name = pipe_name.to_string();
+
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<()> {
+        let mut pipe = OpenOptions::new().read(true).write(true).open(&pipe_name)?;
+
+        let mut process_id: u32 = 0;
+
+        // SAFETY: The handle is valid for the lifetime of the File and we pass a valid pointer.
+        unsafe {
+            GetNamedPipeServerProcessId(HANDLE(pipe.as_raw_handle()), &mut process_id as *mut _)?
+        };
+
+        // Anyone could have created a pipe with this name, so we make sure we are not handing the
+        // connection to an impostor.
+        verify_peer_process_id(process_id, receiver_process_id)?;
+
+        let handoff = SocketHandoff::duplicate(socket, process_id)?;
+        pipe.write_all(handoff.as_bytes())?;
+
+        let mut ack = [0u8; 1];
+        pipe.read_exact(&mut ack)?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Creates the named pipe and waits for the expected sender process to connect and hand over a
+/// socket. The returned pipe must be given to `acknowledge_via_pipe()` once the socket has been
+/// reconstructed.
+///
+/// The pipe is only accessible to the user the current process runs as. If the name is already
+/// taken by another pipe, we fail instead of sharing the name with it.
+pub(crate) async fn receive_via_pipe(
+    pipe_name: &str,
+    sender_process_id: u32,
+) -> io::Result<(SocketHandoff, File)> {
+    let pipe_name = HSTRING::from(pipe_name);
+
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        let security = CurrentUserOnlySecurity::new()?;
+
+        // SAFETY: We immediately transfer ownership of the handle to a File, which closes it. The
+        // security attributes remain valid until the call returns.
+        let mut pipe = unsafe {
+            let handle = CreateNamedPipeW(
+                &pipe_name,
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                SocketHandoff::SIZE as u32,
+                SocketHandoff::SIZE as u32,
+                0,
+                Some(security.attributes()),
+            );
+
+            if handle.is_invalid() {
+                return Err(windows::core::Error::from_win32().into());
+            }
+
+            File::from_raw_handle(handle.0)
+        };
+
+        // SAFETY: The handle is valid for the lifetime of the File.
+        match unsafe { ConnectNamedPipe(HANDLE(pipe.as_raw_handle()), None) } {
+            Ok(()) => {}
+            // The client connected between creation and our wait, which is just as good.
+            Err(e) if e.code() == ERROR_PIPE_CONNECTED.to_hresult() => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut process_id: u32 = 0;
+
+        // SAFETY: The handle is valid for the lifetime of the File and we pass a valid pointer.
+        unsafe {
+            GetNamedPipeClientProcessId(HANDLE(pipe.as_raw_handle()), &mut process_id as *mut _)?
+        };
+
+        // Other processes of the same user can still connect, so we only accept the socket from
+        // the expected sender. Dropping the pipe disconnects anyone else.
+        verify_peer_process_id(process_id, sender_process_id)?;
+
+        let mut bytes = vec![0u8; SocketHandoff::SIZE];
+        pipe.read_exact(&mut bytes)?;
+
+        Ok((SocketHandoff::from_bytes(&bytes)?, pipe))
+    })
+    .await
+}
+
+/// Tells the sender that we have taken over the socket, so it is free to close its own copy.
+pub(crate) async fn acknowledge_via_pipe(mut pipe: File) -> io::Result<()> {
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<()> {
+        pipe.write_all(&[1])?;
+        pipe.flush()?;
+        Ok(())
+    })
+    .await
+}
+
+// Defined in SystemServices, which we do not otherwise need.
+const SECURITY_DESCRIPTOR_REVISION: u32 = 1;
+
+fn verify_peer_process_id(actual: u32, expected: u32) -> io::Result<()> {
+    if actual != expected {
+        return Err(io::Error::StdIo(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("the other end of the pipe is process {actual} instead of process {expected}"),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Security attributes that grant access only to the user the current process runs as. Without
+/// these, a named pipe gets the default security, which lets other users connect to it.
+struct CurrentUserOnlySecurity {
+    // The SID referenced by the ACL is stored here. We use u64 to align the buffers.
+    _token_user: Vec<u64>,
+    _acl: Vec<u64>,
+    _descriptor: Box<SECURITY_DESCRIPTOR>,
+    attributes: SECURITY_ATTRIBUTES,
+}
+
+impl CurrentUserOnlySecurity {
+    fn new() -> io::Result<Self> {
+        // SAFETY: All the buffers we pass are valid and sized as the functions require. The
+        // pointers stored in the result point to heap allocations that move with the result.
+        unsafe {
+            let mut token = HANDLE::default();
+            OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token as *mut _)?;
+            let token = OwnedHandle::from(token);
+
+            let mut token_user_len: u32 = 0;
+
+            // This fails because we provide no buffer but tells us how big the buffer must be.
+            _ = GetTokenInformation(*token, TokenUser, None, 0, &mut token_user_len as *mut _);
+
+            let mut token_user = vec![0u64; (token_user_len as usize).div_ceil(8)];
+
+            GetTokenInformation(
+                *token,
+                TokenUser,
+                Some(token_user.as_mut_ptr() as *mut c_void),
+                token_user_len,
+                &mut token_user_len as *mut _,
+            )?;
+
+            let sid = (*(token_user.as_ptr() as *const TOKEN_USER)).User.Sid;
+
+            // The ACE structure ends with the first u32 of the SID, so we do not count that twice.
+            let acl_len = mem::size_of::<ACL>() + mem::size_of::<ACCESS_ALLOWED_ACE>()
+                - mem::size_of::<u32>()
+                + GetLengthSid(sid) as usize;
+
+            let mut acl = vec![0u64; acl_len.div_ceil(8)];
+            let acl_ptr = acl.as_mut_ptr() as *mut ACL;
+
+            InitializeAcl(acl_ptr, acl_len as u32, ACL_REVISION)?;
+            AddAccessAllowedAce(acl_ptr, ACL_REVISION, GENERIC_ALL.0, sid)?;
+
+            let mut descriptor = Box::new(SECURITY_DESCRIPTOR::default());
+            let descriptor_ptr = PSECURITY_DESCRIPTOR(&mut *descriptor as *mut _ as *mut c_void);
+
+            InitializeSecurityDescriptor(descriptor_ptr, SECURITY_DESCRIPTOR_REVISION)?;
+            SetSecurityDescriptorDacl(descriptor_ptr, true, Some(acl_ptr), false)?;
+
+            let attributes = SECURITY_ATTRIBUTES {
+                nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor_ptr.0,
+                bInheritHandle: false.into(),
+            };
+
+            Ok(Self {
+                _token_user: token_user,
+                _acl: acl,
+                _descriptor: descriptor,
+                attributes,
+            })
+        }
+    }
+
+    fn attributes(&self) -> *const SECURITY_ATTRIBUTES {
+        &self.attributes
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{io, net::winsock};
+use std::time::Duration;
+use std::{mem, ptr};
+use windows::Win32::Networking::WinSock::{
+    tcp_keepalive, WSAIoctl, IPPROTO_TCP, LINGER, SIO_KEEPALIVE_VALS, SOCKET, SOL_SOCKET,
+    SO_KEEPALIVE, SO_LINGER, SO_OOBINLINE, SO_RCVBUF, SO_SNDBUF, TCP_KEEPCNT, TCP_KEEPIDLE, TCP_KEEPINTVL,
+    TCP_NODELAY,
+};
+
+/// A set of socket options to apply to a TCP socket. Options that are not set keep the operating
+/// system default.
+///
+/// Used to configure connections up-front via `ConnectOptions` and `TcpServerBuilder`. Individual
+/// options of an existing `TcpConnection` can also be inspected and changed directly on it.
+/// Presets for common kinds of traffic are available via `TcpProfile`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SocketOptions {
+    nodelay: Option<bool>,
+    keepalive: Option<bool>,
+    keepalive_settings: Option<KeepaliveSettings>,
+    linger: Option<Option<Duration>>,
+    receive_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    oob_inline: Option<bool>,
+}
+
+impl SocketOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets TCP_NODELAY, disabling (if true) the Nagle algorithm.
+    pub fn nodelay(mut self, value: bool) -> Self {
+        self.nodelay = Some(value);
+        self
+    }
+
+    /// Sets SO_KEEPALIVE, enabling (if true) TCP keepalive packets with the operating system
+    /// default timing.
+    pub fn keepalive(mut self, value: bool) -> Self {
+        self.keepalive = Some(value);
+        self
+    }
+
+    /// Enables TCP keepalive packets with custom timing (see `KeepaliveSettings`). A later
+    /// `keepalive(false)` disables keepalive again.
+    pub fn keepalive_settings(mut self, value: KeepaliveSettings) -> Self {
+        self.keepalive = Some(true);
+        self.keepalive_settings = Some(value);
+        self
+    }
+
+    /// Sets SO_LINGER. With `Some(timeout)`, closing the socket waits up to the timeout for unsent
+    /// data to be delivered (a zero timeout resets the connection on close). With `None`, closing
+    /// returns immediately and the operating system delivers unsent data in the background.
+    ///
+    /// The timeout has a resolution of seconds.
+    pub fn linger(mut self, value: Option<Duration>) -> Self {
+        self.linger = Some(value);
+        self
+    }
+
+    /// Sets SO_RCVBUF, the size of the operating system receive buffer, in bytes.
+    pub fn receive_buffer_size(mut self, value: usize) -> Self {
+        self.receive_buffer_size = Some(value);
+        self
+    }
+
+    /// Sets SO_SNDBUF, the size of the operating system send buffer, in bytes.
+    pub fn send_buffer_size(mut self, value: usize) -> Self {
+        self.send_buffer_size = Some(value);
+        self
+    }
+
+    /// Sets SO_OOBINLINE. If true, urgent data sent by the peer (see
+    /// `TcpConnection::send_urgent()`) is delivered in the normal data stream instead of via
+    /// `TcpConnection::receive_urgent()`.
+    pub fn oob_inline(mut self, value: bool) -> Self {
+        self.oob_inline = Some(value);
+        self
+    }
+
+    /// Applies all the options that have been set to the socket, stopping at the first failure.
+    pub(crate) fn apply(&self, socket: SOCKET) -> io::Result<()> {
+        if let Some(value) = self.nodelay {
+            set_nodelay(socket, value)?;
+        }
+
+        if let Some(value) = self.keepalive {
+            set_keepalive(socket, value)?;
+        }
+
+        if let (Some(true), Some(value)) = (self.keepalive, self.keepalive_settings) {
+            set_keepalive_settings(socket, &value)?;
+        }
+
+        if let Some(value) = self.linger {
+            set_linger(socket, value)?;
+        }
+
+        if let Some(value) = self.receive_buffer_size {
+            set_receive_buffer_size(socket, value)?;
+        }
+
+        if let Some(value) = self.send_buffer_size {
+            set_send_buffer_size(socket, value)?;
+        }
+
+        if let Some(value) = self.oob_inline {
+            set_oob_inline(socket, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn nodelay(socket: SOCKET) -> io::Result<bool> {
+    winsock::get_bool_option(socket, IPPROTO_TCP.0, TCP_NODELAY)
+}
+
+pub(crate) fn set_nodelay(socket: SOCKET, value: bool) -> io::Result<()> {
+    winsock::set_bool_option(socket, IPPROTO_TCP.0, TCP_NODELAY, value)
+}
+
+pub(crate) fn keepalive(socket: SOCKET) -> io::Result<bool> {
+    winsock::get_bool_option(socket, SOL_SOCKET, SO_KEEPALIVE)
+}
+
+pub(crate) fn set_keepalive(socket: SOCKET, value: bool) -> io::Result<()> {
+    winsock::set_bool_option(socket, SOL_SOCKET, SO_KEEPALIVE, value)
+}
+
+/// The timing of TCP keepalive packets.
+///
+/// The operating system defaults (2 hours of idle time before the first probe) are too long to
+/// keep connections alive through NATs and firewalls, which commonly drop idle flows after a few
+/// minutes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeepaliveSettings {
+    /// How long the connection must be idle before the first keepalive probe is sent. Has a
+    /// resolution of milliseconds.
+    pub time: Duration,
+
+    /// How long to wait for a response to a probe before sending the next one. Has a resolution of
+    /// milliseconds.
+    pub interval: Duration,
+
+    /// How many probes in a row may go unanswered before the connection is considered dead and
+    /// any further operations on it fail. `None` keeps the operating system default (10).
+    pub probe_count: Option<u8>,
+}
+
+pub(crate) fn keepalive_settings(socket: SOCKET) -> io::Result<KeepaliveSettings> {
+    let time: u32 = winsock::get_option(socket, IPPROTO_TCP.0, TCP_KEEPIDLE)?;
+    let interval: u32 = winsock::get_option(socket, IPPROTO_TCP.0, TCP_KEEPINTVL)?;
+    let probe_count: u32 = winsock::get_option(socket, IPPROTO_TCP.0, TCP_KEEPCNT)?;
+
+    Ok(KeepaliveSettings {
+        time: Duration::from_secs(time as u64),
+        interval: Duration::from_secs(interval as u64),
+        probe_count: Some(probe_count.try_into().unwrap_or(u8::MAX)),
+    })
+}
+
+pub(crate) fn set_keepalive_settings(socket: SOCKET, value: &KeepaliveSettings) -> io::Result<()> {
+    let values = tcp_keepalive {
+        onoff: 1,
+        keepalivetime: keepalive_duration_to_native(value.time)?,
+        keepaliveinterval: keepalive_duration_to_native(value.interval)?,
+    };
+
+    let mut bytes_returned: u32 = 0;
+
+    // SAFETY: We pass a valid input buffer of the size expected for the control code, which has
+    // no output.
+    winsock::to_io_result(unsafe {
+        WSAIoctl(
+            socket,
+            SIO_KEEPALIVE_VALS,
+            Some(ptr::from_ref(&values).cast()),
+            mem::size_of::<tcp_keepalive>() as u32,
+            None,
+            0,
+            &mut bytes_returned as *mut _,
+            None,
+            None,
+        )
+    })?;
+
+    if let Some(probe_count) = value.probe_count {
+        winsock::set_option(socket, IPPROTO_TCP.0, TCP_KEEPCNT, &u32::from(probe_count))?;
+    }
+
+    Ok(())
+}
+
+fn keepalive_duration_to_native(value: Duration) -> io::Result<u32> {
+    value.as_millis().try_into().map_err(|_| {
+        io::Error::InvalidOptions(format!(
+            "keepalive time and interval must be at most {} milliseconds",
+            u32::MAX
+        ))
+    })
+}
+
+pub(crate) fn linger(socket: SOCKET) -> io::Result<Option<Duration>> {
+    let linger: LINGER = winsock::get_option(socket, SOL_SOCKET, SO_LINGER)?;
+
+    Ok((linger.l_onoff != 0).then(|| Duration::from_secs(linger.l_linger as u64)))
+}
+
+pub(crate) fn set_linger(socket: SOCKET, value: Option<Duration>) -> io::Result<()> {
+    let linger = match value {
+        Some(timeout) => LINGER {
+            l_onoff: 1,
+            l_linger: timeout.as_secs().try_into().map_err(|_| {
+                io::Error::InvalidOptions(format!(
+                    "linger timeout must be at most {} seconds",
+                    u16::MAX
+                ))
+            })?,
+        },
+        None => LINGER::default(),
+    };
+
+    winsock::set_option(socket, SOL_SOCKET, SO_LINGER, &linger)
+}
+
+pub(crate) fn receive_buffer_size(socket: SOCKET) -> io::Result<usize> {
+    winsock::get_option::<i32>(socket, SOL_SOCKET, SO_RCVBUF).map(|value| value as usize)
+}
+
+pub(crate) fn set_receive_buffer_size(socket: SOCKET, value: usize) -> io::Result<()> {
+    winsock::set_option(
+        socket,
+        SOL_SOCKET,
+        SO_RCVBUF,
+        &buffer_size_to_native(value)?,
+    )
+}
+
+pub(crate) fn send_buffer_size(socket: SOCKET) -> io::Result<usize> {
+    winsock::get_option::<i32>(socket, SOL_SOCKET, SO_SNDBUF).map(|value| value as usize)
+}
+
+pub(crate) fn set_send_buffer_size(socket: SOCKET, value: usize) -> io::Result<()> {
+    winsock::set_option(
+        socket,
+        SOL_SOCKET,
+        SO_SNDBUF,
+        &buffer_size_to_native(value)?,
+    )
+}
+
+pub(crate) fn oob_inline(socket: SOCKET) -> io::Result<bool> {
+    winsock::get_bool_option(socket, SOL_SOCKET, SO_OOBINLINE)
+}
+
+pub(crate) fn set_oob_inline(socket: SOCKET, value: bool) -> io::Result<()> {
+    winsock::set_bool_option(socket, SOL_SOCKET, SO_OOBINLINE, value)
+}
+
+fn buffer_size_to_native(value: usize) -> io::Result<i32> {
+    value.try_into().map_err(|_| {
+        io::Error::InvalidOptions(format!("buffer size must be at most {} bytes", i32::MAX))
+    })
+}
@@ -0,0 +1,44 @@
+use crate::{io, net::TcpConnection};
+use std::{
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    os::windows::io::OwnedSocket,
+};
+
+/// Creates a pair of TCP connections over the loopback interface, connected to each other and owned
+/// by the current async worker thread. Whatever is sent on one connection is received on the
+/// other, so components can be wired together in-process (e.g. in tests) while exercising the same
+/// I/O path as with remote peers.
+///
+/// Both connections have Nagle's algorithm disabled, as they are meant for low-latency local
+/// communication.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn socket_pair() -> io::Result<(TcpConnection, TcpConnection)> {
+    // Connecting over the loopback interface completes immediately without the listener having to
+    // accept, so we can do all of this synchronously without blocking for any meaningful time.
+    let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?;
+    let first = TcpStream::connect(listener.local_addr()?)?;
+
+    // Some other process on the machine may have connected to the listener before us, so we only
+    // accept our own connection. The listener is closed immediately after, so this is only a
+    // guard against accidents, not against deliberate interference.
+    let expected_peer = first.local_addr()?;
+
+    let second = loop {
+        let (stream, peer) = listener.accept()?;
+
+        if peer == expected_peer {
+            break stream;
+        }
+    };
+
+    first.set_nodelay(true)?;
+    second.set_nodelay(true)?;
+
+    Ok((
+        TcpConnection::from_owned_socket(OwnedSocket::from(first))?,
+        TcpConnection::from_owned_socket(OwnedSocket::from(second))?,
+    ))
+}
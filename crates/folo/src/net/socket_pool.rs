@@ -0,0 +1,255 @@
+use crate::{
+    constants,
+    io::{self, OperationResultExt, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::winsock,
+    rt::{current_async_agent, current_runtime, defer_async, tuning},
+    util::OwnedHandle,
+};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+use windows::{
+    core::Free,
+    Win32::Networking::WinSock::{ADDRESS_FAMILY, AF_INET, SOCKET, TF_REUSE_SOCKET},
+};
+
+/// The default maximum number of idle sockets each async worker keeps per address family (and for
+/// each server that reuses sockets), which can be adjusted via
+/// `RuntimeTuning::max_pooled_sockets()`. Sockets closed while the pool is full are released
+/// instead of being recycled.
+pub const MAX_POOLED_SOCKETS: usize = 256;
+
+/// Where the socket of a connection goes once the connection is closed.
+#[derive(Clone, Debug)]
+pub(crate) enum SocketReuse {
+    /// Into the socket pool of the current async worker, for a future `ConnectEx` on that worker.
+    Connect(ADDRESS_FAMILY),
+
+    /// Into the accept socket pool of the server that accepted the connection, for a future
+    /// `AcceptEx` of that server.
+    Accept(Arc<AcceptSocketPool>),
+}
+
+impl SocketReuse {
+    fn is_full(&self) -> bool {
+        match self {
+            SocketReuse::Connect(family) => is_full(*family),
+            SocketReuse::Accept(pool) => pool.is_full(),
+        }
+    }
+
+    fn put(&self, socket: OwnedHandle<SOCKET>) {
+        match self {
+            SocketReuse::Connect(family) => {
+                POOL.with_borrow_mut(|pool| pool.sockets(*family).push(socket))
+            }
+            SocketReuse::Accept(pool) => pool.put(socket),
+        }
+    }
+}
+
+/// Sockets of closed connections that have been disconnected via `DisconnectEx` with
+/// `TF_REUSE_SOCKET`, ready to be used for a new `ConnectEx`.
+///
+/// Each async worker has its own pool because the sockets remain bound to the completion port of
+/// the worker that used them and can only be used for I/O on that worker. They also remain bound
+/// to a local address of their address family, so we keep the families apart.
+#[derive(Default)]
+struct SocketPool {
+    ipv4: Vec<OwnedHandle<SOCKET>>,
+    ipv6: Vec<OwnedHandle<SOCKET>>,
+}
+
+impl SocketPool {
+    fn sockets(&mut self, family: ADDRESS_FAMILY) -> &mut Vec<OwnedHandle<SOCKET>> {
+        if family == AF_INET {
+            &mut self.ipv4
+        } else {
+            &mut self.ipv6
+        }
+    }
+}
+
+impl Drop for SocketPool {
+    fn drop(&mut self) {
+        // The pool is only dropped when the worker thread exits, at which point the runtime may be
+        // gone, so we close the sockets right here instead of via the usual OwnedHandle path.
+        for socket in self.ipv4.drain(..).chain(self.ipv6.drain(..)) {
+            let mut socket = SOCKET::from(socket);
+
+            // SAFETY: We own the socket and nothing else is using it.
+            unsafe { socket.free() };
+        }
+    }
+}
+
+/// Sockets of closed connections accepted by a `TcpServer`, disconnected via `DisconnectEx` with
+/// `TF_REUSE_SOCKET` and ready to be used for a new `AcceptEx`.
+///
+/// `AcceptEx` is issued by the TCP dispatcher but the sockets remain bound to the completion port of
+/// the async worker that handled the previous connection, so the pool is shared between threads and
+/// keeps a separate list of sockets for each worker. A connection accepted into a pooled socket has
+/// to be handled by the worker that owns the socket.
+#[derive(Debug, Default)]
+pub(crate) struct AcceptSocketPool {
+    // Indexed by `AsyncAgent::worker_index()`, grown as needed.
+    sockets_by_worker: Mutex<Vec<Vec<OwnedHandle<SOCKET>>>>,
+}
+
+impl AcceptSocketPool {
+    /// Takes an idle socket from the pool of any worker, if there is one, together with the index
+    /// of the worker whose completion port the socket is bound to.
+    pub(crate) fn take(&self) -> Option<(OwnedHandle<SOCKET>, usize)> {
+        let mut sockets_by_worker = self
+            .sockets_by_worker
+            .lock()
+            .expect(constants::POISONED_LOCK);
+
+        let socket = sockets_by_worker
+            .iter_mut()
+            .enumerate()
+            .find_map(|(worker_index, sockets)| Some((sockets.pop()?, worker_index)));
+
+        match socket {
+            Some(_) => SOCKETS_REUSED.with(Event::observe_unit),
+            None => SOCKET_POOL_MISSES.with(Event::observe_unit),
+        }
+
+        socket
+    }
+
+    fn is_full(&self) -> bool {
+        let Some(worker_index) = current_async_agent::with(|x| x.worker_index()) else {
+            // Only async workers can take sockets back from the dispatcher, so there is no room for
+            // sockets bound to anything else.
+            return true;
+        };
+
+        let max = tuning::with_current(|x| x.max_pooled_sockets);
+
+        self.sockets_by_worker
+            .lock()
+            .expect(constants::POISONED_LOCK)
+            .get(worker_index)
+            .is_some_and(|sockets| sockets.len() >= max)
+    }
+
+    fn put(&self, socket: OwnedHandle<SOCKET>) {
+        let worker_index = current_async_agent::with(|x| x.worker_index())
+            .expect("we only recycle sockets on async workers with an index");
+
+        let mut sockets_by_worker = self
+            .sockets_by_worker
+            .lock()
+            .expect(constants::POISONED_LOCK);
+
+        if sockets_by_worker.len() <= worker_index {
+            sockets_by_worker.resize_with(worker_index + 1, Vec::new);
+        }
+
+        sockets_by_worker[worker_index].push(socket);
+    }
+}
+
+/// Takes an idle socket of the specified address family from the pool of the current async worker,
+/// if there is one. The socket is already bound to a local address and to the completion port of
+/// the current worker, ready for `ConnectEx`.
+pub(crate) fn take(family: ADDRESS_FAMILY) -> Option<OwnedHandle<SOCKET>> {
+    let socket = POOL.with_borrow_mut(|pool| pool.sockets(family).pop());
+
+    match socket {
+        Some(_) => SOCKETS_REUSED.with(Event::observe_unit),
+        None => SOCKET_POOL_MISSES.with(Event::observe_unit),
+    }
+
+    socket
+}
+
+/// Recycles the socket of a connection that is being closed. The socket is disconnected in the
+/// background and added to the pool it belongs to once that completes.
+///
+/// The socket is simply released instead if the pool is full, if it is still shared with someone
+/// else once we get to it or if the current worker is shutting down.
+pub(crate) fn recycle(socket: Rc<OwnedHandle<SOCKET>>, reuse: SocketReuse) {
+    if current_runtime::with(|x| x.is_stopping())
+        || current_async_agent::with(|x| x.is_shutting_down())
+        || reuse.is_full()
+    {
+        return;
+    }
+
+    defer_async(async move {
+        // Pending `TcpConnection::closed()` futures may still be referencing the socket, in which
+        // case it is not ours to reuse.
+        let Ok(socket) = Rc::try_unwrap(socket) else {
+            return;
+        };
+
+        match disconnect(&socket).await {
+            Ok(()) => {
+                // The pool may have filled up while we were disconnecting, in which case we just
+                // drop the socket to release it.
+                if !reuse.is_full() {
+                    reuse.put(socket);
+                }
+            }
+            Err(_) => SOCKET_DISCONNECTS_FAILED.with(Event::observe_unit),
+        }
+    });
+}
+
+fn is_full(family: ADDRESS_FAMILY) -> bool {
+    let max = tuning::with_current(|x| x.max_pooled_sockets);
+
+    POOL.with_borrow_mut(|pool| pool.sockets(family).len() >= max)
+}
+
+async fn disconnect(socket: &OwnedHandle<SOCKET>) -> io::Result<()> {
+    let disconnect_ex = winsock::disconnect_ex_fn(**socket)?;
+
+    // DisconnectEx does not transfer any data, so an empty buffer suffices.
+    let mut operation = current_async_agent::with_io(|io| {
+        io.new_operation(PinnedBuffer::from_boxed_slice(Box::new([])))
+    });
+    operation.cancel_on_drop(**socket);
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation
+            .begin(|_, overlapped, _| {
+                if disconnect_ex(**socket, overlapped, TF_REUSE_SOCKET, 0).as_bool() {
+                    Ok(())
+                } else {
+                    // DisconnectEx sets the error via WSAGetLastError(), which is the same
+                    // mechanism as GetLastError(), so ERROR_IO_PENDING is detected as expected.
+                    Err(windows::core::Error::from_win32().into())
+                }
+            })
+            .await
+    }
+    .into_inner()?;
+
+    Ok(())
+}
+
+thread_local! {
+    static POOL: RefCell<SocketPool> = RefCell::new(SocketPool::default());
+
+    static SOCKETS_REUSED: Event = EventBuilder::new()
+        .name("net_tcp_sockets_reused")
+        .build()
+        .unwrap();
+
+    static SOCKET_POOL_MISSES: Event = EventBuilder::new()
+        .name("net_tcp_socket_pool_misses")
+        .build()
+        .unwrap();
+
+    static SOCKET_DISCONNECTS_FAILED: Event = EventBuilder::new()
+        .name("net_tcp_socket_disconnects_failed")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,467 @@
+//! Client side of the SOCKS5 protocol (RFC 1928), for reaching services through a SOCKS proxy.
+
+use crate::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::{ConnectOptions, TcpConnection},
+    rt::sleep_until,
+};
+use futures::future::{self, Either};
+use std::{net::SocketAddr, pin::pin};
+
+/// The service to connect to via the proxy.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TargetAddr {
+    Socket(SocketAddr),
+
+    /// A host name and port, resolved by the proxy. Use this when the host name cannot be resolved
+    /// from where the client runs (common in locked-down networks).
+    Domain(String, u16),
+}
+
+impl From<SocketAddr> for TargetAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Socket(addr)
+    }
+}
+
+impl From<(&str, u16)> for TargetAddr {
+    fn from((host, port): (&str, u16)) -> Self {
+        Self::Domain(host.to_string(), port)
+    }
+}
+
+impl From<(String, u16)> for TargetAddr {
+    fn from((host, port): (String, u16)) -> Self {
+        Self::Domain(host, port)
+    }
+}
+
+/// How the client authenticates itself to the proxy.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Authentication {
+    #[default]
+    None,
+
+    /// Username/password authentication (RFC 1929). The credentials are sent in plain text. Each
+    /// may be at most 255 bytes long.
+    UsernamePassword { username: String, password: String },
+}
+
+/// Establishes a TCP connection to the target via the SOCKS5 proxy at `proxy_addr`, without
+/// authenticating to the proxy.
+///
+/// See `connect_with()`.
+pub async fn connect(
+    proxy_addr: SocketAddr,
+    target: impl Into<TargetAddr>,
+) -> io::Result<TcpConnection> {
+    connect_with(
+        proxy_addr,
+        target,
+        Authentication::None,
+        ConnectOptions::default(),
+    )
+    .await
+}
+
+/// Establishes a TCP connection to the target via the SOCKS5 proxy at `proxy_addr`. The returned
+/// connection is tunneled to the target and ready for use - everything sent and received on it is
+/// exchanged with the target.
+///
+/// The options apply to the connection to the proxy. The deadline and the cancellation token also
+/// cover the SOCKS handshake. TCP Fast Open data (`ConnectOptions::initial_data()`) is not
+/// supported, as it would be sent to the proxy instead of the target.
+///
+/// Any data the target sent that arrived together with the reply of the proxy is available via
+/// `TcpConnection::take_initial_data()`.
+///
+/// # Errors
+///
+/// If the proxy rejects the credentials or fails to connect to the target, the reason reported by
+/// the proxy is mapped to the closest `std::io::ErrorKind` (e.g. `ConnectionRefused`).
+pub async fn connect_with(
+    proxy_addr: SocketAddr,
+    target: impl Into<TargetAddr>,
+    authentication: Authentication,
+    options: ConnectOptions,
+) -> io::Result<TcpConnection> {
+    if options.initial_data.is_some() {
+        return Err(io::Error::InvalidOptions(
+            "TCP Fast Open data cannot be sent via a SOCKS proxy".to_string(),
+        ));
+    }
+
+    let target = target.into();
+    let request = encode_request(&target)?;
+    let greeting = encode_greeting(&authentication);
+
+    let deadline = options.deadline;
+    let cancellation_token = options.cancellation_token.clone();
+
+    let connection = TcpConnection::connect_with(proxy_addr, options).await?;
+
+    let handshake = pin!(handshake(connection, greeting, authentication, request));
+
+    let deadline = pin!(async {
+        match deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => future::pending().await,
+        }
+    });
+
+    let cancelled = pin!(async {
+        match &cancellation_token {
+            Some(token) => token.cancelled().await,
+            None => future::pending().await,
+        }
+    });
+
+    let result = match future::select(handshake, future::select(deadline, cancelled)).await {
+        Either::Left((result, _)) => result,
+        Either::Right((Either::Left(_), _)) => Err(io::Error::TimedOut),
+        Either::Right((Either::Right(_), _)) => Err(io::Error::Canceled),
+    };
+
+    match &result {
+        Ok(_) => HANDSHAKES_OK.with(Event::observe_unit),
+        Err(_) => HANDSHAKES_FAILED.with(Event::observe_unit),
+    }
+
+    result
+}
+
+async fn handshake(
+    mut connection: TcpConnection,
+    greeting: Vec<u8>,
+    authentication: Authentication,
+    request: Vec<u8>,
+) -> io::Result<TcpConnection> {
+    let mut received = Vec::new();
+
+    send_all(&mut connection, &greeting).await?;
+    receive_at_least(&mut connection, &mut received, 2).await?;
+
+    let method = parse_method_selection(&received[..2])?;
+    received.drain(..2);
+
+    if method == METHOD_USERNAME_PASSWORD {
+        let Authentication::UsernamePassword { username, password } = &authentication else {
+            return Err(invalid("proxy selected a method we did not offer"));
+        };
+
+        send_all(&mut connection, &encode_credentials(username, password)?).await?;
+        receive_at_least(&mut connection, &mut received, 2).await?;
+
+        parse_authentication_status(&received[..2])?;
+        received.drain(..2);
+    }
+
+    send_all(&mut connection, &request).await?;
+
+    let consumed = loop {
+        if let Some(consumed) = parse_reply(&received)? {
+            break consumed;
+        }
+
+        let len = received.len();
+        receive_at_least(&mut connection, &mut received, len + 1).await?;
+    };
+
+    // The target may have started talking before we got to read the reply of the proxy.
+    if received.len() > consumed {
+        connection.set_initial_data(PinnedBuffer::from_boxed_slice(received[consumed..].into()));
+    }
+
+    Ok(connection)
+}
+
+async fn send_all(connection: &mut TcpConnection, data: &[u8]) -> io::Result<()> {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+
+    connection.send(buffer).await.into_inner()?;
+    Ok(())
+}
+
+async fn receive_at_least(
+    connection: &mut TcpConnection,
+    received: &mut Vec<u8>,
+    len: usize,
+) -> io::Result<()> {
+    while received.len() < len {
+        let buffer = connection
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()?;
+
+        if buffer.len() == 0 {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "proxy closed the connection during the SOCKS handshake",
+            )));
+        }
+
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    Ok(())
+}
+
+const VERSION: u8 = 5;
+
+const METHOD_NONE: u8 = 0x00;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const METHOD_NOT_ACCEPTABLE: u8 = 0xFF;
+
+const USERNAME_PASSWORD_VERSION: u8 = 1;
+
+const COMMAND_CONNECT: u8 = 1;
+
+const ADDRESS_TYPE_IPV4: u8 = 1;
+const ADDRESS_TYPE_DOMAIN: u8 = 3;
+const ADDRESS_TYPE_IPV6: u8 = 4;
+
+fn encode_greeting(authentication: &Authentication) -> Vec<u8> {
+    match authentication {
+        Authentication::None => vec![VERSION, 1, METHOD_NONE],
+        // We also offer no authentication, in case the proxy does not require any.
+        Authentication::UsernamePassword { .. } => {
+            vec![VERSION, 2, METHOD_NONE, METHOD_USERNAME_PASSWORD]
+        }
+    }
+}
+
+fn encode_credentials(username: &str, password: &str) -> io::Result<Vec<u8>> {
+    let (Ok(username_len), Ok(password_len)) =
+        (u8::try_from(username.len()), u8::try_from(password.len()))
+    else {
+        return Err(io::Error::InvalidOptions(
+            "SOCKS username and password must each be at most 255 bytes".to_string(),
+        ));
+    };
+
+    let mut data = vec![USERNAME_PASSWORD_VERSION, username_len];
+    data.extend_from_slice(username.as_bytes());
+    data.push(password_len);
+    data.extend_from_slice(password.as_bytes());
+    Ok(data)
+}
+
+fn encode_request(target: &TargetAddr) -> io::Result<Vec<u8>> {
+    let mut data = vec![VERSION, COMMAND_CONNECT, 0];
+
+    let port = match target {
+        TargetAddr::Socket(SocketAddr::V4(addr)) => {
+            data.push(ADDRESS_TYPE_IPV4);
+            data.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        TargetAddr::Socket(SocketAddr::V6(addr)) => {
+            data.push(ADDRESS_TYPE_IPV6);
+            data.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        TargetAddr::Domain(host, port) => {
+            let len = u8::try_from(host.len())
+                .ok()
+                .filter(|len| *len != 0)
+                .ok_or_else(|| {
+                    io::Error::InvalidOptions(
+                        "SOCKS target host name must be 1 to 255 bytes long".to_string(),
+                    )
+                })?;
+
+            data.push(ADDRESS_TYPE_DOMAIN);
+            data.push(len);
+            data.extend_from_slice(host.as_bytes());
+            *port
+        }
+    };
+
+    data.extend_from_slice(&port.to_be_bytes());
+    Ok(data)
+}
+
+fn parse_method_selection(data: &[u8]) -> io::Result<u8> {
+    match data {
+        [VERSION, METHOD_NOT_ACCEPTABLE] => Err(io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "SOCKS proxy did not accept any of the offered authentication methods",
+        ))),
+        [VERSION, method @ (METHOD_NONE | METHOD_USERNAME_PASSWORD)] => Ok(*method),
+        [VERSION, _] => Err(invalid("proxy selected a method we did not offer")),
+        _ => Err(invalid("proxy does not speak SOCKS5")),
+    }
+}
+
+fn parse_authentication_status(data: &[u8]) -> io::Result<()> {
+    match data {
+        [USERNAME_PASSWORD_VERSION, 0] => Ok(()),
+        [USERNAME_PASSWORD_VERSION, _] => Err(io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "SOCKS proxy rejected the username and password",
+        ))),
+        _ => Err(invalid("invalid SOCKS authentication response")),
+    }
+}
+
+/// Parses the reply of the proxy to our connect request. Returns `None` if more data is needed,
+/// otherwise the number of bytes the reply occupies. Any bytes after the reply come from the
+/// target.
+fn parse_reply(data: &[u8]) -> io::Result<Option<usize>> {
+    // Version, reply code, reserved and address type.
+    const FIXED_LEN: usize = 4;
+
+    if data.len() < FIXED_LEN {
+        return Ok(None);
+    }
+
+    if data[0] != VERSION {
+        return Err(invalid("invalid SOCKS reply"));
+    }
+
+    if let Some(error) = reply_error(data[1]) {
+        return Err(error);
+    }
+
+    // The bound address and port are of no use to a client that only connects, so we skip them.
+    let address_len = match data[3] {
+        ADDRESS_TYPE_IPV4 => 4,
+        ADDRESS_TYPE_IPV6 => 16,
+        ADDRESS_TYPE_DOMAIN => match data.get(FIXED_LEN) {
+            Some(len) => 1 + *len as usize,
+            None => return Ok(None),
+        },
+        _ => return Err(invalid("invalid address type in SOCKS reply")),
+    };
+
+    let len = FIXED_LEN + address_len + 2;
+
+    if data.len() < len {
+        return Ok(None);
+    }
+
+    Ok(Some(len))
+}
+
+fn reply_error(code: u8) -> Option<io::Error> {
+    use std::io::ErrorKind;
+
+    let (kind, message) = match code {
+        0 => return None,
+        2 => (
+            ErrorKind::PermissionDenied,
+            "connection not allowed by SOCKS proxy rules",
+        ),
+        3 => (ErrorKind::NetworkUnreachable, "network unreachable"),
+        4 => (ErrorKind::HostUnreachable, "host unreachable"),
+        5 => (ErrorKind::ConnectionRefused, "connection refused by target"),
+        6 => (ErrorKind::TimedOut, "TTL expired"),
+        7 => (
+            ErrorKind::Unsupported,
+            "command not supported by SOCKS proxy",
+        ),
+        8 => (
+            ErrorKind::Unsupported,
+            "address type not supported by SOCKS proxy",
+        ),
+        _ => (ErrorKind::Other, "general SOCKS proxy failure"),
+    };
+
+    Some(io::Error::StdIo(std::io::Error::new(kind, message)))
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+thread_local! {
+    static HANDSHAKES_OK: Event = EventBuilder::new()
+        .name("net_socks_handshakes_ok")
+        .build()
+        .unwrap();
+
+    static HANDSHAKES_FAILED: Event = EventBuilder::new()
+        .name("net_socks_handshakes_failed")
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn request_encodes_each_address_type() {
+        let ipv4 = encode_request(&SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 80)).into());
+        assert_eq!(ipv4.unwrap(), [5, 1, 0, 1, 10, 0, 0, 1, 0, 80]);
+
+        let ipv6 = encode_request(&SocketAddr::from((Ipv6Addr::LOCALHOST, 443)).into()).unwrap();
+        assert_eq!(&ipv6[..4], [5, 1, 0, 4]);
+        assert_eq!(&ipv6[4..20], Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(&ipv6[20..], 443u16.to_be_bytes());
+
+        let domain = encode_request(&("example.com", 8080).into()).unwrap();
+        assert_eq!(&domain[..5], [5, 1, 0, 3, 11]);
+        assert_eq!(&domain[5..16], b"example.com");
+        assert_eq!(&domain[16..], 8080u16.to_be_bytes());
+
+        assert!(encode_request(&("", 80).into()).is_err());
+        assert!(encode_request(&("a".repeat(256), 80).into()).is_err());
+    }
+
+    #[test]
+    fn credentials_are_length_prefixed() {
+        assert_eq!(
+            encode_credentials("user", "pw").unwrap(),
+            [1, 4, b'u', b's', b'e', b'r', 2, b'p', b'w']
+        );
+
+        assert!(encode_credentials(&"a".repeat(256), "pw").is_err());
+    }
+
+    #[test]
+    fn method_selection() {
+        assert_eq!(parse_method_selection(&[5, 0]).unwrap(), METHOD_NONE);
+        assert_eq!(
+            parse_method_selection(&[5, 2]).unwrap(),
+            METHOD_USERNAME_PASSWORD
+        );
+        assert!(parse_method_selection(&[5, 0xFF]).is_err());
+        assert!(parse_method_selection(&[5, 1]).is_err());
+        assert!(parse_method_selection(&[4, 0]).is_err());
+    }
+
+    #[test]
+    fn reply_incomplete_and_complete() {
+        let reply = [5, 0, 0, 1, 127, 0, 0, 1, 0x1F, 0x90, b'h', b'i'];
+
+        for len in 0..10 {
+            assert_eq!(parse_reply(&reply[..len]).unwrap(), None);
+        }
+
+        assert_eq!(parse_reply(&reply).unwrap(), Some(10));
+
+        let domain_reply = [5, 0, 0, 3, 2, b'a', b'b', 0, 80];
+        assert_eq!(parse_reply(&domain_reply[..4]).unwrap(), None);
+        assert_eq!(parse_reply(&domain_reply).unwrap(), Some(9));
+    }
+
+    #[test]
+    fn reply_errors_map_to_error_kinds() {
+        let Err(io::Error::StdIo(e)) = parse_reply(&[5, 5, 0, 1]) else {
+            panic!("expected an error");
+        };
+        assert_eq!(e.kind(), std::io::ErrorKind::ConnectionRefused);
+
+        assert!(parse_reply(&[4, 0, 0, 1]).is_err());
+        assert!(parse_reply(&[5, 0, 0, 9]).is_err());
+    }
+}
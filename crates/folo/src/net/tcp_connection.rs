@@ -1,88 +1,1565 @@
+#[cfg(feature = "futures-io")]
+mod futures_io;
+mod stats;
+
+pub use stats::ConnectionStats;
+pub(crate) use stats::TrafficCounters;
+
 use crate::{
-    io::{OperationResult, PinnedBuffer},
-    net::winsock,
-    rt::current_async_agent,
-    util::OwnedHandle,
+    constants::{GENERAL_BYTES_BUCKETS, GENERAL_MILLISECONDS_BUCKETS},
+    fs::File,
+    io::{
+        self, transfer_all_in_chunks, transfer_all_vectored_in_chunks, BufferSize, CompletionPort,
+        IngestRing, Operation, OperationError, OperationHandle, OperationKind, OperationResult,
+        OperationResultExt, PinnedBuffer, RioSocket, VectoredOperationError,
+        VectoredOperationResult, MAX_OPERATION_LEN,
+    },
+    metrics::{Event, EventBuilder, Magnitude},
+    net::{
+        drainer, socket_handoff, socket_options,
+        socket_pool::{self, SocketReuse},
+        winsock::{self, NativeSocketAddr},
+        ConnectOptions, ConnectionPermit, KeepaliveSettings, ProxyHeader, ReceiveBufferSizer,
+        SocketHandoff, SocketOptions, ThreadHandoff,
+    },
+    rt::{self, current_async_agent, sleep},
+    util::{LowPrecisionInstant, OwnedHandle},
+};
+use futures::{
+    future::{self, Either},
+    FutureExt,
 };
 use negative_impl::negative_impl;
+use std::{
+    future::Future,
+    iter, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    num::NonZeroUsize,
+    os::windows::io::{
+        AsRawSocket, AsSocket, BorrowedSocket, IntoRawSocket, OwnedSocket, RawSocket,
+    },
+    pin::pin,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tracing::{event, Level};
 use windows::{
     core::PSTR,
-    Win32::Networking::WinSock::{WSARecv, WSASend, SOCKET, WSABUF},
+    Win32::Networking::WinSock::{
+        bind, setsockopt, shutdown, TransmitFile, WSARecv, WSASend, WSASocketA, WSASocketW,
+        FROM_PROTOCOL_INFO, INVALID_SOCKET, IPPROTO_IP, IPPROTO_IPV6, IPPROTO_TCP, IPV6_UNICAST_IF,
+        IP_UNICAST_IF, MSG_OOB, MSG_PEEK, SD_BOTH, SD_RECEIVE, SD_SEND, SOCKET, SOCK_STREAM,
+        SOL_SOCKET, SO_UPDATE_CONNECT_CONTEXT, TCP_FASTOPEN, WSABUF, WSA_FLAG_OVERLAPPED,
+        WSA_FLAG_REGISTERED_IO,
+    },
 };
 
 pub struct TcpConnection {
-    pub(super) socket: OwnedHandle<SOCKET>,
+    // Shared with any pending `closed()` futures, which keep the socket open until they are dropped.
+    socket: Rc<OwnedHandle<SOCKET>>,
+
+    // Set once we have observed that no more data can arrive from the peer (graceful close or
+    // reset), after which any further receives are pointless.
+    read_closed: bool,
+
+    // Set once we have observed that no more data can be sent to the peer.
+    write_closed: bool,
+
+    // If the connection was accepted by a server with a connection limit, this counts us as a
+    // live connection until we are dropped.
+    _connection_permit: Option<ConnectionPermit>,
+
+    // If the connection was accepted by a listener, this counts us as a live connection for the
+    // purpose of draining the listener on shutdown. Released before the socket can be closed.
+    drain_registration: Option<drainer::Registration>,
+
+    // Data received together with accepting the connection, until taken by the user.
+    initial_data: Option<PinnedBuffer>,
+
+    // The PROXY protocol header received on accept, if the listener requires one.
+    proxy_header: Option<ProxyHeader>,
+
+    // If set, the socket is recycled into a socket pool when we are dropped, to be reused by a
+    // future connection (see `SocketReuse` for which one).
+    reuse: Option<SocketReuse>,
+
+    // Picks the buffer size for `receive_adaptive()`.
+    receive_sizer: ReceiveBufferSizer,
+
+    // If set, sends and receives use Registered I/O instead of overlapped I/O.
+    rio: Option<RioSocket>,
+
+    // Shared with operations that do not borrow the connection, so they can count their traffic.
+    traffic: Rc<TrafficCounters>,
+
+    #[cfg(feature = "futures-io")]
+    staging: futures_io::Staging,
 }
 
 impl TcpConnection {
+    pub(super) fn new(
+        socket: OwnedHandle<SOCKET>,
+        connection_permit: Option<ConnectionPermit>,
+    ) -> Self {
+        Self {
+            socket: Rc::new(socket),
+            read_closed: false,
+            write_closed: false,
+            _connection_permit: connection_permit,
+            drain_registration: None,
+            initial_data: None,
+            proxy_header: None,
+            reuse: None,
+            receive_sizer: ReceiveBufferSizer::new(),
+            rio: None,
+            traffic: Rc::new(TrafficCounters::new()),
+            #[cfg(feature = "futures-io")]
+            staging: futures_io::Staging::default(),
+        }
+    }
+
+    pub(super) fn set_initial_data(&mut self, buffer: PinnedBuffer) {
+        self.initial_data = Some(buffer);
+    }
+
+    pub(super) fn set_socket_reuse(&mut self, reuse: SocketReuse) {
+        self.reuse = Some(reuse);
+    }
+
+    pub(super) fn raw_socket(&self) -> SOCKET {
+        **self.socket
+    }
+
+    /// Switches sends and receives over to Registered I/O. The socket must have been created with
+    /// `WSA_FLAG_REGISTERED_IO`.
+    pub(super) fn enable_registered_io(&mut self) -> io::Result<()> {
+        let rio = current_async_agent::with_io(|io| io.register_rio_socket(**self.socket, true))?;
+
+        self.rio = Some(rio);
+        Ok(())
+    }
+
+    /// Whether sends and receives use Registered I/O (see `ConnectOptions::registered_io()`).
+    pub fn is_registered_io(&self) -> bool {
+        self.rio.is_some()
+    }
+
+    /// The traffic of the connection since it was established. See `ConnectionStats` for what is
+    /// counted.
+    pub fn stats(&self) -> ConnectionStats {
+        self.traffic.snapshot()
+    }
+
+    /// Takes the first block of data received from the client together with accepting the
+    /// connection, if the server was configured to receive it via
+    /// `TcpServerBuilder::receive_initial_data()`. Returns `None` on subsequent calls and for
+    /// connections that were not accepted with initial data.
+    ///
+    /// On connections accepted by a listener that requires a PROXY protocol header, this is any
+    /// data that arrived together with the header.
+    ///
+    /// This data is not returned by `receive()`, so make sure to process it first.
+    pub fn take_initial_data(&mut self) -> Option<PinnedBuffer> {
+        self.initial_data.take()
+    }
+
+    pub(super) fn set_proxy_header(&mut self, header: ProxyHeader) {
+        self.proxy_header = Some(header);
+    }
+
+    pub(super) fn set_drain_registration(&mut self, registration: drainer::Registration) {
+        self.drain_registration = Some(registration);
+    }
+
+    /// The PROXY protocol header received from the load balancer or reverse proxy in front of the
+    /// server, describing the original client connection. Only present on connections accepted by
+    /// a listener that requires the header (see `TcpListener::set_proxy_protocol()`).
+    pub fn proxy_header(&self) -> Option<&ProxyHeader> {
+        self.proxy_header.as_ref()
+    }
+
+    /// Establishes a TCP connection to the specified address, using default options.
+    ///
+    /// The connection is bound to the current async worker thread.
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        Self::connect_with(addr, ConnectOptions::default()).await
+    }
+
+    /// Establishes a TCP connection to the specified address.
+    ///
+    /// The attempt is abandoned if the deadline in the options is reached (`io::Error::TimedOut`)
+    /// or the cancellation token in the options is cancelled (`io::Error::Canceled`) before the
+    /// connection is established. Socket options are applied before the connection is returned.
+    ///
+    /// The connection is bound to the current async worker thread.
+    pub async fn connect_with(addr: SocketAddr, options: ConnectOptions) -> io::Result<Self> {
+        let started = LowPrecisionInstant::now();
+
+        let result = Self::connect_core(addr, options).await;
+
+        match &result {
+            Ok(_) => CONNECT_OK_DURATION.with(|x| x.observe_millis(started.elapsed())),
+            Err(io::Error::TimedOut) => CONNECTS_TIMED_OUT.with(Event::observe_unit),
+            Err(io::Error::Canceled) => CONNECTS_CANCELED.with(Event::observe_unit),
+            Err(e) => {
+                CONNECTS_FAILED.with(Event::observe_unit);
+
+                event!(
+                    Level::DEBUG,
+                    message = "TCP connect failed",
+                    addr = addr.to_string(),
+                    error = e.to_string()
+                );
+            }
+        }
+
+        result
+    }
+
+    async fn connect_core(addr: SocketAddr, options: ConnectOptions) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let family = winsock::address_family(&addr);
+
+        if let Some(local_addr) = options.local_addr {
+            if winsock::address_family(&local_addr) != family {
+                return Err(io::Error::InvalidOptions(format!(
+                    "local address {local_addr} is not of the same address family as remote address {addr}"
+                )));
+            }
+        }
+
+        // Pooled sockets are bound to whatever the previous connection used, so we only use the
+        // pool if the caller has no requirements for the local end of the connection.
+        let reuse_socket = options.reuse_socket
+            && !options.registered_io
+            && options.local_addr.is_none()
+            && options.interface_index.is_none();
+
+        // A pooled socket is already bound, both to a local address and to our completion port.
+        let socket = match reuse_socket.then(|| socket_pool::take(family)).flatten() {
+            Some(socket) => socket,
+            None => Self::new_connect_socket(addr, options.local_addr, options.registered_io)?,
+        };
+
+        if let Some(index) = options.interface_index {
+            // Must be set before connecting, so the SYN already goes out via the interface. The
+            // IPv4 option expects the index in network byte order, the IPv6 one in host byte order.
+            match addr {
+                SocketAddr::V4(_) => {
+                    winsock::set_option(*socket, IPPROTO_IP.0, IP_UNICAST_IF, &index.to_be())?
+                }
+                SocketAddr::V6(_) => {
+                    winsock::set_option(*socket, IPPROTO_IPV6.0, IPV6_UNICAST_IF, &index)?
+                }
+            }
+        }
+
+        if options.fast_open {
+            // Must be set before connecting, so the SYN can carry the TFO cookie and data.
+            winsock::set_bool_option(*socket, IPPROTO_TCP.0, TCP_FASTOPEN, true)?;
+        }
+
+        let connect_ex = winsock::connect_ex_fn(*socket)?;
+        let remote_addr = NativeSocketAddr::from(addr);
+
+        // Any initial data is sent by ConnectEx itself, together with the SYN if TFO is in effect.
+        let initial_data = options.initial_data.unwrap_or_default();
+        let initial_data_len = initial_data.len();
+
+        let mut operation = current_async_agent::with_io(|io| {
+            io.new_operation(PinnedBuffer::from_boxed_slice(initial_data))
+        });
+        operation.cancel_on_drop(*socket);
+        operation.set_kind(OperationKind::Connect);
+
+        // The I/O driver cancels the pending ConnectEx once the deadline expires.
+        if let Some(deadline) = options.deadline {
+            operation.set_deadline(deadline);
+        }
+
+        let sent_data = {
+            // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+            let connect = pin!(unsafe {
+                operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                    if connect_ex(
+                        *socket,
+                        remote_addr.as_ptr(),
+                        remote_addr.len(),
+                        buffer.as_ptr() as *const _,
+                        buffer.len() as u32,
+                        immediate_bytes_transferred,
+                        overlapped,
+                    )
+                    .as_bool()
+                    {
+                        Ok(())
+                    } else {
+                        // ConnectEx sets the error via WSAGetLastError(), which is the same mechanism
+                        // as GetLastError(), so ERROR_IO_PENDING is detected as expected.
+                        Err(windows::core::Error::from_win32().into())
+                    }
+                })
+            });
+
+            let cancelled = pin!(async {
+                match &options.cancellation_token {
+                    Some(token) => token.cancelled().await,
+                    None => future::pending().await,
+                }
+            });
+
+            // If we abandon the attempt, dropping the connect future cancels the pending ConnectEx.
+            // The I/O driver will receive the completion and discard it.
+            match future::select(connect, cancelled).await {
+                Either::Left((result, _)) => result.into_inner()?,
+                Either::Right(_) => return Err(io::Error::Canceled),
+            }
+        };
+
+        // Without this, functions like getpeername() and shutdown() do not work on the socket.
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        winsock::to_io_result(unsafe {
+            setsockopt(*socket, SOL_SOCKET, SO_UPDATE_CONNECT_CONTEXT, None)
+        })?;
+
+        options.socket_options.apply(*socket)?;
+
+        let mut connection = Self::new(socket, None);
+
+        if reuse_socket {
+            connection.reuse = Some(SocketReuse::Connect(family));
+        }
+
+        if options.registered_io {
+            connection.enable_registered_io()?;
+        }
+
+        // ConnectEx may complete before sending all the initial data. The buffer tells us how much
+        // was sent, so we send the rest the usual way.
+        if sent_data.len() < initial_data_len {
+            connection
+                .send(sent_data.use_remainder())
+                .await
+                .into_inner()?;
+        }
+
+        Ok(connection)
+    }
+
+    fn new_connect_socket(
+        addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        registered_io: bool,
+    ) -> io::Result<OwnedHandle<SOCKET>> {
+        let flags = if registered_io {
+            WSA_FLAG_OVERLAPPED | WSA_FLAG_REGISTERED_IO
+        } else {
+            WSA_FLAG_OVERLAPPED
+        };
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let socket = unsafe {
+            OwnedHandle::new(WSASocketA(
+                winsock::address_family(&addr).0 as i32,
+                SOCK_STREAM.0,
+                IPPROTO_TCP.0,
+                None,
+                0,
+                flags,
+            )?)
+        };
+
+        // ConnectEx requires the socket to be bound. Unless the caller asked for a specific local
+        // address, we let the OS pick the local address/port.
+        let local_addr = NativeSocketAddr::from(local_addr.unwrap_or_else(|| {
+            let local_ip: IpAddr = match addr {
+                SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+                SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+            };
+
+            SocketAddr::new(local_ip, 0)
+        }));
+
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe { bind(*socket, local_addr.as_ptr(), local_addr.len()) })?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        Ok(socket)
+    }
+
+    /// The local address of the connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        winsock::local_addr(**self.socket)
+    }
+
+    /// The address of the peer the connection is established with.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        winsock::peer_addr(**self.socket)
+    }
+
+    /// Applies all the options that have been set in `options`, stopping at the first failure.
+    pub fn set_options(&self, options: &SocketOptions) -> io::Result<()> {
+        options.apply(**self.socket)
+    }
+
+    /// Whether TCP_NODELAY is set (the Nagle algorithm is disabled).
+    pub fn nodelay(&self) -> io::Result<bool> {
+        socket_options::nodelay(**self.socket)
+    }
+
+    pub fn set_nodelay(&self, value: bool) -> io::Result<()> {
+        socket_options::set_nodelay(**self.socket, value)
+    }
+
+    /// Whether SO_KEEPALIVE is set (TCP keepalive packets are sent).
+    pub fn keepalive(&self) -> io::Result<bool> {
+        socket_options::keepalive(**self.socket)
+    }
+
+    pub fn set_keepalive(&self, value: bool) -> io::Result<()> {
+        socket_options::set_keepalive(**self.socket, value)
+    }
+
+    /// The timing of TCP keepalive packets, as reported by the operating system. Has a resolution
+    /// of seconds, even if the values were set with a finer resolution.
+    pub fn keepalive_settings(&self) -> io::Result<KeepaliveSettings> {
+        socket_options::keepalive_settings(**self.socket)
+    }
+
+    /// Enables TCP keepalive packets with custom timing (see `KeepaliveSettings`).
+    pub fn set_keepalive_settings(&self, value: KeepaliveSettings) -> io::Result<()> {
+        socket_options::set_keepalive_settings(**self.socket, &value)
+    }
+
+    /// The SO_LINGER setting. See `SocketOptions::linger()`.
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        socket_options::linger(**self.socket)
+    }
+
+    pub fn set_linger(&self, value: Option<Duration>) -> io::Result<()> {
+        socket_options::set_linger(**self.socket, value)
+    }
+
+    /// The size of the operating system receive buffer (SO_RCVBUF), in bytes.
+    pub fn receive_buffer_size(&self) -> io::Result<usize> {
+        socket_options::receive_buffer_size(**self.socket)
+    }
+
+    pub fn set_receive_buffer_size(&self, value: usize) -> io::Result<()> {
+        socket_options::set_receive_buffer_size(**self.socket, value)
+    }
+
+    /// The size of the operating system send buffer (SO_SNDBUF), in bytes.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        socket_options::send_buffer_size(**self.socket)
+    }
+
+    pub fn set_send_buffer_size(&self, value: usize) -> io::Result<()> {
+        socket_options::set_send_buffer_size(**self.socket, value)
+    }
+
+    /// Whether SO_OOBINLINE is set (urgent data is delivered in the normal data stream). See
+    /// `SocketOptions::oob_inline()`.
+    pub fn oob_inline(&self) -> io::Result<bool> {
+        socket_options::oob_inline(**self.socket)
+    }
+
+    pub fn set_oob_inline(&self, value: bool) -> io::Result<()> {
+        socket_options::set_oob_inline(**self.socket, value)
+    }
+
+    /// Duplicates the connection for use by another process, returning a handoff that can be
+    /// transferred to that process (e.g. over a pipe) and turned into a connection there via
+    /// `from_handoff()`.
+    ///
+    /// This connection remains usable but you should not continue using it once the target process
+    /// has taken over, as both copies refer to the same underlying socket. Do not drop it before the
+    /// target process has reconstructed the connection, as that may close the socket.
+    pub fn duplicate_for_process(&self, process_id: u32) -> io::Result<SocketHandoff> {
+        SocketHandoff::duplicate(**self.socket, process_id)
+    }
+
+    /// Reconstructs a connection from a handoff created by `duplicate_for_process()` in another
+    /// process. The connection is bound to the current async worker thread.
+    pub fn from_handoff(handoff: &SocketHandoff) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let protocol_info = handoff.protocol_info();
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let socket = unsafe {
+            OwnedHandle::new(WSASocketW(
+                FROM_PROTOCOL_INFO,
+                FROM_PROTOCOL_INFO,
+                FROM_PROTOCOL_INFO,
+                Some(protocol_info as *const _),
+                0,
+                WSA_FLAG_OVERLAPPED,
+            )?)
+        };
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        Ok(Self::new(socket, None))
+    }
+
+    /// Detaches the connection from the current async worker thread, so it can be moved to another
+    /// async worker thread of the same process (e.g. to rebalance load across processors) and
+    /// turned back into a connection there via `from_thread_handoff()`. The connection keeps its
+    /// state, including any initial data that has not been taken and the PROXY protocol header.
+    ///
+    /// The socket is removed from the I/O completion port of the current thread, which requires
+    /// that no I/O operations are in progress on the connection. Operations abandoned by dropping
+    /// their futures remain in progress until the operating system has processed their
+    /// cancellation, which happens promptly once the worker gets to process completions (e.g.
+    /// after `rt::yield_now()`). Pending futures returned by `closed()` must also be dropped first.
+    ///
+    /// # Errors
+    ///
+    /// Fails if operations are still in progress, if the connection uses Registered I/O (which is
+    /// bound to the queues of the current thread) or if the operating system does not support
+    /// removing the socket from the completion port (requires Windows 8.1 or newer). The
+    /// connection is closed on failure.
+    pub fn duplicate_for_thread(mut self) -> io::Result<ThreadHandoff> {
+        if self.rio.is_some() {
+            return Err(io::Error::InvalidOptions(
+                "connections using Registered I/O cannot be moved to another thread".to_string(),
+            ));
+        }
+
+        if Rc::strong_count(&self.socket) != 1 {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::ResourceBusy,
+                "the socket still has I/O operations or closed() futures in progress",
+            )));
+        }
+
+        CompletionPort::unbind(&**self.socket)?;
+
+        // We leave behind an invalid socket, which is not closed or recycled when the connection is
+        // dropped.
+        let socket = mem::replace(&mut self.socket, Rc::new(OwnedHandle::from(INVALID_SOCKET)));
+        let socket = Rc::into_inner(socket).expect("we just checked that nobody else holds it");
+
+        Ok(ThreadHandoff {
+            socket,
+            read_closed: self.read_closed,
+            write_closed: self.write_closed,
+            connection_permit: self._connection_permit.take(),
+            drain_registration: self.drain_registration.take(),
+            initial_data: self
+                .initial_data
+                .take()
+                .map(|buffer| buffer.as_slice().into()),
+            proxy_header: self.proxy_header.take(),
+            reuse: self.reuse.take(),
+            traffic: (*self.traffic).clone(),
+        })
+    }
+
+    /// Reconstructs a connection detached from another async worker thread via
+    /// `duplicate_for_thread()`. The connection is bound to the current async worker thread.
+    pub fn from_thread_handoff(handoff: ThreadHandoff) -> io::Result<Self> {
+        let ThreadHandoff {
+            socket,
+            read_closed,
+            write_closed,
+            connection_permit,
+            drain_registration,
+            initial_data,
+            proxy_header,
+            reuse,
+            traffic,
+        } = handoff;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        let mut connection = Self::new(socket, connection_permit);
+        connection.read_closed = read_closed;
+        connection.write_closed = write_closed;
+        connection.drain_registration = drain_registration;
+        connection.initial_data = initial_data.map(PinnedBuffer::from_boxed_slice);
+        connection.proxy_header = proxy_header;
+        connection.reuse = reuse;
+        connection.traffic = Rc::new(traffic);
+
+        Ok(connection)
+    }
+
+    /// Hands the connection off to the process serving the specified named pipe (e.g.
+    /// `\\.\pipe\my-service-handoff`), which receives it via `receive_via_pipe()`. Returns once
+    /// the receiving process has taken over the connection, closing the local copy.
+    ///
+    /// Fails without handing off the connection if the pipe is not served by the process with the
+    /// ID `receiver_process_id`, as anyone could have created a pipe with that name.
+    pub async fn hand_off_via_pipe(
+        self,
+        pipe_name: &str,
+        receiver_process_id: u32,
+    ) -> io::Result<()> {
+        socket_handoff::send_via_pipe(**self.socket, pipe_name, receiver_process_id).await
+    }
+
+    /// Creates the specified named pipe and waits for another process to hand off a connection to
+    /// us via `hand_off_via_pipe()`. The connection is bound to the current async worker thread.
+    ///
+    /// Only the user the current process runs as may connect to the pipe and the connection is
+    /// only accepted from the process with the ID `sender_process_id`. Fails if the pipe already
+    /// exists or if another process connects first.
+    pub async fn receive_via_pipe(pipe_name: &str, sender_process_id: u32) -> io::Result<Self> {
+        let (handoff, pipe) =
+            socket_handoff::receive_via_pipe(pipe_name, sender_process_id).await?;
+
+        let connection = Self::from_handoff(&handoff)?;
+
+        socket_handoff::acknowledge_via_pipe(pipe).await?;
+
+        Ok(connection)
+    }
+
+    /// Takes ownership of a connected TCP socket created outside of Folo (e.g. via the `socket2`
+    /// crate, to configure socket options that Folo does not expose). The connection is bound to
+    /// the current async worker thread.
+    ///
+    /// The socket must have been created for overlapped I/O (`WSA_FLAG_OVERLAPPED`), as is done by
+    /// the standard library and by `socket2`, and must not already be bound to an I/O completion
+    /// port. A `socket2::Socket` can be turned into an `OwnedSocket` via `OwnedSocket::from()`.
+    pub fn from_owned_socket(socket: OwnedSocket) -> io::Result<Self> {
+        // SAFETY: The socket is ours now and we only ever pass it back to its true owner.
+        unsafe { Self::from_raw_socket(SOCKET(socket.into_raw_socket() as usize)) }
+    }
+
+    /// Takes ownership of a connected TCP socket created outside of Folo. The connection is bound
+    /// to the current async worker thread. See `from_owned_socket()` for the requirements on the
+    /// socket.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own the socket and must not use or close it after this call.
+    pub unsafe fn from_raw_socket(socket: SOCKET) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let socket = OwnedHandle::new(socket);
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        Ok(Self::new(socket, None))
+    }
+
+    /// Releases ownership of the socket without closing it, e.g. to hand it off to code that does
+    /// not use Folo. Any data received together with accepting the connection that has not been
+    /// taken is discarded.
+    ///
+    /// The socket remains bound to the I/O completion port of the async worker thread that owned
+    /// the connection, so it can no longer be used for overlapped I/O elsewhere. Blocking I/O and
+    /// duplicating the socket for another process via `WSADuplicateSocketW` remain possible.
+    ///
+    /// Fails if a future returned by `closed()` is still referencing the socket.
+    pub fn into_raw_socket(mut self) -> io::Result<SOCKET> {
+        if Rc::strong_count(&self.socket) != 1 {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::ResourceBusy,
+                "the socket is still referenced by a pending closed() future",
+            )));
+        }
+
+        // The socket is no longer ours to recycle when the connection is dropped.
+        self.reuse = None;
+
+        // Any receives still in progress via Registered I/O are discarded.
+        self.rio = None;
+
+        // We leave behind an invalid socket, which is not closed when the connection is dropped.
+        let socket = mem::replace(&mut self.socket, Rc::new(OwnedHandle::from(INVALID_SOCKET)));
+
+        let socket = Rc::into_inner(socket).expect("we just checked that nobody else holds it");
+
+        Ok(SOCKET::from(socket))
+    }
+
     /// Receives the next buffer of data.
     ///
     /// The buffer will be returned in the result with the active region set to the bytes read, with
-    /// a length of 0 if the connection was closed.
+    /// a length of 0 if the peer gracefully closed its side of the connection (sent FIN). If the
+    /// connection was reset (e.g. the peer sent RST), `io::Error::ConnectionReset` is returned.
     ///
     /// You should not call this multiple times concurrently because there is no guarantee that the
     /// continuations will be called in a particular order.
     pub async fn receive(&mut self, buffer: PinnedBuffer) -> OperationResult {
-        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
-        unsafe {
-            current_async_agent::with_io(|io| io.new_operation(buffer)).begin(
-                |buffer, overlapped, immediate_bytes_transferred| {
-                    let wsabuf = WSABUF {
-                        len: buffer.len() as u32,
-                        buf: PSTR::from_raw(buffer.as_mut_ptr()),
-                    };
-
-                    let wsabufs = [wsabuf];
-                    let mut flags: u32 = 0;
-
-                    winsock::to_io_result(WSARecv(
-                        *self.socket,
-                        &wsabufs,
-                        Some(immediate_bytes_transferred as *mut u32),
-                        &mut flags as *mut u32,
-                        Some(overlapped),
-                        None,
-                    ))
-                },
-            )
+        let requested_len = buffer.len();
+
+        let result = self.start_receive(buffer).await;
+        self.complete_receive(requested_len, result)
+    }
+
+    /// Receives the next buffer of data into a buffer from the buffer pool, adapting the size of the
+    /// buffer to the sizes of the recent payloads (see `ReceiveBufferSizer`). Otherwise equivalent
+    /// to `receive()`.
+    ///
+    /// This keeps the memory used by many mostly idle connections low while still receiving
+    /// bulk transfers in large chunks.
+    pub async fn receive_adaptive(&mut self) -> OperationResult {
+        let result = self.receive(self.receive_sizer.buffer()).await;
+
+        if let Ok(buffer) = &result {
+            self.receive_sizer.observe(buffer.len());
+        }
+
+        result
+    }
+
+    /// Receives the next buffer of data, giving up if none arrives within `timeout`. Otherwise
+    /// equivalent to `receive()`.
+    ///
+    /// On timeout, the receive is canceled and `io::Error::TimedOut` is returned together with the
+    /// buffer, which is unchanged and can be reused. The connection remains usable.
+    pub async fn receive_with_timeout(
+        &mut self,
+        buffer: PinnedBuffer,
+        timeout: Duration,
+    ) -> OperationResult {
+        let requested_len = buffer.len();
+
+        let result = match &self.rio {
+            Some(rio) => {
+                rio.receive_until(buffer, Some(rt::now() + timeout), None)
+                    .await
+            }
+            None => receive_core(Rc::clone(&self.socket), buffer, Some(timeout)).await,
+        };
+
+        self.complete_receive(requested_len, result)
+    }
+
+    /// Starts receiving the next buffer of data, returning a handle that can cancel the receive
+    /// together with the future that completes it. Otherwise equivalent to `receive()`.
+    ///
+    /// If canceled before data arrives, the future returns `io::Error::Canceled` together with the
+    /// buffer, which is unchanged and can be reused. Cancellation requested before the future is
+    /// first polled takes effect as soon as the receive starts. The connection remains usable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn receive_cancelable(
+        &mut self,
+        buffer: PinnedBuffer,
+    ) -> (OperationHandle, impl Future<Output = OperationResult> + '_) {
+        let requested_len = buffer.len();
+
+        let (handle, receive) = match &self.rio {
+            Some(rio) => {
+                let handle = OperationHandle::new();
+                let receive = rio.receive_until(buffer, None, Some(handle.token().clone()));
+
+                (handle, Either::Left(receive))
+            }
+            None => {
+                let mut operation = new_receive_operation(&self.socket, buffer, None);
+                let handle = operation.handle();
+                let receive = begin_receive(Rc::clone(&self.socket), operation, 0);
+
+                (handle, Either::Right(receive))
+            }
+        };
+
+        let receive = async move {
+            let result = receive.await;
+            self.complete_receive(requested_len, result)
+        };
+
+        (handle, receive)
+    }
+
+    /// Receives urgent (out-of-band) data sent by the peer via `send_urgent()`, completing once
+    /// such data arrives. Only the last byte of each urgent send is delivered this way, so a buffer
+    /// of one byte is enough.
+    ///
+    /// Urgent data exists for legacy protocols (e.g. the Telnet interrupt signal) and middleboxes
+    /// do not reliably preserve it, so do not use it for new protocols. If SO_OOBINLINE is set (see
+    /// `set_oob_inline()`), urgent data is delivered by `receive()` instead and this fails.
+    ///
+    /// This does not interfere with a concurrent `receive()` of normal data. A zero-byte result
+    /// does not mean the connection was closed - use `receive()` or `closed()` to detect that.
+    pub async fn receive_urgent(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        if self.rio.is_some() {
+            return Err(OperationError::new(
+                registered_io_unsupported("receive_urgent()"),
+                buffer,
+            ));
+        }
+
+        let result =
+            receive_with_flags_core(Rc::clone(&self.socket), buffer, None, MSG_OOB.0 as u32).await;
+        self.traffic.record_receive(&result);
+
+        result.map_err(|e| self.inspect_error(e))
+    }
+
+    /// Creates an `IngestRing` that keeps `depth` receives outstanding on the connection, each into
+    /// a buffer of the specified size class, for consumers that want to parse the incoming data in
+    /// place instead of receiving it one buffer at a time.
+    ///
+    /// Any initial data (see `take_initial_data()`) is not included. While the ring exists, do not
+    /// receive data from the connection by other means, as that would take data out of the stream
+    /// seen by the ring. Sending is not affected.
+    pub fn ingest_ring(&self, depth: NonZeroUsize, size: BufferSize) -> IngestRing {
+        let socket = Rc::clone(&self.socket);
+        let rio = self.rio.as_ref().map(RioSocket::receiver);
+        let traffic = Rc::clone(&self.traffic);
+
+        IngestRing::new(depth, size, move |buffer| {
+            let traffic = Rc::clone(&traffic);
+
+            match &rio {
+                Some(rio) => Either::Left(rio.receive(buffer)),
+                None => Either::Right(receive_core(Rc::clone(&socket), buffer, None)),
+            }
+            .inspect(move |result| traffic.record_receive(result))
+        })
+    }
+
+    /// Starts a receive that does not borrow the connection, for adapters that need to store the
+    /// operation in progress (e.g. to implement `Stream`). The caller is responsible for noticing
+    /// that the peer has closed the connection, as the connection state is not updated.
+    pub(crate) fn receive_detached(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        let traffic = Rc::clone(&self.traffic);
+
+        self.start_receive(buffer)
+            .inspect(move |result| traffic.record_receive(result))
+    }
+
+    /// Starts receiving into the buffer via the backend of the connection, without updating the
+    /// connection state or the traffic counters.
+    fn start_receive(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        match &self.rio {
+            Some(rio) => Either::Left(rio.receive(buffer)),
+            None => Either::Right(receive_core(Rc::clone(&self.socket), buffer, None)),
+        }
+    }
+
+    /// Starts a send that does not borrow the connection. See `receive_detached()`. Like `send()`,
+    /// this sends all of the data unless an error occurs.
+    pub(crate) fn send_detached(
+        &self,
+        buffer: PinnedBuffer,
+    ) -> impl Future<Output = OperationResult> + 'static {
+        let traffic = Rc::clone(&self.traffic);
+
+        self.start_send(buffer)
+            .inspect(move |result| traffic.record_send(result))
+    }
+
+    /// Starts sending all of the active region of the buffer via the backend of the connection,
+    /// without updating the connection state or the traffic counters.
+    fn start_send(&self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult> + 'static {
+        match &self.rio {
+            // Registered I/O always sends all of the data, however much there is.
+            Some(rio) => Either::Left(rio.send(buffer)),
+            None => Either::Right(send_all_core(Rc::clone(&self.socket), buffer, None)),
         }
-        .await
     }
 
     /// Sends a buffer of data to the peer.
     ///
-    /// The buffer will be returned in the result to allow reuse.
+    /// The buffer will be returned in the result to allow reuse. Buffers longer than
+    /// `io::MAX_OPERATION_LEN` are sent via multiple sequential operations. If an operation only
+    /// sends part of its data, the rest is sent via another operation.
     ///
     /// You may call this multiple times concurrently. The buffers will be sent in the order they
-    /// are submitted.
+    /// are submitted, except that the data of a buffer sent via multiple operations may be
+    /// interleaved with the data of other concurrent sends. If you need larger buffers to arrive
+    /// intact, do not start another send before the previous one has completed.
     pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        let result = self.start_send(buffer).await;
+        self.traffic.record_send(&result);
+
+        result.map_err(|e| self.inspect_error(e))
+    }
+
+    /// Sends a buffer of data to the peer as urgent (out-of-band) data. The last byte of the buffer
+    /// is marked as urgent and the peer receives it via `receive_urgent()` (or in the normal data
+    /// stream if the peer has set SO_OOBINLINE), while any preceding bytes are delivered as normal
+    /// data. See `receive_urgent()` for why you should only use this for legacy protocols.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    ///
+    /// The data is sent via a single operation, as splitting it would mark the last byte of each
+    /// part as urgent.
+    ///
+    /// # Errors
+    ///
+    /// Buffers longer than `io::MAX_OPERATION_LEN` are rejected with `io::Error::InvalidOptions`.
+    pub async fn send_urgent(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        if self.rio.is_some() {
+            return Err(OperationError::new(
+                registered_io_unsupported("send_urgent()"),
+                buffer,
+            ));
+        }
+
+        if buffer.len() > MAX_OPERATION_LEN {
+            return Err(OperationError::new(
+                io::Error::InvalidOptions(format!(
+                    "send_urgent() can send at most {MAX_OPERATION_LEN} bytes per call"
+                )),
+                buffer,
+            ));
+        }
+
+        let result =
+            send_with_flags_core(Rc::clone(&self.socket), buffer, None, MSG_OOB.0 as u32).await;
+        self.traffic.record_send(&result);
+
+        result.map_err(|e| self.inspect_error(e))
+    }
+
+    /// Sends a buffer of data to the peer, giving up if the data cannot be handed over to the
+    /// operating system within `timeout` (e.g. because the peer is not reading and the send
+    /// window is full). Otherwise equivalent to `send()`.
+    ///
+    /// The timeout applies to the send as a whole, including any further operations needed to send
+    /// a large buffer or the rest of a short send.
+    ///
+    /// On timeout, the send is canceled and `io::Error::TimedOut` is returned together with the
+    /// buffer, with its active region set to the data known to have been sent before the operation
+    /// that timed out. More of the data may have been sent before the cancellation took effect and
+    /// there is no way to know how much, so the connection is marked as closed for writing and
+    /// should be closed.
+    pub async fn send_with_timeout(
+        &mut self,
+        buffer: PinnedBuffer,
+        timeout: Duration,
+    ) -> OperationResult {
+        let deadline = rt::now() + timeout;
+
+        let result = match &self.rio {
+            Some(rio) => rio.send_until(buffer, Some(deadline)).await,
+            None => send_all_core(Rc::clone(&self.socket), buffer, Some(deadline)).await,
+        };
+        self.traffic.record_send(&result);
+
+        result.map_err(|e| {
+            if matches!(e.inner, io::Error::TimedOut) {
+                self.write_closed = true;
+            }
+
+            self.inspect_error(e)
+        })
+    }
+
+    /// Receives data into multiple buffers at once (scatter), filling them in order. This is
+    /// otherwise equivalent to `receive()` - the data received is the active region of each
+    /// returned buffer and a total of zero bytes received means the peer has closed the connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffers` is empty or has more than `io::MAX_VECTORED_BUFFERS` items.
+    pub async fn receive_vectored(
+        &mut self,
+        buffers: Vec<PinnedBuffer>,
+    ) -> VectoredOperationResult {
+        let requested_len: usize = buffers.iter().map(PinnedBuffer::len).sum();
+
+        let result = match &self.rio {
+            Some(rio) => receive_vectored_rio(rio, buffers).await,
+            None => receive_vectored_core(Rc::clone(&self.socket), buffers).await,
+        };
+
+        match result {
+            Ok(buffers) => {
+                let received_len: usize = buffers.iter().map(PinnedBuffer::len).sum();
+                self.traffic.add_received(received_len);
+
+                if received_len == 0 && requested_len != 0 {
+                    self.read_closed = true;
+                }
+
+                Ok(buffers)
+            }
+            Err(e) => Err(self.inspect_vectored_error(e)),
+        }
+    }
+
+    /// Sends the data in multiple buffers at once (gather), in order. Use this to send e.g. a
+    /// header and a body without first copying them into a single buffer.
+    ///
+    /// Like `send()`, this sends all of the data unless an error occurs, via multiple sequential
+    /// operations if the buffers hold more than `io::MAX_OPERATION_LEN` bytes in total or if an
+    /// operation only sends part of its data. The results have the active regions set to the data
+    /// sent from each buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffers` is empty or has more than `io::MAX_VECTORED_BUFFERS` items.
+    pub async fn send_vectored(&mut self, buffers: Vec<PinnedBuffer>) -> VectoredOperationResult {
+        assert!(
+            !buffers.is_empty() && buffers.len() <= io::MAX_VECTORED_BUFFERS,
+            "vectored operations require between 1 and {} buffers",
+            io::MAX_VECTORED_BUFFERS
+        );
+
+        let result = match &self.rio {
+            Some(rio) => send_vectored_rio(rio, buffers).await,
+            None => {
+                transfer_all_vectored_in_chunks(buffers, |buffers| {
+                    send_vectored_core(Rc::clone(&self.socket), buffers)
+                })
+                .await
+            }
+        };
+
+        if let Ok(buffers) = &result {
+            self.traffic
+                .add_sent(buffers.iter().map(PinnedBuffer::len).sum());
+        }
+
+        result.map_err(|e| self.inspect_vectored_error(e))
+    }
+
+    /// Sends `len` bytes of a file, starting at `offset`, directly from the file system cache to the
+    /// connection via `TransmitFile`. The data never passes through a `PinnedBuffer`, which makes
+    /// this the most efficient way to serve static content.
+    ///
+    /// Returns the number of bytes sent, which is always `len` on success.
+    ///
+    /// # Errors
+    ///
+    /// `len` must be at most `MAX_SEND_FILE_LEN`, the limit of `TransmitFile`. Send larger ranges in
+    /// multiple calls.
+    pub async fn send_file(&mut self, file: &File, offset: u64, len: u32) -> io::Result<u64> {
+        if len > MAX_SEND_FILE_LEN {
+            return Err(io::Error::InvalidOptions(format!(
+                "send_file() can send at most {MAX_SEND_FILE_LEN} bytes per call"
+            )));
+        }
+
+        // A zero length means "whole file" to TransmitFile, which is not what we want here.
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let file_handle = file.handle();
+
+        let mut operation = current_async_agent::with_io(|io| {
+            io.new_operation(PinnedBuffer::from_boxed_slice(Box::new([])))
+        });
+        operation.set_offset(offset as usize);
+        operation.cancel_on_drop(**self.socket);
+        operation.keep_alive(Rc::clone(&self.socket));
+        operation.set_kind(OperationKind::Send);
+
         // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
-        unsafe {
-            current_async_agent::with_io(|io| io.new_operation(buffer)).begin(
-                |buffer, overlapped, immediate_bytes_transferred| {
-                    let wsabuf = WSABUF {
-                        len: buffer.len() as u32,
-                        buf: PSTR::from_raw(buffer.as_mut_ptr()),
-                    };
-
-                    let wsabufs = [wsabuf];
-
-                    winsock::to_io_result(WSASend(
-                        *self.socket,
-                        &wsabufs,
-                        Some(immediate_bytes_transferred as *mut u32),
+        let result = unsafe {
+            operation
+                .begin(|_, overlapped, _| {
+                    // The bytes transferred are not reported for immediate completion but we do
+                    // not need them - on success, the entire range has been sent.
+                    TransmitFile(
+                        **self.socket,
+                        file_handle,
+                        len,
                         0,
                         Some(overlapped),
                         None,
-                    ))
-                },
-            )
+                        0,
+                    )
+                    .ok()
+                    .map_err(io::Error::from)
+                })
+                .await
+        };
+
+        result
+            .map_err(|e| self.inspect_error(e))
+            .map_err(|e| e.into_inner())?;
+
+        self.traffic.add_sent(len as usize);
+        SEND_FILE_BYTES.with(|x| x.observe(len as Magnitude));
+
+        Ok(len as u64)
+    }
+
+    /// Returns a future that completes when the peer closes the connection (gracefully or via
+    /// reset) or when the connection fails. This allows a task that only ever sends to notice that
+    /// the peer has gone away, without having to read from the connection.
+    ///
+    /// The future does not borrow the connection, so it can be awaited concurrently with `send()`.
+    /// Note that it keeps the underlying socket open until the future is dropped.
+    ///
+    /// If the peer sends data that is not being read, a graceful close cannot be detected until the
+    /// data is consumed (a reset is still detected).
+    pub fn closed(&self) -> impl Future<Output = ()> + 'static {
+        let socket = Rc::clone(&self.socket);
+        let rio = self.rio.as_ref().map(RioSocket::receiver);
+
+        async move {
+            loop {
+                // A peek does not consume any data - it completes when there is data to read or the
+                // peer has closed the connection, and fails if it was reset. Being an overlapped
+                // operation, it does not require changing the mode of the socket. Registered I/O
+                // has no peek, so there the data is kept for the next receive instead.
+                let result = match &rio {
+                    Some(rio) => rio.peek().await,
+                    None => receive_with_flags_core(
+                        Rc::clone(&socket),
+                        PinnedBuffer::from_boxed_slice(Box::new([0])),
+                        None,
+                        MSG_PEEK.0 as u32,
+                    )
+                    .await
+                    .map(|buffer| buffer.len())
+                    .map_err(OperationError::into_inner),
+                };
+
+                match result {
+                    // Graceful close by peer.
+                    Ok(0) => return,
+                    Ok(_) => {
+                        // Data is waiting to be read, which would immediately complete another
+                        // peek. We give the reader some time to consume it.
+                        sleep(CLOSED_DATA_PENDING_RECHECK_INTERVAL).await;
+                    }
+                    Err(_) => return,
+                }
+            }
+        }
+    }
+
+    /// Shuts down the read side, the write side or both sides of the connection, without releasing
+    /// the socket. Use `Shutdown::Write` to signal end-of-stream to the peer (it will receive a
+    /// graceful close once it has read all the data sent before the shutdown) while still reading
+    /// the peer's response.
+    ///
+    /// Any data already handed to a completed `send()` is delivered before the end-of-stream
+    /// signal. Sends after a write shutdown fail with a Winsock `WSAESHUTDOWN` error.
+    ///
+    /// Shutting down a side that is already closed has no effect.
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        let (read, write) = match how {
+            Shutdown::Read => (true, false),
+            Shutdown::Write => (false, true),
+            Shutdown::Both => (true, true),
+        };
+
+        if (!read || self.read_closed) && (!write || self.write_closed) {
+            return Ok(());
+        }
+
+        let native_how = match how {
+            Shutdown::Read => SD_RECEIVE,
+            Shutdown::Write => SD_SEND,
+            Shutdown::Both => SD_BOTH,
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        winsock::to_io_result(unsafe { shutdown(**self.socket, native_how) })?;
+
+        self.read_closed |= read;
+        self.write_closed |= write;
+
+        Ok(())
+    }
+
+    /// Whether the connection is known to be closed for reading - the peer has gracefully closed
+    /// its side of the connection, the connection was reset or we shut down the read side. This
+    /// only reflects what previous operations have observed; it does not query the state of the
+    /// connection.
+    pub fn is_read_closed(&self) -> bool {
+        self.read_closed
+    }
+
+    /// Whether the connection is known to be closed for writing - the connection was reset or we
+    /// shut down the write side. This only reflects what previous operations have observed; it
+    /// does not query the state of the connection.
+    pub fn is_write_closed(&self) -> bool {
+        self.write_closed
+    }
+
+    /// Updates the connection state based on the result of a receive operation.
+    fn complete_receive(
+        &mut self,
+        requested_len: usize,
+        result: OperationResult,
+    ) -> OperationResult {
+        self.traffic.record_receive(&result);
+
+        match result {
+            Ok(buffer) => {
+                // A zero-byte receive into a zero-length buffer says nothing about the peer.
+                if buffer.len() == 0 && requested_len != 0 {
+                    self.read_closed = true;
+                }
+
+                Ok(buffer)
+            }
+            Err(e) => Err(self.inspect_error(e)),
+        }
+    }
+
+    /// Updates the connection state based on an operation error and translates the various forms
+    /// of "connection reset" reported by the OS into `io::Error::ConnectionReset`.
+    fn inspect_error(&mut self, error: OperationError) -> OperationError {
+        if !error.inner.is_connection_reset() {
+            return error;
+        }
+
+        self.read_closed = true;
+        self.write_closed = true;
+
+        let (_, buffer) = error.into_inner_and_buffer();
+        OperationError::new(io::Error::ConnectionReset, buffer)
+    }
+
+    fn inspect_vectored_error(&mut self, error: VectoredOperationError) -> VectoredOperationError {
+        if !error.inner.is_connection_reset() {
+            return error;
+        }
+
+        self.read_closed = true;
+        self.write_closed = true;
+
+        let (_, buffers) = error.into_inner_and_buffers();
+        VectoredOperationError::new(io::Error::ConnectionReset, buffers)
+    }
+}
+
+impl AsRawSocket for TcpConnection {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.socket.0 as RawSocket
+    }
+}
+
+/// Allows socket options to be inspected and changed via other crates that operate on borrowed
+/// sockets, such as `socket2::SockRef`.
+impl AsSocket for TcpConnection {
+    fn as_socket(&self) -> BorrowedSocket<'_> {
+        // SAFETY: The socket remains open for as long as the connection is borrowed.
+        unsafe { BorrowedSocket::borrow_raw(self.as_raw_socket()) }
+    }
+}
+
+impl TryFrom<OwnedSocket> for TcpConnection {
+    type Error = io::Error;
+
+    fn try_from(socket: OwnedSocket) -> io::Result<Self> {
+        Self::from_owned_socket(socket)
+    }
+}
+
+impl Drop for TcpConnection {
+    fn drop(&mut self) {
+        // The drainer may operate on the socket for as long as we are registered, so we unregister
+        // before the socket has any chance of being closed.
+        self.drain_registration.take();
+
+        // Connections whose socket has been handed off or released are accounted for elsewhere.
+        if **self.socket != INVALID_SOCKET {
+            self.traffic.report();
+        }
+
+        if let Some(reuse) = self.reuse.take() {
+            socket_pool::recycle(Rc::clone(&self.socket), reuse);
         }
-        .await
     }
 }
 
+fn registered_io_unsupported(operation: &str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{operation} is not supported on connections that use Registered I/O"),
+    ))
+}
+
+// Registered I/O receives into one buffer per request, so we receive into the first buffer that
+// has room and leave the others empty, which is a valid outcome of a scatter receive.
+async fn receive_vectored_rio(
+    rio: &RioSocket,
+    mut buffers: Vec<PinnedBuffer>,
+) -> VectoredOperationResult {
+    let index = buffers
+        .iter()
+        .position(|buffer| buffer.len() != 0)
+        .unwrap_or(0);
+    let buffer = buffers.remove(index);
+
+    match rio.receive(buffer).await {
+        Ok(buffer) => {
+            for other in &mut buffers {
+                other.set_len(0);
+            }
+
+            buffers.insert(index, buffer);
+            Ok(buffers)
+        }
+        Err(OperationError { inner, buffer }) => {
+            buffers.insert(index, buffer);
+            Err(VectoredOperationError::new(inner, buffers))
+        }
+    }
+}
+
+// Registered I/O sends one buffer per request, so we send the buffers one after the other. On
+// error, the buffers not known to have been sent are returned with empty active regions.
+async fn send_vectored_rio(rio: &RioSocket, buffers: Vec<PinnedBuffer>) -> VectoredOperationResult {
+    let mut sent = Vec::with_capacity(buffers.len());
+    let mut buffers = buffers.into_iter();
+
+    while let Some(buffer) = buffers.next() {
+        match rio.send(buffer).await {
+            Ok(buffer) => sent.push(buffer),
+            Err(OperationError { inner, buffer }) => {
+                sent.extend(iter::once(buffer).chain(buffers).map(|mut buffer| {
+                    buffer.set_len(0);
+                    buffer
+                }));
+
+                return Err(VectoredOperationError::new(inner, sent));
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+// The receive and send operations own everything they reference, so they can be held across polls
+// by types that cannot borrow the connection (e.g. the `futures-io` adapter).
+
+async fn receive_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    timeout: Option<Duration>,
+) -> OperationResult {
+    receive_with_flags_core(socket, buffer, timeout, 0).await
+}
+
+async fn receive_with_flags_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    timeout: Option<Duration>,
+    flags: u32,
+) -> OperationResult {
+    let operation = new_receive_operation(&socket, buffer, timeout);
+    begin_receive(socket, operation, flags).await
+}
+
+// Creating the operation separately from beginning it allows the caller to obtain a handle to the
+// operation before it is started.
+fn new_receive_operation(
+    socket: &Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    timeout: Option<Duration>,
+) -> Operation {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.cancel_on_drop(***socket);
+    operation.keep_alive(Rc::clone(socket));
+    operation.set_kind(OperationKind::Receive);
+
+    if let Some(timeout) = timeout {
+        operation.cancel_after(timeout);
+    }
+
+    operation
+}
+
+async fn begin_receive(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    operation: Operation,
+    flags: u32,
+) -> OperationResult {
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+            let wsabuf = WSABUF {
+                len: buffer.len() as u32,
+                buf: PSTR::from_raw(buffer.as_mut_ptr()),
+            };
+
+            let wsabufs = [wsabuf];
+            let mut flags = flags;
+
+            winsock::to_io_result(WSARecv(
+                **socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                &mut flags as *mut u32,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+    .await
+}
+
+// Sends all of the active region of the buffer, via as many operations as needed. See `send()`.
+// The deadline applies to all of the operations.
+fn send_all_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    deadline: Option<Instant>,
+) -> impl Future<Output = OperationResult> + 'static {
+    transfer_all_in_chunks(buffer, move |buffer, _| {
+        send_core(Rc::clone(&socket), buffer, deadline)
+    })
+}
+
+async fn send_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    deadline: Option<Instant>,
+) -> OperationResult {
+    send_with_flags_core(socket, buffer, deadline, 0).await
+}
+
+async fn send_with_flags_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffer: PinnedBuffer,
+    deadline: Option<Instant>,
+    flags: u32,
+) -> OperationResult {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.cancel_on_drop(**socket);
+    operation.keep_alive(Rc::clone(&socket));
+    operation.set_kind(OperationKind::Send);
+
+    if let Some(deadline) = deadline {
+        operation.set_deadline(deadline);
+    }
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+            let wsabuf = WSABUF {
+                len: buffer.len() as u32,
+                buf: PSTR::from_raw(buffer.as_mut_ptr()),
+            };
+
+            let wsabufs = [wsabuf];
+
+            winsock::to_io_result(WSASend(
+                **socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                flags,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+    .await
+}
+
+async fn receive_vectored_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffers: Vec<PinnedBuffer>,
+) -> VectoredOperationResult {
+    let mut operation = current_async_agent::with_io(|io| io.new_vectored_operation(buffers));
+    operation.cancel_on_drop(**socket);
+    operation.keep_alive(Rc::clone(&socket));
+    operation.set_kind(OperationKind::Receive);
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin_vectored(|buffers, overlapped, immediate_bytes_transferred| {
+            let wsabufs = to_wsabufs(buffers);
+            let mut flags: u32 = 0;
+
+            winsock::to_io_result(WSARecv(
+                **socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                &mut flags as *mut u32,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+    .await
+}
+
+async fn send_vectored_core(
+    socket: Rc<OwnedHandle<SOCKET>>,
+    buffers: Vec<PinnedBuffer>,
+) -> VectoredOperationResult {
+    let mut operation = current_async_agent::with_io(|io| io.new_vectored_operation(buffers));
+    operation.cancel_on_drop(**socket);
+    operation.keep_alive(Rc::clone(&socket));
+    operation.set_kind(OperationKind::Send);
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin_vectored(|buffers, overlapped, immediate_bytes_transferred| {
+            let wsabufs = to_wsabufs(buffers);
+
+            winsock::to_io_result(WSASend(
+                **socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                0,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+    .await
+}
+
+fn to_wsabufs(buffers: &mut [&'static mut [u8]]) -> Vec<WSABUF> {
+    buffers
+        .iter_mut()
+        .map(|buffer| WSABUF {
+            len: buffer.len() as u32,
+            buf: PSTR::from_raw(buffer.as_mut_ptr()),
+        })
+        .collect()
+}
+
 #[negative_impl]
 impl !Send for TcpConnection {}
 #[negative_impl]
 impl !Sync for TcpConnection {}
+
+/// The maximum number of bytes that can be sent by a single `TcpConnection::send_file()` call.
+pub const MAX_SEND_FILE_LEN: u32 = i32::MAX as u32 - 1;
+
+/// How often `closed()` checks again if the connection has data pending that nobody has read yet.
+const CLOSED_DATA_PENDING_RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+thread_local! {
+    static SEND_FILE_BYTES: Event = EventBuilder::new()
+        .name("net_tcp_send_file_bytes")
+        .buckets(GENERAL_BYTES_BUCKETS)
+        .build()
+        .unwrap();
+
+    static CONNECT_OK_DURATION: Event = EventBuilder::new()
+        .name("net_tcp_connect_ok_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static CONNECTS_FAILED: Event = EventBuilder::new()
+        .name("net_tcp_connects_failed")
+        .build()
+        .unwrap();
+
+    static CONNECTS_TIMED_OUT: Event = EventBuilder::new()
+        .name("net_tcp_connects_timed_out")
+        .build()
+        .unwrap();
+
+    static CONNECTS_CANCELED: Event = EventBuilder::new()
+        .name("net_tcp_connects_canceled")
+        .build()
+        .unwrap();
+}
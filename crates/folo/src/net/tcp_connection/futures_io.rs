@@ -0,0 +1,193 @@
+//! `futures::io::AsyncRead` and `AsyncWrite` for `TcpConnection`, enabled by the `futures-io`
+//! feature. These copy data between the caller's slices and pooled `PinnedBuffer`s that stage the
+//! actual I/O operations, so the native operations always own their buffers.
+
+use super::TcpConnection;
+use crate::io::{self, OperationResult, PinnedBuffer};
+use futures::{
+    future::LocalBoxFuture,
+    io::{AsyncRead, AsyncWrite},
+    FutureExt,
+};
+use std::{
+    io::ErrorKind,
+    net::Shutdown,
+    pin::Pin,
+    task::{self, ready},
+};
+
+/// Operations in progress via the `AsyncRead`/`AsyncWrite` implementations. Dropping these cancels
+/// the operations.
+#[derive(Default)]
+pub(super) struct Staging {
+    read: ReadState,
+
+    // At most one send is in flight. Its outcome is reported by the next write, flush or close.
+    send: Option<SendInProgress>,
+}
+
+struct SendInProgress {
+    // How many bytes we have told the caller were written.
+    len: usize,
+    operation: LocalBoxFuture<'static, OperationResult>,
+}
+
+#[derive(Default)]
+enum ReadState {
+    #[default]
+    Idle,
+
+    Receiving {
+        requested_len: usize,
+        operation: LocalBoxFuture<'static, OperationResult>,
+    },
+
+    // Received data that did not fit into the caller's slice. The active region is what remains.
+    Buffered(PinnedBuffer),
+}
+
+/// Reads the data of the connection, starting with any initial data received together with
+/// accepting the connection (unless already taken via `take_initial_data()`).
+///
+/// A read of zero bytes means the peer has gracefully closed its side of the connection.
+impl AsyncRead for TcpConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> task::Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        if buf.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match &mut this.staging.read {
+                ReadState::Idle => {
+                    if let Some(initial_data) = this.initial_data.take() {
+                        this.staging.read = ReadState::Buffered(initial_data);
+                        continue;
+                    }
+
+                    if this.read_closed {
+                        return task::Poll::Ready(Ok(0));
+                    }
+
+                    let buffer = PinnedBuffer::from_pool();
+
+                    this.staging.read = ReadState::Receiving {
+                        requested_len: buffer.len(),
+                        operation: this.start_receive(buffer).boxed_local(),
+                    };
+                }
+                ReadState::Receiving {
+                    requested_len,
+                    operation,
+                } => {
+                    let requested_len = *requested_len;
+                    let result = ready!(operation.poll_unpin(cx));
+                    this.staging.read = ReadState::Idle;
+
+                    match this.complete_receive(requested_len, result) {
+                        Ok(buffer) if buffer.len() == 0 => return task::Poll::Ready(Ok(0)),
+                        Ok(buffer) => this.staging.read = ReadState::Buffered(buffer),
+                        Err(e) => return task::Poll::Ready(Err(e.into_inner().into_std())),
+                    }
+                }
+                ReadState::Buffered(buffer) => {
+                    let len = buffer.len().min(buf.len());
+                    buf[..len].copy_from_slice(&buffer.as_slice()[..len]);
+
+                    if len == buffer.len() {
+                        this.staging.read = ReadState::Idle;
+                    } else {
+                        let start = buffer.start();
+                        buffer.set_len(buffer.len() - len);
+                        buffer.set_start(start + len);
+                    }
+
+                    return task::Poll::Ready(Ok(len));
+                }
+            }
+        }
+    }
+}
+
+/// Writes data to the connection. Each write copies up to one pooled buffer worth of data into a
+/// staging buffer and starts sending it, completing before the data is actually sent. Any error
+/// from sending that is not reported immediately is reported by the next write, flush or close.
+///
+/// Closing shuts down the write side of the connection after all written data has been sent.
+impl AsyncWrite for TcpConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> task::Poll<std::io::Result<usize>> {
+        ready!(self.poll_send_completed(cx))?;
+
+        if buf.is_empty() {
+            return task::Poll::Ready(Ok(0));
+        }
+
+        let mut buffer = PinnedBuffer::from_pool();
+        let len = buffer.capacity().min(buf.len());
+        buffer
+            .as_mut_slice_with_len(len)
+            .copy_from_slice(&buf[..len]);
+
+        self.staging.send = Some(SendInProgress {
+            len,
+            operation: self.start_send(buffer).boxed_local(),
+        });
+
+        // The future does not start the native operation until polled, so we poll it once here.
+        // Otherwise, nothing would be sent until the caller writes again, flushes or closes.
+        match self.poll_send_completed(cx) {
+            task::Poll::Ready(Err(e)) => task::Poll::Ready(Err(e)),
+            _ => task::Poll::Ready(Ok(len)),
+        }
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        self.poll_send_completed(cx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        ready!(self.poll_send_completed(cx))?;
+
+        task::Poll::Ready(self.shutdown(Shutdown::Write).map_err(io::Error::into_std))
+    }
+}
+
+impl TcpConnection {
+    /// Waits for the in-flight send (if any) to complete and reports its outcome. The send resumes
+    /// after short sends until all of the data is sent, so it only completes with less than all of
+    /// the data if the connection can send no more.
+    fn poll_send_completed(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        let Some(send) = &mut self.staging.send else {
+            return task::Poll::Ready(Ok(()));
+        };
+
+        let result = ready!(send.operation.poll_unpin(cx));
+        let len = send.len;
+        self.staging.send = None;
+        self.traffic.record_send(&result);
+
+        task::Poll::Ready(match result {
+            Ok(buffer) if buffer.len() < len => Err(ErrorKind::WriteZero.into()),
+            Ok(_) => Ok(()),
+            Err(e) => Err(self.inspect_error(e).into_inner().into_std()),
+        })
+    }
+}
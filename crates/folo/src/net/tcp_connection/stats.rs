@@ -0,0 +1,160 @@
+//! Traffic statistics of a `TcpConnection`, exposed via `TcpConnection::stats()` and reported as
+//! aggregate metrics when the connection is closed.
+
+use crate::{
+    constants::{GENERAL_BYTES_BUCKETS, GENERAL_MILLISECONDS_BUCKETS},
+    io::OperationResult,
+    metrics::{Event, EventBuilder, Magnitude},
+    rt,
+};
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+/// The traffic of a `TcpConnection` since it was established. Only operations that completed
+/// successfully are counted. Any initial data received together with accepting the connection is
+/// not counted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ConnectionStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+
+    /// Number of send operations, including vectored sends and `send_file()`.
+    pub sends: u64,
+
+    /// Number of receive operations, including those that observed the peer closing the
+    /// connection.
+    pub receives: u64,
+
+    /// Time since the connection was established, according to the clock of the current thread.
+    pub age: Duration,
+}
+
+/// Counts the traffic of one connection. Shared (via `Rc`) with the operations that do not borrow
+/// the connection, so their traffic is counted even if they outlive the borrow.
+#[derive(Clone, Debug)]
+pub(crate) struct TrafficCounters {
+    established: Instant,
+    totals: Cell<Totals>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Totals {
+    bytes_sent: u64,
+    bytes_received: u64,
+    sends: u64,
+    receives: u64,
+}
+
+impl TrafficCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            established: rt::now(),
+            totals: Cell::new(Totals::default()),
+        }
+    }
+
+    pub(crate) fn add_sent(&self, bytes: usize) {
+        let mut totals = self.totals.get();
+        totals.bytes_sent += bytes as u64;
+        totals.sends += 1;
+        self.totals.set(totals);
+    }
+
+    pub(crate) fn add_received(&self, bytes: usize) {
+        let mut totals = self.totals.get();
+        totals.bytes_received += bytes as u64;
+        totals.receives += 1;
+        self.totals.set(totals);
+    }
+
+    /// Counts a send if it succeeded. The active region of the returned buffer is what was sent.
+    pub(crate) fn record_send(&self, result: &OperationResult) {
+        if let Ok(buffer) = result {
+            self.add_sent(buffer.len());
+        }
+    }
+
+    /// Counts a receive if it succeeded. The active region of the returned buffer is what was
+    /// received.
+    pub(crate) fn record_receive(&self, result: &OperationResult) {
+        if let Ok(buffer) = result {
+            self.add_received(buffer.len());
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ConnectionStats {
+        let totals = self.totals.get();
+
+        ConnectionStats {
+            bytes_sent: totals.bytes_sent,
+            bytes_received: totals.bytes_received,
+            sends: totals.sends,
+            receives: totals.receives,
+            age: rt::now().saturating_duration_since(self.established),
+        }
+    }
+
+    /// Reports the totals of a connection that is being closed to the aggregate metrics.
+    pub(crate) fn report(&self) {
+        let stats = self.snapshot();
+
+        CONNECTION_BYTES_SENT.with(|x| x.observe(stats.bytes_sent as Magnitude));
+        CONNECTION_BYTES_RECEIVED.with(|x| x.observe(stats.bytes_received as Magnitude));
+        CONNECTION_AGE.with(|x| x.observe(stats.age.as_millis() as Magnitude));
+    }
+}
+
+thread_local! {
+    static CONNECTION_BYTES_SENT: Event = EventBuilder::new()
+        .name("net_tcp_connection_bytes_sent")
+        .buckets(GENERAL_BYTES_BUCKETS)
+        .build()
+        .unwrap();
+
+    static CONNECTION_BYTES_RECEIVED: Event = EventBuilder::new()
+        .name("net_tcp_connection_bytes_received")
+        .buckets(GENERAL_BYTES_BUCKETS)
+        .build()
+        .unwrap();
+
+    static CONNECTION_AGE: Event = EventBuilder::new()
+        .name("net_tcp_connection_age_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_bytes_and_operations() {
+        let counters = TrafficCounters::new();
+
+        counters.add_sent(100);
+        counters.add_sent(50);
+        counters.add_received(10);
+        counters.add_received(0);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.bytes_sent, 150);
+        assert_eq!(stats.sends, 2);
+        assert_eq!(stats.bytes_received, 10);
+        assert_eq!(stats.receives, 2);
+    }
+
+    #[test]
+    fn clone_is_independent_copy() {
+        let counters = TrafficCounters::new();
+        counters.add_sent(42);
+
+        let moved = counters.clone();
+        counters.add_sent(1);
+
+        assert_eq!(moved.snapshot().bytes_sent, 42);
+        assert_eq!(moved.established, counters.established);
+    }
+}
@@ -0,0 +1,470 @@
+use crate::{
+    io::{self, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::{
+        proxy_protocol, socket_options,
+        tcp_server::{create_listen_socket, AcceptOne, AcceptedConnection, ListenOptions},
+        winsock, AcceptRateLimiter, ConnectionLimiter, Drainer, SocketOptions, TcpConnection,
+        TcpProfile,
+    },
+    rt::{clock, current_async_agent, sleep},
+    sync::CancellationToken,
+    util::OwnedHandle,
+};
+use futures::{
+    future::{self, Either, LocalBoxFuture},
+    FutureExt, Stream,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::RefCell,
+    net::SocketAddr,
+    num::{NonZeroU16, NonZeroU32, NonZeroUsize},
+    pin::{pin, Pin},
+    rc::Rc,
+    sync::Arc,
+    task,
+    time::Duration,
+};
+use windows::Win32::Networking::WinSock::SOCKET;
+
+/// What a `TcpListener` does with connections that exceed one of its limits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LimitAction {
+    /// Stop accepting until the limit allows more connections, leaving new connections waiting in
+    /// the backlog of the operating system. Once the backlog is full, further connection attempts
+    /// are refused by the operating system.
+    Queue,
+
+    /// Keep accepting and immediately reset the connections that exceed the limit, so clients fail
+    /// fast instead of waiting.
+    Reject,
+}
+
+/// A socket listening for TCP connections, owned by the current async worker thread. Connections
+/// are accepted on the same thread, one at a time, via `accept()` or the `incoming()` stream.
+///
+/// Unlike `TcpServerBuilder`, which dispatches each connection to a callback on any worker, this
+/// leaves the accept loop to the caller, so stream combinators can be used to shape it (e.g.
+/// `take_until()` to stop accepting on shutdown, or `buffer_unordered_local()` to limit how many
+/// connections are handled concurrently).
+///
+/// To protect the server from connection floods, the listener can limit the number of accepted
+/// connections that are open at the same time and the rate at which connections are accepted.
+///
+/// For zero-downtime deploys, `shutdown()` stops accepting and drains the accepted connections.
+pub struct TcpListener {
+    socket: Rc<OwnedHandle<SOCKET>>,
+    local_addr: SocketAddr,
+    socket_options: SocketOptions,
+    limits: Limits,
+    proxy_protocol: bool,
+    accept_filter: Option<AcceptFilter>,
+
+    // Tracks the accepted connections that are still live, shared by all clones of the listener.
+    drainer: Drainer,
+}
+
+type AcceptFilter = Rc<dyn Fn(SocketAddr) -> bool>;
+
+#[derive(Clone, Debug, Default)]
+struct Limits {
+    connections: Option<(Arc<ConnectionLimiter>, LimitAction)>,
+    rate: Option<(Rc<RefCell<AcceptRateLimiter>>, LimitAction)>,
+}
+
+impl TcpListener {
+    /// Starts listening for connections on the specified port on all local IPv4 addresses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub fn bind(port: NonZeroU16) -> io::Result<Self> {
+        let socket = create_listen_socket(ListenOptions {
+            port,
+            fast_open: false,
+            exclusive_address: false,
+        })?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        let local_addr = winsock::local_addr(*socket)?;
+
+        Ok(Self {
+            socket: Rc::new(socket),
+            local_addr,
+            socket_options: SocketOptions::default(),
+            limits: Limits::default(),
+            proxy_protocol: false,
+            accept_filter: None,
+            drainer: Drainer::new(),
+        })
+    }
+
+    /// Sets the socket options to apply to every accepted connection. By default, the operating
+    /// system defaults are used.
+    pub fn set_socket_options(&mut self, options: SocketOptions) {
+        self.socket_options = options;
+    }
+
+    /// Applies the socket options of a named profile to every accepted connection, replacing any
+    /// previously set socket options.
+    pub fn set_profile(&mut self, profile: TcpProfile) {
+        self.set_socket_options(profile.socket_options());
+    }
+
+    /// Limits the number of accepted connections that are open at the same time. A connection
+    /// counts against the limit until the `TcpConnection` is dropped. By default, there is no
+    /// limit.
+    ///
+    /// Only applies to connections accepted after the limit is set.
+    pub fn set_max_connections(&mut self, max: NonZeroUsize, action: LimitAction) {
+        self.limits.connections = Some((ConnectionLimiter::new(max.get()), action));
+    }
+
+    /// Limits the rate at which connections are accepted, on average. Bursts of up to one second
+    /// worth of connections are accepted at once. By default, there is no limit.
+    pub fn set_max_accept_rate(&mut self, per_second: NonZeroU32, action: LimitAction) {
+        self.limits.rate = Some((
+            Rc::new(RefCell::new(AcceptRateLimiter::new(
+                per_second.get(),
+                clock::now(),
+            ))),
+            action,
+        ));
+    }
+
+    /// If enabled, every accepted connection must start with a PROXY protocol header (version 1 or
+    /// 2), as sent by load balancers and reverse proxies such as HAProxy or AWS NLB to tell the
+    /// server about the original client. The header is removed from the stream and made available
+    /// via `TcpConnection::proxy_header()`. Any data received together with the header is available
+    /// via `TcpConnection::take_initial_data()`. Disabled by default.
+    ///
+    /// Connections without a valid header, or that do not deliver the header within
+    /// `PROXY_HEADER_TIMEOUT`, are closed and reported as accept errors. Headers are read one
+    /// connection at a time, before accepting the next, so only enable this if all connections
+    /// come from a trusted proxy - anyone able to connect directly can spoof the client address.
+    pub fn set_proxy_protocol(&mut self, enabled: bool) {
+        self.proxy_protocol = enabled;
+    }
+
+    /// Sets a filter that is given the address of each client before the connection is surfaced to
+    /// the application. Connections for which the filter returns false are reset right away and
+    /// never returned by `accept()` or `incoming()`, e.g. to enforce IP allow/deny lists without
+    /// paying for setting up the connection. Connections from clients whose address cannot be
+    /// determined are also rejected. By default, all connections are accepted.
+    ///
+    /// The filter is applied before the other limits, so rejected connections do not count against
+    /// the accept rate limit (unless the limit action is `LimitAction::Queue`, in which case the
+    /// rate is enforced before the connection is even accepted). The filter must not block - it is
+    /// called on the async worker.
+    pub fn set_accept_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(SocketAddr) -> bool + 'static,
+    {
+        self.accept_filter = Some(Rc::new(filter));
+    }
+
+    /// The local address the listener is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Accepts the next connection.
+    ///
+    /// Fails with `io::Error::Canceled` once the listener has been shut down.
+    pub async fn accept(&self) -> io::Result<TcpConnection> {
+        self.accept_one().await
+    }
+
+    /// Stops accepting connections: pending and future accepts fail with `io::Error::Canceled` and
+    /// the streams returned by `incoming()` end. The listen socket is closed once the listener and
+    /// its streams are dropped.
+    ///
+    /// If `drain_timeout` is set, also waits for the connections accepted by the listener to
+    /// finish, as `Drainer::drain()` does: handlers are expected to observe `shutdown_token()` and
+    /// wrap up, and any connection still live after the timeout has its I/O forcibly cancelled.
+    /// Resolves once all accepted connections have been dropped, returning the number of
+    /// connections that had to be forcibly cancelled. Without a timeout, returns 0 immediately.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+    pub async fn shutdown(&self, drain_timeout: Option<Duration>) -> usize {
+        match drain_timeout {
+            Some(timeout) => self.drainer.drain(timeout).await,
+            None => {
+                self.drainer.shutdown_token().cancel();
+                0
+            }
+        }
+    }
+
+    /// A token that is cancelled once `shutdown()` is called. Connection handlers can watch it to
+    /// wrap up their work when the listener is being drained.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.drainer.shutdown_token()
+    }
+
+    /// Number of connections accepted by the listener that are still live.
+    pub fn active_connections(&self) -> usize {
+        self.drainer.active_connections()
+    }
+
+    /// Returns a stream of accepted connections. The stream does not end on its own - a failure to
+    /// accept one connection is yielded as an error and the stream carries on with the next. The
+    /// stream ends once the listener is shut down.
+    ///
+    /// The stream does not borrow the listener and keeps the listen socket open until dropped.
+    /// Dropping the stream cancels any accept in progress.
+    pub fn incoming(&self) -> Incoming {
+        Incoming {
+            listener: TcpListener {
+                socket: Rc::clone(&self.socket),
+                local_addr: self.local_addr,
+                socket_options: self.socket_options,
+                limits: self.limits.clone(),
+                proxy_protocol: self.proxy_protocol,
+                accept_filter: self.accept_filter.clone(),
+                drainer: self.drainer.clone(),
+            },
+            accept: None,
+        }
+    }
+
+    fn accept_one(&self) -> LocalBoxFuture<'static, io::Result<TcpConnection>> {
+        let listen_socket = Rc::clone(&self.socket);
+        let socket_options = self.socket_options;
+        let limits = self.limits.clone();
+        let proxy_protocol = self.proxy_protocol;
+        let accept_filter = self.accept_filter.clone();
+        let drainer = self.drainer.clone();
+
+        async move {
+            let shutdown = drainer.shutdown_token();
+
+            let accept = accept_core(
+                listen_socket,
+                socket_options,
+                limits,
+                proxy_protocol,
+                accept_filter,
+            );
+
+            // Shutting down drops the accept in progress, which cancels it.
+            let mut connection =
+                match future::select(pin!(shutdown.cancelled()), pin!(accept)).await {
+                    Either::Left(_) => return Err(io::Error::Canceled),
+                    Either::Right((result, _)) => result?,
+                };
+
+            connection.set_drain_registration(drainer.register(connection.raw_socket()));
+            Ok(connection)
+        }
+        .boxed_local()
+    }
+}
+
+#[negative_impl]
+impl !Send for TcpListener {}
+#[negative_impl]
+impl !Sync for TcpListener {}
+
+/// Stream of connections accepted by a `TcpListener`, returned by `TcpListener::incoming()`.
+#[must_use = "streams do nothing unless polled"]
+pub struct Incoming {
+    listener: TcpListener,
+
+    // The accept in progress, if the stream has been polled since the last connection.
+    accept: Option<LocalBoxFuture<'static, io::Result<TcpConnection>>>,
+}
+
+impl Stream for Incoming {
+    type Item = io::Result<TcpConnection>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if this.accept.is_none() && this.listener.drainer.is_draining() {
+            return task::Poll::Ready(None);
+        }
+
+        let accept = this
+            .accept
+            .get_or_insert_with(|| this.listener.accept_one());
+
+        let result = task::ready!(accept.poll_unpin(cx));
+        this.accept = None;
+
+        match result {
+            // The accept was canceled because the listener was shut down.
+            Err(io::Error::Canceled) if this.listener.drainer.is_draining() => {
+                task::Poll::Ready(None)
+            }
+            result => task::Poll::Ready(Some(result)),
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for Incoming {}
+#[negative_impl]
+impl !Sync for Incoming {}
+
+async fn accept_core(
+    listen_socket: Rc<OwnedHandle<SOCKET>>,
+    socket_options: SocketOptions,
+    limits: Limits,
+    proxy_protocol: bool,
+    accept_filter: Option<AcceptFilter>,
+) -> io::Result<TcpConnection> {
+    loop {
+        if let Some((rate, LimitAction::Queue)) = &limits.rate {
+            wait_for_rate(rate).await;
+        }
+
+        // When queueing, we only start accepting once we have a permit (see AcceptOne).
+        let connection_limiter = match &limits.connections {
+            Some((limiter, LimitAction::Queue)) => Some(Arc::clone(limiter)),
+            _ => None,
+        };
+
+        let mut accepted = AcceptOne {
+            listen_socket: Rc::clone(&listen_socket),
+            connection_limiter,
+            socket_options,
+            receive_initial_data: false,
+            registered_io: false,
+            socket_pool: None,
+        }
+        .execute()
+        .await?;
+
+        if let Some(filter) = &accept_filter {
+            if !accepted.peer_addr.is_some_and(|addr| filter(addr)) {
+                REJECTED_BY_FILTER.with(Event::observe_unit);
+                reject(accepted);
+                continue;
+            }
+        }
+
+        if let Some((rate, LimitAction::Reject)) = &limits.rate {
+            if rate.borrow_mut().try_take(clock::now()).is_err() {
+                REJECTED_AT_RATE_LIMIT.with(Event::observe_unit);
+                reject(accepted);
+                continue;
+            }
+        }
+
+        if let Some((limiter, LimitAction::Reject)) = &limits.connections {
+            match limiter.try_acquire() {
+                Some(permit) => accepted.connection_permit = Some(permit),
+                None => {
+                    REJECTED_AT_CONNECTION_LIMIT.with(Event::observe_unit);
+                    reject(accepted);
+                    continue;
+                }
+            }
+        }
+
+        let mut connection = into_connection(accepted)?;
+
+        if proxy_protocol {
+            receive_proxy_header(&mut connection).await?;
+        }
+
+        return Ok(connection);
+    }
+}
+
+async fn wait_for_rate(rate: &RefCell<AcceptRateLimiter>) {
+    loop {
+        let result = rate.borrow_mut().try_take(clock::now());
+
+        match result {
+            Ok(()) => return,
+            Err(wait) => sleep(wait).await,
+        }
+    }
+}
+
+/// Closes an accepted connection that exceeds a limit or fails the accept filter. We reset the connection instead of closing
+/// it gracefully, so the socket is released immediately and the client knows it was turned away.
+fn reject(accepted: AcceptedConnection) {
+    // If this fails, the connection is closed gracefully instead, which is also fine.
+    _ = socket_options::set_linger(*accepted.socket, Some(Duration::ZERO));
+}
+
+fn into_connection(accepted: AcceptedConnection) -> io::Result<TcpConnection> {
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*accepted.socket))?;
+
+    let mut connection = TcpConnection::new(accepted.socket, accepted.connection_permit);
+
+    if let Some(initial_data) = accepted.initial_data {
+        connection.set_initial_data(PinnedBuffer::from_boxed_slice(initial_data));
+    }
+
+    Ok(connection)
+}
+
+/// How long a client of a listener that requires a PROXY protocol header has to deliver it.
+pub const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn receive_proxy_header(connection: &mut TcpConnection) -> io::Result<()> {
+    let deadline = clock::now() + PROXY_HEADER_TIMEOUT;
+    let mut received = Vec::new();
+
+    // The parser rejects data that cannot be the start of a header and headers that are too long,
+    // so we cannot receive forever.
+    loop {
+        if let Some((header, consumed)) = proxy_protocol::parse(&received)? {
+            connection.set_proxy_header(header);
+
+            if consumed < received.len() {
+                connection
+                    .set_initial_data(PinnedBuffer::from_boxed_slice(received[consumed..].into()));
+            }
+
+            PROXY_HEADERS_RECEIVED.with(Event::observe_unit);
+            return Ok(());
+        }
+
+        let timeout = deadline.saturating_duration_since(clock::now());
+
+        let buffer = connection
+            .receive_with_timeout(PinnedBuffer::from_pool(), timeout)
+            .await
+            .map_err(|e| e.into_inner())?;
+
+        if buffer.len() == 0 {
+            return Err(io::Error::ConnectionReset);
+        }
+
+        received.extend_from_slice(buffer.as_slice());
+    }
+}
+
+thread_local! {
+    static PROXY_HEADERS_RECEIVED: Event = EventBuilder::new()
+        .name("net_tcp_listener_proxy_headers_received")
+        .build()
+        .unwrap();
+
+    static REJECTED_AT_CONNECTION_LIMIT: Event = EventBuilder::new()
+        .name("net_tcp_listener_rejected_at_connection_limit")
+        .build()
+        .unwrap();
+
+    static REJECTED_AT_RATE_LIMIT: Event = EventBuilder::new()
+        .name("net_tcp_listener_rejected_at_rate_limit")
+        .build()
+        .unwrap();
+
+    static REJECTED_BY_FILTER: Event = EventBuilder::new()
+        .name("net_tcp_listener_rejected_by_filter")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,96 @@
+use crate::{io, net::SocketOptions};
+use std::{fmt, str::FromStr};
+
+/// Named presets of socket options for common kinds of TCP traffic, so servers get sensible
+/// settings without having to pick each option by hand. Select one for the accepted connections
+/// of a listener via `TcpListener::set_profile()` or `TcpServerBuilder::profile()`.
+///
+/// Profiles can also be parsed from their names (e.g. `"low-latency"`), to select them via
+/// configuration files or environment variables.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TcpProfile {
+    /// For request/response traffic with small messages, where each message should be sent as
+    /// soon as possible (e.g. RPC or interactive protocols). Disables the Nagle algorithm and
+    /// enables keepalive to detect dead peers. Name: `low-latency`.
+    LowLatency,
+
+    /// For transferring large amounts of data (e.g. file transfers or replication streams), where
+    /// throughput matters more than the latency of individual sends. Keeps the Nagle algorithm,
+    /// enables keepalive and uses large operating system buffers to keep the network busy on
+    /// links with high latency. Name: `bulk-throughput`.
+    BulkThroughput,
+}
+
+impl TcpProfile {
+    /// The name of the profile, as accepted by `TcpProfile::from_str()`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TcpProfile::LowLatency => "low-latency",
+            TcpProfile::BulkThroughput => "bulk-throughput",
+        }
+    }
+
+    /// The socket options the profile applies. These can be used as a starting point for
+    /// customized options.
+    pub fn socket_options(self) -> SocketOptions {
+        match self {
+            TcpProfile::LowLatency => SocketOptions::new().nodelay(true).keepalive(true),
+            TcpProfile::BulkThroughput => SocketOptions::new()
+                .nodelay(false)
+                .keepalive(true)
+                .receive_buffer_size(BULK_THROUGHPUT_BUFFER_SIZE)
+                .send_buffer_size(BULK_THROUGHPUT_BUFFER_SIZE),
+        }
+    }
+}
+
+impl FromStr for TcpProfile {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "low-latency" => Ok(TcpProfile::LowLatency),
+            "bulk-throughput" => Ok(TcpProfile::BulkThroughput),
+            _ => Err(io::Error::InvalidOptions(format!(
+                "unknown TCP profile {s}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for TcpProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<TcpProfile> for SocketOptions {
+    fn from(profile: TcpProfile) -> Self {
+        profile.socket_options()
+    }
+}
+
+/// Enough to cover the bandwidth-delay product of a 1 Gbps link with 30 ms of round trip time.
+const BULK_THROUGHPUT_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for profile in [TcpProfile::LowLatency, TcpProfile::BulkThroughput] {
+            assert_eq!(profile.name().parse::<TcpProfile>().unwrap(), profile);
+            assert_eq!(profile.to_string(), profile.name());
+        }
+    }
+
+    #[test]
+    fn parsing_ignores_case_and_whitespace() {
+        assert_eq!(
+            " Low-Latency ".parse::<TcpProfile>().unwrap(),
+            TcpProfile::LowLatency
+        );
+        assert!("fast".parse::<TcpProfile>().is_err());
+    }
+}
@@ -1,18 +1,29 @@
 use crate::{
-    io::{self, OperationResultExt},
-    net::{winsock, TcpConnection},
+    io::{self, OperationKind, OperationResultExt, PinnedBuffer},
+    net::{
+        socket_pool::{AcceptSocketPool, SocketReuse},
+        winsock, ConnectionLimiter, ConnectionPermit, SocketOptions, TcpConnection, TcpProfile,
+    },
     rt::{current_async_agent, current_runtime, spawn_on_any, RemoteJoinHandle},
     util::OwnedHandle,
 };
 use core::slice;
 use negative_impl::negative_impl;
-use std::{future::Future, mem, num::NonZeroU16, rc::Rc};
+use std::{
+    future::Future,
+    mem,
+    net::SocketAddr,
+    num::{NonZeroU16, NonZeroUsize},
+    rc::Rc,
+    sync::Arc,
+};
 use tracing::{event, Level};
 use windows::Win32::Networking::WinSock::{
     bind, htons, listen, setsockopt, AcceptEx, GetAcceptExSockaddrs, WSAIoctl, WSASocketA, AF_INET,
     INADDR_ANY, IN_ADDR, IPPROTO_TCP, SIO_QUERY_RSS_PROCESSOR_INFO, SOCKADDR, SOCKADDR_IN, SOCKET,
-    SOCKET_PROCESSOR_AFFINITY, SOCK_STREAM, SOL_SOCKET, SOMAXCONN, SO_UPDATE_ACCEPT_CONTEXT,
-    WSAEACCES, WSAEOPNOTSUPP, WSA_FLAG_OVERLAPPED,
+    SOCKET_PROCESSOR_AFFINITY, SOCK_STREAM, SOL_SOCKET, SOMAXCONN, SO_EXCLUSIVEADDRUSE,
+    SO_UPDATE_ACCEPT_CONTEXT, TCP_FASTOPEN, WSAEACCES, WSAEOPNOTSUPP, WSA_FLAG_OVERLAPPED,
+    WSA_FLAG_REGISTERED_IO,
 };
 
 pub struct TcpServerBuilder<A, AF>
@@ -21,6 +32,12 @@ where
     AF: Future<Output = io::Result<()>> + 'static,
 {
     port: Option<NonZeroU16>,
+    max_connections: Option<NonZeroUsize>,
+    socket_options: SocketOptions,
+    receive_initial_data: bool,
+    registered_io: bool,
+    fast_open: bool,
+    reuse_sockets: bool,
     on_accept: Option<A>,
 }
 
@@ -32,6 +49,12 @@ where
     pub fn new() -> Self {
         Self {
             port: None,
+            max_connections: None,
+            socket_options: SocketOptions::default(),
+            receive_initial_data: false,
+            registered_io: false,
+            fast_open: false,
+            reuse_sockets: false,
             on_accept: None,
         }
     }
@@ -41,6 +64,73 @@ where
         self
     }
 
+    /// Limits the number of live connections. Once the limit is reached, the server stops accepting
+    /// new connections (leaving them queued in the OS backlog) and resumes as soon as an accepted
+    /// `TcpConnection` is dropped. By default, there is no limit.
+    pub fn max_connections(mut self, max_connections: NonZeroUsize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Sets the socket options to apply to every accepted connection before it is handed to the
+    /// `on_accept` callback. By default, the operating system defaults are used.
+    pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+        self
+    }
+
+    /// Applies the socket options of a named profile to every accepted connection, replacing any
+    /// previously set socket options.
+    pub fn profile(self, profile: TcpProfile) -> Self {
+        self.socket_options(profile.socket_options())
+    }
+
+    /// If enabled, a connection is only accepted once the client has sent its first block of data,
+    /// which is received together with the accept in the same I/O completion. This saves one round
+    /// through the I/O driver for request/response protocols where the client speaks first. The
+    /// data is available via `TcpConnection::take_initial_data()`.
+    ///
+    /// Only use this for protocols where the client always sends data immediately after
+    /// connecting. Connections are accepted one at a time, so a client that connects and then stays
+    /// silent holds up the acceptance of all other connections. Disabled by default.
+    pub fn receive_initial_data(mut self, enabled: bool) -> Self {
+        self.receive_initial_data = enabled;
+        self
+    }
+
+    /// If enabled, accepted connections send and receive data via Registered I/O (RIO) instead of
+    /// overlapped I/O, which lowers the per-operation overhead for connections with many small
+    /// transfers. See `ConnectOptions::registered_io()` for the trade-offs. Disabled by default.
+    pub fn registered_io(mut self, enabled: bool) -> Self {
+        self.registered_io = enabled;
+        self
+    }
+
+    /// If enabled, the server accepts TCP Fast Open (TFO) connections, in which clients that
+    /// connected before send their first data together with the SYN (see
+    /// `ConnectOptions::fast_open()`). Disabled by default.
+    ///
+    /// Data received with the SYN may be a replay of an earlier connection attempt, so only enable
+    /// this for protocols where the first request from the client is safe to process twice.
+    pub fn fast_open(mut self, enabled: bool) -> Self {
+        self.fast_open = enabled;
+        self
+    }
+
+    /// If enabled, the sockets of accepted connections are returned to a socket pool of the server
+    /// when the connections are dropped and used to accept later connections, saving the cost of
+    /// creating a new socket for each connection. Disabled by default and not used together with
+    /// `registered_io()`.
+    ///
+    /// Sockets are returned to the pool via `DisconnectEx`, which may take a while to complete
+    /// (up to the TCP TIME_WAIT period if the server closes the connection first). Each pooled
+    /// socket remains bound to the async worker that handled its previous connection, so a
+    /// connection accepted into it is also handled by that worker.
+    pub fn reuse_sockets(mut self, enabled: bool) -> Self {
+        self.reuse_sockets = enabled;
+        self
+    }
+
     /// Sets the function to call when a new connection is accepted. The function may be called
     /// from any async task worker thread and any number of times concurrently.
     ///
@@ -64,19 +154,43 @@ where
             .on_accept
             .ok_or_else(|| io::Error::InvalidOptions("on_accept must be set".to_string()))?;
 
+        let connection_limiter = self
+            .max_connections
+            .map(|max| ConnectionLimiter::new(max.get()));
+        let listen_options = ListenOptions {
+            port,
+            fast_open: self.fast_open,
+            exclusive_address: false,
+        };
+        let accept_options = AcceptOptions {
+            socket_options: self.socket_options,
+            receive_initial_data: self.receive_initial_data,
+            registered_io: self.registered_io,
+            // Registered I/O sockets are created with a special flag, which pooled sockets lack.
+            socket_pool: (self.reuse_sockets && !self.registered_io)
+                .then(|| Arc::new(AcceptSocketPool::default())),
+        };
+
         let (startup_completed_tx, startup_completed_rx) = oneshot::channel();
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
         let join_handle = current_runtime::with(|x| {
             x.spawn_tcp_dispatcher(move || async move {
-                TcpDispatcher::new(port, on_accept, startup_completed_tx, shutdown_rx)
-                    .run()
-                    .await
+                TcpDispatcher::new(
+                    listen_options,
+                    on_accept,
+                    connection_limiter,
+                    accept_options,
+                    startup_completed_tx,
+                    shutdown_rx,
+                )
+                .run()
+                .await
             })
         });
 
-        match startup_completed_rx.await {
-            Ok(Ok(())) => {}
+        let local_addr = match startup_completed_rx.await {
+            Ok(Ok(local_addr)) => local_addr,
             Ok(Err(e)) => {
                 event!(
                     Level::ERROR,
@@ -90,11 +204,11 @@ where
                     "TCP dispatcher died before reporting startup result".to_string(),
                 ));
             }
-        }
+        };
 
         // We create the server handle even if startup failed because we use it to command the stop
         // in case of a failed startup.
-        let server_handle = TcpServerHandle::new(join_handle, shutdown_tx);
+        let server_handle = TcpServerHandle::new(join_handle, shutdown_tx, local_addr);
 
         event!(
             Level::INFO,
@@ -129,19 +243,28 @@ pub struct TcpServerHandle {
 
     // Consumed after signal is sent.
     dispatcher_shutdown_tx: Option<oneshot::Sender<()>>,
+
+    local_addr: SocketAddr,
 }
 
 impl TcpServerHandle {
     fn new(
         dispatcher_join_handle: RemoteJoinHandle<()>,
         dispatcher_shutdown_tx: oneshot::Sender<()>,
+        local_addr: SocketAddr,
     ) -> Self {
         Self {
             dispatcher_join_handle: dispatcher_join_handle,
             dispatcher_shutdown_tx: Some(dispatcher_shutdown_tx),
+            local_addr,
         }
     }
 
+    /// The local address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
     /// Stop the server. This will signal the server that it is to stop accepting new connections,
     /// and will start terminating existing connections. The method returns immediately. It may take
     /// some unspecified time for connection dispatch to actually stop and for ongoing connections
@@ -162,6 +285,33 @@ impl !Send for TcpServerHandle {}
 #[negative_impl]
 impl !Sync for TcpServerHandle {}
 
+/// Options that apply to the listen socket itself (as opposed to the accepted connections).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct ListenOptions {
+    pub(super) port: NonZeroU16,
+    pub(super) fast_open: bool,
+
+    /// Binds with `SO_EXCLUSIVEADDRUSE`, so no other socket can bind the same port.
+    pub(super) exclusive_address: bool,
+}
+
+/// Options that apply to every connection accepted by the dispatcher.
+#[derive(Clone, Debug)]
+struct AcceptOptions {
+    // Applied to every accepted connection before it is dispatched.
+    socket_options: SocketOptions,
+
+    // Whether accepting a connection also receives the first block of data from the client.
+    receive_initial_data: bool,
+
+    // Whether accepted connections use registered I/O for sending and receiving.
+    registered_io: bool,
+
+    // If set, accepted connections return their sockets to this pool when dropped and we accept
+    // new connections into the sockets from the pool.
+    socket_pool: Option<Arc<AcceptSocketPool>>,
+}
+
 /// The TCP dispatcher manages the listen socket used to receive new connections. When a new
 /// connection is received, it is dispatched to be handled by the user-defined callback on a
 /// suitable worker, at which point the dispatcher is no longer involved.
@@ -173,17 +323,22 @@ where
     // We signal this once we are ready to receive connections (or when startup fails).
     // If this is an error, you can expect that this (or a similar) error will also be included in
     // the result of `TcpServerHandle::wait()`. Consumed on use.
-    startup_completed_tx: Option<oneshot::Sender<io::Result<()>>>,
+    startup_completed_tx: Option<oneshot::Sender<io::Result<SocketAddr>>>,
 
     // If we receive a message from here, it means we need to shut down. Consumed on use.
     shutdown_rx: Option<oneshot::Receiver<()>>,
 
-    port: NonZeroU16,
+    listen_options: ListenOptions,
 
     // Whenever we receive a new connection, we spawn a new task with this callback to handle it.
     // Once we schedule a task to call this, the dispatcher forgets about the connection - anything
     // that happens afterward is the responsibility of the TcpConnection to organize.
     on_accept: A,
+
+    // If set, we only accept a new connection once we have a permit for it.
+    connection_limiter: Option<Arc<ConnectionLimiter>>,
+
+    accept_options: AcceptOptions,
     // TODO: on_connection_error (callback if connection fails, probably without affecting other connections or general health)
     // TODO: on_worker_error (callback if worker-level operation fails and we probably will not receive more traffic on this worker)
     // TODO: on_handler_error (callback if on_accept fails; do we need this or just let on_accept worry about it?)
@@ -195,14 +350,18 @@ where
     AF: Future<Output = io::Result<()>> + 'static,
 {
     fn new(
-        port: NonZeroU16,
+        listen_options: ListenOptions,
         on_accept: A,
-        startup_completed_tx: oneshot::Sender<io::Result<()>>,
+        connection_limiter: Option<Arc<ConnectionLimiter>>,
+        accept_options: AcceptOptions,
+        startup_completed_tx: oneshot::Sender<io::Result<SocketAddr>>,
         shutdown_rx: oneshot::Receiver<()>,
     ) -> Self {
         Self {
-            port,
+            listen_options,
             on_accept,
+            connection_limiter,
+            accept_options,
             startup_completed_tx: Some(startup_completed_tx),
             shutdown_rx: Some(shutdown_rx),
         }
@@ -211,7 +370,7 @@ where
     async fn run(&mut self) {
         let startup_result = match self.startup().await {
             Ok(x) => {
-                _ = self.startup_completed_tx.take().expect("we have completed startup so the tx must still be there because this is the only thing that uses it").send(Ok(()));
+                _ = self.startup_completed_tx.take().expect("we have completed startup so the tx must still be there because this is the only thing that uses it").send(Ok(x.local_addr));
                 x
             }
             Err(e) => {
@@ -232,56 +391,22 @@ where
     }
 
     async fn startup(&mut self) -> io::Result<StartedTcpDispatcher> {
-        winsock::ensure_initialized();
-
         // NOTE: Measure overhead of these operations. In principle, they are synchronous, although
         // sockets also have some thread-specific behaviors so we may want to avoid using them from
         // multiple threads. However, if this can incur significant latency, maybe consider it?
 
-        // SAFETY: We are required to close the handle once we are done with it,
-        // which we do via OwnedHandle that closes the handle on drop.
-        let listen_socket = unsafe {
-            OwnedHandle::new(WSASocketA(
-                AF_INET.0 as i32,
-                SOCK_STREAM.0 as i32,
-                IPPROTO_TCP.0 as i32,
-                None,
-                0,
-                WSA_FLAG_OVERLAPPED,
-            )?)
-        };
-
-        // TODO: Set send/receiver buffer sizes (will be inherited by spawned connections).
-
-        let mut addr = IN_ADDR::default();
-        addr.S_un.S_addr = INADDR_ANY;
-
-        let socket_addr = SOCKADDR_IN {
-            sin_family: AF_INET,
-            // SAFETY: Nothing unsafe here, just an FFI call.
-            sin_port: unsafe { htons(self.port.get()) },
-            sin_addr: addr,
-            sin_zero: [0; 8],
-        };
-
-        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
-        unsafe {
-            winsock::to_io_result(bind(
-                *listen_socket,
-                &socket_addr as *const _ as *const _,
-                mem::size_of::<SOCKADDR_IN>() as i32,
-            ))?;
-
-            winsock::to_io_result(listen(*listen_socket, SOMAXCONN as i32))?;
-        };
+        let listen_socket = create_listen_socket(self.listen_options)?;
 
         // Bind the socket to the I/O completion port so we can process I/O completions.
         current_async_agent::with_io(|io| {
             io.bind_io_primitive(&*listen_socket).unwrap();
         });
 
+        let local_addr = winsock::local_addr(*listen_socket)?;
+
         Ok(StartedTcpDispatcher {
             listen_socket: Rc::new(listen_socket),
+            local_addr,
         })
     }
 
@@ -297,11 +422,8 @@ where
         // socket because we stop polling if we release the resources. Any ongoing accept operations
         // will be terminated when the socket is closed, after which the I/O driver will process a
         // completion that will not be received by any awaiter any more and thus will be ignored.
-        // When we are shutting down, this operation will simply be abandoned.
-        //
-        // TODO: If a completion is ignored, won't that leave a dangling socket? Actually it will
-        // end up with a panic: "no expected code path drops the I/O operation without signaling completion result: RecvError"
-        // We probably need some Operation::on_cancel() callback to clean up the socket in that case, and to ignore drops otherwise.
+        // When we are shutting down, this operation will simply be abandoned, which cancels the
+        // pending accept.
         //
         // Because we are doing two things concurrently (accepting connections + awaiting orders),
         // we must use interior mutability or exclusive mutability only for one of these futures.
@@ -312,6 +434,11 @@ where
         let mut accept_one_fut = Box::pin(
             AcceptOne {
                 listen_socket: Rc::clone(&listen_socket),
+                connection_limiter: self.connection_limiter.clone(),
+                socket_options: self.accept_options.socket_options,
+                receive_initial_data: self.accept_options.receive_initial_data,
+                registered_io: self.accept_options.registered_io,
+                socket_pool: self.accept_options.socket_pool.clone(),
             }
             .execute(),
         );
@@ -334,21 +461,62 @@ where
                 .await
             {
                 futures::future::Either::Left((accept_result, new_shutdown_received_fut)) => {
-                    if let Ok(socket) = accept_result {
+                    if let Ok(AcceptedConnection {
+                        socket,
+                        connection_permit,
+                        initial_data,
+                        worker_index,
+                        ..
+                    }) = accept_result
+                    {
                         // New connection accepted! Spawn as task and detach.
                         let on_accept_clone = self.on_accept.clone();
-
-                        // TODO: Spawn on optimal processor, not a random one.
-                        _ = spawn_on_any(move || async move {
-                            current_async_agent::with_io(|io| {
-                                io.bind_io_primitive(&*socket).unwrap()
-                            });
-
-                            let tcp_connection = TcpConnection { socket };
+                        let registered_io = self.accept_options.registered_io;
+                        let socket_pool = self.accept_options.socket_pool.clone();
+
+                        let handle_connection = move || async move {
+                            // A pooled socket is already bound to the completion port of its worker.
+                            if worker_index.is_none() {
+                                current_async_agent::with_io(|io| {
+                                    io.bind_io_primitive(&*socket).unwrap()
+                                });
+                            }
+
+                            let mut tcp_connection = TcpConnection::new(socket, connection_permit);
+
+                            if let Some(socket_pool) = socket_pool {
+                                tcp_connection.set_socket_reuse(SocketReuse::Accept(socket_pool));
+                            }
+
+                            if let Some(initial_data) = initial_data {
+                                tcp_connection
+                                    .set_initial_data(PinnedBuffer::from_boxed_slice(initial_data));
+                            }
+
+                            if registered_io {
+                                if let Err(e) = tcp_connection.enable_registered_io() {
+                                    event!(
+                                        Level::ERROR,
+                                        message = "failed to enable registered I/O for accepted connection",
+                                        error = e.to_string()
+                                    );
+                                    return;
+                                }
+                            }
 
                             _ = (on_accept_clone)(tcp_connection).await;
                             // TODO: If callback result is error, report this error.
-                        });
+                        };
+
+                        match worker_index {
+                            Some(worker_index) => {
+                                _ = current_runtime::with(|x| {
+                                    x.spawn_on_worker(worker_index, handle_connection)
+                                });
+                            }
+                            // TODO: Spawn on optimal processor, not a random one.
+                            None => _ = spawn_on_any(handle_connection),
+                        }
                     }
 
                     // TODO: Report error if not successfully accepted..
@@ -358,6 +526,11 @@ where
                     accept_one_fut = Box::pin(
                         AcceptOne {
                             listen_socket: Rc::clone(&listen_socket),
+                            connection_limiter: self.connection_limiter.clone(),
+                            socket_options: self.accept_options.socket_options,
+                            receive_initial_data: self.accept_options.receive_initial_data,
+                            registered_io: self.accept_options.registered_io,
+                            socket_pool: self.accept_options.socket_pool.clone(),
                         }
                         .execute(),
                     );
@@ -382,38 +555,139 @@ where
     }
 }
 
+/// Creates a socket listening for connections according to the options. The caller is responsible
+/// for binding it to the I/O driver of the thread that will accept the connections.
+pub(super) fn create_listen_socket(
+    listen_options: ListenOptions,
+) -> io::Result<OwnedHandle<SOCKET>> {
+    winsock::ensure_initialized();
+
+    // SAFETY: We are required to close the handle once we are done with it,
+    // which we do via OwnedHandle that closes the handle on drop.
+    let listen_socket = unsafe {
+        OwnedHandle::new(WSASocketA(
+            AF_INET.0 as i32,
+            SOCK_STREAM.0 as i32,
+            IPPROTO_TCP.0 as i32,
+            None,
+            0,
+            WSA_FLAG_OVERLAPPED,
+        )?)
+    };
+
+    // TODO: Set send/receiver buffer sizes (will be inherited by spawned connections).
+
+    let mut addr = IN_ADDR::default();
+    addr.S_un.S_addr = INADDR_ANY;
+
+    let socket_addr = SOCKADDR_IN {
+        sin_family: AF_INET,
+        // SAFETY: Nothing unsafe here, just an FFI call.
+        sin_port: unsafe { htons(listen_options.port.get()) },
+        sin_addr: addr,
+        sin_zero: [0; 8],
+    };
+
+    if listen_options.exclusive_address {
+        // Must be set before we bind.
+        winsock::set_bool_option(*listen_socket, SOL_SOCKET, SO_EXCLUSIVEADDRUSE, true)?;
+    }
+
+    // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+    unsafe {
+        winsock::to_io_result(bind(
+            *listen_socket,
+            &socket_addr as *const _ as *const _,
+            mem::size_of::<SOCKADDR_IN>() as i32,
+        ))?;
+
+        if listen_options.fast_open {
+            // Must be set before we start listening.
+            winsock::set_bool_option(*listen_socket, IPPROTO_TCP.0, TCP_FASTOPEN, true)?;
+        }
+
+        winsock::to_io_result(listen(*listen_socket, SOMAXCONN as i32))?;
+    };
+
+    Ok(listen_socket)
+}
+
 struct StartedTcpDispatcher {
     // This is an Rc because we need to share it between the worker itself and the "AcceptOne"
     // subtasks that it spawns. We use Rc to avoid the need for AcceptOne to take a reference to
     // the worker, which would at the very least conflict with the worker itself using an exclusive
     // reference to itself.
     listen_socket: Rc<OwnedHandle<SOCKET>>,
+
+    local_addr: SocketAddr,
 }
 
 /// The state of a single "accept one connection" operation. We create this separate type to more
 /// easily separate the resource management of the command-processing loop from the resource
 /// management of the connection-accepting tasks.
-struct AcceptOne {
-    listen_socket: Rc<OwnedHandle<SOCKET>>,
+pub(super) struct AcceptOne {
+    pub(super) listen_socket: Rc<OwnedHandle<SOCKET>>,
+    pub(super) connection_limiter: Option<Arc<ConnectionLimiter>>,
+    pub(super) socket_options: SocketOptions,
+    pub(super) receive_initial_data: bool,
+    pub(super) registered_io: bool,
+
+    // If set, we accept into an idle socket from this pool if there is one.
+    pub(super) socket_pool: Option<Arc<AcceptSocketPool>>,
+}
+
+/// A connection accepted by `AcceptOne`, ready to be dispatched to a worker.
+pub(super) struct AcceptedConnection {
+    pub(super) socket: OwnedHandle<SOCKET>,
+    pub(super) connection_permit: Option<ConnectionPermit>,
+
+    // The first block of data received from the client, if we were asked to receive it. This is
+    // copied out of the I/O buffer because buffers are bound to the thread that created them and
+    // the connection will be handled on a different thread.
+    pub(super) initial_data: Option<Box<[u8]>>,
+
+    // The address of the client, as reported by AcceptEx. `None` if of an unknown address family.
+    pub(super) peer_addr: Option<SocketAddr>,
+
+    // If the socket came from the socket pool, the async worker whose completion port it is bound
+    // to and which therefore has to handle the connection.
+    pub(super) worker_index: Option<usize>,
 }
 
 impl AcceptOne {
-    async fn execute(self) -> io::Result<OwnedHandle<SOCKET>> {
-        // SAFETY: All we need to worry about here is cleanup, which we do via OwnedHandle.
-        let connection_socket = unsafe {
-            OwnedHandle::new(WSASocketA(
-                AF_INET.0 as i32,
-                SOCK_STREAM.0 as i32,
-                IPPROTO_TCP.0 as i32,
-                None,
-                0,
-                WSA_FLAG_OVERLAPPED,
-            )?)
+    pub(super) async fn execute(self) -> io::Result<AcceptedConnection> {
+        // If we are at the connection limit, we do not even start accepting until a slot frees up.
+        let connection_permit = match &self.connection_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
         };
 
-        // NOTE: AcceptEx supports immediately pasting the first block of received data in here,
-        // which may provide a performance boost when accepting the connection. This is optional
-        // and for now we disable this via setting dwReceiveDataLength to 0.
+        let pooled = self.socket_pool.as_ref().and_then(|pool| pool.take());
+
+        let (connection_socket, worker_index) = match pooled {
+            Some((socket, worker_index)) => (socket, Some(worker_index)),
+            // SAFETY: All we need to worry about here is cleanup, which we do via OwnedHandle.
+            None => unsafe {
+                let socket = OwnedHandle::new(WSASocketA(
+                    AF_INET.0 as i32,
+                    SOCK_STREAM.0 as i32,
+                    IPPROTO_TCP.0 as i32,
+                    None,
+                    0,
+                    if self.registered_io {
+                        WSA_FLAG_OVERLAPPED | WSA_FLAG_REGISTERED_IO
+                    } else {
+                        WSA_FLAG_OVERLAPPED
+                    },
+                )?);
+
+                (socket, None)
+            },
+        };
+
+        // AcceptEx supports immediately pasting the first block of received data in here, which
+        // saves a round trip through the I/O driver when accepting the connection. This is
+        // optional and disabled by setting dwReceiveDataLength to 0.
         //
         // Contents (not in order):
         // * Local address
@@ -433,7 +707,18 @@ impl AcceptOne {
 
         assert!(buffer.len() >= ADDRESS_LENGTH * 2);
 
-        let operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        let receive_data_length = if self.receive_initial_data {
+            buffer.len() - ADDRESS_LENGTH * 2
+        } else {
+            0
+        };
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+
+        // If the dispatcher stops waiting for us (e.g. because it is shutting down), the pending
+        // accept is canceled and the accept socket is released when this future is dropped.
+        operation.cancel_on_drop(**self.listen_socket);
+        operation.set_kind(OperationKind::Accept);
 
         // SAFETY: We are required to pass the OVERLAPPED struct to the native I/O function to avoid
         // a resource leak. We do.
@@ -443,7 +728,7 @@ impl AcceptOne {
                     **self.listen_socket,
                     *connection_socket,
                     buffer.as_mut_ptr() as *mut _,
-                    0,
+                    receive_data_length as u32,
                     ADDRESS_LENGTH as u32,
                     ADDRESS_LENGTH as u32,
                     immediate_bytes_transferred,
@@ -472,7 +757,7 @@ impl AcceptOne {
         unsafe {
             GetAcceptExSockaddrs(
                 payload.as_slice().as_ptr() as *const _,
-                0,
+                receive_data_length as u32,
                 ADDRESS_LENGTH as u32,
                 ADDRESS_LENGTH as u32,
                 &mut local_addr as *mut _,
@@ -482,6 +767,9 @@ impl AcceptOne {
             )
         };
 
+        // SAFETY: GetAcceptExSockaddrs points us at a valid address within the payload buffer.
+        let peer_addr = unsafe { winsock::from_native_socket_addr(remote_addr) };
+
         // We need to refer to this via pointer, so let's copy it out to an lvalue first.
         let listen_socket = self.listen_socket.0;
         // SAFETY: The size is right, so creating the slice is OK. We only use it for the single
@@ -505,6 +793,8 @@ impl AcceptOne {
             )
         })?;
 
+        self.socket_options.apply(*connection_socket)?;
+
         let affinity_info: SOCKET_PROCESSOR_AFFINITY = SOCKET_PROCESSOR_AFFINITY::default();
         let mut bytes_returned: u32 = 0;
 
@@ -561,7 +851,18 @@ impl AcceptOne {
 
         // The new socket is connected and ready! Finally!
         // TODO: Attach RSS info so it can actually be used for smart dispatch decisions.
-        Ok(connection_socket)
+        // The active region of the buffer is the received data (which excludes the addresses).
+        let initial_data = self
+            .receive_initial_data
+            .then(|| payload.as_slice().to_vec().into_boxed_slice());
+
+        Ok(AcceptedConnection {
+            socket: connection_socket,
+            connection_permit,
+            initial_data,
+            peer_addr,
+            worker_index,
+        })
     }
 }
 
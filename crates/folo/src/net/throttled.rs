@@ -0,0 +1,218 @@
+use crate::{
+    io::{OperationResult, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::TcpConnection,
+    rt::{self, sleep_until},
+};
+use std::{
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
+
+/// Caps the throughput of a connection in either direction, for services that need to limit the
+/// bandwidth of each client (e.g. bulk downloads).
+///
+/// Each direction has a token bucket that holds up to one second worth of bytes, allowing short
+/// bursts up to the per-second rate while keeping the long-term average at or below it. Operations
+/// wait on the timers of the runtime until the bucket is no longer in debt, after which they
+/// proceed regardless of their size and charge what they transferred to the bucket. This means a
+/// single buffer larger than the per-second rate is still transferred in one operation, with the
+/// following operations delayed accordingly.
+///
+/// Limiting receives only limits how fast we take data from the operating system - the peer is
+/// slowed down by TCP flow control once the receive window fills up.
+///
+/// Only operations made via the wrapper are throttled. Operations made directly on the inner
+/// connection (via `get_mut()`) are neither delayed nor charged.
+#[derive(Debug)]
+pub struct Throttled<C> {
+    inner: C,
+
+    read: Option<TokenBucket>,
+    write: Option<TokenBucket>,
+}
+
+impl<C> Throttled<C> {
+    /// Wraps a connection, initially without any limits.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            read: None,
+            write: None,
+        }
+    }
+
+    /// The maximum long-term average rate at which data is received, in bytes per second.
+    pub fn read_limit(&self) -> Option<NonZeroU64> {
+        self.read.as_ref().map(|bucket| bucket.bytes_per_second)
+    }
+
+    /// Sets the maximum long-term average rate at which data is received, in bytes per second, or
+    /// removes the limit. Changing the limit starts over with a full bucket.
+    pub fn set_read_limit(&mut self, bytes_per_second: Option<NonZeroU64>) {
+        self.read = bytes_per_second.map(|limit| TokenBucket::new(limit, rt::now()));
+    }
+
+    /// The maximum long-term average rate at which data is sent, in bytes per second.
+    pub fn write_limit(&self) -> Option<NonZeroU64> {
+        self.write.as_ref().map(|bucket| bucket.bytes_per_second)
+    }
+
+    /// Sets the maximum long-term average rate at which data is sent, in bytes per second, or
+    /// removes the limit. Changing the limit starts over with a full bucket.
+    pub fn set_write_limit(&mut self, bytes_per_second: Option<NonZeroU64>) {
+        self.write = bytes_per_second.map(|limit| TokenBucket::new(limit, rt::now()));
+    }
+
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Provides access to the inner connection. Operations made directly on the inner connection
+    /// are not throttled.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl Throttled<TcpConnection> {
+    /// Receives the next buffer of data, first waiting until the read limit allows it. See
+    /// `TcpConnection::receive()`.
+    pub async fn receive(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        wait_for_tokens(&mut self.read).await;
+
+        let result = self.inner.receive(buffer).await;
+
+        if let (Some(bucket), Ok(buffer)) = (&mut self.read, &result) {
+            bucket.consume(buffer.len(), rt::now());
+        }
+
+        result
+    }
+
+    /// Sends a buffer of data to the peer, first waiting until the write limit allows it. See
+    /// `TcpConnection::send()`.
+    pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        wait_for_tokens(&mut self.write).await;
+
+        // We charge the bucket up front, as the data is committed to the wire once handed over.
+        if let Some(bucket) = &mut self.write {
+            bucket.consume(buffer.len(), rt::now());
+        }
+
+        self.inner.send(buffer).await
+    }
+}
+
+async fn wait_for_tokens(bucket: &mut Option<TokenBucket>) {
+    let Some(bucket) = bucket else {
+        return;
+    };
+
+    // Timers may fire slightly early relative to our calculations, so we check again after waking.
+    loop {
+        let now = rt::now();
+
+        let Err(delay) = bucket.check(now) else {
+            return;
+        };
+
+        THROTTLE_DELAYS.with(Event::observe_unit);
+        sleep_until(now + delay).await;
+    }
+}
+
+/// A token bucket of bytes that may go into debt, so operations of any size can proceed once the
+/// debt has been repaid.
+#[derive(Debug)]
+struct TokenBucket {
+    bytes_per_second: NonZeroU64,
+
+    // Negative while in debt. Fractional tokens accumulate between operations.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_second: NonZeroU64, now: Instant) -> Self {
+        Self {
+            bytes_per_second,
+            tokens: bytes_per_second.get() as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Returns whether an operation may proceed. Otherwise, returns how long until the debt has
+    /// been repaid.
+    fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+
+        if self.tokens >= 0.0 {
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            -self.tokens / self.bytes_per_second.get() as f64,
+        ))
+    }
+
+    fn consume(&mut self, bytes: usize, now: Instant) {
+        self.refill(now);
+        self.tokens -= bytes as f64;
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+
+        let rate = self.bytes_per_second.get() as f64;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+    }
+}
+
+thread_local! {
+    static THROTTLE_DELAYS: Event = EventBuilder::new()
+        .name("net_throttled_delays")
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: NonZeroU64 = NonZeroU64::new(1000).unwrap();
+
+    #[test]
+    fn allows_burst_then_waits_for_debt() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RATE, start);
+
+        assert!(bucket.check(start).is_ok());
+        bucket.consume(600, start);
+        assert!(bucket.check(start).is_ok());
+
+        // This goes 500 bytes into debt, which takes half a second to repay.
+        bucket.consume(900, start);
+        let delay = bucket.check(start).unwrap_err();
+        assert!((delay.as_secs_f64() - 0.5).abs() < 1e-6);
+
+        assert!(bucket.check(start + Duration::from_millis(500)).is_ok());
+    }
+
+    #[test]
+    fn holds_at_most_one_second_of_tokens() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RATE, start);
+
+        let much_later = start + Duration::from_secs(60);
+        bucket.consume(2000, much_later);
+
+        let delay = bucket.check(much_later).unwrap_err();
+        assert!((delay.as_secs_f64() - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,17 @@
+//! TLS sessions over TCP connections, built on SChannel (the TLS implementation of Windows).
+//!
+//! Use `TlsConnector` to establish sessions as a client and `TlsAcceptor` to establish them as a
+//! server, either directly or via `TlsServerBuilder` or `TlsListener`. Certificates come from the
+//! Windows certificate store.
+
+mod acceptor;
+mod certificate;
+mod connector;
+mod context;
+mod credentials;
+mod stream;
+
+pub use acceptor::*;
+pub use certificate::*;
+pub use connector::*;
+pub use stream::*;
@@ -0,0 +1,47 @@
+use crate::{
+    io,
+    net::{
+        tls::{
+            context::{SecurityContext, Side},
+            credentials::Credentials,
+            Certificate, TlsStream,
+        },
+        TcpConnection,
+    },
+};
+use std::{future::Future, sync::Arc};
+
+/// Establishes TLS sessions as a server, via SChannel.
+///
+/// The acceptor holds the server credentials, so it should be created once and reused for all
+/// the connections. It is cheap to clone and can be shared between threads. It can be given to
+/// `TlsServerBuilder` or `TlsListener` to terminate TLS on accepted connections.
+#[derive(Clone, Debug)]
+pub struct TlsAcceptor {
+    credentials: Arc<Credentials>,
+}
+
+impl TlsAcceptor {
+    /// Creates an acceptor that identifies the server via the specified certificate, which must
+    /// have a private key.
+    pub fn new(certificate: &Certificate) -> io::Result<Self> {
+        Ok(Self {
+            credentials: Arc::new(Credentials::server(certificate)?),
+        })
+    }
+
+    /// Performs the TLS handshake with the client on the other end of the connection.
+    pub async fn accept(&self, connection: TcpConnection) -> io::Result<TlsStream> {
+        let context = SecurityContext::new(Arc::clone(&self.credentials), Side::Server);
+
+        TlsStream::establish(connection, context).await
+    }
+}
+
+impl crate::net::AcceptTls for TlsAcceptor {
+    type Stream = TlsStream;
+
+    fn accept(&self, connection: TcpConnection) -> impl Future<Output = io::Result<Self::Stream>> {
+        TlsAcceptor::accept(self, connection)
+    }
+}
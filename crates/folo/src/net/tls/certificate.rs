@@ -0,0 +1,267 @@
+use crate::io;
+use std::{ffi::c_void, fmt};
+use windows::{
+    core::HSTRING,
+    Win32::Security::Cryptography::{
+        CertCloseStore, CertCreateSelfSignCertificate, CertDuplicateCertificateContext,
+        CertFindCertificateInStore, CertFreeCertificateContext, CertGetCertificateContextProperty,
+        CertOpenStore, CertStrToNameW, CERT_CONTEXT, CERT_CREATE_SELFSIGN_FLAGS, CERT_FIND_FLAGS,
+        CERT_FIND_SHA1_HASH, CERT_FIND_SUBJECT_STR_W, CERT_OPEN_STORE_FLAGS,
+        CERT_QUERY_ENCODING_TYPE, CERT_SHA1_HASH_PROP_ID, CERT_STORE_OPEN_EXISTING_FLAG,
+        CERT_STORE_PROV_SYSTEM_W, CERT_STORE_READONLY_FLAG, CERT_SYSTEM_STORE_CURRENT_USER,
+        CERT_SYSTEM_STORE_LOCAL_MACHINE, CERT_X500_NAME_STR, CRYPT_INTEGER_BLOB, HCERTSTORE,
+        HCRYPTPROV_LEGACY, HCRYPTPROV_OR_NCRYPT_KEY_HANDLE, PKCS_7_ASN_ENCODING, X509_ASN_ENCODING,
+    },
+};
+
+const ENCODING: CERT_QUERY_ENCODING_TYPE =
+    CERT_QUERY_ENCODING_TYPE(X509_ASN_ENCODING.0 | PKCS_7_ASN_ENCODING.0);
+
+/// Which of the system certificate stores to search for certificates.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CertificateStoreLocation {
+    /// The stores of the user the process runs as.
+    CurrentUser,
+
+    /// The stores of the machine, typically used by services.
+    LocalMachine,
+}
+
+/// A certificate from the Windows certificate store, together with access to its private key
+/// (if the store has one for it). Used to identify a TLS server to its clients or a TLS client to
+/// servers that ask for client certificates.
+///
+/// Certificates are cheap to clone and can be shared between threads.
+pub struct Certificate {
+    context: *const CERT_CONTEXT,
+}
+
+impl Certificate {
+    /// Finds the first certificate whose subject contains the specified string (case-insensitive)
+    /// in a system certificate store, such as "My" (the personal certificates).
+    pub fn find_by_subject(
+        location: CertificateStoreLocation,
+        store_name: &str,
+        subject: &str,
+    ) -> io::Result<Self> {
+        let subject = HSTRING::from(subject);
+
+        Self::find(
+            location,
+            store_name,
+            CERT_FIND_SUBJECT_STR_W,
+            subject.as_ptr() as *const c_void,
+        )
+    }
+
+    /// Finds the certificate with the specified SHA-1 thumbprint (as shown by the certificate
+    /// management tools of Windows) in a system certificate store, such as "My" (the personal
+    /// certificates).
+    pub fn find_by_thumbprint(
+        location: CertificateStoreLocation,
+        store_name: &str,
+        thumbprint: &[u8],
+    ) -> io::Result<Self> {
+        let blob = CRYPT_INTEGER_BLOB {
+            cbData: thumbprint.len() as u32,
+            pbData: thumbprint.as_ptr() as *mut u8,
+        };
+
+        Self::find(
+            location,
+            store_name,
+            CERT_FIND_SHA1_HASH,
+            &blob as *const _ as *const c_void,
+        )
+    }
+
+    /// Creates a new self-signed certificate with the specified subject (an X.500 name such as
+    /// "CN=localhost"), valid for one year. Intended for testing and development, as clients do not
+    /// trust such certificates unless told to.
+    ///
+    /// The private key is created in a new key container of the current user, which is not deleted
+    /// when the certificate is dropped.
+    pub fn create_self_signed(subject: &str) -> io::Result<Self> {
+        let subject = HSTRING::from(subject);
+
+        let mut len = 0;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The first call
+        // only determines the size of the encoded name, the second fills it in.
+        unsafe {
+            CertStrToNameW(
+                X509_ASN_ENCODING,
+                &subject,
+                CERT_X500_NAME_STR,
+                None,
+                None,
+                &mut len,
+                None,
+            )?;
+        }
+
+        let mut encoded = vec![0_u8; len as usize];
+
+        // SAFETY: See above. The buffer is as large as the first call told us it needs to be.
+        unsafe {
+            CertStrToNameW(
+                X509_ASN_ENCODING,
+                &subject,
+                CERT_X500_NAME_STR,
+                None,
+                Some(encoded.as_mut_ptr()),
+                &mut len,
+                None,
+            )?;
+        }
+
+        let name = CRYPT_INTEGER_BLOB {
+            cbData: len,
+            pbData: encoded.as_mut_ptr(),
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. Without a key
+        // handle or key provider info, a new key pair is created in a new key container.
+        let context = unsafe {
+            CertCreateSelfSignCertificate(
+                HCRYPTPROV_OR_NCRYPT_KEY_HANDLE::default(),
+                &name,
+                CERT_CREATE_SELFSIGN_FLAGS(0),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+        };
+
+        if context.is_null() {
+            return Err(windows::core::Error::from_win32().into());
+        }
+
+        Ok(Self { context })
+    }
+
+    /// The SHA-1 thumbprint of the certificate, which identifies it in the certificate store.
+    pub fn thumbprint(&self) -> io::Result<Vec<u8>> {
+        let mut len = 0;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The first call
+        // only determines the size of the value, the second fills it in.
+        unsafe {
+            CertGetCertificateContextProperty(self.context, CERT_SHA1_HASH_PROP_ID, None, &mut len)?
+        };
+
+        let mut thumbprint = vec![0_u8; len as usize];
+
+        // SAFETY: See above. The buffer is as large as the first call told us it needs to be.
+        unsafe {
+            CertGetCertificateContextProperty(
+                self.context,
+                CERT_SHA1_HASH_PROP_ID,
+                Some(thumbprint.as_mut_ptr() as *mut c_void),
+                &mut len,
+            )?
+        };
+
+        thumbprint.truncate(len as usize);
+        Ok(thumbprint)
+    }
+
+    pub(super) fn as_ptr(&self) -> *const CERT_CONTEXT {
+        self.context
+    }
+
+    fn find(
+        location: CertificateStoreLocation,
+        store_name: &str,
+        find_type: CERT_FIND_FLAGS,
+        find_parameter: *const c_void,
+    ) -> io::Result<Self> {
+        let store = open_store(location, store_name)?;
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The certificate
+        // remains valid after the store is closed, as it keeps its own reference to the store.
+        let context = unsafe {
+            CertFindCertificateInStore(store.0, ENCODING, 0, find_type, Some(find_parameter), None)
+        };
+
+        if context.is_null() {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no matching certificate found in the {store_name} certificate store"),
+            )));
+        }
+
+        Ok(Self { context })
+    }
+}
+
+impl Clone for Certificate {
+    fn clone(&self) -> Self {
+        Self {
+            // SAFETY: Nothing unsafe here, just an FFI call with a valid certificate. This merely
+            // increments the reference count of the certificate.
+            context: unsafe { CertDuplicateCertificateContext(Some(self.context)) },
+        }
+    }
+}
+
+impl Drop for Certificate {
+    fn drop(&mut self) {
+        // SAFETY: Nothing unsafe here, just an FFI call with a valid certificate that we own.
+        unsafe {
+            _ = CertFreeCertificateContext(Some(self.context));
+        }
+    }
+}
+
+impl fmt::Debug for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Certificate")
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+// SAFETY: Certificate contexts are reference counted in a thread-safe manner and we never mutate
+// them, so they can be used from any thread.
+unsafe impl Send for Certificate {}
+// SAFETY: See above.
+unsafe impl Sync for Certificate {}
+
+struct Store(HCERTSTORE);
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        // SAFETY: Nothing unsafe here, just an FFI call with a valid store that we own.
+        unsafe {
+            _ = CertCloseStore(self.0, 0);
+        }
+    }
+}
+
+fn open_store(location: CertificateStoreLocation, store_name: &str) -> io::Result<Store> {
+    let location = match location {
+        CertificateStoreLocation::CurrentUser => CERT_SYSTEM_STORE_CURRENT_USER,
+        CertificateStoreLocation::LocalMachine => CERT_SYSTEM_STORE_LOCAL_MACHINE,
+    };
+
+    let store_name = HSTRING::from(store_name);
+
+    // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. We close the store via
+    // the returned wrapper once we are done with it.
+    let store = unsafe {
+        CertOpenStore(
+            CERT_STORE_PROV_SYSTEM_W,
+            CERT_QUERY_ENCODING_TYPE(0),
+            HCRYPTPROV_LEGACY::default(),
+            CERT_OPEN_STORE_FLAGS(location)
+                | CERT_STORE_READONLY_FLAG
+                | CERT_STORE_OPEN_EXISTING_FLAG,
+            Some(store_name.as_ptr() as *const c_void),
+        )?
+    };
+
+    Ok(Store(store))
+}
@@ -0,0 +1,93 @@
+use crate::{
+    io,
+    net::{
+        tls::{
+            context::{SecurityContext, Side},
+            credentials::Credentials,
+            Certificate, TlsStream,
+        },
+        TcpConnection,
+    },
+};
+use std::sync::Arc;
+
+/// Establishes TLS sessions as a client, via SChannel.
+///
+/// The connector holds the client credentials, so it should be created once and reused for all
+/// the connections. It is cheap to clone and can be shared between threads.
+#[derive(Clone, Debug)]
+pub struct TlsConnector {
+    credentials: Arc<Credentials>,
+}
+
+impl TlsConnector {
+    /// Creates a connector that validates the certificates of servers and does not present a
+    /// client certificate.
+    pub fn new() -> io::Result<Self> {
+        TlsConnectorBuilder::new().build()
+    }
+
+    /// Performs the TLS handshake with the server on the other end of the connection.
+    ///
+    /// The server name is sent to the server (via SNI) and the certificate of the server must be
+    /// valid for it, unless validation was disabled when building the connector.
+    pub async fn connect(
+        &self,
+        server_name: &str,
+        connection: TcpConnection,
+    ) -> io::Result<TlsStream> {
+        let target_name = server_name.encode_utf16().chain([0]).collect();
+
+        let context =
+            SecurityContext::new(Arc::clone(&self.credentials), Side::Client { target_name });
+
+        TlsStream::establish(connection, context).await
+    }
+}
+
+#[derive(Debug)]
+pub struct TlsConnectorBuilder {
+    client_certificate: Option<Certificate>,
+    accept_invalid_certificates: bool,
+}
+
+impl TlsConnectorBuilder {
+    pub fn new() -> Self {
+        Self {
+            client_certificate: None,
+            accept_invalid_certificates: false,
+        }
+    }
+
+    /// Sets the certificate to present to servers that ask for a client certificate. The
+    /// certificate must have a private key.
+    pub fn client_certificate(mut self, certificate: Certificate) -> Self {
+        self.client_certificate = Some(certificate);
+        self
+    }
+
+    /// Disables the validation of server certificates, accepting any certificate the server
+    /// presents (e.g. self-signed ones). This makes the connection vulnerable to interception, so
+    /// only use it for testing.
+    pub fn danger_accept_invalid_certificates(mut self, value: bool) -> Self {
+        self.accept_invalid_certificates = value;
+        self
+    }
+
+    pub fn build(self) -> io::Result<TlsConnector> {
+        let credentials = Credentials::client(
+            self.client_certificate.as_ref(),
+            !self.accept_invalid_certificates,
+        )?;
+
+        Ok(TlsConnector {
+            credentials: Arc::new(credentials),
+        })
+    }
+}
+
+impl Default for TlsConnectorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{tls::credentials::Credentials, TcpConnection},
+};
+use std::{ffi::c_void, ops::Range, ptr, slice, sync::Arc};
+use windows::Win32::{
+    Foundation::{
+        SEC_E_INCOMPLETE_MESSAGE, SEC_E_OK, SEC_I_CONTEXT_EXPIRED, SEC_I_CONTINUE_NEEDED,
+        SEC_I_INCOMPLETE_CREDENTIALS, SEC_I_RENEGOTIATE,
+    },
+    Security::{
+        Authentication::Identity::{
+            AcceptSecurityContext, ApplyControlToken, DecryptMessage, DeleteSecurityContext,
+            EncryptMessage, FreeContextBuffer, InitializeSecurityContextW, QueryContextAttributesW,
+            SecBuffer, SecBufferDesc, SecPkgContext_StreamSizes, ASC_REQ_ALLOCATE_MEMORY,
+            ASC_REQ_CONFIDENTIALITY, ASC_REQ_EXTENDED_ERROR, ASC_REQ_REPLAY_DETECT,
+            ASC_REQ_SEQUENCE_DETECT, ASC_REQ_STREAM, ISC_REQ_ALLOCATE_MEMORY,
+            ISC_REQ_CONFIDENTIALITY, ISC_REQ_EXTENDED_ERROR, ISC_REQ_FLAGS, ISC_REQ_REPLAY_DETECT,
+            ISC_REQ_SEQUENCE_DETECT, ISC_REQ_STREAM, ISC_REQ_USE_SUPPLIED_CREDS, SCHANNEL_SHUTDOWN,
+            SECBUFFER_DATA, SECBUFFER_EMPTY, SECBUFFER_EXTRA, SECBUFFER_STREAM_HEADER,
+            SECBUFFER_STREAM_TRAILER, SECBUFFER_TOKEN, SECBUFFER_VERSION, SECPKG_ATTR_STREAM_SIZES,
+        },
+        Credentials::SecHandle,
+    },
+};
+
+const CLIENT_FLAGS: ISC_REQ_FLAGS = ISC_REQ_FLAGS(
+    ISC_REQ_SEQUENCE_DETECT.0
+        | ISC_REQ_REPLAY_DETECT.0
+        | ISC_REQ_CONFIDENTIALITY.0
+        | ISC_REQ_EXTENDED_ERROR.0
+        | ISC_REQ_ALLOCATE_MEMORY.0
+        | ISC_REQ_STREAM.0,
+);
+
+/// The side of the TLS session we are on.
+#[derive(Debug)]
+pub(crate) enum Side {
+    /// The name of the server, as a null-terminated wide string. Used for SNI and to validate the
+    /// certificate of the server.
+    Client {
+        target_name: Vec<u16>,
+    },
+
+    Server,
+}
+
+/// How far along the handshake is after feeding it the data received so far.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum StepStatus {
+    /// The handshake is complete and the session can be used to exchange data.
+    Complete,
+
+    /// The peer needs to respond to our output before the handshake can continue.
+    ContinueNeeded,
+
+    /// The input did not contain a complete handshake message - receive more and try again.
+    IncompleteMessage,
+}
+
+#[derive(Debug)]
+pub(crate) struct Step {
+    pub status: StepStatus,
+
+    /// Data to send to the peer, if any.
+    pub output: Vec<u8>,
+
+    /// How many bytes from the start of the input were consumed. The rest belongs to the next
+    /// step (or to the data exchanged after the handshake).
+    pub consumed: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum DecryptStatus {
+    /// A record was decrypted. The plaintext (which may be empty) is in the input buffer.
+    Data,
+
+    /// The input did not contain a complete record - receive more and try again.
+    IncompleteMessage,
+
+    /// The peer closed the TLS session (sent close_notify).
+    Closed,
+
+    /// The peer sent handshake data, which must be fed to the handshake before receiving more.
+    Renegotiate,
+}
+
+#[derive(Debug)]
+pub(crate) struct Decrypted {
+    pub status: DecryptStatus,
+
+    /// Where the plaintext is in the input buffer, which SChannel decrypts in place.
+    pub plaintext: Range<usize>,
+
+    /// How many bytes from the start of the input were consumed.
+    pub consumed: usize,
+}
+
+/// An SChannel security context, representing one TLS session.
+#[derive(Debug)]
+pub(crate) struct SecurityContext {
+    credentials: Arc<Credentials>,
+
+    // None until the first handshake step has been performed.
+    handle: Option<SecHandle>,
+
+    side: Side,
+}
+
+impl SecurityContext {
+    pub fn new(credentials: Arc<Credentials>, side: Side) -> Self {
+        Self {
+            credentials,
+            handle: None,
+            side,
+        }
+    }
+
+    /// Feeds the data received from the peer (if any) to the handshake, producing the data to send
+    /// to the peer in response. Also used to produce the close_notify alert after
+    /// `begin_shutdown()`.
+    pub fn step(&mut self, input: &mut [u8]) -> io::Result<Step> {
+        let mut input_buffers = [
+            SecBuffer {
+                cbBuffer: input.len() as u32,
+                BufferType: SECBUFFER_TOKEN,
+                pvBuffer: input.as_mut_ptr() as *mut c_void,
+            },
+            empty_buffer(),
+        ];
+
+        let input_desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: input_buffers.len() as u32,
+            pBuffers: input_buffers.as_mut_ptr(),
+        };
+
+        let mut output_buffers = [SecBuffer {
+            cbBuffer: 0,
+            BufferType: SECBUFFER_TOKEN,
+            pvBuffer: ptr::null_mut(),
+        }];
+
+        let mut output_desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: output_buffers.len() as u32,
+            pBuffers: output_buffers.as_mut_ptr(),
+        };
+
+        let mut client_flags = CLIENT_FLAGS;
+
+        // The first step of the client has no input (it starts the handshake) and neither does
+        // the step that produces the close_notify alert.
+        let input_desc = (!input.is_empty()).then_some(&input_desc as *const _);
+
+        let status = loop {
+            let mut new_handle = SecHandle::default();
+            let mut attributes = 0;
+
+            // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The buffer
+            // descriptors point to buffers that live until the end of the function and the output
+            // token is allocated by SChannel, to be freed by us below.
+            let status = unsafe {
+                match &self.side {
+                    Side::Client { target_name } => InitializeSecurityContextW(
+                        Some(self.credentials.handle()),
+                        self.handle.as_ref().map(|x| x as *const _),
+                        Some(target_name.as_ptr()),
+                        client_flags,
+                        0,
+                        0,
+                        input_desc,
+                        0,
+                        Some(&mut new_handle),
+                        Some(&mut output_desc),
+                        &mut attributes,
+                        None,
+                    ),
+                    Side::Server => AcceptSecurityContext(
+                        Some(self.credentials.handle()),
+                        self.handle.as_ref().map(|x| x as *const _),
+                        input_desc,
+                        ASC_REQ_SEQUENCE_DETECT
+                            | ASC_REQ_REPLAY_DETECT
+                            | ASC_REQ_CONFIDENTIALITY
+                            | ASC_REQ_EXTENDED_ERROR
+                            | ASC_REQ_ALLOCATE_MEMORY
+                            | ASC_REQ_STREAM,
+                        0,
+                        Some(&mut new_handle),
+                        Some(&mut output_desc),
+                        &mut attributes,
+                        None,
+                    ),
+                }
+            };
+
+            // The context is created by the first call, which we must delete once done with it.
+            if self.handle.is_none() && new_handle != SecHandle::default() {
+                self.handle = Some(new_handle);
+            }
+
+            // The server asked for a client certificate and we do not have one. We just carry on
+            // without one and let the server decide whether that is acceptable.
+            if status == SEC_I_INCOMPLETE_CREDENTIALS && client_flags == CLIENT_FLAGS {
+                client_flags |= ISC_REQ_USE_SUPPLIED_CREDS;
+                continue;
+            }
+
+            break status;
+        };
+
+        let output = take_output_token(&mut output_buffers[0]);
+
+        let status = match status {
+            // We get the latter when producing the close_notify alert.
+            SEC_E_OK | SEC_I_CONTEXT_EXPIRED => StepStatus::Complete,
+            SEC_I_CONTINUE_NEEDED => StepStatus::ContinueNeeded,
+            SEC_E_INCOMPLETE_MESSAGE => {
+                return Ok(Step {
+                    status: StepStatus::IncompleteMessage,
+                    output,
+                    consumed: 0,
+                })
+            }
+            status => return Err(windows::core::Error::from(status).into()),
+        };
+
+        Ok(Step {
+            status,
+            output,
+            consumed: input.len() - extra_len(&input_buffers),
+        })
+    }
+
+    /// Decrypts the first record in `data` in place.
+    pub fn decrypt(&mut self, data: &mut [u8]) -> io::Result<Decrypted> {
+        let mut buffers = [
+            SecBuffer {
+                cbBuffer: data.len() as u32,
+                BufferType: SECBUFFER_DATA,
+                pvBuffer: data.as_mut_ptr() as *mut c_void,
+            },
+            empty_buffer(),
+            empty_buffer(),
+            empty_buffer(),
+        ];
+
+        let desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: buffers.len() as u32,
+            pBuffers: buffers.as_mut_ptr(),
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The buffers all point
+        // into `data`, which SChannel decrypts in place.
+        let status = unsafe { DecryptMessage(self.handle(), &desc, 0, None) };
+
+        let status = match status {
+            SEC_E_OK => DecryptStatus::Data,
+            SEC_E_INCOMPLETE_MESSAGE => {
+                return Ok(Decrypted {
+                    status: DecryptStatus::IncompleteMessage,
+                    plaintext: 0..0,
+                    consumed: 0,
+                })
+            }
+            SEC_I_CONTEXT_EXPIRED => DecryptStatus::Closed,
+            SEC_I_RENEGOTIATE => DecryptStatus::Renegotiate,
+            status => return Err(windows::core::Error::from(status).into()),
+        };
+
+        let plaintext = buffers
+            .iter()
+            .find(|buffer| buffer.BufferType == SECBUFFER_DATA && !buffer.pvBuffer.is_null())
+            .map_or(0..0, |buffer| {
+                let start = buffer.pvBuffer as usize - data.as_ptr() as usize;
+                start..start + buffer.cbBuffer as usize
+            });
+
+        Ok(Decrypted {
+            status,
+            plaintext,
+            consumed: data.len() - extra_len(&buffers),
+        })
+    }
+
+    /// Encrypts `data` into one record, returned as a buffer ready to be sent to the peer. The data
+    /// must not be longer than the maximum message size of the session.
+    pub fn encrypt(
+        &mut self,
+        sizes: &SecPkgContext_StreamSizes,
+        data: &[u8],
+    ) -> io::Result<PinnedBuffer> {
+        let header_len = sizes.cbHeader as usize;
+        let trailer_len = sizes.cbTrailer as usize;
+        let record_len = header_len + data.len() + trailer_len;
+
+        let mut record = PinnedBuffer::from_pool();
+
+        if record.capacity() < record_len {
+            record = PinnedBuffer::from_boxed_slice(vec![0; record_len].into_boxed_slice());
+        }
+
+        let record_slice = record.as_mut_slice_with_len(record_len);
+        record_slice[header_len..header_len + data.len()].copy_from_slice(data);
+
+        let (header, rest) = record_slice.split_at_mut(header_len);
+        let (body, trailer) = rest.split_at_mut(data.len());
+
+        let mut buffers = [
+            SecBuffer {
+                cbBuffer: header.len() as u32,
+                BufferType: SECBUFFER_STREAM_HEADER,
+                pvBuffer: header.as_mut_ptr() as *mut c_void,
+            },
+            SecBuffer {
+                cbBuffer: body.len() as u32,
+                BufferType: SECBUFFER_DATA,
+                pvBuffer: body.as_mut_ptr() as *mut c_void,
+            },
+            SecBuffer {
+                cbBuffer: trailer.len() as u32,
+                BufferType: SECBUFFER_STREAM_TRAILER,
+                pvBuffer: trailer.as_mut_ptr() as *mut c_void,
+            },
+            empty_buffer(),
+        ];
+
+        let desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: buffers.len() as u32,
+            pBuffers: buffers.as_mut_ptr(),
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The buffers all point
+        // into the record, which SChannel fills in place.
+        let status = unsafe { EncryptMessage(self.handle(), 0, &desc, 0) };
+
+        if status != SEC_E_OK {
+            return Err(windows::core::Error::from(status).into());
+        }
+
+        // The trailer may end up shorter than the maximum.
+        let record_len = buffers[..3]
+            .iter()
+            .map(|buffer| buffer.cbBuffer as usize)
+            .sum();
+        record.set_len(record_len);
+
+        Ok(record)
+    }
+
+    pub fn stream_sizes(&self) -> io::Result<SecPkgContext_StreamSizes> {
+        let mut sizes = SecPkgContext_StreamSizes::default();
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        unsafe {
+            QueryContextAttributesW(
+                self.handle(),
+                SECPKG_ATTR_STREAM_SIZES,
+                &mut sizes as *mut _ as *mut c_void,
+            )?;
+        }
+
+        Ok(sizes)
+    }
+
+    /// Prepares the context for closing the session. The next handshake step produces the
+    /// close_notify alert to send to the peer.
+    pub fn begin_shutdown(&mut self) -> io::Result<()> {
+        let mut token = SCHANNEL_SHUTDOWN;
+
+        let mut buffers = [SecBuffer {
+            cbBuffer: size_of_val(&token) as u32,
+            BufferType: SECBUFFER_TOKEN,
+            pvBuffer: &mut token as *mut _ as *mut c_void,
+        }];
+
+        let desc = SecBufferDesc {
+            ulVersion: SECBUFFER_VERSION,
+            cBuffers: buffers.len() as u32,
+            pBuffers: buffers.as_mut_ptr(),
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        unsafe {
+            ApplyControlToken(self.handle(), &desc)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle(&self) -> &SecHandle {
+        self.handle
+            .as_ref()
+            .expect("the handshake must be performed before the session is used")
+    }
+}
+
+impl Drop for SecurityContext {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            // SAFETY: Nothing unsafe here, just an FFI call with a valid handle that we own.
+            unsafe {
+                _ = DeleteSecurityContext(handle);
+            }
+        }
+    }
+}
+
+/// Performs the handshake (or continues it, e.g. after the peer asked for renegotiation) until it
+/// completes, using `incoming` as the data already received from the peer.
+///
+/// Returns any data received after the end of the handshake, which belongs to the session.
+pub(crate) async fn handshake(
+    context: &mut SecurityContext,
+    connection: &mut TcpConnection,
+    mut incoming: Vec<u8>,
+) -> io::Result<Vec<u8>> {
+    // The client has to say something first, without waiting for the server.
+    let mut need_input = matches!(context.side, Side::Server) || context.handle.is_some();
+
+    loop {
+        if need_input && incoming.is_empty() {
+            receive_more(connection, &mut incoming).await?;
+        }
+
+        let step = context.step(&mut incoming)?;
+        incoming.drain(..step.consumed);
+
+        if !step.output.is_empty() {
+            connection
+                .send(PinnedBuffer::from_boxed_slice(
+                    step.output.into_boxed_slice(),
+                ))
+                .await
+                .into_inner()?;
+        }
+
+        match step.status {
+            StepStatus::Complete => return Ok(incoming),
+            StepStatus::ContinueNeeded => need_input = true,
+            StepStatus::IncompleteMessage => receive_more(connection, &mut incoming).await?,
+        }
+    }
+}
+
+/// Receives the next chunk of data from the peer and appends it to `incoming`.
+pub(crate) async fn receive_more(
+    connection: &mut TcpConnection,
+    incoming: &mut Vec<u8>,
+) -> io::Result<()> {
+    let buffer = connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()?;
+
+    if buffer.len() == 0 {
+        return Err(io::Error::StdIo(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "connection closed in the middle of a TLS record",
+        )));
+    }
+
+    incoming.extend_from_slice(buffer.as_slice());
+    Ok(())
+}
+
+fn empty_buffer() -> SecBuffer {
+    SecBuffer {
+        cbBuffer: 0,
+        BufferType: SECBUFFER_EMPTY,
+        pvBuffer: ptr::null_mut(),
+    }
+}
+
+/// The number of bytes at the end of the input that SChannel did not consume.
+fn extra_len(buffers: &[SecBuffer]) -> usize {
+    buffers
+        .iter()
+        .find(|buffer| buffer.BufferType == SECBUFFER_EXTRA)
+        .map_or(0, |buffer| buffer.cbBuffer as usize)
+}
+
+/// Copies the token that SChannel allocated for us and frees the original.
+fn take_output_token(buffer: &mut SecBuffer) -> Vec<u8> {
+    if buffer.pvBuffer.is_null() {
+        return Vec::new();
+    }
+
+    // SAFETY: SChannel allocated a buffer of the specified size for us.
+    let token =
+        unsafe { slice::from_raw_parts(buffer.pvBuffer as *const u8, buffer.cbBuffer as usize) }
+            .to_vec();
+
+    // SAFETY: Nothing unsafe here, just an FFI call with a buffer allocated by SChannel.
+    unsafe {
+        _ = FreeContextBuffer(buffer.pvBuffer);
+    }
+
+    buffer.pvBuffer = ptr::null_mut();
+    token
+}
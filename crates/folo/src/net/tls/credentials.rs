@@ -0,0 +1,102 @@
+use crate::{io, net::tls::Certificate};
+use std::{ffi::c_void, ptr};
+use windows::{
+    core::PCWSTR,
+    Win32::Security::{
+        Authentication::Identity::{
+            AcquireCredentialsHandleW, FreeCredentialsHandle, SCHANNEL_CRED_FLAGS, SCH_CREDENTIALS,
+            SCH_CREDENTIALS_VERSION, SCH_CRED_AUTO_CRED_VALIDATION,
+            SCH_CRED_MANUAL_CRED_VALIDATION, SCH_CRED_NO_DEFAULT_CREDS, SCH_USE_STRONG_CRYPTO,
+            SECPKG_CRED, SECPKG_CRED_INBOUND, SECPKG_CRED_OUTBOUND, UNISP_NAME,
+        },
+        Credentials::SecHandle,
+        Cryptography::CERT_CONTEXT,
+    },
+};
+
+/// An SChannel credentials handle, shared by all the TLS sessions of a connector or acceptor.
+#[derive(Debug)]
+pub(crate) struct Credentials {
+    handle: SecHandle,
+}
+
+impl Credentials {
+    pub fn client(certificate: Option<&Certificate>, validate_server: bool) -> io::Result<Self> {
+        let validation = if validate_server {
+            SCH_CRED_AUTO_CRED_VALIDATION
+        } else {
+            SCH_CRED_MANUAL_CRED_VALIDATION
+        };
+
+        // Without NO_DEFAULT_CREDS, SChannel would pick a client certificate on its own if the
+        // server asks for one, which may pop up UI or pick an unexpected certificate.
+        Self::acquire(
+            SECPKG_CRED_OUTBOUND,
+            certificate,
+            validation | SCH_CRED_NO_DEFAULT_CREDS | SCH_USE_STRONG_CRYPTO,
+        )
+    }
+
+    pub fn server(certificate: &Certificate) -> io::Result<Self> {
+        Self::acquire(
+            SECPKG_CRED_INBOUND,
+            Some(certificate),
+            SCH_USE_STRONG_CRYPTO,
+        )
+    }
+
+    pub fn handle(&self) -> &SecHandle {
+        &self.handle
+    }
+
+    fn acquire(
+        direction: SECPKG_CRED,
+        certificate: Option<&Certificate>,
+        flags: SCHANNEL_CRED_FLAGS,
+    ) -> io::Result<Self> {
+        let mut certificate = certificate.map_or(ptr::null(), Certificate::as_ptr);
+
+        let auth_data = SCH_CREDENTIALS {
+            dwVersion: SCH_CREDENTIALS_VERSION,
+            cCreds: if certificate.is_null() { 0 } else { 1 },
+            paCred: &mut certificate as *mut *const CERT_CONTEXT as *mut *mut CERT_CONTEXT,
+            dwFlags: flags.0,
+            ..Default::default()
+        };
+
+        let mut handle = SecHandle::default();
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments. The certificate is
+        // only used during the call (SChannel keeps its own reference to it). We free the handle
+        // on drop.
+        unsafe {
+            AcquireCredentialsHandleW(
+                PCWSTR::null(),
+                UNISP_NAME,
+                direction,
+                None,
+                Some(&auth_data as *const _ as *const c_void),
+                None,
+                None,
+                &mut handle,
+                None,
+            )?;
+        }
+
+        Ok(Self { handle })
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        // SAFETY: Nothing unsafe here, just an FFI call with a valid handle that we own.
+        unsafe {
+            _ = FreeCredentialsHandle(&self.handle);
+        }
+    }
+}
+
+// SAFETY: SChannel credentials handles may be used from any thread, including concurrently.
+unsafe impl Send for Credentials {}
+// SAFETY: See above.
+unsafe impl Sync for Credentials {}
@@ -0,0 +1,214 @@
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    io::{self, OperationError, OperationResult, OperationResultExt, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::{
+        tls::context::{self, DecryptStatus, SecurityContext},
+        TcpConnection,
+    },
+    util::LowPrecisionInstant,
+};
+use negative_impl::negative_impl;
+use std::cmp;
+use windows::Win32::Security::Authentication::Identity::SecPkgContext_StreamSizes;
+
+/// An established TLS session over a TCP connection, obtained from `TlsConnector::connect()` or
+/// `TlsAcceptor::accept()`.
+///
+/// The API mirrors that of `TcpConnection` - data is exchanged via `PinnedBuffer` instances, with
+/// encryption and decryption performed by SChannel on the current thread.
+pub struct TlsStream {
+    connection: TcpConnection,
+    context: SecurityContext,
+    sizes: SecPkgContext_StreamSizes,
+
+    // Data received from the peer that has not yet been decrypted.
+    incoming: Vec<u8>,
+
+    // Decrypted data that has not yet been handed to the caller, starting at `plaintext_start`.
+    plaintext: Vec<u8>,
+    plaintext_start: usize,
+
+    // The peer has closed the TLS session (or the connection).
+    read_closed: bool,
+}
+
+impl TlsStream {
+    /// Performs the handshake on the connection and wraps it into a TLS session.
+    pub(crate) async fn establish(
+        mut connection: TcpConnection,
+        mut context: SecurityContext,
+    ) -> io::Result<Self> {
+        let started = LowPrecisionInstant::now();
+
+        // Anything the connection received before we took it over belongs to the handshake.
+        let initial = connection
+            .take_initial_data()
+            .map(|buffer| buffer.as_slice().to_vec())
+            .unwrap_or_default();
+
+        let result = match context::handshake(&mut context, &mut connection, initial).await {
+            Ok(incoming) => context.stream_sizes().map(|sizes| (incoming, sizes)),
+            Err(e) => Err(e),
+        };
+
+        let (incoming, sizes) = match result {
+            Ok(x) => x,
+            Err(e) => {
+                HANDSHAKES_FAILED.with(Event::observe_unit);
+                return Err(e);
+            }
+        };
+
+        HANDSHAKE_DURATION.with(|x| x.observe_millis(started.elapsed()));
+
+        Ok(Self {
+            connection,
+            context,
+            sizes,
+            incoming,
+            plaintext: Vec::new(),
+            plaintext_start: 0,
+            read_closed: false,
+        })
+    }
+
+    /// Receives the next chunk of decrypted data into the buffer.
+    ///
+    /// The buffer will be returned in the result with the active region set to the bytes read, with
+    /// a length of 0 if the peer closed the TLS session. A peer that closes the connection without
+    /// closing the TLS session first results in an error, as the data may have been truncated.
+    pub async fn receive(&mut self, mut buffer: PinnedBuffer) -> OperationResult {
+        loop {
+            let available = &self.plaintext[self.plaintext_start..];
+
+            if !available.is_empty() {
+                let len = cmp::min(available.len(), buffer.len());
+                buffer.as_mut_slice()[..len].copy_from_slice(&available[..len]);
+                buffer.set_len(len);
+
+                self.plaintext_start += len;
+                return Ok(buffer);
+            }
+
+            if self.read_closed {
+                buffer.set_len(0);
+                return Ok(buffer);
+            }
+
+            if let Err(e) = self.decrypt_next().await {
+                return Err(OperationError::new(e, buffer));
+            }
+        }
+    }
+
+    /// Encrypts and sends a buffer of data to the peer, split into as many TLS records as needed.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        let max_record_len = self.sizes.cbMaximumMessage as usize;
+
+        for chunk in buffer.as_slice().chunks(max_record_len) {
+            let result = match self.context.encrypt(&self.sizes, chunk) {
+                Ok(record) => self.connection.send(record).await.into_inner(),
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                return Err(OperationError::new(e, buffer));
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Closes the TLS session by sending a close_notify alert to the peer, which then sees the end
+    /// of the stream. The TCP connection itself remains open until the stream is dropped.
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.context.begin_shutdown()?;
+
+        let step = self.context.step(&mut [])?;
+
+        if !step.output.is_empty() {
+            self.connection
+                .send(PinnedBuffer::from_boxed_slice(
+                    step.output.into_boxed_slice(),
+                ))
+                .await
+                .into_inner()?;
+        }
+
+        Ok(())
+    }
+
+    /// The underlying TCP connection.
+    pub fn get_ref(&self) -> &TcpConnection {
+        &self.connection
+    }
+
+    /// The underlying TCP connection. Sending or receiving data directly on it corrupts the TLS
+    /// session.
+    pub fn get_mut(&mut self) -> &mut TcpConnection {
+        &mut self.connection
+    }
+
+    /// Decrypts the next record received from the peer, receiving more data as needed.
+    async fn decrypt_next(&mut self) -> io::Result<()> {
+        loop {
+            if !self.incoming.is_empty() {
+                let decrypted = self.context.decrypt(&mut self.incoming)?;
+
+                match decrypted.status {
+                    DecryptStatus::Data => {
+                        self.plaintext.clear();
+                        self.plaintext
+                            .extend_from_slice(&self.incoming[decrypted.plaintext]);
+                        self.plaintext_start = 0;
+
+                        self.incoming.drain(..decrypted.consumed);
+                        return Ok(());
+                    }
+                    DecryptStatus::Closed => {
+                        self.read_closed = true;
+                        self.incoming.clear();
+                        return Ok(());
+                    }
+                    DecryptStatus::Renegotiate => {
+                        // Whatever was not consumed is handshake data (e.g. TLS 1.3 session
+                        // tickets), followed by any records sent after it.
+                        let handshake_data = self.incoming.split_off(decrypted.consumed);
+
+                        self.incoming = context::handshake(
+                            &mut self.context,
+                            &mut self.connection,
+                            handshake_data,
+                        )
+                        .await?;
+                        continue;
+                    }
+                    DecryptStatus::IncompleteMessage => {}
+                }
+            }
+
+            context::receive_more(&mut self.connection, &mut self.incoming).await?;
+        }
+    }
+}
+
+#[negative_impl]
+impl !Send for TlsStream {}
+#[negative_impl]
+impl !Sync for TlsStream {}
+
+thread_local! {
+    static HANDSHAKE_DURATION: Event = EventBuilder::new()
+        .name("net_tls_handshake_duration_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
+    static HANDSHAKES_FAILED: Event = EventBuilder::new()
+        .name("net_tls_handshakes_failed")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,79 @@
+use crate::{
+    io,
+    net::{tls_server, AcceptTls, TcpListener},
+};
+use negative_impl::negative_impl;
+use std::{net::SocketAddr, time::Duration};
+
+/// A `TcpListener` that terminates TLS, accepting established TLS sessions instead of plain TCP
+/// connections. Connections that fail the handshake or do not complete it within the handshake
+/// timeout are closed without the caller ever seeing them.
+///
+/// Like `TcpListener`, this leaves the accept loop to the caller. Handshakes are performed one at
+/// a time as part of `accept()`, so a slow client holds up the next accept for up to the handshake
+/// timeout. Use `TlsServerBuilder` to perform handshakes concurrently on any worker thread.
+pub struct TlsListener<T>
+where
+    T: AcceptTls,
+{
+    listener: TcpListener,
+    acceptor: T,
+    handshake_timeout: Duration,
+}
+
+impl<T> TlsListener<T>
+where
+    T: AcceptTls,
+{
+    /// Wraps a listener, using `acceptor` to establish TLS sessions on the accepted connections.
+    /// Any limits and options of the listener keep applying to the accepted connections.
+    pub fn new(listener: TcpListener, acceptor: T) -> Self {
+        Self {
+            listener,
+            acceptor,
+            handshake_timeout: tls_server::DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// How long a client may take to complete the TLS handshake, counted from the moment the
+    /// connection is accepted. Defaults to 10 seconds.
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) {
+        self.handshake_timeout = timeout;
+    }
+
+    /// The local address the listener is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// The wrapped listener, e.g. to shut it down.
+    pub fn listener(&self) -> &TcpListener {
+        &self.listener
+    }
+
+    /// Accepts the next connection that completes the TLS handshake.
+    ///
+    /// Fails if accepting a connection fails, e.g. with `io::Error::Canceled` once the listener
+    /// has been shut down.
+    pub async fn accept(&self) -> io::Result<T::Stream> {
+        loop {
+            let connection = self.listener.accept().await?;
+
+            // The handshake already reports the failure, so we simply move on to the next client.
+            if let Ok(stream) =
+                tls_server::handshake(&self.acceptor, connection, self.handshake_timeout).await
+            {
+                return Ok(stream);
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> TcpListener {
+        self.listener
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for TlsListener<T> where T: AcceptTls {}
+#[negative_impl]
+impl<T> !Sync for TlsListener<T> where T: AcceptTls {}
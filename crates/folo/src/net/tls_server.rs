@@ -0,0 +1,195 @@
+use crate::{
+    io,
+    metrics::{Event, EventBuilder},
+    net::{TcpConnection, TcpServerBuilder, TcpServerHandle},
+    rt::sleep,
+};
+use futures::future::{self, Either};
+use negative_impl::negative_impl;
+use std::{future::Future, num::NonZeroU16, pin::pin, time::Duration};
+use tracing::{event, Level};
+
+/// Performs the server side of a TLS handshake on an accepted TCP connection. Implemented by TLS
+/// integrations to plug them into `TlsServerBuilder` and `TlsListener`.
+///
+/// The acceptor is cloned to every worker thread that handles connections, so it should be cheap
+/// to clone (e.g. an `Arc` around the shared server configuration).
+pub trait AcceptTls: Clone + Send + 'static {
+    /// The established TLS session, which the application uses to exchange data with the peer.
+    type Stream: 'static;
+
+    fn accept(&self, connection: TcpConnection) -> impl Future<Output = io::Result<Self::Stream>>;
+}
+
+/// Builds a TCP server that terminates TLS before handing connections to the application. The
+/// `on_accept` callback is only ever called with established TLS sessions - connections that fail
+/// the handshake or do not complete it within the handshake timeout are closed without the
+/// application ever seeing them.
+///
+/// Apart from the TLS handshake, the server behaves the same as one built via `TcpServerBuilder`.
+pub struct TlsServerBuilder<T, A, AF>
+where
+    T: AcceptTls,
+    A: Fn(T::Stream) -> AF + Clone + Send + 'static,
+    AF: Future<Output = io::Result<()>> + 'static,
+{
+    port: Option<NonZeroU16>,
+    acceptor: Option<T>,
+    handshake_timeout: Duration,
+    on_accept: Option<A>,
+}
+
+impl<T, A, AF> TlsServerBuilder<T, A, AF>
+where
+    T: AcceptTls,
+    A: Fn(T::Stream) -> AF + Clone + Send + 'static,
+    AF: Future<Output = io::Result<()>> + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            port: None,
+            acceptor: None,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            on_accept: None,
+        }
+    }
+
+    pub fn port(mut self, port: NonZeroU16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn acceptor(mut self, acceptor: T) -> Self {
+        self.acceptor = Some(acceptor);
+        self
+    }
+
+    /// How long a client may take to complete the TLS handshake, counted from the moment the
+    /// connection is picked up by a worker thread. Defaults to 10 seconds.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Sets the function to call when a new TLS session has been established. The function may be
+    /// called from any async task worker thread and any number of times concurrently.
+    ///
+    /// The connection will be closed when the provided stream is dropped.
+    pub fn on_accept(mut self, callback: A) -> Self {
+        self.on_accept = Some(callback);
+        self
+    }
+
+    /// Builds the TLS server and starts accepting new connections. See `TcpServerBuilder::build()`.
+    pub async fn build(self) -> io::Result<TcpServerHandle> {
+        let port = self
+            .port
+            .ok_or_else(|| io::Error::InvalidOptions("port must be set".to_string()))?;
+        let acceptor = self
+            .acceptor
+            .ok_or_else(|| io::Error::InvalidOptions("acceptor must be set".to_string()))?;
+        let on_accept = self
+            .on_accept
+            .ok_or_else(|| io::Error::InvalidOptions("on_accept must be set".to_string()))?;
+
+        let handshake_timeout = self.handshake_timeout;
+
+        TcpServerBuilder::new()
+            .port(port)
+            .on_accept(move |connection| {
+                let acceptor = acceptor.clone();
+                let on_accept = on_accept.clone();
+
+                async move {
+                    let stream = handshake(&acceptor, connection, handshake_timeout).await?;
+                    (on_accept)(stream).await
+                }
+            })
+            .build()
+            .await
+    }
+}
+
+impl<T, A, AF> Default for TlsServerBuilder<T, A, AF>
+where
+    T: AcceptTls,
+    A: Fn(T::Stream) -> AF + Clone + Send + 'static,
+    AF: Future<Output = io::Result<()>> + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[negative_impl]
+impl<T, A, AF> !Send for TlsServerBuilder<T, A, AF>
+where
+    T: AcceptTls,
+    A: Fn(T::Stream) -> AF + Clone + Send + 'static,
+    AF: Future<Output = io::Result<()>> + 'static,
+{
+}
+#[negative_impl]
+impl<T, A, AF> !Sync for TlsServerBuilder<T, A, AF>
+where
+    T: AcceptTls,
+    A: Fn(T::Stream) -> AF + Clone + Send + 'static,
+    AF: Future<Output = io::Result<()>> + 'static,
+{
+}
+
+pub(super) async fn handshake<T>(
+    acceptor: &T,
+    connection: TcpConnection,
+    timeout: Duration,
+) -> io::Result<T::Stream>
+where
+    T: AcceptTls,
+{
+    let accept = pin!(acceptor.accept(connection));
+
+    // If the timeout wins, dropping the handshake future drops the connection, closing it.
+    match future::select(accept, pin!(sleep(timeout))).await {
+        Either::Left((Ok(stream), _)) => {
+            HANDSHAKES_COMPLETED.with(Event::observe_unit);
+            Ok(stream)
+        }
+        Either::Left((Err(e), _)) => {
+            HANDSHAKES_FAILED.with(Event::observe_unit);
+
+            event!(
+                Level::DEBUG,
+                message = "TLS handshake failed",
+                error = e.to_string()
+            );
+
+            Err(e)
+        }
+        Either::Right(_) => {
+            HANDSHAKES_TIMED_OUT.with(Event::observe_unit);
+
+            event!(Level::DEBUG, message = "TLS handshake timed out");
+
+            Err(io::Error::TimedOut)
+        }
+    }
+}
+
+pub(super) const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+thread_local! {
+    static HANDSHAKES_COMPLETED: Event = EventBuilder::new()
+        .name("net_tls_handshakes_completed")
+        .build()
+        .unwrap();
+
+    static HANDSHAKES_FAILED: Event = EventBuilder::new()
+        .name("net_tls_handshakes_failed")
+        .build()
+        .unwrap();
+
+    static HANDSHAKES_TIMED_OUT: Event = EventBuilder::new()
+        .name("net_tls_handshakes_timed_out")
+        .build()
+        .unwrap();
+}
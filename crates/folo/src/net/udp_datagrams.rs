@@ -0,0 +1,135 @@
+//! `futures::Stream` and `Sink` over the datagrams of a `UdpSocket`, enabled by the `udp-stream`
+//! feature.
+
+use crate::{
+    io::{self, BufferSize, OperationError, OperationResult, PinnedBuffer},
+    net::UdpSocket,
+};
+use futures::{future::LocalBoxFuture, FutureExt, Sink, Stream};
+use negative_impl::negative_impl;
+use std::{
+    fmt,
+    net::SocketAddr,
+    pin::Pin,
+    task::{self, ready},
+};
+
+type ReceiveFromResult = Result<(PinnedBuffer, SocketAddr), OperationError>;
+
+/// Exchanges datagrams with any peer of a `UdpSocket` via the `futures::Stream` and `futures::Sink`
+/// traits. Obtain one via `UdpSocket::datagrams()`.
+///
+/// As a stream, yields each received datagram together with its sender, with the active region of
+/// the buffer set to the datagram. The stream never ends - a failed receive yields an error and
+/// the next poll starts a new receive. One receive is outstanding at a time, started when the
+/// stream is polled.
+///
+/// As a sink, sends each buffer to the address it is paired with. One send is in flight at a time:
+/// the sink is ready for the next datagram once the previous one has been handed to the operating
+/// system, and any error from sending is reported by the next `poll_ready()`, flush or close.
+///
+/// Receiving and sending proceed independently, so the two halves may be driven concurrently, e.g.
+/// after `StreamExt::split()`. Dropping the adapter cancels the operations in progress.
+pub struct UdpDatagrams<'a> {
+    socket: &'a UdpSocket,
+    size: BufferSize,
+
+    receive: Option<LocalBoxFuture<'a, ReceiveFromResult>>,
+    send: Option<LocalBoxFuture<'a, OperationResult>>,
+}
+
+impl<'a> UdpDatagrams<'a> {
+    pub(super) fn new(socket: &'a UdpSocket, size: BufferSize) -> Self {
+        Self {
+            socket,
+            size,
+            receive: None,
+            send: None,
+        }
+    }
+
+    pub fn socket(&self) -> &'a UdpSocket {
+        self.socket
+    }
+
+    /// Waits for the in-flight send (if any) to complete and reports its outcome.
+    fn poll_send_completed(&mut self, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        let Some(operation) = &mut self.send else {
+            return task::Poll::Ready(Ok(()));
+        };
+
+        let result = ready!(operation.poll_unpin(cx));
+        self.send = None;
+
+        task::Poll::Ready(result.map(|_| ()).map_err(OperationError::into_inner))
+    }
+}
+
+impl Stream for UdpDatagrams<'_> {
+    type Item = io::Result<(PinnedBuffer, SocketAddr)>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let socket = this.socket;
+        let size = this.size;
+
+        let operation = this.receive.get_or_insert_with(|| {
+            socket
+                .receive_from_core(PinnedBuffer::from_pool_with_size(size))
+                .boxed_local()
+        });
+
+        let result = ready!(operation.poll_unpin(cx));
+        this.receive = None;
+
+        task::Poll::Ready(Some(result.map_err(OperationError::into_inner)))
+    }
+}
+
+impl Sink<(PinnedBuffer, SocketAddr)> for UdpDatagrams<'_> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        self.get_mut().poll_send_completed(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: (PinnedBuffer, SocketAddr)) -> io::Result<()> {
+        let this = self.get_mut();
+        assert!(
+            this.send.is_none(),
+            "start_send() called without poll_ready() reporting readiness"
+        );
+
+        let (buffer, addr) = item;
+        this.send = Some(this.socket.send_to_core(buffer, addr).boxed_local());
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        self.get_mut().poll_send_completed(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<io::Result<()>> {
+        self.get_mut().poll_send_completed(cx)
+    }
+}
+
+impl fmt::Debug for UdpDatagrams<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpDatagrams")
+            .field("size", &self.size)
+            .field("receiving", &self.receive.is_some())
+            .field("sending", &self.send.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[negative_impl]
+impl !Send for UdpDatagrams<'_> {}
+#[negative_impl]
+impl !Sync for UdpDatagrams<'_> {}
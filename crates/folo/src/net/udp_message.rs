@@ -0,0 +1,423 @@
+use crate::io::PinnedBuffer;
+use std::{
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    num::NonZeroUsize,
+    ptr,
+};
+use windows::Win32::Networking::WinSock::{
+    CMSGHDR, IPPROTO_IP, IPPROTO_IPV6, IPPROTO_UDP, IPV6_ECN, IPV6_HOPLIMIT, IPV6_PKTINFO, IP_ECN,
+    IP_HOPLIMIT, IP_PKTINFO, IP_TTL, SOCKADDR_STORAGE, UDP_COALESCED_INFO, WSABUF, WSAMSG,
+};
+
+/// How many bytes at the end of the buffer given to the `UdpSocket` receives that deliver control
+/// information along with the datagram (`UdpSocket::receive_message()` and
+/// `UdpSocket::receive_coalesced()`) are used to store
+/// the address of the sender and the control information.
+pub const RECEIVE_MESSAGE_RESERVE: usize =
+    mem::size_of::<MessageSlots>() + mem::align_of::<MessageSlots>();
+
+const CONTROL_BUFFER_LEN: usize = 256;
+
+/// Everything that WSARecvMsg references, which must remain valid until the operation completes.
+#[repr(C)]
+pub(crate) struct MessageSlots {
+    pub(crate) sender: SOCKADDR_STORAGE,
+    pub(crate) message: WSAMSG,
+    pub(crate) data: WSABUF,
+
+    // Of u64, to satisfy the alignment of the control message headers.
+    pub(crate) control: [u64; CONTROL_BUFFER_LEN / mem::size_of::<u64>()],
+}
+
+impl MessageSlots {
+    /// Carves out the (aligned) slots from the reserved region at the end of a receive buffer.
+    /// Always yields the same slots for the same region.
+    pub(crate) fn from_reserve(reserve: &mut [u8]) -> &mut Self {
+        assert!(reserve.len() >= RECEIVE_MESSAGE_RESERVE);
+
+        let offset = reserve.as_ptr().align_offset(mem::align_of::<Self>());
+
+        // SAFETY: The region is large enough for an aligned instance and every bit pattern is a
+        // valid instance, as the type only consists of integers and raw pointers.
+        unsafe { &mut *(reserve.as_mut_ptr().add(offset) as *mut Self) }
+    }
+
+    pub(crate) fn control_bytes(&self) -> &[u8] {
+        let len = (self.message.Control.len as usize).min(CONTROL_BUFFER_LEN);
+
+        // SAFETY: The control buffer is plain memory of CONTROL_BUFFER_LEN bytes.
+        unsafe { std::slice::from_raw_parts(self.control.as_ptr() as *const u8, len) }
+    }
+}
+
+/// The control information received together with a datagram.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ControlData {
+    /// If the operating system coalesced multiple datagrams into the buffer, the size of each of
+    /// them (except the last, which may be shorter).
+    pub(crate) coalesced_segment_size: Option<NonZeroUsize>,
+
+    /// The local address the datagram was sent to and the index of the network interface it
+    /// arrived on (IP_PKTINFO/IPV6_PKTINFO).
+    pub(crate) destination: Option<IpAddr>,
+    pub(crate) interface_index: Option<u32>,
+
+    /// The TTL or hop limit of the IP packet (IP_RECVTTL/IPV6_HOPLIMIT).
+    pub(crate) hop_limit: Option<u8>,
+
+    /// The ECN codepoint of the IP packet (IP_RECVECN/IPV6_RECVECN).
+    pub(crate) ecn: Option<EcnCodepoint>,
+}
+
+/// Parses the control messages filled in by WSARecvMsg. Unknown and malformed messages are
+/// ignored.
+pub(crate) fn parse_control(control: &[u8]) -> ControlData {
+    let mut data = ControlData::default();
+
+    for (level, kind, payload) in control_messages(control) {
+        match (level, kind) {
+            (level, kind) if level == IPPROTO_UDP.0 && kind == UDP_COALESCED_INFO as i32 => {
+                data.coalesced_segment_size =
+                    read_u32(payload).and_then(|size| NonZeroUsize::new(size as usize));
+            }
+            (level, IP_PKTINFO) if level == IPPROTO_IP.0 => {
+                // IN_PKTINFO: the address in network byte order, followed by the interface index.
+                if let (Some(addr), Some(index)) = (payload.get(..4), payload.get(4..)) {
+                    let octets: [u8; 4] = addr.try_into().expect("slice is 4 bytes");
+                    data.destination = Some(Ipv4Addr::from(octets).into());
+                    data.interface_index = read_u32(index);
+                }
+            }
+            (level, IPV6_PKTINFO) if level == IPPROTO_IPV6.0 => {
+                // IN6_PKTINFO: the address in network byte order, followed by the interface index.
+                if let (Some(addr), Some(index)) = (payload.get(..16), payload.get(16..)) {
+                    let octets: [u8; 16] = addr.try_into().expect("slice is 16 bytes");
+                    data.destination = Some(Ipv6Addr::from(octets).into());
+                    data.interface_index = read_u32(index);
+                }
+            }
+            // IP_RECVTTL is documented to deliver IP_TTL but shares its value with IP_HOPLIMIT,
+            // which some versions of Windows deliver instead.
+            (level, IP_TTL | IP_HOPLIMIT) if level == IPPROTO_IP.0 => {
+                data.hop_limit = read_u32(payload).and_then(|value| u8::try_from(value).ok());
+            }
+            (level, IPV6_HOPLIMIT) if level == IPPROTO_IPV6.0 => {
+                data.hop_limit = read_u32(payload).and_then(|value| u8::try_from(value).ok());
+            }
+            (level, IP_ECN) if level == IPPROTO_IP.0 => {
+                data.ecn = read_u32(payload).and_then(EcnCodepoint::from_bits);
+            }
+            (level, IPV6_ECN) if level == IPPROTO_IPV6.0 => {
+                data.ecn = read_u32(payload).and_then(EcnCodepoint::from_bits);
+            }
+            _ => {}
+        }
+    }
+
+    data
+}
+
+const HEADER_LEN: usize = mem::size_of::<CMSGHDR>();
+const HEADER_ALIGN: usize = mem::align_of::<CMSGHDR>();
+
+// WSA_CMSGDATA_ALIGN aligns the payload to MAX_NATURAL_ALIGNMENT, which is 8 bytes.
+const PAYLOAD_OFFSET: usize = align_up(HEADER_LEN, 8);
+
+const fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// Iterates over the (level, type, payload) of each control message, as walked by the
+/// WSA_CMSG_FIRSTHDR/WSA_CMSG_NXTHDR macros.
+fn control_messages(control: &[u8]) -> impl Iterator<Item = (i32, i32, &[u8])> {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        if offset + HEADER_LEN > control.len() {
+            return None;
+        }
+
+        // SAFETY: We just checked that the header is within bounds. It may be unaligned if the
+        // caller passed an unaligned slice, hence the unaligned read.
+        let header = unsafe { ptr::read_unaligned(control[offset..].as_ptr() as *const CMSGHDR) };
+
+        let end = offset + header.cmsg_len;
+        if header.cmsg_len < PAYLOAD_OFFSET || end > control.len() {
+            return None;
+        }
+
+        let payload = &control[offset + PAYLOAD_OFFSET..end];
+        offset += align_up(header.cmsg_len, HEADER_ALIGN);
+
+        Some((header.cmsg_level, header.cmsg_type, payload))
+    })
+}
+
+fn read_u32(payload: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(payload.get(..4)?.try_into().ok()?))
+}
+
+/// Datagrams received by `UdpSocket::receive_coalesced()`. With receive coalescing enabled, the
+/// operating system may deliver multiple datagrams of the same flow in one buffer, each except the
+/// last exactly `segment_size()` bytes long.
+#[derive(Debug)]
+pub struct CoalescedDatagrams {
+    buffer: PinnedBuffer,
+    sender: SocketAddr,
+    segment_size: Option<NonZeroUsize>,
+}
+
+impl CoalescedDatagrams {
+    pub(crate) fn new(
+        buffer: PinnedBuffer,
+        sender: SocketAddr,
+        segment_size: Option<NonZeroUsize>,
+    ) -> Self {
+        Self {
+            buffer,
+            sender,
+            segment_size,
+        }
+    }
+
+    /// The sender of the datagrams. Only datagrams from the same sender are coalesced.
+    pub fn sender(&self) -> SocketAddr {
+        self.sender
+    }
+
+    /// The size of each coalesced datagram, or `None` if the buffer holds a single datagram.
+    pub fn segment_size(&self) -> Option<NonZeroUsize> {
+        self.segment_size
+    }
+
+    /// The individual datagrams, in the order in which they arrived.
+    pub fn datagrams(&self) -> impl Iterator<Item = &[u8]> {
+        let data = self.buffer.as_slice();
+
+        // chunks() yields nothing for empty data but an empty datagram is still a datagram.
+        let segment_size = self
+            .segment_size
+            .map_or(data.len().max(1), NonZeroUsize::get);
+
+        data.chunks(segment_size)
+            .chain(data.is_empty().then_some(data))
+    }
+
+    /// The buffer, with the active region set to all the received data.
+    pub fn buffer(&self) -> &PinnedBuffer {
+        &self.buffer
+    }
+
+    pub fn into_buffer(self) -> PinnedBuffer {
+        self.buffer
+    }
+}
+
+/// The Explicit Congestion Notification codepoint of an IP packet (RFC 3168), as used by
+/// congestion controllers such as those of QUIC.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EcnCodepoint {
+    /// The sender does not support ECN (Not-ECT).
+    NotCapable,
+
+    /// ECN capable transport, codepoint 1 (ECT(1)).
+    Ect1,
+
+    /// ECN capable transport, codepoint 0 (ECT(0)).
+    Ect0,
+
+    /// A router along the path experienced congestion (CE).
+    CongestionExperienced,
+}
+
+impl EcnCodepoint {
+    /// Interprets the two ECN bits of the IP header.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            0b00 => Some(Self::NotCapable),
+            0b01 => Some(Self::Ect1),
+            0b10 => Some(Self::Ect0),
+            0b11 => Some(Self::CongestionExperienced),
+            _ => None,
+        }
+    }
+
+    /// The two ECN bits of the IP header.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::NotCapable => 0b00,
+            Self::Ect1 => 0b01,
+            Self::Ect0 => 0b10,
+            Self::CongestionExperienced => 0b11,
+        }
+    }
+}
+
+/// A datagram received by `UdpSocket::receive_message()`, together with the control information
+/// that the socket has been configured to deliver. Each piece of control information is `None` if
+/// the corresponding socket option is not enabled or the operating system did not report it.
+#[derive(Debug)]
+pub struct ReceivedMessage {
+    buffer: PinnedBuffer,
+    sender: SocketAddr,
+    control: ControlData,
+}
+
+impl ReceivedMessage {
+    pub(crate) fn new(buffer: PinnedBuffer, sender: SocketAddr, control: ControlData) -> Self {
+        Self {
+            buffer,
+            sender,
+            control,
+        }
+    }
+
+    pub fn sender(&self) -> SocketAddr {
+        self.sender
+    }
+
+    /// The local address that the datagram was sent to. On a socket bound to the unspecified
+    /// address, this is the address to reply from so the peer recognizes the reply. Requires
+    /// `UdpSocket::set_receive_packet_info()`.
+    pub fn destination(&self) -> Option<IpAddr> {
+        self.control.destination
+    }
+
+    /// The index of the network interface that the datagram arrived on. Requires
+    /// `UdpSocket::set_receive_packet_info()`.
+    pub fn interface_index(&self) -> Option<u32> {
+        self.control.interface_index
+    }
+
+    /// The remaining TTL (IPv4) or hop limit (IPv6) of the datagram when it arrived. Requires
+    /// `UdpSocket::set_receive_hop_limit()`.
+    pub fn hop_limit(&self) -> Option<u8> {
+        self.control.hop_limit
+    }
+
+    /// The ECN codepoint of the datagram. Requires `UdpSocket::set_receive_ecn()`.
+    pub fn ecn(&self) -> Option<EcnCodepoint> {
+        self.control.ecn
+    }
+
+    /// The buffer, with the active region set to the datagram.
+    pub fn buffer(&self) -> &PinnedBuffer {
+        &self.buffer
+    }
+
+    pub fn into_buffer(self) -> PinnedBuffer {
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_message(level: i32, kind: i32, payload: &[u8]) -> Vec<u8> {
+        let header = CMSGHDR {
+            cmsg_len: PAYLOAD_OFFSET + payload.len(),
+            cmsg_level: level,
+            cmsg_type: kind,
+        };
+
+        let mut data = vec![0; PAYLOAD_OFFSET];
+
+        // SAFETY: The vector is large enough for the header.
+        unsafe { ptr::write_unaligned(data.as_mut_ptr() as *mut CMSGHDR, header) };
+
+        data.extend_from_slice(payload);
+        data.resize(align_up(data.len(), HEADER_ALIGN), 0);
+        data
+    }
+
+    #[test]
+    fn parses_coalesced_info_among_other_messages() {
+        let mut control = control_message(41, 50, &[1, 2, 3]);
+        control.extend(control_message(
+            IPPROTO_UDP.0,
+            UDP_COALESCED_INFO as i32,
+            &1200u32.to_ne_bytes(),
+        ));
+
+        let data = parse_control(&control);
+        assert_eq!(data.coalesced_segment_size, NonZeroUsize::new(1200));
+    }
+
+    #[test]
+    fn parses_ipv4_packet_info_ttl_and_ecn() {
+        let mut pktinfo = vec![192, 168, 1, 10];
+        pktinfo.extend_from_slice(&7u32.to_ne_bytes());
+
+        let mut control = control_message(IPPROTO_IP.0, IP_PKTINFO, &pktinfo);
+        control.extend(control_message(IPPROTO_IP.0, IP_TTL, &64u32.to_ne_bytes()));
+        control.extend(control_message(
+            IPPROTO_IP.0,
+            IP_ECN,
+            &0b11u32.to_ne_bytes(),
+        ));
+
+        let data = parse_control(&control);
+        assert_eq!(
+            data.destination,
+            Some(IpAddr::from(Ipv4Addr::new(192, 168, 1, 10)))
+        );
+        assert_eq!(data.interface_index, Some(7));
+        assert_eq!(data.hop_limit, Some(64));
+        assert_eq!(data.ecn, Some(EcnCodepoint::CongestionExperienced));
+        assert_eq!(data.coalesced_segment_size, None);
+    }
+
+    #[test]
+    fn parses_ipv6_packet_info_hop_limit_and_ecn() {
+        let mut pktinfo = Ipv6Addr::LOCALHOST.octets().to_vec();
+        pktinfo.extend_from_slice(&3u32.to_ne_bytes());
+
+        let mut control = control_message(IPPROTO_IPV6.0, IPV6_PKTINFO, &pktinfo);
+        control.extend(control_message(
+            IPPROTO_IPV6.0,
+            IPV6_HOPLIMIT,
+            &255u32.to_ne_bytes(),
+        ));
+        control.extend(control_message(
+            IPPROTO_IPV6.0,
+            IPV6_ECN,
+            &0b10u32.to_ne_bytes(),
+        ));
+
+        let data = parse_control(&control);
+        assert_eq!(data.destination, Some(IpAddr::from(Ipv6Addr::LOCALHOST)));
+        assert_eq!(data.interface_index, Some(3));
+        assert_eq!(data.hop_limit, Some(255));
+        assert_eq!(data.ecn, Some(EcnCodepoint::Ect0));
+    }
+
+    #[test]
+    fn ecn_codepoint_round_trips() {
+        for bits in 0..4 {
+            assert_eq!(EcnCodepoint::from_bits(bits).unwrap().bits(), bits);
+        }
+
+        assert_eq!(EcnCodepoint::from_bits(4), None);
+    }
+
+    #[test]
+    fn ignores_malformed_messages() {
+        assert_eq!(parse_control(&[]), ControlData::default());
+        assert_eq!(parse_control(&[1, 2, 3]), ControlData::default());
+
+        // The length claims more data than there is.
+        let mut control = control_message(
+            IPPROTO_UDP.0,
+            UDP_COALESCED_INFO as i32,
+            &1200u32.to_ne_bytes(),
+        );
+        control.truncate(PAYLOAD_OFFSET + 2);
+        assert_eq!(parse_control(&control), ControlData::default());
+
+        // The packet info is too short to hold the address.
+        let control = control_message(IPPROTO_IP.0, IP_PKTINFO, &[127, 0]);
+        assert_eq!(parse_control(&control), ControlData::default());
+    }
+}
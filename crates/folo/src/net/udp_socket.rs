@@ -0,0 +1,946 @@
+#[cfg(feature = "udp-stream")]
+use crate::net::UdpDatagrams;
+use crate::{
+    io::{
+        self, BufferSize, OperationError, OperationKind, OperationResult, PinnedBuffer, RioSocket,
+    },
+    net::{
+        udp_message::{self, ControlData, MessageSlots},
+        winsock::{self, NativeSocketAddr},
+        CoalescedDatagrams, ReceiveBatches, ReceivedMessage, RECEIVE_MESSAGE_RESERVE,
+    },
+    rt::current_async_agent,
+    util::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::Cell,
+    mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    num::NonZeroUsize,
+    os::windows::io::{IntoRawSocket, OwnedSocket},
+    ptr,
+};
+use windows::{
+    core::PSTR,
+    Win32::{
+        Foundation::BOOL,
+        Networking::WinSock::{
+            bind, connect, WSAIoctl, WSARecv, WSARecvFrom, WSASend, WSASendTo, WSASocketA,
+            IN6_ADDR, IN_ADDR, IPPROTO_IP, IPPROTO_IPV6, IPPROTO_UDP, IPV6_ADD_MEMBERSHIP,
+            IPV6_DROP_MEMBERSHIP, IPV6_HOPLIMIT, IPV6_MREQ, IPV6_MULTICAST_HOPS,
+            IPV6_MULTICAST_LOOP, IPV6_PKTINFO, IPV6_RECVECN, IP_ADD_MEMBERSHIP, IP_DROP_MEMBERSHIP,
+            IP_MREQ, IP_MULTICAST_LOOP, IP_MULTICAST_TTL, IP_PKTINFO, IP_RECVECN, IP_RECVTTL,
+            SIO_UDP_CONNRESET, SOCKADDR, SOCKADDR_STORAGE, SOCKET, SOCK_DGRAM, SOL_SOCKET,
+            SO_BROADCAST, UDP_RECV_MAX_COALESCED_SIZE, UDP_SEND_MSG_SIZE, WSABUF, WSAMSG,
+            WSA_FLAG_OVERLAPPED, WSA_FLAG_REGISTERED_IO,
+        },
+    },
+};
+
+/// A UDP socket bound to the current async worker thread.
+///
+/// The socket can be used in two modes:
+///
+/// * Unconnected - datagrams are exchanged with any peer via `send_to()` and `receive_from()`.
+/// * Connected - after `connect()`, datagrams are exchanged with a single peer via `send()` and
+///   `receive()`. The operating system discards datagrams arriving from any other address, so the
+///   application does not need to filter them.
+pub struct UdpSocket {
+    socket: OwnedHandle<SOCKET>,
+
+    // The peer we are connected to, if any.
+    peer: Option<SocketAddr>,
+
+    // If set, `send()`, `receive()`, `receive_many()` and `receive_batches()` use Registered I/O
+    // instead of overlapped I/O.
+    rio: Option<RioSocket>,
+
+    // Mirrors SO_BROADCAST, so we can explain why sending to the broadcast address fails.
+    broadcast: bool,
+
+    // Loaded on first use by the receives that deliver control information.
+    wsa_recv_msg: Cell<Option<winsock::WsaRecvMsgFn>>,
+
+    // Whether ICMP port unreachable messages are kept from failing receives (SIO_UDP_CONNRESET)
+    // and whether the user has decided this explicitly, in which case connecting leaves it alone.
+    suppress_connection_resets: bool,
+    suppress_connection_resets_explicit: bool,
+}
+
+impl UdpSocket {
+    /// Creates a UDP socket bound to the specified local address. Use port 0 to let the operating
+    /// system pick a free port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Self::bind_core(addr, false)
+    }
+
+    /// Creates a UDP socket bound to the specified local address, which sends and receives
+    /// datagrams with the connected peer via Registered I/O (RIO) instead of overlapped I/O. This
+    /// lowers the per-datagram overhead for sockets with a high packet rate.
+    ///
+    /// Only `send()`, `receive()`, `receive_many()` and `receive_batches()` use RIO - `send_to()`
+    /// and `receive_from()` always use overlapped I/O. Datagrams sent via RIO must fit into `io::RIO_SLOT_SIZE` bytes.
+    /// Each socket registers 512 KiB of memory with the operating system for its RIO buffers and
+    /// receives in progress cannot be canceled - a datagram arriving for a dropped receive is
+    /// delivered to the next one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn bind_with_registered_io(addr: SocketAddr) -> io::Result<Self> {
+        Self::bind_core(addr, true)
+    }
+
+    fn bind_core(addr: SocketAddr, registered_io: bool) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let flags = if registered_io {
+            WSA_FLAG_OVERLAPPED | WSA_FLAG_REGISTERED_IO
+        } else {
+            WSA_FLAG_OVERLAPPED
+        };
+
+        // SAFETY: We are required to close the handle once we are done with it,
+        // which we do via OwnedHandle that closes the handle on drop.
+        let socket = unsafe {
+            OwnedHandle::new(WSASocketA(
+                winsock::address_family(&addr).0 as i32,
+                SOCK_DGRAM.0,
+                IPPROTO_UDP.0,
+                None,
+                0,
+                flags,
+            )?)
+        };
+
+        let native_addr = NativeSocketAddr::from(addr);
+
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe { bind(*socket, native_addr.as_ptr(), native_addr.len()) })?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        let rio = if registered_io {
+            Some(current_async_agent::with_io(|io| {
+                io.register_rio_socket(*socket, false)
+            })?)
+        } else {
+            None
+        };
+
+        set_report_connection_resets(*socket, false)?;
+
+        Ok(Self::new(socket, rio, false))
+    }
+
+    /// Takes ownership of a bound UDP socket created outside of Folo (e.g. a
+    /// `std::net::UdpSocket`, or a `socket2::Socket` to configure socket options that Folo does not
+    /// expose). The socket is bound to the current async worker thread.
+    ///
+    /// The socket must have been created for overlapped I/O (`WSA_FLAG_OVERLAPPED`), as is done by
+    /// the standard library and by `socket2`, and must not already be bound to an I/O completion
+    /// port. As for sockets created by Folo, connection resets are suppressed (see
+    /// `set_suppress_connection_resets()`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn from_owned_socket(socket: OwnedSocket) -> io::Result<Self> {
+        // SAFETY: The socket is ours now and we only ever pass it back to its true owner.
+        unsafe { Self::from_raw_socket(SOCKET(socket.into_raw_socket() as usize)) }
+    }
+
+    /// Takes ownership of a bound UDP socket created outside of Folo. The socket is bound to the
+    /// current async worker thread. See `from_owned_socket()` for the requirements on the socket.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own the socket and must not use or close it after this call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub unsafe fn from_raw_socket(socket: SOCKET) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let socket = OwnedHandle::new(socket);
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        set_report_connection_resets(*socket, false)?;
+
+        // The creator of the socket may have already allowed broadcasts.
+        let broadcast = winsock::get_bool_option(*socket, SOL_SOCKET, SO_BROADCAST)?;
+
+        Ok(Self::new(socket, None, broadcast))
+    }
+
+    fn new(socket: OwnedHandle<SOCKET>, rio: Option<RioSocket>, broadcast: bool) -> Self {
+        Self {
+            socket,
+            peer: None,
+            rio,
+            broadcast,
+            wsa_recv_msg: Cell::new(None),
+            suppress_connection_resets: true,
+            suppress_connection_resets_explicit: false,
+        }
+    }
+
+    /// Connects the socket to a peer. Afterwards, `send()` and `receive()` exchange datagrams with
+    /// that peer only. No packets are exchanged by connecting - this only sets the default
+    /// destination and the source filter. Connecting again replaces the previous peer.
+    pub fn connect(&mut self, addr: SocketAddr) -> io::Result<()> {
+        let native_addr = NativeSocketAddr::from(addr);
+
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe {
+            connect(*self.socket, native_addr.as_ptr(), native_addr.len())
+        })?;
+
+        // A connected socket only talks to one peer, so learning that it is unreachable is useful.
+        if !self.suppress_connection_resets_explicit && self.suppress_connection_resets {
+            set_report_connection_resets(*self.socket, true)?;
+            self.suppress_connection_resets = false;
+        }
+
+        self.peer = Some(addr);
+        Ok(())
+    }
+
+    /// Whether ICMP "port unreachable" messages received in response to sent datagrams are kept
+    /// from failing receives. See `set_suppress_connection_resets()`.
+    pub fn suppresses_connection_resets(&self) -> bool {
+        self.suppress_connection_resets
+    }
+
+    /// Sets whether ICMP "port unreachable" messages received in response to sent datagrams are
+    /// kept from failing receives (via `SIO_UDP_CONNRESET`).
+    ///
+    /// By default, Windows fails the next receive with a connection reset error when such a
+    /// message arrives, even though UDP has no connections. On an unconnected socket serving many
+    /// peers, one peer going away would thus disrupt the receive loop for everyone. Suppression is
+    /// therefore enabled for new sockets and disabled once the socket is connected to a single
+    /// peer, unless set explicitly via this method.
+    pub fn set_suppress_connection_resets(&mut self, value: bool) -> io::Result<()> {
+        set_report_connection_resets(*self.socket, !value)?;
+
+        self.suppress_connection_resets = value;
+        self.suppress_connection_resets_explicit = true;
+        Ok(())
+    }
+
+    /// The local address the socket is bound to. Useful to find out which port the operating system
+    /// picked if the socket was bound to port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        winsock::local_addr(*self.socket)
+    }
+
+    /// The peer the socket is connected to, if any.
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// Whether `send()`, `receive()`, `receive_many()` and `receive_batches()` use Registered I/O
+    /// (see `UdpSocket::bind_with_registered_io()`).
+    pub fn is_registered_io(&self) -> bool {
+        self.rio.is_some()
+    }
+
+    /// Whether the socket may send datagrams to broadcast addresses (SO_BROADCAST). Disabled by
+    /// default.
+    pub fn broadcast(&self) -> io::Result<bool> {
+        winsock::get_bool_option(*self.socket, SOL_SOCKET, SO_BROADCAST)
+    }
+
+    /// Allows or forbids sending datagrams to broadcast addresses, both the limited broadcast
+    /// address (`255.255.255.255`) and the broadcast addresses of directly connected subnets (e.g.
+    /// `192.168.1.255`), as used for LAN discovery and Wake-on-LAN.
+    ///
+    /// Datagrams sent to the limited broadcast address leave through a single network interface
+    /// picked by the operating system. To reach a specific network on a machine with multiple
+    /// interfaces, send to the broadcast address of its subnet instead.
+    ///
+    /// Receiving broadcast datagrams does not require this option - only a socket bound to the
+    /// unspecified address (or the broadcast address) and the right port.
+    pub fn set_broadcast(&mut self, value: bool) -> io::Result<()> {
+        winsock::set_bool_option(*self.socket, SOL_SOCKET, SO_BROADCAST, value)?;
+
+        self.broadcast = value;
+        Ok(())
+    }
+
+    /// The largest buffer of datagrams that the operating system may coalesce into a single
+    /// receive (UDP receive offload), or 0 if receive coalescing is disabled (the default).
+    pub fn receive_coalescing(&self) -> io::Result<u32> {
+        winsock::get_option(*self.socket, IPPROTO_UDP.0, UDP_RECV_MAX_COALESCED_SIZE)
+    }
+
+    /// Allows the operating system to coalesce multiple datagrams of the same flow into a single
+    /// receive of up to `max_size` bytes (UDP receive offload), or disables this if `max_size` is
+    /// 0. This greatly reduces the per-datagram overhead for high-throughput flows such as QUIC or
+    /// media streams.
+    ///
+    /// Coalesced datagrams are only delivered by `receive_coalesced()`, which reports how to split
+    /// them up again. Use it for all receives once coalescing is enabled, as the other receives
+    /// cannot tell coalesced datagrams apart. Requires Windows 11 or Windows Server 2022.
+    pub fn set_receive_coalescing(&self, max_size: u32) -> io::Result<()> {
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_UDP.0,
+            UDP_RECV_MAX_COALESCED_SIZE,
+            &max_size,
+        )
+    }
+
+    /// The size of the datagrams that sends are split into by the operating system (UDP segmentation
+    /// offload), or 0 if send segmentation is disabled (the default).
+    pub fn send_segment_size(&self) -> io::Result<u32> {
+        winsock::get_option(*self.socket, IPPROTO_UDP.0, UDP_SEND_MSG_SIZE)
+    }
+
+    /// Makes the operating system split each buffer sent via this socket into datagrams of
+    /// `segment_size` bytes (the last one may be shorter), or disables this if `segment_size` is
+    /// 0. Sending many datagrams to the same peer as one buffer greatly reduces the per-datagram
+    /// overhead, with the splitting offloaded to the network adapter where supported. This is the
+    /// sending counterpart of `set_receive_coalescing()`, as used by QUIC stacks for bulk
+    /// transfers.
+    ///
+    /// Each buffer may hold up to 64 KiB of data. Not available on older versions of Windows.
+    pub fn set_send_segment_size(&self, segment_size: u32) -> io::Result<()> {
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_UDP.0,
+            UDP_SEND_MSG_SIZE,
+            &segment_size,
+        )
+    }
+
+    /// Whether received datagrams carry the local address they were sent to and the index of the
+    /// interface they arrived on (IP_PKTINFO or IPV6_PKTINFO, depending on the address family of
+    /// the socket). Disabled by default.
+    pub fn receive_packet_info(&self) -> io::Result<bool> {
+        let (level, name) = self.family_option(IP_PKTINFO, IPV6_PKTINFO)?;
+        winsock::get_bool_option(*self.socket, level, name)
+    }
+
+    /// Enables or disables reporting the local address that each datagram was sent to and the
+    /// interface it arrived on via `receive_message()`. A server bound to the unspecified address
+    /// needs this to reply from the address the client sent its request to.
+    pub fn set_receive_packet_info(&self, value: bool) -> io::Result<()> {
+        let (level, name) = self.family_option(IP_PKTINFO, IPV6_PKTINFO)?;
+        winsock::set_bool_option(*self.socket, level, name, value)
+    }
+
+    /// Whether received datagrams carry the TTL or hop limit of their IP packet (IP_RECVTTL or
+    /// IPV6_HOPLIMIT). Disabled by default.
+    pub fn receive_hop_limit(&self) -> io::Result<bool> {
+        let (level, name) = self.family_option(IP_RECVTTL, IPV6_HOPLIMIT)?;
+        winsock::get_bool_option(*self.socket, level, name)
+    }
+
+    /// Enables or disables reporting the TTL or hop limit of each datagram via
+    /// `receive_message()`, e.g. to verify that a peer is on the local link (RFC 5082).
+    pub fn set_receive_hop_limit(&self, value: bool) -> io::Result<()> {
+        let (level, name) = self.family_option(IP_RECVTTL, IPV6_HOPLIMIT)?;
+        winsock::set_bool_option(*self.socket, level, name, value)
+    }
+
+    /// Whether received datagrams carry the ECN codepoint of their IP packet (IP_RECVECN or
+    /// IPV6_RECVECN). Disabled by default.
+    pub fn receive_ecn(&self) -> io::Result<bool> {
+        let (level, name) = self.family_option(IP_RECVECN, IPV6_RECVECN)?;
+        winsock::get_bool_option(*self.socket, level, name)
+    }
+
+    /// Enables or disables reporting the ECN codepoint of each datagram via `receive_message()`,
+    /// which congestion controllers such as those of QUIC use to detect congestion before packets
+    /// are lost. Not available on older versions of Windows.
+    pub fn set_receive_ecn(&self, value: bool) -> io::Result<()> {
+        let (level, name) = self.family_option(IP_RECVECN, IPV6_RECVECN)?;
+        winsock::set_bool_option(*self.socket, level, name, value)
+    }
+
+    /// Picks the option level and name matching the address family of the socket.
+    fn family_option(&self, ipv4_name: i32, ipv6_name: i32) -> io::Result<(i32, i32)> {
+        Ok(match self.local_addr()? {
+            SocketAddr::V4(_) => (IPPROTO_IP.0, ipv4_name),
+            SocketAddr::V6(_) => (IPPROTO_IPV6.0, ipv6_name),
+        })
+    }
+
+    /// Joins an IPv4 multicast group on the network interface with the specified local address
+    /// (`Ipv4Addr::UNSPECIFIED` lets the operating system pick one), so that datagrams sent to the
+    /// group are received by this socket. The socket must be bound to an IPv4 address, usually the
+    /// unspecified address and the port the group uses.
+    pub fn join_multicast_v4(&self, group: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        ensure_multicast(group.is_multicast())?;
+
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_IP.0,
+            IP_ADD_MEMBERSHIP,
+            &ipv4_membership(group, interface),
+        )
+    }
+
+    /// Leaves an IPv4 multicast group joined via `join_multicast_v4()`.
+    pub fn leave_multicast_v4(&self, group: Ipv4Addr, interface: Ipv4Addr) -> io::Result<()> {
+        ensure_multicast(group.is_multicast())?;
+
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_IP.0,
+            IP_DROP_MEMBERSHIP,
+            &ipv4_membership(group, interface),
+        )
+    }
+
+    /// Joins an IPv6 multicast group on the network interface with the specified index (0 lets
+    /// the operating system pick one), so that datagrams sent to the group are received by this
+    /// socket. The socket must be bound to an IPv6 address, usually the unspecified address and the
+    /// port the group uses.
+    pub fn join_multicast_v6(&self, group: Ipv6Addr, interface_index: u32) -> io::Result<()> {
+        ensure_multicast(group.is_multicast())?;
+
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_IPV6.0,
+            IPV6_ADD_MEMBERSHIP,
+            &ipv6_membership(group, interface_index),
+        )
+    }
+
+    /// Leaves an IPv6 multicast group joined via `join_multicast_v6()`.
+    pub fn leave_multicast_v6(&self, group: Ipv6Addr, interface_index: u32) -> io::Result<()> {
+        ensure_multicast(group.is_multicast())?;
+
+        winsock::set_option(
+            *self.socket,
+            IPPROTO_IPV6.0,
+            IPV6_DROP_MEMBERSHIP,
+            &ipv6_membership(group, interface_index),
+        )
+    }
+
+    /// The time-to-live of IPv4 multicast datagrams sent by this socket, which limits how many
+    /// routers they may cross. The operating system default is 1 (the local network only).
+    pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        winsock::get_option(*self.socket, IPPROTO_IP.0, IP_MULTICAST_TTL)
+    }
+
+    pub fn set_multicast_ttl_v4(&self, value: u32) -> io::Result<()> {
+        winsock::set_option(*self.socket, IPPROTO_IP.0, IP_MULTICAST_TTL, &value)
+    }
+
+    /// Whether IPv4 multicast datagrams sent by this socket are also delivered to sockets on the
+    /// local machine that have joined the group. Enabled by default.
+    pub fn multicast_loop_v4(&self) -> io::Result<bool> {
+        winsock::get_bool_option(*self.socket, IPPROTO_IP.0, IP_MULTICAST_LOOP)
+    }
+
+    pub fn set_multicast_loop_v4(&self, value: bool) -> io::Result<()> {
+        winsock::set_bool_option(*self.socket, IPPROTO_IP.0, IP_MULTICAST_LOOP, value)
+    }
+
+    /// The hop limit of IPv6 multicast datagrams sent by this socket. The operating system default
+    /// is 1 (the local network only).
+    pub fn multicast_hops_v6(&self) -> io::Result<u32> {
+        winsock::get_option(*self.socket, IPPROTO_IPV6.0, IPV6_MULTICAST_HOPS)
+    }
+
+    pub fn set_multicast_hops_v6(&self, value: u32) -> io::Result<()> {
+        winsock::set_option(*self.socket, IPPROTO_IPV6.0, IPV6_MULTICAST_HOPS, &value)
+    }
+
+    /// Whether IPv6 multicast datagrams sent by this socket are also delivered to sockets on the
+    /// local machine that have joined the group. Enabled by default.
+    pub fn multicast_loop_v6(&self) -> io::Result<bool> {
+        winsock::get_bool_option(*self.socket, IPPROTO_IPV6.0, IPV6_MULTICAST_LOOP)
+    }
+
+    pub fn set_multicast_loop_v6(&self, value: bool) -> io::Result<()> {
+        winsock::set_bool_option(*self.socket, IPPROTO_IPV6.0, IPV6_MULTICAST_LOOP, value)
+    }
+
+    /// Sends the active region of the buffer as one datagram to the connected peer.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        if let Some(rio) = &self.rio {
+            return rio.send(buffer).await;
+        }
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Send);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                winsock::to_io_result(WSASend(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    0,
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await
+    }
+
+    /// Receives the next datagram from the connected peer (or from any peer if the socket is not
+    /// connected).
+    ///
+    /// The buffer will be returned in the result with the active region set to the datagram.
+    pub async fn receive(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        self.receive_core(buffer).await
+    }
+
+    /// Receives multiple datagrams from the connected peer (or from any peer if the socket is not
+    /// connected), one into each of the provided buffers. Uses Registered I/O if the socket was
+    /// bound with it.
+    ///
+    /// All the receives are posted to the operating system at once and the results are returned
+    /// once every one of them has completed, in the same order as the buffers. This amortizes the
+    /// per-datagram overhead for high-volume receivers that manage their own buffers, at the cost
+    /// of latency for the first datagrams if traffic is sparse. See `receive_batches()` for a
+    /// receiver that does not wait for the whole batch.
+    pub async fn receive_many(&mut self, buffers: Vec<PinnedBuffer>) -> Vec<OperationResult> {
+        let count = buffers.len();
+        let mut batches = ReceiveBatches::with_buffers(self, buffers, None);
+
+        let mut results = Vec::with_capacity(count);
+
+        while results.len() < count {
+            results.extend(batches.next_batch().await);
+        }
+
+        results
+    }
+
+    /// Creates a `ReceiveBatches` that keeps `depth` receives outstanding on the socket, each into
+    /// a buffer of the specified size class, and yields the datagrams that have arrived in batches.
+    /// This lets high packet rate services (e.g. game servers or DNS) handle many datagrams per
+    /// wakeup. Uses Registered I/O if the socket was bound with it.
+    ///
+    /// While the receiver exists, do not receive datagrams from the socket by other means, as the
+    /// receiver would not see those datagrams.
+    pub fn receive_batches(&self, depth: NonZeroUsize, size: BufferSize) -> ReceiveBatches<'_> {
+        ReceiveBatches::new(self, depth, size)
+    }
+
+    /// Creates a `UdpDatagrams` that exchanges datagrams with any peer via the `futures::Stream`
+    /// and `futures::Sink` traits, receiving into buffers of the specified size class. This lets
+    /// datagram pipelines use the standard combinators for buffering, filtering and fan-out.
+    #[cfg(feature = "udp-stream")]
+    pub fn datagrams(&self, size: BufferSize) -> UdpDatagrams<'_> {
+        UdpDatagrams::new(self, size)
+    }
+
+    pub(super) async fn receive_core(&self, buffer: PinnedBuffer) -> OperationResult {
+        if let Some(rio) = &self.rio {
+            return rio.receive(buffer).await;
+        }
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                let mut flags: u32 = 0;
+
+                winsock::to_io_result(WSARecv(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    &mut flags as *mut u32,
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await
+    }
+
+    /// Sends the active region of the buffer as one datagram to the specified peer.
+    ///
+    /// Sending to a broadcast address requires `set_broadcast(true)` first. Sending to the limited
+    /// broadcast address without it fails with `io::Error::InvalidOptions`, while the operating
+    /// system refuses sends to subnet broadcast addresses with a permission error.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    pub async fn send_to(&mut self, buffer: PinnedBuffer, addr: SocketAddr) -> OperationResult {
+        self.send_to_core(buffer, addr).await
+    }
+
+    pub(super) async fn send_to_core(
+        &self,
+        buffer: PinnedBuffer,
+        addr: SocketAddr,
+    ) -> OperationResult {
+        if !self.broadcast && matches!(addr, SocketAddr::V4(v4) if v4.ip().is_broadcast()) {
+            return Err(OperationError::new(
+                io::Error::InvalidOptions(
+                    "sending to the broadcast address requires set_broadcast(true)".to_string(),
+                ),
+                buffer,
+            ));
+        }
+
+        // Winsock captures the destination address when the operation is started, so it only
+        // needs to live until the call returns.
+        let native_addr = NativeSocketAddr::from(addr);
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Send);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                winsock::to_io_result(WSASendTo(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    0,
+                    Some(native_addr.as_ptr()),
+                    native_addr.len(),
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await
+    }
+
+    /// Receives the next datagram from any peer, returning it together with the address of the
+    /// sender.
+    ///
+    /// The sender address is written by the operating system into the last
+    /// `RECEIVE_FROM_ADDRESS_RESERVE` bytes of the active region of the buffer (as it must remain
+    /// valid until the operation completes), so the largest datagram that can be received is that
+    /// much smaller than the buffer.
+    pub async fn receive_from(
+        &mut self,
+        buffer: PinnedBuffer,
+    ) -> Result<(PinnedBuffer, SocketAddr), OperationError> {
+        self.receive_from_core(buffer).await
+    }
+
+    pub(super) async fn receive_from_core(
+        &self,
+        mut buffer: PinnedBuffer,
+    ) -> Result<(PinnedBuffer, SocketAddr), OperationError> {
+        if buffer.len() <= RECEIVE_FROM_ADDRESS_RESERVE {
+            return Err(OperationError::new(
+                io::Error::InvalidOptions(format!(
+                    "buffer must be larger than {RECEIVE_FROM_ADDRESS_RESERVE} bytes"
+                )),
+                buffer,
+            ));
+        }
+
+        let requested_len = buffer.len();
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        buffer = unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let data_len = buffer.len() - RECEIVE_FROM_ADDRESS_RESERVE;
+                let (data, reserve) = buffer.split_at_mut(data_len);
+                let (address, address_len) = sender_address_slots(reserve);
+
+                *address_len = mem::size_of::<SOCKADDR_STORAGE>() as i32;
+
+                let wsabufs = [WSABUF {
+                    len: data.len() as u32,
+                    buf: PSTR::from_raw(data.as_mut_ptr()),
+                }];
+
+                let mut flags: u32 = 0;
+
+                winsock::to_io_result(WSARecvFrom(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    &mut flags as *mut u32,
+                    Some(address),
+                    Some(address_len as *mut i32),
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await?;
+
+        // The completion has set the active region to the datagram. We temporarily widen it again
+        // to reach the sender address at the end.
+        let datagram_len = buffer.len();
+        buffer.set_len(requested_len);
+
+        let reserve = &mut buffer.as_mut_slice()[requested_len - RECEIVE_FROM_ADDRESS_RESERVE..];
+        let (address, _) = sender_address_slots(reserve);
+
+        // SAFETY: The operating system has filled the address slot with a valid socket address.
+        let sender = unsafe { winsock::from_native_socket_addr(address) };
+
+        buffer.set_len(datagram_len);
+
+        match sender {
+            Some(sender) => Ok((buffer, sender)),
+            None => Err(OperationError::new(
+                io::Error::Internal("datagram sender has unsupported address family".to_string()),
+                buffer,
+            )),
+        }
+    }
+
+    /// Receives the next datagram from any peer, together with the control information enabled via
+    /// `set_receive_packet_info()`, `set_receive_hop_limit()` and `set_receive_ecn()`.
+    ///
+    /// The sender address and the control information are written by the operating system into the
+    /// last `RECEIVE_MESSAGE_RESERVE` bytes of the active region of the buffer, so the datagram
+    /// that can be received is that much smaller than the buffer. Do not combine this with receive
+    /// coalescing - use `receive_coalesced()` for that.
+    pub async fn receive_message(
+        &mut self,
+        buffer: PinnedBuffer,
+    ) -> Result<ReceivedMessage, OperationError> {
+        let (buffer, sender, control) = self.receive_message_core(buffer).await?;
+
+        Ok(ReceivedMessage::new(buffer, sender, control))
+    }
+
+    /// Receives the next datagrams from any peer, with any datagrams that the operating system has
+    /// coalesced into the buffer (see `set_receive_coalescing()`) reported as such, so they can be
+    /// split up via `CoalescedDatagrams::datagrams()`. Without coalescing, this receives a single
+    /// datagram, like `receive_from()`.
+    ///
+    /// The sender address and the control information are written by the operating system into the
+    /// last `RECEIVE_MESSAGE_RESERVE` bytes of the active region of the buffer, so the data that
+    /// can be received is that much smaller than the buffer. Leave room for the maximum coalesced
+    /// size, as data that does not fit is discarded.
+    pub async fn receive_coalesced(
+        &mut self,
+        buffer: PinnedBuffer,
+    ) -> Result<CoalescedDatagrams, OperationError> {
+        let (buffer, sender, control) = self.receive_message_core(buffer).await?;
+
+        Ok(CoalescedDatagrams::new(
+            buffer,
+            sender,
+            control.coalesced_segment_size,
+        ))
+    }
+
+    /// Receives the next datagram via WSARecvMsg, together with the sender address and the control
+    /// information.
+    pub(super) async fn receive_message_core(
+        &self,
+        mut buffer: PinnedBuffer,
+    ) -> Result<(PinnedBuffer, SocketAddr, ControlData), OperationError> {
+        if buffer.len() <= RECEIVE_MESSAGE_RESERVE {
+            return Err(OperationError::new(
+                io::Error::InvalidOptions(format!(
+                    "buffer must be larger than {RECEIVE_MESSAGE_RESERVE} bytes"
+                )),
+                buffer,
+            ));
+        }
+
+        let wsa_recv_msg = match self.wsa_recv_msg() {
+            Ok(wsa_recv_msg) => wsa_recv_msg,
+            Err(e) => return Err(OperationError::new(e, buffer)),
+        };
+
+        let requested_len = buffer.len();
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        buffer = unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let data_len = buffer.len() - RECEIVE_MESSAGE_RESERVE;
+                let (data, reserve) = buffer.split_at_mut(data_len);
+                let slots = MessageSlots::from_reserve(reserve);
+
+                slots.data = WSABUF {
+                    len: data.len() as u32,
+                    buf: PSTR::from_raw(data.as_mut_ptr()),
+                };
+
+                slots.message = WSAMSG {
+                    name: &mut slots.sender as *mut SOCKADDR_STORAGE as *mut SOCKADDR,
+                    namelen: mem::size_of::<SOCKADDR_STORAGE>() as i32,
+                    lpBuffers: &mut slots.data,
+                    dwBufferCount: 1,
+                    Control: WSABUF {
+                        len: mem::size_of_val(&slots.control) as u32,
+                        buf: PSTR::from_raw(slots.control.as_mut_ptr() as *mut u8),
+                    },
+                    dwFlags: 0,
+                };
+
+                winsock::to_io_result(wsa_recv_msg(
+                    *self.socket,
+                    &mut slots.message,
+                    immediate_bytes_transferred as *mut u32,
+                    overlapped,
+                    None,
+                ))
+            })
+        }
+        .await?;
+
+        // The completion has set the active region to the datagram. We temporarily widen it again
+        // to reach the slots at the end.
+        let datagram_len = buffer.len();
+        buffer.set_len(requested_len);
+
+        let reserve = &mut buffer.as_mut_slice()[requested_len - RECEIVE_MESSAGE_RESERVE..];
+        let slots = MessageSlots::from_reserve(reserve);
+
+        let control = udp_message::parse_control(slots.control_bytes());
+
+        // SAFETY: The operating system has filled the address slot with a valid socket address.
+        let sender = unsafe {
+            winsock::from_native_socket_addr(
+                &slots.sender as *const SOCKADDR_STORAGE as *const SOCKADDR,
+            )
+        };
+
+        buffer.set_len(datagram_len);
+
+        match sender {
+            Some(sender) => Ok((buffer, sender, control)),
+            None => Err(OperationError::new(
+                io::Error::Internal("datagram sender has unsupported address family".to_string()),
+                buffer,
+            )),
+        }
+    }
+
+    fn wsa_recv_msg(&self) -> io::Result<winsock::WsaRecvMsgFn> {
+        if let Some(wsa_recv_msg) = self.wsa_recv_msg.get() {
+            return Ok(wsa_recv_msg);
+        }
+
+        let wsa_recv_msg = winsock::wsa_recv_msg_fn(*self.socket)?;
+        self.wsa_recv_msg.set(Some(wsa_recv_msg));
+
+        Ok(wsa_recv_msg)
+    }
+}
+
+#[negative_impl]
+impl !Send for UdpSocket {}
+#[negative_impl]
+impl !Sync for UdpSocket {}
+
+/// How many bytes at the end of the buffer given to `UdpSocket::receive_from()` are used to store
+/// the address of the sender.
+pub const RECEIVE_FROM_ADDRESS_RESERVE: usize = mem::size_of::<SOCKADDR_STORAGE>()
+    + mem::size_of::<i32>()
+    + mem::align_of::<SOCKADDR_STORAGE>();
+
+/// Carves out the (aligned) sender address structure and its length from the reserved region at
+/// the end of a receive_from() buffer. Always yields the same slots for the same region.
+fn sender_address_slots(reserve: &mut [u8]) -> (*mut SOCKADDR, &mut i32) {
+    assert!(reserve.len() >= RECEIVE_FROM_ADDRESS_RESERVE);
+
+    let offset = reserve
+        .as_ptr()
+        .align_offset(mem::align_of::<SOCKADDR_STORAGE>());
+
+    let (address, rest) = reserve[offset..].split_at_mut(mem::size_of::<SOCKADDR_STORAGE>());
+
+    // SAFETY: The length slot directly follows an aligned SOCKADDR_STORAGE, whose size is a
+    // multiple of its alignment, so the slot is suitably aligned for i32 and within bounds.
+    let address_len = unsafe { &mut *(rest.as_mut_ptr() as *mut i32) };
+
+    (address.as_mut_ptr() as *mut SOCKADDR, address_len)
+}
+
+fn set_report_connection_resets(socket: SOCKET, value: bool) -> io::Result<()> {
+    let value = BOOL::from(value);
+    let mut bytes_returned: u32 = 0;
+
+    // SAFETY: We pass a valid input buffer of the size expected for the control code, which has
+    // no output.
+    winsock::to_io_result(unsafe {
+        WSAIoctl(
+            socket,
+            SIO_UDP_CONNRESET,
+            Some(ptr::from_ref(&value).cast()),
+            mem::size_of::<BOOL>() as u32,
+            None,
+            0,
+            &mut bytes_returned as *mut _,
+            None,
+            None,
+        )
+    })
+}
+
+fn ensure_multicast(is_multicast: bool) -> io::Result<()> {
+    if !is_multicast {
+        return Err(io::Error::InvalidOptions(
+            "the group must be a multicast address".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn ipv4_membership(group: Ipv4Addr, interface: Ipv4Addr) -> IP_MREQ {
+    IP_MREQ {
+        imr_multiaddr: to_in_addr(group),
+        imr_interface: to_in_addr(interface),
+    }
+}
+
+fn ipv6_membership(group: Ipv6Addr, interface_index: u32) -> IPV6_MREQ {
+    let mut multiaddr = IN6_ADDR::default();
+    multiaddr.u.Byte = group.octets();
+
+    IPV6_MREQ {
+        ipv6mr_multiaddr: multiaddr,
+        ipv6mr_interface: interface_index,
+    }
+}
+
+fn to_in_addr(addr: Ipv4Addr) -> IN_ADDR {
+    let mut native = IN_ADDR::default();
+    native.S_un.S_addr = u32::from_ne_bytes(addr.octets());
+    native
+}
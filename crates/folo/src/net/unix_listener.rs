@@ -0,0 +1,142 @@
+use crate::{
+    io::{self, OperationKind, OperationResultExt, PinnedBuffer},
+    net::{
+        unix_stream::{create_unix_socket, native_unix_addr},
+        winsock, UnixStream,
+    },
+    rt::current_async_agent,
+    util::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    mem,
+    path::{Path, PathBuf},
+    slice,
+};
+use windows::Win32::Networking::WinSock::{
+    bind, listen, setsockopt, AcceptEx, SOCKADDR, SOCKADDR_UN, SOCKET, SOL_SOCKET, SOMAXCONN,
+    SO_UPDATE_ACCEPT_CONTEXT,
+};
+
+/// A socket listening for AF_UNIX stream connections on a socket file, owned by the current async
+/// worker thread. The counterpart of `TcpListener` for local inter-process communication: clients
+/// connect via `UnixStream::connect()` and who may connect is controlled by the access control
+/// list of the socket file.
+///
+/// The socket file is created when binding and is not deleted when the listener is dropped, as with
+/// AF_UNIX sockets on other platforms. Binding fails if the file already exists, so remove any
+/// stale socket file left behind by a previous instance before binding.
+///
+/// Requires Windows 10 version 1803 or newer.
+pub struct UnixListener {
+    socket: OwnedHandle<SOCKET>,
+    path: PathBuf,
+}
+
+impl UnixListener {
+    /// Creates the socket file at the specified path and starts listening for connections on it.
+    /// The path must be at most 107 bytes of UTF-8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let native_addr = native_unix_addr(path)?;
+
+        let socket = create_unix_socket()?;
+
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        unsafe {
+            winsock::to_io_result(bind(
+                *socket,
+                &native_addr as *const SOCKADDR_UN as *const SOCKADDR,
+                mem::size_of::<SOCKADDR_UN>() as i32,
+            ))?;
+
+            winsock::to_io_result(listen(*socket, SOMAXCONN as i32))?;
+        }
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        Ok(Self {
+            socket,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The path of the socket file that the listener is bound to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits for the next client to connect and returns the connection.
+    pub async fn accept(&self) -> io::Result<UnixStream> {
+        let connection_socket = create_unix_socket()?;
+
+        // AcceptEx requires room for the local and remote address, each 16 bytes more than the
+        // largest address of the protocol. We do not receive any data together with the accept.
+        const ADDRESS_LENGTH: usize = mem::size_of::<SOCKADDR_UN>() + 16;
+
+        let buffer = PinnedBuffer::from_pool();
+        assert!(buffer.len() >= ADDRESS_LENGTH * 2);
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Accept);
+
+        // SAFETY: We are required to pass the OVERLAPPED struct to the native I/O function to avoid
+        // a resource leak. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                if AcceptEx(
+                    *self.socket,
+                    *connection_socket,
+                    buffer.as_mut_ptr() as *mut _,
+                    0,
+                    ADDRESS_LENGTH as u32,
+                    ADDRESS_LENGTH as u32,
+                    immediate_bytes_transferred,
+                    overlapped,
+                )
+                .as_bool()
+                {
+                    Ok(())
+                } else {
+                    Err(windows::core::Error::from_win32().into())
+                }
+            })
+        }
+        .await
+        .into_inner()?;
+
+        let listen_socket: SOCKET = *self.socket;
+
+        // SAFETY: The slice covers exactly the listen socket handle, which lives until the end of
+        // the call.
+        let listen_socket_as_slice = unsafe {
+            slice::from_raw_parts(
+                &listen_socket as *const SOCKET as *const u8,
+                mem::size_of::<SOCKET>(),
+            )
+        };
+
+        // Makes the accepted socket inherit the properties of the listen socket.
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        winsock::to_io_result(unsafe {
+            setsockopt(
+                *connection_socket,
+                SOL_SOCKET,
+                SO_UPDATE_ACCEPT_CONTEXT,
+                Some(listen_socket_as_slice),
+            )
+        })?;
+
+        UnixStream::new(connection_socket)
+    }
+}
+
+#[negative_impl]
+impl !Send for UnixListener {}
+#[negative_impl]
+impl !Sync for UnixListener {}
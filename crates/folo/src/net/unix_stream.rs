@@ -0,0 +1,220 @@
+use crate::{
+    io::{self, OperationKind, OperationResult, PinnedBuffer},
+    net::winsock,
+    rt::current_async_agent,
+    util::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{mem, net::Shutdown, path::Path};
+use windows::{
+    core::PSTR,
+    Win32::Networking::WinSock::{
+        connect, shutdown, WSARecv, WSASend, WSASocketA, ADDRESS_FAMILY, AF_UNIX, SD_BOTH,
+        SD_RECEIVE, SD_SEND, SOCKADDR, SOCKADDR_UN, SOCKET, SOCK_STREAM, WSABUF,
+        WSA_FLAG_OVERLAPPED,
+    },
+};
+
+/// A connected AF_UNIX stream socket, owned by the current async worker thread. Exchanges data with
+/// a process on the same machine like a `TcpConnection` does, without the overhead of the TCP/IP
+/// stack and with access to the listener controlled by the permissions of its socket file.
+///
+/// Requires Windows 10 version 1803 or newer.
+pub struct UnixStream {
+    socket: OwnedHandle<SOCKET>,
+
+    read_closed: bool,
+    write_closed: bool,
+}
+
+impl UnixStream {
+    /// Connects to the listener bound to the socket file at the specified path. Connecting is a
+    /// local operation that does not wait for the listener to accept the connection, so this does
+    /// not need to be awaited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let native_addr = native_unix_addr(path.as_ref())?;
+
+        let socket = create_unix_socket()?;
+
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe {
+            connect(
+                *socket,
+                &native_addr as *const SOCKADDR_UN as *const SOCKADDR,
+                mem::size_of::<SOCKADDR_UN>() as i32,
+            )
+        })?;
+
+        Self::new(socket)
+    }
+
+    pub(super) fn new(socket: OwnedHandle<SOCKET>) -> io::Result<Self> {
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket))?;
+
+        Ok(Self {
+            socket,
+            read_closed: false,
+            write_closed: false,
+        })
+    }
+
+    /// Receives the next buffer of data. The buffer will be returned in the result with the active
+    /// region set to the bytes read, with a length of 0 if the peer has closed its side of the
+    /// connection.
+    pub async fn receive(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Receive);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        let result = unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                let mut flags: u32 = 0;
+
+                winsock::to_io_result(WSARecv(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    &mut flags as *mut u32,
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await;
+
+        if matches!(&result, Ok(buffer) if buffer.len() == 0) {
+            self.read_closed = true;
+        }
+
+        result
+    }
+
+    /// Sends a buffer of data to the peer.
+    ///
+    /// The buffer will be returned in the result to allow reuse.
+    pub async fn send(&mut self, buffer: PinnedBuffer) -> OperationResult {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.cancel_on_drop(*self.socket);
+        operation.set_kind(OperationKind::Send);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                winsock::to_io_result(WSASend(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    0,
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await
+    }
+
+    /// Shuts down the read side, the write side or both sides of the connection. Shutting down the
+    /// write side lets the peer know that no more data is coming. Shutting down a side that has
+    /// already been shut down does nothing.
+    pub fn shutdown(&mut self, how: Shutdown) -> io::Result<()> {
+        let (read, write) = match how {
+            Shutdown::Read => (true, false),
+            Shutdown::Write => (false, true),
+            Shutdown::Both => (true, true),
+        };
+
+        if (!read || self.read_closed) && (!write || self.write_closed) {
+            return Ok(());
+        }
+
+        let native_how = match how {
+            Shutdown::Read => SD_RECEIVE,
+            Shutdown::Write => SD_SEND,
+            Shutdown::Both => SD_BOTH,
+        };
+
+        // SAFETY: Nothing unsafe here, just an FFI call with valid arguments.
+        winsock::to_io_result(unsafe { shutdown(*self.socket, native_how) })?;
+
+        self.read_closed |= read;
+        self.write_closed |= write;
+
+        Ok(())
+    }
+
+    /// Whether the connection is known to be closed for reading - the peer has gracefully closed
+    /// its side of the connection or we shut down the read side.
+    pub fn is_read_closed(&self) -> bool {
+        self.read_closed
+    }
+
+    /// Whether we have shut down the write side of the connection.
+    pub fn is_write_closed(&self) -> bool {
+        self.write_closed
+    }
+}
+
+#[negative_impl]
+impl !Send for UnixStream {}
+#[negative_impl]
+impl !Sync for UnixStream {}
+
+pub(super) fn create_unix_socket() -> io::Result<OwnedHandle<SOCKET>> {
+    winsock::ensure_initialized();
+
+    // SAFETY: We are required to close the handle once we are done with it,
+    // which we do via OwnedHandle that closes the handle on drop.
+    Ok(unsafe {
+        OwnedHandle::new(WSASocketA(
+            AF_UNIX as i32,
+            SOCK_STREAM.0,
+            0,
+            None,
+            0,
+            WSA_FLAG_OVERLAPPED,
+        )?)
+    })
+}
+
+/// Converts a socket file path to the native address. Windows expects the path as UTF-8 and it must
+/// fit into the address together with a terminating zero.
+pub(super) fn native_unix_addr(path: &Path) -> io::Result<SOCKADDR_UN> {
+    let mut native = SOCKADDR_UN {
+        sun_family: ADDRESS_FAMILY(AF_UNIX),
+        sun_path: [0; 108],
+    };
+
+    let Some(bytes) = path.to_str().map(str::as_bytes) else {
+        return Err(io::Error::InvalidOptions(
+            "socket file path must be valid UTF-8".to_string(),
+        ));
+    };
+
+    if bytes.is_empty() || bytes.len() >= native.sun_path.len() {
+        return Err(io::Error::InvalidOptions(format!(
+            "socket file path must be 1 to {} bytes long",
+            native.sun_path.len() - 1
+        )));
+    }
+
+    for (target, byte) in native.sun_path.iter_mut().zip(bytes) {
+        *target = *byte as i8;
+    }
+
+    Ok(native)
+}
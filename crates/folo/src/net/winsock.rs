@@ -1,6 +1,21 @@
 use crate::io;
-use std::sync::LazyLock;
-use windows::Win32::Networking::WinSock::{WSAGetLastError, WSAStartup, WSADATA};
+use std::{
+    mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    slice,
+    sync::LazyLock,
+};
+use windows::{
+    core::{GUID, PSTR},
+    Win32::Networking::WinSock::{
+        getpeername, getsockname, getsockopt, setsockopt, WSAGetLastError, WSAIoctl, WSAStartup,
+        ADDRESS_FAMILY, AF_INET, AF_INET6, LPFN_CONNECTEX, LPFN_DISCONNECTEX, LPFN_WSARECVMSG,
+        LPWSAOVERLAPPED_COMPLETION_ROUTINE, RIO_EXTENSION_FUNCTION_TABLE,
+        SIO_GET_EXTENSION_FUNCTION_POINTER, SIO_GET_MULTIPLE_EXTENSION_FUNCTION_POINTER, SOCKADDR,
+        SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_INET, SOCKADDR_STORAGE, SOCKET, WSADATA,
+        WSAID_CONNECTEX, WSAID_DISCONNECTEX, WSAID_MULTIPLE_RIO, WSAID_WSARECVMSG, WSAMSG,
+    },
+};
 
 pub fn ensure_initialized() {
     _ = *WINSOCK_STARTUP;
@@ -30,3 +45,270 @@ pub fn to_io_result(winsock_result: i32) -> io::Result<()> {
         })
     }
 }
+
+/// A socket address in the native format expected by Winsock functions.
+#[derive(Clone, Copy)]
+pub struct NativeSocketAddr {
+    inner: SOCKADDR_INET,
+    len: i32,
+}
+
+impl NativeSocketAddr {
+    pub fn as_ptr(&self) -> *const SOCKADDR {
+        &self.inner as *const _ as *const SOCKADDR
+    }
+
+    /// Size of the native address structure, in bytes.
+    pub fn len(&self) -> i32 {
+        self.len
+    }
+}
+
+impl From<SocketAddr> for NativeSocketAddr {
+    fn from(value: SocketAddr) -> Self {
+        match value {
+            SocketAddr::V4(addr) => {
+                let mut native = SOCKADDR_IN {
+                    sin_family: AF_INET,
+                    sin_port: addr.port().to_be(),
+                    ..Default::default()
+                };
+                native.sin_addr.S_un.S_addr = u32::from_ne_bytes(addr.ip().octets());
+
+                Self {
+                    inner: SOCKADDR_INET { Ipv4: native },
+                    len: mem::size_of::<SOCKADDR_IN>() as i32,
+                }
+            }
+            SocketAddr::V6(addr) => {
+                let mut native = SOCKADDR_IN6 {
+                    sin6_family: AF_INET6,
+                    sin6_port: addr.port().to_be(),
+                    sin6_flowinfo: addr.flowinfo(),
+                    ..Default::default()
+                };
+                native.sin6_addr.u.Byte = addr.ip().octets();
+                native.Anonymous.sin6_scope_id = addr.scope_id();
+
+                Self {
+                    inner: SOCKADDR_INET { Ipv6: native },
+                    len: mem::size_of::<SOCKADDR_IN6>() as i32,
+                }
+            }
+        }
+    }
+}
+
+/// Converts a native socket address to the Rust representation. Returns `None` if the address
+/// family is not IPv4 or IPv6.
+///
+/// # Safety
+///
+/// The pointer must point to a valid socket address structure of the family it declares.
+pub unsafe fn from_native_socket_addr(addr: *const SOCKADDR) -> Option<SocketAddr> {
+    let family = (*addr).sa_family;
+
+    if family == AF_INET {
+        let addr = &*(addr as *const SOCKADDR_IN);
+
+        Some(SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::from(addr.sin_addr.S_un.S_addr.to_ne_bytes()),
+            u16::from_be(addr.sin_port),
+        )))
+    } else if family == AF_INET6 {
+        let addr = &*(addr as *const SOCKADDR_IN6);
+
+        Some(SocketAddr::V6(SocketAddrV6::new(
+            Ipv6Addr::from(addr.sin6_addr.u.Byte),
+            u16::from_be(addr.sin6_port),
+            addr.sin6_flowinfo,
+            addr.Anonymous.sin6_scope_id,
+        )))
+    } else {
+        None
+    }
+}
+
+/// The local address that the socket is bound to.
+pub fn local_addr(socket: SOCKET) -> io::Result<SocketAddr> {
+    // SAFETY: We pass valid pointers to a buffer large enough for any address.
+    query_addr(|addr, len| unsafe { getsockname(socket, addr, len) })
+}
+
+/// The remote address that the socket is connected to.
+pub fn peer_addr(socket: SOCKET) -> io::Result<SocketAddr> {
+    // SAFETY: We pass valid pointers to a buffer large enough for any address.
+    query_addr(|addr, len| unsafe { getpeername(socket, addr, len) })
+}
+
+fn query_addr(f: impl FnOnce(*mut SOCKADDR, *mut i32) -> i32) -> io::Result<SocketAddr> {
+    let mut storage = SOCKADDR_STORAGE::default();
+    let mut len = mem::size_of::<SOCKADDR_STORAGE>() as i32;
+
+    to_io_result(f(
+        &mut storage as *mut _ as *mut SOCKADDR,
+        &mut len as *mut _,
+    ))?;
+
+    // SAFETY: The OS has filled the storage with a valid socket address.
+    unsafe { from_native_socket_addr(&storage as *const _ as *const SOCKADDR) }
+        .ok_or_else(|| io::Error::Internal("socket has unsupported address family".to_string()))
+}
+
+pub fn address_family(addr: &SocketAddr) -> ADDRESS_FAMILY {
+    match addr {
+        SocketAddr::V4(_) => AF_INET,
+        SocketAddr::V6(_) => AF_INET6,
+    }
+}
+
+/// Sets a boolean socket option (one that takes a `BOOL` value).
+pub fn set_bool_option(socket: SOCKET, level: i32, name: i32, value: bool) -> io::Result<()> {
+    set_option(socket, level, name, &i32::from(value))
+}
+
+/// Gets a boolean socket option (one that takes a `BOOL` value).
+pub fn get_bool_option(socket: SOCKET, level: i32, name: i32) -> io::Result<bool> {
+    // Some options are documented as BOOL but return a single byte, so we start from zero.
+    get_option::<i32>(socket, level, name).map(|value| value != 0)
+}
+
+/// Sets a socket option whose value is a plain structure or integer of type T.
+pub fn set_option<T: Copy>(socket: SOCKET, level: i32, name: i32, value: &T) -> io::Result<()> {
+    // SAFETY: The slice covers exactly the value, which lives until the end of the call.
+    let bytes =
+        unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) };
+
+    // SAFETY: We pass a valid buffer of the size expected for the option.
+    to_io_result(unsafe { setsockopt(socket, level, name, Some(bytes)) })
+}
+
+/// Gets a socket option whose value is a plain structure or integer of type T.
+pub fn get_option<T: Copy + Default>(socket: SOCKET, level: i32, name: i32) -> io::Result<T> {
+    let mut value = T::default();
+    let mut len = mem::size_of::<T>() as i32;
+
+    // SAFETY: We pass a valid buffer and its real size, so the OS cannot write out of bounds.
+    to_io_result(unsafe {
+        getsockopt(
+            socket,
+            level,
+            name,
+            PSTR::from_raw(&mut value as *mut T as *mut u8),
+            &mut len as *mut _,
+        )
+    })?;
+
+    Ok(value)
+}
+
+pub type ConnectExFn = unsafe extern "system" fn(
+    SOCKET,
+    *const SOCKADDR,
+    i32,
+    *const core::ffi::c_void,
+    u32,
+    *mut u32,
+    *mut windows::Win32::System::IO::OVERLAPPED,
+) -> windows::Win32::Foundation::BOOL;
+
+/// Loads the ConnectEx extension function for the provider of the given socket. Extension
+/// functions are not exported by Winsock directly and must be loaded via the socket itself.
+pub fn connect_ex_fn(socket: SOCKET) -> io::Result<ConnectExFn> {
+    load_extension_fn::<LPFN_CONNECTEX>(
+        socket,
+        SIO_GET_EXTENSION_FUNCTION_POINTER,
+        WSAID_CONNECTEX,
+    )?
+    .ok_or_else(|| io::Error::Internal("Winsock did not provide ConnectEx".to_string()))
+}
+
+pub type DisconnectExFn = unsafe extern "system" fn(
+    SOCKET,
+    *mut windows::Win32::System::IO::OVERLAPPED,
+    u32,
+    u32,
+) -> windows::Win32::Foundation::BOOL;
+
+/// Loads the DisconnectEx extension function for the provider of the given socket.
+pub fn disconnect_ex_fn(socket: SOCKET) -> io::Result<DisconnectExFn> {
+    load_extension_fn::<LPFN_DISCONNECTEX>(
+        socket,
+        SIO_GET_EXTENSION_FUNCTION_POINTER,
+        WSAID_DISCONNECTEX,
+    )?
+    .ok_or_else(|| io::Error::Internal("Winsock did not provide DisconnectEx".to_string()))
+}
+
+pub type WsaRecvMsgFn = unsafe extern "system" fn(
+    SOCKET,
+    *mut WSAMSG,
+    *mut u32,
+    *mut windows::Win32::System::IO::OVERLAPPED,
+    LPWSAOVERLAPPED_COMPLETION_ROUTINE,
+) -> i32;
+
+/// Loads the WSARecvMsg extension function for the provider of the given socket.
+pub fn wsa_recv_msg_fn(socket: SOCKET) -> io::Result<WsaRecvMsgFn> {
+    load_extension_fn::<LPFN_WSARECVMSG>(
+        socket,
+        SIO_GET_EXTENSION_FUNCTION_POINTER,
+        WSAID_WSARECVMSG,
+    )?
+    .ok_or_else(|| io::Error::Internal("Winsock did not provide WSARecvMsg".to_string()))
+}
+
+/// Loads the table of Registered I/O (RIO) extension functions for the provider of the given
+/// socket.
+pub fn rio_functions(socket: SOCKET) -> io::Result<RIO_EXTENSION_FUNCTION_TABLE> {
+    let functions = load_extension_fn::<RIO_EXTENSION_FUNCTION_TABLE>(
+        socket,
+        SIO_GET_MULTIPLE_EXTENSION_FUNCTION_POINTER,
+        WSAID_MULTIPLE_RIO,
+    )?;
+
+    if functions.RIOReceive.is_none()
+        || functions.RIOSend.is_none()
+        || functions.RIOCreateCompletionQueue.is_none()
+        || functions.RIOCloseCompletionQueue.is_none()
+        || functions.RIOCreateRequestQueue.is_none()
+        || functions.RIODequeueCompletion.is_none()
+        || functions.RIONotify.is_none()
+        || functions.RIORegisterBuffer.is_none()
+        || functions.RIODeregisterBuffer.is_none()
+        || functions.RIOResizeCompletionQueue.is_none()
+    {
+        return Err(io::Error::Internal(
+            "Winsock did not provide the Registered I/O functions".to_string(),
+        ));
+    }
+
+    Ok(functions)
+}
+
+fn load_extension_fn<F>(socket: SOCKET, control_code: u32, id: GUID) -> io::Result<F>
+where
+    F: Default,
+{
+    let mut function = F::default();
+    let mut bytes_returned: u32 = 0;
+
+    // SAFETY: We pass valid buffers of the sizes we declare. F is an Option of a function pointer
+    // (or a table of them for the "multiple" control code), which is exactly what the control code
+    // writes into the output buffer.
+    to_io_result(unsafe {
+        WSAIoctl(
+            socket,
+            control_code,
+            Some(&id as *const _ as *const _),
+            mem::size_of::<GUID>() as u32,
+            Some(&mut function as *mut _ as *mut _),
+            mem::size_of::<F>() as u32,
+            &mut bytes_returned as *mut _,
+            None,
+            None,
+        )
+    })?;
+
+    Ok(function)
+}
@@ -0,0 +1,11 @@
+//! WebSocket connections (RFC 6455) over TCP connections and TLS sessions.
+//!
+//! Once the opening handshake (an HTTP upgrade request and response) has been completed, wrap the
+//! connection into `WebSocket` to exchange messages. To work with individual frames instead, use
+//! `WebSocketCodec` with `codec::Framed`.
+
+mod frame;
+mod web_socket;
+
+pub use frame::{Frame, OpCode, Role, WebSocketCodec, DEFAULT_MAX_PAYLOAD_LEN};
+pub use web_socket::*;
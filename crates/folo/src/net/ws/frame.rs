@@ -0,0 +1,432 @@
+use crate::{
+    io,
+    net::codec::{Decoder, Encoder},
+};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// The default maximum length of a frame payload (and of a message reassembled from fragments),
+/// to protect against peers that claim to send huge frames.
+pub const DEFAULT_MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+/// Control frames (close, ping, pong) may carry at most this many bytes of payload.
+const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+const FIN_BIT: u8 = 0x80;
+const RESERVED_BITS: u8 = 0x70;
+const OPCODE_BITS: u8 = 0x0F;
+const MASK_BIT: u8 = 0x80;
+const LEN_BITS: u8 = 0x7F;
+
+/// Which end of the connection we are, which determines the direction in which frames are
+/// masked - clients mask the frames they send, servers do not.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// The type of a frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpCode {
+    /// A further fragment of a fragmented text or binary message.
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xA => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+
+    /// Whether this is a control frame, which may be interleaved with the fragments of a message.
+    pub fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+/// A single WebSocket frame, with its payload unmasked.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    fin: bool,
+    opcode: OpCode,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Creates a frame. `fin` marks the last (or only) fragment of a message and must be set for
+    /// control frames.
+    pub fn new(fin: bool, opcode: OpCode, payload: Vec<u8>) -> Self {
+        Self {
+            fin,
+            opcode,
+            payload,
+        }
+    }
+
+    pub fn fin(&self) -> bool {
+        self.fin
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Vec<u8> {
+        self.payload
+    }
+}
+
+/// A codec for WebSocket frames as defined by RFC 6455, for use with `codec::Framed` or via
+/// `WebSocket`, which builds messages out of the frames.
+///
+/// Frames sent by a client are masked with a random key, as the protocol requires, and the codec
+/// rejects frames that are masked the wrong way for its role. No extensions are supported, so
+/// frames with any of the reserved bits set are rejected.
+#[derive(Clone, Debug)]
+pub struct WebSocketCodec {
+    role: Role,
+    max_payload_len: usize,
+
+    mask_hasher: RandomState,
+    frames_masked: u64,
+}
+
+impl WebSocketCodec {
+    pub fn new(role: Role) -> Self {
+        Self {
+            role,
+            max_payload_len: DEFAULT_MAX_PAYLOAD_LEN,
+            mask_hasher: RandomState::new(),
+            frames_masked: 0,
+        }
+    }
+
+    /// The maximum length of a frame payload and of a message reassembled from fragments.
+    /// Receiving a longer frame fails with `std::io::ErrorKind::InvalidData`. Defaults to
+    /// `DEFAULT_MAX_PAYLOAD_LEN`.
+    pub fn max_payload_len(mut self, value: usize) -> Self {
+        self.max_payload_len = value;
+        self
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub(super) fn max_message_len(&self) -> usize {
+        self.max_payload_len
+    }
+
+    fn next_mask(&mut self) -> [u8; 4] {
+        self.frames_masked = self.frames_masked.wrapping_add(1);
+
+        let mut hasher = self.mask_hasher.build_hasher();
+        hasher.write_u64(self.frames_masked);
+        (hasher.finish() as u32).to_ne_bytes()
+    }
+}
+
+impl Decoder for WebSocketCodec {
+    type Item = Frame;
+
+    fn decode(&mut self, src: &[u8]) -> io::Result<Option<(Self::Item, usize)>> {
+        let [first, second, ..] = *src else {
+            return Ok(None);
+        };
+
+        if first & RESERVED_BITS != 0 {
+            return Err(protocol_error("frame uses reserved bits"));
+        }
+
+        let fin = first & FIN_BIT != 0;
+        let Some(opcode) = OpCode::from_bits(first & OPCODE_BITS) else {
+            return Err(protocol_error("frame has an unknown opcode"));
+        };
+
+        let masked = second & MASK_BIT != 0;
+        if masked != (self.role == Role::Server) {
+            return Err(protocol_error(match self.role {
+                Role::Server => "frame from client is not masked",
+                Role::Client => "frame from server is masked",
+            }));
+        }
+
+        let (payload_len, mut header_len) = match second & LEN_BITS {
+            126 => match src.get(2..4) {
+                Some(bytes) => (u64::from(u16::from_be_bytes([bytes[0], bytes[1]])), 4),
+                None => return Ok(None),
+            },
+            127 => match src.get(2..10) {
+                Some(bytes) => (u64::from_be_bytes(bytes.try_into().unwrap()), 10),
+                None => return Ok(None),
+            },
+            len => (u64::from(len), 2),
+        };
+
+        if opcode.is_control() && (!fin || payload_len > MAX_CONTROL_PAYLOAD_LEN as u64) {
+            return Err(protocol_error(
+                "control frame is fragmented or its payload is too long",
+            ));
+        }
+
+        if payload_len > self.max_payload_len as u64 {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "frame payload of {payload_len} bytes exceeds the maximum of {} bytes",
+                    self.max_payload_len
+                ),
+            )));
+        }
+
+        let mask = if masked {
+            let Some(mask) = src.get(header_len..header_len + 4) else {
+                return Ok(None);
+            };
+
+            header_len += 4;
+            Some([mask[0], mask[1], mask[2], mask[3]])
+        } else {
+            None
+        };
+
+        // Cannot overflow because it is no more than the maximum payload length.
+        let frame_len = header_len + payload_len as usize;
+
+        let Some(payload) = src.get(header_len..frame_len) else {
+            return Ok(None);
+        };
+
+        let mut payload = payload.to_vec();
+
+        if let Some(mask) = mask {
+            apply_mask(&mut payload, mask);
+        }
+
+        Ok(Some((Frame::new(fin, opcode, payload), frame_len)))
+    }
+}
+
+impl Encoder<Frame> for WebSocketCodec {
+    fn encode(&mut self, item: Frame, dst: &mut Vec<u8>) -> io::Result<()> {
+        if item.opcode.is_control() && (!item.fin || item.payload.len() > MAX_CONTROL_PAYLOAD_LEN) {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "control frame must not be fragmented and its payload must be at most \
+                     {MAX_CONTROL_PAYLOAD_LEN} bytes"
+                ),
+            )));
+        }
+
+        let mask_bit = match self.role {
+            Role::Client => MASK_BIT,
+            Role::Server => 0,
+        };
+
+        dst.reserve(14 + item.payload.len());
+        dst.push(if item.fin { FIN_BIT } else { 0 } | item.opcode.bits());
+
+        match item.payload.len() {
+            len @ 0..=125 => dst.push(mask_bit | len as u8),
+            len @ 126..=0xFFFF => {
+                dst.push(mask_bit | 126);
+                dst.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                dst.push(mask_bit | 127);
+                dst.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        match self.role {
+            Role::Client => {
+                let mask = self.next_mask();
+                dst.extend_from_slice(&mask);
+
+                let payload_start = dst.len();
+                dst.extend_from_slice(&item.payload);
+                apply_mask(&mut dst[payload_start..], mask);
+            }
+            Role::Server => dst.extend_from_slice(&item.payload),
+        }
+
+        Ok(())
+    }
+}
+
+/// Masks or unmasks (the operation is its own inverse) a payload with the masking key of a frame.
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (byte, mask_byte) in payload.iter_mut().zip(mask.iter().cycle()) {
+        *byte ^= mask_byte;
+    }
+}
+
+pub(super) fn protocol_error(message: &str) -> io::Error {
+    io::Error::StdIo(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("WebSocket protocol violation: {message}"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(codec: &mut WebSocketCodec, frame: Frame) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        codec.encode(frame, &mut encoded).unwrap();
+        encoded
+    }
+
+    #[test]
+    fn server_frames_are_unmasked() {
+        let mut server = WebSocketCodec::new(Role::Server);
+
+        // The unmasked "Hello" text frame from RFC 6455 section 5.7.
+        let encoded = encode(
+            &mut server,
+            Frame::new(true, OpCode::Text, b"Hello".to_vec()),
+        );
+        assert_eq!(encoded, b"\x81\x05Hello");
+
+        let mut client = WebSocketCodec::new(Role::Client);
+        let (frame, consumed) = client.decode(&encoded).unwrap().unwrap();
+        assert_eq!(frame, Frame::new(true, OpCode::Text, b"Hello".to_vec()));
+        assert_eq!(consumed, encoded.len());
+    }
+
+    #[test]
+    fn client_frames_are_masked() {
+        // The masked "Hello" text frame from RFC 6455 section 5.7.
+        let encoded = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58";
+
+        let mut server = WebSocketCodec::new(Role::Server);
+        let (frame, consumed) = server.decode(encoded).unwrap().unwrap();
+        assert_eq!(frame, Frame::new(true, OpCode::Text, b"Hello".to_vec()));
+        assert_eq!(consumed, encoded.len());
+
+        let mut client = WebSocketCodec::new(Role::Client);
+        let encoded = encode(
+            &mut client,
+            Frame::new(true, OpCode::Text, b"Hello".to_vec()),
+        );
+        assert_eq!(encoded[1], MASK_BIT | 5);
+        assert_eq!(encoded.len(), 2 + 4 + 5);
+
+        let (frame, _) = server.decode(&encoded).unwrap().unwrap();
+        assert_eq!(frame.payload(), b"Hello");
+    }
+
+    #[test]
+    fn extended_lengths_round_trip() {
+        let mut server = WebSocketCodec::new(Role::Server);
+        let mut client = WebSocketCodec::new(Role::Client);
+
+        for len in [125, 126, 0xFFFF, 0x10000] {
+            let payload = vec![7; len];
+            let encoded = encode(
+                &mut client,
+                Frame::new(false, OpCode::Binary, payload.clone()),
+            );
+
+            let (frame, consumed) = server.decode(&encoded).unwrap().unwrap();
+            assert_eq!(frame, Frame::new(false, OpCode::Binary, payload));
+            assert_eq!(consumed, encoded.len());
+        }
+    }
+
+    #[test]
+    fn incomplete_frame_waits_for_more() {
+        let mut client = WebSocketCodec::new(Role::Client);
+
+        assert!(client.decode(b"").unwrap().is_none());
+        assert!(client.decode(b"\x81").unwrap().is_none());
+        assert!(client.decode(b"\x82\x7e\x01").unwrap().is_none());
+        assert!(client.decode(b"\x81\x05Hel").unwrap().is_none());
+
+        // Only the first frame is consumed.
+        let (frame, consumed) = client.decode(b"\x89\x00\x81\x05Hello").unwrap().unwrap();
+        assert_eq!(frame, Frame::new(true, OpCode::Ping, Vec::new()));
+        assert_eq!(consumed, 2);
+    }
+
+    #[test]
+    fn protocol_violations_are_rejected() {
+        let mut client = WebSocketCodec::new(Role::Client);
+        let mut server = WebSocketCodec::new(Role::Server);
+
+        // Reserved bit set.
+        assert!(client.decode(b"\xc1\x00").is_err());
+        // Unknown opcode.
+        assert!(client.decode(b"\x83\x00").is_err());
+        // Masked frame from a server.
+        assert!(client.decode(b"\x81\x80\x00\x00\x00\x00").is_err());
+        // Unmasked frame from a client.
+        assert!(server.decode(b"\x81\x00").is_err());
+        // Fragmented control frame.
+        assert!(client.decode(b"\x09\x00").is_err());
+        // Control frame with a payload that is too long.
+        assert!(client.decode(b"\x89\x7e\x00\x7e").is_err());
+    }
+
+    #[test]
+    fn too_long_frame_is_rejected() {
+        let mut client = WebSocketCodec::new(Role::Client).max_payload_len(4);
+
+        assert!(client.decode(b"\x82\x05").is_err());
+        assert!(client.decode(b"\x82\x04abcd").unwrap().is_some());
+    }
+
+    #[test]
+    fn invalid_control_frame_is_not_encoded() {
+        let mut server = WebSocketCodec::new(Role::Server);
+
+        assert!(server
+            .encode(Frame::new(false, OpCode::Ping, Vec::new()), &mut Vec::new())
+            .is_err());
+        assert!(server
+            .encode(
+                Frame::new(true, OpCode::Ping, vec![0; 126]),
+                &mut Vec::new()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn masks_differ_between_frames() {
+        let mut client = WebSocketCodec::new(Role::Client);
+
+        assert_ne!(client.next_mask(), client.next_mask());
+    }
+}
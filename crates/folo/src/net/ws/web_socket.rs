@@ -0,0 +1,377 @@
+use super::frame::{protocol_error, Frame, OpCode, WebSocketCodec};
+use crate::{
+    io::{self, OperationResult, OperationResultExt, PinnedBuffer},
+    net::{
+        codec::{Decoder, Encoder},
+        tls::TlsStream,
+        TcpConnection,
+    },
+};
+use negative_impl::negative_impl;
+use std::future::Future;
+
+/// The close status code sent when closing without a specific reason ("normal closure").
+pub const CLOSE_NORMAL: u16 = 1000;
+
+/// A connection that WebSocket frames can be exchanged over - a `TcpConnection` or a `TlsStream`.
+pub trait Transport {
+    fn receive(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult>;
+
+    fn send(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult>;
+}
+
+impl Transport for TcpConnection {
+    fn receive(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult> {
+        TcpConnection::receive(self, buffer)
+    }
+
+    fn send(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult> {
+        TcpConnection::send(self, buffer)
+    }
+}
+
+impl Transport for TlsStream {
+    fn receive(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult> {
+        TlsStream::receive(self, buffer)
+    }
+
+    fn send(&mut self, buffer: PinnedBuffer) -> impl Future<Output = OperationResult> {
+        TlsStream::send(self, buffer)
+    }
+}
+
+/// A complete WebSocket message, reassembled from its fragments if it was sent as several frames.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+
+    /// The peer has started (or answered) the closing handshake, with an optional status code and
+    /// reason.
+    Close(Option<CloseFrame>),
+}
+
+/// The status code and reason that accompany a close message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloseFrame {
+    code: u16,
+    reason: String,
+}
+
+impl CloseFrame {
+    /// Creates a close frame. The reason must fit into a control frame together with the code, so
+    /// it is limited to 123 bytes.
+    pub fn new(code: u16, reason: impl Into<String>) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    fn parse(payload: &[u8]) -> io::Result<Option<Self>> {
+        match payload {
+            [] => Ok(None),
+            [_] => Err(protocol_error("close frame payload is truncated")),
+            [high, low, reason @ ..] => {
+                let Ok(reason) = std::str::from_utf8(reason) else {
+                    return Err(protocol_error("close reason is not valid UTF-8"));
+                };
+
+                Ok(Some(Self::new(u16::from_be_bytes([*high, *low]), reason)))
+            }
+        }
+    }
+
+    fn to_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(2 + self.reason.len());
+        payload.extend_from_slice(&self.code.to_be_bytes());
+        payload.extend_from_slice(self.reason.as_bytes());
+        payload
+    }
+}
+
+/// A WebSocket connection over a `TcpConnection` or `TlsStream`, exchanging messages as defined by
+/// RFC 6455.
+///
+/// The opening handshake is an HTTP request and response that must be completed before the
+/// connection is wrapped into a `WebSocket` - this type only takes care of what comes after it.
+///
+/// Received pings are answered automatically (and also returned to the caller) and the closing
+/// handshake is completed automatically when the peer starts it. After the closing handshake,
+/// `receive()` returns `None` and the connection can be dropped.
+pub struct WebSocket<T = TcpConnection> {
+    transport: T,
+    codec: WebSocketCodec,
+
+    // Received bytes not yet consumed by decoded frames, starting at `read_start`.
+    read_buffer: Vec<u8>,
+    read_start: usize,
+
+    // The opcode and payload received so far of a message whose fragments are still arriving.
+    fragmented: Option<(OpCode, Vec<u8>)>,
+
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl<T> WebSocket<T>
+where
+    T: Transport,
+{
+    /// Wraps a connection on which the opening handshake has been completed. The role of the codec
+    /// determines whether we are the client or the server.
+    pub fn new(transport: T, codec: WebSocketCodec) -> Self {
+        Self {
+            transport,
+            codec,
+            read_buffer: Vec::new(),
+            read_start: 0,
+            fragmented: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// Provides bytes received after the end of the opening handshake (e.g. read together with the
+    /// HTTP response), which are the start of the first frame.
+    pub fn with_received_data(mut self, data: &[u8]) -> Self {
+        self.read_buffer.extend_from_slice(data);
+        self
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    /// The underlying connection. Sending or receiving data directly on it corrupts the stream of
+    /// frames.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the connection, dropping any received bytes that have not been decoded.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Whether the peer has sent a close message, after which no more messages arrive.
+    pub fn is_close_received(&self) -> bool {
+        self.close_received
+    }
+
+    /// Whether we have sent a close message, after which no more messages can be sent.
+    pub fn is_close_sent(&self) -> bool {
+        self.close_sent
+    }
+
+    /// Receives the next message, or `None` once the peer has closed the WebSocket. Text messages
+    /// must be valid UTF-8.
+    ///
+    /// A received ping is answered with a pong before it is returned. A received close message is
+    /// answered with a close message (unless we have already sent one) before it is returned.
+    pub async fn receive(&mut self) -> io::Result<Option<Message>> {
+        loop {
+            if self.close_received {
+                return Ok(None);
+            }
+
+            let Some(frame) = self.receive_frame().await? else {
+                return Err(io::Error::StdIo(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "connection closed without a WebSocket close message",
+                )));
+            };
+
+            if let Some(message) = self.handle_frame(frame).await? {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    /// Sends a message. Text and binary messages are sent as a single frame, while ping, pong and
+    /// close messages are limited to 125 bytes of payload.
+    ///
+    /// Sending a close message starts the closing handshake, after which nothing more can be sent.
+    /// Keep receiving until `receive()` returns `None` to complete the handshake, or use `close()`.
+    pub async fn send(&mut self, message: Message) -> io::Result<()> {
+        if self.close_sent {
+            return Err(io::Error::StdIo(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "cannot send after a WebSocket close message has been sent",
+            )));
+        }
+
+        let closing = matches!(message, Message::Close(_));
+
+        let frame = match message {
+            Message::Text(text) => Frame::new(true, OpCode::Text, text.into_bytes()),
+            Message::Binary(data) => Frame::new(true, OpCode::Binary, data),
+            Message::Ping(data) => Frame::new(true, OpCode::Ping, data),
+            Message::Pong(data) => Frame::new(true, OpCode::Pong, data),
+            Message::Close(close) => Frame::new(
+                true,
+                OpCode::Close,
+                close.map(|close| close.to_payload()).unwrap_or_default(),
+            ),
+        };
+
+        self.send_frame(frame).await?;
+        self.close_sent |= closing;
+
+        Ok(())
+    }
+
+    /// Performs the closing handshake - sends a close message (unless one has already been sent)
+    /// and waits for the peer to answer with its own, discarding any other messages that arrive
+    /// before it.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> io::Result<()> {
+        if !self.close_sent {
+            self.send(Message::Close(frame)).await?;
+        }
+
+        while self.receive().await?.is_some() {}
+
+        Ok(())
+    }
+
+    async fn receive_frame(&mut self) -> io::Result<Option<Frame>> {
+        loop {
+            if let Some((frame, consumed)) =
+                self.codec.decode(&self.read_buffer[self.read_start..])?
+            {
+                self.read_start += consumed;
+
+                if self.read_start == self.read_buffer.len() {
+                    self.read_buffer.clear();
+                    self.read_start = 0;
+                } else if self.read_start > self.read_buffer.len() / 2 {
+                    self.read_buffer.drain(..self.read_start);
+                    self.read_start = 0;
+                }
+
+                return Ok(Some(frame));
+            }
+
+            let buffer = self
+                .transport
+                .receive(PinnedBuffer::from_pool())
+                .await
+                .into_inner()?;
+
+            if buffer.len() == 0 {
+                return Ok(None);
+            }
+
+            self.read_buffer.extend_from_slice(buffer.as_slice());
+        }
+    }
+
+    async fn send_frame(&mut self, frame: Frame) -> io::Result<()> {
+        let mut encoded = Vec::new();
+        self.codec.encode(frame, &mut encoded)?;
+
+        self.transport
+            .send(PinnedBuffer::from_boxed_slice(encoded.into_boxed_slice()))
+            .await
+            .into_inner()?;
+
+        Ok(())
+    }
+
+    /// Processes a received frame, returning the message it completes (if any).
+    async fn handle_frame(&mut self, frame: Frame) -> io::Result<Option<Message>> {
+        let fin = frame.fin();
+
+        match frame.opcode() {
+            OpCode::Ping => {
+                let payload = frame.into_payload();
+
+                if !self.close_sent {
+                    self.send_frame(Frame::new(true, OpCode::Pong, payload.clone()))
+                        .await?;
+                }
+
+                Ok(Some(Message::Ping(payload)))
+            }
+            OpCode::Pong => Ok(Some(Message::Pong(frame.into_payload()))),
+            OpCode::Close => {
+                let close = CloseFrame::parse(frame.payload())?;
+                self.close_received = true;
+
+                if !self.close_sent {
+                    // We echo the status code, as is customary.
+                    let code = close.as_ref().map_or(CLOSE_NORMAL, CloseFrame::code);
+                    self.send(Message::Close(Some(CloseFrame::new(code, ""))))
+                        .await?;
+                }
+
+                Ok(Some(Message::Close(close)))
+            }
+            opcode @ (OpCode::Text | OpCode::Binary) => {
+                if self.fragmented.is_some() {
+                    return Err(protocol_error(
+                        "new message started before the previous one was complete",
+                    ));
+                }
+
+                if fin {
+                    return to_message(opcode, frame.into_payload()).map(Some);
+                }
+
+                self.fragmented = Some((opcode, frame.into_payload()));
+                Ok(None)
+            }
+            OpCode::Continuation => {
+                let Some((opcode, payload)) = &mut self.fragmented else {
+                    return Err(protocol_error(
+                        "continuation frame without a message to continue",
+                    ));
+                };
+
+                if payload.len() + frame.payload().len() > self.codec.max_message_len() {
+                    return Err(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "message exceeds the maximum of {} bytes",
+                            self.codec.max_message_len()
+                        ),
+                    )));
+                }
+
+                payload.extend_from_slice(frame.payload());
+                let opcode = *opcode;
+
+                if !fin {
+                    return Ok(None);
+                }
+
+                let (_, payload) = self.fragmented.take().expect("checked above");
+                to_message(opcode, payload).map(Some)
+            }
+        }
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for WebSocket<T> {}
+#[negative_impl]
+impl<T> !Sync for WebSocket<T> {}
+
+fn to_message(opcode: OpCode, payload: Vec<u8>) -> io::Result<Message> {
+    match opcode {
+        OpCode::Text => String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| protocol_error("text message is not valid UTF-8")),
+        _ => Ok(Message::Binary(payload)),
+    }
+}
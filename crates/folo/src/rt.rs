@@ -1,9 +1,13 @@
 mod async_agent;
 mod async_task_engine;
 mod builder;
+pub(crate) mod clock;
+mod config;
+pub mod context;
 pub(crate) mod current_async_agent;
 pub(crate) mod current_runtime;
 pub(crate) mod current_sync_agent;
+mod deadline;
 mod erased_async_task;
 mod functions;
 mod local_join;
@@ -14,13 +18,22 @@ mod remote_result_box;
 mod remote_task;
 mod remote_waker;
 mod runtime_client;
+mod select;
 mod sync_agent;
+mod timers;
+pub(crate) mod tuning;
 mod types;
 mod waker;
 
 pub use builder::*;
+pub use clock::{now, Clock, ManualClock, SystemClock};
+pub use config::*;
+pub use deadline::{with_deadline, WithDeadline};
 pub use functions::*;
 pub use local_join::*;
 pub use remote_join::*;
 pub use runtime_client::*;
+pub use select::*;
+pub use timers::*;
+pub use tuning::{IdleStrategy, RuntimeTuning};
 pub(crate) use types::*;
@@ -1,11 +1,14 @@
 use super::erased_async_task::ErasedResultAsyncTask;
 use crate::{
     io,
-    metrics::{self, Event, EventBuilder, ReportPage},
+    mem::{self, Subsystem},
+    metrics::{self, Event, EventBuilder, Magnitude, ReportPage},
     rt::{
         async_task_engine::{AsyncTaskEngine, CycleResult},
+        clock, context,
         local_task::LocalTask,
-        LocalJoinHandle,
+        tuning::{self, IdleStrategy, RuntimeTuning},
+        LocalJoinHandle, Timers, DEFERRED_CLEANUP_GRACE_PERIOD,
     },
 };
 use core_affinity::CoreId;
@@ -15,7 +18,11 @@ use std::{
     collections::VecDeque,
     fmt::{self, Debug, Formatter},
     future::Future,
+    num::NonZeroUsize,
     pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
 };
 use tracing::{event, Level};
 use windows::Win32::System::Threading::INFINITE;
@@ -42,10 +49,16 @@ pub struct AsyncAgent {
     metrics_tx: Option<channel::Sender<ReportPage>>,
     processor_id: CoreId,
 
+    // The index of the worker among the async workers of the runtime, used to schedule tasks on
+    // this specific worker. None for the TCP dispatcher, which is not one of them.
+    worker_index: Option<usize>,
+
     engine: RefCell<AsyncTaskEngine>,
 
     io: RefCell<io::Driver>,
 
+    timers: RefCell<Timers>,
+
     // Tasks that have been enqueued but have not yet been handed over to the async task engine.
     // Includes both locally queued tasks and tasks enqueued from another thread, which are both
     // unified to the `ErasedResultAsyncTask` type.
@@ -54,6 +67,14 @@ pub struct AsyncAgent {
     // If we are shutting down, we try ignore requests to schedule new tasks and do our best to
     // cleanup ASAP.
     shutting_down: Cell<bool>,
+
+    // Number of deferred cleanup tasks that have not yet completed or been dropped. When asked to
+    // terminate, we give these a chance to complete before we start shutting down.
+    pending_deferred: Rc<Cell<usize>>,
+
+    // Set once we have been asked to terminate but are still waiting for deferred cleanup tasks,
+    // to the point in time after which we stop waiting.
+    drain_deadline: Cell<Option<Instant>>,
 }
 
 impl AsyncAgent {
@@ -61,19 +82,33 @@ impl AsyncAgent {
         command_rx: channel::Receiver<AsyncAgentCommand>,
         metrics_tx: Option<channel::Sender<ReportPage>>,
         processor_id: CoreId,
+        worker_index: Option<usize>,
+        latency_slos: Option<Arc<io::LatencySlos>>,
+        io_completion_batch_size: NonZeroUsize,
+        io_completion_max_batches: NonZeroUsize,
     ) -> Self {
         Self {
             command_rx,
             metrics_tx,
             processor_id,
+            worker_index,
             // SAFETY: The async task engine must not be dropped until we get a
             // `CycleResult::Shutdown` from it. We do wait for this in `run()`.
             engine: RefCell::new(unsafe { AsyncTaskEngine::new() }),
             // SAFETY: The I/O driver must not be dropped while there are pending I/O operations.
             // We ensure this by waiting for I/O to complete before returning from `run()`.
-            io: RefCell::new(unsafe { io::Driver::new() }),
+            io: RefCell::new(unsafe {
+                io::Driver::new(
+                    latency_slos,
+                    io_completion_batch_size,
+                    io_completion_max_batches,
+                )
+            }),
+            timers: RefCell::new(Timers::new()),
             new_tasks: RefCell::new(VecDeque::new()),
             shutting_down: Cell::new(false),
+            pending_deferred: Rc::new(Cell::new(0)),
+            drain_deadline: Cell::new(None),
         }
     }
 
@@ -81,10 +116,25 @@ impl AsyncAgent {
         self.processor_id
     }
 
+    /// The index of this worker, for `RuntimeClient::spawn_on_worker()`. None for the TCP
+    /// dispatcher.
+    pub fn worker_index(&self) -> Option<usize> {
+        self.worker_index
+    }
+
     pub fn io(&self) -> &RefCell<io::Driver> {
         &self.io
     }
 
+    pub fn timers(&self) -> &RefCell<Timers> {
+        &self.timers
+    }
+
+    /// Whether the agent has started shutting down, after which no new tasks may be spawned.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.get()
+    }
+
     /// Spawns a task to execute a future on the current async worker thread.
     ///
     /// # Panics
@@ -103,6 +153,8 @@ impl AsyncAgent {
 
         LOCAL_TASKS.with(Event::observe_unit);
 
+        let _subsystem = mem::enter_subsystem(Subsystem::Tasks);
+
         // SAFETY: We must ensure that the LocalTask is not dropped while any references to its
         // outcome exist (i.e. as long as the join handle is referenced by someone). The join handle
         // is returned from this function and may be referenced by any other task owned by the same
@@ -130,6 +182,29 @@ impl AsyncAgent {
         join_handle
     }
 
+    /// Spawns a deferred cleanup task on the current async worker thread. Returns `false` without
+    /// spawning anything if the agent is already shutting down, in which case the future is dropped.
+    pub fn defer<F>(&self, future: F) -> bool
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        if self.shutting_down.get() {
+            DEFERRED_ABANDONED.with(Event::observe_unit);
+            return false;
+        }
+
+        DEFERRED.with(Event::observe_unit);
+
+        let pending = PendingDeferred::new(Rc::clone(&self.pending_deferred));
+
+        _ = self.spawn(async move {
+            future.await;
+            pending.complete();
+        });
+
+        true
+    }
+
     pub fn run(&self) {
         event!(Level::TRACE, "Started");
 
@@ -183,45 +258,43 @@ impl AsyncAgent {
                     // coordination of worker threads, it is conceivable that somehow we might get
                     // multiple shutdown commands. Just ignore any extra ones - we cannot be
                     // shutting down any harder than we already are.
-                    if !self.shutting_down.get() {
-                        // This *starts* our shutdown - we still need to wait for the async task
-                        // engine to clean up and for pending I/O operations to complete.
-                        event!(
-                            Level::TRACE,
-                            "received terminate command; shutdown process starting"
-                        );
-
-                        self.shutting_down.set(true);
-
-                        // The tasks in this list may own resources that are already referenced by other
-                        // tasks or external entities. We need to accept them into our regular process
-                        // before dropping them - they are not safe to drop just because they are new.
-                        while let Some(erased_task) = self.new_tasks.borrow_mut().pop_front() {
-                            engine.enqueue_erased(erased_task);
-                        }
-
-                        // Start cleaning up the async task engine. This may require some time if there
-                        // are foreign threads holding our wakers. We wait for all wakers to be dropped.
-                        engine.begin_shutdown();
-
-                        // The I/O driver itself does not have a shutdown process - we simply need
-                        // to wait for all pending operations to complete. This will occur naturally
-                        // over time, speeded up by the fact that the async task engine dropped a
-                        // bunch of tasks that were hopefully holding I/O handles that now got
-                        // closed and resulted in pending I/O being canceled (which we still need to
-                        // wait for - a cancellation is just a regular I/O completion for us).
+                    if !self.shutting_down.get() && self.drain_deadline.get().is_none() {
+                        event!(Level::TRACE, "received terminate command");
+
+                        // Deferred cleanup tasks get some time to complete before we shut down.
+                        // Everything else keeps running in the meantime, as the cleanup may depend
+                        // on other tasks (and certainly on the I/O driver) to make progress.
+                        self.drain_deadline
+                            .set(Some(clock::now() + DEFERRED_CLEANUP_GRACE_PERIOD));
                     }
                 }
             }
 
+            if let Some(deadline) = self.drain_deadline.get() {
+                if self.pending_deferred.get() == 0 || clock::now() >= deadline {
+                    self.drain_deadline.set(None);
+                    self.begin_shutdown(&mut engine);
+                }
+            }
+
             // If new tasks have been enqueued but not yet handed over to the engine, we inhibit I/O
             // sleep to get to processing those new tasks ASAP after any pending I/O is completed.
             allow_io_sleep &= self.new_tasks.borrow().is_empty();
 
+            // When spinning, we never sleep and just keep polling for work.
+            allow_io_sleep &= tuning::with_current(|x| x.idle_strategy) == IdleStrategy::Sleep;
+
             let io_wait_time_ms = if allow_io_sleep {
                 CYCLES_WITH_SLEEP.with(Event::observe_unit);
 
-                CROSS_THREAD_WORK_POLL_INTERVAL_MS
+                let poll_interval_ms =
+                    tuning::with_current(RuntimeTuning::cross_thread_poll_interval_ms);
+
+                // If a timer is due before the next poll for cross-thread work, we wake up for it.
+                match self.timers.borrow_mut().next_deadline() {
+                    Some(deadline) => clock::milliseconds_until(deadline).min(poll_interval_ms),
+                    None => poll_interval_ms,
+                }
             } else {
                 CYCLES_WITHOUT_SLEEP.with(Event::observe_unit);
 
@@ -230,7 +303,16 @@ impl AsyncAgent {
 
             self.io.borrow_mut().process_completions(io_wait_time_ms);
 
-            // TODO: Process timers.
+            // The wakers are collected first, so we do not hold the timers borrowed while waking.
+            let expired_timers = self.timers.borrow_mut().take_expired(clock::now());
+
+            if !expired_timers.is_empty() {
+                TIMERS_FIRED.with(|x| x.observe(expired_timers.len() as i64));
+            }
+
+            for waker in expired_timers {
+                waker.wake();
+            }
 
             {
                 let mut new_tasks = self.new_tasks.borrow_mut();
@@ -285,10 +367,45 @@ impl AsyncAgent {
         event!(Level::TRACE, "shutdown completed");
 
         if let Some(tx) = &self.metrics_tx {
+            mem::publish_metrics();
             _ = tx.send(metrics::report_page());
         }
     }
 
+    /// Starts the shutdown process, after which no new tasks are accepted.
+    fn begin_shutdown(&self, engine: &mut AsyncTaskEngine) {
+        // This *starts* our shutdown - we still need to wait for the async task engine to clean up
+        // and for pending I/O operations to complete.
+        event!(Level::TRACE, "shutdown process starting");
+
+        self.shutting_down.set(true);
+
+        // The tasks in this list may own resources that are already referenced by other tasks or
+        // external entities. We need to accept them into our regular process before dropping them -
+        // they are not safe to drop just because they are new.
+        while let Some(erased_task) = self.new_tasks.borrow_mut().pop_front() {
+            engine.enqueue_erased(erased_task);
+        }
+
+        // Start cleaning up the async task engine. This may require some time if there are foreign
+        // threads holding our wakers. We wait for all wakers to be dropped.
+        engine.begin_shutdown();
+
+        // Pending timers hold wakers of tasks that are now being dropped. They will never fire, so
+        // we drop them to allow the engine to finish its cleanup.
+        self.timers.borrow_mut().clear();
+
+        // Values in the worker-local context may own I/O primitives, which we close now so their
+        // pending operations complete before the I/O driver shuts down.
+        context::clear();
+
+        // The I/O driver itself does not have a shutdown process - we simply need to wait for all
+        // pending operations to complete. This will occur naturally over time, speeded up by the
+        // fact that the async task engine dropped a bunch of tasks that were hopefully holding I/O
+        // handles that now got closed and resulted in pending I/O being canceled (which we still
+        // need to wait for - a cancellation is just a regular I/O completion for us).
+    }
+
     fn process_commands(&self) -> ProcessCommandsResult {
         let mut received_commands = false;
         let mut received_terminate = false;
@@ -301,7 +418,10 @@ impl AsyncAgent {
                     // because remote tasks are expected to always be inert (they hold no resources
                     // that need special cleanup, at least not yet, because they have no local
                     // presence yet).
-                    if self.shutting_down.get() || received_terminate {
+                    if self.shutting_down.get()
+                        || self.drain_deadline.get().is_some()
+                        || received_terminate
+                    {
                         assert!(
                             erased_task.is_inert(),
                             "all remote tasks must be always inert"
@@ -313,6 +433,13 @@ impl AsyncAgent {
                     REMOTE_TASKS.with(Event::observe_unit);
                     self.new_tasks.borrow_mut().push_back(erased_task);
                 }
+                Ok(AsyncAgentCommand::ApplyTuning { tuning }) => {
+                    // We are between two iterations of the work loop, so nothing is in the middle
+                    // of using the current values.
+                    TUNING_APPLIED.with(Event::observe_unit);
+                    tuning::apply(tuning);
+                    continue;
+                }
                 Ok(AsyncAgentCommand::Terminate) => {
                     // We continue processing commands even after the terminate signal because
                     // we need to clean up any messages received during the shutdown process,
@@ -340,10 +467,38 @@ impl AsyncAgent {
     }
 }
 
-/// How often to poll for cross-thread work, in milliseconds. We do not have cross-thread real time
-/// signals and use polling to check for arriving work. This sets our maximum sleep time, although
-/// we will often check much more often if activity on the current thread wakes us up.
-const CROSS_THREAD_WORK_POLL_INTERVAL_MS: u32 = 10;
+/// Keeps track of a pending deferred cleanup task, until the task is dropped.
+struct PendingDeferred {
+    count: Rc<Cell<usize>>,
+    completed: bool,
+}
+
+impl PendingDeferred {
+    fn new(count: Rc<Cell<usize>>) -> Self {
+        count.set(count.get() + 1);
+
+        Self {
+            count,
+            completed: false,
+        }
+    }
+
+    fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for PendingDeferred {
+    fn drop(&mut self) {
+        self.count.set(self.count.get() - 1);
+
+        if !self.completed {
+            DEFERRED_ABANDONED.with(Event::observe_unit);
+        }
+    }
+}
+
+const TIMERS_FIRED_BUCKETS: &[Magnitude] = &[1, 2, 4, 8, 16, 32, 64];
 
 impl Debug for AsyncAgent {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -352,6 +507,8 @@ impl Debug for AsyncAgent {
             .field("engine", &self.engine)
             .field("io", &self.io)
             .field("shutting_down", &self.shutting_down)
+            .field("pending_deferred", &self.pending_deferred)
+            .field("drain_deadline", &self.drain_deadline)
             .finish()
     }
 }
@@ -361,6 +518,9 @@ pub enum AsyncAgentCommand {
         erased_task: Pin<Box<dyn ErasedResultAsyncTask + Send>>,
     },
 
+    /// Replaces the runtime parameters applied on the worker thread.
+    ApplyTuning { tuning: RuntimeTuning },
+
     /// Shuts down the worker thread immediately, without waiting for any pending operations to
     /// complete. The worker will still complete the current task and perform necessary cleanup
     /// to avoid resource leaks, which may take some time.
@@ -371,6 +531,7 @@ impl Debug for AsyncAgentCommand {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Self::EnqueueTask { .. } => write!(f, "EnqueueTask"),
+            Self::ApplyTuning { tuning } => write!(f, "ApplyTuning({tuning:?})"),
             Self::Terminate => write!(f, "Terminate"),
         }
     }
@@ -409,4 +570,25 @@ thread_local! {
         .name("rt_async_cycles_without_sleep")
         .build()
         .unwrap();
+
+    static DEFERRED: Event = EventBuilder::new()
+        .name("rt_async_deferred_cleanups")
+        .build()
+        .unwrap();
+
+    static DEFERRED_ABANDONED: Event = EventBuilder::new()
+        .name("rt_async_deferred_cleanups_abandoned")
+        .build()
+        .unwrap();
+
+    static TUNING_APPLIED: Event = EventBuilder::new()
+        .name("rt_async_tuning_applied")
+        .build()
+        .unwrap();
+
+    static TIMERS_FIRED: Event = EventBuilder::new()
+        .name("rt_async_timers_fired")
+        .buckets(TIMERS_FIRED_BUCKETS)
+        .build()
+        .unwrap();
 }
@@ -2,14 +2,15 @@ use crate::{
     constants::{GENERAL_MILLISECONDS_BUCKETS, POISONED_LOCK},
     io::IO_DEQUEUE_BATCH_SIZE,
     metrics::{Event, EventBuilder},
-    rt::{erased_async_task::ErasedResultAsyncTask, waker::WakeSignal},
+    rt::{deadline, erased_async_task::ErasedResultAsyncTask, waker::WakeSignal},
     util::{BuildPointerHasher, LowPrecisionInstant, PinnedSlabChain},
 };
 use negative_impl::negative_impl;
 use pin_project::pin_project;
 use std::{
-    cell::RefCell,
-    collections::{HashSet, VecDeque},
+    cell::{Cell, RefCell},
+    cmp,
+    collections::{BinaryHeap, HashSet, VecDeque},
     fmt::{self, Debug, Formatter},
     pin::Pin,
     sync::{
@@ -17,6 +18,7 @@ use std::{
         Arc, Mutex,
     },
     task,
+    time::Instant,
 };
 
 type TaskKey = usize;
@@ -65,9 +67,7 @@ pub struct AsyncTaskEngine {
 
     // The active set contains all the tasks we want to poll. This is where all futures start.
     // The items are pinned pointers into the `tasks` collection.
-    //
-    // This is a VedDeque because we do not require set characteristics and a deque is faster.
-    active: VecDeque<*mut Task>,
+    active: ActiveTasks,
 
     // The inactive set contains all the tasks that are sleeping. We will move them back to the
     // active set after a waker notifies us that a future needs to wake up. Note that the wakeup
@@ -124,7 +124,7 @@ impl AsyncTaskEngine {
     pub unsafe fn new() -> Self {
         Self {
             tasks: PinnedSlabChain::new(),
-            active: VecDeque::new(),
+            active: ActiveTasks::default(),
             inactive: HashSet::with_hasher(BuildPointerHasher::default()),
             awakened: Arc::new(Mutex::new(VecDeque::with_capacity(AWAKENED_CAPACITY))),
             probe_embedded_wake_signals: Arc::new(AtomicBool::new(false)),
@@ -168,7 +168,7 @@ impl AsyncTaskEngine {
         let task_pin = unsafe { Pin::new_unchecked(&mut *task_ptr) };
         task_pin.initialize();
 
-        self.active.push_back(task_ptr);
+        self.active.push(task_ptr);
     }
 
     pub fn execute_cycle(&mut self) -> CycleResult {
@@ -188,14 +188,21 @@ impl AsyncTaskEngine {
         // We do not really care why/how the wake signal was sent - same handling for all cases.
         self.activate_awakened_tasks();
 
-        while let Some(task_ptr) = self.active.pop_front() {
+        while let Some(task_ptr) = self.active.pop() {
             // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
             // we never do until they progress through the lifecycle into the `completed` list.
             let task = unsafe { Pin::new_unchecked(&*task_ptr) };
 
+            // Anything reported outside of task polls (e.g. by futures polled directly by the
+            // worker) must not be attributed to this task.
+            _ = deadline::take_reported_deadline();
+
             let poll_result =
                 TASK_POLL_DURATION.with(|x| x.observe_duration_millis(|| task.poll()));
 
+            // Any deadline reported by the task during the poll applies until the next poll.
+            task.deadline.set(deadline::take_reported_deadline());
+
             match poll_result {
                 task::Poll::Ready(()) => {
                     TASKS_COMPLETED.with(Event::observe_unit);
@@ -259,7 +266,7 @@ impl AsyncTaskEngine {
                 // we do nothing. We detect this by ensuring that the task was in the "inactive" set
                 // before we react to the wake notification. This also eliminates spurious wakes.
                 if self.inactive.remove(&task_ptr) {
-                    self.active.push_back(task_ptr);
+                    self.active.push(task_ptr);
 
                     TASK_ACTIVATED_VIA_SET.with(Event::observe_unit);
                 } else {
@@ -279,7 +286,7 @@ impl AsyncTaskEngine {
 
                 if task.wake_signal.consume_awakened() {
                     TASK_ACTIVATED_VIA_SIGNAL.with(Event::observe_unit);
-                    self.active.push_back(*task_ptr);
+                    self.active.push(*task_ptr);
                     false
                 } else {
                     true
@@ -329,7 +336,7 @@ impl AsyncTaskEngine {
         // We call .count() to force the iterator to be evaluated. We do not care about the count.
         _ = self
             .active
-            .drain(..)
+            .drain()
             .chain(self.inactive.drain())
             .map(|task_ptr| {
                 // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
@@ -365,6 +372,78 @@ impl !Send for AsyncTaskEngine {}
 #[negative_impl]
 impl !Sync for AsyncTaskEngine {}
 
+/// The tasks that are ready to be polled. Tasks with a deadline (see `rt::with_deadline()`) are
+/// polled first, earliest deadline first, followed by the rest in the order they became active.
+#[derive(Debug, Default)]
+struct ActiveTasks {
+    // This is a VecDeque because we do not require set characteristics and a deque is faster.
+    fifo: VecDeque<*mut Task>,
+
+    by_deadline: BinaryHeap<ByDeadline>,
+}
+
+impl ActiveTasks {
+    fn push(&mut self, task_ptr: *mut Task) {
+        // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
+        // we never do until they progress through the lifecycle into the `completed` list.
+        let task = unsafe { &*task_ptr };
+
+        match task.deadline.get() {
+            Some(deadline) => self.by_deadline.push(ByDeadline { deadline, task_ptr }),
+            None => self.fifo.push_back(task_ptr),
+        }
+    }
+
+    fn pop(&mut self) -> Option<*mut Task> {
+        self.by_deadline
+            .pop()
+            .map(|entry| entry.task_ptr)
+            .or_else(|| self.fifo.pop_front())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fifo.is_empty() && self.by_deadline.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.fifo.len() + self.by_deadline.len()
+    }
+
+    fn drain(&mut self) -> impl Iterator<Item = *mut Task> + '_ {
+        self.by_deadline
+            .drain()
+            .map(|entry| entry.task_ptr)
+            .chain(self.fifo.drain(..))
+    }
+}
+
+#[derive(Debug)]
+struct ByDeadline {
+    deadline: Instant,
+    task_ptr: *mut Task,
+}
+
+// Reversed, so the earliest deadline is at the top of the (max-)heap.
+impl Ord for ByDeadline {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+impl PartialOrd for ByDeadline {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByDeadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for ByDeadline {}
+
 /// The result of executing one cycle of the async task engine.
 #[derive(Debug, PartialEq, Eq)]
 pub enum CycleResult {
@@ -387,6 +466,9 @@ pub(super) struct Task {
     // Used for dropping the task once we are done with it.
     index: usize,
 
+    // The soft deadline reported by the task when it was last polled, if any.
+    deadline: Cell<Option<Instant>>,
+
     #[pin]
     wake_signal: WakeSignal,
 }
@@ -405,6 +487,7 @@ impl Task {
         Self {
             inner: RefCell::new(inner),
             index,
+            deadline: Cell::new(None),
             wake_signal: WakeSignal::new(awakened_queue, probe_embedded_wake_signals),
         }
     }
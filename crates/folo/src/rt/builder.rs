@@ -1,49 +1,63 @@
 use super::{
     current_sync_agent,
-    sync_agent::{SyncAgent, SyncAgentCommand},
+    sync_agent::{SyncAgent, SyncAgentCommand, SyncWorkerKind},
 };
 use crate::{
-    io::{self, IoWaker},
+    io::{self, buffer_pool, IoWaker, LatencySlos},
     metrics::ReportPage,
     rt::{
         async_agent::{AsyncAgent, AsyncAgentCommand},
-        current_async_agent, current_runtime, RuntimeClient,
+        clock, current_async_agent, current_runtime, tuning, Clock, RuntimeClient,
+        RuntimeConfig, RuntimeTuning,
     },
 };
 use crossbeam::{channel, queue::SegQueue};
 use std::{
     collections::HashMap,
     fmt::{self, Debug, Formatter},
+    num::NonZeroUsize,
     rc::Rc,
     sync::{atomic::AtomicBool, Arc},
     thread,
 };
-use tracing::{event, Level};
-
-/// The thing with synchronous worker threads is that they often get blocked and spend time doing
-/// essentially nothing due to offloading blocking I/O onto these threads. Therefore, we spawn many
-/// of them to ensure that we can keep processing synchronous work when a large batch comes in.
-/// In the future we might replace this with a more dynamically sizing thread pool but for now the
-/// fixed size might be acceptable.
-const SYNC_WORKERS_PER_PROCESSOR: usize = 2;
+use tracing::{event, span, Level, Span};
 
 pub struct RuntimeBuilder {
     worker_init: Option<Arc<dyn Fn() + Send + Sync + 'static>>,
+    context_init: Option<Arc<dyn Fn() + Send + Sync + 'static>>,
     ad_hoc_entrypoint: bool,
     metrics_tx: Option<channel::Sender<ReportPage>>,
-    max_processors: Option<usize>,
+    config: RuntimeConfig,
+    name: Option<String>,
+    latency_slos: Option<Arc<LatencySlos>>,
+    tuning: RuntimeTuning,
+    clock: Option<Arc<dyn Clock>>,
 }
 
 impl RuntimeBuilder {
     pub fn new() -> Self {
         Self {
             worker_init: None,
+            context_init: None,
             ad_hoc_entrypoint: false,
             metrics_tx: None,
-            max_processors: None,
+            config: RuntimeConfig::default(),
+            name: None,
+            latency_slos: None,
+            tuning: RuntimeTuning::default(),
+            clock: None,
         }
     }
 
+    /// Sets the name of the runtime, which tells it apart from other runtimes in the same process.
+    /// The name prefixes the names of the worker threads, is attached (as the `runtime` field of a
+    /// span) to all tracing events emitted on the worker threads and is recorded in the metrics
+    /// report pages they publish. Defaults to `DEFAULT_RUNTIME_NAME`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
     /// Registers a function to call when initializing every created worker thread.
     pub fn worker_init<F>(mut self, f: F) -> Self
     where
@@ -53,6 +67,18 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Registers a function to call on every async worker thread once it has been attached to the
+    /// runtime, right before it starts executing tasks. Use this to populate the worker-local
+    /// context (see `rt::context`) with per-worker resources. Unlike the `worker_init` function,
+    /// this may create I/O primitives, as the I/O driver of the worker is already available.
+    pub fn context_init<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.context_init = Some(Arc::new(f));
+        self
+    }
+
     /// Registers the Folo runtime as the owner of the entrypoint thread. This may be useful for
     /// interoperability purposes when using custom entry points (such as benchmarking logic).
     ///
@@ -78,8 +104,57 @@ impl RuntimeBuilder {
     /// a closer look at some behavior without 99 different worker threads going wild. Not super
     /// valuable in real usage because it does not specify which processor (actually, it will use
     /// the first N).
+    ///
+    /// Overrides the same setting of any previously provided `RuntimeConfig`.
     pub fn max_processors(mut self, max_processors: usize) -> Self {
-        self.max_processors = Some(max_processors);
+        self.config.max_processors = NonZeroUsize::new(max_processors);
+        self
+    }
+
+    /// Limits the memory that the buffer pool of each worker thread holds on to.
+    ///
+    /// Overrides the same setting of any previously provided `RuntimeConfig`.
+    pub fn buffer_pool_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.config.buffer_pool_max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the max number of I/O completions each async worker dequeues from the operating system
+    /// in one call. See `RuntimeConfig::io_completion_batch_size()`.
+    ///
+    /// Overrides the same setting of any previously provided `RuntimeConfig`.
+    pub fn io_completion_batch_size(mut self, value: NonZeroUsize) -> Self {
+        self.config.io_completion_batch_size = value;
+        self
+    }
+
+    /// Sets the latency thresholds to monitor I/O operations against, on all async workers.
+    pub fn latency_slos(mut self, value: LatencySlos) -> Self {
+        // Without any thresholds there is nothing to check, so we skip the checking altogether.
+        self.latency_slos = (!value.is_empty()).then(|| Arc::new(value));
+        self
+    }
+
+    /// Sets the tunable parameters of the runtime, replacing any previously set configuration.
+    pub fn config(mut self, config: RuntimeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the initial values of the runtime parameters that can be adjusted at run time via
+    /// `RuntimeClient::set_tuning()`.
+    pub fn tuning(mut self, tuning: RuntimeTuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    /// Sets the clock that the worker threads of the runtime read the time from, for timers,
+    /// deadlines, timeouts and metrics. Defaults to `SystemClock`. A `ManualClock` makes the
+    /// passage of time deterministic, for testing timeout-heavy logic.
+    ///
+    /// Code running on the worker threads can read the time of this clock via `rt::now()`.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
         self
     }
 
@@ -94,19 +169,34 @@ impl RuntimeBuilder {
         let mut processor_ids =
             core_affinity::get_core_ids().expect("must always be able to identify processor IDs");
 
-        if let Some(max_processors) = self.max_processors {
-            processor_ids.truncate(max_processors);
+        if let Some(max_processors) = self.config.max_processors {
+            processor_ids.truncate(max_processors.get());
         }
 
+        let name: Arc<str> = self.name.as_deref().unwrap_or(DEFAULT_RUNTIME_NAME).into();
+
+        let sync_workers_per_processor = self.config.sync_workers_per_processor.get();
+        let fs_workers_per_processor = self.config.fs_workers_per_processor.get();
+        let pin_workers = self.config.pin_workers;
+        let buffer_pool_max_bytes = self.config.buffer_pool_max_bytes;
+        let io_completion_batch_size = self.config.io_completion_batch_size;
+        let io_completion_max_batches = self.config.io_completion_max_batches;
+
+        // If metrics are disabled, we pretend nobody asked for them.
+        let metrics_tx = self.metrics_tx.filter(|_| self.config.metrics_enabled);
+
         // We will spawn one agent of each type (async + sync) for each processor.
         let processor_count = processor_ids.len();
 
         let async_worker_count = processor_count;
-        let sync_worker_count = SYNC_WORKERS_PER_PROCESSOR * processor_count;
+        let sync_worker_count =
+            (sync_workers_per_processor + fs_workers_per_processor) * processor_count;
 
-        event!(Level::INFO, processor_count);
+        event!(Level::INFO, runtime = &*name, processor_count);
 
         let worker_init = self.worker_init.unwrap_or(Arc::new(|| {}));
+        let context_init = self.context_init.unwrap_or(Arc::new(|| {}));
+        let runtime_clock = self.clock;
 
         let mut join_handles = Vec::with_capacity(sync_worker_count + async_worker_count);
 
@@ -127,20 +217,44 @@ impl RuntimeBuilder {
             async_command_txs.push(command_tx);
 
             let worker_init = worker_init.clone();
+            let context_init = context_init.clone();
+            let runtime_clock = runtime_clock.clone();
 
-            let metrics_tx = match self.metrics_tx {
+            let metrics_tx = match metrics_tx {
                 Some(ref tx) => Some(tx.clone()),
                 None => None,
             };
 
+            let latency_slos = self.latency_slos.clone();
+            let initial_tuning = self.tuning.clone();
+
             let processor_id = processor_ids[worker_index];
+            let name = Arc::clone(&name);
 
             let join_handle = thread::Builder::new()
-                .name(format!("async-{}", worker_index))
+                .name(format!("{name}-async-{worker_index}"))
                 .spawn(move || {
+                    let _span = worker_span(&name).entered();
+
                     (worker_init)();
 
-                    let agent = Rc::new(AsyncAgent::new(command_rx, metrics_tx, processor_id));
+                    if let Some(runtime_clock) = runtime_clock {
+                        clock::set(runtime_clock);
+                    }
+
+                    buffer_pool::set_max_bytes(buffer_pool_max_bytes);
+
+                    tuning::apply(initial_tuning);
+
+                    let agent = Rc::new(AsyncAgent::new(
+                        command_rx,
+                        metrics_tx,
+                        processor_id,
+                        Some(worker_index),
+                        latency_slos,
+                        io_completion_batch_size,
+                        io_completion_max_batches,
+                    ));
 
                     // Signal that we are ready to start.
                     ready_tx
@@ -155,11 +269,15 @@ impl RuntimeBuilder {
                         .recv()
                         .expect("runtime startup process failed in infallible code");
 
-                    core_affinity::set_for_current(processor_id);
+                    if pin_workers {
+                        core_affinity::set_for_current(processor_id);
+                    }
 
                     current_async_agent::set(Rc::clone(&agent));
                     current_runtime::set(start.runtime_client);
 
+                    (context_init)();
+
                     agent.run();
                 })?;
 
@@ -179,11 +297,13 @@ impl RuntimeBuilder {
         // # Sync workers
 
         let mut sync_command_txs_by_processor = HashMap::new();
+        let mut fs_command_txs_by_processor = HashMap::new();
         let mut sync_start_txs = Vec::with_capacity(sync_worker_count);
         let mut sync_ready_rxs = Vec::with_capacity(sync_worker_count);
 
         let mut sync_task_queues_by_processor = HashMap::new();
         let mut sync_priority_task_queues_by_processor = HashMap::new();
+        let mut fs_task_queues_by_processor = HashMap::new();
 
         for processor_id in &processor_ids {
             // There is a single queue of synchronous tasks per processor, shared by all the sync
@@ -197,64 +317,104 @@ impl RuntimeBuilder {
             sync_priority_task_queues_by_processor
                 .insert(*processor_id, Arc::clone(&sync_priority_task_queue));
 
-            for worker_index in 0..SYNC_WORKERS_PER_PROCESSOR {
-                let processor_id = processor_id.clone();
-
-                let (start_tx, start_rx) = channel::unbounded::<AgentStartArguments>();
-                sync_start_txs.push(start_tx);
-
-                let (ready_tx, ready_rx) = channel::unbounded::<SyncAgentReady>();
-                sync_ready_rxs.push(ready_rx);
-
-                let sync_command_txs = sync_command_txs_by_processor
-                    .entry(processor_id)
-                    .or_insert_with(|| Vec::with_capacity(sync_worker_count));
-
-                let (command_tx, command_rx) = channel::unbounded::<SyncAgentCommand>();
-                sync_command_txs.push(command_tx);
-
-                let worker_init = worker_init.clone();
-
-                let metrics_tx = match self.metrics_tx {
-                    Some(ref tx) => Some(tx.clone()),
-                    None => None,
-                };
-
-                let sync_task_queue = Arc::clone(&sync_task_queue);
-                let sync_priority_task_queue = Arc::clone(&sync_priority_task_queue);
-
-                let join_handle = thread::Builder::new()
-                    .name(format!("sync-{}-{}", processor_id.id, worker_index))
-                    .spawn(move || {
-                        (worker_init)();
-
-                        let agent = Rc::new(SyncAgent::new(
-                            command_rx,
-                            metrics_tx,
-                            sync_task_queue,
-                            sync_priority_task_queue,
-                        ));
-
-                        // Signal that we are ready to start.
-                        ready_tx
-                            .send(SyncAgentReady {})
-                            .expect("runtime startup process failed in infallible code");
-
-                        // We first wait for the startup signal, which indicates that all agents have been
-                        // created and registered with the runtime, and the runtime is ready to be used.
-                        let start = start_rx
-                            .recv()
-                            .expect("runtime startup process failed in infallible code");
-
-                        core_affinity::set_for_current(processor_id);
-
-                        current_sync_agent::set(Rc::clone(&agent));
-                        current_runtime::set(start.runtime_client);
-
-                        agent.run();
-                    })?;
-
-                join_handles.push(join_handle);
+            // File system operations have their own queue and workers, so a storm of slow metadata
+            // operations cannot hold up the other synchronous work (and vice versa). They share the
+            // high-priority queue, as releasing resources is beneficial no matter who does it.
+            let fs_task_queue = Arc::new(SegQueue::new());
+            fs_task_queues_by_processor.insert(*processor_id, Arc::clone(&fs_task_queue));
+
+            let pools = [
+                (
+                    SyncWorkerKind::General,
+                    sync_workers_per_processor,
+                    &sync_task_queue,
+                    &mut sync_command_txs_by_processor,
+                ),
+                (
+                    SyncWorkerKind::FileSystem,
+                    fs_workers_per_processor,
+                    &fs_task_queue,
+                    &mut fs_command_txs_by_processor,
+                ),
+            ];
+
+            for (kind, workers_per_processor, task_queue, command_txs_by_processor) in pools {
+                for worker_index in 0..workers_per_processor {
+                    let processor_id = processor_id.clone();
+
+                    let (start_tx, start_rx) = channel::unbounded::<AgentStartArguments>();
+                    sync_start_txs.push(start_tx);
+
+                    let (ready_tx, ready_rx) = channel::unbounded::<SyncAgentReady>();
+                    sync_ready_rxs.push(ready_rx);
+
+                    let sync_command_txs = command_txs_by_processor
+                        .entry(processor_id)
+                        .or_insert_with(|| Vec::with_capacity(workers_per_processor));
+
+                    let (command_tx, command_rx) = channel::unbounded::<SyncAgentCommand>();
+                    sync_command_txs.push(command_tx);
+
+                    let worker_init = worker_init.clone();
+                    let runtime_clock = runtime_clock.clone();
+
+                    let metrics_tx = match metrics_tx {
+                        Some(ref tx) => Some(tx.clone()),
+                        None => None,
+                    };
+
+                    let task_queue = Arc::clone(task_queue);
+                    let sync_priority_task_queue = Arc::clone(&sync_priority_task_queue);
+                    let name = Arc::clone(&name);
+
+                    let join_handle = thread::Builder::new()
+                        .name(format!(
+                            "{name}-{}-{}-{worker_index}",
+                            kind.thread_name_infix(),
+                            processor_id.id
+                        ))
+                        .spawn(move || {
+                            let _span = worker_span(&name).entered();
+
+                            (worker_init)();
+
+                            if let Some(runtime_clock) = runtime_clock {
+                                clock::set(runtime_clock);
+                            }
+
+                            buffer_pool::set_max_bytes(buffer_pool_max_bytes);
+
+                            let agent = Rc::new(SyncAgent::new(
+                                kind,
+                                command_rx,
+                                metrics_tx,
+                                task_queue,
+                                sync_priority_task_queue,
+                            ));
+
+                            // Signal that we are ready to start.
+                            ready_tx
+                                .send(SyncAgentReady {})
+                                .expect("runtime startup process failed in infallible code");
+
+                            // We first wait for the startup signal, which indicates that all agents have been
+                            // created and registered with the runtime, and the runtime is ready to be used.
+                            let start = start_rx
+                                .recv()
+                                .expect("runtime startup process failed in infallible code");
+
+                            if pin_workers {
+                                core_affinity::set_for_current(processor_id);
+                            }
+
+                            current_sync_agent::set(Rc::clone(&agent));
+                            current_runtime::set(start.runtime_client);
+
+                            agent.run();
+                        })?;
+
+                    join_handles.push(join_handle);
+                }
             }
         }
 
@@ -276,22 +436,41 @@ impl RuntimeBuilder {
             channel::unbounded::<AsyncAgentCommand>();
 
         let tcp_dispatcher_worker_init = worker_init.clone();
-        let tcp_dispatcher_metrics_tx = match self.metrics_tx {
+        let tcp_dispatcher_metrics_tx = match metrics_tx {
             Some(ref tx) => Some(tx.clone()),
             None => None,
         };
 
+        let tcp_dispatcher_name = Arc::clone(&name);
+        let tcp_dispatcher_latency_slos = self.latency_slos.clone();
+        let tcp_dispatcher_tuning = self.tuning.clone();
+        let tcp_dispatcher_clock = runtime_clock;
+
         let tcp_dispatcher_join_handle = thread::Builder::new()
-            .name("tcp-dispatcher".to_string())
+            .name(format!("{name}-tcp-dispatcher"))
             .spawn(move || {
+                let _span = worker_span(&tcp_dispatcher_name).entered();
+
                 (tcp_dispatcher_worker_init)();
 
+                if let Some(runtime_clock) = tcp_dispatcher_clock {
+                    clock::set(runtime_clock);
+                }
+
+                buffer_pool::set_max_bytes(buffer_pool_max_bytes);
+
+                tuning::apply(tcp_dispatcher_tuning);
+
                 // HACK: We hardcode the first processor ID here. It is used for synchronous work dispatch.
                 // Ideally, we would auto-detect this on the fly because the TCP dispatcher is not pinned.
                 let agent = Rc::new(AsyncAgent::new(
                     tcp_dispatcher_command_rx,
                     tcp_dispatcher_metrics_tx,
                     processor_ids[0],
+                    None,
+                    tcp_dispatcher_latency_slos,
+                    io_completion_batch_size,
+                    io_completion_max_batches,
                 ));
 
                 // Signal that we are ready to start.
@@ -332,6 +511,7 @@ impl RuntimeBuilder {
         let is_stopping = Arc::new(AtomicBool::new(false));
 
         let client = RuntimeClient::new(
+            name,
             async_command_txs.into_boxed_slice(),
             async_io_wakers.into_boxed_slice(),
             tcp_dispatcher_command_tx,
@@ -342,8 +522,14 @@ impl RuntimeBuilder {
                 .collect(),
             sync_task_queues_by_processor,
             sync_priority_task_queues_by_processor,
+            fs_command_txs_by_processor
+                .into_iter()
+                .map(|(k, v)| (k, v.into_boxed_slice()))
+                .collect(),
+            fs_task_queues_by_processor,
             join_handles.into_boxed_slice(),
             Arc::clone(&is_stopping),
+            self.tuning,
         );
 
         // In most cases, the entrypoint thread is merely parked. However, for interoperability
@@ -381,6 +567,13 @@ impl RuntimeBuilder {
     }
 }
 
+/// The name of a runtime for which no name was set via `RuntimeBuilder::name()`.
+pub const DEFAULT_RUNTIME_NAME: &str = "folo";
+
+fn worker_span(runtime_name: &str) -> Span {
+    span!(Level::INFO, "folo_worker", runtime = runtime_name)
+}
+
 impl Default for RuntimeBuilder {
     fn default() -> Self {
         Self::new()
@@ -409,3 +602,32 @@ struct SyncAgentReady {}
 struct AgentStartArguments {
     runtime_client: RuntimeClient,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_completion_batch_settings_reach_driver() {
+        let config = RuntimeConfig::new().io_completion_max_batches(NonZeroUsize::new(3).unwrap());
+
+        let folo = RuntimeBuilder::new()
+            .config(config)
+            .io_completion_batch_size(NonZeroUsize::new(16).unwrap())
+            .build()
+            .unwrap();
+
+        let (tx, rx) = oneshot::channel();
+
+        folo.spawn_on_any(|| async move {
+            _ = tx.send(current_async_agent::with_io(|io| {
+                (io.batch_size(), io.max_batches().get())
+            }));
+        });
+
+        assert_eq!(rx.recv().unwrap(), (16, 3));
+
+        folo.stop();
+        folo.wait();
+    }
+}
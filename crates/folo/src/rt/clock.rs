@@ -0,0 +1,166 @@
+use crate::constants::POISONED_LOCK;
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use windows::Win32::System::SystemInformation::GetTickCount64;
+
+/// A source of time for the runtime. Timers, deadlines, timeouts, rate limits and the durations
+/// recorded in metrics all read the time from the clock of the current thread, which is the clock
+/// set via `RuntimeBuilder::clock()` on threads owned by a runtime and `SystemClock` elsewhere.
+///
+/// Replacing the system clock enables tests to control the passage of time (see `ManualClock`),
+/// so timeout-heavy logic can be tested without waiting in real time.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// The current time in milliseconds since an arbitrary fixed point in time. This is the cheap
+    /// low precision time source used for metrics and other measurements that do not need better
+    /// than millisecond precision.
+    fn now_millis(&self) -> u64;
+}
+
+/// The clock of the operating system. This is the default clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn now_millis(&self) -> u64 {
+        // SAFETY: Nothing unsafe about this, just an FFI call.
+        unsafe { GetTickCount64() }
+    }
+}
+
+/// A clock that only moves forward when told to via `advance()`, for deterministic tests.
+///
+/// Advancing the clock does not wake up async workers that are waiting for I/O. Timers that expire
+/// due to the clock advancing fire once the worker next checks its timers, which happens at least
+/// as often as the cross-thread poll interval (see `RuntimeTuning`).
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Creates a clock that starts at the current time of the system clock and stays there until
+    /// advanced.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward by the specified duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().expect(POISONED_LOCK) += duration;
+    }
+
+    /// How far the clock has been advanced since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().expect(POISONED_LOCK)
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.elapsed().as_millis().try_into().unwrap_or(u64::MAX)
+    }
+}
+
+/// The current time, according to the clock of the current thread. Use this instead of
+/// `Instant::now()` to calculate deadlines for the timers and timeouts of the runtime, so they
+/// follow the clock set via `RuntimeBuilder::clock()`.
+pub fn now() -> Instant {
+    CURRENT.with_borrow(|current| match current {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    })
+}
+
+/// The current time in milliseconds, according to the clock of the current thread. See
+/// `Clock::now_millis()`.
+pub(crate) fn now_millis() -> u64 {
+    CURRENT.with_borrow(|current| match current {
+        Some(clock) => clock.now_millis(),
+        None => SystemClock.now_millis(),
+    })
+}
+
+/// The number of whole milliseconds from now until the deadline, according to the clock of the
+/// current thread. Rounds up, so we never wake up before the deadline only to find nothing to do.
+pub(crate) fn milliseconds_until(deadline: Instant) -> u32 {
+    let remaining = deadline.saturating_duration_since(now());
+
+    remaining
+        .as_micros()
+        .div_ceil(1000)
+        .try_into()
+        .unwrap_or(u32::MAX)
+}
+
+/// Sets the clock of the current thread. Called by the runtime when starting its worker threads.
+pub(crate) fn set(clock: Arc<dyn Clock>) {
+    CURRENT.with_borrow_mut(|current| *current = Some(clock));
+}
+
+thread_local! {
+    // The clock of the current thread. Threads without a clock use the system clock, which we read
+    // directly to avoid the dynamic dispatch in the common case.
+    static CURRENT: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_only_moves_when_advanced() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now_millis(), 0);
+
+        clock.advance(Duration::from_millis(1500));
+
+        assert_eq!(clock.now() - start, Duration::from_millis(1500));
+        assert_eq!(clock.now_millis(), 1500);
+        assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn thread_uses_clock_once_set() {
+        let clock = Arc::new(ManualClock::new());
+        set(Arc::clone(&clock) as Arc<dyn Clock>);
+
+        let before = now();
+        clock.advance(Duration::from_secs(60));
+
+        assert_eq!(now() - before, Duration::from_secs(60));
+        assert_eq!(now_millis(), 60_000);
+
+        // Other threads are not affected.
+        std::thread::spawn(|| assert!(CURRENT.with_borrow(Option::is_none)))
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use crate::io::{
+    self, DEFAULT_BUFFER_POOL_MAX_BYTES, IO_DEQUEUE_BATCH_SIZE, IO_DEQUEUE_MAX_BATCHES,
+};
+use std::{env, num::NonZeroUsize, str::FromStr};
+
+/// Tunable parameters of a Folo runtime. Can be constructed in code, loaded from environment
+/// variables or both (code provides the defaults, the environment overrides them), and is given
+/// to `RuntimeBuilder::config()` or the `config_fn` option of the entrypoint macros.
+///
+/// # Environment variables
+///
+/// | Variable                            | Setting                         |
+/// |-------------------------------------|---------------------------------|
+/// | `FOLO_MAX_PROCESSORS`               | `max_processors()`              |
+/// | `FOLO_PIN_WORKERS`                  | `pin_workers()`                 |
+/// | `FOLO_SYNC_WORKERS_PER_PROCESSOR`   | `sync_workers_per_processor()`  |
+/// | `FOLO_FS_WORKERS_PER_PROCESSOR`     | `fs_workers_per_processor()`    |
+/// | `FOLO_METRICS_ENABLED`              | `metrics_enabled()`             |
+/// | `FOLO_BUFFER_POOL_MAX_BYTES`        | `buffer_pool_max_bytes()`       |
+/// | `FOLO_IO_COMPLETION_BATCH_SIZE`     | `io_completion_batch_size()`    |
+/// | `FOLO_IO_COMPLETION_MAX_BATCHES`    | `io_completion_max_batches()`   |
+///
+/// Boolean variables accept `true`/`false` and `1`/`0`.
+#[derive(Clone, Debug)]
+pub struct RuntimeConfig {
+    pub(crate) max_processors: Option<NonZeroUsize>,
+    pub(crate) pin_workers: bool,
+    pub(crate) sync_workers_per_processor: NonZeroUsize,
+    pub(crate) fs_workers_per_processor: NonZeroUsize,
+    pub(crate) metrics_enabled: bool,
+    pub(crate) buffer_pool_max_bytes: usize,
+    pub(crate) io_completion_batch_size: NonZeroUsize,
+    pub(crate) io_completion_max_batches: NonZeroUsize,
+}
+
+impl RuntimeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the configuration from environment variables, using the defaults for anything that
+    /// is not set.
+    pub fn from_env() -> io::Result<Self> {
+        Self::default().with_env_overrides()
+    }
+
+    /// Overrides any settings for which environment variables are set, keeping the rest as-is.
+    pub fn with_env_overrides(self) -> io::Result<Self> {
+        self.with_overrides_from(|name| env::var(name).ok())
+    }
+
+    fn with_overrides_from(mut self, lookup: impl Fn(&str) -> Option<String>) -> io::Result<Self> {
+        if let Some(value) = parse(&lookup, "FOLO_MAX_PROCESSORS")? {
+            self.max_processors = Some(value);
+        }
+
+        if let Some(value) = parse_bool(&lookup, "FOLO_PIN_WORKERS")? {
+            self.pin_workers = value;
+        }
+
+        if let Some(value) = parse(&lookup, "FOLO_SYNC_WORKERS_PER_PROCESSOR")? {
+            self.sync_workers_per_processor = value;
+        }
+
+        if let Some(value) = parse(&lookup, "FOLO_FS_WORKERS_PER_PROCESSOR")? {
+            self.fs_workers_per_processor = value;
+        }
+
+        if let Some(value) = parse_bool(&lookup, "FOLO_METRICS_ENABLED")? {
+            self.metrics_enabled = value;
+        }
+
+        if let Some(value) = parse(&lookup, "FOLO_BUFFER_POOL_MAX_BYTES")? {
+            self.buffer_pool_max_bytes = value;
+        }
+
+        if let Some(value) = parse(&lookup, "FOLO_IO_COMPLETION_BATCH_SIZE")? {
+            self.io_completion_batch_size = value;
+        }
+
+        if let Some(value) = parse(&lookup, "FOLO_IO_COMPLETION_MAX_BATCHES")? {
+            self.io_completion_max_batches = value;
+        }
+
+        Ok(self)
+    }
+
+    /// Limits the number of processors the runtime will use (one async worker is started per
+    /// processor). By default, all processors are used.
+    pub fn max_processors(mut self, value: NonZeroUsize) -> Self {
+        self.max_processors = Some(value);
+        self
+    }
+
+    /// Whether to pin each worker thread to its processor. Enabled by default. Disabling this may
+    /// be useful when sharing the machine with other processes that are pinned to processors.
+    pub fn pin_workers(mut self, value: bool) -> Self {
+        self.pin_workers = value;
+        self
+    }
+
+    /// How many synchronous worker threads to start per processor. These mostly sit blocked in
+    /// slow syscalls, so a higher number helps if many such calls are made concurrently.
+    pub fn sync_workers_per_processor(mut self, value: NonZeroUsize) -> Self {
+        self.sync_workers_per_processor = value;
+        self
+    }
+
+    /// How many worker threads to start per processor for file system operations that have no
+    /// asynchronous form (opening files, querying metadata, renaming and the like). These are kept
+    /// separate from the general synchronous workers, so a storm of slow disk metadata operations
+    /// does not starve other synchronous work (and vice versa).
+    pub fn fs_workers_per_processor(mut self, value: NonZeroUsize) -> Self {
+        self.fs_workers_per_processor = value;
+        self
+    }
+
+    /// Whether workers publish their metrics when they shut down (to the channel given to
+    /// `RuntimeBuilder::metrics_tx()`). Enabled by default.
+    pub fn metrics_enabled(mut self, value: bool) -> Self {
+        self.metrics_enabled = value;
+        self
+    }
+
+    /// The maximum number of bytes the buffer pool of each worker thread holds on to, counting
+    /// both the buffers in use and the idle buffers kept for reuse. When over the limit, idle
+    /// buffers are released, least recently used first. Buffers in use are never released, so the
+    /// limit may be exceeded while they are in use. Defaults to `DEFAULT_BUFFER_POOL_MAX_BYTES`.
+    pub fn buffer_pool_max_bytes(mut self, value: usize) -> Self {
+        self.buffer_pool_max_bytes = value;
+        self
+    }
+
+    /// The max number of I/O completions each async worker dequeues from the operating system in
+    /// one call. Smaller batches let the worker get back to running tasks sooner, which favors
+    /// latency, whereas larger batches make fewer calls into the operating system under heavy load,
+    /// which favors throughput. Defaults to `IO_DEQUEUE_BATCH_SIZE`.
+    ///
+    /// How long a worker waits for I/O when it has nothing else to do is governed by
+    /// `RuntimeTuning::idle_strategy()` and `RuntimeTuning::cross_thread_poll_interval()`.
+    pub fn io_completion_batch_size(mut self, value: NonZeroUsize) -> Self {
+        self.io_completion_batch_size = value;
+        self
+    }
+
+    /// The max number of batches of I/O completions each async worker dequeues before going back
+    /// to running tasks. Further batches are only dequeued while the previous batch came back
+    /// full and never wait for I/O to complete. Defaults to `IO_DEQUEUE_MAX_BATCHES`.
+    pub fn io_completion_max_batches(mut self, value: NonZeroUsize) -> Self {
+        self.io_completion_max_batches = value;
+        self
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            max_processors: None,
+            pin_workers: true,
+            sync_workers_per_processor: DEFAULT_SYNC_WORKERS_PER_PROCESSOR,
+            fs_workers_per_processor: DEFAULT_FS_WORKERS_PER_PROCESSOR,
+            metrics_enabled: true,
+            buffer_pool_max_bytes: DEFAULT_BUFFER_POOL_MAX_BYTES,
+            io_completion_batch_size: NonZeroUsize::new(IO_DEQUEUE_BATCH_SIZE).unwrap(),
+            io_completion_max_batches: NonZeroUsize::new(IO_DEQUEUE_MAX_BATCHES).unwrap(),
+        }
+    }
+}
+
+/// The thing with synchronous worker threads is that they often get blocked and spend time doing
+/// essentially nothing due to offloading blocking I/O onto these threads. Therefore, we spawn many
+/// of them to ensure that we can keep processing synchronous work when a large batch comes in.
+/// In the future we might replace this with a more dynamically sizing thread pool but for now the
+/// fixed size might be acceptable.
+const DEFAULT_SYNC_WORKERS_PER_PROCESSOR: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+/// File system operations are rarely issued in large concurrent batches, so the same small number
+/// of workers is a reasonable start. Services that walk large directory trees may want more.
+const DEFAULT_FS_WORKERS_PER_PROCESSOR: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+
+fn parse<T>(lookup: &impl Fn(&str) -> Option<String>, name: &str) -> io::Result<Option<T>>
+where
+    T: FromStr,
+{
+    let Some(value) = lookup(name) else {
+        return Ok(None);
+    };
+
+    value.trim().parse().map(Some).map_err(|_| {
+        io::Error::InvalidOptions(format!(
+            "environment variable {name} has invalid value {value}"
+        ))
+    })
+}
+
+fn parse_bool(lookup: &impl Fn(&str) -> Option<String>, name: &str) -> io::Result<Option<bool>> {
+    let Some(value) = lookup(name) else {
+        return Ok(None);
+    };
+
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" => Ok(Some(true)),
+        "false" | "0" => Ok(Some(false)),
+        _ => Err(io::Error::InvalidOptions(format!(
+            "environment variable {name} has invalid value {value}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup_in(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn overrides_only_what_is_set() {
+        let config = RuntimeConfig::new()
+            .max_processors(NonZeroUsize::new(4).unwrap())
+            .with_overrides_from(lookup_in(&[
+                ("FOLO_PIN_WORKERS", "false"),
+                ("FOLO_SYNC_WORKERS_PER_PROCESSOR", " 8 "),
+                ("FOLO_FS_WORKERS_PER_PROCESSOR", "3"),
+                ("FOLO_BUFFER_POOL_MAX_BYTES", "1048576"),
+                ("FOLO_IO_COMPLETION_BATCH_SIZE", "64"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.max_processors, NonZeroUsize::new(4));
+        assert!(!config.pin_workers);
+        assert_eq!(config.sync_workers_per_processor.get(), 8);
+        assert_eq!(config.fs_workers_per_processor.get(), 3);
+        assert_eq!(config.buffer_pool_max_bytes, 1024 * 1024);
+        assert_eq!(config.io_completion_batch_size.get(), 64);
+        assert_eq!(
+            config.io_completion_max_batches.get(),
+            IO_DEQUEUE_MAX_BATCHES
+        );
+        assert!(config.metrics_enabled);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(RuntimeConfig::new()
+            .with_overrides_from(lookup_in(&[("FOLO_MAX_PROCESSORS", "0")]))
+            .is_err());
+
+        assert!(RuntimeConfig::new()
+            .with_overrides_from(lookup_in(&[("FOLO_METRICS_ENABLED", "maybe")]))
+            .is_err());
+
+        assert!(RuntimeConfig::new()
+            .with_overrides_from(lookup_in(&[("FOLO_IO_COMPLETION_BATCH_SIZE", "0")]))
+            .is_err());
+    }
+}
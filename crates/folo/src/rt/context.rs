@@ -0,0 +1,123 @@
+//! Worker-local context: a typed map of values that any task on the current thread can reach,
+//! without having to pass them through every call. Useful for per-worker resources such as
+//! caches, buffer pools or database connections that are not shared between threads.
+//!
+//! Each thread has its own context, holding at most one value of each type. Populate the context
+//! of every async worker via `RuntimeBuilder::context_init()`, which runs on each async worker
+//! before it starts executing tasks. The context of an async worker is cleared when the worker
+//! shuts down, before it waits for pending I/O to complete, so values may own I/O primitives.
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::Rc,
+};
+
+/// Inserts a value into the context of the current thread, returning the value of the same type
+/// that it replaces, if any.
+pub fn insert<T: 'static>(value: T) -> Option<Rc<T>> {
+    let previous = CONTEXT.with_borrow_mut(|context| {
+        context.insert(TypeId::of::<T>(), Rc::new(value) as Rc<dyn Any>)
+    });
+
+    previous.map(downcast)
+}
+
+/// Gets the value of type T from the context of the current thread, if one has been inserted.
+///
+/// The value is shared, so use interior mutability (e.g. `RefCell`) for values that need to
+/// change after insertion.
+pub fn get<T: 'static>() -> Option<Rc<T>> {
+    CONTEXT.with_borrow(|context| context.get(&TypeId::of::<T>()).cloned().map(downcast))
+}
+
+/// Whether the context of the current thread holds a value of type T.
+pub fn contains<T: 'static>() -> bool {
+    CONTEXT.with_borrow(|context| context.contains_key(&TypeId::of::<T>()))
+}
+
+/// Removes the value of type T from the context of the current thread, returning it if there was
+/// one. The value is dropped once any clones obtained via `get()` are also dropped.
+pub fn remove<T: 'static>() -> Option<Rc<T>> {
+    let removed = CONTEXT.with_borrow_mut(|context| context.remove(&TypeId::of::<T>()));
+
+    removed.map(downcast)
+}
+
+/// Removes all values from the context of the current thread. Called by the async agent when it
+/// starts shutting down.
+pub(crate) fn clear() {
+    // We release the borrow before dropping, as the values may access the context when dropped.
+    let values = CONTEXT.with_borrow_mut(mem::take);
+    drop(values);
+}
+
+fn downcast<T: 'static>(value: Rc<dyn Any>) -> Rc<T> {
+    value
+        .downcast::<T>()
+        .expect("context values are always stored under the TypeId of their own type")
+}
+
+thread_local! {
+    static CONTEXT: RefCell<HashMap<TypeId, Rc<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn insert_get_remove() {
+        assert!(get::<u32>().is_none());
+        assert!(insert(5_u32).is_none());
+        assert!(insert("hello").is_none());
+
+        assert_eq!(*get::<u32>().unwrap(), 5);
+        assert_eq!(*get::<&str>().unwrap(), "hello");
+        assert!(contains::<u32>());
+
+        assert_eq!(*insert(6_u32).unwrap(), 5);
+        assert_eq!(*remove::<u32>().unwrap(), 6);
+
+        assert!(!contains::<u32>());
+        assert!(remove::<u32>().is_none());
+
+        clear();
+        assert!(!contains::<&str>());
+    }
+
+    #[test]
+    fn values_are_per_thread() {
+        insert(1_u64);
+
+        std::thread::spawn(|| assert!(get::<u64>().is_none()))
+            .join()
+            .unwrap();
+
+        assert_eq!(*get::<u64>().unwrap(), 1);
+        clear();
+    }
+
+    #[test]
+    fn clear_drops_values() {
+        struct SetOnDrop(Rc<Cell<bool>>);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.set(true);
+
+                // Dropping may touch the context again without panicking.
+                assert!(!contains::<SetOnDrop>());
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(false));
+        insert(SetOnDrop(Rc::clone(&dropped)));
+
+        clear();
+        assert!(dropped.get());
+    }
+}
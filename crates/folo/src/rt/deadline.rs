@@ -0,0 +1,139 @@
+use crate::{
+    constants::GENERAL_MILLISECONDS_BUCKETS,
+    metrics::{Event, EventBuilder},
+    rt::clock,
+};
+use pin_project::pin_project;
+use std::{cell::Cell, future::Future, pin::Pin, task, time::Instant};
+
+/// Attaches a soft deadline to a future, as a hint to the async worker that runs the task that
+/// polls it. When multiple tasks are ready to make progress, the worker polls the ones with
+/// deadlines first, earliest deadline first, followed by the rest in the order they became ready.
+///
+/// The deadline is only a scheduling hint - nothing happens to the future when the deadline
+/// passes. If the future completes after its deadline, this is counted in the
+/// `rt_async_deadlines_missed` metric and the delay recorded in the
+/// `rt_async_deadline_miss_millis` metric, which makes it easy to see whether request handlers
+/// are keeping within their latency budgets.
+///
+/// A task that awaits multiple futures with deadlines (e.g. nested ones) is scheduled according to
+/// the earliest deadline among those it polled most recently.
+pub fn with_deadline<F>(future: F, deadline: Instant) -> WithDeadline<F>
+where
+    F: Future,
+{
+    WithDeadline {
+        inner: future,
+        deadline,
+    }
+}
+
+/// Future returned by `with_deadline()`.
+#[pin_project]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithDeadline<F> {
+    #[pin]
+    inner: F,
+
+    deadline: Instant,
+}
+
+impl<F> Future for WithDeadline<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+
+        let result = this.inner.poll(cx);
+
+        if result.is_ready() {
+            let now = clock::now();
+
+            if now > *this.deadline {
+                DEADLINES_MISSED.with(Event::observe_unit);
+                DEADLINE_MISS.with(|x| x.observe_millis(now - *this.deadline));
+            }
+        } else {
+            // Only a future that is still pending has a say in when the task is polled next.
+            report_deadline(*this.deadline);
+        }
+
+        result
+    }
+}
+
+thread_local! {
+    // The earliest deadline reported by the futures polled as part of the current task poll.
+    static REPORTED_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+fn report_deadline(deadline: Instant) {
+    REPORTED_DEADLINE.with(|reported| {
+        let earliest = reported
+            .get()
+            .map_or(deadline, |existing| existing.min(deadline));
+        reported.set(Some(earliest));
+    });
+}
+
+/// Takes the earliest deadline reported since the last call. The async task engine calls this
+/// after polling each task to learn the deadline of the task.
+pub(crate) fn take_reported_deadline() -> Option<Instant> {
+    REPORTED_DEADLINE.with(Cell::take)
+}
+
+thread_local! {
+    static DEADLINES_MISSED: Event = EventBuilder::new()
+        .name("rt_async_deadlines_missed")
+        .build()
+        .unwrap();
+
+    static DEADLINE_MISS: Event = EventBuilder::new()
+        .name("rt_async_deadline_miss_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future, task::noop_waker_ref};
+    use std::{pin::pin, time::Duration};
+
+    #[test]
+    fn pending_future_reports_earliest_deadline() {
+        let now = Instant::now();
+        let early = now + Duration::from_secs(1);
+        let late = now + Duration::from_secs(2);
+
+        let mut outer = pin!(with_deadline(
+            with_deadline(future::pending::<()>(), early),
+            late
+        ));
+
+        _ = take_reported_deadline();
+
+        let cx = &mut task::Context::from_waker(noop_waker_ref());
+        assert!(outer.as_mut().poll(cx).is_pending());
+
+        assert_eq!(take_reported_deadline(), Some(early));
+
+        // Taking it clears it.
+        assert_eq!(take_reported_deadline(), None);
+    }
+
+    #[test]
+    fn completed_future_reports_nothing() {
+        _ = take_reported_deadline();
+
+        let result = block_on(with_deadline(future::ready(42), Instant::now()));
+
+        assert_eq!(result, 42);
+        assert_eq!(take_reported_deadline(), None);
+    }
+}
@@ -1,11 +1,19 @@
 //! Top-level free functions that can be called to manipulate the Folo runtime.
+//!
+//! These always target the runtime that owns the current thread. If multiple runtimes exist in the
+//! same process, use the methods of the `RuntimeClient` of a specific runtime to target it from
+//! anywhere.
 
 use super::SynchronousTaskType;
 use crate::rt::{
-    current_async_agent, current_runtime, ready_after_poll::ReadyAfterPoll, LocalJoinHandle,
-    RemoteJoinHandle,
+    clock, current_async_agent, current_runtime, ready_after_poll::ReadyAfterPoll,
+    LocalJoinHandle, RemoteJoinHandle, RuntimeClient, Sleep,
 };
-use std::future::Future;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tracing::{event, Level};
 
 /// Spawns a task to execute a future on the current async worker thread.
 ///
@@ -69,9 +77,82 @@ where
     current_runtime::with(|runtime| runtime.spawn_sync(task_type, f))
 }
 
+/// How long async workers wait for pending `defer_async()` cleanup to complete when the runtime is
+/// stopped, before they give up on it and shut down.
+pub const DEFERRED_CLEANUP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Runs an async cleanup future in the background on the current async worker thread. This is for
+/// `Drop` implementations of types that need to do async work to release their resources gracefully
+/// (e.g. notifying the peer before closing a connection), which cannot be awaited in `Drop` itself.
+///
+/// When the runtime is stopped, each async worker gives its pending deferred cleanup up to
+/// `DEFERRED_CLEANUP_GRACE_PERIOD` to complete before shutting down. Other tasks keep running
+/// during that time, as the cleanup may depend on them.
+///
+/// If the current thread is not an async worker thread or the worker has already started shutting
+/// down, the future is dropped without being polled. Types that use this must therefore still
+/// release their resources correctly (if less gracefully) when their cleanup future is dropped.
+pub fn defer_async<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    if current_async_agent::try_with(|agent| agent.defer(future)).is_none() {
+        event!(
+            Level::DEBUG,
+            "deferred cleanup dropped because the current thread is not an async worker thread"
+        );
+    }
+}
+
+/// Registers an async cleanup hook to execute when the runtime that owns the current thread is
+/// stopped. See `RuntimeClient::on_shutdown()`.
+///
+/// # Panics
+///
+/// Panics if the current thread is not owned by a Folo runtime.
+pub fn on_shutdown<FN, F>(hook_fn: FN)
+where
+    FN: FnOnce() -> F + Send + 'static,
+    F: Future<Output = ()> + 'static,
+{
+    current_runtime::with(|runtime| runtime.on_shutdown(hook_fn))
+}
+
+/// Returns the client of the runtime that owns the current thread, or `None` if the current thread
+/// is not owned by a Folo runtime. This is the runtime targeted by the other functions here.
+pub fn current_runtime_client() -> Option<RuntimeClient> {
+    current_runtime::try_get()
+}
+
 /// Yields control back to the async task runtime to allow other tasks to run.
 /// There is no guarantee that other tasks will run in any particular order.
 /// Even the same task that called this may be scheduled again immediately.
 pub fn yield_now() -> impl Future<Output = ()> {
     ReadyAfterPoll::default()
 }
+
+/// Returns a future that completes after the specified duration has elapsed.
+///
+/// The timer is serviced by the current async worker thread and has low precision - expect the
+/// future to complete some milliseconds after the requested duration.
+///
+/// # Panics
+///
+/// Polling the returned future panics if the current thread is not an async worker thread owned
+/// by a Folo runtime.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(clock::now() + duration)
+}
+
+/// Returns a future that completes once the specified deadline has been reached.
+///
+/// The timer is serviced by the current async worker thread and has low precision - expect the
+/// future to complete some milliseconds after the deadline.
+///
+/// # Panics
+///
+/// Polling the returned future panics if the current thread is not an async worker thread owned
+/// by a Folo runtime.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(deadline)
+}
@@ -3,23 +3,42 @@ use super::sync_agent::SyncAgentCommand;
 use super::{current_async_agent, ErasedSyncTask};
 use crate::constants::{self, GENERAL_MILLISECONDS_BUCKETS};
 use crate::io::IoWaker;
+use crate::mem::{self, Subsystem};
 use crate::metrics::{Event, EventBuilder};
-use crate::rt::{async_agent::AsyncAgentCommand, remote_task::RemoteTask, RemoteJoinHandle};
+use crate::rt::{
+    async_agent::AsyncAgentCommand, remote_task::RemoteTask, select2, sleep, RemoteJoinHandle,
+    RuntimeTuning,
+};
 use crate::util::LowPrecisionInstant;
 use core_affinity::CoreId;
 use crossbeam::channel;
 use crossbeam::queue::SegQueue;
+use futures::future::Either;
 use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use std::{cell::Cell, future::Future, sync::Mutex, thread};
+use tracing::{event, Level};
+
+/// How long each hook registered via `RuntimeClient::on_shutdown()` may run before the runtime
+/// gives up on it and moves on to the next one.
+pub const SHUTDOWN_HOOK_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// The multithreaded entry point for the Folo runtime, used for operations that affect more than
 /// the current thread.
 ///
+/// Any number of independent runtimes may exist in the same process, each with its own worker
+/// threads. A client always targets the runtime it was obtained from, regardless of which thread
+/// it is used on, so it can be used to schedule work on a specific runtime from anywhere.
+///
 /// This type is thread-safe.
 #[derive(Clone, Debug)]
 pub struct RuntimeClient {
+    name: Arc<str>,
+
     async_command_txs: Box<[channel::Sender<AsyncAgentCommand>]>,
     async_io_wakers: Box<[IoWaker]>,
 
@@ -38,16 +57,26 @@ pub struct RuntimeClient {
     sync_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
     sync_priority_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
 
+    // File system tasks have a dedicated pool of sync workers, split up the same way.
+    fs_command_txs_by_processor: HashMap<CoreId, Box<[channel::Sender<SyncAgentCommand>]>>,
+    fs_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
+
     // This is None if `.wait()` has already been called - the field can be consumed only once,
     // typically done by the runtime client provided to the entry point thread.
     join_handles: Arc<Mutex<Option<Box<[thread::JoinHandle<()>]>>>>,
 
     // This can be used by cleanup logic to detect that the runtime is not usable anymore.
     is_stopping: Arc<AtomicBool>,
+
+    // The tuning most recently handed to the workers, which they may not all have applied yet.
+    tuning: Arc<Mutex<RuntimeTuning>>,
+
+    shutdown_hooks: Arc<Mutex<ShutdownHooks>>,
 }
 
 impl RuntimeClient {
     pub(super) fn new(
+        name: Arc<str>,
         async_command_txs: Box<[channel::Sender<AsyncAgentCommand>]>,
         async_io_wakers: Box<[IoWaker]>,
         tcp_dispatcher_command_tx: channel::Sender<AsyncAgentCommand>,
@@ -55,10 +84,14 @@ impl RuntimeClient {
         sync_command_txs_by_processor: HashMap<CoreId, Box<[channel::Sender<SyncAgentCommand>]>>,
         sync_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
         sync_priority_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
+        fs_command_txs_by_processor: HashMap<CoreId, Box<[channel::Sender<SyncAgentCommand>]>>,
+        fs_task_queues_by_processor: HashMap<CoreId, Arc<SegQueue<ErasedSyncTask>>>,
         join_handles: Box<[thread::JoinHandle<()>]>,
         is_stopping: Arc<AtomicBool>,
+        tuning: RuntimeTuning,
     ) -> Self {
         Self {
+            name,
             async_command_txs,
             async_io_wakers,
             tcp_dispatcher_command_tx,
@@ -66,13 +99,40 @@ impl RuntimeClient {
             sync_command_txs_by_processor,
             sync_task_queues_by_processor,
             sync_priority_task_queues_by_processor,
+            fs_command_txs_by_processor,
+            fs_task_queues_by_processor,
             join_handles: Arc::new(Mutex::new(Some(join_handles))),
             is_stopping,
+            tuning: Arc::new(Mutex::new(tuning)),
+            shutdown_hooks: Arc::new(Mutex::new(ShutdownHooks::default())),
         }
     }
 
+    /// The name of the runtime, as set via `RuntimeBuilder::name()`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Spawns a task to execute a future on any worker thread, creating the future via closure.
     pub fn spawn_on_any<FN, F, R>(&self, future_fn: FN) -> RemoteJoinHandle<R>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        let worker_index = next_async_worker(self.async_command_txs.len());
+
+        self.spawn_on_worker(worker_index, future_fn)
+    }
+
+    /// Spawns a task to execute a future on a specific worker thread (see
+    /// `AsyncAgent::worker_index()`), creating the future via closure. Used for work that depends
+    /// on resources bound to the I/O driver of that worker.
+    pub(crate) fn spawn_on_worker<FN, F, R>(
+        &self,
+        worker_index: usize,
+        future_fn: FN,
+    ) -> RemoteJoinHandle<R>
     where
         FN: FnOnce() -> F + Send + 'static,
         F: Future<Output = R> + 'static,
@@ -94,11 +154,11 @@ impl RuntimeClient {
             join_handle.await
         };
 
+        let _subsystem = mem::enter_subsystem(Subsystem::Tasks);
+
         let task = RemoteTask::new(thread_safe_wrapper_future);
         let join_handle = task.join_handle(self.current_thread_io_waker());
 
-        let worker_index = next_async_worker(self.async_command_txs.len());
-
         // We ignore the return value because it is theoretically possible that something is trying
         // to schedule new work when we are in the middle of a shutdown process.
         _ = self.async_command_txs[worker_index].send(AsyncAgentCommand::EnqueueTask {
@@ -135,6 +195,8 @@ impl RuntimeClient {
             join_handle.await
         };
 
+        let _subsystem = mem::enter_subsystem(Subsystem::Tasks);
+
         let task = RemoteTask::new(thread_safe_wrapper_future);
         let join_handle = task.join_handle(self.current_thread_io_waker());
 
@@ -192,6 +254,8 @@ impl RuntimeClient {
                 join_handle.await
             };
 
+            let _subsystem = mem::enter_subsystem(Subsystem::Tasks);
+
             let task = RemoteTask::new(thread_safe_wrapper_future);
             let join_handle = task.join_handle(self.current_thread_io_waker());
 
@@ -235,6 +299,9 @@ impl RuntimeClient {
                 SynchronousTaskType::HighPrioritySyscall => {
                     SYNC_SPAWN_DELAY_HIGH_PRIORITY.with(|x| x.observe_millis(started.elapsed()))
                 }
+                SynchronousTaskType::FileSystem => {
+                    SYNC_SPAWN_DELAY_FS.with(|x| x.observe_millis(started.elapsed()))
+                }
                 _ => unreachable!(),
             };
 
@@ -255,10 +322,18 @@ impl RuntimeClient {
             SynchronousTaskType::HighPrioritySyscall => {
                 _ = self.sync_priority_task_queues_by_processor[&processor_id].push(Box::new(task));
             }
+            SynchronousTaskType::FileSystem => {
+                _ = self.fs_task_queues_by_processor[&processor_id].push(Box::new(task));
+            }
             _ => unreachable!(),
         }
 
-        for tx in &self.sync_command_txs_by_processor[&processor_id] {
+        let command_txs = match task_type {
+            SynchronousTaskType::FileSystem => &self.fs_command_txs_by_processor,
+            _ => &self.sync_command_txs_by_processor,
+        };
+
+        for tx in &command_txs[&processor_id] {
             // We ignore the return value because it is theoretically possible that something is trying
             // to schedule new work when we are in the middle of a shutdown process.
             _ = tx.send(SyncAgentCommand::CheckForTasks);
@@ -267,10 +342,111 @@ impl RuntimeClient {
         RemoteJoinHandle::new(result_box_rx, self.current_thread_io_waker())
     }
 
+    /// The runtime parameters most recently set via `RuntimeBuilder::tuning()` or `set_tuning()`.
+    pub fn tuning(&self) -> RuntimeTuning {
+        self.tuning.lock().expect(constants::POISONED_LOCK).clone()
+    }
+
+    /// Adjusts the runtime parameters without restarting the runtime. This returns immediately -
+    /// each async worker applies the new values between two iterations of its work loop.
+    pub fn set_tuning(&self, tuning: RuntimeTuning) {
+        // We hold the lock while sending, so concurrent calls reach every worker in the same order
+        // and they all end up with whatever was set last.
+        let mut current = self.tuning.lock().expect(constants::POISONED_LOCK);
+        *current = tuning;
+
+        for (tx, io_waker) in self
+            .async_command_txs
+            .iter()
+            .zip(self.async_io_wakers.iter())
+            .chain([(
+                &self.tcp_dispatcher_command_tx,
+                &self.tcp_dispatcher_io_waker,
+            )])
+        {
+            // We ignore the return value because if the worker has already stopped, the channel
+            // may be closed in which case the send may simply fail.
+            _ = tx.send(AsyncAgentCommand::ApplyTuning {
+                tuning: current.clone(),
+            });
+
+            // Wake up the agent if it might be sleeping and waiting for I/O.
+            io_waker.wake();
+        }
+    }
+
+    /// Registers an async cleanup hook to execute when the runtime is stopped, before the workers
+    /// start shutting down. This is the place to flush metrics, close connection pools, notify
+    /// peers and the like, while the runtime is still fully functional.
+    ///
+    /// Hooks are executed one at a time on an async worker, in the reverse order of registration,
+    /// so resources are released in the reverse order of their creation. Each hook gets up to
+    /// `SHUTDOWN_HOOK_TIMEOUT` to complete, after which it is dropped and the next hook executed.
+    ///
+    /// Hooks registered after `stop()` has been called are never executed.
+    pub fn on_shutdown<FN, F>(&self, hook_fn: FN)
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = ()> + 'static,
+    {
+        let mut shutdown_hooks = self.shutdown_hooks.lock().expect(constants::POISONED_LOCK);
+
+        if shutdown_hooks.stopping {
+            event!(
+                Level::DEBUG,
+                "shutdown hook ignored because the runtime is already stopping"
+            );
+            return;
+        }
+
+        shutdown_hooks
+            .hooks
+            .push(Box::new(move || Box::pin(hook_fn())));
+    }
+
     /// Commands the runtime to stop processing tasks and shut down. Safe to call multiple times.
     ///
+    /// If any hooks have been registered via `on_shutdown()`, these are executed first and the
+    /// workers only start shutting down once the hooks are done.
+    ///
     /// This returns immediately. To wait for the runtime to stop, use `wait()`.
     pub fn stop(&self) {
+        let hooks = {
+            let mut shutdown_hooks = self.shutdown_hooks.lock().expect(constants::POISONED_LOCK);
+
+            // If the hooks are still executing, the workers will be terminated once they are done.
+            if shutdown_hooks.stopping {
+                return;
+            }
+
+            shutdown_hooks.stopping = true;
+            std::mem::take(&mut shutdown_hooks.hooks)
+        };
+
+        if hooks.is_empty() {
+            self.terminate();
+            return;
+        }
+
+        let client = self.clone();
+
+        // The join handle is not needed - the runtime stopping is the signal that we are done.
+        _ = self.spawn_on_any(move || async move {
+            for hook in hooks.into_iter().rev() {
+                if let Either::Right(()) = select2(hook(), sleep(SHUTDOWN_HOOK_TIMEOUT)).await {
+                    event!(
+                        Level::WARN,
+                        "shutdown hook did not complete within {SHUTDOWN_HOOK_TIMEOUT:?}"
+                    );
+                }
+            }
+
+            client.terminate();
+        });
+    }
+
+    // Commands all the workers to shut down.
+    fn terminate(&self) {
         for tx in &self.async_command_txs {
             // We ignore the return value because if the worker has already stopped, the channel
             // may be closed in which case the send may simply fail.
@@ -283,7 +459,11 @@ impl RuntimeClient {
             .tcp_dispatcher_command_tx
             .send(AsyncAgentCommand::Terminate);
 
-        for txs in self.sync_command_txs_by_processor.values() {
+        for txs in self
+            .sync_command_txs_by_processor
+            .values()
+            .chain(self.fs_command_txs_by_processor.values())
+        {
             for tx in txs {
                 // We ignore the return value because if the worker has already stopped, the channel
                 // may be closed in which case the send may simply fail.
@@ -345,6 +525,26 @@ impl RuntimeClient {
     }
 }
 
+type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+#[derive(Default)]
+struct ShutdownHooks {
+    // In order of registration.
+    hooks: Vec<ShutdownHook>,
+
+    // Set once `stop()` has been called, after which the hooks have been taken for execution.
+    stopping: bool,
+}
+
+impl Debug for ShutdownHooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownHooks")
+            .field("hooks", &self.hooks.len())
+            .field("stopping", &self.stopping)
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SynchronousTaskType {
     /// Some syscall that the runtime needs to perform synchronously and which may take an unknown
@@ -353,6 +553,12 @@ pub enum SynchronousTaskType {
     /// you may have things like antivirus drivers that freeze your syscall for many seconds.
     Syscall,
 
+    /// A file system operation that has no asynchronous form (e.g. opening a file, querying
+    /// metadata, renaming). These execute on a dedicated pool of sync workers (sized via
+    /// `RuntimeConfig::fs_workers_per_processor()`), so a burst of slow disk metadata operations
+    /// cannot exhaust the workers available for other synchronous work.
+    FileSystem,
+
     /// A high-priority syscall whose execution we benefit from in some way. For example, this may
     /// release resources, thereby improving our overall efficiency. The high-priority tasks are
     /// always executed, even if the runtime is shutting down. This is because they may be cleanup
@@ -389,6 +595,12 @@ thread_local! {
         .build()
         .unwrap();
 
+    static SYNC_SPAWN_DELAY_FS: Event = EventBuilder::new()
+        .name("rt_sync_spawn_delay_fs_millis")
+        .buckets(GENERAL_MILLISECONDS_BUCKETS)
+        .build()
+        .unwrap();
+
     static SYNC_SPAWN_DELAY_LOW_PRIORITY: Event = EventBuilder::new()
         .name("rt_sync_spawn_delay_low_priority_millis")
         .buckets(GENERAL_MILLISECONDS_BUCKETS)
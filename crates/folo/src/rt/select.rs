@@ -0,0 +1,198 @@
+use futures::future::Either;
+use pin_project::pin_project;
+use std::{future::Future, pin::Pin, task};
+
+/// Waits for the first of two futures to complete and returns its result, dropping the other
+/// future before returning.
+///
+/// Unlike `futures::select!`, which polls borrowed futures and leaves the losers pending (with any
+/// I/O operations they started still in flight until the caller gets around to dropping them),
+/// this takes ownership of both futures and drops the loser as soon as the winner is known. Folo
+/// I/O futures cancel their pending operations when dropped, so the loser's operations are
+/// canceled right away and do not hold on to their buffers and sockets.
+///
+/// Note that canceling an operation is not the same as it never happening - the loser may have
+/// already transferred data that is now lost (e.g. a `receive()` that completed in the operating
+/// system in the same instant as the winner). Do not race operations whose partial results you
+/// cannot afford to lose; spawn them as separate tasks and race their join handles instead.
+///
+/// The futures are polled in order, so if both are ready at the same time, the first one wins.
+pub fn select2<A, B>(a: A, b: B) -> Select2<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    Select2 {
+        a: Some(a),
+        b: Some(b),
+    }
+}
+
+/// Waits for the first of any number of futures of the same type to complete and returns its
+/// result and its index in the input, dropping the other futures before returning. See `select2()`
+/// for how this interacts with the cancellation of I/O operations.
+///
+/// The futures are polled in order, so if multiple are ready at the same time, the first one wins.
+///
+/// # Panics
+///
+/// Polling the returned future panics if `futures` is empty.
+pub fn race<I>(futures: I) -> Race<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    Race {
+        futures: futures.into_iter().map(Box::pin).collect(),
+    }
+}
+
+/// Future returned by `select2()`.
+#[pin_project]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select2<A, B> {
+    // Both are cleared once either completes.
+    #[pin]
+    a: Option<A>,
+    #[pin]
+    b: Option<B>,
+}
+
+impl<A, B> Future for Select2<A, B>
+where
+    A: Future,
+    B: Future,
+{
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        let a = this
+            .a
+            .as_mut()
+            .as_pin_mut()
+            .expect("Select2 polled after completion");
+
+        let result = if let task::Poll::Ready(result) = a.poll(cx) {
+            Either::Left(result)
+        } else {
+            let b = this
+                .b
+                .as_mut()
+                .as_pin_mut()
+                .expect("Select2 polled after completion");
+
+            match b.poll(cx) {
+                task::Poll::Ready(result) => Either::Right(result),
+                task::Poll::Pending => return task::Poll::Pending,
+            }
+        };
+
+        this.a.set(None);
+        this.b.set(None);
+
+        task::Poll::Ready(result)
+    }
+}
+
+/// Future returned by `race()`.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Race<F> {
+    // Cleared once any completes.
+    futures: Vec<Pin<Box<F>>>,
+}
+
+impl<F> Future for Race<F>
+where
+    F: Future,
+{
+    type Output = (F::Output, usize);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        assert!(
+            !self.futures.is_empty(),
+            "Race polled after completion or without any futures"
+        );
+
+        for (index, future) in self.futures.iter_mut().enumerate() {
+            if let task::Poll::Ready(result) = future.as_mut().poll(cx) {
+                self.futures.clear();
+                return task::Poll::Ready((result, index));
+            }
+        }
+
+        task::Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future};
+    use std::{cell::Cell, rc::Rc};
+
+    /// Never completes and counts how many instances have been dropped.
+    struct Pending(Rc<Cell<usize>>);
+
+    impl Future for Pending {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+            task::Poll::Pending
+        }
+    }
+
+    impl Drop for Pending {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn select2_returns_winner_and_drops_loser() {
+        let dropped = Rc::new(Cell::new(0));
+
+        let mut select = Box::pin(select2(Pending(Rc::clone(&dropped)), future::ready("b")));
+
+        let result = block_on(select.as_mut());
+
+        assert!(matches!(result, Either::Right("b")));
+
+        // The loser is dropped before the result is returned, not when the Select2 is dropped.
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn select2_prefers_first_when_both_ready() {
+        let result = block_on(select2(future::ready(1), future::ready(2)));
+
+        assert!(matches!(result, Either::Left(1)));
+    }
+
+    #[test]
+    fn race_returns_winner_index_and_drops_losers() {
+        let dropped = Rc::new(Cell::new(0));
+
+        let futures: Vec<future::Either<Pending, future::Ready<u32>>> = vec![
+            future::Either::Left(Pending(Rc::clone(&dropped))),
+            future::Either::Right(future::ready(42)),
+            future::Either::Left(Pending(Rc::clone(&dropped))),
+        ];
+
+        let mut race = Box::pin(race(futures));
+
+        let result = block_on(race.as_mut());
+
+        assert_eq!(result, (42, 1));
+        assert_eq!(dropped.get(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn race_without_futures_panics() {
+        block_on(race(Vec::<future::Ready<()>>::new()));
+    }
+}
@@ -1,6 +1,7 @@
 use super::ErasedSyncTask;
 use crate::{
     constants::GENERAL_MILLISECONDS_BUCKETS,
+    mem,
     metrics::{self, Event, EventBuilder, Magnitude, ReportPage},
 };
 use crossbeam::{channel, queue::SegQueue};
@@ -9,6 +10,8 @@ use tracing::{event, Level};
 
 #[derive(Debug)]
 pub struct SyncAgent {
+    kind: SyncWorkerKind,
+
     command_rx: channel::Receiver<SyncAgentCommand>,
     metrics_tx: Option<channel::Sender<ReportPage>>,
 
@@ -33,12 +36,14 @@ pub struct SyncAgent {
 
 impl SyncAgent {
     pub fn new(
+        kind: SyncWorkerKind,
         command_rx: channel::Receiver<SyncAgentCommand>,
         metrics_tx: Option<channel::Sender<ReportPage>>,
         task_queue: Arc<SegQueue<ErasedSyncTask>>,
         priority_task_queue: Arc<SegQueue<ErasedSyncTask>>,
     ) -> Self {
         Self {
+            kind,
             command_rx,
             metrics_tx,
             task_queue,
@@ -90,12 +95,19 @@ impl SyncAgent {
         );
 
         if let Some(tx) = &self.metrics_tx {
+            mem::publish_metrics();
             _ = tx.send(metrics::report_page());
         }
     }
 
     fn next_task(&self) -> Option<ErasedSyncTask> {
-        LOW_PRIORITY_QUEUE_SIZE.with(|x| x.observe(self.task_queue.len() as i64));
+        let queue_size = self.task_queue.len() as i64;
+
+        match self.kind {
+            SyncWorkerKind::General => LOW_PRIORITY_QUEUE_SIZE.with(|x| x.observe(queue_size)),
+            SyncWorkerKind::FileSystem => FS_QUEUE_SIZE.with(|x| x.observe(queue_size)),
+        }
+
         HIGH_PRIORITY_QUEUE_SIZE.with(|x| x.observe(self.priority_task_queue.len() as i64));
 
         self.priority_task_queue
@@ -104,6 +116,25 @@ impl SyncAgent {
     }
 }
 
+/// The pool a sync worker belongs to, determining which task queue it takes tasks from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncWorkerKind {
+    /// Executes `SynchronousTaskType::Syscall` tasks.
+    General,
+
+    /// Executes `SynchronousTaskType::FileSystem` tasks.
+    FileSystem,
+}
+
+impl SyncWorkerKind {
+    pub fn thread_name_infix(&self) -> &'static str {
+        match self {
+            Self::General => "sync",
+            Self::FileSystem => "fs",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SyncAgentCommand {
     /// Indicates that there may be new tasks available in the task queue. This command may be sent
@@ -148,4 +179,9 @@ thread_local! {
         .build()
         .unwrap();
 
+    static FS_QUEUE_SIZE: Event = EventBuilder::new()
+        .name("rt_sync_fs_queue_size")
+        .buckets(QUEUE_SIZE_BUCKETS)
+        .build()
+        .unwrap();
 }
@@ -0,0 +1,309 @@
+use crate::rt::{clock, current_async_agent};
+use negative_impl::negative_impl;
+use std::{
+    cell::{Cell, RefCell},
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{self, Waker},
+    time::Instant,
+};
+
+/// The timers registered on a single async worker thread. The async agent fires expired timers
+/// once per cycle and limits its I/O sleep so that it wakes up in time for the next deadline.
+///
+/// Timers are not removed right away when the awaiting future is dropped, as that would require
+/// a search. Instead, we count the abandoned timers and compact the queue once they make up half
+/// of it, which keeps both registration and cancellation cheap.
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    queue: BinaryHeap<Reverse<TimerEntry>>,
+
+    // How many of the entries in the queue belong to timers that have been abandoned.
+    abandoned: usize,
+
+    // Tie-breaker to keep the order of timers with equal deadlines stable.
+    next_sequence: u64,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(&mut self, deadline: Instant) -> Rc<TimerState> {
+        let state = Rc::new(TimerState::default());
+
+        self.queue.push(Reverse(TimerEntry {
+            deadline,
+            sequence: self.next_sequence,
+            state: Rc::clone(&state),
+        }));
+
+        self.next_sequence += 1;
+
+        state
+    }
+
+    /// Marks a timer that has not yet fired as abandoned, so it no longer needs to fire.
+    fn abandon(&mut self, state: &TimerState) {
+        if state.fired.get() || state.abandoned.replace(true) {
+            return;
+        }
+
+        self.abandoned += 1;
+
+        if self.abandoned * 2 >= self.queue.len() {
+            self.queue.retain(|entry| !entry.0.state.abandoned.get());
+            self.abandoned = 0;
+        }
+    }
+
+    /// The deadline of the earliest timer, if any timers are registered.
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        // Abandoned timers have no need to wake up the worker.
+        while self
+            .queue
+            .peek()
+            .is_some_and(|entry| entry.0.state.abandoned.get())
+        {
+            self.queue.pop();
+            self.abandoned -= 1;
+        }
+
+        self.queue.peek().map(|entry| entry.0.deadline)
+    }
+
+    /// Marks all timers with a deadline at or before `now` as fired and returns the wakers of the
+    /// tasks awaiting them. The caller is expected to wake them after releasing the timers.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+
+        while let Some(entry) = self.queue.peek() {
+            if entry.0.deadline > now {
+                break;
+            }
+
+            let entry = self.queue.pop().expect("we just peeked at it").0;
+
+            if entry.state.abandoned.get() {
+                self.abandoned -= 1;
+                continue;
+            }
+
+            entry.state.fired.set(true);
+
+            if let Some(waker) = entry.state.waker.take() {
+                wakers.push(waker);
+            }
+        }
+
+        wakers
+    }
+
+    /// Drops all registered timers (and the wakers they hold) without firing them. Used when the
+    /// worker is shutting down, as the wakers may otherwise keep tasks from becoming inert.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.abandoned = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[negative_impl]
+impl !Send for Timers {}
+#[negative_impl]
+impl !Sync for Timers {}
+
+#[derive(Debug)]
+struct TimerEntry {
+    deadline: Instant,
+    sequence: u64,
+    state: Rc<TimerState>,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline
+            .cmp(&other.deadline)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+#[derive(Debug, Default)]
+struct TimerState {
+    fired: Cell<bool>,
+    abandoned: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+/// A future that completes once a deadline has been reached. Created by `folo::rt::sleep()` or
+/// `folo::rt::sleep_until()`.
+///
+/// The timer has the same low precision as the async worker loop (typically around 10-20 ms), so
+/// it may complete somewhat later than the deadline but never earlier.
+///
+/// # Panics
+///
+/// Polling panics if the current thread is not an async worker thread owned by the Folo runtime.
+#[derive(Debug)]
+pub struct Sleep {
+    deadline: Instant,
+
+    // Registered lazily on first poll, so that creating a Sleep is free.
+    state: Option<Rc<TimerState>>,
+}
+
+impl Sleep {
+    pub(crate) fn new(deadline: Instant) -> Self {
+        Self {
+            deadline,
+            state: None,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        if let Some(state) = &self.state {
+            if state.fired.get() {
+                return task::Poll::Ready(());
+            }
+
+            *state.waker.borrow_mut() = Some(cx.waker().clone());
+            return task::Poll::Pending;
+        }
+
+        if clock::now() >= self.deadline {
+            return task::Poll::Ready(());
+        }
+
+        let deadline = self.deadline;
+        let state =
+            current_async_agent::with(|agent| agent.timers().borrow_mut().register(deadline));
+        *state.waker.borrow_mut() = Some(cx.waker().clone());
+
+        self.state = Some(state);
+        task::Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        // We do not want the timer to keep our task's waker alive until the deadline, so we
+        // release the waker immediately even if we cannot remove the timer right now.
+        state.waker.take();
+
+        // If the worker is shutting down, the timers are about to be cleared anyway.
+        current_async_agent::try_with(|agent| {
+            if let Ok(mut timers) = agent.timers().try_borrow_mut() {
+                timers.abandon(state);
+            }
+        });
+    }
+}
+
+#[negative_impl]
+impl !Send for Sleep {}
+#[negative_impl]
+impl !Sync for Sleep {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+    use std::time::Duration;
+
+    #[test]
+    fn expires_in_deadline_order() {
+        let mut timers = Timers::new();
+        let now = Instant::now();
+
+        let late = timers.register(now + Duration::from_secs(10));
+        let early = timers.register(now + Duration::from_secs(1));
+        *early.waker.borrow_mut() = Some(noop_waker());
+
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(1)));
+
+        let woken = timers.take_expired(now + Duration::from_secs(5));
+        assert_eq!(woken.len(), 1);
+        assert!(early.fired.get());
+        assert!(!late.fired.get());
+
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(10)));
+
+        // No waker registered for this one, so nothing to wake but it still fires.
+        let woken = timers.take_expired(now + Duration::from_secs(10));
+        assert!(woken.is_empty());
+        assert!(late.fired.get());
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn abandoned_timers_are_removed() {
+        let mut timers = Timers::new();
+        let now = Instant::now();
+
+        let early = timers.register(now + Duration::from_secs(1));
+        let middle = timers.register(now + Duration::from_secs(5));
+        let late = timers.register(now + Duration::from_secs(10));
+
+        // One of three is not yet worth compacting for but no longer determines the next deadline.
+        timers.abandon(&early);
+        assert_eq!(Rc::strong_count(&early), 2);
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(5)));
+        assert_eq!(Rc::strong_count(&early), 1);
+
+        // One of two is enough to compact the queue.
+        timers.abandon(&late);
+        assert_eq!(Rc::strong_count(&late), 1);
+
+        let woken = timers.take_expired(now + Duration::from_secs(10));
+        assert!(woken.is_empty());
+        assert!(middle.fired.get());
+        assert!(!early.fired.get());
+        assert!(!late.fired.get());
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn clear_drops_wakers() {
+        let mut timers = Timers::new();
+        let state = timers.register(Instant::now());
+        *state.waker.borrow_mut() = Some(noop_waker());
+
+        timers.clear();
+
+        assert!(timers.is_empty());
+        assert_eq!(Rc::strong_count(&state), 1);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{cell::RefCell, time::Duration};
+
+/// Runtime parameters that can be adjusted while the runtime is running, without a restart. Set
+/// the initial values via `RuntimeBuilder::tuning()` and adjust them later via
+/// `RuntimeClient::set_tuning()`.
+///
+/// New values are applied by each async worker between two iterations of its work loop, so tasks
+/// never observe a change in the middle of being polled. Different workers may apply the change
+/// at slightly different times.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuntimeTuning {
+    pub(crate) idle_strategy: IdleStrategy,
+    pub(crate) cross_thread_poll_interval: Duration,
+    pub(crate) max_pooled_sockets: usize,
+}
+
+impl RuntimeTuning {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What an async worker does when it has no work to do. Defaults to `IdleStrategy::Sleep`.
+    pub fn idle_strategy(mut self, value: IdleStrategy) -> Self {
+        self.idle_strategy = value;
+        self
+    }
+
+    /// How long a sleeping async worker waits for I/O before checking for work arriving from
+    /// other threads (which does not always wake it up). Shorter intervals reduce the worst-case
+    /// latency of cross-thread work at the cost of more frequent wakeups. Has millisecond
+    /// precision. Defaults to 10 milliseconds.
+    pub fn cross_thread_poll_interval(mut self, value: Duration) -> Self {
+        self.cross_thread_poll_interval = value;
+        self
+    }
+
+    /// The maximum number of idle sockets each async worker keeps per address family for reuse
+    /// by new outbound connections, and for each `TcpServer` that reuses sockets for reuse by new
+    /// accepted connections. Lowering the limit does not release sockets already in the pool - they
+    /// are consumed over time by new connections. Defaults to `MAX_POOLED_SOCKETS`.
+    pub fn max_pooled_sockets(mut self, value: usize) -> Self {
+        self.max_pooled_sockets = value;
+        self
+    }
+
+    /// The cross-thread poll interval in whole milliseconds, as used for waiting on I/O.
+    pub(crate) fn cross_thread_poll_interval_ms(&self) -> u32 {
+        self.cross_thread_poll_interval
+            .as_millis()
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+}
+
+impl Default for RuntimeTuning {
+    fn default() -> Self {
+        Self {
+            idle_strategy: IdleStrategy::Sleep,
+            cross_thread_poll_interval: DEFAULT_CROSS_THREAD_POLL_INTERVAL,
+            max_pooled_sockets: crate::net::MAX_POOLED_SOCKETS,
+        }
+    }
+}
+
+/// What an async worker does when it has no work to do.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IdleStrategy {
+    /// Sleep until I/O completes, a timer expires or the cross-thread poll interval elapses.
+    Sleep,
+
+    /// Keep polling for I/O completions without ever sleeping. This minimizes latency at the cost
+    /// of keeping every worker's processor fully busy, so it is only suitable for machines that
+    /// are dedicated to the service.
+    Spin,
+}
+
+/// We do not have cross-thread real time signals and use polling to check for arriving work,
+/// which sets our maximum sleep time.
+const DEFAULT_CROSS_THREAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+thread_local! {
+    // The tuning currently applied on this thread. Threads that are not async workers just use
+    // the defaults.
+    static CURRENT: RefCell<RuntimeTuning> = RefCell::new(RuntimeTuning::default());
+}
+
+/// Applies new tuning on the current thread. Called by the async agent at loop boundaries.
+pub(crate) fn apply(tuning: RuntimeTuning) {
+    CURRENT.with_borrow_mut(|current| *current = tuning);
+}
+
+/// Calls `f` with the tuning currently applied on this thread.
+pub(crate) fn with_current<R>(f: impl FnOnce(&RuntimeTuning) -> R) -> R {
+    CURRENT.with_borrow(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_replaces_current() {
+        let tuning = RuntimeTuning::new()
+            .idle_strategy(IdleStrategy::Spin)
+            .cross_thread_poll_interval(Duration::from_micros(2500))
+            .max_pooled_sockets(3);
+
+        apply(tuning.clone());
+
+        assert_eq!(with_current(Clone::clone), tuning);
+        assert_eq!(
+            with_current(RuntimeTuning::cross_thread_poll_interval_ms),
+            2
+        );
+
+        apply(RuntimeTuning::default());
+
+        assert_eq!(with_current(|x| x.idle_strategy), IdleStrategy::Sleep);
+    }
+}
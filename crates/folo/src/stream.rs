@@ -0,0 +1,16 @@
+//! Stream utilities for pipelines running on the Folo runtime (e.g. processing incoming
+//! connections or directory entries).
+//!
+//! Folo uses the `Stream` trait of the `futures` crate, re-exported here together with `StreamExt`,
+//! which provides the general-purpose combinators such as `map()` and `chunks()`. The combinators
+//! in `FoloStreamExt` complement those with ones that rely on the Folo runtime (spawning local
+//! tasks, timers) and are therefore only usable on async worker threads.
+
+mod buffer_unordered_local;
+mod folo_stream_ext;
+mod timeout_per_item;
+
+pub use buffer_unordered_local::*;
+pub use folo_stream_ext::*;
+pub use futures::stream::{Stream, StreamExt};
+pub use timeout_per_item::*;
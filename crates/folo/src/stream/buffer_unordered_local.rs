@@ -0,0 +1,77 @@
+use crate::rt::{spawn, LocalJoinHandle};
+use futures::stream::{Fuse, FuturesUnordered, Stream, StreamExt};
+use pin_project::pin_project;
+use std::{future::Future, pin::Pin, task};
+
+/// Stream returned by `FoloStreamExt::buffer_unordered_local()`.
+#[pin_project]
+#[must_use = "streams do nothing unless polled"]
+pub struct BufferUnorderedLocal<S>
+where
+    S: Stream,
+    S::Item: Future,
+{
+    #[pin]
+    stream: Fuse<S>,
+
+    in_flight: FuturesUnordered<LocalJoinHandle<<S::Item as Future>::Output>>,
+
+    limit: usize,
+}
+
+impl<S> BufferUnorderedLocal<S>
+where
+    S: Stream,
+    S::Item: Future + 'static,
+    <S::Item as Future>::Output: 'static,
+{
+    pub(super) fn new(stream: S, limit: usize) -> Self {
+        assert!(limit > 0, "limit must be greater than zero");
+
+        Self {
+            stream: stream.fuse(),
+            in_flight: FuturesUnordered::new(),
+            limit,
+        }
+    }
+}
+
+impl<S> Stream for BufferUnorderedLocal<S>
+where
+    S: Stream,
+    S::Item: Future + 'static,
+    <S::Item as Future>::Output: 'static,
+{
+    type Item = <S::Item as Future>::Output;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        while this.in_flight.len() < *this.limit {
+            match this.stream.as_mut().poll_next(cx) {
+                task::Poll::Ready(Some(future)) => this.in_flight.push(spawn(future)),
+                task::Poll::Ready(None) | task::Poll::Pending => break,
+            }
+        }
+
+        match this.in_flight.poll_next_unpin(cx) {
+            task::Poll::Ready(Some(result)) => task::Poll::Ready(Some(result)),
+            task::Poll::Ready(None) if this.stream.is_done() => task::Poll::Ready(None),
+            // Nothing in flight but the stream has more to give - it will wake us when it does.
+            task::Poll::Ready(None) | task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let in_flight = self.in_flight.len();
+        let (lower, upper) = self.stream.size_hint();
+
+        (
+            lower.saturating_add(in_flight),
+            upper.and_then(|upper| upper.checked_add(in_flight)),
+        )
+    }
+}
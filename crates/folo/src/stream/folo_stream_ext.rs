@@ -0,0 +1,39 @@
+use crate::stream::{BufferUnorderedLocal, Stream, TimeoutPerItem};
+use std::{future::Future, time::Duration};
+
+/// Stream combinators that rely on the Folo runtime. Streams using these must be polled on an
+/// async worker thread owned by a Folo runtime.
+pub trait FoloStreamExt: Stream {
+    /// Spawns each future yielded by the stream as a task on the current async worker thread, with
+    /// up to `limit` tasks running at the same time, and yields their results in the order they
+    /// complete.
+    ///
+    /// Unlike `StreamExt::buffer_unordered()`, which polls the futures as part of polling the
+    /// stream, each future runs as its own task and makes progress even while the consumer of the
+    /// stream is busy with something else. Dropping the stream detaches the running tasks, which
+    /// continue to run to completion.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    fn buffer_unordered_local(self, limit: usize) -> BufferUnorderedLocal<Self>
+    where
+        Self: Sized,
+        Self::Item: Future + 'static,
+        <Self::Item as Future>::Output: 'static,
+    {
+        BufferUnorderedLocal::new(self, limit)
+    }
+
+    /// Fails with `io::Error::TimedOut` whenever the stream takes longer than `timeout` to yield
+    /// its next item. The stream remains usable after a timeout - polling it again starts waiting
+    /// for the same item with a fresh timeout.
+    fn timeout_per_item(self, timeout: Duration) -> TimeoutPerItem<Self>
+    where
+        Self: Sized,
+    {
+        TimeoutPerItem::new(self, timeout)
+    }
+}
+
+impl<S> FoloStreamExt for S where S: Stream + ?Sized {}
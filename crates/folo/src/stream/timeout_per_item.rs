@@ -0,0 +1,66 @@
+use crate::{
+    io,
+    rt::{sleep, Sleep},
+};
+use futures::stream::Stream;
+use pin_project::pin_project;
+use std::{future::Future, pin::Pin, task, time::Duration};
+
+/// Stream returned by `FoloStreamExt::timeout_per_item()`.
+#[pin_project]
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct TimeoutPerItem<S> {
+    #[pin]
+    stream: S,
+
+    timeout: Duration,
+
+    // Started when we first find the stream pending and cleared whenever it yields something.
+    sleep: Option<Sleep>,
+}
+
+impl<S> TimeoutPerItem<S> {
+    pub(super) fn new(stream: S, timeout: Duration) -> Self {
+        Self {
+            stream,
+            timeout,
+            sleep: None,
+        }
+    }
+}
+
+impl<S> Stream for TimeoutPerItem<S>
+where
+    S: Stream,
+{
+    type Item = io::Result<S::Item>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        if let task::Poll::Ready(item) = this.stream.poll_next(cx) {
+            *this.sleep = None;
+            return task::Poll::Ready(item.map(Ok));
+        }
+
+        let timeout = *this.timeout;
+        let sleep = this.sleep.get_or_insert_with(|| sleep(timeout));
+
+        match Pin::new(sleep).poll(cx) {
+            task::Poll::Ready(()) => {
+                *this.sleep = None;
+                task::Poll::Ready(Some(Err(io::Error::TimedOut)))
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Timeouts may add any number of items.
+        (self.stream.size_hint().0, None)
+    }
+}
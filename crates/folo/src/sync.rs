@@ -1,3 +1,5 @@
+mod cancellation_token;
 mod semaphores;
 
+pub use cancellation_token::*;
 pub use semaphores::*;
\ No newline at end of file
@@ -0,0 +1,176 @@
+use crate::constants::POISONED_LOCK;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{self, AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
+    task::{self, Waker},
+};
+
+/// A signal that can be used to request cancellation of one or more operations. Clones of the
+/// token share the same state, so cancelling any clone cancels all of them.
+///
+/// Unlike most Folo primitives, the token is thread-safe - a typical use is to cancel work running
+/// on one thread from a task running on another.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+
+    // Tasks awaiting `cancelled()`, keyed by the future that registered them so the future can
+    // remove its waker when dropped. Drained when the token is cancelled.
+    awaiting: Mutex<Vec<(u64, Waker)>>,
+
+    next_key: AtomicU64,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signals cancellation to all holders of this token. Repeated calls have no further effect.
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, atomic::Ordering::AcqRel) {
+            return;
+        }
+
+        let awaiting = std::mem::take(&mut *self.inner.awaiting.lock().expect(POISONED_LOCK));
+
+        for (_, waker) in awaiting {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(atomic::Ordering::Acquire)
+    }
+
+    /// Returns a future that completes when the token is cancelled.
+    pub fn cancelled(&self) -> impl Future<Output = ()> + '_ {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+}
+
+struct Cancelled<'a> {
+    token: &'a CancellationToken,
+
+    // Identifies our waker in the list of awaiting tasks, once registered.
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.token.is_cancelled() {
+            return task::Poll::Ready(());
+        }
+
+        let mut awaiting = this.token.inner.awaiting.lock().expect(POISONED_LOCK);
+
+        // We check again under the lock because `cancel()` sets the flag before draining the list.
+        // If we see the flag unset here, our waker is guaranteed to be drained and woken.
+        if this.token.is_cancelled() {
+            return task::Poll::Ready(());
+        }
+
+        let existing = this
+            .key
+            .and_then(|key| awaiting.iter_mut().find(|(k, _)| *k == key));
+
+        match existing {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => {
+                let key = this
+                    .token
+                    .inner
+                    .next_key
+                    .fetch_add(1, atomic::Ordering::Relaxed);
+
+                awaiting.push((key, cx.waker().clone()));
+                this.key = Some(key);
+            }
+        }
+
+        task::Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+
+        // The waker is already gone if the token has been cancelled in the meantime.
+        let mut awaiting = self.token.inner.awaiting.lock().expect(POISONED_LOCK);
+
+        if let Some(index) = awaiting.iter().position(|(k, _)| *k == key) {
+            awaiting.swap_remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, task::noop_waker_ref, FutureExt};
+    use std::thread;
+
+    #[test]
+    fn cancel_is_visible_to_clones() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+
+        // Repeated cancellation is harmless.
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn cancelled_completes_after_cancel() {
+        let token = CancellationToken::new();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        let mut cancelled = Box::pin(token.cancelled());
+        assert!(cancelled.poll_unpin(&mut cx).is_pending());
+
+        let remote = token.clone();
+        thread::spawn(move || remote.cancel()).join().unwrap();
+
+        assert!(cancelled.poll_unpin(&mut cx).is_ready());
+        block_on(token.cancelled());
+    }
+
+    #[test]
+    fn dropped_cancelled_removes_waker() {
+        let token = CancellationToken::new();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        let mut cancelled = Box::pin(token.cancelled());
+
+        // Repeated polls update the registered waker instead of adding more.
+        assert!(cancelled.poll_unpin(&mut cx).is_pending());
+        assert!(cancelled.poll_unpin(&mut cx).is_pending());
+        assert_eq!(token.inner.awaiting.lock().unwrap().len(), 1);
+
+        drop(cancelled);
+        assert!(token.inner.awaiting.lock().unwrap().is_empty());
+    }
+}
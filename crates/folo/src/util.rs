@@ -1,3 +1,4 @@
+mod handle_wait;
 mod local_cell;
 mod low_precision_instant;
 pub mod once_event;
@@ -8,6 +9,7 @@ mod ptr_hash;
 mod slab_rc;
 mod thread_safe;
 
+pub(crate) use handle_wait::*;
 pub use local_cell::*;
 pub use low_precision_instant::*;
 pub use owned_handle::*;
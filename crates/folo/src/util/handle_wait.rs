@@ -0,0 +1,132 @@
+use crate::{
+    io::{self, IoWaker},
+    rt::current_async_agent,
+};
+use futures::channel::oneshot;
+use negative_impl::negative_impl;
+use std::{ffi::c_void, future::Future, pin::Pin, task};
+use windows::Win32::{
+    Foundation::{BOOLEAN, HANDLE, INVALID_HANDLE_VALUE},
+    System::Threading::{
+        RegisterWaitForSingleObject, UnregisterWaitEx, INFINITE, WT_EXECUTEINWAITTHREAD,
+        WT_EXECUTEONLYONCE,
+    },
+};
+
+/// Waits for a waitable kernel object (e.g. an event) to become signaled, without blocking any
+/// thread of the runtime. The wait is performed by the operating system thread pool, which
+/// notifies the current async worker once the object is signaled.
+///
+/// The handle must remain open until the returned future is dropped.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+pub(crate) fn wait_for_handle(handle: HANDLE) -> io::Result<HandleWait> {
+    let (tx, rx) = oneshot::channel();
+
+    let context = Box::into_raw(Box::new(WaitContext {
+        tx,
+        io_waker: current_async_agent::with_io(|io| io.waker()),
+    }));
+
+    let mut wait_handle = HANDLE::default();
+
+    // SAFETY: The context stays alive until either the callback consumes it or we free it after
+    // unregistering the wait (see Drop). If registration fails, the callback is never called.
+    if let Err(e) = unsafe {
+        RegisterWaitForSingleObject(
+            &mut wait_handle,
+            handle,
+            Some(on_signaled),
+            Some(context as *const c_void),
+            INFINITE,
+            WT_EXECUTEONLYONCE | WT_EXECUTEINWAITTHREAD,
+        )
+    } {
+        // SAFETY: The wait was not registered, so we still own the context.
+        drop(unsafe { Box::from_raw(context) });
+        return Err(e.into());
+    }
+
+    Ok(HandleWait {
+        wait_handle,
+        context,
+        result_rx: rx,
+        signaled: false,
+    })
+}
+
+/// Future returned by `wait_for_handle()`.
+#[derive(Debug)]
+pub(crate) struct HandleWait {
+    wait_handle: HANDLE,
+
+    // Owned by the thread pool callback once it has been called, by us otherwise.
+    context: *mut WaitContext,
+
+    result_rx: oneshot::Receiver<()>,
+    signaled: bool,
+}
+
+impl Future for HandleWait {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        if self.signaled {
+            return task::Poll::Ready(());
+        }
+
+        match Pin::new(&mut self.result_rx).poll(cx) {
+            // The sender is only ever dropped after sending, so both results mean "signaled".
+            task::Poll::Ready(_) => {
+                self.signaled = true;
+                task::Poll::Ready(())
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+impl Drop for HandleWait {
+    fn drop(&mut self) {
+        // Passing INVALID_HANDLE_VALUE makes this wait for any callback in progress to complete,
+        // after which we know for sure whether the callback consumed the context. The callback is
+        // trivial, so this blocks for a negligible time at worst.
+        //
+        // SAFETY: The wait handle is valid until unregistered, which only happens here.
+        _ = unsafe { UnregisterWaitEx(self.wait_handle, INVALID_HANDLE_VALUE) };
+
+        if self.signaled || !matches!(self.result_rx.try_recv(), Ok(None)) {
+            // The callback was called and took ownership of the context.
+            return;
+        }
+
+        // SAFETY: The wait is unregistered and the callback was never called, so we own it.
+        drop(unsafe { Box::from_raw(self.context) });
+    }
+}
+
+#[negative_impl]
+impl !Send for HandleWait {}
+#[negative_impl]
+impl !Sync for HandleWait {}
+
+#[derive(Debug)]
+struct WaitContext {
+    tx: oneshot::Sender<()>,
+
+    // The async worker may be asleep waiting for I/O, so we need to wake it up in addition to
+    // waking the task that awaits the signal.
+    io_waker: IoWaker,
+}
+
+unsafe extern "system" fn on_signaled(context: *mut c_void, _timed_out: BOOLEAN) {
+    // SAFETY: This is the context we registered and the callback is called at most once because
+    // the wait was registered with WT_EXECUTEONLYONCE.
+    let context = unsafe { Box::from_raw(context as *mut WaitContext) };
+
+    // The receiver may already be gone, which is fine.
+    _ = context.tx.send(());
+    context.io_waker.wake();
+}
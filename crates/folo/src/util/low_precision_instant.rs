@@ -1,7 +1,9 @@
-use windows::Win32::System::SystemInformation::GetTickCount64;
+use crate::rt::clock;
 
 /// A cheaper version of `Instant` that is capable of representing time with less precision. The
 /// granularity is typically around 15-20 ms, so no point trying to see differences below that.
+///
+/// The time is read from the clock of the current thread (see `rt::Clock`).
 /// 
 /// TODO: Some thread local variable we update once per tick might be even better for performance,
 /// so we can avoid the FFI call (which is fast but still expensive compared to a variable read).
@@ -13,13 +15,13 @@ pub struct LowPrecisionInstant {
 impl LowPrecisionInstant {
     pub fn now() -> Self {
         LowPrecisionInstant {
-            // SAFETY: Nothing unsafe about this, just an FFI call.
-            value: unsafe { GetTickCount64() },
+            value: clock::now_millis(),
         }
     }
 
     pub fn duration_since(&self, earlier: LowPrecisionInstant) -> std::time::Duration {
-        std::time::Duration::from_millis(self.value - earlier.value)
+        // Saturates because instants taken on threads with different clocks are not comparable.
+        std::time::Duration::from_millis(self.value.saturating_sub(earlier.value))
     }
 
     pub fn elapsed(&self) -> std::time::Duration {
@@ -1,14 +1,18 @@
 use super::{
     LocalCell, PinnedSlabChain, RcSlabRc, RefSlabRc, SlabRcCell, SlabRcCellStorage, UnsafeSlabRc,
 };
+use crate::rt::{sleep, Sleep};
 use negative_impl::negative_impl;
 use std::{
     cell::UnsafeCell,
+    error::Error,
+    fmt,
     future::Future,
     mem,
     pin::Pin,
     rc::Rc,
     task::{self, Waker},
+    time::Duration,
 };
 
 /// Shorthand type for defining the slab-based backing storage for OnceEvent instances. Use
@@ -28,6 +32,13 @@ pub type OnceEventSlabStorage<T> = SlabRcCellStorage<OnceEvent<T>>;
 /// Event notifications are triggered instantly via waker if a listener is already awaiting, and
 /// the result is delivered instantly if the listener starts after the result is set.
 ///
+/// # Abandonment
+///
+/// Dropping the receiver before it has received the result (e.g. because the receiver gave up via
+/// `with_timeout()`) abandons the event. This immediately releases the waker registered by the
+/// receiver and any result that was set but never received, as well as the reference the receiver
+/// holds on the backing storage. Setting the result of an abandoned event simply drops the result.
+///
 /// # Thread safety
 ///
 /// The event is single-threaded.
@@ -62,6 +73,20 @@ impl<T> OnceEvent<T> {
             EventState::Consumed => {
                 panic!("result already consumed");
             }
+            EventState::Abandoned => {
+                // Nobody is listening anymore, so the result is dropped right here.
+            }
+        }
+    }
+
+    /// Called when the receiver is dropped. Anything held on behalf of the receiver is released.
+    fn abandon(&self) {
+        // SAFETY: See comments on field.
+        let state = unsafe { &mut *self.state.get() };
+
+        // Once consumed, we keep that state to still detect setting the result twice.
+        if !matches!(state, EventState::Consumed) {
+            *state = EventState::Abandoned;
         }
     }
 
@@ -94,6 +119,9 @@ impl<T> OnceEvent<T> {
                 // The futures API contract allows us to panic in this situation.
                 panic!("event polled after result was already consumed");
             }
+            EventState::Abandoned => {
+                unreachable!("only the receiver polls the event and it abandons it when dropped")
+            }
         }
     }
 
@@ -126,6 +154,26 @@ impl<T> OnceEvent<T> {
         )
     }
 
+    /// Creates `count` events in the storage at once. This is cheaper than calling `new_in_ref()`
+    /// repeatedly when setting up many events together (e.g. one per request in a batch), as the
+    /// storage is only borrowed once and all the required capacity is allocated up front.
+    pub fn new_many_in_ref<'storage>(
+        storage: &'storage OnceEventSlabStorage<T>,
+        count: usize,
+    ) -> Vec<(RefSender<'storage, T>, RefReceiver<'storage, T>)> {
+        SlabRcCell::insert_many_into_ref((0..count).map(|_| SlabRcCell::new(Self::new())), storage)
+            .into_iter()
+            .map(|event| {
+                (
+                    RefSender {
+                        event: event.clone(),
+                    },
+                    RefReceiver { event },
+                )
+            })
+            .collect()
+    }
+
     pub fn new_in_rc(storage: Rc<OnceEventSlabStorage<T>>) -> (RcSender<T>, RcReceiver<T>) {
         let event = SlabRcCell::new(Self::new()).insert_into_rc(storage);
 
@@ -188,6 +236,9 @@ enum EventState<T> {
 
     /// The event has been set and the result has been consumed.
     Consumed,
+
+    /// The receiver was dropped without consuming the result.
+    Abandoned,
 }
 
 #[negative_impl]
@@ -213,6 +264,14 @@ pub struct RefReceiver<'storage, T> {
     event: RefSlabRc<'storage, OnceEvent<T>>,
 }
 
+impl<'storage, T> RefReceiver<'storage, T> {
+    /// Waits for the result for up to the specified duration, after which the receiver gives up
+    /// and abandons the event. See `WithTimeout`.
+    pub fn with_timeout(self, duration: Duration) -> WithTimeout<Self> {
+        WithTimeout::new(self, duration)
+    }
+}
+
 impl<T> Future for RefReceiver<'_, T> {
     type Output = T;
 
@@ -226,6 +285,12 @@ impl<T> Future for RefReceiver<'_, T> {
     }
 }
 
+impl<T> Drop for RefReceiver<'_, T> {
+    fn drop(&mut self) {
+        self.event.deref_pin().abandon();
+    }
+}
+
 // ############## Rc ##############
 
 #[derive(Debug)]
@@ -244,6 +309,14 @@ pub struct RcReceiver<T> {
     event: RcSlabRc<OnceEvent<T>>,
 }
 
+impl<T> RcReceiver<T> {
+    /// Waits for the result for up to the specified duration, after which the receiver gives up
+    /// and abandons the event. See `WithTimeout`.
+    pub fn with_timeout(self, duration: Duration) -> WithTimeout<Self> {
+        WithTimeout::new(self, duration)
+    }
+}
+
 impl<T> Future for RcReceiver<T> {
     type Output = T;
 
@@ -257,6 +330,12 @@ impl<T> Future for RcReceiver<T> {
     }
 }
 
+impl<T> Drop for RcReceiver<T> {
+    fn drop(&mut self) {
+        self.event.deref_pin().abandon();
+    }
+}
+
 // ############## Unsafe ##############
 
 #[derive(Debug)]
@@ -275,6 +354,14 @@ pub struct UnsafeReceiver<T> {
     event: UnsafeSlabRc<OnceEvent<T>>,
 }
 
+impl<T> UnsafeReceiver<T> {
+    /// Waits for the result for up to the specified duration, after which the receiver gives up
+    /// and abandons the event. See `WithTimeout`.
+    pub fn with_timeout(self, duration: Duration) -> WithTimeout<Self> {
+        WithTimeout::new(self, duration)
+    }
+}
+
 impl<T> Future for UnsafeReceiver<T> {
     type Output = T;
 
@@ -288,6 +375,12 @@ impl<T> Future for UnsafeReceiver<T> {
     }
 }
 
+impl<T> Drop for UnsafeReceiver<T> {
+    fn drop(&mut self) {
+        self.event.deref_pin().abandon();
+    }
+}
+
 // ############## Embedded ##############
 
 /// Shorthand type for defining inline backing storage for OnceEvent instances embedded into custom
@@ -362,6 +455,14 @@ pub struct EmbeddedReceiver<T> {
     event: *const OnceEventEmbeddedStorage<T>,
 }
 
+impl<T> EmbeddedReceiver<T> {
+    /// Waits for the result for up to the specified duration, after which the receiver gives up
+    /// and abandons the event. See `WithTimeout`.
+    pub fn with_timeout(self, duration: Duration) -> WithTimeout<Self> {
+        WithTimeout::new(self, duration)
+    }
+}
+
 impl<T> Future for EmbeddedReceiver<T> {
     type Output = T;
 
@@ -395,11 +496,85 @@ impl<T> Drop for EmbeddedReceiver<T> {
         // SAFETY: See comments on storage type alias.
         let storage = unsafe { &mut *storage.inner.get() };
 
+        storage
+            .get()
+            .as_ref()
+            .expect("OnceEvent must still exist because receiver exists")
+            .abandon();
+
         // There is no receiver anymore, so we can drop a reference.
         storage.dec_ref();
     }
 }
 
+// ############## Timeout ##############
+
+/// A receiver that waits for the result of a `OnceEvent` for a limited time, created via the
+/// `with_timeout()` method of the receivers. Resolves to `Err(Elapsed)` if the result is not set
+/// in time, in which case the receiver is dropped right away, abandoning the event.
+///
+/// The timeout is serviced by the current async worker thread and has the same low precision as
+/// `rt::sleep()`.
+///
+/// # Panics
+///
+/// Polling panics if the current thread is not an async worker thread owned by a Folo runtime.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithTimeout<R> {
+    // None once the receiver has completed or given up.
+    receiver: Option<R>,
+    sleep: Sleep,
+}
+
+impl<R> WithTimeout<R> {
+    fn new(receiver: R, duration: Duration) -> Self {
+        Self {
+            receiver: Some(receiver),
+            sleep: sleep(duration),
+        }
+    }
+}
+
+impl<R> Future for WithTimeout<R>
+where
+    R: Future + Unpin,
+{
+    type Output = Result<R::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let receiver = self
+            .receiver
+            .as_mut()
+            .expect("WithTimeout polled after it already completed");
+
+        if let task::Poll::Ready(result) = Pin::new(receiver).poll(cx) {
+            self.receiver = None;
+            return task::Poll::Ready(Ok(result));
+        }
+
+        if Pin::new(&mut self.sleep).poll(cx).is_ready() {
+            // Dropping the receiver abandons the event, releasing what it held for us.
+            self.receiver = None;
+            return task::Poll::Ready(Err(Elapsed));
+        }
+
+        task::Poll::Pending
+    }
+}
+
+/// The result of a `OnceEvent` was not set before the timeout elapsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("timed out waiting for the event")
+    }
+}
+
+impl Error for Elapsed {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -433,6 +608,26 @@ mod tests {
         assert_eq!(result, task::Poll::Ready(42));
     }
 
+    #[test]
+    fn new_many_ref() {
+        let storage = OnceEvent::new_slab_storage();
+        let events = OnceEvent::new_many_in_ref(&storage, 2000);
+
+        assert_eq!(events.len(), 2000);
+        assert_eq!(storage.borrow().len(), 2000);
+
+        let cx = &mut task::Context::from_waker(noop_waker_ref());
+
+        for (value, (sender, mut receiver)) in events.into_iter().enumerate() {
+            sender.set(value);
+
+            let result = receiver.poll_unpin(cx);
+            assert_eq!(result, task::Poll::Ready(value));
+        }
+
+        assert!(storage.borrow().is_empty());
+    }
+
     #[test]
     fn get_after_set_rc() {
         let storage = Rc::new(OnceEvent::new_slab_storage());
@@ -519,4 +714,37 @@ mod tests {
         let result = receiver.poll_unpin(cx);
         assert_eq!(result, task::Poll::Ready(42));
     }
+
+    #[test]
+    fn set_after_receiver_dropped_ref() {
+        let storage = OnceEvent::new_slab_storage();
+        let (sender, mut receiver) = OnceEvent::new_in_ref(&storage);
+
+        let cx = &mut task::Context::from_waker(noop_waker_ref());
+        assert_eq!(receiver.poll_unpin(cx), task::Poll::Pending);
+
+        drop(receiver);
+
+        let value = Rc::new(42);
+        sender.set(Rc::clone(&value));
+
+        // The abandoned event drops the result immediately, as nobody will ever receive it.
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert!(storage.borrow().is_empty());
+    }
+
+    #[test]
+    fn receiver_dropped_after_set_embedded() {
+        let storage = Box::pin(OnceEvent::new_embedded_storage());
+        let (sender, receiver) = unsafe { OnceEvent::new_embedded(storage.as_ref()) };
+
+        let value = Rc::new(42);
+        sender.set(Rc::clone(&value));
+        assert_eq!(Rc::strong_count(&value), 2);
+
+        // The result that was never received is released together with the receiver.
+        drop(receiver);
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert!(storage.is_inert());
+    }
 }
@@ -71,6 +71,19 @@ impl<T, const SLAB_SIZE: usize> PinnedSlabChain<T, SLAB_SIZE> {
         index
     }
 
+    /// Ensures that at least `additional` items can be inserted without allocating a new slab,
+    /// allocating the missing slabs up front. Slabs allocated here are filled in order, so items
+    /// inserted right after reserving into an otherwise full chain occupy contiguous indexes.
+    pub fn reserve(&mut self, additional: usize) {
+        let vacant: usize = self.slabs.iter().map(|slab| SLAB_SIZE - slab.len()).sum();
+
+        let missing = additional.saturating_sub(vacant);
+
+        for _ in 0..missing.div_ceil(SLAB_SIZE) {
+            self.slabs.push(PinnedSlab::new());
+        }
+    }
+
     pub fn remove(&mut self, index: usize) {
         let index = ChainIndex::<SLAB_SIZE>::from_whole(index);
 
@@ -186,6 +199,27 @@ mod tests {
         chain.insert(90);
     }
 
+    #[test]
+    fn reserve_allocates_missing_slabs() {
+        let mut chain = PinnedSlabChain::<u32, 3>::new();
+
+        chain.insert(1);
+        chain.reserve(5);
+
+        // The first slab has 2 vacant slots, so one more slab covers the rest.
+        assert_eq!(chain.slabs.len(), 2);
+
+        let indexes = (0..5).map(|x| chain.insert(x)).collect::<Vec<_>>();
+        assert_eq!(indexes, vec![1, 2, 3, 4, 5]);
+        assert_eq!(chain.slabs.len(), 2);
+
+        // Nothing to do if there is already enough room.
+        chain.reserve(0);
+        assert_eq!(chain.slabs.len(), 2);
+
+        chain.integrity_check();
+    }
+
     #[test]
     #[should_panic]
     fn panic_when_empty_oob_get() {
@@ -121,6 +121,40 @@ impl<T> SlabRcCell<T> {
         }
     }
 
+    /// Inserts all the items while borrowing the slab chain only once and allocating all the
+    /// required slabs up front, which is cheaper than inserting the items one by one.
+    pub fn insert_many_into_ref<'slab>(
+        items: impl IntoIterator<Item = Self>,
+        slab_chain: &'slab RefCell<PinnedSlabChain<SlabRcCell<T>>>,
+    ) -> Vec<RefSlabRc<'slab, T>> {
+        let items = items.into_iter();
+
+        let mut slab_chain_mut = slab_chain.borrow_mut();
+        slab_chain_mut.reserve(items.size_hint().0);
+
+        items
+            .map(|item| {
+                let inserter = slab_chain_mut.begin_insert();
+                let index = inserter.index();
+
+                // We are creating the first reference here, embodied in the SlabRc we return.
+                item.ref_count.set(1);
+
+                // See insert_into_ref() for why this is not marked unsafe.
+                let value = inserter.insert(item);
+
+                RefSlabRc {
+                    slab_chain,
+                    // SAFETY: The risk is that we un-pin something !Unpin. We do not do that - all
+                    // pinned slab items are forever pinned and we always expose them as pinned
+                    // pointers.
+                    value: unsafe { Pin::into_inner_unchecked(value) } as *const _,
+                    index,
+                }
+            })
+            .collect()
+    }
+
     pub fn insert_into_rc(
         self,
         slab_chain: Rc<RefCell<PinnedSlabChain<SlabRcCell<T>>>>,
@@ -449,6 +483,21 @@ mod tests {
         assert!(canary_weak.upgrade().is_none());
     }
 
+    #[test]
+    fn ref_insert_many() {
+        let storage = SlabRcCell::<usize>::new_storage_ref();
+
+        let items = SlabRcCell::insert_many_into_ref((0..3).map(SlabRcCell::new), &storage);
+        assert_eq!(storage.borrow().len(), 3);
+
+        for (expected, item) in items.iter().enumerate() {
+            assert_eq!(*item.deref_pin(), expected);
+        }
+
+        drop(items);
+        assert!(storage.borrow().is_empty());
+    }
+
     #[test]
     fn rc_smoke_test() {
         let storage = SlabRcCell::<usize>::new_storage_rc();
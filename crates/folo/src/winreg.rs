@@ -0,0 +1,7 @@
+mod functions;
+mod key;
+mod value;
+
+pub use functions::*;
+pub use key::*;
+pub use value::*;
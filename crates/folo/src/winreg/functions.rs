@@ -0,0 +1,60 @@
+use crate::{
+    io,
+    util::{wait_for_handle, OwnedHandle},
+    winreg::{RegistryKey, RegistryRoot, RegistryValue},
+};
+use windows::Win32::System::{
+    Registry::{
+        RegNotifyChangeKeyValue, REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME,
+        REG_NOTIFY_THREAD_AGNOSTIC,
+    },
+    Threading::CreateEventW,
+};
+
+/// Waits for the next change to the values of a registry key or to its set of subkeys. If
+/// `include_subkeys` is set, changes anywhere in the subtree of the key are also reported.
+///
+/// Changes that happen between two calls are not reported, so after each change you should read
+/// the values you are interested in again before calling this again, instead of expecting a
+/// notification for every single change. Multiple changes in quick succession may complete a
+/// single wait.
+///
+/// Waiting does not occupy any thread of the runtime - the operating system signals an event
+/// when the key changes, which wakes up the waiting task.
+pub async fn watch_key(key: &RegistryKey, include_subkeys: bool) -> io::Result<()> {
+    // SAFETY: We are required to close the handle once we are done with it,
+    // which we do via OwnedHandle that closes the handle on drop.
+    let event = unsafe { OwnedHandle::new(CreateEventW(None, false, false, None)?) };
+
+    // The notification is registered on the key and signals the event on the next change. It is
+    // thread-agnostic, so it does not depend on the current thread staying alive. This call does
+    // not block because we ask for an asynchronous notification.
+    //
+    // SAFETY: Both handles are valid and we keep them open while waiting for the notification.
+    unsafe {
+        RegNotifyChangeKeyValue(
+            key.handle(),
+            include_subkeys,
+            REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET | REG_NOTIFY_THREAD_AGNOSTIC,
+            *event,
+            true,
+        )
+        .ok()?;
+    }
+
+    wait_for_handle(*event)?.await;
+
+    Ok(())
+}
+
+/// Reads a value of the key at the specified path under a root, returning `None` if there is no
+/// value with the specified name. An empty name reads the default value of the key.
+///
+/// If you read multiple values of the same key, open it once via `RegistryKey::open()` instead.
+pub async fn read_value(
+    root: RegistryRoot,
+    path: &str,
+    name: &str,
+) -> io::Result<Option<RegistryValue>> {
+    RegistryKey::open(root, path).await?.read_value(name).await
+}
@@ -0,0 +1,165 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+    util::{OwnedHandle, ThreadSafe},
+    winreg::RegistryValue,
+};
+use negative_impl::negative_impl;
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA},
+        System::Registry::{
+            RegOpenKeyExW, RegQueryValueExW, HKEY, HKEY_CLASSES_ROOT, HKEY_CURRENT_CONFIG,
+            HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, HKEY_USERS, KEY_READ, REG_VALUE_TYPE,
+        },
+    },
+};
+
+/// One of the predefined root keys of the registry, under which all other keys are opened.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegistryRoot {
+    ClassesRoot,
+    CurrentConfig,
+    CurrentUser,
+    LocalMachine,
+    Users,
+}
+
+impl RegistryRoot {
+    fn hkey(self) -> HKEY {
+        match self {
+            Self::ClassesRoot => HKEY_CLASSES_ROOT,
+            Self::CurrentConfig => HKEY_CURRENT_CONFIG,
+            Self::CurrentUser => HKEY_CURRENT_USER,
+            Self::LocalMachine => HKEY_LOCAL_MACHINE,
+            Self::Users => HKEY_USERS,
+        }
+    }
+}
+
+/// A registry key opened for reading values and watching for changes.
+///
+/// Registry calls may block (e.g. when the hive needs to be paged in from disk), so they are
+/// offloaded to synchronous worker threads. Watching for changes does not occupy any thread.
+#[derive(Debug)]
+pub struct RegistryKey {
+    handle: OwnedHandle<HKEY>,
+}
+
+impl RegistryKey {
+    /// Opens an existing key at the specified path (e.g. `SOFTWARE\Contoso\Service`) under a root.
+    pub async fn open(root: RegistryRoot, path: &str) -> io::Result<Self> {
+        let path = to_wide(path);
+
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The root is a predefined key, which does not need to be kept open.
+            unsafe { open_key(root.hkey(), &path) }
+        })
+        .await?;
+
+        Ok(Self { handle })
+    }
+
+    /// Opens an existing key at the specified path relative to this key.
+    pub async fn open_subkey(&self, path: &str) -> io::Result<Self> {
+        let path = to_wide(path);
+
+        // SAFETY: Registry key handles can be used from any thread and we keep the handle open
+        // until the synchronous task has completed because we wait for it while borrowing self.
+        let parent = unsafe { ThreadSafe::new(*self.handle) };
+
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: See above.
+            unsafe { open_key(*parent, &path) }
+        })
+        .await?;
+
+        Ok(Self { handle })
+    }
+
+    /// Reads a value of the key, returning `None` if there is no value with the specified name.
+    /// An empty name reads the default value of the key.
+    pub async fn read_value(&self, name: &str) -> io::Result<Option<RegistryValue>> {
+        let name = to_wide(name);
+
+        // SAFETY: Registry key handles can be used from any thread and we keep the handle open
+        // until the synchronous task has completed because we wait for it while borrowing self.
+        let key = unsafe { ThreadSafe::new(*self.handle) };
+
+        spawn_sync(SynchronousTaskType::Syscall, move || {
+            // SAFETY: See above.
+            unsafe { query_value(*key, &name) }
+        })
+        .await
+    }
+
+    pub(crate) fn handle(&self) -> HKEY {
+        *self.handle
+    }
+}
+
+#[negative_impl]
+impl !Send for RegistryKey {}
+#[negative_impl]
+impl !Sync for RegistryKey {}
+
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// # Safety
+///
+/// The parent key must be open for the duration of the call.
+unsafe fn open_key(parent: HKEY, path: &[u16]) -> io::Result<OwnedHandle<HKEY>> {
+    let mut key = HKEY::default();
+
+    RegOpenKeyExW(
+        parent,
+        PCWSTR::from_raw(path.as_ptr()),
+        0,
+        KEY_READ,
+        &mut key,
+    )
+    .ok()?;
+
+    // SAFETY: Registry key handles are valid to close from any thread.
+    Ok(OwnedHandle::new(key))
+}
+
+/// # Safety
+///
+/// The key must be open for the duration of the call.
+unsafe fn query_value(key: HKEY, name: &[u16]) -> io::Result<Option<RegistryValue>> {
+    // Most values are small, so we start with a modest buffer and grow it if needed. The value
+    // may change between calls, so we keep retrying until it fits.
+    let mut data = vec![0; 256];
+
+    loop {
+        let mut value_type = REG_VALUE_TYPE::default();
+        let mut len = data.len() as u32;
+
+        let result = RegQueryValueExW(
+            key,
+            PCWSTR::from_raw(name.as_ptr()),
+            None,
+            Some(&mut value_type),
+            Some(data.as_mut_ptr()),
+            Some(&mut len),
+        );
+
+        if result == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+
+        if result == ERROR_MORE_DATA {
+            data.resize(len as usize, 0);
+            continue;
+        }
+
+        result.ok()?;
+
+        data.truncate(len as usize);
+        return Ok(Some(RegistryValue::from_raw(value_type.0, data)));
+    }
+}
@@ -0,0 +1,133 @@
+use windows::Win32::System::Registry::{
+    REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_QWORD, REG_SZ,
+};
+
+/// The data of a registry value, decoded according to its type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegistryValue {
+    /// `REG_SZ`
+    String(String),
+
+    /// `REG_EXPAND_SZ`, with any environment variable references left unexpanded.
+    ExpandString(String),
+
+    /// `REG_MULTI_SZ`
+    MultiString(Vec<String>),
+
+    /// `REG_DWORD`
+    Dword(u32),
+
+    /// `REG_QWORD`
+    Qword(u64),
+
+    /// `REG_BINARY`
+    Binary(Vec<u8>),
+
+    /// Any other type of value (or a value whose data does not match its type), as raw bytes.
+    Other { value_type: u32, data: Vec<u8> },
+}
+
+impl RegistryValue {
+    pub(crate) fn from_raw(value_type: u32, data: Vec<u8>) -> Self {
+        match value_type {
+            t if t == REG_SZ.0 => Self::String(decode_string(&data)),
+            t if t == REG_EXPAND_SZ.0 => Self::ExpandString(decode_string(&data)),
+            t if t == REG_MULTI_SZ.0 => Self::MultiString(
+                decode_string(&data)
+                    .split('\0')
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            t if t == REG_DWORD.0 && data.len() == 4 => {
+                Self::Dword(u32::from_le_bytes(data.try_into().expect("length checked")))
+            }
+            t if t == REG_QWORD.0 && data.len() == 8 => {
+                Self::Qword(u64::from_le_bytes(data.try_into().expect("length checked")))
+            }
+            t if t == REG_BINARY.0 => Self::Binary(data),
+            _ => Self::Other { value_type, data },
+        }
+    }
+
+    /// The value as a string, if it is a `String` or an `ExpandString`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) | Self::ExpandString(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value as an integer, if it is a `Dword` or a `Qword`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Dword(value) => Some(*value as u64),
+            Self::Qword(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes UTF-16 string data, which may or may not be terminated by one or more nul characters
+/// (the registry does not enforce termination). Any trailing nul characters are removed.
+fn decode_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16_bytes(value: &str) -> Vec<u8> {
+        value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn strings_with_and_without_terminator() {
+        assert_eq!(
+            RegistryValue::from_raw(REG_SZ.0, utf16_bytes("hello\0")),
+            RegistryValue::String("hello".to_string())
+        );
+
+        assert_eq!(
+            RegistryValue::from_raw(REG_EXPAND_SZ.0, utf16_bytes("%TEMP%")),
+            RegistryValue::ExpandString("%TEMP%".to_string())
+        );
+    }
+
+    #[test]
+    fn multi_string() {
+        assert_eq!(
+            RegistryValue::from_raw(REG_MULTI_SZ.0, utf16_bytes("a\0bc\0\0")),
+            RegistryValue::MultiString(vec!["a".to_string(), "bc".to_string()])
+        );
+
+        assert_eq!(
+            RegistryValue::from_raw(REG_MULTI_SZ.0, utf16_bytes("\0")),
+            RegistryValue::MultiString(vec![])
+        );
+    }
+
+    #[test]
+    fn integers() {
+        let dword = RegistryValue::from_raw(REG_DWORD.0, 42u32.to_le_bytes().to_vec());
+        assert_eq!(dword, RegistryValue::Dword(42));
+        assert_eq!(dword.as_u64(), Some(42));
+
+        let qword = RegistryValue::from_raw(REG_QWORD.0, u64::MAX.to_le_bytes().to_vec());
+        assert_eq!(qword.as_u64(), Some(u64::MAX));
+
+        // Malformed data is not silently truncated.
+        assert!(matches!(
+            RegistryValue::from_raw(REG_DWORD.0, vec![1, 2]),
+            RegistryValue::Other { .. }
+        ));
+    }
+}
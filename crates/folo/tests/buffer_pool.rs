@@ -0,0 +1,46 @@
+use folo::io::{BufferPool, BufferSize};
+use folo_testing::init_test_worker;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn take_for_len_picks_smallest_size_class() {
+    let buffer = BufferPool::take_for_len(100);
+    assert_eq!(buffer.capacity(), BufferSize::Small.capacity());
+    assert_eq!(buffer.len(), 100);
+
+    let buffer = BufferPool::take_for_len(BufferSize::Small.capacity() + 1);
+    assert_eq!(buffer.capacity(), BufferSize::Medium.capacity());
+
+    let buffer = BufferPool::take_for_len(BufferSize::Large.capacity());
+    assert_eq!(buffer.capacity(), BufferSize::Large.capacity());
+
+    // Beyond the largest size class, the buffer is allocated directly.
+    let len = BufferSize::Large.capacity() * 2;
+    let buffer = BufferPool::take_for_len(len);
+    assert_eq!(buffer.capacity(), len);
+    assert_eq!(buffer.len(), len);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn dropped_buffers_are_reused() {
+    let before = BufferPool::stats();
+
+    let buffer = BufferPool::take(BufferSize::Medium);
+    assert_eq!(
+        BufferPool::stats().outstanding_buffers,
+        before.outstanding_buffers + 1
+    );
+
+    drop(buffer);
+
+    let after_drop = BufferPool::stats();
+    assert_eq!(after_drop.outstanding_buffers, before.outstanding_buffers);
+    assert!(after_drop.idle_buffers >= 1);
+
+    // There is now an idle buffer of the size class, so this is a hit.
+    let _buffer = BufferPool::take(BufferSize::Medium);
+
+    let after_reuse = BufferPool::stats();
+    assert_eq!(after_reuse.hits, after_drop.hits + 1);
+    assert_eq!(after_reuse.misses, after_drop.misses);
+    assert!(after_reuse.hit_rate() > 0.0);
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "bytes")]
+
+use bytes::{Bytes, BytesMut};
+use folo::io::PinnedBuffer;
+
+#[test]
+fn unique_bytes_are_converted_without_copying() {
+    let bytes = Bytes::from(b"hello world".to_vec());
+    let address = bytes.as_ptr();
+
+    let buffer = PinnedBuffer::from(bytes);
+    assert_eq!(buffer.as_slice(), b"hello world");
+    assert_eq!(buffer.as_slice().as_ptr(), address);
+
+    let mut bytes_mut = BytesMut::with_capacity(64);
+    bytes_mut.extend_from_slice(b"data");
+    let address = bytes_mut.as_ptr();
+
+    let buffer = PinnedBuffer::from(bytes_mut);
+    assert_eq!(buffer.as_slice(), b"data");
+    assert_eq!(buffer.as_slice().as_ptr(), address);
+}
+
+#[test]
+fn shared_bytes_are_copied() {
+    let bytes = Bytes::from(b"shared".to_vec());
+    let other = bytes.clone();
+
+    let buffer = PinnedBuffer::from(bytes);
+    assert_eq!(buffer.as_slice(), b"shared");
+    assert_ne!(buffer.as_slice().as_ptr(), other.as_ptr());
+}
+
+#[test]
+fn active_region_is_converted_without_copying() {
+    let mut buffer = PinnedBuffer::from_vec(b"hello world".to_vec());
+    let address = buffer.as_slice().as_ptr();
+    buffer.set_start(6);
+    buffer.set_len(5);
+
+    let bytes = Bytes::from(buffer);
+    assert_eq!(bytes, &b"world"[..]);
+    assert_eq!(bytes.as_ptr(), address.wrapping_add(6));
+
+    let mut buffer = PinnedBuffer::from_vec(b"hello world".to_vec());
+    buffer.set_len(5);
+
+    let bytes_mut = BytesMut::from(buffer);
+    assert_eq!(bytes_mut, &b"hello"[..]);
+}
+
+#[test]
+fn pooled_buffer_is_copied() {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"abcde");
+
+    assert_eq!(Bytes::from(buffer), &b"abcde"[..]);
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(b"xyz");
+
+    assert_eq!(BytesMut::from(buffer), &b"xyz"[..]);
+}
@@ -0,0 +1,44 @@
+use folo::rt::{self, ManualClock, RuntimeBuilder};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn sleep_follows_manual_clock() {
+    let clock = Arc::new(ManualClock::new());
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .clock(Arc::clone(&clock) as _)
+        .build()
+        .unwrap();
+
+    let (started_tx, started_rx) = oneshot::channel();
+    let (slept_tx, slept_rx) = oneshot::channel();
+
+    folo.spawn_on_any(|| async move {
+        let started = rt::now();
+
+        // The deadline is calculated when the future is created, before the clock advances.
+        let sleep = rt::sleep(Duration::from_secs(3600));
+        _ = started_tx.send(());
+
+        sleep.await;
+
+        _ = slept_tx.send(rt::now() - started);
+    });
+
+    started_rx.recv().unwrap();
+
+    let real_start = Instant::now();
+    clock.advance(Duration::from_secs(3600));
+
+    let slept = slept_rx.recv().unwrap();
+
+    assert_eq!(slept, Duration::from_secs(3600));
+    assert!(real_start.elapsed() < Duration::from_secs(60));
+
+    folo.stop();
+    folo.wait();
+}
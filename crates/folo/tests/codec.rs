@@ -0,0 +1,139 @@
+use folo::{
+    net::{
+        codec::{Framed, LengthDelimitedCodec, LinesCodec, LinesCodecBuilder},
+        TcpConnection, TcpListener,
+    },
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use futures::{SinkExt, StreamExt};
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_281;
+const LINES_PORT: u16 = 41_282;
+const SMALL_SEND_BUFFER_PORT: u16 = 41_314;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn length_delimited_frames_round_trip() {
+    const FRAME_COUNT: usize = 1000;
+
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        PORT,
+    ))));
+
+    let server = listener.accept().await.unwrap();
+    let client = client.await.unwrap();
+
+    let sender = spawn(async move {
+        let mut framed = Framed::new(server, LengthDelimitedCodec::new());
+
+        for i in 0..FRAME_COUNT {
+            // Frames of varying length, including empty ones.
+            framed.feed(vec![i as u8; i % 100]).await.unwrap();
+        }
+
+        // The codec encodes anything that is `AsRef<[u8]>`, so we need to say which sink we close.
+        SinkExt::<Vec<u8>>::close(&mut framed).await.unwrap();
+    });
+
+    let mut framed = Framed::new(client, LengthDelimitedCodec::new());
+
+    for i in 0..FRAME_COUNT {
+        let frame = framed.next().await.unwrap().unwrap();
+        assert_eq!(frame, vec![i as u8; i % 100]);
+    }
+
+    assert!(framed.next().await.is_none());
+
+    sender.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn large_frames_survive_small_send_buffer() {
+    // Each flush hands the connection far more data than fits into its send buffer, so the data
+    // may be accepted by the operating system in parts.
+    const FRAME_COUNT: usize = 16;
+    const FRAME_LEN: usize = 256 * 1024;
+
+    let listener = TcpListener::bind(SMALL_SEND_BUFFER_PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        SMALL_SEND_BUFFER_PORT,
+    ))));
+
+    let server = listener.accept().await.unwrap();
+    let client = client.await.unwrap();
+
+    server.set_send_buffer_size(4096).unwrap();
+
+    let sender = spawn(async move {
+        let mut framed = Framed::new(server, LengthDelimitedCodec::new());
+
+        for i in 0..FRAME_COUNT {
+            framed.feed(vec![i as u8; FRAME_LEN]).await.unwrap();
+        }
+
+        SinkExt::<Vec<u8>>::close(&mut framed).await.unwrap();
+    });
+
+    let mut framed = Framed::new(client, LengthDelimitedCodec::new());
+
+    for i in 0..FRAME_COUNT {
+        let frame = framed.next().await.unwrap().unwrap();
+        assert_eq!(frame, vec![i as u8; FRAME_LEN]);
+    }
+
+    assert!(framed.next().await.is_none());
+
+    sender.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn lines_request_response() {
+    let listener = TcpListener::bind(LINES_PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        LINES_PORT,
+    ))));
+
+    let server = listener.accept().await.unwrap();
+    let client = client.await.unwrap();
+
+    let responder = spawn(async move {
+        let mut framed = Framed::new(server, crlf_codec());
+
+        while let Some(line) = framed.next().await {
+            let response = match line.unwrap().as_str() {
+                "PING" => "+PONG".to_string(),
+                other => format!("-ERR unknown command '{other}'"),
+            };
+
+            framed.send(response).await.unwrap();
+        }
+    });
+
+    let mut framed = Framed::new(client, crlf_codec());
+
+    framed.send("PING").await.unwrap();
+    assert_eq!(framed.next().await.unwrap().unwrap(), "+PONG");
+
+    framed.send("ECHO").await.unwrap();
+    assert_eq!(
+        framed.next().await.unwrap().unwrap(),
+        "-ERR unknown command 'ECHO'"
+    );
+
+    SinkExt::<&str>::close(&mut framed).await.unwrap();
+    assert!(framed.next().await.is_none());
+
+    responder.await;
+}
+
+fn crlf_codec() -> LinesCodec {
+    LinesCodecBuilder::new().delimiter("\r\n").build().unwrap()
+}
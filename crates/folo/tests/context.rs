@@ -0,0 +1,42 @@
+use folo::{
+    net::UdpSocket,
+    rt::{context, RuntimeBuilder},
+};
+use std::{
+    cell::Cell,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+struct RequestCounter(Cell<u32>);
+
+#[test]
+fn context_init_populates_every_async_worker() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .context_init(|| {
+            context::insert(RequestCounter(Cell::new(0)));
+
+            // The I/O driver is already available, so per-worker sockets can be created here.
+            context::insert(UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap());
+        })
+        .build()
+        .unwrap();
+
+    for _ in 0..3 {
+        let (tx, rx) = oneshot::channel();
+
+        folo.spawn_on_any(|| async move {
+            let counter = context::get::<RequestCounter>().unwrap();
+            counter.0.set(counter.0.get() + 1);
+
+            _ = tx.send((counter.0.get(), context::contains::<UdpSocket>()));
+        });
+
+        let (count, has_socket) = rx.recv().unwrap();
+        assert!(count >= 1);
+        assert!(has_socket);
+    }
+
+    folo.stop();
+    folo.wait();
+}
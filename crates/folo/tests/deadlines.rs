@@ -0,0 +1,55 @@
+use folo::rt::{spawn, with_deadline, yield_now};
+use folo_testing::init_test_worker;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tasks_with_earlier_deadlines_run_first() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+
+    let tasks = [
+        ("none", None),
+        ("late", Some(Duration::from_secs(20))),
+        ("early", Some(Duration::from_secs(10))),
+    ]
+    .map(|(name, deadline)| {
+        let order = Rc::clone(&order);
+
+        spawn(async move {
+            // All the tasks wake up from this at the same time, after which the worker decides
+            // which of them to poll first.
+            let work = async move {
+                yield_now().await;
+                order.borrow_mut().push(name);
+            };
+
+            match deadline {
+                Some(deadline) => with_deadline(work, Instant::now() + deadline).await,
+                None => work.await,
+            }
+        })
+    });
+
+    for task in tasks {
+        task.await;
+    }
+
+    assert_eq!(*order.borrow(), ["early", "late", "none"]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn missed_deadline_does_not_affect_result() {
+    let result = with_deadline(
+        async {
+            yield_now().await;
+            42
+        },
+        Instant::now() - Duration::from_millis(1),
+    )
+    .await;
+
+    assert_eq!(result, 42);
+}
@@ -0,0 +1,257 @@
+use folo::{
+    io::{self, BufferSize, OperationResultExt, PinnedBuffer},
+    net::{
+        dns::{Resolver, ResolverOptions},
+        TcpListener, UdpSocket,
+    },
+    rt::{spawn, LocalJoinHandle},
+};
+use folo_testing::init_test_worker;
+use std::{
+    cell::Cell,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    rc::Rc,
+    time::Duration,
+};
+
+const TCP_FALLBACK_PORT: u16 = 41_301;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Builds the response to a query, with the answers of the queried type.
+fn respond(query: &[u8], rcode: u8, truncated: bool, answers: &[IpAddr]) -> Vec<u8> {
+    // The question ends with its name (terminated by a zero byte), type and class.
+    let name_end = 12 + query[12..].iter().position(|byte| *byte == 0).unwrap() + 1;
+    let question = &query[12..name_end + 4];
+    let kind = u16::from_be_bytes([query[name_end], query[name_end + 1]]);
+
+    let answers = answers
+        .iter()
+        .filter_map(|addr| match (addr, kind) {
+            (IpAddr::V4(addr), TYPE_A) => Some(addr.octets().to_vec()),
+            (IpAddr::V6(addr), TYPE_AAAA) => Some(addr.octets().to_vec()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut response = query[..2].to_vec();
+    response.push(if truncated { 0x83 } else { 0x81 });
+    response.push(0x80 | rcode);
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(question);
+
+    for rdata in answers {
+        response.extend_from_slice(&[0xC0, 12]);
+        response.extend_from_slice(&kind.to_be_bytes());
+        response.extend_from_slice(&1u16.to_be_bytes());
+        response.extend_from_slice(&300u32.to_be_bytes());
+        response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        response.extend_from_slice(&rdata);
+    }
+
+    response
+}
+
+fn to_buffer(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool_with_size(BufferSize::Small);
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+/// A DNS server that answers the specified number of queries via UDP with the specified response
+/// code and addresses, counting the queries it has received.
+fn spawn_udp_server(
+    mut socket: UdpSocket,
+    query_count: usize,
+    rcode: u8,
+    truncated: bool,
+    answers: Vec<IpAddr>,
+) -> (LocalJoinHandle<()>, Rc<Cell<usize>>) {
+    let queries = Rc::new(Cell::new(0));
+    let queries_clone = Rc::clone(&queries);
+
+    let server = spawn(async move {
+        for _ in 0..query_count {
+            let (query, client) = socket
+                .receive_from(PinnedBuffer::from_pool_with_size(BufferSize::Small))
+                .await
+                .unwrap();
+
+            queries_clone.set(queries_clone.get() + 1);
+
+            let response = respond(query.as_slice(), rcode, truncated, &answers);
+            _ = socket.send_to(to_buffer(&response), client).await;
+        }
+    });
+
+    (server, queries)
+}
+
+fn resolver_for(server: SocketAddr) -> Resolver {
+    Resolver::new(
+        ResolverOptions::new()
+            .server(server)
+            .timeout(Duration::from_millis(500))
+            .attempts(1),
+    )
+    .unwrap()
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn resolves_and_caches_addresses() {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let server = socket.local_addr().unwrap();
+
+    let (server_task, queries) = spawn_udp_server(
+        socket,
+        4,
+        0,
+        false,
+        vec![
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(),
+        ],
+    );
+
+    let resolver = resolver_for(server);
+
+    let addrs = resolver.resolve("example.com", 443).await.unwrap();
+    assert_eq!(
+        addrs,
+        [
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 443)),
+            SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 443)),
+        ]
+    );
+
+    // One query for each address family.
+    assert_eq!(queries.get(), 2);
+
+    // The second time, the addresses come from the cache.
+    let addrs = resolver.resolve("EXAMPLE.com.", 80).await.unwrap();
+    assert_eq!(addrs.len(), 2);
+    assert_eq!(queries.get(), 2);
+
+    resolver.clear_cache();
+    resolver.resolve("example.com", 80).await.unwrap();
+    assert_eq!(queries.get(), 4);
+
+    server_task.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn returns_literals_without_querying() {
+    let resolver = resolver_for(SocketAddr::from((Ipv4Addr::LOCALHOST, 9)));
+
+    assert_eq!(
+        resolver.lookup_ip("192.0.2.7").await.unwrap(),
+        [IpAddr::from(Ipv4Addr::new(192, 0, 2, 7))]
+    );
+    assert_eq!(
+        resolver.lookup_ip("[::1]").await.unwrap(),
+        [IpAddr::from(Ipv6Addr::LOCALHOST)]
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn reports_nonexistent_name_as_not_found() {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let server = socket.local_addr().unwrap();
+
+    let (server_task, _) = spawn_udp_server(socket, 2, 3, false, Vec::new());
+
+    let resolver = resolver_for(server);
+
+    match resolver.lookup_ip("missing.example").await {
+        Err(io::Error::StdIo(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    server_task.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn times_out_if_server_does_not_respond() {
+    // Bound but never read from, so queries go unanswered.
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let server = socket.local_addr().unwrap();
+
+    let resolver = Resolver::new(
+        ResolverOptions::new()
+            .server(server)
+            .timeout(Duration::from_millis(100))
+            .attempts(2),
+    )
+    .unwrap();
+
+    assert!(matches!(
+        resolver.lookup_ip("example.com").await,
+        Err(io::Error::TimedOut)
+    ));
+
+    drop(socket);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn retries_truncated_response_over_tcp() {
+    let socket =
+        UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, TCP_FALLBACK_PORT))).unwrap();
+    let listener = TcpListener::bind(TCP_FALLBACK_PORT.try_into().unwrap()).unwrap();
+
+    let (udp_server, udp_queries) = spawn_udp_server(socket, 2, 0, true, Vec::new());
+
+    let addr = IpAddr::from(Ipv4Addr::new(192, 0, 2, 9));
+
+    let tcp_server = spawn(async move {
+        // One connection for each address family.
+        for _ in 0..2 {
+            let mut connection = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+
+            while received.len() < 2
+                || received.len() < 2 + u16::from_be_bytes([received[0], received[1]]) as usize
+            {
+                let buffer = connection
+                    .receive(PinnedBuffer::from_pool())
+                    .await
+                    .into_inner()
+                    .unwrap();
+
+                assert_ne!(buffer.len(), 0);
+                received.extend_from_slice(buffer.as_slice());
+            }
+
+            let response = respond(&received[2..], 0, false, &[addr]);
+
+            let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+            framed.extend_from_slice(&response);
+
+            connection
+                .send(to_buffer(&framed))
+                .await
+                .into_inner()
+                .unwrap();
+        }
+    });
+
+    let resolver = resolver_for(SocketAddr::from((Ipv4Addr::LOCALHOST, TCP_FALLBACK_PORT)));
+
+    assert_eq!(resolver.lookup_ip("big.example").await.unwrap(), [addr]);
+    assert_eq!(udp_queries.get(), 2);
+
+    udp_server.await;
+    tcp_server.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn requires_a_server() {
+    assert!(matches!(
+        Resolver::new(ResolverOptions::new()),
+        Err(io::Error::InvalidOptions(_))
+    ));
+}
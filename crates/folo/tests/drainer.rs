@@ -0,0 +1,73 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{Drainer, TcpConnection, TcpListener},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const PORT: u16 = 41_270;
+
+async fn connected_pair(listener: &TcpListener) -> (TcpConnection, TcpConnection) {
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        listener.local_addr().port(),
+    ))));
+
+    let server = listener.accept().await.unwrap();
+    (server, client.await.unwrap())
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn drain_waits_for_connections_to_finish() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+    let drainer = Drainer::new();
+
+    let (server, _client) = connected_pair(&listener).await;
+    let connection = drainer.track(server);
+    assert_eq!(drainer.active_connections(), 1);
+
+    let handler = spawn({
+        let drainer = drainer.clone();
+
+        async move {
+            drainer.shutdown_token().cancelled().await;
+            assert!(drainer.is_draining());
+            drop(connection);
+        }
+    });
+
+    assert_eq!(drainer.drain(Duration::from_secs(10)).await, 0);
+    assert_eq!(drainer.active_connections(), 0);
+
+    handler.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn drain_force_cancels_stuck_connections() {
+    let listener = TcpListener::bind((PORT + 1).try_into().unwrap()).unwrap();
+    let drainer = Drainer::new();
+
+    let (server, _client) = connected_pair(&listener).await;
+    let mut connection = drainer.track(server);
+
+    // This handler ignores the shutdown signal and waits for data that never arrives.
+    let handler = spawn(async move {
+        let result = connection
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner();
+
+        // Either an error or an empty receive, depending on which of the cancellation and the
+        // shutdown took effect first.
+        assert!(result.map_or(true, |buffer| buffer.len() == 0));
+    });
+
+    assert_eq!(drainer.drain(Duration::from_millis(100)).await, 1);
+    assert_eq!(drainer.active_connections(), 0);
+
+    handler.await;
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "futures-io")]
+
+use folo::{
+    io,
+    net::{ConnectOptions, TcpConnection, TcpServerBuilder},
+};
+use folo_testing::init_test_worker;
+use futures::{AsyncReadExt, AsyncWriteExt};
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_262;
+const UNFLUSHED_PORT: u16 = 41_307;
+const REGISTERED_IO_PORT: u16 = 41_315;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_connection_round_trips_via_futures_io() {
+    let mut server = TcpServerBuilder::new()
+        .port(PORT.try_into().unwrap())
+        .on_accept(echo_until_closed)
+        .build()
+        .await
+        .unwrap();
+
+    // Larger than a pooled buffer, so both directions take multiple staged operations.
+    let payload: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)))
+        .await
+        .unwrap();
+
+    connection.write_all(&payload).await.unwrap();
+    connection.close().await.unwrap();
+
+    let mut received = Vec::new();
+    connection.read_to_end(&mut received).await.unwrap();
+
+    assert_eq!(received, payload);
+
+    server.stop();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn write_is_sent_without_flush() {
+    let mut server = TcpServerBuilder::new()
+        .port(UNFLUSHED_PORT.try_into().unwrap())
+        .on_accept(reply_to_hello)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection =
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, UNFLUSHED_PORT)))
+            .await
+            .unwrap();
+
+    // The peer only replies once it has received our data, so this would hang if the write did
+    // not start sending until the next write, flush or close.
+    connection.write_all(b"hello").await.unwrap();
+
+    let mut reply = [0; 5];
+    connection.read_exact(&mut reply).await.unwrap();
+
+    assert_eq!(&reply, b"world");
+
+    server.stop();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn registered_io_connection_round_trips_via_futures_io() {
+    let mut server = TcpServerBuilder::new()
+        .port(REGISTERED_IO_PORT.try_into().unwrap())
+        .on_accept(echo_until_closed)
+        .build()
+        .await
+        .unwrap();
+
+    // Larger than a Registered I/O slot, so each staged send is split over multiple slots.
+    let payload: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+
+    let mut connection = TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, REGISTERED_IO_PORT)),
+        ConnectOptions::new().registered_io(true),
+    )
+    .await
+    .unwrap();
+
+    assert!(connection.is_registered_io());
+
+    connection.write_all(&payload).await.unwrap();
+    connection.close().await.unwrap();
+
+    let mut received = Vec::new();
+    connection.read_to_end(&mut received).await.unwrap();
+
+    assert_eq!(received, payload);
+
+    server.stop();
+}
+
+async fn reply_to_hello(mut connection: TcpConnection) -> io::Result<()> {
+    let mut request = [0; 5];
+    connection.read_exact(&mut request).await?;
+    assert_eq!(&request, b"hello");
+
+    connection.write_all(b"world").await?;
+    connection.flush().await?;
+
+    Ok(())
+}
+
+async fn echo_until_closed(mut connection: TcpConnection) -> io::Result<()> {
+    let mut data = Vec::new();
+    connection.read_to_end(&mut data).await?;
+
+    connection.write_all(&data).await?;
+    connection.close().await?;
+
+    Ok(())
+}
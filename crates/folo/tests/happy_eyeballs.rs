@@ -0,0 +1,50 @@
+use folo::{
+    io,
+    net::{self, ConnectOptions, TcpListener},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::time::Duration;
+
+const PORT: u16 = 41_274;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connects_by_host_name() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let server = spawn(async move { listener.accept().await.unwrap() });
+
+    // "localhost" resolves to both ::1 and 127.0.0.1 - whichever the listener accepts wins.
+    let client = net::connect("localhost", PORT).await.unwrap();
+
+    let server = server.await;
+    assert_eq!(client.peer_addr().unwrap().port(), PORT);
+    assert_eq!(server.local_addr().unwrap().port(), PORT);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connects_to_ip_literal() {
+    let port = PORT + 1;
+    let listener = TcpListener::bind(port.try_into().unwrap()).unwrap();
+
+    let server = spawn(async move { listener.accept().await.unwrap() });
+
+    let client = net::connect("127.0.0.1", port).await.unwrap();
+    assert!(client.peer_addr().unwrap().is_ipv4());
+
+    server.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn fails_when_nothing_listens() {
+    // Nobody listens on this port, so every attempt is refused.
+    let result = net::connect_with(
+        "localhost",
+        PORT + 2,
+        ConnectOptions::new().timeout(Duration::from_secs(10)),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(!matches!(result, Err(io::Error::TimedOut)));
+}
@@ -0,0 +1,189 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{
+        http1::{Client, ClientOptions, Request},
+        pool::ConnectionPool,
+        TcpConnection, TcpListener,
+    },
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const KEEP_ALIVE_PORT: u16 = 41_302;
+const CHUNKED_PORT: u16 = 41_303;
+const CONNECTION_CLOSE_PORT: u16 = 41_304;
+const TIMEOUT_PORT: u16 = 41_305;
+
+fn to_buffer(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+/// Receives a request without a body and answers it with the canned response.
+async fn serve(connection: &mut TcpConnection, response: &[u8]) -> String {
+    let mut request = Vec::new();
+
+    while !request.ends_with(b"\r\n\r\n") {
+        let buffer = connection
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+        assert_ne!(buffer.len(), 0, "client closed the connection");
+
+        request.extend_from_slice(buffer.as_slice());
+    }
+
+    connection
+        .send(to_buffer(response))
+        .await
+        .into_inner()
+        .unwrap();
+
+    String::from_utf8(request).unwrap()
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn keep_alive_connection_is_reused() {
+    let listener = TcpListener::bind(KEEP_ALIVE_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, KEEP_ALIVE_PORT));
+
+    let pool = ConnectionPool::new();
+    let client = Client::new(ClientOptions::new().connection_pool(pool.clone()));
+
+    let server = async {
+        let mut connection = listener.accept().await.unwrap();
+
+        let first = serve(
+            &mut connection,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst",
+        )
+        .await;
+        let second = serve(
+            &mut connection,
+            b"HTTP/1.1 201 Created\r\nContent-Length: 6\r\n\r\nsecond",
+        )
+        .await;
+
+        (first, second)
+    };
+
+    let requests = async {
+        let first = client
+            .send(
+                target,
+                Request::get("/items").header("Accept", "text/plain"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(pool.idle_connections(target), 1);
+
+        let second = client.send(target, Request::get("/other")).await.unwrap();
+
+        (first, second)
+    };
+
+    let ((first_request, second_request), (first, second)) =
+        futures::future::join(server, requests).await;
+
+    assert!(first_request.starts_with("GET /items HTTP/1.1\r\n"));
+    assert!(first_request.contains(&format!("Host: {target}\r\n")));
+    assert!(first_request.contains("Accept: text/plain\r\n"));
+    assert!(second_request.starts_with("GET /other HTTP/1.1\r\n"));
+
+    assert_eq!(first.status(), 200);
+    assert_eq!(first.reason(), "OK");
+    assert_eq!(first.body(), b"first");
+
+    assert_eq!(second.status(), 201);
+    assert!(second.is_success());
+    assert_eq!(second.body(), b"second");
+
+    assert_eq!(pool.idle_connections(target), 1);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn chunked_body_is_decoded() {
+    let listener = TcpListener::bind(CHUNKED_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, CHUNKED_PORT));
+
+    let client = Client::new(ClientOptions::new());
+
+    let server = async {
+        let mut connection = listener.accept().await.unwrap();
+
+        serve(
+            &mut connection,
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n",
+        )
+        .await;
+    };
+
+    let (_, response) = futures::future::join(server, client.send(target, Request::get("/"))).await;
+    let response = response.unwrap();
+
+    assert_eq!(response.header("transfer-encoding"), Some("chunked"));
+    assert_eq!(response.body(), b"hello, world");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connection_close_is_not_reused() {
+    let listener = TcpListener::bind(CONNECTION_CLOSE_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, CONNECTION_CLOSE_PORT));
+
+    let pool = ConnectionPool::new();
+    let client = Client::new(ClientOptions::new().connection_pool(pool.clone()));
+
+    let server = async {
+        let mut connection = listener.accept().await.unwrap();
+
+        serve(
+            &mut connection,
+            b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        )
+        .await;
+    };
+
+    let (_, response) =
+        futures::future::join(server, client.send(target, Request::get("/missing"))).await;
+    let response = response.unwrap();
+
+    assert_eq!(response.status(), 404);
+    assert!(!response.is_success());
+    assert!(response.body().is_empty());
+
+    assert_eq!(pool.idle_connections(target), 0);
+    assert_eq!(pool.active_connections(target), 0);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn unanswered_request_times_out() {
+    let listener = TcpListener::bind(TIMEOUT_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, TIMEOUT_PORT));
+
+    let pool = ConnectionPool::new();
+    let client = Client::new(
+        ClientOptions::new()
+            .timeout(Duration::from_millis(100))
+            .connection_pool(pool.clone()),
+    );
+
+    // The server accepts the connection but never responds.
+    let (accepted, response) = futures::future::join(
+        listener.accept(),
+        client.send(target, Request::get("/slow")),
+    )
+    .await;
+    let _accepted = accepted.unwrap();
+
+    assert!(matches!(response, Err(io::Error::TimedOut)));
+
+    // A connection with an exchange in progress must not be reused.
+    assert_eq!(pool.idle_connections(target), 0);
+}
@@ -0,0 +1,71 @@
+use folo::{
+    io::{BufferSize, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
+};
+
+const PORT: u16 = 41_280;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn ingest_ring_preserves_order() {
+    const TOTAL_LEN: usize = 4 * 1024 * 1024;
+
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        PORT,
+    ))));
+
+    let mut server = listener.accept().await.unwrap();
+    let client = client.await.unwrap();
+
+    let sender = spawn(async move {
+        let mut sent = 0;
+
+        while sent < TOTAL_LEN {
+            let mut buffer = PinnedBuffer::from_pool();
+            let len = buffer.len().min(TOTAL_LEN - sent);
+
+            for (i, b) in buffer.as_mut_slice_with_len(len).iter_mut().enumerate() {
+                *b = ((sent + i) % 251) as u8;
+            }
+
+            server.send(buffer).await.into_inner().unwrap();
+            sent += len;
+        }
+
+        // Dropping the connection closes it, ending the data seen by the ring.
+    });
+
+    let mut ring = client.ingest_ring(NonZeroUsize::new(4).unwrap(), BufferSize::Small);
+    let mut received = 0;
+
+    while ring.ready().await.unwrap() {
+        // Parse in small steps that often straddle region boundaries.
+        let chunk_len = ring.available().min(1000);
+
+        let chunk = ring
+            .regions()
+            .flatten()
+            .take(chunk_len)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for (i, b) in chunk.iter().enumerate() {
+            assert_eq!(*b, ((received + i) % 251) as u8);
+        }
+
+        ring.commit(chunk_len);
+        received += chunk_len;
+    }
+
+    assert_eq!(received, TOTAL_LEN);
+
+    sender.await;
+}
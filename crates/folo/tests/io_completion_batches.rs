@@ -0,0 +1,59 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::socket_pair,
+    rt::{RuntimeBuilder, RuntimeConfig},
+};
+use futures::future::join_all;
+use std::num::NonZeroUsize;
+
+const PAIR_COUNT: usize = 16;
+
+#[test]
+fn tiny_batches_still_complete_all_io() {
+    let config = RuntimeConfig::new().io_completion_max_batches(NonZeroUsize::new(3).unwrap());
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .config(config)
+        .io_completion_batch_size(NonZeroUsize::MIN)
+        .build()
+        .unwrap();
+
+    let (tx, rx) = oneshot::channel();
+
+    folo.spawn_on_any(|| async move {
+        let pairs = (0..PAIR_COUNT)
+            .map(|_| socket_pair().unwrap())
+            .collect::<Vec<_>>();
+
+        // Many operations complete at the same time, so the driver has to pick them up over
+        // multiple batches and cycles.
+        let received = join_all(pairs.into_iter().enumerate().map(
+            |(index, (mut first, mut second))| async move {
+                let mut buffer = PinnedBuffer::from_pool();
+                buffer.as_mut_slice_with_len(1)[0] = index as u8;
+                first.send(buffer).await.into_inner().unwrap();
+
+                let received = second
+                    .receive(PinnedBuffer::from_pool())
+                    .await
+                    .into_inner()
+                    .unwrap();
+
+                received.as_slice().to_vec()
+            },
+        ))
+        .await;
+
+        _ = tx.send(received);
+    });
+
+    let received = rx.recv().unwrap();
+
+    for (index, data) in received.iter().enumerate() {
+        assert_eq!(data.as_slice(), [index as u8]);
+    }
+
+    folo.stop();
+    folo.wait();
+}
@@ -0,0 +1,81 @@
+use folo::{
+    io::{LatencySlos, OperationKind, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+    rt::{sleep, spawn, RuntimeBuilder},
+};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const PORT: u16 = 41_277;
+
+#[test]
+fn slow_receive_is_reported_as_breach() {
+    let breaches = Arc::new(Mutex::new(Vec::new()));
+
+    let slos = LatencySlos::new()
+        .threshold(OperationKind::Receive, Duration::from_millis(50))
+        .on_breach({
+            let breaches = Arc::clone(&breaches);
+            move |breach| breaches.lock().unwrap().push(*breach)
+        });
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .max_processors(1)
+        .latency_slos(slos)
+        .build()
+        .unwrap();
+
+    let (tx, rx) = oneshot::channel();
+
+    folo.spawn_on_any(|| async move {
+        let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+        let client = spawn(TcpConnection::connect(SocketAddr::from((
+            Ipv4Addr::LOCALHOST,
+            PORT,
+        ))));
+
+        let mut server = listener.accept().await.unwrap();
+        let mut client = client.await.unwrap();
+
+        // The data only arrives well after the threshold has passed.
+        let sender = spawn(async move {
+            sleep(Duration::from_millis(250)).await;
+
+            server
+                .send(PinnedBuffer::from_boxed_slice(Box::new([1, 2, 3])))
+                .await
+                .into_inner()
+                .unwrap();
+
+            server
+        });
+
+        client
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        sender.await;
+        _ = tx.send(());
+    });
+
+    rx.recv().unwrap();
+
+    folo.stop();
+    folo.wait();
+
+    let breaches = breaches.lock().unwrap();
+    assert!(breaches
+        .iter()
+        .any(|breach| breach.kind == OperationKind::Receive
+            && breach.duration > Duration::from_millis(50)));
+    assert!(breaches
+        .iter()
+        .all(|breach| breach.kind == OperationKind::Receive));
+}
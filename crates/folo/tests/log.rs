@@ -0,0 +1,58 @@
+use folo::log::{AsyncWriterBuilder, OverflowPolicy};
+use folo_testing::init_test_worker;
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("folo-log-{}-{name}", std::process::id()))
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn records_written_to_file() {
+    let path = temp_path("records");
+
+    let log = AsyncWriterBuilder::new().file(&path).await.unwrap();
+
+    for i in 0..100 {
+        assert!(log.write_line(format_args!("record {i}")));
+    }
+
+    log.flush().await.unwrap();
+    assert_eq!(log.dropped_records(), 0);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let lines = contents.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines.len(), 100);
+    assert_eq!(lines[0], "record 0");
+    assert_eq!(lines[99], "record 99");
+
+    drop(log);
+    _ = std::fs::remove_file(&path);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn overflow_drops_oldest_records() {
+    let path = temp_path("overflow");
+
+    let log = AsyncWriterBuilder::new()
+        .max_queued_bytes(12)
+        .overflow_policy(OverflowPolicy::DropOldest)
+        .file(&path)
+        .await
+        .unwrap();
+
+    // The background task cannot run before we yield, so all of these land in the queue at once.
+    assert!(log.write_record(b"first\n"));
+    assert!(log.write_record(b"second\n"));
+    assert!(log.write_record(b"third\n"));
+    assert!(!log.write_record(b"far too long to ever fit\n"));
+
+    log.flush().await.unwrap();
+    assert_eq!(log.dropped_records(), 3);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(contents, "third\n");
+
+    drop(log);
+    _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,81 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    ipc::{MailslotClient, MailslotServer},
+};
+use folo_testing::init_test_worker;
+
+fn mailslot_name(name: &str) -> String {
+    format!(r"\\.\mailslot\folo-{}-{name}", std::process::id())
+}
+
+fn to_buffer(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn messages_arrive_whole_and_in_order() {
+    let name = mailslot_name("order");
+    let server = MailslotServer::create(&name).await.unwrap();
+    assert_eq!(server.next_message_len().unwrap(), None);
+
+    let client = MailslotClient::open(&name).await.unwrap();
+    client.send(to_buffer(b"first")).await.into_inner().unwrap();
+    client
+        .send(to_buffer(b"second message"))
+        .await
+        .into_inner()
+        .unwrap();
+
+    assert_eq!(server.next_message_len().unwrap(), Some(5));
+
+    let received = server
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(received.as_slice(), b"first");
+
+    let received = server
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(received.as_slice(), b"second message");
+
+    assert_eq!(server.next_message_len().unwrap(), None);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_waits_for_message() {
+    let name = mailslot_name("wait");
+    let server = MailslotServer::create(&name).await.unwrap();
+    let client = MailslotClient::open(&name).await.unwrap();
+
+    let (received, sent) = futures::future::join(
+        server.receive(PinnedBuffer::from_pool()),
+        client.send(to_buffer(b"hello")),
+    )
+    .await;
+
+    sent.into_inner().unwrap();
+    assert_eq!(received.into_inner().unwrap().as_slice(), b"hello");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn duplicate_name_is_rejected() {
+    let name = mailslot_name("duplicate");
+    let _server = MailslotServer::create(&name).await.unwrap();
+
+    assert!(MailslotServer::create(&name).await.is_err());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn open_missing_mailslot_fails() {
+    assert!(MailslotClient::open(&mailslot_name("missing"))
+        .await
+        .is_err());
+}
@@ -0,0 +1,69 @@
+use folo::rt::{current_runtime_client, spawn_on_any, RuntimeBuilder, DEFAULT_RUNTIME_NAME};
+use std::thread;
+
+#[test]
+fn named_runtimes_coexist() {
+    let first = RuntimeBuilder::new()
+        .name("first")
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let second = RuntimeBuilder::new()
+        .name("second")
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    assert_eq!(first.name(), "first");
+    assert_eq!(second.name(), "second");
+
+    // The entrypoint thread is not owned by either runtime.
+    assert!(current_runtime_client().is_none());
+
+    let (tx, rx) = oneshot::channel();
+
+    // Work spawned via a client runs on the runtime of that client and the free functions called
+    // from there target the same runtime.
+    first.spawn_on_any(|| async move {
+        let observed = spawn_on_any(|| async {
+            (
+                current_runtime_client().unwrap().name().to_string(),
+                thread::current().name().unwrap().to_string(),
+            )
+        })
+        .await;
+
+        _ = tx.send(observed);
+    });
+
+    let (runtime_name, thread_name) = rx.recv().unwrap();
+    assert_eq!(runtime_name, "first");
+    assert!(thread_name.starts_with("first-"));
+
+    let (tx, rx) = oneshot::channel();
+
+    second.spawn_on_any(|| async move {
+        _ = tx.send(current_runtime_client().unwrap().name().to_string());
+    });
+
+    assert_eq!(rx.recv().unwrap(), "second");
+
+    first.stop();
+    second.stop();
+    first.wait();
+    second.wait();
+}
+
+#[test]
+fn unnamed_runtime_has_default_name() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    assert_eq!(folo.name(), DEFAULT_RUNTIME_NAME);
+
+    folo.stop();
+    folo.wait();
+}
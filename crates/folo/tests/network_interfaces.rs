@@ -0,0 +1,39 @@
+use folo::net::{interface_changes, interfaces};
+use folo_testing::init_test_worker;
+use std::net::{IpAddr, Ipv4Addr};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn lists_loopback_interface() {
+    let interfaces = interfaces().await.unwrap();
+
+    let loopback = interfaces
+        .iter()
+        .find(|interface| {
+            interface
+                .addresses()
+                .iter()
+                .any(|address| address.addr() == IpAddr::from(Ipv4Addr::LOCALHOST))
+        })
+        .expect("no interface has the IPv4 loopback address");
+
+    assert!(loopback.is_loopback());
+    assert!(loopback.is_up());
+    assert_ne!(loopback.index(), 0);
+    assert!(!loopback.name().is_empty());
+
+    let address = loopback
+        .addresses()
+        .iter()
+        .find(|address| address.addr() == IpAddr::from(Ipv4Addr::LOCALHOST))
+        .unwrap();
+    assert_eq!(address.prefix_length(), 8);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn subscribes_and_unsubscribes_from_changes() {
+    let changes = interface_changes().unwrap();
+    drop(changes);
+
+    // Subscribing again after unsubscribing works just the same.
+    let _changes = interface_changes().unwrap();
+}
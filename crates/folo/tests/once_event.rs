@@ -0,0 +1,36 @@
+use folo::{
+    rt::{sleep, spawn},
+    util::once_event::{Elapsed, OnceEvent},
+};
+use folo_testing::init_test_worker;
+use std::{rc::Rc, time::Duration};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn with_timeout_elapses_and_releases_slot() {
+    let storage = Rc::new(OnceEvent::<usize>::new_slab_storage());
+    let (sender, receiver) = OnceEvent::new_in_rc(Rc::clone(&storage));
+
+    let result = receiver.with_timeout(Duration::from_millis(50)).await;
+    assert_eq!(result, Err(Elapsed));
+
+    // The sender may still set the result, which is simply dropped.
+    sender.set(42);
+    assert!(storage.borrow().is_empty());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn with_timeout_receives_result_in_time() {
+    let storage = Rc::new(OnceEvent::<usize>::new_slab_storage());
+    let (sender, receiver) = OnceEvent::new_in_rc(Rc::clone(&storage));
+
+    let setter = spawn(async move {
+        sleep(Duration::from_millis(10)).await;
+        sender.set(42);
+    });
+
+    let result = receiver.with_timeout(Duration::from_secs(10)).await;
+    assert_eq!(result, Ok(42));
+
+    setter.await;
+    assert!(storage.borrow().is_empty());
+}
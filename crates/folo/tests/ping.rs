@@ -0,0 +1,58 @@
+use folo::{
+    io,
+    net::{ping, ping_with, PingOptions},
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn pings_ipv4_loopback() {
+    let addr = IpAddr::from(Ipv4Addr::LOCALHOST);
+
+    let reply = ping(addr).await.unwrap();
+
+    assert_eq!(reply.address(), addr);
+    assert!(reply.ttl().is_some());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn pings_ipv6_loopback() {
+    let addr = IpAddr::from(Ipv6Addr::LOCALHOST);
+
+    let reply = ping(addr).await.unwrap();
+
+    assert_eq!(reply.address(), addr);
+    assert_eq!(reply.ttl(), None);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn pings_concurrently_with_custom_payload() {
+    let addr = IpAddr::from(Ipv4Addr::LOCALHOST);
+    let options = PingOptions::new()
+        .payload(vec![0xAB; 1000])
+        .timeout(Duration::from_secs(1));
+
+    let (first, second) =
+        futures::future::join(ping_with(addr, options.clone()), ping_with(addr, options)).await;
+
+    assert_eq!(first.unwrap().address(), addr);
+    assert_eq!(second.unwrap().address(), addr);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn rejects_invalid_options() {
+    let addr = IpAddr::from(Ipv4Addr::LOCALHOST);
+
+    assert!(matches!(
+        ping_with(addr, PingOptions::new().timeout(Duration::ZERO)).await,
+        Err(io::Error::InvalidOptions(_))
+    ));
+
+    assert!(matches!(
+        ping_with(addr, PingOptions::new().payload(vec![0; 70_000])).await,
+        Err(io::Error::InvalidOptions(_))
+    ));
+}
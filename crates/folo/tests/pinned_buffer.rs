@@ -0,0 +1,109 @@
+use folo::io::PinnedBuffer;
+
+#[test]
+fn vec_is_used_without_copying() {
+    let mut vec = Vec::with_capacity(100);
+    vec.extend_from_slice(b"hello world");
+    let address = vec.as_ptr();
+
+    let mut buffer = PinnedBuffer::from_vec(vec);
+
+    // Spare capacity of the vector is not part of the buffer.
+    assert_eq!(buffer.capacity(), 11);
+    assert_eq!(buffer.len(), 11);
+    assert_eq!(buffer.as_slice().as_ptr(), address);
+
+    buffer.set_start(6);
+    buffer.set_len(5);
+    assert_eq!(buffer.as_slice(), b"world");
+    buffer.as_mut_slice().copy_from_slice(b"folks");
+
+    // The active region does not matter for getting the vector back.
+    let vec = buffer.into_inner_vec();
+    assert_eq!(vec.as_ptr(), address);
+    assert_eq!(vec, b"hello folks");
+}
+
+#[test]
+fn boxed_slice_can_be_recovered_as_vec() {
+    let slice: Box<[u8]> = Box::new(*b"data");
+    let address = slice.as_ptr();
+
+    let buffer = PinnedBuffer::from_boxed_slice(slice);
+    let vec = buffer.into_inner_vec();
+
+    assert_eq!(vec.as_ptr(), address);
+    assert_eq!(vec, b"data");
+}
+
+#[test]
+#[should_panic]
+fn pooled_buffer_cannot_be_recovered_as_vec() {
+    PinnedBuffer::from_pool().into_inner_vec();
+}
+
+#[test]
+fn aligned_buffer_starts_at_boundary() {
+    let mut buffer = PinnedBuffer::new_aligned(8192, 4096);
+
+    assert_eq!(buffer.capacity(), 8192);
+    assert_eq!(buffer.len(), 8192);
+    assert_eq!(buffer.as_slice().as_ptr() as usize % 4096, 0);
+    assert!(buffer.as_slice().iter().all(|b| *b == 0));
+
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    assert_eq!(buffer.as_slice(), b"hello");
+}
+
+#[test]
+#[should_panic]
+fn aligned_buffer_cannot_be_recovered_as_vec() {
+    PinnedBuffer::new_aligned(4096, 4096).into_inner_vec();
+}
+
+#[test]
+fn split_to_hands_out_front_of_active_region() {
+    let mut buffer = PinnedBuffer::from_vec(b"xxheaderbody....".to_vec());
+    buffer.set_start(2);
+    buffer.set_len(10);
+
+    let mut header = buffer.split_to(6);
+    assert_eq!(header.as_slice(), b"header");
+    assert_eq!(header.start(), 2);
+    assert_eq!(header.capacity(), 8);
+    assert_eq!(buffer.as_slice(), b"body");
+    assert_eq!(buffer.start(), 0);
+    assert_eq!(buffer.capacity(), 8);
+
+    // The pieces do not overlap, so writing to one does not affect the other.
+    header.as_mut_slice().copy_from_slice(b"HEADER");
+    buffer.as_mut_slice().copy_from_slice(b"BODY");
+    assert_eq!(header.as_slice(), b"HEADER");
+    assert_eq!(buffer.as_slice(), b"BODY");
+
+    // The unused capacity after the active region stays with the remainder.
+    let buffer = buffer.use_remainder();
+    assert_eq!(buffer.as_slice(), b"....");
+}
+
+#[test]
+fn split_off_hands_out_back_of_active_region() {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(11)
+        .copy_from_slice(b"hello world");
+
+    let mut world = buffer.split_off(6);
+    assert_eq!(buffer.as_slice(), b"hello ");
+    assert_eq!(buffer.capacity(), 6);
+    assert_eq!(world.as_slice(), b"world");
+
+    // Pieces can be split further.
+    let rest = world.split_off(1);
+    assert_eq!(world.as_slice(), b"w");
+    assert_eq!(rest.as_slice(), b"orld");
+
+    drop(buffer);
+    drop(world);
+    assert_eq!(rest.as_slice(), b"orld");
+}
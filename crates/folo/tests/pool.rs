@@ -0,0 +1,74 @@
+use folo::net::{pool::ConnectionPoolBuilder, TcpListener};
+use folo_testing::init_test_worker;
+use std::{
+    cell::Cell,
+    net::{Ipv4Addr, SocketAddr},
+    rc::Rc,
+};
+
+const REUSE_PORT: u16 = 41_290;
+const HEALTH_CHECK_PORT: u16 = 41_291;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn returned_connection_is_reused() {
+    let listener = TcpListener::bind(REUSE_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, REUSE_PORT));
+
+    let pool = ConnectionPoolBuilder::new().build();
+
+    let (connection, accepted) = futures::future::join(pool.get(target), listener.accept()).await;
+    let connection = connection.unwrap();
+    let _accepted = accepted.unwrap();
+
+    let local_addr = connection.local_addr().unwrap();
+    assert_eq!(pool.active_connections(target), 1);
+
+    drop(connection);
+    assert_eq!(pool.idle_connections(target), 1);
+    assert_eq!(pool.active_connections(target), 0);
+
+    // No new connection is accepted - we get the same one back.
+    let connection = pool.get(target).await.unwrap();
+    assert_eq!(connection.local_addr().unwrap(), local_addr);
+    assert_eq!(pool.idle_connections(target), 0);
+
+    connection.discard();
+    assert_eq!(pool.idle_connections(target), 0);
+    assert_eq!(pool.active_connections(target), 0);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn unhealthy_connection_is_replaced() {
+    let listener = TcpListener::bind(HEALTH_CHECK_PORT.try_into().unwrap()).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::LOCALHOST, HEALTH_CHECK_PORT));
+
+    let checks = Rc::new(Cell::new(0));
+
+    let pool = ConnectionPoolBuilder::new()
+        .health_check({
+            let checks = Rc::clone(&checks);
+            move |_| {
+                checks.set(checks.get() + 1);
+                false
+            }
+        })
+        .build();
+
+    let (connection, accepted) = futures::future::join(pool.get(target), listener.accept()).await;
+    let connection = connection.unwrap();
+    let _first_accepted = accepted.unwrap();
+
+    let first_local_addr = connection.local_addr().unwrap();
+    drop(connection);
+    assert_eq!(pool.idle_connections(target), 1);
+
+    // The idle connection fails the check, so a new one is established.
+    let (connection, accepted) = futures::future::join(pool.get(target), listener.accept()).await;
+    let connection = connection.unwrap();
+    let _second_accepted = accepted.unwrap();
+
+    assert_eq!(checks.get(), 1);
+    assert_ne!(connection.local_addr().unwrap(), first_local_addr);
+    assert_eq!(pool.idle_connections(target), 0);
+    assert_eq!(pool.active_connections(target), 1);
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "quinn")]
+
+use folo::net::QuinnRuntime;
+use folo_testing::init_test_worker;
+use futures::future::join;
+use quinn::{
+    rustls::{pki_types::PrivatePkcs8KeyDer, RootCertStore},
+    ClientConfig, Endpoint, EndpointConfig, ServerConfig,
+};
+use std::{
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::Arc,
+};
+
+const REQUEST: &[u8] = b"hello over QUIC";
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn quinn_handshake_and_stream_over_loopback() {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let certificate = certified.cert.der().clone();
+    let key = PrivatePkcs8KeyDer::from(certified.signing_key.serialize_der());
+
+    let server_config =
+        ServerConfig::with_single_cert(vec![certificate.clone()], key.into()).unwrap();
+    let server = Endpoint::new(
+        EndpointConfig::default(),
+        Some(server_config),
+        loopback_socket(),
+        Arc::new(QuinnRuntime),
+    )
+    .unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate).unwrap();
+
+    let mut client = Endpoint::new(
+        EndpointConfig::default(),
+        None,
+        loopback_socket(),
+        Arc::new(QuinnRuntime),
+    )
+    .unwrap();
+    client
+        .set_default_client_config(ClientConfig::with_root_certificates(Arc::new(roots)).unwrap());
+
+    let serve = async {
+        let connection = server.accept().await.unwrap().await.unwrap();
+        let (mut send, mut receive) = connection.accept_bi().await.unwrap();
+
+        let request = receive.read_to_end(1024).await.unwrap();
+        send.write_all(&request).await.unwrap();
+        send.finish().unwrap();
+
+        // The client closes the connection once it has the response.
+        connection.closed().await;
+    };
+
+    let request = async {
+        let connection = client
+            .connect(server_addr, "localhost")
+            .unwrap()
+            .await
+            .unwrap();
+        let (mut send, mut receive) = connection.open_bi().await.unwrap();
+
+        send.write_all(REQUEST).await.unwrap();
+        send.finish().unwrap();
+        let response = receive.read_to_end(1024).await.unwrap();
+
+        connection.close(0_u32.into(), b"done");
+        response
+    };
+
+    let ((), response) = join(serve, request).await;
+
+    assert_eq!(response, REQUEST);
+
+    client.wait_idle().await;
+    server.wait_idle().await;
+}
+
+fn loopback_socket() -> UdpSocket {
+    UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap()
+}
@@ -0,0 +1,52 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+};
+use folo_testing::init_test_worker;
+use socket2::{Domain, SockRef, Socket, Type};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    os::windows::io::{FromRawSocket, OwnedSocket},
+};
+
+const PORT: u16 = 41_284;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn socket2_interop() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, PORT));
+
+    // The operating system completes the handshake without waiting for us to accept, so a
+    // blocking connect does not hold up the listener.
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket.set_nodelay(true).unwrap();
+    socket.connect(&addr.into()).unwrap();
+
+    let mut client = TcpConnection::try_from(OwnedSocket::from(socket)).unwrap();
+    let server = listener.accept().await.unwrap();
+
+    // Options set before the handoff are preserved and can be inspected via socket2.
+    assert!(client.nodelay().unwrap());
+    assert!(SockRef::from(&client).nodelay().unwrap());
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(b"abc");
+    client.send(buffer).await.into_inner().unwrap();
+
+    let server_addr = server.local_addr().unwrap();
+    let raw = server.into_raw_socket().unwrap();
+
+    // SAFETY: We own the socket we just took out of the connection.
+    let server = unsafe { Socket::from_raw_socket(raw.0 as _) };
+
+    assert_eq!(server.local_addr().unwrap().as_socket(), Some(server_addr));
+
+    let mut received = [0; 3];
+    let mut read = 0;
+
+    while read < received.len() {
+        read += std::io::Read::read(&mut &server, &mut received[read..]).unwrap();
+    }
+
+    assert_eq!(&received, b"abc");
+}
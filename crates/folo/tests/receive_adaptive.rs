@@ -0,0 +1,54 @@
+use folo::{
+    io::{BufferSize, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_278;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_adaptive_grows_for_bulk_transfer() {
+    const TOTAL_LEN: usize = 4 * 1024 * 1024;
+
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        PORT,
+    ))));
+
+    let mut server = listener.accept().await.unwrap();
+    let mut client = client.await.unwrap();
+
+    let sender = spawn(async move {
+        let mut remaining = TOTAL_LEN;
+
+        while remaining > 0 {
+            let mut buffer = PinnedBuffer::from_pool();
+            let len = buffer.len().min(remaining);
+            buffer.as_mut_slice_with_len(len).fill(42);
+
+            server.send(buffer).await.into_inner().unwrap();
+            remaining -= len;
+        }
+    });
+
+    let mut received = 0;
+    let mut largest_capacity = 0;
+
+    while received < TOTAL_LEN {
+        let buffer = client.receive_adaptive().await.into_inner().unwrap();
+        assert_ne!(buffer.len(), 0);
+        assert!(buffer.as_slice().iter().all(|b| *b == 42));
+
+        received += buffer.len();
+        largest_capacity = largest_capacity.max(buffer.capacity());
+    }
+
+    assert_eq!(received, TOTAL_LEN);
+    assert_eq!(largest_capacity, BufferSize::Large.capacity());
+
+    sender.await;
+}
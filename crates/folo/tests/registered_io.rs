@@ -0,0 +1,240 @@
+use folo::{
+    io::{self, BufferSize, OperationResultExt, PinnedBuffer, RIO_SLOT_SIZE},
+    net::{ConnectOptions, TcpConnection, TcpListener, UdpSocket},
+    rt::{sleep, spawn},
+};
+use folo_testing::init_test_worker;
+use futures::future::join;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
+    time::Duration,
+};
+
+const TCP_PORT: u16 = 41_285;
+const TIMEOUT_PORT: u16 = 41_316;
+const CANCEL_PORT: u16 = 41_317;
+const URGENT_PORT: u16 = 41_318;
+const CLOSED_PORT: u16 = 41_319;
+
+/// How long the peer waits before sending, long enough for the receives under test to give up.
+const SEND_DELAY: Duration = Duration::from_millis(500);
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_registered_io_exchanges_data() {
+    let listener = TcpListener::bind(TCP_PORT.try_into().unwrap()).unwrap();
+
+    // Larger than one RIO slot, so the send is split into multiple requests.
+    const LEN: usize = RIO_SLOT_SIZE * 3 + 100;
+
+    let server = spawn(async move {
+        let mut connection = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+
+        while received.len() < LEN {
+            let buffer = connection
+                .receive(PinnedBuffer::from_pool())
+                .await
+                .into_inner()
+                .unwrap();
+
+            assert_ne!(buffer.len(), 0);
+            received.extend_from_slice(buffer.as_slice());
+        }
+
+        let mut reply = PinnedBuffer::from_pool();
+        reply.as_mut_slice_with_len(2).copy_from_slice(b"ok");
+        connection.send(reply).await.into_inner().unwrap();
+
+        received
+    });
+
+    let mut connection = TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, TCP_PORT)),
+        ConnectOptions::new().registered_io(true),
+    )
+    .await
+    .unwrap();
+
+    assert!(connection.is_registered_io());
+
+    let payload = (0..LEN).map(|i| i as u8).collect::<Vec<_>>();
+    connection
+        .send(PinnedBuffer::from_boxed_slice(
+            payload.clone().into_boxed_slice(),
+        ))
+        .await
+        .into_inner()
+        .unwrap();
+
+    let reply = connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(reply.as_slice(), b"ok");
+
+    assert_eq!(server.await, payload);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_registered_io_receive_times_out_without_losing_data() {
+    let (mut connection, server) = connect_registered_io(TIMEOUT_PORT).await;
+
+    let peer = spawn(send_hello_after_delay(server));
+
+    let buffer = PinnedBuffer::from_pool();
+    let len = buffer.len();
+
+    let error = connection
+        .receive_with_timeout(buffer, Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(matches!(error.inner, io::Error::TimedOut));
+
+    let (_, buffer) = error.into_inner_and_buffer();
+    assert_eq!(buffer.len(), len);
+    assert!(!connection.is_read_closed());
+
+    // The receive we gave up on remains in progress and its data goes to the next receive.
+    let buffer = connection.receive(buffer).await.into_inner().unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    peer.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_registered_io_receive_is_cancelable() {
+    let (mut connection, server) = connect_registered_io(CANCEL_PORT).await;
+
+    let peer = spawn(send_hello_after_delay(server));
+
+    let (handle, receive) = connection.receive_cancelable(PinnedBuffer::from_pool());
+
+    let cancel = async {
+        sleep(Duration::from_millis(50)).await;
+        handle.cancel();
+    };
+
+    let (result, ()) = join(receive, cancel).await;
+
+    let error = result.unwrap_err();
+    assert!(matches!(error.inner, io::Error::Canceled));
+
+    let (_, buffer) = error.into_inner_and_buffer();
+    let buffer = connection.receive(buffer).await.into_inner().unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    peer.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_registered_io_rejects_urgent_data() {
+    let (mut connection, _server) = connect_registered_io(URGENT_PORT).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(1).copy_from_slice(b"!");
+
+    let error = connection.send_urgent(buffer).await.unwrap_err();
+    assert!(
+        matches!(&error.inner, io::Error::StdIo(e) if e.kind() == std::io::ErrorKind::Unsupported)
+    );
+
+    let error = connection
+        .receive_urgent(PinnedBuffer::from_pool())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error.inner, io::Error::StdIo(e) if e.kind() == std::io::ErrorKind::Unsupported)
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_registered_io_closed_keeps_pending_data() {
+    let (mut connection, server) = connect_registered_io(CLOSED_PORT).await;
+
+    // Waits for the close while the data arrives, without consuming the data.
+    let closed = spawn(connection.closed());
+
+    // The peer sends data and then closes the connection.
+    send_hello_after_delay(server).await;
+
+    let buffer = connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    closed.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn udp_registered_io_exchanges_datagrams() {
+    let mut receiver =
+        UdpSocket::bind_with_registered_io(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender =
+        UdpSocket::bind_with_registered_io(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    assert!(receiver.is_registered_io());
+
+    receiver.connect(sender.local_addr().unwrap()).unwrap();
+    sender.connect(receiver.local_addr().unwrap()).unwrap();
+
+    let mut batches = receiver.receive_batches(NonZeroUsize::new(2).unwrap(), BufferSize::Small);
+
+    let receives = async {
+        let mut received = Vec::new();
+
+        while received.len() < 2 {
+            received.extend(batches.next_batch().await);
+        }
+
+        received
+    };
+
+    let sends = async {
+        for datagram in [b"first", b"other"] {
+            let mut buffer = PinnedBuffer::from_pool();
+            buffer
+                .as_mut_slice_with_len(datagram.len())
+                .copy_from_slice(datagram);
+            sender.send(buffer).await.into_inner().unwrap();
+        }
+    };
+
+    let (received, ()) = futures::future::join(receives, sends).await;
+
+    let mut received = received
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().as_slice().to_vec())
+        .collect::<Vec<_>>();
+    received.sort_unstable();
+
+    assert_eq!(received, vec![b"first".to_vec(), b"other".to_vec()]);
+}
+
+/// Connects a client that uses Registered I/O to a server connection that does not.
+async fn connect_registered_io(port: u16) -> (TcpConnection, TcpConnection) {
+    let listener = TcpListener::bind(port.try_into().unwrap()).unwrap();
+
+    let client = spawn(TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        ConnectOptions::new().registered_io(true),
+    ));
+
+    let server = listener.accept().await.unwrap();
+    let client = client.await.unwrap();
+
+    assert!(client.is_registered_io());
+
+    (client, server)
+}
+
+async fn send_hello_after_delay(mut connection: TcpConnection) {
+    sleep(SEND_DELAY).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    connection.send(buffer).await.into_inner().unwrap();
+}
@@ -0,0 +1,39 @@
+use folo::net::resolve;
+use folo_testing::init_test_worker;
+use futures::FutureExt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn resolves_localhost() {
+    let addrs = resolve("localhost", 1234).await.unwrap();
+
+    assert!(!addrs.is_empty());
+    assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+    assert!(addrs.iter().all(|addr| addr.port() == 1234));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn resolves_ip_literal() {
+    let addrs = resolve("127.0.0.1", 80).await.unwrap();
+
+    assert_eq!(
+        addrs,
+        vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 80)]
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn unknown_name_fails() {
+    // The .invalid top level domain is guaranteed to never resolve.
+    assert!(resolve("folo.invalid", 80).await.is_err());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn abandoned_lookup_is_canceled() {
+    // Polls once (starting the lookup) and then drops the future, which cancels the lookup. This
+    // must not crash or leak, regardless of whether the lookup had already completed.
+    _ = resolve("folo.invalid", 80).now_or_never();
+
+    // The resolver remains usable afterwards.
+    assert!(!resolve("localhost", 80).await.unwrap().is_empty());
+}
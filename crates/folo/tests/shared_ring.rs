@@ -0,0 +1,86 @@
+use folo::{
+    ipc::{SharedRingBuilder, SharedRingReader},
+    rt::{sleep, spawn},
+};
+use folo_testing::init_test_worker;
+use std::{process, time::Duration};
+
+fn unique_name(test: &str) -> String {
+    format!("folo-test-{test}-{}", process::id())
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn readers_receive_published_messages() {
+    let name = unique_name("publish");
+    let ring = SharedRingBuilder::new(&name).capacity(8).build().unwrap();
+
+    // Messages published before a reader attaches are not seen by it.
+    ring.publish(b"too early").unwrap();
+
+    let mut first = SharedRingReader::open(&name).unwrap();
+    let mut second = SharedRingReader::open(&name).unwrap();
+    assert_eq!(first.try_receive(), None);
+
+    ring.publish(b"hello").unwrap();
+    ring.publish(b"world").unwrap();
+
+    for reader in [&mut first, &mut second] {
+        assert_eq!(reader.receive().await.unwrap(), b"hello");
+        assert_eq!(reader.receive().await.unwrap(), b"world");
+        assert_eq!(reader.try_receive(), None);
+        assert_eq!(reader.missed(), 0);
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn waiting_reader_is_woken_up() {
+    let name = unique_name("wake");
+    let ring = SharedRingBuilder::new(&name).build().unwrap();
+    let mut reader = SharedRingReader::open(&name).unwrap();
+
+    let received = spawn(async move { reader.receive().await.unwrap() });
+
+    // Give the reader a chance to start waiting.
+    sleep(Duration::from_millis(50)).await;
+
+    ring.publish(b"wake up").unwrap();
+
+    assert_eq!(received.await, b"wake up");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn lagging_reader_misses_oldest_messages() {
+    let name = unique_name("lag");
+    let ring = SharedRingBuilder::new(&name).capacity(4).build().unwrap();
+    let mut reader = SharedRingReader::open(&name).unwrap();
+
+    for i in 0..10u8 {
+        ring.publish(&[i]).unwrap();
+    }
+
+    assert_eq!(reader.receive().await.unwrap(), [6]);
+    assert_eq!(reader.missed(), 6);
+
+    for i in 7..10u8 {
+        assert_eq!(reader.try_receive().unwrap(), [i]);
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn invalid_usage_fails() {
+    let name = unique_name("invalid");
+
+    assert!(SharedRingReader::open(&name).is_err());
+    assert!(SharedRingBuilder::new(&name).capacity(0).build().is_err());
+
+    let ring = SharedRingBuilder::new(&name)
+        .max_message_len(4)
+        .build()
+        .unwrap();
+
+    // There can only be one writer.
+    assert!(SharedRingBuilder::new(&name).build().is_err());
+
+    assert!(ring.publish(b"too long").is_err());
+    assert!(ring.publish(b"fits").is_ok());
+}
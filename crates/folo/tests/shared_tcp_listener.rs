@@ -0,0 +1,63 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::SharedTcpListener,
+    rt::{spawn, RuntimeBuilder},
+};
+use std::{
+    io::Read,
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    sync::mpsc,
+};
+
+const PORT: u16 = 41_288;
+const CONNECTION_COUNT: usize = 8;
+
+#[test]
+fn every_worker_accepts_on_shared_listener() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let listener = SharedTcpListener::bind(PORT.try_into().unwrap()).unwrap();
+    assert_eq!(listener.local_addr().port(), PORT);
+
+    // The port is bound exclusively, so nobody else can listen on it while we do.
+    assert!(std::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT))).is_err());
+
+    let (accepted_tx, accepted_rx) = mpsc::channel();
+
+    let accept_loops = folo.spawn_on_all(|| {
+        let listener = listener.clone();
+        let accepted_tx = accepted_tx.clone();
+
+        move || async move {
+            loop {
+                let mut connection = listener.accept().await.unwrap();
+                _ = accepted_tx.send(());
+
+                // The connection is handled on the worker that accepted it.
+                spawn(async move {
+                    let mut buffer = PinnedBuffer::from_pool();
+                    buffer.as_mut_slice_with_len(1)[0] = 42;
+                    connection.send(buffer).await.into_inner().unwrap();
+                });
+            }
+        }
+    });
+
+    for _ in 0..CONNECTION_COUNT {
+        let mut client = TcpStream::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT))).unwrap();
+
+        let mut response = [0; 1];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(response[0], 42);
+
+        accepted_rx.recv().unwrap();
+    }
+
+    // Stopping the runtime cancels the accepts that are still pending on every worker.
+    drop(accept_loops);
+    folo.stop();
+    folo.wait();
+}
@@ -210,6 +210,77 @@ fn runtime_stops_with_external_dependency() {
     folo.wait();
 }
 
+#[test]
+fn runtime_stops_after_deferred_cleanup() {
+    // Cleanup deferred via `defer_async()` gets a chance to complete before the runtime stops,
+    // even if it needs to wait for timers (and thereby the rest of the runtime) to make progress.
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let (started_tx, started_rx) = oneshot::channel();
+    let (cleaned_up_tx, cleaned_up_rx) = oneshot::channel();
+
+    folo.spawn_on_any(move || async move {
+        folo::rt::defer_async(async move {
+            folo::rt::sleep(Duration::from_millis(50)).await;
+            _ = cleaned_up_tx.send(());
+        });
+
+        _ = started_tx.send(());
+    });
+
+    started_rx.recv().unwrap();
+
+    folo.stop();
+    folo.wait();
+
+    cleaned_up_rx.try_recv().unwrap();
+}
+
+#[test]
+fn deferred_cleanup_outside_runtime_is_dropped() {
+    let (cleaned_up_tx, cleaned_up_rx) = oneshot::channel::<()>();
+
+    folo::rt::defer_async(async move {
+        _ = cleaned_up_tx.send(());
+    });
+
+    // The future was dropped without being polled, so the sender is gone.
+    assert!(cleaned_up_rx.recv().is_err());
+}
+
+#[test]
+fn shutdown_hooks_execute_in_reverse_order() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let executed = Arc::new(Mutex::new(Vec::new()));
+
+    for index in 0..3 {
+        let executed = Arc::clone(&executed);
+
+        folo.on_shutdown(move || async move {
+            // The runtime is still fully functional while the hooks execute.
+            folo::rt::sleep(Duration::from_millis(10)).await;
+            executed.lock().unwrap().push(index);
+        });
+    }
+
+    assert!(executed.lock().unwrap().is_empty());
+
+    folo.stop();
+    folo.wait();
+
+    assert_eq!(*executed.lock().unwrap(), [2, 1, 0]);
+
+    // Once stopped, new hooks are ignored.
+    folo.on_shutdown(|| async { unreachable!() });
+}
+
 /// A future that progresses or completes (and wakes up the last poller) when manually commanded.
 struct ManualFuture {
     state: Mutex<ManualFutureState>,
@@ -0,0 +1,58 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{socket_pair, TcpConnection},
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use futures::future::join;
+use std::{process, time::Duration};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connection_is_handed_off_to_expected_process() {
+    const PIPE_NAME: &str = r"\\.\pipe\folo-test-handoff-expected";
+
+    let (local, mut peer) = socket_pair().unwrap();
+
+    let receive = TcpConnection::receive_via_pipe(PIPE_NAME, process::id());
+
+    let hand_off = async {
+        // The pipe must exist before we can connect to it.
+        sleep(Duration::from_millis(100)).await;
+        local.hand_off_via_pipe(PIPE_NAME, process::id()).await
+    };
+
+    let (received, handed_off) = join(receive, hand_off).await;
+    handed_off.unwrap();
+    let mut received = received.unwrap();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    received.send(buffer).await.into_inner().unwrap();
+
+    let buffer = peer.receive(PinnedBuffer::from_pool()).await.unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connection_is_not_handed_off_to_unexpected_process() {
+    const PIPE_NAME: &str = r"\\.\pipe\folo-test-handoff-unexpected";
+
+    let (local, _peer) = socket_pair().unwrap();
+
+    let receive = TcpConnection::receive_via_pipe(PIPE_NAME, process::id());
+
+    let hand_off = async {
+        sleep(Duration::from_millis(100)).await;
+        local.hand_off_via_pipe(PIPE_NAME, process::id() + 1).await
+    };
+
+    let (received, handed_off) = join(receive, hand_off).await;
+
+    let io::Error::StdIo(e) = handed_off.unwrap_err() else {
+        panic!("expected a permission error");
+    };
+    assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+
+    // The receiver sees the sender disconnect without handing anything off.
+    assert!(received.is_err());
+}
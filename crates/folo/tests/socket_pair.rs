@@ -0,0 +1,53 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::socket_pair,
+};
+use folo_testing::init_test_worker;
+
+fn to_buffer(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn pair_is_connected_in_both_directions() {
+    let (mut first, mut second) = socket_pair().unwrap();
+
+    assert_eq!(first.local_addr().unwrap(), second.peer_addr().unwrap());
+    assert_eq!(first.peer_addr().unwrap(), second.local_addr().unwrap());
+    assert!(first.nodelay().unwrap());
+    assert!(second.nodelay().unwrap());
+
+    first.send(to_buffer(b"ping")).await.into_inner().unwrap();
+    let received = second
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(received.as_slice(), b"ping");
+
+    second.send(to_buffer(b"pong")).await.into_inner().unwrap();
+    let received = first
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(received.as_slice(), b"pong");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn dropping_one_side_closes_the_other() {
+    let (first, mut second) = socket_pair().unwrap();
+
+    drop(first);
+
+    let received = second
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+    assert_eq!(received.len(), 0);
+}
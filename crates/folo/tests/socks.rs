@@ -0,0 +1,91 @@
+use folo::{
+    io::{BufferSize, OperationResultExt, PinnedBuffer},
+    net::{
+        socks::{self, Authentication},
+        TcpConnection, TcpListener,
+    },
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PROXY_PORT: u16 = 41_299;
+
+async fn receive_exactly(connection: &mut TcpConnection, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+
+    while received.len() < len {
+        let buffer = connection
+            .receive(PinnedBuffer::from_pool_with_size(BufferSize::Small))
+            .await
+            .into_inner()
+            .unwrap();
+
+        assert_ne!(buffer.len(), 0);
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    assert_eq!(received.len(), len);
+    received
+}
+
+async fn send(connection: &mut TcpConnection, data: &[u8]) {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    connection.send(buffer).await.into_inner().unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connects_via_proxy_with_credentials() {
+    let listener = TcpListener::bind(PROXY_PORT.try_into().unwrap()).unwrap();
+
+    // A minimal SOCKS5 proxy that accepts one client and pretends to connect it to the target,
+    // which immediately greets the client.
+    let proxy = spawn(async move {
+        let mut connection = listener.accept().await.unwrap();
+
+        assert_eq!(receive_exactly(&mut connection, 4).await, [5, 2, 0, 2]);
+        send(&mut connection, &[5, 2]).await;
+
+        assert_eq!(
+            receive_exactly(&mut connection, 9).await,
+            [1, 4, b'u', b's', b'e', b'r', 2, b'p', b'w']
+        );
+        send(&mut connection, &[1, 0]).await;
+
+        let request = receive_exactly(&mut connection, 18).await;
+        assert_eq!(&request[..5], [5, 1, 0, 3, 11]);
+        assert_eq!(&request[5..16], b"example.com");
+        assert_eq!(&request[16..], 80u16.to_be_bytes());
+
+        send(
+            &mut connection,
+            &[5, 0, 0, 1, 127, 0, 0, 1, 0, 80, b'h', b'i'],
+        )
+        .await;
+
+        assert_eq!(receive_exactly(&mut connection, 3).await, b"hey");
+    });
+
+    let mut connection = socks::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, PROXY_PORT)),
+        ("example.com", 80),
+        Authentication::UsernamePassword {
+            username: "user".to_string(),
+            password: "pw".to_string(),
+        },
+        Default::default(),
+    )
+    .await
+    .unwrap();
+
+    // The greeting of the target arrived together with the reply of the proxy.
+    let initial_data = connection.take_initial_data().unwrap();
+    assert_eq!(initial_data.as_slice(), b"hi");
+
+    send(&mut connection, b"hey").await;
+
+    proxy.await;
+}
@@ -0,0 +1,62 @@
+use folo::{
+    io,
+    rt::{sleep, yield_now},
+    stream::{FoloStreamExt, StreamExt},
+};
+use folo_testing::init_test_worker;
+use futures::stream;
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn buffer_unordered_local_limits_concurrency() {
+    let running = Rc::new(Cell::new(0));
+    let max_running = Rc::new(Cell::new(0));
+
+    let mut results = stream::iter(0..10)
+        .map(|i| {
+            let running = Rc::clone(&running);
+            let max_running = Rc::clone(&max_running);
+
+            async move {
+                running.set(running.get() + 1);
+                max_running.set(max_running.get().max(running.get()));
+
+                yield_now().await;
+
+                running.set(running.get() - 1);
+                i * 2
+            }
+        })
+        .buffer_unordered_local(3)
+        .collect::<Vec<_>>()
+        .await;
+
+    results.sort_unstable();
+
+    assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+    assert!(max_running.get() <= 3);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn timeout_per_item_reports_slow_items() {
+    let items = stream::iter([Duration::ZERO, Duration::from_millis(500)])
+        .then(|delay| async move {
+            sleep(delay).await;
+            delay
+        })
+        .timeout_per_item(Duration::from_millis(300))
+        .chunks(2);
+
+    let mut items = Box::pin(items);
+    let first = items.next().await.unwrap();
+
+    assert!(matches!(first[0], Ok(Duration::ZERO)));
+    assert!(matches!(first[1], Err(io::Error::TimedOut)));
+
+    // The stream remains usable after a timeout and the slow item arrives before the next one.
+    let rest = items.next().await.unwrap();
+
+    assert_eq!(rest.len(), 1);
+    assert!(matches!(rest[0], Ok(delay) if delay == Duration::from_millis(500)));
+    assert!(items.next().await.is_none());
+}
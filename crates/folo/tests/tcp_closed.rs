@@ -0,0 +1,54 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpServerBuilder},
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use futures::FutureExt;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    pin::pin,
+    time::Duration,
+};
+
+const PORT: u16 = 41_310;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn closed_completes_only_after_peer_closes() {
+    let mut server = TcpServerBuilder::new()
+        .port(PORT.try_into().unwrap())
+        .on_accept(send_then_close)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)))
+        .await
+        .unwrap();
+
+    let mut closed = pin!(connection.closed());
+
+    // Data arriving does not mean the connection is closed and the data is still ours to read.
+    let buffer = connection.receive(PinnedBuffer::from_pool()).await.unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+    assert!((&mut closed).now_or_never().is_none());
+
+    closed.await;
+
+    let buffer = connection.receive(buffer).await.unwrap();
+    assert_eq!(buffer.len(), 0);
+
+    server.stop();
+}
+
+async fn send_then_close(mut connection: TcpConnection) -> io::Result<()> {
+    sleep(Duration::from_millis(100)).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    connection.send(buffer).await.into_inner()?;
+
+    sleep(Duration::from_millis(300)).await;
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use folo::{
+    io,
+    net::{ConnectOptions, KeepaliveSettings, TcpConnection, TcpListener},
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+const PORT: u16 = 41_268;
+const KEEPALIVE_PORT: u16 = 41_286;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_binds_to_requested_local_addr() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let local_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+
+    let (connection, accepted) = futures::future::join(
+        TcpConnection::connect_with(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)),
+            ConnectOptions::new().local_addr(local_addr),
+        ),
+        listener.accept(),
+    )
+    .await;
+
+    let connection = connection.unwrap();
+    let accepted = accepted.unwrap();
+
+    let bound_addr = connection.local_addr().unwrap();
+    assert_eq!(bound_addr.ip(), local_addr.ip());
+    assert_eq!(accepted.peer_addr().unwrap(), bound_addr);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_rejects_local_addr_of_other_family() {
+    let result = TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)),
+        ConnectOptions::new().local_addr(SocketAddr::from((Ipv6Addr::LOCALHOST, 0))),
+    )
+    .await;
+
+    assert!(matches!(result, Err(io::Error::InvalidOptions(_))));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_applies_keepalive_settings() {
+    let listener = TcpListener::bind(KEEPALIVE_PORT.try_into().unwrap()).unwrap();
+
+    let settings = KeepaliveSettings {
+        time: Duration::from_secs(30),
+        interval: Duration::from_secs(5),
+        probe_count: Some(4),
+    };
+
+    let (connection, accepted) = futures::future::join(
+        TcpConnection::connect_with(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, KEEPALIVE_PORT)),
+            ConnectOptions::new().keepalive_settings(settings),
+        ),
+        listener.accept(),
+    )
+    .await;
+
+    let connection = connection.unwrap();
+    let accepted = accepted.unwrap();
+
+    assert!(connection.keepalive().unwrap());
+    assert_eq!(connection.keepalive_settings().unwrap(), settings);
+
+    let changed = KeepaliveSettings {
+        time: Duration::from_secs(60),
+        interval: Duration::from_secs(10),
+        probe_count: Some(3),
+    };
+
+    accepted.set_keepalive_settings(changed).unwrap();
+    assert_eq!(accepted.keepalive_settings().unwrap(), changed);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_times_out_at_deadline() {
+    let timeout = Duration::from_millis(200);
+    let started = Instant::now();
+
+    // Nothing answers on this documentation-only address, so the attempt can only time out
+    // (or fail right away if the machine has no route to it at all).
+    let result = TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 80)),
+        ConnectOptions::new().timeout(timeout),
+    )
+    .await;
+
+    let elapsed = started.elapsed();
+
+    assert!(result.is_err());
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+
+    if matches!(result, Err(io::Error::TimedOut)) {
+        assert!(elapsed >= timeout, "{elapsed:?}");
+    }
+}
@@ -0,0 +1,161 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener, TcpProfile},
+    rt::spawn,
+    stream::StreamExt,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const PORT: u16 = 41_267;
+const PROFILE_PORT: u16 = 41_283;
+const PROXY_PORT: u16 = 41_287;
+const SHUTDOWN_PORT: u16 = 41_289;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn incoming_yields_accepted_connections() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+    assert_eq!(listener.local_addr().port(), PORT);
+
+    let clients = spawn(async {
+        for i in 0..3u8 {
+            let mut connection =
+                TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)))
+                    .await
+                    .unwrap();
+
+            let mut buffer = PinnedBuffer::from_pool();
+            buffer.as_mut_slice_with_len(1)[0] = i;
+            connection.send(buffer).await.into_inner().unwrap();
+        }
+    });
+
+    let mut received = listener
+        .incoming()
+        .take(3)
+        .then(|connection| async move {
+            let mut connection = connection.unwrap();
+            let buffer = connection
+                .receive(PinnedBuffer::from_pool())
+                .await
+                .into_inner()
+                .unwrap();
+
+            buffer.as_slice()[0]
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+    clients.await;
+
+    received.sort_unstable();
+    assert_eq!(received, vec![0, 1, 2]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn profile_applied_to_accepted_connections() {
+    let mut listener = TcpListener::bind(PROFILE_PORT.try_into().unwrap()).unwrap();
+    listener.set_profile("low-latency".parse::<TcpProfile>().unwrap());
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        PROFILE_PORT,
+    ))));
+
+    let server = listener.accept().await.unwrap();
+    let _client = client.await.unwrap();
+
+    assert!(server.nodelay().unwrap());
+    assert!(server.keepalive().unwrap());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn proxy_protocol_header_exposed_on_accepted_connections() {
+    let mut listener = TcpListener::bind(PROXY_PORT.try_into().unwrap()).unwrap();
+    listener.set_proxy_protocol(true);
+
+    async fn connect_and_send(data: &'static [u8]) -> TcpConnection {
+        let mut connection =
+            TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PROXY_PORT)))
+                .await
+                .unwrap();
+
+        let mut buffer = PinnedBuffer::from_pool();
+        buffer
+            .as_mut_slice_with_len(data.len())
+            .copy_from_slice(data);
+        connection.send(buffer).await.into_inner().unwrap();
+
+        connection
+    }
+
+    // A client that connects directly, without a header, is turned away.
+    let client = spawn(connect_and_send(b"GET / HTTP/1.1\r\n\r\n"));
+    assert!(listener.accept().await.is_err());
+    let _client = client.await;
+
+    let client = spawn(connect_and_send(
+        b"PROXY TCP4 203.0.113.7 192.0.2.1 40000 443\r\nhello",
+    ));
+    let mut server = listener.accept().await.unwrap();
+    let _client = client.await;
+
+    let header = server.proxy_header().unwrap();
+    assert_eq!(header.version, 1);
+    assert_eq!(header.source, Some("203.0.113.7:40000".parse().unwrap()));
+    assert_eq!(header.destination, Some("192.0.2.1:443".parse().unwrap()));
+
+    // The data after the header may or may not have arrived together with it.
+    let mut payload = server
+        .take_initial_data()
+        .map(|buffer| buffer.as_slice().to_vec())
+        .unwrap_or_default();
+
+    while payload.len() < 5 {
+        let buffer = server
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+        payload.extend_from_slice(buffer.as_slice());
+    }
+
+    assert_eq!(payload, b"hello");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn shutdown_stops_accepting_and_drains_connections() {
+    let listener = TcpListener::bind(SHUTDOWN_PORT.try_into().unwrap()).unwrap();
+    let mut incoming = listener.incoming();
+
+    let client = spawn(TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        SHUTDOWN_PORT,
+    ))));
+    let server = incoming.next().await.unwrap().unwrap();
+    let _client = client.await.unwrap();
+
+    assert_eq!(listener.active_connections(), 1);
+
+    // The handler finishes up its work once asked to.
+    let shutdown_token = listener.shutdown_token();
+    let handler = spawn(async move {
+        shutdown_token.cancelled().await;
+        drop(server);
+    });
+
+    let forced = listener.shutdown(Some(Duration::from_secs(10))).await;
+    handler.await;
+
+    assert_eq!(forced, 0);
+    assert_eq!(listener.active_connections(), 0);
+
+    assert!(matches!(
+        listener.accept().await,
+        Err(folo::io::Error::Canceled)
+    ));
+    assert!(incoming.next().await.is_none());
+}
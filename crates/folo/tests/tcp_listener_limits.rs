@@ -0,0 +1,112 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{LimitAction, TcpConnection, TcpListener},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use futures::StreamExt;
+use std::{
+    cell::Cell,
+    net::{Ipv4Addr, SocketAddr},
+    num::{NonZeroU32, NonZeroUsize},
+    time::{Duration, Instant},
+};
+
+const PORT: u16 = 41_272;
+const FILTER_PORT: u16 = 41_296;
+
+fn localhost(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connection_limit_rejects_excess_connections() {
+    let mut listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+    listener.set_max_connections(NonZeroUsize::new(1).unwrap(), LimitAction::Reject);
+
+    let first_client = spawn(TcpConnection::connect(localhost(PORT)));
+    let first = listener.accept().await.unwrap();
+    let _first_client = first_client.await.unwrap();
+
+    // The second connection is accepted by the operating system but reset by the listener.
+    let mut incoming = listener.incoming();
+    let next = spawn(async move { incoming.next().await.unwrap().unwrap() });
+
+    let mut second_client = TcpConnection::connect(localhost(PORT)).await.unwrap();
+
+    let result = second_client
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner();
+
+    assert!(result.map_or(true, |buffer| buffer.len() == 0));
+
+    // Once the first connection is closed, the next one is let through.
+    drop(first);
+
+    let _third_client = TcpConnection::connect(localhost(PORT)).await.unwrap();
+    let _third = next.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn accept_rate_limit_queues_excess_connections() {
+    let port = PORT + 1;
+
+    let mut listener = TcpListener::bind(port.try_into().unwrap()).unwrap();
+    listener.set_max_accept_rate(NonZeroU32::new(5).unwrap(), LimitAction::Queue);
+
+    let start = Instant::now();
+
+    // The first 5 are accepted as a burst, the sixth has to wait for the bucket to refill.
+    let mut clients = Vec::new();
+    let mut connections = Vec::new();
+
+    for _ in 0..6 {
+        clients.push(spawn(TcpConnection::connect(localhost(port))));
+        connections.push(listener.accept().await.unwrap());
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    for client in clients {
+        client.await.unwrap();
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn accept_filter_rejects_connections() {
+    let mut listener = TcpListener::bind(FILTER_PORT.try_into().unwrap()).unwrap();
+
+    // Every client comes from localhost, so we let through every other connection instead.
+    let seen = Cell::new(0);
+    listener.set_accept_filter(move |addr| {
+        assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
+
+        seen.set(seen.get() + 1);
+        seen.get() % 2 == 0
+    });
+
+    let accepted = spawn(async move { listener.accept().await.unwrap() });
+
+    // The first connection is accepted by the operating system but reset by the listener.
+    let mut first_client = TcpConnection::connect(localhost(FILTER_PORT))
+        .await
+        .unwrap();
+
+    let result = first_client
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner();
+
+    assert!(result.map_or(true, |buffer| buffer.len() == 0));
+
+    let second_client = TcpConnection::connect(localhost(FILTER_PORT))
+        .await
+        .unwrap();
+    let accepted = accepted.await;
+
+    assert_eq!(
+        accepted.peer_addr().unwrap(),
+        second_client.local_addr().unwrap()
+    );
+}
@@ -0,0 +1,102 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpServerBuilder},
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use futures::future::join;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const CANCEL_BEFORE_START_PORT: u16 = 41_308;
+const CANCEL_IN_FLIGHT_PORT: u16 = 41_309;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_canceled_before_start_returns_buffer() {
+    let mut server = TcpServerBuilder::new()
+        .port(CANCEL_BEFORE_START_PORT.try_into().unwrap())
+        .on_accept(send_after_delay)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        CANCEL_BEFORE_START_PORT,
+    )))
+    .await
+    .unwrap();
+
+    let buffer = PinnedBuffer::from_pool();
+    let len = buffer.len();
+
+    let (handle, receive) = connection.receive_cancelable(buffer);
+    handle.cancel();
+    assert!(handle.is_canceled());
+
+    let error = receive.await.unwrap_err();
+    assert!(matches!(error.inner, io::Error::Canceled));
+
+    let (_, buffer) = error.into_inner_and_buffer();
+    assert_eq!(buffer.len(), len);
+
+    // The connection is still usable and the buffer can be reused for the next receive.
+    assert!(!connection.is_read_closed());
+
+    let buffer = connection.receive(buffer).await.unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    server.stop();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_canceled_in_flight_returns_buffer() {
+    let mut server = TcpServerBuilder::new()
+        .port(CANCEL_IN_FLIGHT_PORT.try_into().unwrap())
+        .on_accept(send_after_delay)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        CANCEL_IN_FLIGHT_PORT,
+    )))
+    .await
+    .unwrap();
+
+    let buffer = PinnedBuffer::from_pool();
+    let len = buffer.len();
+
+    let (handle, receive) = connection.receive_cancelable(buffer);
+
+    let cancel = async {
+        sleep(Duration::from_millis(50)).await;
+        handle.cancel();
+    };
+
+    let (result, ()) = join(receive, cancel).await;
+
+    let error = result.unwrap_err();
+    assert!(matches!(error.inner, io::Error::Canceled));
+
+    let (_, buffer) = error.into_inner_and_buffer();
+    assert_eq!(buffer.len(), len);
+
+    let buffer = connection.receive(buffer).await.unwrap();
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    server.stop();
+}
+
+async fn send_after_delay(mut connection: TcpConnection) -> io::Result<()> {
+    sleep(Duration::from_millis(500)).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    connection.send(buffer).await.into_inner()?;
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{ConnectOptions, TcpConnection, TcpServerBuilder},
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    os::windows::io::AsRawSocket,
+    time::Duration,
+};
+
+const ACCEPT_PORT: u16 = 41_320;
+const CONNECT_PORT: u16 = 41_321;
+
+// Long enough for the closing side to finish DisconnectEx and return the socket to the pool.
+const RECYCLE_DELAY: Duration = Duration::from_millis(500);
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn server_accepts_into_recycled_socket() {
+    let mut server = TcpServerBuilder::new()
+        .port(ACCEPT_PORT.try_into().unwrap())
+        .reuse_sockets(true)
+        .on_accept(send_socket_until_closed)
+        .build()
+        .await
+        .unwrap();
+
+    let first = accepted_socket().await;
+    sleep(RECYCLE_DELAY).await;
+
+    // The accept for the second connection was already started while the first one was being
+    // handled, when the pool was still empty.
+    let second = accepted_socket().await;
+    sleep(RECYCLE_DELAY).await;
+
+    // The accept for the third connection was started after the first socket was recycled.
+    let third = accepted_socket().await;
+
+    assert_ne!(second, first);
+    assert_eq!(third, first);
+
+    server.stop();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_reuses_recycled_socket() {
+    let mut server = TcpServerBuilder::new()
+        .port(CONNECT_PORT.try_into().unwrap())
+        .on_accept(close_immediately)
+        .build()
+        .await
+        .unwrap();
+
+    let first = connect_until_closed().await;
+    sleep(RECYCLE_DELAY).await;
+
+    let second = connect_until_closed().await;
+
+    assert_eq!(second, first);
+
+    server.stop();
+}
+
+// Tells the client which socket the server accepted the connection into. The client closes the
+// connection first, so the socket is not held up in TIME_WAIT before it can be reused.
+async fn send_socket_until_closed(mut connection: TcpConnection) -> io::Result<()> {
+    let socket = connection.as_raw_socket().to_le_bytes();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(socket.len())
+        .copy_from_slice(&socket);
+    connection.send(buffer).await.into_inner()?;
+
+    // An empty receive means the client has closed the connection.
+    while connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()?
+        .len()
+        != 0
+    {}
+
+    Ok(())
+}
+
+async fn accepted_socket() -> u64 {
+    let mut connection =
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, ACCEPT_PORT)))
+            .await
+            .unwrap();
+
+    let buffer = connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap();
+
+    u64::from_le_bytes(buffer.as_slice().try_into().unwrap())
+}
+
+// The server closes the connection first, so the socket of the client is not held up in TIME_WAIT
+// before it can be reused.
+async fn close_immediately(_connection: TcpConnection) -> io::Result<()> {
+    Ok(())
+}
+
+async fn connect_until_closed() -> u64 {
+    let mut connection = TcpConnection::connect_with(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, CONNECT_PORT)),
+        ConnectOptions::new().reuse_socket(true),
+    )
+    .await
+    .unwrap();
+
+    while connection
+        .receive(PinnedBuffer::from_pool())
+        .await
+        .into_inner()
+        .unwrap()
+        .len()
+        != 0
+    {}
+
+    connection.as_raw_socket()
+}
@@ -0,0 +1,59 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_297;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn stats_count_traffic_in_both_directions() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let (client, accepted) = futures::future::join(
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT))),
+        listener.accept(),
+    )
+    .await;
+
+    let mut client = client.unwrap();
+    let mut accepted = accepted.unwrap();
+
+    assert_eq!(client.stats().bytes_sent, 0);
+    assert_eq!(client.stats().sends, 0);
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(5)
+        .copy_from_slice(&[1, 2, 3, 4, 5]);
+    client.send(buffer).await.into_inner().unwrap();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(&[6, 7, 8]);
+    client.send(buffer).await.into_inner().unwrap();
+
+    let client_stats = client.stats();
+    assert_eq!(client_stats.bytes_sent, 8);
+    assert_eq!(client_stats.sends, 2);
+    assert_eq!(client_stats.bytes_received, 0);
+    assert_eq!(client_stats.receives, 0);
+
+    let mut received = 0;
+
+    while received < 8 {
+        let buffer = accepted
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        assert_ne!(buffer.len(), 0);
+        received += buffer.len();
+    }
+
+    let accepted_stats = accepted.stats();
+    assert_eq!(accepted_stats.bytes_received, 8);
+    assert!(accepted_stats.receives >= 1);
+    assert_eq!(accepted_stats.bytes_sent, 0);
+}
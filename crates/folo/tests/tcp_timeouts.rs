@@ -0,0 +1,112 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpServerBuilder},
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const PORT: u16 = 41_266;
+const SYNC_COMPLETION_PORT: u16 = 41_306;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_with_timeout_returns_buffer_and_keeps_connection() {
+    let mut server = TcpServerBuilder::new()
+        .port(PORT.try_into().unwrap())
+        .on_accept(send_after_delay)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)))
+        .await
+        .unwrap();
+
+    let buffer = PinnedBuffer::from_pool();
+    let len = buffer.len();
+
+    let error = connection
+        .receive_with_timeout(buffer, Duration::from_millis(50))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error.inner, io::Error::TimedOut));
+    assert!(!connection.is_read_closed());
+
+    let (_, buffer) = error.into_inner_and_buffer();
+    assert_eq!(buffer.len(), len);
+
+    // The connection is still usable and the buffer can be reused for the next receive.
+    let buffer = connection
+        .receive_with_timeout(buffer, Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    server.stop();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn deadline_of_synchronously_completed_receive_does_not_affect_next_receive() {
+    let mut server = TcpServerBuilder::new()
+        .port(SYNC_COMPLETION_PORT.try_into().unwrap())
+        .on_accept(send_twice)
+        .build()
+        .await
+        .unwrap();
+
+    let mut connection = TcpConnection::connect(SocketAddr::from((
+        Ipv4Addr::LOCALHOST,
+        SYNC_COMPLETION_PORT,
+    )))
+    .await
+    .unwrap();
+
+    // By the time we receive, the first message is already waiting for us, so the receive
+    // completes synchronously.
+    sleep(Duration::from_millis(100)).await;
+
+    let buffer = connection
+        .receive_with_timeout(PinnedBuffer::from_pool(), Duration::from_millis(50))
+        .await
+        .unwrap();
+
+    assert_eq!(buffer.as_slice(), b"hello");
+
+    // This receive outlives the deadline of the previous one and must not be canceled by it.
+    let buffer = connection
+        .receive_with_timeout(buffer, Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    assert_eq!(buffer.as_slice(), b"world");
+
+    server.stop();
+}
+
+async fn send_twice(mut connection: TcpConnection) -> io::Result<()> {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    let mut buffer = connection.send(buffer).await.into_inner()?;
+
+    sleep(Duration::from_millis(300)).await;
+
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"world");
+    connection.send(buffer).await.into_inner()?;
+
+    Ok(())
+}
+
+async fn send_after_delay(mut connection: TcpConnection) -> io::Result<()> {
+    sleep(Duration::from_millis(500)).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    connection.send(buffer).await.into_inner()?;
+
+    Ok(())
+}
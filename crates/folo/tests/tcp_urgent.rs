@@ -0,0 +1,67 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_292;
+const INLINE_PORT: u16 = 41_293;
+
+async fn connect_pair(port: u16) -> (TcpConnection, TcpConnection) {
+    let listener = TcpListener::bind(port.try_into().unwrap()).unwrap();
+
+    let (connection, accepted) = futures::future::join(
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+        listener.accept(),
+    )
+    .await;
+
+    (connection.unwrap(), accepted.unwrap())
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn urgent_data_arrives_out_of_band() {
+    let (mut client, mut server) = connect_pair(PORT).await;
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(&[1, 2, 3]);
+    client.send_urgent(buffer).await.into_inner().unwrap();
+
+    // Only the last byte is urgent.
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.set_len(1);
+    let urgent = server.receive_urgent(buffer).await.into_inner().unwrap();
+    assert_eq!(urgent.as_slice(), [3]);
+
+    assert_eq!(receive_exactly(&mut server, 2).await, [1, 2]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn urgent_data_arrives_inline_if_configured() {
+    let (mut client, mut server) = connect_pair(INLINE_PORT).await;
+
+    server.set_oob_inline(true).unwrap();
+    assert!(server.oob_inline().unwrap());
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(2).copy_from_slice(&[1, 2]);
+    client.send_urgent(buffer).await.into_inner().unwrap();
+
+    assert_eq!(receive_exactly(&mut server, 2).await, [1, 2]);
+}
+
+async fn receive_exactly(connection: &mut TcpConnection, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+
+    while received.len() < len {
+        let mut buffer = PinnedBuffer::from_pool();
+        buffer.set_len(len - received.len());
+
+        let buffer = connection.receive(buffer).await.into_inner().unwrap();
+        assert_ne!(buffer.len(), 0);
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    received
+}
@@ -0,0 +1,58 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+    rt::spawn_on_any,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_295;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connection_continues_on_other_worker() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let (client, accepted) = futures::future::join(
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT))),
+        listener.accept(),
+    )
+    .await;
+
+    let mut client = client.unwrap();
+    let accepted = accepted.unwrap();
+
+    let handoff = accepted.duplicate_for_thread().unwrap();
+
+    let echo = spawn_on_any(move || async move {
+        let mut connection = TcpConnection::from_thread_handoff(handoff).unwrap();
+
+        let received = connection
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        connection.send(received).await.into_inner().unwrap();
+    });
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(&[1, 2, 3]);
+    client.send(buffer).await.into_inner().unwrap();
+
+    echo.await;
+
+    let mut received = Vec::new();
+
+    while received.len() < 3 {
+        let buffer = client
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        assert_ne!(buffer.len(), 0);
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    assert_eq!(received, [1, 2, 3]);
+}
@@ -0,0 +1,58 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener, Throttled},
+    rt,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    num::NonZeroU64,
+    time::Duration,
+};
+
+const PORT: u16 = 41_298;
+
+const CHUNK_LEN: usize = 10_000;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn send_is_limited_to_write_limit() {
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let (client, accepted) = futures::future::join(
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT))),
+        listener.accept(),
+    )
+    .await;
+
+    let mut client = Throttled::new(client.unwrap());
+    let mut accepted = accepted.unwrap();
+
+    client.set_write_limit(NonZeroU64::new(CHUNK_LEN as u64));
+    assert_eq!(client.write_limit(), NonZeroU64::new(CHUNK_LEN as u64));
+    assert_eq!(client.read_limit(), None);
+
+    let start = rt::now();
+
+    // The first chunk empties the bucket and the second puts it one second into debt, which has to
+    // be repaid before the third chunk can be sent.
+    for _ in 0..3 {
+        let mut buffer = PinnedBuffer::from_pool();
+        buffer.as_mut_slice_with_len(CHUNK_LEN).fill(42);
+        client.send(buffer).await.into_inner().unwrap();
+    }
+
+    assert!(rt::now() - start >= Duration::from_millis(900));
+
+    let mut received = 0;
+
+    while received < CHUNK_LEN * 3 {
+        let buffer = accepted
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        assert_ne!(buffer.len(), 0);
+        received += buffer.len();
+    }
+}
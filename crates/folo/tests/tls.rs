@@ -0,0 +1,145 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{
+        tls::{Certificate, TlsAcceptor, TlsConnectorBuilder},
+        TcpConnection, TcpListener, TlsListener,
+    },
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+const PORT: u16 = 41_279;
+const LISTENER_PORT: u16 = 41_311;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tls_session_exchanges_data_both_ways() {
+    // More than fits into one TLS record, to exercise splitting into records.
+    const REQUEST_LEN: usize = 100 * 1024;
+
+    let certificate = Certificate::create_self_signed("CN=localhost").unwrap();
+    let acceptor = TlsAcceptor::new(&certificate).unwrap();
+
+    let connector = TlsConnectorBuilder::new()
+        .danger_accept_invalid_certificates(true)
+        .build()
+        .unwrap();
+
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let client = spawn(async move {
+        let connection = TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)))
+            .await
+            .unwrap();
+
+        let mut stream = connector.connect("localhost", connection).await.unwrap();
+
+        let mut request = PinnedBuffer::from_boxed_slice(vec![42; REQUEST_LEN].into_boxed_slice());
+        request = stream.send(request).await.into_inner().unwrap();
+        assert_eq!(request.len(), REQUEST_LEN);
+
+        let mut response = Vec::new();
+
+        loop {
+            let buffer = stream
+                .receive(PinnedBuffer::from_pool())
+                .await
+                .into_inner()
+                .unwrap();
+
+            if buffer.len() == 0 {
+                break;
+            }
+
+            response.extend_from_slice(buffer.as_slice());
+        }
+
+        response
+    });
+
+    let connection = listener.accept().await.unwrap();
+    let mut stream = acceptor.accept(connection).await.unwrap();
+
+    let mut received = 0;
+
+    while received < REQUEST_LEN {
+        let buffer = stream
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+        assert_ne!(buffer.len(), 0);
+        assert!(buffer.as_slice().iter().all(|b| *b == 42));
+
+        received += buffer.len();
+    }
+
+    assert_eq!(received, REQUEST_LEN);
+
+    let response = PinnedBuffer::from_boxed_slice(b"hello".to_vec().into_boxed_slice());
+    stream.send(response).await.into_inner().unwrap();
+    stream.shutdown().await.unwrap();
+
+    assert_eq!(client.await, b"hello");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tls_listener_skips_failed_handshakes() {
+    let certificate = Certificate::create_self_signed("CN=localhost").unwrap();
+    let acceptor = TlsAcceptor::new(&certificate).unwrap();
+
+    let connector = TlsConnectorBuilder::new()
+        .danger_accept_invalid_certificates(true)
+        .build()
+        .unwrap();
+
+    let mut listener = TlsListener::new(
+        TcpListener::bind(LISTENER_PORT.try_into().unwrap()).unwrap(),
+        acceptor,
+    );
+
+    // If the handshake does not fail outright, it times out instead, so we do not wait long.
+    listener.set_handshake_timeout(Duration::from_secs(1));
+
+    let client = spawn(async move {
+        // The first client is not speaking TLS, so the listener closes its connection.
+        let mut connection =
+            TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, LISTENER_PORT)))
+                .await
+                .unwrap();
+
+        let garbage = PinnedBuffer::from_boxed_slice(b"not a TLS client hello".to_vec().into());
+        connection.send(garbage).await.into_inner().unwrap();
+
+        // Once closed, no data ever comes back (although the connection may also be reset).
+        if let Ok(buffer) = connection.receive(PinnedBuffer::from_pool()).await {
+            assert_eq!(buffer.len(), 0);
+        }
+
+        let connection =
+            TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, LISTENER_PORT)))
+                .await
+                .unwrap();
+
+        let mut stream = connector.connect("localhost", connection).await.unwrap();
+
+        stream
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap()
+            .as_slice()
+            .to_vec()
+    });
+
+    let mut stream = listener.accept().await.unwrap();
+
+    let greeting = PinnedBuffer::from_boxed_slice(b"hello".to_vec().into_boxed_slice());
+    stream.send(greeting).await.into_inner().unwrap();
+    stream.shutdown().await.unwrap();
+
+    assert_eq!(client.await, b"hello");
+}
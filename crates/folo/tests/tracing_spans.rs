@@ -0,0 +1,82 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{TcpConnection, TcpListener},
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+use tracing::{span, subscriber::set_default, Instrument, Subscriber};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    Layer,
+};
+
+const PORT: u16 = 41_294;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn io_operation_span_is_child_of_submitting_span() {
+    let spans = Arc::new(Mutex::new(Vec::new()));
+
+    let _subscriber = set_default(tracing_subscriber::registry().with(SpanRecorder {
+        spans: Arc::clone(&spans),
+    }));
+
+    let listener = TcpListener::bind(PORT.try_into().unwrap()).unwrap();
+
+    let (client, server) = futures::future::join(
+        TcpConnection::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, PORT))),
+        listener.accept(),
+    )
+    .await;
+
+    let mut client = client.unwrap();
+    let mut server = server.unwrap();
+
+    // The receive is submitted before there is anything to receive, so it completes via the
+    // completion port only once the client sends.
+    let receive = server
+        .receive(PinnedBuffer::from_pool())
+        .instrument(tracing::info_span!("request"));
+
+    let send = async {
+        let mut buffer = PinnedBuffer::from_pool();
+        buffer.as_mut_slice_with_len(1)[0] = 42;
+        client.send(buffer).await.into_inner().unwrap();
+    };
+
+    let (received, ()) = futures::future::join(receive, send).await;
+    assert_eq!(received.into_inner().unwrap().as_slice(), [42]);
+
+    let spans = spans.lock().unwrap();
+    assert!(spans
+        .iter()
+        .any(|(name, parent)| name == "io_operation" && parent.as_deref() == Some("request")));
+}
+
+/// The name of each span created, together with the name of its parent.
+type RecordedSpans = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Records the name of each span created, together with the name of its parent.
+struct SpanRecorder {
+    spans: RecordedSpans,
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx
+            .span(id)
+            .expect("span must exist because it was just created");
+        let parent = span.parent().map(|parent| parent.name().to_string());
+
+        self.spans
+            .lock()
+            .unwrap()
+            .push((span.name().to_string(), parent));
+    }
+}
@@ -0,0 +1,44 @@
+use folo::rt::{IdleStrategy, RuntimeBuilder, RuntimeTuning};
+use std::time::Duration;
+
+#[test]
+fn tuning_adjusted_at_run_time() {
+    let initial = RuntimeTuning::new().cross_thread_poll_interval(Duration::from_millis(5));
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .tuning(initial.clone())
+        .build()
+        .unwrap();
+
+    assert_eq!(folo.tuning(), initial);
+
+    let spinning = RuntimeTuning::new()
+        .idle_strategy(IdleStrategy::Spin)
+        .max_pooled_sockets(0);
+
+    folo.set_tuning(spinning.clone());
+    assert_eq!(folo.tuning(), spinning);
+
+    // The workers keep processing work after applying the new tuning.
+    let (tx, rx) = oneshot::channel();
+
+    folo.spawn_on_any(|| async move {
+        _ = tx.send(42);
+    });
+
+    assert_eq!(rx.recv().unwrap(), 42);
+
+    folo.set_tuning(RuntimeTuning::default());
+
+    let (tx, rx) = oneshot::channel();
+
+    folo.spawn_on_any(|| async move {
+        _ = tx.send(43);
+    });
+
+    assert_eq!(rx.recv().unwrap(), 43);
+
+    folo.stop();
+    folo.wait();
+}
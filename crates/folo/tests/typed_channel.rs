@@ -0,0 +1,141 @@
+#![cfg(feature = "typed-channel")]
+
+use folo::{
+    io,
+    ipc::{TypedChannel, TypedChannelBuilder},
+    rt::spawn,
+};
+use folo_testing::init_test_worker;
+use serde::{Deserialize, Serialize};
+use std::{process, time::Duration};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Message {
+    Ping(u32),
+    Pong(u32),
+    Text(String),
+}
+
+fn unique_name(test: &str) -> String {
+    format!(r"\\.\pipe\folo-test-{test}-{}", process::id())
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn request_and_response() {
+    let name = unique_name("request");
+    let mut listener = TypedChannelBuilder::new(&name)
+        .listen::<Message>()
+        .await
+        .unwrap();
+
+    let server = spawn(async move {
+        let mut channel = listener.accept().await.unwrap();
+
+        while let Some(message) = channel.receive().await.unwrap() {
+            let Message::Ping(n) = message else {
+                panic!("unexpected message {message:?}");
+            };
+
+            channel.send(&Message::Pong(n)).await.unwrap();
+        }
+    });
+
+    let mut client: TypedChannel<Message> =
+        TypedChannelBuilder::new(&name).connect().await.unwrap();
+
+    for n in 0..3 {
+        client.send(&Message::Ping(n)).await.unwrap();
+        assert_eq!(client.receive().await.unwrap(), Some(Message::Pong(n)));
+    }
+
+    drop(client);
+    server.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn large_messages_arrive_whole() {
+    let name = unique_name("large");
+    let mut listener = TypedChannelBuilder::new(&name)
+        .listen::<Message>()
+        .await
+        .unwrap();
+
+    // Larger than both the pipe buffers and the buffers we read into.
+    let text = "folo".repeat(100_000);
+
+    let server = spawn({
+        let text = text.clone();
+
+        async move {
+            let mut channel = listener.accept().await.unwrap();
+            channel.send(&Message::Text(text)).await.unwrap();
+        }
+    });
+
+    let mut client = TypedChannelBuilder::new(&name).connect().await.unwrap();
+    assert_eq!(client.receive().await.unwrap(), Some(Message::Text(text)));
+
+    server.await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn client_reconnects_after_server_disconnects() {
+    let name = unique_name("reconnect");
+    let mut listener = TypedChannelBuilder::new(&name)
+        .listen::<Message>()
+        .await
+        .unwrap();
+
+    let server = spawn(async move {
+        // The first connection is closed right away.
+        drop(listener.accept().await.unwrap());
+
+        let mut channel = listener.accept().await.unwrap();
+        channel.receive().await.unwrap()
+    });
+
+    let mut client = TypedChannelBuilder::new(&name).connect().await.unwrap();
+
+    assert_eq!(client.receive().await.unwrap(), None);
+    assert!(!client.is_connected());
+
+    client.send(&Message::Ping(42)).await.unwrap();
+    assert!(client.is_connected());
+
+    assert_eq!(server.await, Some(Message::Ping(42)));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_without_server_times_out() {
+    let result = TypedChannelBuilder::new(unique_name("missing"))
+        .connect_timeout(Duration::from_millis(100))
+        .connect::<Message>()
+        .await;
+
+    assert!(matches!(result, Err(io::Error::TimedOut)));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn oversized_message_is_rejected() {
+    let name = unique_name("oversized");
+    let mut listener = TypedChannelBuilder::new(&name)
+        .listen::<Message>()
+        .await
+        .unwrap();
+
+    let server = spawn(async move { listener.accept().await.unwrap() });
+
+    let mut client = TypedChannelBuilder::new(&name)
+        .max_message_len(16)
+        .connect()
+        .await
+        .unwrap();
+
+    let _server_channel = server.await;
+
+    assert!(client
+        .send(&Message::Text("this does not fit".to_string()))
+        .await
+        .is_err());
+    assert!(client.send(&Message::Ping(1)).await.is_ok());
+}
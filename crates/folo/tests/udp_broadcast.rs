@@ -0,0 +1,33 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::UdpSocket,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+const PORT: u16 = 41_300;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn broadcast_requires_option() {
+    let mut socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).unwrap();
+    let target = SocketAddr::from((Ipv4Addr::BROADCAST, PORT));
+
+    assert!(!socket.broadcast().unwrap());
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+
+    let buffer = match socket.send_to(buffer, target).await {
+        Err(e) => {
+            let (error, buffer) = e.into_inner_and_buffer();
+            assert!(matches!(error, io::Error::InvalidOptions(_)));
+            buffer
+        }
+        Ok(_) => panic!("broadcast without SO_BROADCAST must fail"),
+    };
+
+    socket.set_broadcast(true).unwrap();
+    assert!(socket.broadcast().unwrap());
+
+    socket.send_to(buffer, target).await.into_inner().unwrap();
+}
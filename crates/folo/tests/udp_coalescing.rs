@@ -0,0 +1,34 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::UdpSocket,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_coalesced_delivers_datagram_with_sender() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    // Receive coalescing is not available on older versions of Windows, in which case we still
+    // expect single datagrams to be delivered.
+    if receiver.set_receive_coalescing(64 * 1024).is_ok() {
+        assert_eq!(receiver.receive_coalescing().unwrap(), 64 * 1024);
+    }
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    sender
+        .send_to(buffer, receiver.local_addr().unwrap())
+        .await
+        .into_inner()
+        .unwrap();
+
+    let received = receiver
+        .receive_coalesced(PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(received.sender(), sender.local_addr().unwrap());
+    assert_eq!(received.datagrams().collect::<Vec<_>>(), [b"hello"]);
+}
@@ -0,0 +1,70 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::UdpSocket,
+    rt::sleep,
+};
+use folo_testing::init_test_worker;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+fn datagram(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn unreachable_peer_does_not_fail_receive() {
+    let mut server = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut client = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    assert!(server.suppresses_connection_resets());
+
+    // Nobody is listening on this port anymore, so the datagram is answered by ICMP "port
+    // unreachable".
+    let gone = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let gone_addr = gone.local_addr().unwrap();
+    drop(gone);
+
+    server
+        .send_to(datagram(b"anyone?"), gone_addr)
+        .await
+        .into_inner()
+        .unwrap();
+
+    sleep(Duration::from_millis(100)).await;
+
+    client
+        .send_to(datagram(b"hello"), server.local_addr().unwrap())
+        .await
+        .into_inner()
+        .unwrap();
+
+    let (buffer, sender) = server
+        .receive_from(PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(buffer.as_slice(), b"hello");
+    assert_eq!(sender, client.local_addr().unwrap());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connecting_reports_resets_unless_set_explicitly() {
+    let mut socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    socket
+        .connect(SocketAddr::from((Ipv4Addr::LOCALHOST, 9)))
+        .unwrap();
+    assert!(!socket.suppresses_connection_resets());
+
+    let mut socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    socket.set_suppress_connection_resets(true).unwrap();
+    socket
+        .connect(SocketAddr::from((Ipv4Addr::LOCALHOST, 9)))
+        .unwrap();
+    assert!(socket.suppresses_connection_resets());
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "udp-stream")]
+
+use folo::{
+    io::{BufferSize, PinnedBuffer},
+    net::UdpSocket,
+};
+use folo_testing::init_test_worker;
+use futures::{stream, SinkExt, StreamExt};
+use std::net::{Ipv4Addr, SocketAddr};
+
+fn datagram(data: &[u8]) -> PinnedBuffer {
+    let mut buffer = PinnedBuffer::from_pool_with_size(BufferSize::Small);
+    buffer
+        .as_mut_slice_with_len(data.len())
+        .copy_from_slice(data);
+    buffer
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn sink_sends_and_stream_receives_datagrams() {
+    let receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let target = receiver.local_addr().unwrap();
+
+    let mut sink = sender.datagrams(BufferSize::Small);
+    sink.send_all(&mut stream::iter(
+        [b"one".as_slice(), b"two", b"three"]
+            .into_iter()
+            .map(|data| Ok((datagram(data), target))),
+    ))
+    .await
+    .unwrap();
+
+    let received = receiver
+        .datagrams(BufferSize::Small)
+        .take(3)
+        .map(|result| {
+            let (buffer, sender) = result.unwrap();
+            (buffer.as_slice().to_vec(), sender)
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+    let sender_addr = sender.local_addr().unwrap();
+
+    assert_eq!(
+        received,
+        [
+            (b"one".to_vec(), sender_addr),
+            (b"two".to_vec(), sender_addr),
+            (b"three".to_vec(), sender_addr),
+        ]
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn echoes_datagrams_via_forward() {
+    let server = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut client = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let server_addr = server.local_addr().unwrap();
+
+    // The server echoes each datagram back to its sender, dropping empty ones.
+    let (sink, stream) = server.datagrams(BufferSize::Small).split();
+    let echo = stream
+        .filter(|result| {
+            let keep = !matches!(result, Ok((buffer, _)) if buffer.len() == 0);
+            async move { keep }
+        })
+        .take(1)
+        .forward(sink);
+
+    let exchange = async {
+        client.send_to(datagram(b""), server_addr).await.unwrap();
+        client
+            .send_to(datagram(b"ping"), server_addr)
+            .await
+            .unwrap();
+
+        let (buffer, sender) = client
+            .receive_from(PinnedBuffer::from_pool())
+            .await
+            .unwrap();
+
+        assert_eq!(buffer.as_slice(), b"ping");
+        assert_eq!(sender, server_addr);
+    };
+
+    let (echoed, ()) = futures::future::join(echo, exchange).await;
+    echoed.unwrap();
+}
@@ -0,0 +1,43 @@
+use folo::net::UdpSocket;
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const GROUP_V4: Ipv4Addr = Ipv4Addr::new(239, 255, 42, 1);
+const GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0x42, 1);
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn ipv4_membership_and_options() {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).unwrap();
+
+    socket
+        .join_multicast_v4(GROUP_V4, Ipv4Addr::UNSPECIFIED)
+        .unwrap();
+    socket
+        .leave_multicast_v4(GROUP_V4, Ipv4Addr::UNSPECIFIED)
+        .unwrap();
+
+    socket.set_multicast_ttl_v4(4).unwrap();
+    assert_eq!(socket.multicast_ttl_v4().unwrap(), 4);
+
+    socket.set_multicast_loop_v4(false).unwrap();
+    assert!(!socket.multicast_loop_v4().unwrap());
+
+    // Only multicast addresses can be joined.
+    assert!(socket
+        .join_multicast_v4(Ipv4Addr::LOCALHOST, Ipv4Addr::UNSPECIFIED)
+        .is_err());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn ipv6_membership_and_options() {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))).unwrap();
+
+    socket.join_multicast_v6(GROUP_V6, 0).unwrap();
+    socket.leave_multicast_v6(GROUP_V6, 0).unwrap();
+
+    socket.set_multicast_hops_v6(4).unwrap();
+    assert_eq!(socket.multicast_hops_v6().unwrap(), 4);
+
+    socket.set_multicast_loop_v6(false).unwrap();
+    assert!(!socket.multicast_loop_v6().unwrap());
+}
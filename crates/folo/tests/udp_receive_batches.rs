@@ -0,0 +1,104 @@
+use folo::{
+    io::{BufferSize, OperationResultExt, PinnedBuffer},
+    net::UdpSocket,
+};
+use folo_testing::init_test_worker;
+use futures::future::join;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receives_datagrams_in_batches() {
+    let receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let target = receiver.local_addr().unwrap();
+
+    let mut batches = receiver.receive_batches(NonZeroUsize::new(4).unwrap(), BufferSize::Small);
+
+    for datagram in [b"one", b"two", b"six"] {
+        let mut buffer = PinnedBuffer::from_pool();
+        buffer
+            .as_mut_slice_with_len(datagram.len())
+            .copy_from_slice(datagram);
+        sender.send_to(buffer, target).await.into_inner().unwrap();
+    }
+
+    let mut received = Vec::new();
+
+    while received.len() < 3 {
+        let batch = batches.next_batch().await;
+        assert!(!batch.is_empty());
+
+        for result in batch {
+            received.push(result.into_inner().unwrap().as_slice().to_vec());
+        }
+    }
+
+    assert_eq!(received, [b"one", b"two", b"six"]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn batch_does_not_wait_for_all_receives() {
+    let receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let target = receiver.local_addr().unwrap();
+
+    let mut batches = receiver.receive_batches(NonZeroUsize::new(4).unwrap(), BufferSize::Small);
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(4).copy_from_slice(b"only");
+    sender.send_to(buffer, target).await.into_inner().unwrap();
+
+    // Only one of the four receives can complete, yet we get the datagram right away.
+    let batch = batches.next_batch().await;
+    assert_eq!(batch.len(), 1);
+
+    let datagram = batch.into_iter().next().unwrap().into_inner().unwrap();
+    assert_eq!(datagram.as_slice(), b"only");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_many_fills_caller_buffers_in_order() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let target = receiver.local_addr().unwrap();
+
+    let buffers = vec![
+        PinnedBuffer::from_pool_with_size(BufferSize::Small),
+        PinnedBuffer::from_pool_with_size(BufferSize::Small),
+        PinnedBuffer::from_pool_with_size(BufferSize::Small),
+    ];
+
+    let receives = receiver.receive_many(buffers);
+
+    let sends = async {
+        for datagram in [b"one", b"two", b"six"] {
+            let mut buffer = PinnedBuffer::from_pool();
+            buffer
+                .as_mut_slice_with_len(datagram.len())
+                .copy_from_slice(datagram);
+            sender.send_to(buffer, target).await.into_inner().unwrap();
+        }
+    };
+
+    let (results, ()) = join(receives, sends).await;
+
+    let received = results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().as_slice().to_vec())
+        .collect::<Vec<_>>();
+
+    assert_eq!(received, [b"one", b"two", b"six"]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_many_with_no_buffers_returns_immediately() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    assert!(receiver.receive_many(Vec::new()).await.is_empty());
+}
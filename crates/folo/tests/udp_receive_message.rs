@@ -0,0 +1,102 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{EcnCodepoint, UdpSocket},
+};
+use folo_testing::init_test_worker;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_message_reports_destination_and_hop_limit() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    receiver.set_receive_packet_info(true).unwrap();
+    receiver.set_receive_hop_limit(true).unwrap();
+    assert!(receiver.receive_packet_info().unwrap());
+    assert!(receiver.receive_hop_limit().unwrap());
+
+    // ECN reporting is not available on older versions of Windows.
+    let ecn_enabled = receiver.set_receive_ecn(true).is_ok();
+
+    let port = receiver.local_addr().unwrap().port();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    sender
+        .send_to(buffer, SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .await
+        .into_inner()
+        .unwrap();
+
+    let message = receiver
+        .receive_message(PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(message.buffer().as_slice(), b"hello");
+    assert_eq!(message.sender(), sender.local_addr().unwrap());
+    assert_eq!(
+        message.destination(),
+        Some(IpAddr::from(Ipv4Addr::LOCALHOST))
+    );
+    assert!(message.interface_index().is_some());
+    assert!(message.hop_limit().is_some_and(|hops| hops > 0));
+
+    if ecn_enabled {
+        assert_eq!(message.ecn(), Some(EcnCodepoint::NotCapable));
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_message_reports_ipv6_destination() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv6Addr::LOCALHOST, 0))).unwrap();
+
+    receiver.set_receive_packet_info(true).unwrap();
+
+    let port = receiver.local_addr().unwrap().port();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(3).copy_from_slice(b"abc");
+    sender
+        .send_to(buffer, SocketAddr::from((Ipv6Addr::LOCALHOST, port)))
+        .await
+        .into_inner()
+        .unwrap();
+
+    let message = receiver
+        .receive_message(PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(message.buffer().as_slice(), b"abc");
+    assert_eq!(
+        message.destination(),
+        Some(IpAddr::from(Ipv6Addr::LOCALHOST))
+    );
+    assert_eq!(message.hop_limit(), None);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn receive_message_without_options_reports_no_control_information() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(1).copy_from_slice(b"x");
+    sender
+        .send_to(buffer, receiver.local_addr().unwrap())
+        .await
+        .into_inner()
+        .unwrap();
+
+    let message = receiver
+        .receive_message(PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(message.buffer().as_slice(), b"x");
+    assert_eq!(message.destination(), None);
+    assert_eq!(message.hop_limit(), None);
+    assert_eq!(message.ecn(), None);
+}
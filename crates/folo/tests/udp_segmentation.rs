@@ -0,0 +1,43 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::UdpSocket,
+};
+use folo_testing::init_test_worker;
+use std::net::{Ipv4Addr, SocketAddr};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn send_segmentation_splits_buffer_into_datagrams() {
+    let mut receiver = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+    let mut sender = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+
+    // Send segmentation is not available on older versions of Windows.
+    if sender.set_send_segment_size(100).is_err() {
+        return;
+    }
+
+    assert_eq!(sender.send_segment_size().unwrap(), 100);
+
+    let mut buffer = PinnedBuffer::from_pool();
+    let data = buffer.as_mut_slice_with_len(250);
+
+    for (index, byte) in data.iter_mut().enumerate() {
+        *byte = (index / 100) as u8;
+    }
+
+    sender
+        .send_to(buffer, receiver.local_addr().unwrap())
+        .await
+        .into_inner()
+        .unwrap();
+
+    for (index, expected_len) in [100, 100, 50].into_iter().enumerate() {
+        let (buffer, sender_addr) = receiver
+            .receive_from(PinnedBuffer::from_pool())
+            .await
+            .unwrap();
+
+        assert_eq!(sender_addr, sender.local_addr().unwrap());
+        assert_eq!(buffer.len(), expected_len);
+        assert!(buffer.as_slice().iter().all(|byte| *byte == index as u8));
+    }
+}
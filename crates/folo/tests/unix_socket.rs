@@ -0,0 +1,76 @@
+use folo::{
+    io::{OperationResultExt, PinnedBuffer},
+    net::{UnixListener, UnixStream},
+};
+use folo_testing::init_test_worker;
+use std::{net::Shutdown, path::PathBuf};
+
+fn socket_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("folo-{}-{name}.sock", std::process::id()));
+
+    // The socket file of an earlier run may have been left behind.
+    _ = std::fs::remove_file(&path);
+    path
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn exchanges_data_over_socket_file() {
+    let path = socket_path("exchange");
+    let listener = UnixListener::bind(&path).unwrap();
+    assert_eq!(listener.path(), path);
+
+    let (accepted, client) =
+        futures::future::join(listener.accept(), async { UnixStream::connect(&path) }).await;
+
+    let mut accepted = accepted.unwrap();
+    let mut client = client.unwrap();
+
+    let mut buffer = PinnedBuffer::from_pool();
+    buffer.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+    client.send(buffer).await.into_inner().unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+    assert!(client.is_write_closed());
+
+    let mut received = Vec::new();
+
+    loop {
+        let buffer = accepted
+            .receive(PinnedBuffer::from_pool())
+            .await
+            .into_inner()
+            .unwrap();
+
+        if buffer.len() == 0 {
+            break;
+        }
+
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    assert_eq!(received, b"hello");
+    assert!(accepted.is_read_closed());
+
+    drop(listener);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn rejects_overly_long_path() {
+    let path = std::env::temp_dir().join("x".repeat(200));
+
+    assert!(matches!(
+        UnixListener::bind(&path),
+        Err(folo::io::Error::InvalidOptions(_))
+    ));
+    assert!(matches!(
+        UnixStream::connect(&path),
+        Err(folo::io::Error::InvalidOptions(_))
+    ));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn connect_without_listener_fails() {
+    let path = socket_path("nobody");
+
+    assert!(UnixStream::connect(&path).is_err());
+}
@@ -0,0 +1,125 @@
+use folo::{
+    io::{self, OperationResultExt, PinnedBuffer},
+    net::{
+        codec::Encoder,
+        socket_pair,
+        ws::{CloseFrame, Frame, Message, OpCode, Role, WebSocket, WebSocketCodec},
+    },
+};
+use folo_testing::init_test_worker;
+
+fn web_socket_pair() -> (WebSocket, WebSocket) {
+    let (client, server) = socket_pair().unwrap();
+
+    (
+        WebSocket::new(client, WebSocketCodec::new(Role::Client)),
+        WebSocket::new(server, WebSocketCodec::new(Role::Server)),
+    )
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn exchanges_messages() {
+    let (mut client, mut server) = web_socket_pair();
+
+    client
+        .send(Message::Text("hello".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(
+        server.receive().await.unwrap(),
+        Some(Message::Text("hello".to_string()))
+    );
+
+    // Large enough to need the longest length encoding.
+    let data = vec![42; 100_000];
+    server.send(Message::Binary(data.clone())).await.unwrap();
+    assert_eq!(client.receive().await.unwrap(), Some(Message::Binary(data)));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn answers_pings() {
+    let (mut client, mut server) = web_socket_pair();
+
+    client.send(Message::Ping(b"1".to_vec())).await.unwrap();
+    assert_eq!(
+        server.receive().await.unwrap(),
+        Some(Message::Ping(b"1".to_vec()))
+    );
+    assert_eq!(
+        client.receive().await.unwrap(),
+        Some(Message::Pong(b"1".to_vec()))
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn reassembles_fragmented_messages() {
+    let (mut client, server) = socket_pair().unwrap();
+    let mut server = WebSocket::new(server, WebSocketCodec::new(Role::Server));
+
+    // A text message in three fragments, with a ping in between.
+    let mut codec = WebSocketCodec::new(Role::Client);
+    let mut encoded = Vec::new();
+
+    for frame in [
+        Frame::new(false, OpCode::Text, b"hel".to_vec()),
+        Frame::new(true, OpCode::Ping, Vec::new()),
+        Frame::new(false, OpCode::Continuation, b"lo ".to_vec()),
+        Frame::new(true, OpCode::Continuation, b"world".to_vec()),
+    ] {
+        codec.encode(frame, &mut encoded).unwrap();
+    }
+
+    client
+        .send(PinnedBuffer::from_boxed_slice(encoded.into_boxed_slice()))
+        .await
+        .into_inner()
+        .unwrap();
+
+    assert_eq!(
+        server.receive().await.unwrap(),
+        Some(Message::Ping(Vec::new()))
+    );
+    assert_eq!(
+        server.receive().await.unwrap(),
+        Some(Message::Text("hello world".to_string()))
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn completes_closing_handshake() {
+    let (mut client, mut server) = web_socket_pair();
+
+    client
+        .send(Message::Close(Some(CloseFrame::new(1001, "going away"))))
+        .await
+        .unwrap();
+
+    // The server answers the close automatically.
+    assert_eq!(
+        server.receive().await.unwrap(),
+        Some(Message::Close(Some(CloseFrame::new(1001, "going away"))))
+    );
+    assert_eq!(server.receive().await.unwrap(), None);
+    assert!(server.is_close_sent());
+
+    client.close(None).await.unwrap();
+    assert!(client.is_close_received());
+    assert_eq!(client.receive().await.unwrap(), None);
+
+    assert!(matches!(
+        client.send(Message::Text("late".to_string())).await,
+        Err(io::Error::StdIo(_))
+    ));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn rejects_unmasked_client_frames() {
+    let (client, server) = socket_pair().unwrap();
+
+    // Both sides think they are the server, so neither masks its frames.
+    let mut client = WebSocket::new(client, WebSocketCodec::new(Role::Server));
+    let mut server = WebSocket::new(server, WebSocketCodec::new(Role::Server));
+
+    client.send(Message::Binary(vec![1])).await.unwrap();
+    assert!(server.receive().await.is_err());
+}
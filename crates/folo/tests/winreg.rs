@@ -0,0 +1,36 @@
+use folo::winreg::{self, RegistryKey, RegistryRoot};
+use folo_testing::init_test_worker;
+
+const CURRENT_VERSION: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn reads_existing_values() {
+    let key = RegistryKey::open(RegistryRoot::LocalMachine, CURRENT_VERSION)
+        .await
+        .unwrap();
+
+    let product_name = key.read_value("ProductName").await.unwrap().unwrap();
+    assert!(product_name.as_str().unwrap().contains("Windows"));
+
+    assert!(key
+        .read_value("folo_value_that_does_not_exist")
+        .await
+        .unwrap()
+        .is_none());
+
+    let via_function =
+        winreg::read_value(RegistryRoot::LocalMachine, CURRENT_VERSION, "ProductName")
+            .await
+            .unwrap();
+
+    assert_eq!(via_function, Some(product_name));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn opening_missing_key_fails() {
+    assert!(
+        RegistryKey::open(RegistryRoot::CurrentUser, r"SOFTWARE\folo\does\not\exist")
+            .await
+            .is_err()
+    );
+}
@@ -23,6 +23,11 @@ struct EntrypointOptions {
     /// not used for running tasks, so this function is not called on the entrypoint thread.
     worker_init_fn: Option<syn::Ident>,
 
+    /// Function that returns the `RuntimeConfig` to use, e.g. loaded from environment variables via
+    /// `RuntimeConfig::from_env()`. Settings given directly to the macro (e.g. `max_processors`)
+    /// take precedence over the returned configuration.
+    config_fn: Option<syn::Ident>,
+
     /// Limits the number of processors the runtime will use. Just for debugging purposes - not
     /// flexible enough to be used as a resource management tool.
     max_processors: Option<usize>,
@@ -96,6 +101,13 @@ fn core(
         None => quote! {},
     };
 
+    let config = match options.config_fn {
+        Some(ident) => quote! {
+            .config(#ident())
+        },
+        None => quote! {},
+    };
+
     let worker_init = match options.worker_init_fn {
         Some(ident) => quote! {
             .worker_init(move || { #ident(); })
@@ -139,6 +151,7 @@ fn core(
                 let __entrypoint_metrics_collector = ::folo::__private::MetricsCollector::new();
 
                 let __entrypoint_runtime = ::folo::rt::RuntimeBuilder::new()
+                    #config
                     #worker_init
                     #metrics_init
                     #max_processors
@@ -165,6 +178,7 @@ fn core(
                 let __entrypoint_metrics_collector = ::folo::__private::MetricsCollector::new();
 
                 let __entrypoint_runtime = ::folo::rt::RuntimeBuilder::new()
+                    #config
                     #worker_init
                     #metrics_init
                     #max_processors
@@ -411,6 +425,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn main_with_config_fn() {
+        let attr = parse_quote! {
+            config_fn = load_config
+        };
+
+        let input = parse_quote! {
+            async fn main() {
+                println!("Hello, world!");
+                yield_now().await;
+            }
+        };
+
+        let expected = quote! {
+            fn main() {
+                let __entrypoint_metrics_collector = ::folo::__private::MetricsCollector::new();
+
+                let __entrypoint_runtime = ::folo::rt::RuntimeBuilder::new()
+                    .config(load_config())
+                    .build()
+                    .unwrap();
+                let __entrypoint_runtime_clone = __entrypoint_runtime.clone();
+
+                __entrypoint_runtime.spawn_on_any(|| async move {
+                    __inner_main().await;
+
+                    __entrypoint_runtime_clone.stop();
+                });
+
+                __entrypoint_runtime.wait();
+            }
+
+            async fn __inner_main() {
+                println!("Hello, world!");
+                yield_now().await;
+            }
+        };
+
+        assert_eq!(
+            entrypoint(attr, input, EntrypointType::Main).to_string(),
+            expected.to_string()
+        );
+    }
+
     #[test]
     fn main_with_max_processors() {
         let attr = parse_quote! {